@@ -0,0 +1,31 @@
+use serde::{Deserialize, Serialize};
+use ts_rs::TS;
+use utoipa::ToSchema;
+
+/// An uploaded file, the URLs are signed and expire after a while so they
+/// should be fetched again rather than stored
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema, TS)]
+#[ts(export)]
+pub struct Asset {
+    pub id: i32,
+    pub filename: String,
+    pub content_type: String,
+    pub size_bytes: i64,
+    /// Set for images
+    pub width: Option<i32>,
+    pub height: Option<i32>,
+    pub url: String,
+    /// Resized copies of an image, they appear shortly after the upload
+    pub variants: Vec<AssetVariant>,
+    pub created_at: chrono::NaiveDateTime,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema, TS)]
+#[ts(export)]
+pub struct AssetVariant {
+    /// e.g. `w480`, to request it with `?variant=w480`
+    pub name: String,
+    pub width: i32,
+    pub height: i32,
+    pub url: String,
+}
@@ -0,0 +1,205 @@
+use serde::{Deserialize, Serialize};
+use ts_rs::TS;
+use utoipa::ToSchema;
+
+// The model that maps to the database table
+#[derive(Debug, Serialize, Clone, ToSchema, TS)]
+#[ts(export)]
+pub struct Comment {
+    pub id: i32,
+    pub author_name: String,
+    pub content: String,
+    pub parent_id: Option<i32>,
+    pub created_at: chrono::NaiveDateTime,
+    #[ts(type = "number")]
+    pub votes: i64,
+    #[ts(type = "number")]
+    pub depth: i64,
+}
+
+// The model that will be returned to the client
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, ToSchema, TS)]
+#[ts(export)]
+pub struct CommentTree {
+    pub id: i32,
+    pub author_name: String,
+    pub content: String,
+    pub parent_id: Option<i32>,
+    pub created_at: chrono::NaiveDateTime,
+    #[schema(no_recursion)]
+    pub children: Option<Vec<CommentTree>>,
+    #[ts(type = "number")]
+    pub upvote: i64,
+    pub depth: usize,
+    /// Replies left out by `children_limit`, to be fetched with the thread
+    #[ts(type = "number")]
+    #[serde(default)]
+    pub remaining_children: i64,
+    pub is_comment_owner: bool,
+    /// Whether the commenter is the site owner or a co-author
+    pub is_blog_author: bool,
+    /// Badges of the commenter
+    #[serde(default)]
+    pub roles: Vec<CommentRole>,
+    /// Only present for the blog author (moderator)
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    #[ts(optional)]
+    pub author_geo: Option<GeoInfo>,
+    /// Low scored or old and unvoted, the client shows it folded
+    #[serde(default)]
+    pub collapsed_by_default: bool,
+    /// Set on the replies flattened past `max_depth`, the ID of their ancestor
+    /// whose nested thread can be fetched separately
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    #[ts(optional)]
+    pub continue_thread_id: Option<i32>,
+    /// Commenters mentioned with `@handle` in the content
+    #[serde(default)]
+    pub mentions: Vec<CommentMention>,
+    /// Where the comment was written
+    #[serde(default)]
+    pub source: CommentSource,
+}
+
+/// An `@handle` in a comment that resolved to a commenter of the post
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, ToSchema, TS)]
+#[ts(export)]
+pub struct CommentMention {
+    /// As written after the `@`, lowercased
+    pub handle: String,
+    pub identity_id: i32,
+    pub name: String,
+}
+
+/// Reading time and summary of a post, generated when it's registered
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, ToSchema, TS)]
+#[ts(export)]
+pub struct BlogPostMeta {
+    pub slug: String,
+    pub title: Option<String>,
+    /// Minutes at an average reading speed, not generated yet if absent
+    pub reading_time_mins: Option<i32>,
+    /// A couple of sentences written by the LLM, absent if no LLM is configured
+    pub summary: Option<String>,
+}
+
+/// Further reading of a post, most similar first
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, ToSchema, TS)]
+#[ts(export)]
+pub struct RelatedReading {
+    pub posts: Vec<RelatedPost>,
+    pub articles: Vec<RelatedArticle>,
+}
+
+/// Another post of the blog
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, ToSchema, TS)]
+#[ts(export)]
+pub struct RelatedPost {
+    pub slug: String,
+    pub title: Option<String>,
+    /// Between 0 and 1, of the most similar chunks of the two posts
+    pub similarity: f64,
+}
+
+/// An article crawled by the recommendation system
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, ToSchema, TS)]
+#[ts(export)]
+pub struct RelatedArticle {
+    pub id: i32,
+    pub title: String,
+    pub url: String,
+    /// Between 0 and 1, of the most similar chunks of the post and the article
+    pub similarity: f64,
+}
+
+/// Role granted to an identity, shown as a badge next to its comments
+#[derive(
+    Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq, Hash, PartialOrd, Ord, ToSchema, TS,
+)]
+#[serde(rename_all = "snake_case")]
+#[ts(export)]
+pub enum CommentRole {
+    /// Writes on the blog
+    Author,
+    /// Sees where the comments come from
+    Moderator,
+    /// A trusted regular
+    Verified,
+}
+
+impl CommentRole {
+    pub const ALL: [CommentRole; 3] = [
+        CommentRole::Author,
+        CommentRole::Moderator,
+        CommentRole::Verified,
+    ];
+
+    /// Name of the role in the database and in the URLs
+    pub fn as_str(self) -> &'static str {
+        match self {
+            CommentRole::Author => "author",
+            CommentRole::Moderator => "moderator",
+            CommentRole::Verified => "verified",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|role| role.as_str() == s)
+    }
+}
+
+/// Where a comment was written
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize, PartialEq, Eq, ToSchema, TS)]
+#[serde(rename_all = "snake_case")]
+#[ts(export)]
+pub enum CommentSource {
+    /// The comment section of the website
+    #[default]
+    Site,
+    /// A reply from the fediverse (e.g. Mastodon) to the post's ActivityPub
+    /// object
+    Fediverse,
+}
+
+impl CommentSource {
+    /// Name of the source in the database
+    pub fn as_str(self) -> &'static str {
+        match self {
+            CommentSource::Site => "site",
+            CommentSource::Fediverse => "fediverse",
+        }
+    }
+
+    /// Unknown sources are treated as the website's
+    pub fn parse(s: &str) -> Self {
+        match s {
+            "fediverse" => CommentSource::Fediverse,
+            _ => CommentSource::Site,
+        }
+    }
+}
+
+/// Country and ASN information of an IP address resolved from the MaxMind
+/// (GeoLite2) databases.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq, ToSchema, TS)]
+#[ts(export)]
+pub struct GeoInfo {
+    /// ISO 3166-1 alpha-2 country code, e.g. `VN`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[ts(optional)]
+    pub country: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[ts(optional)]
+    pub asn: Option<u32>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[ts(optional)]
+    pub asn_org: Option<String>,
+}
+
+impl GeoInfo {
+    pub fn is_empty(&self) -> bool {
+        self.country.is_none() && self.asn.is_none() && self.asn_org.is_none()
+    }
+}
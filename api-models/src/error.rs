@@ -0,0 +1,52 @@
+use std::collections::HashMap;
+
+use serde::Serialize;
+use serde_json::Value;
+use ts_rs::TS;
+
+/// The body of the error responses
+#[derive(Serialize, TS)]
+#[ts(export)]
+pub struct ErrorResponse {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[ts(optional)]
+    pub error: Option<String>,
+
+    pub msg: String,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[ts(optional)]
+    pub reason: Option<Value>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[ts(optional)]
+    pub context: Option<HashMap<String, Value>>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[ts(optional, as = "Option<HashMap<String, Value>>")]
+    pub debug_info: Option<HashMap<&'static str, Value>>,
+}
+
+impl ErrorResponse {
+    pub fn new(msg: &str) -> Self {
+        Self {
+            error: None,
+            msg: msg.into(),
+            reason: None,
+            context: None,
+            debug_info: None,
+        }
+    }
+}
+
+impl std::fmt::Display for ErrorResponse {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Code {}: {} ({})",
+            self.error.as_deref().unwrap_or("UNKNOWN"),
+            self.msg,
+            self.reason.as_ref().unwrap_or(&serde_json::Value::Null)
+        )
+    }
+}
@@ -0,0 +1,16 @@
+use serde::{Deserialize, Serialize};
+use ts_rs::TS;
+use utoipa::ToSchema;
+
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema, TS)]
+#[ts(export)]
+pub struct HighlightItem {
+    pub id: String,
+    pub title: String,
+    pub text: String,
+    pub note: Option<String>,
+    pub color: String,
+    pub created_at: String,
+    pub link: String,
+    pub tags: Vec<String>,
+}
@@ -0,0 +1,54 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value as JsonValue;
+use ts_rs::TS;
+use utoipa::ToSchema;
+
+#[derive(Serialize, Deserialize, Clone, Debug, ToSchema, TS)]
+#[ts(export)]
+pub struct Traits {
+    pub email: Option<String>,
+    pub name: Option<String>,
+}
+
+impl Traits {
+    /// Defines rules for determining which fields are identifiers. Returns a
+    /// list of references to the fields that are considered identifiers.
+    pub fn get_identifiers(&self) -> Vec<&String> {
+        let mut ids = vec![];
+        if let Some(email) = &self.email {
+            ids.push(email);
+        }
+        ids
+    }
+}
+
+// TODO maybe write a macro for this?
+impl From<&Traits> for JsonValue {
+    fn from(t: &Traits) -> Self {
+        serde_json::to_value(t).unwrap()
+    }
+}
+
+impl From<JsonValue> for Traits {
+    fn from(value: JsonValue) -> Self {
+        serde_json::from_value(value).unwrap()
+    }
+}
+
+#[derive(Serialize, ToSchema, TS)]
+#[ts(export)]
+pub struct IsAuth {
+    pub is_auth: bool,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[ts(optional)]
+    pub id: Option<i32>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[ts(optional)]
+    pub traits: Option<Traits>,
+
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    #[ts(as = "Option<bool>", optional)]
+    pub site_owner: bool,
+}
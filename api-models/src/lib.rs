@@ -0,0 +1,32 @@
+//! Request and response types of the API. The TypeScript definitions of the
+//! types are exported to `web/src/types/api` by `cargo test -p api-models`, so
+//! that the frontend stays in sync with them.
+
+mod asset;
+mod blog;
+mod error;
+mod great_reads;
+mod identity;
+mod note;
+mod now;
+mod recommendation;
+mod status;
+
+pub use asset::{Asset, AssetVariant};
+pub use blog::{
+    BlogPostMeta, Comment, CommentMention, CommentRole, CommentSource, CommentTree, GeoInfo,
+    RelatedArticle, RelatedPost, RelatedReading,
+};
+pub use error::ErrorResponse;
+pub use great_reads::HighlightItem;
+pub use identity::{IsAuth, Traits};
+pub use note::{Note, NoteKind};
+pub use now::{Now, NowCommit, NowGaming, NowPlaying, SteamGame, StravaActivity};
+pub use recommendation::{
+    CrawlFailure, CrawlRun, CrawlSource, CrawlStatus, CrawlTiming, CrawlTrigger, DigestItem,
+    DigestPeriod, DigestTopic, FeedDigest, FeedEvent, FeedExplanation, FeedItem, FeedItemDetail,
+    FeedItemSummary, FeedSnapshot, HistoryImport, HistoryJob, HistoryJobStatus, HistoryMatch,
+    HistorySource, HistoryToken, LinkStatus, RankingPreset, SharedFeed, SharedFeedItem,
+    SourceFilter, SourceInfo,
+};
+pub use status::{ComponentStatus, Incident, StatusReport};
@@ -0,0 +1,45 @@
+use serde::{Deserialize, Serialize};
+use ts_rs::TS;
+use utoipa::ToSchema;
+
+/// A short post published with a Micropub client
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema, TS)]
+#[ts(export)]
+pub struct Note {
+    pub id: i32,
+    pub kind: NoteKind,
+    /// Optional for bookmarks and likes
+    pub content: Option<String>,
+    /// A title, notes usually have none
+    pub name: Option<String>,
+    /// The bookmarked or liked page
+    pub url: Option<String>,
+    pub categories: Vec<String>,
+    pub published_at: chrono::NaiveDateTime,
+}
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq, ToSchema, TS)]
+#[serde(rename_all = "snake_case")]
+#[ts(export)]
+pub enum NoteKind {
+    Note,
+    Bookmark,
+    Like,
+}
+
+impl NoteKind {
+    pub const ALL: [NoteKind; 3] = [NoteKind::Note, NoteKind::Bookmark, NoteKind::Like];
+
+    /// Name of the kind in the database
+    pub fn as_str(self) -> &'static str {
+        match self {
+            NoteKind::Note => "note",
+            NoteKind::Bookmark => "bookmark",
+            NoteKind::Like => "like",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|kind| kind.as_str() == s)
+    }
+}
@@ -0,0 +1,87 @@
+use serde::{Deserialize, Serialize};
+use ts_rs::TS;
+use utoipa::ToSchema;
+
+use crate::{HighlightItem, Note};
+
+/// What the owner is up to lately, for the "now" page. A source that isn't
+/// set up or has nothing yet is none.
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema, TS)]
+#[ts(export)]
+pub struct Now {
+    pub playing: Option<NowPlaying>,
+    pub commit: Option<NowCommit>,
+    pub gaming: Option<NowGaming>,
+    /// The latest activity recorded on Strava
+    pub activity: Option<StravaActivity>,
+    pub highlight: Option<HighlightItem>,
+    pub note: Option<Note>,
+    /// The sources that failed or took too long, their data is missing
+    pub unavailable: Vec<String>,
+}
+
+/// The track or the episode playing on Spotify
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema, TS)]
+#[ts(export)]
+pub struct NowPlaying {
+    /// Paused if false, the last played item is shown
+    pub is_playing: bool,
+    pub title: String,
+    /// The artists of the track, or the show of the episode
+    pub artists: Vec<String>,
+    pub url: Option<String>,
+}
+
+/// The latest commit pushed to a public repository on GitHub
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema, TS)]
+#[ts(export)]
+pub struct NowCommit {
+    /// `owner/name`
+    pub repo: String,
+    pub sha: String,
+    pub message: Option<String>,
+    pub url: String,
+    pub pushed_at: chrono::NaiveDateTime,
+}
+
+/// The game being played on Steam and the ones played lately
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema, TS)]
+#[ts(export)]
+pub struct NowGaming {
+    /// None if not in a game
+    pub playing: Option<SteamGame>,
+    /// The games played in the last two weeks, the most played first
+    pub recent: Vec<SteamGame>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema, TS)]
+#[ts(export)]
+pub struct SteamGame {
+    pub app_id: u32,
+    pub name: String,
+    /// Played in the last two weeks
+    pub recent_minutes: u32,
+    pub total_minutes: u32,
+    /// The page of the game on the Steam store
+    pub url: String,
+}
+
+/// An activity recorded on Strava
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema, TS)]
+#[ts(export)]
+pub struct StravaActivity {
+    #[ts(type = "number")]
+    pub id: i64,
+    pub name: String,
+    /// As Strava calls it, `Run`, `TrailRun`, `Ride`...
+    pub sport_type: String,
+    pub distance_meters: f64,
+    pub moving_seconds: u32,
+    /// None for the activities without a distance
+    pub pace_seconds_per_km: Option<u32>,
+    pub started_at: chrono::NaiveDateTime,
+    /// The route as a simplified encoded polyline, none for the activities
+    /// without a map
+    pub polyline: Option<String>,
+    pub url: String,
+}
@@ -0,0 +1,497 @@
+use serde::{Deserialize, Serialize};
+use ts_rs::TS;
+use utoipa::ToSchema;
+
+#[derive(Clone, Debug, Serialize, Deserialize, Default, ToSchema, TS)]
+#[ts(export)]
+pub struct SourceInfo {
+    pub key: String,
+    pub score: Option<f64>,
+    pub external_id: Option<String>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema, TS)]
+#[ts(export)]
+pub struct FeedItem {
+    pub id: i32,
+    pub title: String,
+    pub url: String,
+    pub score: f64,
+    pub similarity_score: Option<f64>,
+    pub submitted_at: Option<chrono::NaiveDateTime>,
+    pub sources: Vec<SourceInfo>,
+    /// ISO 639-3 code of the language of the article, none if it couldn't be
+    /// detected
+    pub lang: Option<String>,
+    /// Only a teaser of the article could be read, it was ranked by its title
+    /// and the text of the submission
+    pub paywalled: bool,
+    /// Only present with `explain=true`
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    #[ts(optional)]
+    pub explanation: Option<FeedExplanation>,
+}
+
+/// How each signal contributed to the score of a feed item. The ranks are
+/// among the candidates of the ranking, from 1.
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema, TS)]
+#[ts(export)]
+pub struct FeedExplanation {
+    /// None if the item has no chunks or there is no history
+    pub similarity_rank: Option<i32>,
+    /// `1 / (k + rank)` of the similarity
+    pub similarity_contribution: f64,
+    pub external_rank: Option<i32>,
+    /// `1 / (k + rank)` of the external score
+    pub external_contribution: f64,
+    pub freshness_rank: i32,
+    /// The decay the summed contributions are multiplied with
+    pub freshness_score: Option<f64>,
+    /// The multiplier of the terms the item shares with the history
+    pub lexical_boost: f64,
+    /// The history articles closest to the item, closest first
+    pub matches: Vec<HistoryMatch>,
+}
+
+/// A history article close to a feed item, through their closest chunks
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema, TS)]
+#[ts(export)]
+pub struct HistoryMatch {
+    pub history_article_id: i32,
+    pub title: String,
+    pub url: String,
+    /// The chunk of the feed item
+    pub chunk_id: i32,
+    /// The chunk of the history article
+    pub history_chunk_id: i32,
+    /// Weighted by the history entry, as in the similarity rank
+    pub similarity: f64,
+}
+
+/// The feed snapshot, streamed by the server rather than serialized at once
+#[derive(Serialize, ToSchema, TS)]
+#[ts(export)]
+pub struct FeedSnapshot {
+    pub items: Vec<FeedItem>,
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, PartialEq, Eq, ToSchema, TS)]
+#[serde(rename_all = "snake_case")]
+#[ts(export)]
+pub enum RankingPreset {
+    #[default]
+    Balanced,
+    NewerFirst,
+    TopFirst,
+    SimilarFirst,
+}
+
+impl RankingPreset {
+    pub const ALL: [RankingPreset; 4] = [
+        RankingPreset::Balanced,
+        RankingPreset::NewerFirst,
+        RankingPreset::TopFirst,
+        RankingPreset::SimilarFirst,
+    ];
+
+    /// Name of the preset in the database and the query strings
+    pub fn as_str(self) -> &'static str {
+        match self {
+            RankingPreset::Balanced => "balanced",
+            RankingPreset::NewerFirst => "newer_first",
+            RankingPreset::TopFirst => "top_first",
+            RankingPreset::SimilarFirst => "similar_first",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|preset| preset.as_str() == s)
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, PartialEq, Eq, ToSchema, TS)]
+#[serde(rename_all = "snake_case")]
+#[ts(export)]
+pub enum SourceFilter {
+    #[default]
+    All,
+    HackerNews,
+    Lobsters,
+}
+
+/// Where the entries of a reader's history come from: Raindrop is linked and
+/// synced, the extension saves single pages and the others are imported from
+/// their export files
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq, Eq, ToSchema, TS)]
+#[serde(rename_all = "snake_case")]
+#[ts(export)]
+pub enum HistorySource {
+    Raindrop,
+    Pocket,
+    Instapaper,
+    Omnivore,
+    Extension,
+}
+
+impl HistorySource {
+    pub const ALL: [HistorySource; 5] = [
+        HistorySource::Raindrop,
+        HistorySource::Pocket,
+        HistorySource::Instapaper,
+        HistorySource::Omnivore,
+        HistorySource::Extension,
+    ];
+
+    /// Name of the source in the database and in the URLs
+    pub fn as_str(self) -> &'static str {
+        match self {
+            HistorySource::Raindrop => "raindrop",
+            HistorySource::Pocket => "pocket",
+            HistorySource::Instapaper => "instapaper",
+            HistorySource::Omnivore => "omnivore",
+            HistorySource::Extension => "extension",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|source| source.as_str() == s)
+    }
+}
+
+/// An export file the reader imported into their history
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema, TS)]
+#[ts(export)]
+pub struct HistoryImport {
+    pub source: HistorySource,
+    /// How much the imported articles count, from 0 to 1
+    pub weight: f64,
+    /// Articles found in the last imported file
+    pub item_count: i32,
+    pub imported_at: chrono::NaiveDateTime,
+}
+
+/// A page saved to the history, it's fetched and embedded in the background
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema, TS)]
+#[ts(export)]
+pub struct HistoryJob {
+    pub id: i32,
+    pub url: String,
+    pub status: HistoryJobStatus,
+    /// Why the page couldn't be saved
+    pub error: Option<String>,
+    pub created_at: chrono::NaiveDateTime,
+    pub finished_at: Option<chrono::NaiveDateTime>,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq, Eq, ToSchema, TS)]
+#[serde(rename_all = "snake_case")]
+#[ts(export)]
+pub enum HistoryJobStatus {
+    Pending,
+    Done,
+    Failed,
+}
+
+impl HistoryJobStatus {
+    pub const ALL: [HistoryJobStatus; 3] = [
+        HistoryJobStatus::Pending,
+        HistoryJobStatus::Done,
+        HistoryJobStatus::Failed,
+    ];
+
+    /// Name of the status in the database
+    pub fn as_str(self) -> &'static str {
+        match self {
+            HistoryJobStatus::Pending => "pending",
+            HistoryJobStatus::Done => "done",
+            HistoryJobStatus::Failed => "failed",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|status| status.as_str() == s)
+    }
+}
+
+/// A token the extensions save pages to the reader's history with, the token
+/// itself is only returned once when it's created
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema, TS)]
+#[ts(export)]
+pub struct HistoryToken {
+    pub id: i32,
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    #[ts(optional)]
+    pub token: Option<String>,
+    pub last_used_at: Option<chrono::NaiveDateTime>,
+    pub created_at: chrono::NaiveDateTime,
+}
+
+/// A run of the crawler over the sources, in progress or finished
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema, TS)]
+#[ts(export)]
+pub struct CrawlRun {
+    pub id: i32,
+    pub status: CrawlStatus,
+    pub trigger: CrawlTrigger,
+    /// Entries fetched from the sources
+    pub fetched: i32,
+    /// New articles fetched, embedded and stored
+    pub embedded: i32,
+    pub failed: i32,
+    /// Why the entries failed, only the first ones are kept
+    pub failures: Vec<CrawlFailure>,
+    /// How long each source and step took, in the order they ran
+    pub timings: Vec<CrawlTiming>,
+    pub started_at: chrono::NaiveDateTime,
+    pub finished_at: Option<chrono::NaiveDateTime>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema, TS)]
+#[ts(export)]
+pub struct CrawlTiming {
+    /// The key of a source for fetching its entries, `articles` for fetching
+    /// and embedding the new articles and `comments` for their discussions
+    pub step: String,
+    #[ts(type = "number")]
+    pub millis: i64,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema, TS)]
+#[ts(export)]
+pub struct CrawlFailure {
+    /// The article, or none if a whole source failed
+    pub url: Option<String>,
+    pub reason: String,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq, Eq, ToSchema, TS)]
+#[serde(rename_all = "snake_case")]
+#[ts(export)]
+pub enum CrawlStatus {
+    Running,
+    Completed,
+    Failed,
+    Cancelled,
+}
+
+impl CrawlStatus {
+    pub const ALL: [CrawlStatus; 4] = [
+        CrawlStatus::Running,
+        CrawlStatus::Completed,
+        CrawlStatus::Failed,
+        CrawlStatus::Cancelled,
+    ];
+
+    /// Name of the status in the database
+    pub fn as_str(self) -> &'static str {
+        match self {
+            CrawlStatus::Running => "running",
+            CrawlStatus::Completed => "completed",
+            CrawlStatus::Failed => "failed",
+            CrawlStatus::Cancelled => "cancelled",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|status| status.as_str() == s)
+    }
+}
+
+/// Whether the link of an article still works, it's checked periodically
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq, Eq, ToSchema, TS)]
+#[serde(rename_all = "snake_case")]
+#[ts(export)]
+pub enum LinkStatus {
+    /// Not checked since the article was stored
+    Unchecked,
+    Alive,
+    /// The last checks failed, it's checked again with a backoff
+    Failing,
+    /// Failed too many checks in a row
+    Dead,
+}
+
+impl LinkStatus {
+    pub const ALL: [LinkStatus; 4] = [
+        LinkStatus::Unchecked,
+        LinkStatus::Alive,
+        LinkStatus::Failing,
+        LinkStatus::Dead,
+    ];
+
+    /// Name of the status in the database
+    pub fn as_str(self) -> &'static str {
+        match self {
+            LinkStatus::Unchecked => "unchecked",
+            LinkStatus::Alive => "alive",
+            LinkStatus::Failing => "failing",
+            LinkStatus::Dead => "dead",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|status| status.as_str() == s)
+    }
+}
+
+/// A feed item with the state of its link
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema, TS)]
+#[ts(export)]
+pub struct FeedItemDetail {
+    pub id: i32,
+    pub title: String,
+    pub url: String,
+    pub submitted_at: Option<chrono::NaiveDateTime>,
+    pub sources: Vec<SourceInfo>,
+    pub link_status: LinkStatus,
+    pub link_checked_at: Option<chrono::NaiveDateTime>,
+    /// A copy on the Wayback Machine, looked up once the link is dead
+    pub archive_url: Option<String>,
+    /// When the content and embeddings were last refreshed because the page
+    /// changed
+    pub content_refreshed_at: Option<chrono::NaiveDateTime>,
+}
+
+/// A short summary of a feed article, generated once and kept
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema, TS)]
+#[ts(export)]
+pub struct FeedItemSummary {
+    pub id: i32,
+    pub summary: String,
+    /// The key points of the article, a few short sentences
+    pub takeaways: Vec<String>,
+    pub generated_at: chrono::NaiveDateTime,
+}
+
+/// The period a digest of the feed covers, up to now
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, PartialEq, Eq, ToSchema, TS)]
+#[serde(rename_all = "snake_case")]
+#[ts(export)]
+pub enum DigestPeriod {
+    Day,
+    #[default]
+    Week,
+    Month,
+}
+
+impl DigestPeriod {
+    pub fn days(self) -> u64 {
+        match self {
+            DigestPeriod::Day => 1,
+            DigestPeriod::Week => 7,
+            DigestPeriod::Month => 30,
+        }
+    }
+}
+
+/// The top articles of a period that the reader hasn't read, grouped by topic
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema, TS)]
+#[ts(export)]
+pub struct FeedDigest {
+    pub period: DigestPeriod,
+    /// Start of the period, the articles were submitted since
+    pub since: chrono::NaiveDateTime,
+    /// The topic of the best article first, the unlabelled one last
+    pub topics: Vec<DigestTopic>,
+}
+
+/// Articles of a digest that share their terms
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema, TS)]
+#[ts(export)]
+pub struct DigestTopic {
+    /// The terms most of the articles share, none for the articles that
+    /// share them with no other
+    pub label: Option<String>,
+    pub items: Vec<DigestItem>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema, TS)]
+#[ts(export)]
+pub struct DigestItem {
+    pub id: i32,
+    pub title: String,
+    pub url: String,
+    pub score: f64,
+    pub submitted_at: Option<chrono::NaiveDateTime>,
+    pub sources: Vec<SourceInfo>,
+    pub paywalled: bool,
+    /// The kept summary, none if the article hasn't been summarized yet
+    pub summary: Option<FeedItemSummary>,
+}
+
+/// The top recommendations of the owner, shared with a public link
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema, TS)]
+#[ts(export)]
+pub struct SharedFeed {
+    pub generated_at: chrono::NaiveDateTime,
+    pub items: Vec<SharedFeedItem>,
+}
+
+/// The fields of a feed item that are fine to share, without the scores
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema, TS)]
+#[ts(export)]
+pub struct SharedFeedItem {
+    pub title: String,
+    pub url: String,
+    pub submitted_at: Option<chrono::NaiveDateTime>,
+    /// Keys of the sites the article was submitted to
+    pub sources: Vec<String>,
+}
+
+/// How a source of the recommendations is crawled
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema, TS)]
+#[ts(export)]
+pub struct CrawlSource {
+    pub key: String,
+    pub name: String,
+    pub enabled: bool,
+    /// Minutes between two crawls, the crawl interval of the config if unset
+    pub crawl_interval_mins: Option<i32>,
+    /// Entries taken from the source per crawl, the crawler's default if unset
+    pub max_items: Option<i32>,
+    pub last_crawled_at: Option<chrono::NaiveDateTime>,
+    /// The RSS or Atom feed the source is crawled from, if it's one
+    pub feed_url: Option<String>,
+}
+
+/// What started a crawl
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq, Eq, ToSchema, TS)]
+#[serde(rename_all = "snake_case")]
+#[ts(export)]
+pub enum CrawlTrigger {
+    /// The crawl interval elapsed
+    Schedule,
+    /// Someone loaded the feed
+    Feed,
+    /// Started by the owner
+    Admin,
+}
+
+impl CrawlTrigger {
+    pub const ALL: [CrawlTrigger; 3] = [
+        CrawlTrigger::Schedule,
+        CrawlTrigger::Feed,
+        CrawlTrigger::Admin,
+    ];
+
+    /// Name of the trigger in the database
+    pub fn as_str(self) -> &'static str {
+        match self {
+            CrawlTrigger::Schedule => "schedule",
+            CrawlTrigger::Feed => "feed",
+            CrawlTrigger::Admin => "admin",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|trigger| trigger.as_str() == s)
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema, TS)]
+#[serde(tag = "type", content = "data")]
+#[ts(export)]
+pub enum FeedEvent {
+    NewEntries { count: usize },
+}
@@ -0,0 +1,37 @@
+use serde::{Deserialize, Serialize};
+use ts_rs::TS;
+use utoipa::ToSchema;
+
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema, TS)]
+#[ts(export)]
+pub struct StatusReport {
+    pub components: Vec<ComponentStatus>,
+    /// The open incidents and the ones resolved recently, newest first
+    pub incidents: Vec<Incident>,
+}
+
+/// A dependency of the API and its uptime, in percent of the probes that
+/// succeeded over the period
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema, TS)]
+#[ts(export)]
+pub struct ComponentStatus {
+    pub name: String,
+    /// Whether the latest probe succeeded
+    pub operational: bool,
+    pub last_checked_at: chrono::NaiveDateTime,
+    pub uptime_24h: Option<f64>,
+    pub uptime_7d: Option<f64>,
+    pub uptime_30d: Option<f64>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema, TS)]
+#[ts(export)]
+pub struct Incident {
+    pub id: i32,
+    pub title: String,
+    pub description: Option<String>,
+    /// The affected component, if it's a single one
+    pub component: Option<String>,
+    pub started_at: chrono::NaiveDateTime,
+    pub resolved_at: Option<chrono::NaiveDateTime>,
+}
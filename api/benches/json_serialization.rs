@@ -0,0 +1,96 @@
+use std::hint::black_box;
+
+use api_models::{CommentSource, CommentTree};
+use criterion::{BenchmarkId, Criterion, criterion_group, criterion_main};
+
+/// Same as `STREAM_CHUNK_ITEMS` in `src/json.rs`
+const STREAM_CHUNK_ITEMS: usize = 64;
+
+pub fn criterion_benchmark(c: &mut Criterion) {
+    let mut group = c.benchmark_group("json_serialization");
+    for roots in [10, 100, 1000, 10000] {
+        let comments = generate_trees(roots);
+        group.bench_function(BenchmarkId::new("buffered", roots), |b| {
+            b.iter(|| serde_json::to_vec(black_box(&comments)))
+        });
+        group.bench_function(BenchmarkId::new("streamed", roots), |b| {
+            b.iter(|| {
+                stream_chunks(black_box(&comments))
+                    .map(|chunk| chunk.len())
+                    .sum::<usize>()
+            })
+        });
+        // What the client waits for before the first byte
+        group.bench_function(BenchmarkId::new("streamed_first_chunk", roots), |b| {
+            b.iter(|| stream_chunks(black_box(&comments)).next())
+        });
+    }
+    group.finish();
+}
+
+/// The chunks of `json::array_body("[", items, "]")`, serialized one at a time
+/// as they're pulled
+fn stream_chunks(items: &[CommentTree]) -> impl Iterator<Item = Vec<u8>> + '_ {
+    let chunks = items
+        .chunks(STREAM_CHUNK_ITEMS)
+        .enumerate()
+        .map(|(i, chunk)| {
+            let mut buf = Vec::new();
+            for (j, item) in chunk.iter().enumerate() {
+                if i > 0 || j > 0 {
+                    buf.push(b',');
+                }
+                let _ = serde_json::to_writer(&mut buf, item);
+            }
+            buf
+        });
+
+    std::iter::once(b"[".to_vec())
+        .chain(chunks)
+        .chain(std::iter::once(b"]".to_vec()))
+}
+
+/// Root comments with 3 replies each, which have 2 replies each
+fn generate_trees(roots: usize) -> Vec<CommentTree> {
+    let mut id = 0;
+    let mut comment = |parent_id: Option<i32>, depth: usize| {
+        id += 1;
+        CommentTree {
+            id,
+            author_name: "author".to_string(),
+            content: "Lorem ipsum dolor sit amet, consectetur adipiscing elit. ".repeat(8),
+            parent_id,
+            created_at: chrono::offset::Local::now().naive_local(),
+            children: None,
+            upvote: 0,
+            depth,
+            remaining_children: 0,
+            is_comment_owner: false,
+            is_blog_author: false,
+            roles: vec![],
+            author_geo: None,
+            collapsed_by_default: false,
+            continue_thread_id: None,
+            mentions: vec![],
+            source: CommentSource::Site,
+        }
+    };
+
+    (0..roots)
+        .map(|_| {
+            let mut root = comment(None, 0);
+            let replies = (0..3)
+                .map(|_| {
+                    let mut reply = comment(Some(root.id), 1);
+                    reply.children = Some((0..2).map(|_| comment(Some(reply.id), 2)).collect());
+                    reply
+                })
+                .collect();
+            root.children = Some(replies);
+            root
+        })
+        .collect()
+}
+
+criterion_group!(benches, criterion_benchmark);
+criterion_main!(benches);
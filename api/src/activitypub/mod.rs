@@ -0,0 +1,907 @@
+//! A minimal ActivityPub actor of the blog, so that fediverse (e.g. Mastodon)
+//! users can follow it and reply to the posts. The owner federates a post to
+//! announce it to the followers, and the replies to it are stored as comments
+//! of the post.
+
+mod signature;
+
+use std::{sync::Arc, time::Duration};
+
+use axum::{
+    Router,
+    body::Bytes,
+    extract::{Path, Query, State},
+    http::{HeaderMap, Method, StatusCode, header},
+    response::{IntoResponse, Response},
+    routing::{get, post},
+};
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+use diesel_async::RunQueryDsl;
+use eyre::{Context as _, OptionExt as _, eyre};
+use futures::StreamExt as _;
+use rsa::{
+    RsaPrivateKey, RsaPublicKey,
+    pkcs8::{DecodePrivateKey as _, EncodePublicKey as _, LineEnding},
+};
+use serde::Deserialize;
+use serde_json::{Value, json};
+
+use crate::{
+    App,
+    blog::{self, fediverse},
+    config::ActivityPubConfig,
+    error::AppError,
+    identity::AuthUser,
+    models::activitypub::NewActivityPubFollower,
+    outbound::HttpClient,
+    real_ip::ClientIp,
+    schema::{activitypub_followers, blog_comments, blog_posts},
+    ssrf,
+};
+
+use self::signature::SignatureHeader;
+
+const ACTIVITY_JSON: &str = "application/activity+json";
+/// What the remote actors are requested as, some servers only answer to the
+/// JSON-LD one
+const ACTIVITY_ACCEPT: &str = r#"application/activity+json, application/ld+json; profile="https://www.w3.org/ns/activitystreams""#;
+const AS_CONTEXT: &str = "https://www.w3.org/ns/activitystreams";
+const SECURITY_CONTEXT: &str = "https://w3id.org/security/v1";
+const PUBLIC: &str = "https://www.w3.org/ns/activitystreams#Public";
+
+/// Posts listed in the outbox, the latest ones
+const OUTBOX_POSTS: i64 = 20;
+/// Remote actors and keys larger than this are rejected
+const MAX_DOCUMENT_BYTES: usize = 1024 * 1024;
+const ACTOR_CACHE_DURATION: Duration = Duration::from_hours(1);
+const MAX_CONCURRENT_DELIVERIES: usize = 8;
+
+pub struct ActivityPub {
+    username: String,
+    /// Host of the website, the actor is `@username@domain`
+    domain: String,
+    site_url: String,
+    /// Where the API is served publicly, the IDs of the objects are under it
+    base_url: String,
+    private_key: RsaPrivateKey,
+    public_key_pem: String,
+    /// Remote actors by the ID of their key, so that every activity of an
+    /// instance doesn't fetch its actor again
+    actors: retainer::Cache<String, Arc<RemoteActor>>,
+}
+
+impl ActivityPub {
+    pub fn new(site_url: &str, config: &ActivityPubConfig) -> Result<Self, eyre::Error> {
+        let private_key = RsaPrivateKey::from_pkcs8_pem(&config.private_key_pem)
+            .wrap_err("ACTIVITYPUB_PRIVATE_KEY is not a PKCS#8 PEM RSA key")?;
+        let public_key_pem = RsaPublicKey::from(&private_key)
+            .to_public_key_pem(LineEnding::LF)
+            .wrap_err("failed to encode the public key")?;
+        let site_url = site_url.trim_end_matches('/').to_string();
+        let domain = url::Url::parse(&site_url)?
+            .host_str()
+            .ok_or_eyre("SITE_URL has no host")?
+            .to_string();
+
+        Ok(Self {
+            username: config.username.clone(),
+            domain,
+            // The reverse proxy serves the API under /api of the website
+            base_url: format!("{site_url}/api"),
+            site_url,
+            private_key,
+            public_key_pem,
+            actors: retainer::Cache::new(),
+        })
+    }
+
+    fn actor_id(&self) -> String {
+        format!("{}/ap/actor", self.base_url)
+    }
+
+    fn key_id(&self) -> String {
+        format!("{}#main-key", self.actor_id())
+    }
+
+    fn inbox(&self) -> String {
+        format!("{}/ap/inbox", self.base_url)
+    }
+
+    fn post_id(&self, slug: &str) -> String {
+        format!("{}/ap/posts/{slug}", self.base_url)
+    }
+
+    /// Slug of the post with the object ID
+    fn slug_of<'a>(&self, object_id: &'a str) -> Option<&'a str> {
+        object_id
+            .strip_prefix(&self.base_url)?
+            .strip_prefix("/ap/posts/")
+            .filter(|slug| !slug.is_empty() && !slug.contains('/'))
+    }
+
+    fn actor(&self) -> Value {
+        json!({
+            "@context": [AS_CONTEXT, SECURITY_CONTEXT],
+            "id": self.actor_id(),
+            "type": "Person",
+            "preferredUsername": self.username,
+            "name": self.domain,
+            "summary": format!("<p>Posts of {}</p>", escape(&self.site_url)),
+            "url": format!("{}/blog", self.site_url),
+            "inbox": self.inbox(),
+            "outbox": format!("{}/ap/outbox", self.base_url),
+            "followers": format!("{}/ap/followers", self.base_url),
+            "manuallyApprovesFollowers": false,
+            "discoverable": true,
+            "endpoints": { "sharedInbox": self.inbox() },
+            "publicKey": {
+                "id": self.key_id(),
+                "owner": self.actor_id(),
+                "publicKeyPem": self.public_key_pem,
+            },
+        })
+    }
+
+    fn article(&self, post: &FederatedPost) -> Value {
+        let url = blog::post_url(&self.site_url, &post.slug);
+        let title = post
+            .title
+            .clone()
+            .unwrap_or_else(|| post.slug.replace('-', " "));
+        let summary = post
+            .summary
+            .as_deref()
+            .map(|summary| format!("<p>{}</p>", escape(summary)))
+            .unwrap_or_default();
+
+        json!({
+            "id": self.post_id(&post.slug),
+            "type": "Article",
+            "attributedTo": self.actor_id(),
+            "name": title,
+            "content": format!(
+                r#"{summary}<p><a href="{url}">{url}</a></p>"#,
+                url = escape(&url)
+            ),
+            "url": url,
+            "published": post.federated_at.and_utc().to_rfc3339(),
+            "to": [PUBLIC],
+            "cc": [format!("{}/ap/followers", self.base_url)],
+        })
+    }
+
+    fn create_activity(&self, post: &FederatedPost) -> Value {
+        let object = self.article(post);
+        json!({
+            "@context": AS_CONTEXT,
+            "id": format!("{}/activity", self.post_id(&post.slug)),
+            "type": "Create",
+            "actor": self.actor_id(),
+            "published": object["published"],
+            "to": object["to"],
+            "cc": object["cc"],
+            "object": object,
+        })
+    }
+
+    /// GETs an ActivityPub document, signed for the instances that only serve
+    /// them to other servers
+    async fn fetch(&self, http: &HttpClient, url: &str) -> Result<Value, eyre::Error> {
+        let url = url::Url::parse(url)?;
+        ssrf::check_url(&url).await?;
+
+        let mut request = http
+            .public_only()
+            .get(url.clone())
+            .header(header::ACCEPT, ACTIVITY_ACCEPT);
+        for (name, value) in
+            signature::sign(&self.private_key, &self.key_id(), &Method::GET, &url, None)?
+        {
+            request = request.header(name, value);
+        }
+        let mut response = request
+            .send()
+            .await
+            .wrap_err_with(|| format!("failed to fetch {url}"))?
+            .error_for_status()?;
+
+        // The length header may be missing or lie
+        let mut body = Vec::new();
+        while let Some(chunk) = response.chunk().await? {
+            if body.len() + chunk.len() > MAX_DOCUMENT_BYTES {
+                eyre::bail!("{url} is larger than {MAX_DOCUMENT_BYTES} bytes");
+            }
+            body.extend_from_slice(&chunk);
+        }
+        serde_json::from_slice(&body).wrap_err_with(|| format!("{url} is not JSON"))
+    }
+
+    /// Whether the server of the actor answers that it's deleted
+    async fn actor_gone(&self, http: &HttpClient, actor: &str) -> bool {
+        match self.fetch(http, actor).await {
+            Ok(_) => false,
+            Err(e) => e
+                .downcast_ref::<reqwest::Error>()
+                .and_then(reqwest::Error::status)
+                .is_some_and(|status| matches!(status, StatusCode::NOT_FOUND | StatusCode::GONE)),
+        }
+    }
+
+    /// The actor of the key, cached unless `refresh`
+    async fn actor_of_key(
+        &self,
+        http: &HttpClient,
+        key_id: &str,
+        refresh: bool,
+    ) -> Result<Arc<RemoteActor>, eyre::Error> {
+        if !refresh && let Some(actor) = self.actors.get(key_id).await {
+            return Ok(actor.clone());
+        }
+
+        // Mastodon's key IDs are a fragment of the actor, others point to a
+        // key document with the actor as its owner
+        let document_url = key_id.split('#').next().unwrap_or(key_id);
+        let mut document = self.fetch(http, document_url).await?;
+        if document.get("publicKey").is_none()
+            && let Some(owner) = document.get("owner").and_then(Value::as_str)
+        {
+            document = self.fetch(http, owner).await?;
+        }
+
+        let actor = serde_json::from_value::<RemoteActor>(document)
+            .wrap_err("the key's owner is not an actor")?;
+        if actor.public_key.id != key_id || actor.public_key.owner != actor.id {
+            return Err(eyre!("the key is not the actor's"));
+        }
+
+        let actor = Arc::new(actor);
+        self.actors
+            .insert(key_id.to_string(), actor.clone(), ACTOR_CACHE_DURATION)
+            .await;
+        Ok(actor)
+    }
+
+    /// The actor that signed the request to the inbox
+    async fn verify(
+        &self,
+        http: &HttpClient,
+        headers: &HeaderMap,
+        body: &[u8],
+    ) -> Result<Arc<RemoteActor>, eyre::Error> {
+        let header = headers
+            .get("signature")
+            .and_then(|v| v.to_str().ok())
+            .ok_or_eyre("the request is not signed")?;
+        let header = SignatureHeader::parse(header)?;
+        // Signed with the public path, the proxy in front strips the /api
+        let target = format!("post {}", url::Url::parse(&self.inbox())?.path());
+
+        let actor = self.actor_of_key(http, &header.key_id, false).await?;
+        if signature::verify(
+            &header,
+            &actor.public_key.public_key_pem,
+            &target,
+            headers,
+            body,
+        )
+        .is_ok()
+        {
+            return Ok(actor);
+        }
+
+        // The key may have been rotated since the actor was cached
+        let actor = self.actor_of_key(http, &header.key_id, true).await?;
+        signature::verify(
+            &header,
+            &actor.public_key.public_key_pem,
+            &target,
+            headers,
+            body,
+        )?;
+        Ok(actor)
+    }
+
+    /// Sends the activity to the inboxes concurrently, failed deliveries are
+    /// logged and not retried
+    async fn deliver(&self, http: &HttpClient, activity: &Value, inboxes: Vec<String>) {
+        let body = activity.to_string().into_bytes();
+        futures::stream::iter(inboxes)
+            .for_each_concurrent(MAX_CONCURRENT_DELIVERIES, |inbox| {
+                let body = &body;
+                async move {
+                    let _ = self
+                        .post(http, &inbox, body)
+                        .await
+                        .inspect_err(|e| tracing::warn!(?e, inbox, "Failed to deliver activity"));
+                }
+            })
+            .await;
+    }
+
+    async fn post(&self, http: &HttpClient, inbox: &str, body: &[u8]) -> Result<(), eyre::Error> {
+        let url = url::Url::parse(inbox)?;
+        ssrf::check_url(&url).await?;
+
+        let mut request = http
+            .public_only()
+            .post(url.clone())
+            .header(header::CONTENT_TYPE, ACTIVITY_JSON)
+            .body(body.to_vec());
+        for (name, value) in signature::sign(
+            &self.private_key,
+            &self.key_id(),
+            &Method::POST,
+            &url,
+            Some(body),
+        )? {
+            request = request.header(name, value);
+        }
+        request
+            .send()
+            .await
+            .wrap_err_with(|| format!("failed to post to {url}"))?
+            .error_for_status()?;
+        Ok(())
+    }
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct RemoteActor {
+    id: String,
+    inbox: String,
+    preferred_username: Option<String>,
+    name: Option<String>,
+    endpoints: Option<Endpoints>,
+    public_key: PublicKey,
+}
+
+impl RemoteActor {
+    /// The display name, or `@username@host` if there's none
+    fn display_name(&self) -> String {
+        if let Some(name) = self
+            .name
+            .as_deref()
+            .map(str::trim)
+            .filter(|n| !n.is_empty())
+        {
+            return name.to_string();
+        }
+        let host = url::Url::parse(&self.id)
+            .ok()
+            .and_then(|url| url.host_str().map(str::to_string))
+            .unwrap_or_default();
+        format!(
+            "@{}@{host}",
+            self.preferred_username.as_deref().unwrap_or("unknown")
+        )
+    }
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct Endpoints {
+    shared_inbox: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct PublicKey {
+    id: String,
+    owner: String,
+    public_key_pem: String,
+}
+
+#[derive(Deserialize)]
+struct Activity {
+    #[serde(rename = "type")]
+    kind: String,
+    actor: String,
+    #[serde(default)]
+    object: Value,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Note {
+    id: String,
+    #[serde(rename = "type")]
+    kind: String,
+    #[serde(default)]
+    attributed_to: Value,
+    in_reply_to: Option<String>,
+    #[serde(default)]
+    content: String,
+}
+
+struct FederatedPost {
+    slug: String,
+    title: Option<String>,
+    summary: Option<String>,
+    federated_at: NaiveDateTime,
+}
+
+/// The ID of an object that may be embedded or referenced by its ID
+fn object_id(object: &Value) -> Option<&str> {
+    object
+        .as_str()
+        .or_else(|| object.get("id").and_then(Value::as_str))
+}
+
+fn same_host(a: &str, b: &str) -> bool {
+    let host = |id: &str| url::Url::parse(id).ok()?.host_str().map(str::to_lowercase);
+    host(a).is_some_and(|host_a| Some(host_a) == host(b))
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn activity_json(value: Value) -> Response {
+    ([(header::CONTENT_TYPE, ACTIVITY_JSON)], value.to_string()).into_response()
+}
+
+fn enabled(ctx: &App) -> Result<&ActivityPub, AppError> {
+    ctx.activitypub
+        .as_ref()
+        .ok_or(("Not found", StatusCode::NOT_FOUND).into())
+}
+
+/// Served outside of the versioned API since the IDs of the objects must
+/// never change, and WebFinger has a fixed path
+pub fn route() -> Router<App> {
+    Router::<App>::new()
+        .route("/.well-known/webfinger", get(webfinger))
+        .route("/ap/actor", get(get_actor))
+        .route("/ap/inbox", post(post_inbox))
+        .route("/ap/outbox", get(get_outbox))
+        .route("/ap/followers", get(get_followers))
+        .route("/ap/posts/{slug}", get(get_post))
+}
+
+/// Announcing the posts to the followers, for the owner
+pub fn admin_route() -> Router<App> {
+    Router::<App>::new().route("/admin/blog/posts/{slug}/federate", post(federate_post))
+}
+
+#[derive(Deserialize)]
+struct WebFingerQuery {
+    resource: String,
+}
+
+async fn webfinger(
+    State(ctx): State<App>,
+    Query(query): Query<WebFingerQuery>,
+) -> Result<Response, AppError> {
+    let ap = enabled(&ctx)?;
+
+    let subject = format!("acct:{}@{}", ap.username, ap.domain);
+    let resource = query.resource.trim();
+    if !resource.eq_ignore_ascii_case(&subject) && resource != ap.actor_id() {
+        return Err(("No such account", StatusCode::NOT_FOUND).into());
+    }
+
+    let body = json!({
+        "subject": subject,
+        "aliases": [ap.actor_id()],
+        "links": [
+            {
+                "rel": "self",
+                "type": ACTIVITY_JSON,
+                "href": ap.actor_id(),
+            },
+            {
+                "rel": "http://webfinger.net/rel/profile-page",
+                "type": "text/html",
+                "href": format!("{}/blog", ap.site_url),
+            },
+        ],
+    });
+    Ok((
+        [(header::CONTENT_TYPE, "application/jrd+json")],
+        body.to_string(),
+    )
+        .into_response())
+}
+
+async fn get_actor(State(ctx): State<App>) -> Result<Response, AppError> {
+    Ok(activity_json(enabled(&ctx)?.actor()))
+}
+
+async fn get_outbox(State(ctx): State<App>) -> Result<Response, AppError> {
+    let ap = enabled(&ctx)?;
+    let mut conn = ctx.diesel.get().await?;
+
+    let total = blog_posts::table
+        .filter(blog_posts::federated_at.is_not_null())
+        .count()
+        .get_result::<i64>(&mut conn)
+        .await?;
+    let activities = blog_posts::table
+        .filter(blog_posts::federated_at.is_not_null())
+        .order(blog_posts::federated_at.desc())
+        .limit(OUTBOX_POSTS)
+        .select((
+            blog_posts::slug,
+            blog_posts::title,
+            blog_posts::summary,
+            blog_posts::federated_at.assume_not_null(),
+        ))
+        .load::<(String, Option<String>, Option<String>, NaiveDateTime)>(&mut conn)
+        .await?
+        .into_iter()
+        .map(|(slug, title, summary, federated_at)| {
+            ap.create_activity(&FederatedPost {
+                slug,
+                title,
+                summary,
+                federated_at,
+            })
+        })
+        .collect::<Vec<_>>();
+
+    Ok(activity_json(json!({
+        "@context": AS_CONTEXT,
+        "id": format!("{}/ap/outbox", ap.base_url),
+        "type": "OrderedCollection",
+        "totalItems": total,
+        "orderedItems": activities,
+    })))
+}
+
+/// Only the number of followers, who they are isn't shared
+async fn get_followers(State(ctx): State<App>) -> Result<Response, AppError> {
+    let ap = enabled(&ctx)?;
+    let mut conn = ctx.diesel.get().await?;
+
+    let total = activitypub_followers::table
+        .count()
+        .get_result::<i64>(&mut conn)
+        .await?;
+
+    Ok(activity_json(json!({
+        "@context": AS_CONTEXT,
+        "id": format!("{}/ap/followers", ap.base_url),
+        "type": "OrderedCollection",
+        "totalItems": total,
+    })))
+}
+
+async fn get_post(State(ctx): State<App>, Path(slug): Path<String>) -> Result<Response, AppError> {
+    let ap = enabled(&ctx)?;
+    let mut conn = ctx.diesel.get().await?;
+
+    let (title, summary, federated_at) = blog_posts::table
+        .filter(blog_posts::category.eq("blog"))
+        .filter(blog_posts::slug.eq(&slug))
+        .filter(blog_posts::federated_at.is_not_null())
+        .select((
+            blog_posts::title,
+            blog_posts::summary,
+            blog_posts::federated_at.assume_not_null(),
+        ))
+        .first::<(Option<String>, Option<String>, NaiveDateTime)>(&mut conn)
+        .await
+        .optional()?
+        .ok_or(("Post not found", StatusCode::NOT_FOUND))?;
+
+    let mut article = ap.article(&FederatedPost {
+        slug,
+        title,
+        summary,
+        federated_at,
+    });
+    article["@context"] = json!(AS_CONTEXT);
+    Ok(activity_json(article))
+}
+
+async fn post_inbox(
+    State(ctx): State<App>,
+    client_ip: ClientIp,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<StatusCode, AppError> {
+    let ap = enabled(&ctx)?;
+
+    let raw = serde_json::from_slice::<Value>(&body)
+        .map_err(|_| ("Invalid activity", StatusCode::BAD_REQUEST))?;
+    let activity = serde_json::from_value::<Activity>(raw.clone())
+        .map_err(|_| ("Invalid activity", StatusCode::BAD_REQUEST))?;
+
+    // A deleted account can't be verified since its key is gone with it, and
+    // is announced to every instance it ever talked to. Its server is asked
+    // whether it's really gone, the others have to be signed.
+    if activity.kind == "Delete" && object_id(&activity.object) == Some(activity.actor.as_str()) {
+        let follows = diesel::select(diesel::dsl::exists(
+            activitypub_followers::table
+                .filter(activitypub_followers::actor_id.eq(&activity.actor)),
+        ))
+        .get_result::<bool>(&mut ctx.diesel.get().await?)
+        .await?;
+        // Nothing to delete for the accounts that don't follow the blog
+        if !follows {
+            return Ok(StatusCode::ACCEPTED);
+        }
+        if ap.actor_gone(&ctx.http, &activity.actor).await {
+            let mut conn = ctx.diesel.get().await?;
+            remove_follower(&mut conn, &activity.actor).await?;
+            return Ok(StatusCode::ACCEPTED);
+        }
+    }
+
+    let actor = ap.verify(&ctx.http, &headers, &body).await.map_err(|e| {
+        tracing::info!(
+            ?e,
+            actor = activity.actor,
+            "Rejected an unverified activity"
+        );
+        ("Invalid signature", StatusCode::UNAUTHORIZED)
+    })?;
+    if actor.id != activity.actor {
+        return Err(("The activity is not the signer's", StatusCode::UNAUTHORIZED).into());
+    }
+
+    match activity.kind.as_str() {
+        "Follow" if object_id(&activity.object) == Some(ap.actor_id().as_str()) => {
+            follow(&ctx, &actor, raw).await?;
+        }
+        "Undo" if activity.object.get("type").and_then(Value::as_str) == Some("Follow") => {
+            let mut conn = ctx.diesel.get().await?;
+            remove_follower(&mut conn, &actor.id).await?;
+        }
+        "Delete" if object_id(&activity.object) == Some(actor.id.as_str()) => {
+            let mut conn = ctx.diesel.get().await?;
+            remove_follower(&mut conn, &actor.id).await?;
+        }
+        "Create" => reply(&ctx, ap, &actor, &activity.object, &client_ip).await?,
+        "Delete" => {
+            // Only the instance of the reply can delete it
+            if let Some(id) = object_id(&activity.object).filter(|id| same_host(id, &actor.id)) {
+                let mut conn = ctx.diesel.get().await?;
+                fediverse::delete(&mut conn, id).await?;
+            }
+        }
+        kind => tracing::debug!(kind, actor = actor.id, "Ignored an activity"),
+    }
+
+    Ok(StatusCode::ACCEPTED)
+}
+
+async fn remove_follower(
+    conn: &mut diesel_async::AsyncPgConnection,
+    actor_id: &str,
+) -> Result<(), diesel::result::Error> {
+    diesel::delete(
+        activitypub_followers::table.filter(activitypub_followers::actor_id.eq(actor_id)),
+    )
+    .execute(conn)
+    .await?;
+    Ok(())
+}
+
+/// Stores the follower and accepts the follow, the blog doesn't approve them
+async fn follow(ctx: &App, actor: &RemoteActor, follow: Value) -> Result<(), AppError> {
+    let follower = NewActivityPubFollower {
+        actor_id: actor.id.clone(),
+        inbox: actor.inbox.clone(),
+        shared_inbox: actor
+            .endpoints
+            .as_ref()
+            .and_then(|e| e.shared_inbox.clone()),
+    };
+
+    let mut conn = ctx.diesel.get().await?;
+    diesel::insert_into(activitypub_followers::table)
+        .values(&follower)
+        .on_conflict(activitypub_followers::actor_id)
+        .do_update()
+        .set(&follower)
+        .execute(&mut conn)
+        .await?;
+
+    let ctx = ctx.clone();
+    tokio::spawn(async move {
+        let Some(ap) = ctx.activitypub.as_ref() else {
+            return;
+        };
+        let accept = json!({
+            "@context": AS_CONTEXT,
+            "id": format!("{}#accepts/{}", ap.actor_id(), uuid::Uuid::new_v4()),
+            "type": "Accept",
+            "actor": ap.actor_id(),
+            "object": follow,
+        });
+        ap.deliver(&ctx.http, &accept, vec![follower.inbox]).await;
+    });
+
+    Ok(())
+}
+
+/// Stores a note replying to a federated post, or to another reply of one,
+/// as a comment of the post
+async fn reply(
+    ctx: &App,
+    ap: &ActivityPub,
+    actor: &RemoteActor,
+    object: &Value,
+    client_ip: &ClientIp,
+) -> Result<(), AppError> {
+    let Ok(note) = Note::deserialize(object) else {
+        return Ok(());
+    };
+    if note.kind != "Note"
+        || object_id(&note.attributed_to) != Some(actor.id.as_str())
+        || !same_host(&note.id, &actor.id)
+    {
+        return Ok(());
+    }
+    let Some(in_reply_to) = note.in_reply_to else {
+        return Ok(());
+    };
+
+    let mut conn = ctx.diesel.get().await?;
+    let replied = match ap.slug_of(&in_reply_to) {
+        Some(slug) => blog_posts::table
+            .filter(blog_posts::category.eq("blog"))
+            .filter(blog_posts::slug.eq(slug))
+            .filter(blog_posts::federated_at.is_not_null())
+            .select((blog_posts::id, blog_posts::slug))
+            .first::<(i32, String)>(&mut conn)
+            .await
+            .optional()?
+            .map(|(post_id, slug)| (post_id, slug, None)),
+        None => blog_comments::table
+            .inner_join(blog_posts::table)
+            .filter(blog_comments::source_id.eq(&in_reply_to))
+            .select((blog_comments::post_id, blog_posts::slug, blog_comments::id))
+            .first::<(i32, String, i32)>(&mut conn)
+            .await
+            .optional()?
+            .map(|(post_id, slug, parent_id)| (post_id, slug, Some(parent_id))),
+    };
+    drop(conn);
+
+    // Not a reply to the blog, e.g. delivered to the shared inbox of a
+    // follower
+    let Some((post_id, slug, parent_id)) = replied else {
+        return Ok(());
+    };
+
+    let comment_id = fediverse::create(
+        ctx,
+        client_ip,
+        fediverse::FediverseReply {
+            object_id: note.id,
+            post_id,
+            slug,
+            parent_id,
+            author_name: actor.display_name(),
+            html: note.content,
+        },
+    )
+    .await?;
+    if let Some(comment_id) = comment_id {
+        tracing::info!(
+            comment_id,
+            actor = actor.id,
+            "Stored a reply from the fediverse"
+        );
+    }
+
+    Ok(())
+}
+
+/// Announces the post to the followers, it's in the outbox since. The post is
+/// registered and its meta generated first if needed, so that it's announced
+/// with its title and summary.
+async fn federate_post(
+    State(ctx): State<App>,
+    AuthUser(i): AuthUser,
+    Path(slug): Path<String>,
+) -> Result<Response, AppError> {
+    if i.id != ctx.config.owner_identity_id {
+        return Err(("Not permitted", StatusCode::FORBIDDEN).into());
+    }
+    let ap = enabled(&ctx)?;
+
+    let has_meta = {
+        let mut conn = ctx.diesel.get().await?;
+        let (post_id, _) = blog::register_post(&mut conn, &slug).await?;
+        blog_posts::table
+            .filter(blog_posts::id.eq(post_id))
+            .select(blog_posts::meta_updated_at.is_not_null())
+            .first::<bool>(&mut conn)
+            .await?
+    };
+    if !has_meta {
+        blog::meta::generate(&ctx, &slug).await?;
+    }
+
+    let mut conn = ctx.diesel.get().await?;
+    let (title, summary, federated_at) = diesel::update(
+        blog_posts::table
+            .filter(blog_posts::category.eq("blog"))
+            .filter(blog_posts::slug.eq(&slug))
+            .filter(blog_posts::federated_at.is_null()),
+    )
+    .set(blog_posts::federated_at.eq(chrono::Utc::now().naive_utc()))
+    .returning((
+        blog_posts::title,
+        blog_posts::summary,
+        blog_posts::federated_at.assume_not_null(),
+    ))
+    .get_result::<(Option<String>, Option<String>, NaiveDateTime)>(&mut conn)
+    .await
+    .optional()?
+    .ok_or(("The post is federated already", StatusCode::CONFLICT))?;
+
+    let mut inboxes = activitypub_followers::table
+        .select((
+            activitypub_followers::inbox,
+            activitypub_followers::shared_inbox,
+        ))
+        .load::<(String, Option<String>)>(&mut conn)
+        .await?
+        .into_iter()
+        // Once per instance if its followers share an inbox
+        .map(|(inbox, shared_inbox)| shared_inbox.unwrap_or(inbox))
+        .collect::<Vec<_>>();
+    inboxes.sort_unstable();
+    inboxes.dedup();
+
+    let activity = ap.create_activity(&FederatedPost {
+        slug,
+        title,
+        summary,
+        federated_at,
+    });
+
+    let delivered = activity.clone();
+    let ctx = ctx.clone();
+    tokio::spawn(async move {
+        if let Some(ap) = ctx.activitypub.as_ref() {
+            tracing::info!(followers = inboxes.len(), "Delivering a federated post");
+            ap.deliver(&ctx.http, &delivered, inboxes).await;
+        }
+    });
+
+    Ok(activity_json(activity))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn object_ids_are_read_from_embedded_objects_and_references() {
+        assert_eq!(
+            object_id(&json!("https://mastodon.example/users/alice")),
+            Some("https://mastodon.example/users/alice")
+        );
+        assert_eq!(
+            object_id(&json!({ "id": "https://mastodon.example/notes/1", "type": "Note" })),
+            Some("https://mastodon.example/notes/1")
+        );
+        assert_eq!(
+            object_id(&json!(["https://mastodon.example/notes/1"])),
+            None
+        );
+    }
+
+    #[test]
+    fn same_host_compares_the_hosts_only() {
+        assert!(same_host(
+            "https://mastodon.example/users/alice/statuses/1",
+            "https://Mastodon.example/users/alice"
+        ));
+        assert!(!same_host(
+            "https://evil.example/users/alice/statuses/1",
+            "https://mastodon.example/users/alice"
+        ));
+        assert!(!same_host("not a url", "not a url"));
+    }
+}
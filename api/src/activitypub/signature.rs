@@ -0,0 +1,244 @@
+//! HTTP Signatures (draft-cavage-http-signatures-12) with RSA SHA-256, which
+//! is what Mastodon and most of the fediverse sign their requests with
+
+use axum::http::{HeaderMap, Method};
+use base64::{Engine as _, engine::general_purpose::STANDARD};
+use eyre::{Context as _, OptionExt as _, eyre};
+use rsa::{
+    RsaPrivateKey, RsaPublicKey,
+    pkcs1::DecodeRsaPublicKey as _,
+    pkcs1v15::{Signature, SigningKey, VerifyingKey},
+    pkcs8::DecodePublicKey as _,
+    sha2::Sha256,
+    signature::{SignatureEncoding as _, Signer as _, Verifier as _},
+};
+use sha2::Digest as _;
+
+/// Requests dated further from now are rejected, so that a captured request
+/// can't be replayed much later
+const MAX_CLOCK_SKEW: chrono::TimeDelta = chrono::TimeDelta::hours(12);
+
+/// Signed headers of the outgoing requests, the digest only if there's a body
+const SIGNED_HEADERS: &[&str] = &["(request-target)", "host", "date"];
+const SIGNED_HEADERS_WITH_BODY: &[&str] = &["(request-target)", "host", "date", "digest"];
+
+/// The `Digest` header of a body
+pub fn digest(body: &[u8]) -> String {
+    format!("SHA-256={}", STANDARD.encode(sha2::Sha256::digest(body)))
+}
+
+/// The `Date` header of a request sent now
+pub fn http_date(now: chrono::DateTime<chrono::Utc>) -> String {
+    now.format("%a, %d %b %Y %H:%M:%S GMT").to_string()
+}
+
+/// The headers to add to a request to `url`, `Date`, `Digest` if there's a
+/// body, and `Signature`
+pub fn sign(
+    key: &RsaPrivateKey,
+    key_id: &str,
+    method: &Method,
+    url: &url::Url,
+    body: Option<&[u8]>,
+) -> Result<Vec<(&'static str, String)>, eyre::Error> {
+    let host = match url.port() {
+        Some(port) => format!("{}:{port}", url.host_str().ok_or_eyre("URL has no host")?),
+        None => url.host_str().ok_or_eyre("URL has no host")?.to_string(),
+    };
+    let mut headers = vec![("host", host), ("date", http_date(chrono::Utc::now()))];
+    if let Some(body) = body {
+        headers.push(("digest", digest(body)));
+    }
+
+    let target = match url.query() {
+        Some(query) => format!("{} {}?{query}", method.as_str().to_lowercase(), url.path()),
+        None => format!("{} {}", method.as_str().to_lowercase(), url.path()),
+    };
+    let signed = match body {
+        Some(_) => SIGNED_HEADERS_WITH_BODY,
+        None => SIGNED_HEADERS,
+    };
+    let signing_string = signing_string(signed, &target, |name| {
+        headers
+            .iter()
+            .find(|(header, _)| *header == name)
+            .map(|(_, value)| value.clone())
+    })?;
+
+    let signature = SigningKey::<Sha256>::new(key.clone()).sign(signing_string.as_bytes());
+    let signature = format!(
+        r#"keyId="{key_id}",algorithm="rsa-sha256",headers="{}",signature="{}""#,
+        signed.join(" "),
+        STANDARD.encode(signature.to_bytes())
+    );
+
+    // The host header is set by the client from the URL
+    headers.retain(|(name, _)| *name != "host");
+    headers.push(("signature", signature));
+    Ok(headers)
+}
+
+/// The parameters of a `Signature` header
+#[derive(Debug, PartialEq)]
+pub struct SignatureHeader {
+    pub key_id: String,
+    pub headers: Vec<String>,
+    pub signature: Vec<u8>,
+}
+
+impl SignatureHeader {
+    pub fn parse(value: &str) -> Result<Self, eyre::Error> {
+        let mut key_id = None;
+        let mut headers = None;
+        let mut signature = None;
+        for param in value.split(',') {
+            let Some((name, value)) = param.trim().split_once('=') else {
+                continue;
+            };
+            let value = value.trim_matches('"');
+            match name.trim() {
+                "keyId" => key_id = Some(value.to_string()),
+                "headers" => {
+                    headers = Some(value.split_whitespace().map(str::to_lowercase).collect());
+                }
+                "signature" => {
+                    signature = Some(STANDARD.decode(value).wrap_err("invalid signature")?);
+                }
+                // hs2019 leaves the algorithm to the key, which is RSA
+                "algorithm" if !matches!(value, "rsa-sha256" | "hs2019") => {
+                    return Err(eyre!("unsupported signature algorithm `{value}`"));
+                }
+                _ => {}
+            }
+        }
+
+        Ok(Self {
+            key_id: key_id.ok_or_eyre("signature has no keyId")?,
+            // Only the date is signed if the headers aren't listed
+            headers: headers.unwrap_or_else(|| vec!["date".to_string()]),
+            signature: signature.ok_or_eyre("signature has no value")?,
+        })
+    }
+}
+
+/// Verifies a request signed with [SignatureHeader::key_id]'s key,
+/// `request_target` is the method and the path the sender signed, which is
+/// the public path of the endpoint rather than the one behind the proxy
+pub fn verify(
+    header: &SignatureHeader,
+    public_key_pem: &str,
+    request_target: &str,
+    headers: &HeaderMap,
+    body: &[u8],
+) -> Result<(), eyre::Error> {
+    // The body must be signed, otherwise anything could be sent with the
+    // signed headers of another request
+    if !header.headers.iter().any(|h| h == "digest") {
+        return Err(eyre!("the digest is not signed"));
+    }
+    let digest_header = headers
+        .get("digest")
+        .and_then(|v| v.to_str().ok())
+        .ok_or_eyre("request has no digest")?;
+    let body_digest = digest(body);
+    if !digest_header
+        .split(',')
+        .any(|d| d.trim().eq_ignore_ascii_case(&body_digest))
+    {
+        return Err(eyre!("the digest doesn't match the body"));
+    }
+
+    let date = headers
+        .get("date")
+        .and_then(|v| v.to_str().ok())
+        .ok_or_eyre("request has no date")?;
+    let date = chrono::DateTime::parse_from_rfc2822(date).wrap_err("invalid date")?;
+    if (chrono::Utc::now() - date.to_utc()).abs() > MAX_CLOCK_SKEW {
+        return Err(eyre!("the request is too old or from the future"));
+    }
+
+    let signing_string = signing_string(&header.headers, request_target, |name| {
+        let values = headers
+            .get_all(name)
+            .iter()
+            .filter_map(|v| v.to_str().ok())
+            .collect::<Vec<_>>();
+        (!values.is_empty()).then(|| values.join(", "))
+    })?;
+
+    let public_key = RsaPublicKey::from_public_key_pem(public_key_pem)
+        .or_else(|_| RsaPublicKey::from_pkcs1_pem(public_key_pem))
+        .wrap_err("invalid public key")?;
+    let signature = Signature::try_from(header.signature.as_slice())?;
+    VerifyingKey::<Sha256>::new(public_key)
+        .verify(signing_string.as_bytes(), &signature)
+        .wrap_err("invalid signature")
+}
+
+/// The lines of `name: value` the signature is computed over, in the order of
+/// `names`
+fn signing_string<N: AsRef<str>>(
+    names: &[N],
+    request_target: &str,
+    value_of: impl Fn(&str) -> Option<String>,
+) -> Result<String, eyre::Error> {
+    names
+        .iter()
+        .map(|name| {
+            let name = name.as_ref();
+            let value = match name {
+                "(request-target)" => request_target.to_string(),
+                _ => value_of(name).ok_or_else(|| eyre!("signed header `{name}` is missing"))?,
+            };
+            Ok(format!("{name}: {value}"))
+        })
+        .collect::<Result<Vec<_>, eyre::Error>>()
+        .map(|lines| lines.join("\n"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_mastodon_signature_headers() {
+        let header = SignatureHeader::parse(
+            r#"keyId="https://mastodon.example/users/alice#main-key",algorithm="rsa-sha256",headers="(request-target) host date digest content-type",signature="aGVsbG8=""#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            header,
+            SignatureHeader {
+                key_id: "https://mastodon.example/users/alice#main-key".to_string(),
+                headers: ["(request-target)", "host", "date", "digest", "content-type"]
+                    .map(str::to_string)
+                    .to_vec(),
+                signature: b"hello".to_vec(),
+            }
+        );
+        assert!(
+            SignatureHeader::parse(r#"keyId="k",algorithm="hmac-sha256",signature="""#).is_err()
+        );
+    }
+
+    #[test]
+    fn signing_string_follows_the_signed_header_order() {
+        let value_of = |name: &str| match name {
+            "host" => Some("wrx.sh".to_string()),
+            "date" => Some("Tue, 07 Jun 2026 20:51:35 GMT".to_string()),
+            _ => None,
+        };
+
+        assert_eq!(
+            signing_string(
+                &["date", "(request-target)", "host"],
+                "post /api/ap/inbox",
+                value_of
+            )
+            .unwrap(),
+            "date: Tue, 07 Jun 2026 20:51:35 GMT\n(request-target): post /api/ap/inbox\nhost: wrx.sh"
+        );
+        assert!(signing_string(&["digest"], "post /api/ap/inbox", value_of).is_err());
+    }
+}
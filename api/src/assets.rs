@@ -0,0 +1,583 @@
+//! Photos and other files uploaded to the S3-compatible bucket, so that the
+//! blog posts and comments can reference hosted images. The files are private
+//! and served through short-lived signed URLs.
+
+use std::{io::Cursor, sync::Arc};
+
+use axum::{
+    Json, Router,
+    body::Bytes,
+    extract::{DefaultBodyLimit, Multipart, Path, Query, State, multipart::MultipartError},
+    http::{StatusCode, header},
+    response::{IntoResponse, Response},
+    routing::get,
+};
+use base64::{Engine as _, engine::general_purpose::URL_SAFE_NO_PAD};
+use diesel::prelude::*;
+use diesel_async::RunQueryDsl;
+use image::{ImageFormat, imageops::FilterType};
+use rand::TryRng as _;
+use serde::Deserialize;
+use utoipa::{IntoParams, OpenApi};
+
+use crate::{
+    App,
+    clients::ObjectStorage,
+    config::BodyLimits,
+    crypto::random,
+    error::AppError,
+    identity::AuthUser,
+    models::asset::{Asset as AssetRow, AssetVariant as VariantRow, NewAsset},
+    schema::{asset_variants, assets},
+};
+
+pub use api_models::{Asset, AssetVariant};
+
+/// Size of the parts of a multipart upload, the minimum S3 accepts
+const PART_SIZE: usize = 5 * 1024 * 1024;
+/// Widths of the resized copies, only the ones smaller than the image are made
+const VARIANT_WIDTHS: &[u32] = &[480, 1200];
+const MAX_FILENAME_CHARS: usize = 255;
+const DEFAULT_LIMIT: i64 = 50;
+const MAX_LIMIT: i64 = 100;
+
+#[derive(OpenApi)]
+#[openapi(paths(upload_asset, list_assets, get_asset))]
+pub struct ApiDoc;
+
+pub fn route(limits: &BodyLimits) -> Router<App> {
+    Router::<App>::new()
+        .route(
+            "/assets",
+            get(list_assets)
+                .post(upload_asset)
+                .layer(DefaultBodyLimit::max(limits.assets)),
+        )
+        .route("/assets/{id}", get(get_asset))
+}
+
+fn storage(ctx: &App) -> Result<&Arc<dyn ObjectStorage>, AppError> {
+    ctx.clients
+        .storage
+        .as_ref()
+        .ok_or(("Uploads are not available", StatusCode::SERVICE_UNAVAILABLE).into())
+}
+
+/// Kinds of files accepted, told by their content rather than by what the
+/// client claims so that nothing else can be hosted
+fn sniff_content_type(head: &[u8]) -> Option<&'static str> {
+    if head.starts_with(b"%PDF-") {
+        return Some("application/pdf");
+    }
+    match image::guess_format(head).ok()? {
+        format @ (ImageFormat::Jpeg
+        | ImageFormat::Png
+        | ImageFormat::WebP
+        | ImageFormat::Gif
+        | ImageFormat::Avif) => Some(format.to_mime_type()),
+        _ => None,
+    }
+}
+
+fn multipart_error(e: MultipartError) -> AppError {
+    (e.body_text(), e.status()).into()
+}
+
+/// Streams the `file` field of the form to the bucket, in parts once it's
+/// larger than [PART_SIZE]
+#[utoipa::path(
+    post,
+    path = "/assets",
+    tag = "assets",
+    request_body(content_type = "multipart/form-data", description = "The file in the `file` field"),
+    responses(
+        (status = 200, description = "The uploaded asset, the image variants are generated afterwards", body = Asset),
+        (status = 400, description = "No file or an empty one"),
+        (status = 401, description = "Not logged in"),
+        (status = 413, description = "The file is too large"),
+        (status = 415, description = "Not an image or a PDF"),
+        (status = 503, description = "Uploads aren't configured"),
+    ),
+    security(("session" = [])),
+)]
+async fn upload_asset(
+    State(ctx): State<App>,
+    AuthUser(i): AuthUser,
+    mut multipart: Multipart,
+) -> Result<Json<Asset>, AppError> {
+    let storage = storage(&ctx)?;
+
+    let mut field = loop {
+        match multipart.next_field().await.map_err(multipart_error)? {
+            Some(field) if field.name() == Some("file") => break field,
+            Some(_) => continue,
+            None => return Err(("The `file` field is missing", StatusCode::BAD_REQUEST).into()),
+        }
+    };
+    let filename = field
+        .file_name()
+        .map(sanitize_filename)
+        .filter(|name| !name.is_empty())
+        .unwrap_or("upload".to_string());
+
+    let mut buffer = Vec::new();
+    let mut content_type = None;
+    let mut size_bytes = 0usize;
+    let mut upload: Option<MultipartUpload> = None;
+    let key = generate_key()?;
+
+    // Matched in place, the error isn't Send and can't be held across the
+    // awaits below
+    let content_type = match async {
+        loop {
+            // Not a `while let`, its temporaries would live through the awaits
+            // of the body
+            let Some(chunk) = field.chunk().await.map_err(multipart_error)? else {
+                break;
+            };
+            size_bytes += chunk.len();
+            buffer.extend_from_slice(&chunk);
+
+            if content_type.is_none() && buffer.len() >= 16 {
+                content_type = Some(sniff_content_type(&buffer).ok_or((
+                    "Only images and PDFs can be uploaded",
+                    StatusCode::UNSUPPORTED_MEDIA_TYPE,
+                ))?);
+            }
+
+            if buffer.len() >= PART_SIZE {
+                let content_type = content_type.unwrap_or("application/octet-stream");
+                if upload.is_none() {
+                    upload = Some(MultipartUpload {
+                        id: storage.create_multipart_upload(&key, content_type).await?,
+                        parts: Vec::new(),
+                    });
+                }
+                if let Some(upload) = &mut upload {
+                    upload
+                        .send_part(storage.as_ref(), &key, std::mem::take(&mut buffer))
+                        .await?;
+                }
+            }
+        }
+
+        if size_bytes == 0 {
+            return Err(("The file is empty", StatusCode::BAD_REQUEST).into());
+        }
+        let content_type = match content_type {
+            Some(content_type) => content_type,
+            None => sniff_content_type(&buffer).ok_or((
+                "Only images and PDFs can be uploaded",
+                StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            ))?,
+        };
+
+        match &mut upload {
+            Some(upload) => {
+                if !buffer.is_empty() {
+                    upload
+                        .send_part(storage.as_ref(), &key, std::mem::take(&mut buffer))
+                        .await?;
+                }
+                storage
+                    .complete_multipart_upload(&key, &upload.id, &upload.parts)
+                    .await?;
+            }
+            None => {
+                storage
+                    .put(&key, content_type, Bytes::from(std::mem::take(&mut buffer)))
+                    .await?
+            }
+        }
+        Ok::<_, AppError>(content_type)
+    }
+    .await
+    {
+        Ok(content_type) => content_type,
+        Err(e) => {
+            // Otherwise the bucket keeps the parts, and bills them, indefinitely
+            if let Some(upload) = upload {
+                let storage = storage.clone();
+                tokio::spawn(async move {
+                    let _ = storage
+                        .abort_multipart_upload(&key, &upload.id)
+                        .await
+                        .inspect_err(|e| tracing::error!(?e, key, "Failed to abort the upload"));
+                });
+            }
+            return Err(e);
+        }
+    };
+
+    let mut conn = ctx.diesel.get().await?;
+
+    let asset = diesel::insert_into(assets::table)
+        .values(&NewAsset {
+            key,
+            identity_id: i.id,
+            filename,
+            content_type: content_type.to_string(),
+            size_bytes: i64::try_from(size_bytes).unwrap_or(i64::MAX),
+        })
+        .returning(AssetRow::as_returning())
+        .get_result(&mut conn)
+        .await?;
+
+    if let Some(format) = resizable_format(&asset.content_type) {
+        spawn_variants(ctx.clone(), asset.clone(), format);
+    }
+
+    Ok(Json(to_response(
+        storage.as_ref(),
+        &ctx,
+        asset,
+        Vec::new(),
+    )?))
+}
+
+struct MultipartUpload {
+    id: String,
+    /// Part numbers and ETags
+    parts: Vec<(u32, String)>,
+}
+
+impl MultipartUpload {
+    async fn send_part(
+        &mut self,
+        storage: &dyn ObjectStorage,
+        key: &str,
+        body: Vec<u8>,
+    ) -> Result<(), eyre::Error> {
+        let number = u32::try_from(self.parts.len() + 1)?;
+        let etag = storage
+            .upload_part(key, &self.id, number, Bytes::from(body))
+            .await?;
+        self.parts.push((number, etag));
+        Ok(())
+    }
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ListQueries {
+    /// Assets returned, 50 by default and 100 at most
+    limit: Option<i64>,
+    /// Only the assets uploaded before the one with this ID, to page through
+    before: Option<i32>,
+}
+
+/// The assets the caller uploaded, newest first
+#[utoipa::path(
+    get,
+    path = "/assets",
+    tag = "assets",
+    params(ListQueries),
+    responses(
+        (status = 200, description = "The caller's assets with their signed URLs", body = [Asset]),
+        (status = 401, description = "Not logged in"),
+    ),
+    security(("session" = [])),
+)]
+async fn list_assets(
+    State(ctx): State<App>,
+    AuthUser(i): AuthUser,
+    Query(query): Query<ListQueries>,
+) -> Result<Json<Vec<Asset>>, AppError> {
+    let storage = storage(&ctx)?;
+    let limit = query.limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT);
+
+    let mut conn = ctx.diesel.get().await?;
+
+    let mut select = assets::table
+        .filter(assets::identity_id.eq(i.id))
+        .order(assets::id.desc())
+        .limit(limit)
+        .select(AssetRow::as_select())
+        .into_boxed();
+    if let Some(before) = query.before {
+        select = select.filter(assets::id.lt(before));
+    }
+    let rows = select.load(&mut conn).await?;
+
+    let mut variants = asset_variants::table
+        .filter(asset_variants::asset_id.eq_any(rows.iter().map(|a| a.id)))
+        .order(asset_variants::width)
+        .select(VariantRow::as_select())
+        .load(&mut conn)
+        .await?;
+
+    let assets = rows
+        .into_iter()
+        .map(|asset| {
+            let (own, rest) = variants.drain(..).partition(|v| v.asset_id == asset.id);
+            variants = rest;
+            to_response(storage.as_ref(), &ctx, asset, own)
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(Json(assets))
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct GetQueries {
+    /// A resized copy such as `w480`, the original if absent
+    variant: Option<String>,
+}
+
+/// A stable URL of the asset to reference in posts and comments, redirecting
+/// to a freshly signed one
+#[utoipa::path(
+    get,
+    path = "/assets/{id}",
+    tag = "assets",
+    params(("id" = i32, Path, description = "ID of the asset"), GetQueries),
+    responses(
+        (status = 302, description = "Redirects to the signed URL of the file"),
+        (status = 404, description = "No such asset or variant"),
+    ),
+)]
+async fn get_asset(
+    State(ctx): State<App>,
+    Path(id): Path<i32>,
+    Query(query): Query<GetQueries>,
+) -> Result<Response, AppError> {
+    let storage = storage(&ctx)?;
+
+    let mut conn = ctx.diesel.get().await?;
+
+    let key = match &query.variant {
+        None => assets::table
+            .filter(assets::id.eq(id))
+            .select(assets::key)
+            .first::<String>(&mut conn)
+            .await
+            .optional()?,
+        Some(variant) => asset_variants::table
+            .filter(asset_variants::asset_id.eq(id))
+            .filter(asset_variants::name.eq(variant))
+            .select(asset_variants::key)
+            .first::<String>(&mut conn)
+            .await
+            .optional()?,
+    }
+    .ok_or(("Asset not found", StatusCode::NOT_FOUND))?;
+
+    let expiry = url_expiry(&ctx);
+    let url = storage.presigned_get(&key, expiry)?;
+
+    Ok((
+        StatusCode::FOUND,
+        [
+            (header::LOCATION, url),
+            // Cached for half the validity so that a cached redirect never
+            // points to an expired URL
+            (
+                header::CACHE_CONTROL,
+                format!("private, max-age={}", expiry.as_secs() / 2),
+            ),
+        ],
+    )
+        .into_response())
+}
+
+fn url_expiry(ctx: &App) -> std::time::Duration {
+    ctx.config
+        .asset_storage
+        .as_ref()
+        .map(|s| s.url_expiry)
+        .unwrap_or(std::time::Duration::from_hours(1))
+}
+
+fn to_response(
+    storage: &dyn ObjectStorage,
+    ctx: &App,
+    asset: AssetRow,
+    variants: Vec<VariantRow>,
+) -> Result<Asset, eyre::Error> {
+    let expiry = url_expiry(ctx);
+    Ok(Asset {
+        id: asset.id,
+        url: storage.presigned_get(&asset.key, expiry)?,
+        variants: variants
+            .into_iter()
+            .map(|v| {
+                Ok(AssetVariant {
+                    url: storage.presigned_get(&v.key, expiry)?,
+                    name: v.name,
+                    width: v.width,
+                    height: v.height,
+                })
+            })
+            .collect::<Result<_, eyre::Error>>()?,
+        filename: asset.filename,
+        content_type: asset.content_type,
+        size_bytes: asset.size_bytes,
+        width: asset.width,
+        height: asset.height,
+        created_at: asset.created_at,
+    })
+}
+
+/// The images resized as themselves, animated GIFs would lose their frames
+fn resizable_format(content_type: &str) -> Option<ImageFormat> {
+    match content_type {
+        "image/jpeg" => Some(ImageFormat::Jpeg),
+        "image/png" => Some(ImageFormat::Png),
+        "image/webp" => Some(ImageFormat::WebP),
+        _ => None,
+    }
+}
+
+fn spawn_variants(ctx: App, asset: AssetRow, format: ImageFormat) {
+    tokio::spawn(async move {
+        if let Err(e) = generate_variants(&ctx, &asset, format).await {
+            tracing::error!(?e, asset.id, "Failed to generate the asset variants");
+        }
+    });
+}
+
+struct Resized {
+    width: u32,
+    height: u32,
+    encoded: Vec<u8>,
+}
+
+/// Records the dimensions of the image and stores its smaller copies
+async fn generate_variants(
+    ctx: &App,
+    asset: &AssetRow,
+    format: ImageFormat,
+) -> Result<(), eyre::Error> {
+    let storage = ctx
+        .clients
+        .storage
+        .as_ref()
+        .ok_or_else(|| eyre::eyre!("uploads are not configured"))?;
+    let original = storage.get(&asset.key).await?;
+
+    let (width, height, resized) =
+        tokio::task::spawn_blocking(move || resize(&original, format)).await??;
+
+    let mut conn = ctx.diesel.get().await?;
+
+    diesel::update(assets::table.filter(assets::id.eq(asset.id)))
+        .set((
+            assets::width.eq(i32::try_from(width)?),
+            assets::height.eq(i32::try_from(height)?),
+        ))
+        .execute(&mut conn)
+        .await?;
+
+    let output = output_format(format);
+    for variant in resized {
+        let name = format!("w{}", variant.width);
+        let key = format!("{}/{name}.{}", asset.key, output.extensions_str()[0]);
+        storage
+            .put(&key, output.to_mime_type(), Bytes::from(variant.encoded))
+            .await?;
+
+        diesel::insert_into(asset_variants::table)
+            .values(&VariantRow {
+                asset_id: asset.id,
+                name,
+                key,
+                width: i32::try_from(variant.width)?,
+                height: i32::try_from(variant.height)?,
+            })
+            .on_conflict_do_nothing()
+            .execute(&mut conn)
+            .await?;
+    }
+
+    Ok(())
+}
+
+/// The `image` crate only encodes lossless WebP, larger than PNG
+fn output_format(format: ImageFormat) -> ImageFormat {
+    match format {
+        ImageFormat::Jpeg => ImageFormat::Jpeg,
+        _ => ImageFormat::Png,
+    }
+}
+
+/// The dimensions of the image and its copies at each of [VARIANT_WIDTHS]
+/// narrower than it
+fn resize(original: &[u8], format: ImageFormat) -> Result<(u32, u32, Vec<Resized>), eyre::Error> {
+    let image = image::load_from_memory_with_format(original, format)?;
+    let (width, height) = (image.width(), image.height());
+
+    let resized = VARIANT_WIDTHS
+        .iter()
+        .filter(|w| **w < width)
+        .map(|&w| {
+            let h = (u64::from(height) * u64::from(w) / u64::from(width)).max(1);
+            let copy = image.resize_exact(w, u32::try_from(h)?, FilterType::Lanczos3);
+            let mut encoded = Cursor::new(Vec::new());
+            // JPEG has no alpha channel
+            match output_format(format) {
+                ImageFormat::Jpeg => copy.into_rgb8().write_to(&mut encoded, ImageFormat::Jpeg)?,
+                output => copy.write_to(&mut encoded, output)?,
+            }
+            Ok(Resized {
+                width: w,
+                height: u32::try_from(h)?,
+                encoded: encoded.into_inner(),
+            })
+        })
+        .collect::<Result<Vec<_>, eyre::Error>>()?;
+
+    Ok((width, height, resized))
+}
+
+/// The name shown in the listing, path separators and control characters are
+/// dropped
+fn sanitize_filename(name: &str) -> String {
+    let name = name.rsplit(['/', '\\']).next().unwrap_or(name);
+    name.chars()
+        .filter(|c| !c.is_control())
+        .take(MAX_FILENAME_CHARS)
+        .collect::<String>()
+        .trim()
+        .to_string()
+}
+
+/// A random key, the bucket is private so it only needs to be unique
+fn generate_key() -> Result<String, eyre::Error> {
+    let mut bytes = [0u8; 16];
+    random::get_rng()
+        .try_fill_bytes(&mut bytes)
+        .map_err(|_| eyre::eyre!("could not generate asset key"))?;
+    Ok(format!("assets/{}", URL_SAFE_NO_PAD.encode(bytes)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn uploads_are_sniffed_and_resized() {
+        let mut png = Cursor::new(Vec::new());
+        image::RgbaImage::new(1600, 900)
+            .write_to(&mut png, ImageFormat::Png)
+            .expect("encodable");
+        let png = png.into_inner();
+
+        assert_eq!(sniff_content_type(&png), Some("image/png"));
+        assert_eq!(sniff_content_type(b"%PDF-1.7\n"), Some("application/pdf"));
+        assert_eq!(sniff_content_type(b"<html><script>"), None);
+
+        let (width, height, resized) = resize(&png, ImageFormat::Png).expect("resizable");
+        assert_eq!((width, height), (1600, 900));
+        assert_eq!(
+            resized
+                .iter()
+                .map(|r| (r.width, r.height))
+                .collect::<Vec<_>>(),
+            vec![(480, 270), (1200, 675)]
+        );
+
+        assert_eq!(sanitize_filename("../../etc/passwd"), "passwd");
+        assert_eq!(sanitize_filename("C:\\photos\\cat.jpg"), "cat.jpg");
+    }
+}
@@ -1,3 +1,59 @@
 mod comment;
+pub mod lookup;
+pub mod meta;
 pub mod models;
+pub mod notify;
+pub mod og_image;
+pub mod related;
 pub mod routes;
+
+use diesel::prelude::*;
+use diesel_async::{AsyncPgConnection, RunQueryDsl};
+
+use crate::schema::blog_posts;
+
+pub use comment::{fediverse, report::rate_limiter as report_rate_limiter, scrub::CommentScrubber};
+
+/// Public URL of the post on the website
+pub fn post_url(site_url: &str, slug: &str) -> String {
+    format!("{}/blog/{slug}", site_url.trim_end_matches('/'))
+}
+
+/// Registers the post if it isn't yet, returns its ID and whether this call
+/// registered it
+pub async fn register_post(
+    conn: &mut AsyncPgConnection,
+    slug: &str,
+) -> Result<(i32, bool), diesel::result::Error> {
+    let filter = blog_posts::category
+        .eq("blog")
+        .and(blog_posts::slug.eq(slug));
+
+    if let Some(id) = blog_posts::table
+        .filter(filter)
+        .select(blog_posts::id)
+        .first::<i32>(conn)
+        .await
+        .optional()?
+    {
+        return Ok((id, false));
+    }
+
+    let inserted = diesel::insert_into(blog_posts::table)
+        .values(&models::NewBlogPost {
+            category: "blog".to_string(),
+            slug: slug.to_string(),
+            title: None,
+        })
+        .on_conflict((blog_posts::category, blog_posts::slug))
+        .do_nothing()
+        .execute(conn)
+        .await?;
+
+    let id = blog_posts::table
+        .filter(filter)
+        .select(blog_posts::id)
+        .first(conn)
+        .await?;
+    Ok((id, inserted > 0))
+}
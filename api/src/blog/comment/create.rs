@@ -1,68 +1,67 @@
 use axum::{
     Json, debug_handler,
     extract::{Path, State},
+    http::StatusCode,
 };
+use axum_extra::extract::CookieJar;
 use diesel::prelude::*;
 use diesel_async::RunQueryDsl;
 use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
 
 use crate::{
     App,
-    blog::models::{NewBlogComment, NewBlogPost},
+    blog::{self, meta, models::NewBlogComment, notify::NewCommentNotification},
     error::AppError,
-    identity::{AuthUser, models::identity::Traits},
+    identity::{MaybeAuthUser, models::identity::Traits},
     real_ip::ClientIp,
-    schema::{blog_comments, blog_posts, identities},
+    schema::{blog_comments, identities},
 };
 
-use crate::blog::comment::Comment;
-
+use crate::blog::comment::{Comment, CommentSource, mention, ownership};
+
+const MAX_AUTHOR_NAME_CHARS: usize = 50;
+
+#[utoipa::path(
+    post,
+    path = "/{slug}/comments",
+    tag = "blog",
+    params(("slug" = String, Path, description = "Slug of the blog post")),
+    request_body = CommentSubmission,
+    responses(
+        (status = 200, description = "The created comment, anonymous commenters get a cookie to edit or delete it for a while", body = Comment),
+        (status = 400, description = "Empty or too long content"),
+        (status = 401, description = "Not logged in and anonymous comments are disabled"),
+    ),
+    security(("session" = [])),
+)]
 #[debug_handler]
 pub async fn create_comment(
     State(ctx): State<App>,
     Path(slug): Path<String>,
-    ClientIp(ip): ClientIp,
-    AuthUser(auth_user): AuthUser,
+    client_ip: ClientIp,
+    MaybeAuthUser(auth_user): MaybeAuthUser,
     crate::json::Json(mut comment): crate::json::Json<CommentSubmission>,
-) -> Result<Json<Comment>, AppError> {
+) -> Result<(CookieJar, Json<Comment>), AppError> {
     comment
         .validate()
-        .map_err(|e| (e, axum::http::StatusCode::BAD_REQUEST))?;
+        .map_err(|e| (e, StatusCode::BAD_REQUEST))?;
+
+    // Readers may comment without an account only if it's enabled, in which
+    // case the browser proves ownership of the comment with a signed cookie
+    let auth_user = match (auth_user, &ctx.config.anonymous_comments) {
+        (Ok(identity), _) => Some(identity),
+        (Err(_), Some(_)) => None,
+        (Err(e), None) => return Err(e.into()),
+    };
 
     let mut conn = ctx.diesel.get().await?;
 
-    // check if the post exists, otherwise create it
-    let post_exists = blog_posts::table
-        .filter(blog_posts::category.eq("blog"))
-        .filter(blog_posts::slug.eq(&slug))
-        .select(blog_posts::id)
-        .first::<i32>(&mut conn)
-        .await
-        .optional()?;
-
-    let post_id = if let Some(id) = post_exists {
-        id
-    } else {
-        let new_post = NewBlogPost {
-            category: "blog".to_string(),
-            slug: slug.clone(),
-            title: None,
-        };
-
-        diesel::insert_into(blog_posts::table)
-            .values(&new_post)
-            .on_conflict((blog_posts::category, blog_posts::slug))
-            .do_nothing()
-            .execute(&mut conn)
-            .await?;
-
-        blog_posts::table
-            .filter(blog_posts::category.eq("blog"))
-            .filter(blog_posts::slug.eq(&slug))
-            .select(blog_posts::id)
-            .first(&mut conn)
-            .await?
-    };
+    let (post_id, registered) = blog::register_post(&mut conn, &slug).await?;
+    // Only the request that registered the post generates its meta
+    if registered {
+        meta::spawn_generate(ctx.clone(), slug.clone());
+    }
 
     // check if the parent comment actually belongs to the post
     if let Some(parent_id) = comment.parent_id {
@@ -79,16 +78,25 @@ pub async fn create_comment(
         }
     }
 
+    let geo = client_ip.geo(&ctx);
+
     let new_comment = NewBlogComment {
-        author_ip: ip.to_string(),
-        // Name comes from the linked identity's traits at read time; no
-        // per-comment override is accepted now that auth is required.
-        author_name: None,
+        author_ip: client_ip.0.to_string(),
+        // Name comes from the linked identity's traits at read time, the
+        // submitted one is only used for anonymous comments
+        author_name: match auth_user {
+            Some(_) => None,
+            None => comment.author_name.clone(),
+        },
         author_email: None,
-        identity_id: Some(auth_user.id),
+        identity_id: auth_user.as_ref().map(|identity| identity.id),
         content: comment.content.clone(),
         post_id,
         parent_id: comment.parent_id,
+        author_country: geo.as_ref().and_then(|g| g.country.clone()),
+        author_asn: geo.and_then(|g| g.asn).map(i64::from),
+        source: CommentSource::Site.as_str().to_string(),
+        source_id: None,
     };
 
     let resulting_comment = diesel::insert_into(blog_comments::table)
@@ -102,36 +110,79 @@ pub async fn create_comment(
         .get_result::<(i32, String, Option<i32>, chrono::NaiveDateTime)>(&mut conn)
         .await?;
 
-    let identity_traits = identities::table
-        .filter(identities::id.eq(auth_user.id))
-        .select(identities::traits)
-        .first::<serde_json::Value>(&mut conn)
-        .await
-        .optional()?;
-
-    let author_name = identity_traits
-        .and_then(|traits| serde_json::from_value::<Traits>(traits).ok())
-        .and_then(|t| t.name)
-        .unwrap_or_else(|| {
-            tracing::error!("No name in traits for identity ID `{}`", auth_user.id);
-            "No name".into()
+    mention::save_mentions(&mut conn, resulting_comment.0, &resulting_comment.1).await?;
+
+    let author_name = match &auth_user {
+        Some(identity) => {
+            let identity_traits = identities::table
+                .filter(identities::id.eq(identity.id))
+                .select(identities::traits)
+                .first::<serde_json::Value>(&mut conn)
+                .await
+                .optional()?;
+
+            identity_traits
+                .and_then(|traits| serde_json::from_value::<Traits>(traits).ok())
+                .and_then(|t| t.name)
+                .unwrap_or_else(|| {
+                    tracing::error!("No name in traits for identity ID `{}`", identity.id);
+                    "No name".into()
+                })
+        }
+        None => new_comment
+            .author_name
+            .clone()
+            .unwrap_or_else(|| "Anonymous".to_string()),
+    };
+
+    let jar = match (&auth_user, &ctx.config.anonymous_comments) {
+        (None, Some(config)) => {
+            CookieJar::new().add(ownership::issue(config, resulting_comment.0)?)
+        }
+        _ => CookieJar::new(),
+    };
+
+    // Not waited for so that a slow notification doesn't delay the response
+    if let Some(notifier) = ctx.comment_notifier.clone()
+        && auth_user.is_none_or(|identity| identity.id != ctx.config.owner_identity_id)
+    {
+        let notification = NewCommentNotification {
+            comment_id: resulting_comment.0,
+            slug,
+            author_name: author_name.clone(),
+            content: resulting_comment.1.clone(),
+            is_reply: resulting_comment.2.is_some(),
+            author_country: new_comment.author_country,
+            author_asn: new_comment.author_asn,
+        };
+        tokio::spawn(async move {
+            let _ = notifier
+                .new_comment(&notification)
+                .await
+                .inspect_err(|e| tracing::error!(?e, "Failed to send comment notification"));
         });
+    }
 
-    Ok(Json(Comment {
-        id: resulting_comment.0,
-        author_name,
-        content: resulting_comment.1,
-        parent_id: resulting_comment.2,
-        created_at: resulting_comment.3,
-        votes: 0,
-        depth: -1,
-    }))
+    Ok((
+        jar,
+        Json(Comment {
+            id: resulting_comment.0,
+            author_name,
+            content: resulting_comment.1,
+            parent_id: resulting_comment.2,
+            created_at: resulting_comment.3,
+            votes: 0,
+            depth: -1,
+        }),
+    ))
 }
 
-#[derive(Deserialize, Serialize)]
+#[derive(Deserialize, Serialize, ToSchema)]
 pub struct CommentSubmission {
     content: String,
     parent_id: Option<i32>,
+    /// Shown on anonymous comments, ignored when logged in
+    author_name: Option<String>,
 }
 
 impl CommentSubmission {
@@ -145,6 +196,20 @@ impl CommentSubmission {
             return Err("No content provided");
         }
 
+        self.author_name = self
+            .author_name
+            .as_deref()
+            .map(str::trim)
+            .filter(|name| !name.is_empty())
+            .map(str::to_string);
+        if self
+            .author_name
+            .as_ref()
+            .is_some_and(|name| name.chars().count() > MAX_AUTHOR_NAME_CHARS)
+        {
+            return Err("Name too long (max 50 characters)");
+        }
+
         Ok(())
     }
 }
@@ -3,28 +3,41 @@ use axum::{
     extract::{Path, State},
     http::StatusCode,
 };
+use axum_extra::extract::CookieJar;
 use diesel::prelude::*;
 use diesel_async::RunQueryDsl;
 
-use crate::{App, error::AppError, identity::AuthUser, schema::blog_comments};
+use crate::{
+    App, blog::comment::ownership, error::AppError, identity::MaybeAuthUser, schema::blog_comments,
+};
 
+#[utoipa::path(
+    delete,
+    path = "/{slug}/comments/{id}",
+    tag = "blog",
+    params(
+        ("slug" = String, Path, description = "Slug of the blog post"),
+        ("id" = i32, Path, description = "ID of the comment"),
+    ),
+    responses(
+        (status = 200, description = "The comment was deleted"),
+        (status = 401, description = "Not logged in, nor the anonymous author of the comment"),
+        (status = 403, description = "Not the author of the comment"),
+    ),
+    security(("session" = [])),
+)]
 #[debug_handler]
 pub async fn delete_comment(
     State(ctx): State<App>,
     Path((_slug, id)): Path<(String, i32)>,
-    AuthUser(auth_user): AuthUser,
+    MaybeAuthUser(auth_user): MaybeAuthUser,
+    jar: CookieJar,
 ) -> Result<(), AppError> {
     let mut conn = ctx.diesel.get().await?;
 
-    let is_owner = blog_comments::table
-        .filter(blog_comments::id.eq(id))
-        .filter(blog_comments::identity_id.eq(auth_user.id))
-        .select(blog_comments::id)
-        .first::<i32>(&mut conn)
-        .await
-        .optional()?;
-
-    if is_owner.is_none() {
+    if !ownership::is_owner(&ctx, &mut conn, &jar, auth_user.as_ref().ok(), id).await? {
+        // Not logged in and without the token of an anonymous comment
+        auth_user?;
         return Err((
             "You are not the owner of this comment",
             StatusCode::FORBIDDEN,
@@ -0,0 +1,159 @@
+//! Replies from the fediverse to the federated posts, stored as comments of
+//! the post so that they show up with the others
+
+use std::sync::LazyLock;
+
+use diesel::prelude::*;
+use diesel_async::{AsyncPgConnection, RunQueryDsl};
+use eyre::OptionExt as _;
+use regex::Regex;
+
+use crate::{
+    App,
+    blog::{models::NewBlogComment, notify::NewCommentNotification},
+    real_ip::ClientIp,
+    schema::blog_comments,
+};
+
+use super::{CommentSource, mention};
+
+/// The same limit as the comments written on the website
+const MAX_CONTENT_CHARS: usize = 5000;
+const MAX_AUTHOR_NAME_CHARS: usize = 50;
+
+/// The mentions a reply starts with, e.g. `[@blog](https://wrx.sh/blog)`,
+/// which the fediverse adds to address the reply
+static LEADING_MENTIONS: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"^(\s*\[@[^\]]*\]\([^)]*\))+\s*").expect("leading mentions regex is valid")
+});
+
+pub struct FediverseReply {
+    /// ID of the ActivityPub object, a reply is only stored once
+    pub object_id: String,
+    pub post_id: i32,
+    pub slug: String,
+    /// The comment of another reply it replies to
+    pub parent_id: Option<i32>,
+    pub author_name: String,
+    /// As sent by the author's instance
+    pub html: String,
+}
+
+/// Stores the reply, returns the ID of its comment or `None` if it's empty or
+/// was stored already. `client_ip` is the instance that delivered it.
+pub async fn create(
+    ctx: &App,
+    client_ip: &ClientIp,
+    reply: FediverseReply,
+) -> Result<Option<i32>, eyre::Error> {
+    let content = to_markdown(&reply.html)?;
+    if content.is_empty() {
+        return Ok(None);
+    }
+
+    let geo = client_ip.geo(ctx);
+    let new_comment = NewBlogComment {
+        author_ip: client_ip.0.to_string(),
+        author_name: Some(
+            reply
+                .author_name
+                .chars()
+                .take(MAX_AUTHOR_NAME_CHARS)
+                .collect(),
+        ),
+        author_email: None,
+        identity_id: None,
+        content,
+        post_id: reply.post_id,
+        parent_id: reply.parent_id,
+        author_country: geo.as_ref().and_then(|g| g.country.clone()),
+        author_asn: geo.and_then(|g| g.asn).map(i64::from),
+        source: CommentSource::Fediverse.as_str().to_string(),
+        source_id: Some(reply.object_id),
+    };
+
+    let mut conn = ctx.diesel.get().await?;
+    let Some(id) = diesel::insert_into(blog_comments::table)
+        .values(&new_comment)
+        .on_conflict(blog_comments::source_id)
+        .do_nothing()
+        .returning(blog_comments::id)
+        .get_result::<i32>(&mut conn)
+        .await
+        .optional()?
+    else {
+        return Ok(None);
+    };
+
+    mention::save_mentions(&mut conn, id, &new_comment.content).await?;
+
+    if let Some(notifier) = ctx.comment_notifier.clone() {
+        let notification = NewCommentNotification {
+            comment_id: id,
+            slug: reply.slug,
+            author_name: new_comment.author_name.unwrap_or_default(),
+            content: new_comment.content,
+            is_reply: new_comment.parent_id.is_some(),
+            author_country: new_comment.author_country,
+            author_asn: new_comment.author_asn,
+        };
+        tokio::spawn(async move {
+            let _ = notifier
+                .new_comment(&notification)
+                .await
+                .inspect_err(|e| tracing::error!(?e, "Failed to send comment notification"));
+        });
+    }
+
+    Ok(Some(id))
+}
+
+/// Deletes the comment of a reply that was deleted on the fediverse
+pub async fn delete(
+    conn: &mut AsyncPgConnection,
+    object_id: &str,
+) -> Result<usize, diesel::result::Error> {
+    diesel::delete(
+        blog_comments::table
+            .filter(blog_comments::source.eq(CommentSource::Fediverse.as_str()))
+            .filter(blog_comments::source_id.eq(object_id)),
+    )
+    .execute(conn)
+    .await
+}
+
+/// The reply as the Markdown the comments are written in, without the
+/// mentions it starts with
+fn to_markdown(html: &str) -> Result<String, eyre::Error> {
+    let markdown = html_to_markdown_rs::convert(html, None)?
+        .content
+        .ok_or_eyre("html to markdown conversion produced no content")?;
+
+    Ok(LEADING_MENTIONS
+        .replace(&markdown, "")
+        .trim()
+        .chars()
+        .take(MAX_CONTENT_CHARS)
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn leading_mentions_are_stripped() {
+        let strip = |markdown: &str| LEADING_MENTIONS.replace(markdown, "").into_owned();
+
+        assert_eq!(
+            strip(
+                "[@blog](https://wrx.sh/blog) [@alice](https://mastodon.example/@alice) Great post!"
+            ),
+            "Great post!"
+        );
+        assert_eq!(
+            strip("Thanks [@blog](https://wrx.sh/blog)!"),
+            "Thanks [@blog](https://wrx.sh/blog)!"
+        );
+    }
+}
@@ -3,25 +3,59 @@ use std::collections::HashMap;
 use axum::{
     Json,
     extract::{Path, Query, State},
+    http::{StatusCode, header},
+    response::IntoResponse,
 };
+use axum_extra::extract::CookieJar;
 use chrono::NaiveDateTime;
 use diesel::prelude::*;
 use diesel::sql_types::*;
-use diesel_async::RunQueryDsl;
+use diesel_async::{AsyncPgConnection, RunQueryDsl};
 use serde::Deserialize;
+use utoipa::{IntoParams, ToSchema};
 
-use crate::{App, error::AppError, identity::MaybeAuthUser};
+use crate::{
+    App,
+    error::AppError,
+    geoip::GeoInfo,
+    identity::{
+        self, AuthenticationError, MaybeAuthUser, models::identity::Identity, roles::CommentRole,
+    },
+    schema::blog_comments,
+};
+
+use super::{CommentSource, CommentTree, mention, ownership};
 
-use super::CommentTree;
+/// Replies scoring at most this are collapsed by default
+const COLLAPSE_SCORE: i64 = -3;
+/// Replies without a positive score older than this are collapsed by default
+const COLLAPSE_AGE_DAYS: i64 = 365;
 
-#[derive(Deserialize)]
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
 pub struct Queries {
     page_offset: usize,
     page_size: usize,
     sort: Option<SortType>,
+    /// Replies nested deeper are flattened into their ancestor at this depth
+    max_depth: Option<usize>,
+    /// Replies returned per comment, the top ones by the sort type
+    children_limit: Option<usize>,
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ThreadQueries {
+    sort: Option<SortType>,
+    /// Replies nested deeper are flattened into their ancestor at this depth,
+    /// relative to the thread
+    max_depth: Option<usize>,
+    /// Replies returned per comment, the top ones by the sort type
+    children_limit: Option<usize>,
 }
 
-#[derive(PartialEq)]
+#[derive(PartialEq, ToSchema)]
+#[schema(rename_all = "snake_case")]
 enum SortType {
     Best,
     New,
@@ -58,41 +92,154 @@ struct CommentQueryResult {
     votes: Option<i64>,
     #[diesel(sql_type = Nullable<Integer>)]
     depth: Option<i32>,
+    #[diesel(sql_type = Nullable<Text>)]
+    author_country: Option<String>,
+    #[diesel(sql_type = Nullable<BigInt>)]
+    author_asn: Option<i64>,
+    #[diesel(sql_type = Nullable<BigInt>)]
+    remaining_children: Option<i64>,
+    #[diesel(sql_type = Nullable<Text>)]
+    source: Option<String>,
 }
 
+#[utoipa::path(
+    get,
+    path = "/{slug}/comments",
+    tag = "blog",
+    params(("slug" = String, Path, description = "Slug of the blog post"), Queries),
+    responses(
+        (status = 200, description = "Top level comments with their replies", body = Vec<CommentTree>),
+    ),
+)]
 pub async fn get_comments(
     State(ctx): State<App>,
     Path(slug): Path<String>,
     q: Query<Queries>,
     MaybeAuthUser(auth_user): MaybeAuthUser,
-) -> Result<Json<Vec<CommentTree>>, AppError> {
+    jar: CookieJar,
+) -> Result<impl IntoResponse, AppError> {
     let sort = q.sort.as_ref().unwrap_or(&SortType::Best);
 
     let mut conn = ctx.diesel.get().await?;
 
-    // Determine the ORDER BY clause based on sort type
+    let rows = load_rows(
+        &mut conn,
+        &slug,
+        Roots::Page {
+            offset: q.page_offset,
+            size: q.page_size,
+        },
+        sort,
+        q.children_limit,
+    )
+    .await?;
+
+    let mut result = into_trees(&ctx, &mut conn, rows, &auth_user, &jar, sort).await?;
+    if let Some(max_depth) = q.max_depth {
+        flatten_deep_replies(&mut result, max_depth);
+    }
+
+    // Streamed since a page of large threads can be a few megabytes
+    Ok((
+        [(header::CONTENT_TYPE, "application/json")],
+        crate::json::array_body("[", result, "]"),
+    ))
+}
+
+#[utoipa::path(
+    get,
+    path = "/{slug}/comments/{id}/thread",
+    tag = "blog",
+    params(
+        ("slug" = String, Path, description = "Slug of the blog post"),
+        ("id" = i32, Path, description = "ID of the comment the thread starts at"),
+        ThreadQueries,
+    ),
+    responses(
+        (status = 200, description = "The comment with its replies, depths are relative to it", body = CommentTree),
+        (status = 404, description = "No such comment on the post"),
+    ),
+)]
+pub async fn get_thread(
+    State(ctx): State<App>,
+    Path((slug, id)): Path<(String, i32)>,
+    q: Query<ThreadQueries>,
+    MaybeAuthUser(auth_user): MaybeAuthUser,
+    jar: CookieJar,
+) -> Result<Json<CommentTree>, AppError> {
+    let sort = q.sort.as_ref().unwrap_or(&SortType::Best);
+
+    let mut conn = ctx.diesel.get().await?;
+
+    let rows = load_rows(&mut conn, &slug, Roots::Thread(id), sort, q.children_limit).await?;
+    let mut result = into_trees(&ctx, &mut conn, rows, &auth_user, &jar, sort).await?;
+    if let Some(max_depth) = q.max_depth {
+        flatten_deep_replies(&mut result, max_depth);
+    }
+
+    let mut thread = result
+        .into_iter()
+        .next()
+        .ok_or(("Comment not found", StatusCode::NOT_FOUND))?;
+
+    // The query starts the thread at its root, which has no parent there
+    thread.parent_id = blog_comments::table
+        .filter(blog_comments::id.eq(id))
+        .select(blog_comments::parent_id)
+        .first::<Option<i32>>(&mut conn)
+        .await?;
+
+    Ok(Json(thread))
+}
+
+/// Comments the recursive query starts from
+enum Roots {
+    /// A page of the top level comments of the post
+    Page { offset: usize, size: usize },
+    /// A single comment of the post
+    Thread(i32),
+}
+
+async fn load_rows(
+    conn: &mut AsyncPgConnection,
+    slug: &str,
+    roots: Roots,
+    sort: &SortType,
+    children_limit: Option<usize>,
+) -> Result<Vec<CommentQueryResult>, diesel::result::Error> {
+    // Determine the ORDER BY clause based on sort type, the same for the
+    // roots and the replies to a comment
     let order_by_clause = match sort {
-        SortType::Best => "ORDER BY votes DESC, comments.created_at",
-        SortType::New => "ORDER BY comments.created_at DESC",
+        SortType::Best => "ORDER BY votes DESC, created_at",
+        SortType::New => "ORDER BY created_at DESC",
+    };
+    let (root_filter, pagination, children_limit_param) = match roots {
+        Roots::Page { .. } => (
+            "ranked.parent_id IS NULL",
+            format!("{order_by_clause} LIMIT $2 OFFSET $3"),
+            "$4",
+        ),
+        Roots::Thread(_) => ("ranked.id = $2", String::new(), "$3"),
     };
 
-    // Single SQL template with dynamic ORDER BY
+    // Single SQL template with dynamic root selection
     let sql = format!(
         "
         ----------------------------------------------------------------
-        -- First we get the root comments by sorting based on type
+        -- First we count the votes of every comment of the post
         ----------------------------------------------------------------
-        WITH RECURSIVE root_comments AS (
+        WITH RECURSIVE post_comments AS (
             SELECT
-                NULL::integer as parent_id,
                 comments.id,
+                comments.parent_id,
                 comments.author_name,
                 comments.identity_id,
                 comments.content,
-                0 depth,
+                comments.author_country,
+                comments.author_asn,
+                comments.source,
                 comments.created_at,
-                SUM(CASE WHEN votes.score IS NOT NULL
-                    THEN votes.score ELSE 0 END) votes
+                COALESCE(SUM(votes.score), 0) votes
             FROM blog_comments as comments
             LEFT JOIN blog_comment_votes votes
             ON comments.id = votes.comment_id
@@ -100,90 +247,148 @@ pub async fn get_comments(
                 SELECT id FROM blog_posts
                 WHERE category = 'blog' AND slug = $1
             )
-            AND comments.parent_id IS NULL
-            GROUP BY
-                comments.id,
-                comments.author_name,
-                comments.identity_id,
-                comments.content,
-                depth,
-                comments.created_at
-            {}
-            LIMIT $2 OFFSET $3
+            GROUP BY comments.id
         ----------------------------------------------------------------
-        -- Then we recursively get the children comments of those roots
+        -- Then rank the replies to each comment by the sort type, and
+        -- count them
         ----------------------------------------------------------------
-        ), t(
-            parent_id,
-            id,
-            author_name,
-            identity_id,
-            content,
-            depth,
-            created_at
-        )
-        AS (
+        ), ranked AS (
+            SELECT
+                post_comments.*,
+                ROW_NUMBER() OVER (
+                    PARTITION BY post_comments.parent_id {order_by_clause}
+                ) sibling_rank,
+                COALESCE(children.count, 0) child_count
+            FROM post_comments
+            LEFT JOIN (
+                SELECT parent_id, COUNT(*) count
+                FROM post_comments
+                GROUP BY parent_id
+            ) children
+            ON children.parent_id = post_comments.id
+        ----------------------------------------------------------------
+        -- Finally walk down from the roots, keeping the top ranked
+        -- replies of each comment only if limited
+        ----------------------------------------------------------------
+        ), t AS (
             (
                 SELECT
-                    parent_id,
-                    id,
-                    author_name,
-                    identity_id,
-                    content,
-                    depth,
-                    created_at
-                FROM root_comments
+                    NULL::integer as parent_id,
+                    ranked.id,
+                    ranked.author_name,
+                    ranked.identity_id,
+                    ranked.content,
+                    ranked.author_country,
+                    ranked.author_asn,
+                    ranked.source,
+                    ranked.created_at,
+                    ranked.votes,
+                    ranked.child_count,
+                    0 depth
+                FROM ranked
+                WHERE {root_filter}
+                {pagination}
             )
             UNION ALL
             SELECT
-                comments.parent_id,
-                comments.id,
-                comments.author_name,
-                comments.identity_id,
-                comments.content,
-                t.depth + 1,
-                comments.created_at
+                ranked.parent_id,
+                ranked.id,
+                ranked.author_name,
+                ranked.identity_id,
+                ranked.content,
+                ranked.author_country,
+                ranked.author_asn,
+                ranked.source,
+                ranked.created_at,
+                ranked.votes,
+                ranked.child_count,
+                t.depth + 1
             FROM t
-                JOIN blog_comments as comments
-                ON (comments.parent_id = t.id)
+            JOIN ranked
+            ON ranked.parent_id = t.id
+            WHERE {children_limit_param}::bigint IS NULL
+                OR ranked.sibling_rank <= {children_limit_param}::bigint
         )
-        ----------------------------------------------------------------
-        -- Finally we get the vote count for each comment because
-        -- we can't do it in the recursive query
-        ----------------------------------------------------------------
         SELECT
             t.parent_id,
             t.id,
             COALESCE(t.author_name, i.traits->>'name') as author_name,
             t.identity_id,
             t.content,
+            t.author_country,
+            t.author_asn,
+            t.source,
             t.depth,
             t.created_at,
-            SUM(CASE WHEN votes.score IS NOT NULL
-                THEN votes.score ELSE 0 END) votes
-        FROM t LEFT JOIN blog_comment_votes votes
-        ON t.id = votes.comment_id
+            t.votes,
+            GREATEST(
+                t.child_count - COALESCE({children_limit_param}::bigint, t.child_count),
+                0
+            ) remaining_children
+        FROM t
         LEFT JOIN identities i
-        ON t.identity_id IS NOT NULL AND t.identity_id = i.id
-        GROUP BY
-            t.parent_id,
-            t.id,
-            COALESCE(t.author_name, i.traits->>'name'),
-            t.identity_id,
-            t.content,
-            t.depth,
-            t.created_at;
-        ",
-        order_by_clause
+        ON t.identity_id IS NOT NULL AND t.identity_id = i.id;
+        "
     );
 
-    let rows = diesel::sql_query(&sql)
-        .bind::<Text, _>(&slug)
-        .bind::<BigInt, _>(q.page_size as i64)
-        .bind::<BigInt, _>(q.page_offset as i64)
-        .load::<CommentQueryResult>(&mut conn)
-        .await?;
+    let children_limit = children_limit.map(|limit| limit as i64);
+    match roots {
+        Roots::Page { offset, size } => {
+            diesel::sql_query(&sql)
+                .bind::<Text, _>(slug)
+                .bind::<BigInt, _>(size as i64)
+                .bind::<BigInt, _>(offset as i64)
+                .bind::<Nullable<BigInt>, _>(children_limit)
+                .load::<CommentQueryResult>(conn)
+                .await
+        }
+        Roots::Thread(id) => {
+            diesel::sql_query(&sql)
+                .bind::<Text, _>(slug)
+                .bind::<Integer, _>(id)
+                .bind::<Nullable<BigInt>, _>(children_limit)
+                .load::<CommentQueryResult>(conn)
+                .await
+        }
+    }
+}
+
+async fn into_trees(
+    ctx: &App,
+    conn: &mut AsyncPgConnection,
+    rows: Vec<CommentQueryResult>,
+    auth_user: &Result<Identity, AuthenticationError>,
+    jar: &CookieJar,
+    sort: &SortType,
+) -> Result<Vec<CommentTree>, AppError> {
+    let mut identity_ids = rows
+        .iter()
+        .filter_map(|c| c.identity_id)
+        .collect::<Vec<_>>();
+    if let Ok(user) = &auth_user {
+        identity_ids.push(user.id);
+    }
+    identity_ids.sort_unstable();
+    identity_ids.dedup();
+    let roles = identity::roles::roles_of(ctx, conn, &identity_ids).await?;
+    let roles_of = |identity_id: Option<i32>| {
+        identity_id
+            .and_then(|id| roles.get(&id))
+            .cloned()
+            .unwrap_or_default()
+    };
 
+    // Only the blog author and the moderators get to see where the comments
+    // come from, for moderation purposes
+    let is_moderator = auth_user.as_ref().is_ok_and(|u| {
+        u.id == ctx.config.owner_identity_id
+            || roles_of(Some(u.id)).contains(&CommentRole::Moderator)
+    });
+
+    let comment_ids = rows.iter().filter_map(|c| c.id).collect::<Vec<_>>();
+    let mut mentions = mention::mentions_of(conn, &comment_ids).await?;
+
+    let now = chrono::Utc::now().naive_utc();
     let final_comments = rows
         .into_iter()
         .filter(|c| {
@@ -197,23 +402,92 @@ pub async fn get_comments(
         .map(|c| CommentTree {
             id: c.id.unwrap(),
             author_name: c.author_name.unwrap(),
-            content: c.content.unwrap(),
+            // Readers who aren't logged in get the content scrubbed, the
+            // moderators see the original
+            content: match auth_user {
+                Ok(_) => c.content.unwrap(),
+                Err(_) => ctx.comment_scrubber.scrub(&c.content.unwrap()).into_owned(),
+            },
             parent_id: c.parent_id,
             created_at: c.created_at.unwrap(),
             children: None,
             upvote: c.votes.unwrap(),
             depth: c.depth.unwrap() as usize,
+            remaining_children: c.remaining_children.unwrap_or_default(),
             is_comment_owner: match c.identity_id {
                 Some(id) => Some(id) == auth_user.as_ref().ok().map(|u| u.id),
-                None => false,
+                None => {
+                    ownership::verify(ctx.config.anonymous_comments.as_ref(), jar, c.id.unwrap())
+                }
+            },
+            is_blog_author: roles_of(c.identity_id).contains(&CommentRole::Author),
+            roles: roles_of(c.identity_id),
+            collapsed_by_default: false,
+            continue_thread_id: None,
+            mentions: c.id.and_then(|id| mentions.remove(&id)).unwrap_or_default(),
+            source: c
+                .source
+                .as_deref()
+                .map(CommentSource::parse)
+                .unwrap_or_default(),
+            author_geo: if is_moderator && (c.author_country.is_some() || c.author_asn.is_some()) {
+                Some(GeoInfo {
+                    country: c.author_country,
+                    asn: c.author_asn.and_then(|asn| u32::try_from(asn).ok()),
+                    asn_org: None,
+                })
+            } else {
+                None
             },
-            is_blog_author: c.identity_id == Some(ctx.config.owner_identity_id),
+        })
+        .map(|mut tree| {
+            tree.collapsed_by_default = is_collapsed_by_default(&tree, now);
+            tree
         })
         .collect();
 
-    let result = intermediate_tree_sort(final_comments, sort);
+    Ok(intermediate_tree_sort(final_comments, sort))
+}
 
-    Ok(Json(result))
+fn is_collapsed_by_default(comment: &CommentTree, now: NaiveDateTime) -> bool {
+    let old = now - comment.created_at > chrono::TimeDelta::days(COLLAPSE_AGE_DAYS);
+    comment.upvote <= COLLAPSE_SCORE || (comment.depth > 0 && comment.upvote <= 0 && old)
+}
+
+/// Moves the replies nested deeper than `max_depth` up into the children of
+/// their ancestor at `max_depth`, oldest first. The moved ones are marked with
+/// the ID of that ancestor so that the client can fetch its nested thread.
+fn flatten_deep_replies(comments: &mut [CommentTree], max_depth: usize) {
+    fn collect(comment: &mut CommentTree, thread_id: i32, flat: &mut Vec<CommentTree>) {
+        for mut child in comment.children.take().into_iter().flatten() {
+            collect(&mut child, thread_id, flat);
+            child.continue_thread_id = Some(thread_id);
+            flat.push(child);
+        }
+    }
+
+    for comment in comments {
+        let Some(children) = comment.children.as_mut() else {
+            continue;
+        };
+        if comment.depth < max_depth {
+            flatten_deep_replies(children, max_depth);
+            continue;
+        }
+
+        let mut flat = Vec::new();
+        for child in children.iter_mut() {
+            collect(child, comment.id, &mut flat);
+        }
+        if flat.is_empty() {
+            continue;
+        }
+        for reply in &mut flat {
+            reply.depth = max_depth + 1;
+        }
+        children.append(&mut flat);
+        children.sort_by_key(|c| c.created_at);
+    }
 }
 
 fn intermediate_tree_sort(comments: Vec<CommentTree>, sort: &SortType) -> Vec<CommentTree> {
@@ -297,8 +571,15 @@ mod test {
             children: None,
             upvote,
             depth: 0,
+            remaining_children: 0,
             is_comment_owner: false,
             is_blog_author: false,
+            roles: vec![],
+            author_geo: None,
+            collapsed_by_default: false,
+            continue_thread_id: None,
+            mentions: vec![],
+            source: CommentSource::Site,
         }
     }
 
@@ -317,8 +598,15 @@ mod test {
                 children: None,
                 upvote: 5,
                 depth: 0,
+                remaining_children: 0,
                 is_comment_owner: false,
                 is_blog_author: false,
+                roles: vec![],
+                author_geo: None,
+                collapsed_by_default: false,
+                continue_thread_id: None,
+                mentions: vec![],
+                source: CommentSource::Site,
             },
             CommentTree {
                 id: 2,
@@ -332,8 +620,15 @@ mod test {
                 children: None,
                 upvote: 10,
                 depth: 1,
+                remaining_children: 0,
                 is_comment_owner: false,
                 is_blog_author: false,
+                roles: vec![],
+                author_geo: None,
+                collapsed_by_default: false,
+                continue_thread_id: None,
+                mentions: vec![],
+                source: CommentSource::Site,
             },
         ];
 
@@ -368,4 +663,35 @@ mod test {
         assert_eq!(result[0].id, 2, "Higher voted comment should come first");
         assert_eq!(result[1].id, 1, "Lower voted comment should come second");
     }
+
+    #[test]
+    fn flatten_deep_replies_moves_them_up_to_max_depth() {
+        let comments = [
+            (1, None, 4),
+            (2, Some(1), 3),
+            (3, Some(2), 2),
+            (4, Some(3), 1),
+        ]
+        .into_iter()
+        .enumerate()
+        .map(|(depth, (id, parent_id, days_ago))| CommentTree {
+            depth,
+            ..create_mock_comment(id, parent_id, 0, days_ago)
+        })
+        .collect();
+        let mut result = intermediate_tree_sort(comments, &SortType::Best);
+
+        flatten_deep_replies(&mut result, 1);
+
+        let thread = &result[0].children.as_ref().unwrap()[0];
+        let replies = thread.children.as_ref().unwrap();
+        assert_eq!(
+            replies
+                .iter()
+                .map(|c| (c.id, c.depth, c.continue_thread_id))
+                .collect::<Vec<_>>(),
+            vec![(3, 2, None), (4, 2, Some(2))]
+        );
+        assert!(replies.iter().all(|c| c.children.is_none()));
+    }
 }
@@ -0,0 +1,296 @@
+use std::collections::{HashMap, HashSet};
+
+use axum::{Json, extract::State};
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+use diesel_async::{AsyncPgConnection, RunQueryDsl};
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use crate::{
+    App,
+    error::AppError,
+    identity::AuthUser,
+    schema::{blog_comment_mentions, blog_comments, blog_posts, identities},
+};
+
+pub use api_models::CommentMention;
+
+/// Mentions of a comment beyond this are ignored, so that a comment can't
+/// ping everyone on the post
+const MAX_MENTIONS: usize = 10;
+const PREVIEW_CHARS: usize = 200;
+
+/// The `@handle` of a commenter: their name without spaces, lowercased
+fn handle_of(name: &str) -> String {
+    name.chars()
+        .filter(|c| is_handle_char(*c))
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+fn is_handle_char(c: char) -> bool {
+    c.is_alphanumeric() || matches!(c, '_' | '-' | '.')
+}
+
+/// The lowercased handles mentioned in the content, in order of appearance. An
+/// `@` following a word character, as in an email address, isn't a mention.
+fn parse_handles(content: &str) -> Vec<String> {
+    let mut handles = Vec::new();
+    let mut previous = None;
+
+    for (i, c) in content.char_indices() {
+        let starts_mention =
+            c == '@' && previous.is_none_or(|p: char| !is_handle_char(p) && p != '@');
+        previous = Some(c);
+        if !starts_mention {
+            continue;
+        }
+
+        let rest = &content[i + c.len_utf8()..];
+        let end = rest
+            .find(|c: char| !is_handle_char(c))
+            .unwrap_or(rest.len());
+        // Punctuation ending a sentence isn't part of the handle
+        let handle = rest[..end].trim_end_matches(['.', '-']).to_lowercase();
+        if handle.chars().count() >= 2 && !handles.contains(&handle) {
+            handles.push(handle);
+        }
+    }
+
+    handles
+}
+
+/// Stores the commenters of the post the comment mentions, replacing the
+/// mentions of its previous content. The new ones are unseen, notifying the
+/// mentioned users.
+pub async fn save_mentions(
+    conn: &mut AsyncPgConnection,
+    comment_id: i32,
+    content: &str,
+) -> Result<(), diesel::result::Error> {
+    let handles = parse_handles(content);
+
+    let mentioned = if handles.is_empty() {
+        vec![]
+    } else {
+        let (post_id, author_id) = blog_comments::table
+            .filter(blog_comments::id.eq(comment_id))
+            .select((blog_comments::post_id, blog_comments::identity_id))
+            .first::<(i32, Option<i32>)>(conn)
+            .await?;
+
+        // Only the names shown on the post can be mentioned
+        let commenters = blog_comments::table
+            .inner_join(identities::table)
+            .filter(blog_comments::post_id.eq(post_id))
+            .select((
+                identities::id,
+                identities::traits.retrieve_as_text("name").nullable(),
+            ))
+            .distinct()
+            .load::<(i32, Option<String>)>(conn)
+            .await?;
+
+        resolve(&handles, &commenters)
+            .into_iter()
+            .map(|(_, identity_id)| identity_id)
+            .filter(|identity_id| Some(*identity_id) != author_id)
+            .collect::<Vec<_>>()
+    };
+
+    diesel::delete(
+        blog_comment_mentions::table
+            .filter(blog_comment_mentions::comment_id.eq(comment_id))
+            .filter(blog_comment_mentions::identity_id.ne_all(&mentioned)),
+    )
+    .execute(conn)
+    .await?;
+
+    if mentioned.is_empty() {
+        return Ok(());
+    }
+
+    diesel::insert_into(blog_comment_mentions::table)
+        .values(
+            mentioned
+                .iter()
+                .map(|identity_id| {
+                    (
+                        blog_comment_mentions::comment_id.eq(comment_id),
+                        blog_comment_mentions::identity_id.eq(identity_id),
+                    )
+                })
+                .collect::<Vec<_>>(),
+        )
+        .on_conflict_do_nothing()
+        .execute(conn)
+        .await?;
+
+    Ok(())
+}
+
+/// `(handle, identity)` of the handles naming exactly one of the commenters
+fn resolve(handles: &[String], commenters: &[(i32, Option<String>)]) -> Vec<(String, i32)> {
+    let mut by_handle = HashMap::<String, HashSet<i32>>::new();
+    for (identity_id, name) in commenters {
+        if let Some(name) = name {
+            by_handle
+                .entry(handle_of(name))
+                .or_default()
+                .insert(*identity_id);
+        }
+    }
+
+    handles
+        .iter()
+        .filter_map(|handle| {
+            let ids = by_handle.get(handle)?;
+            // Two commenters sharing a handle are both left unmentioned
+            let [identity_id] = ids.iter().copied().collect::<Vec<_>>()[..] else {
+                return None;
+            };
+            Some((handle.clone(), identity_id))
+        })
+        .take(MAX_MENTIONS)
+        .collect()
+}
+
+/// The mentions of the comments, for [CommentTree](super::CommentTree)
+pub async fn mentions_of(
+    conn: &mut AsyncPgConnection,
+    comment_ids: &[i32],
+) -> Result<HashMap<i32, Vec<CommentMention>>, diesel::result::Error> {
+    let rows = blog_comment_mentions::table
+        .inner_join(identities::table)
+        .filter(blog_comment_mentions::comment_id.eq_any(comment_ids))
+        .select((
+            blog_comment_mentions::comment_id,
+            identities::id,
+            identities::traits.retrieve_as_text("name").nullable(),
+        ))
+        .load::<(i32, i32, Option<String>)>(conn)
+        .await?;
+
+    let mut mentions = HashMap::<i32, Vec<CommentMention>>::new();
+    for (comment_id, identity_id, name) in rows {
+        let Some(name) = name else {
+            continue;
+        };
+        mentions
+            .entry(comment_id)
+            .or_default()
+            .push(CommentMention {
+                handle: handle_of(&name),
+                identity_id,
+                name,
+            });
+    }
+
+    Ok(mentions)
+}
+
+/// A comment mentioning the user
+#[derive(Queryable, Serialize, ToSchema)]
+pub struct Mention {
+    comment_id: i32,
+    slug: String,
+    /// The beginning of the comment
+    preview: String,
+    created_at: NaiveDateTime,
+    seen: bool,
+}
+
+#[utoipa::path(
+    get,
+    path = "/mentions",
+    tag = "blog",
+    responses(
+        (status = 200, description = "The latest comments mentioning the user, newest first", body = Vec<Mention>),
+        (status = 401, description = "Not logged in"),
+    ),
+    security(("session" = [])),
+)]
+pub async fn get_mentions(
+    State(ctx): State<App>,
+    AuthUser(i): AuthUser,
+) -> Result<Json<Vec<Mention>>, AppError> {
+    const MAX_LISTED: i64 = 50;
+
+    let mut conn = ctx.diesel.get().await?;
+
+    let rows = blog_comment_mentions::table
+        .inner_join(blog_comments::table.inner_join(blog_posts::table))
+        .filter(blog_comment_mentions::identity_id.eq(i.id))
+        .order(blog_comment_mentions::created_at.desc())
+        .limit(MAX_LISTED)
+        .select((
+            blog_comment_mentions::comment_id,
+            blog_posts::slug,
+            blog_comments::content,
+            blog_comment_mentions::created_at,
+            blog_comment_mentions::seen_at.is_not_null(),
+        ))
+        .load::<Mention>(&mut conn)
+        .await?;
+
+    Ok(Json(
+        rows.into_iter()
+            .map(|mention| Mention {
+                preview: mention.preview.chars().take(PREVIEW_CHARS).collect(),
+                ..mention
+            })
+            .collect(),
+    ))
+}
+
+#[utoipa::path(
+    post,
+    path = "/mentions/seen",
+    tag = "blog",
+    responses(
+        (status = 200, description = "Every mention of the user is marked as seen"),
+        (status = 401, description = "Not logged in"),
+    ),
+    security(("session" = [])),
+)]
+pub async fn mark_mentions_seen(
+    State(ctx): State<App>,
+    AuthUser(i): AuthUser,
+) -> Result<(), AppError> {
+    let mut conn = ctx.diesel.get().await?;
+
+    diesel::update(
+        blog_comment_mentions::table
+            .filter(blog_comment_mentions::identity_id.eq(i.id))
+            .filter(blog_comment_mentions::seen_at.is_null()),
+    )
+    .set(blog_comment_mentions::seen_at.eq(diesel::dsl::now))
+    .execute(&mut conn)
+    .await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mentions_resolve_to_unique_commenter_handles() {
+        let handles =
+            parse_handles("@HaHuy thanks! cc @alice, @bob. Mail me at me@example.com or @x @hahuy");
+        assert_eq!(handles, vec!["hahuy", "alice", "bob"]);
+
+        let commenters = [
+            (1, Some("Ha Huy".to_string())),
+            (2, Some("Bob".to_string())),
+            (3, Some("bob".to_string())),
+            (4, None),
+        ];
+        assert_eq!(
+            resolve(&handles, &commenters),
+            vec![("hahuy".to_string(), 1)]
+        );
+    }
+}
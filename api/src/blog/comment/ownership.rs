@@ -0,0 +1,69 @@
+use axum_extra::extract::cookie::{Cookie, CookieJar};
+use diesel::prelude::*;
+use diesel_async::{AsyncPgConnection, RunQueryDsl};
+
+use crate::{
+    App, config::AnonymousComments, crypto::signed_token, identity::models::identity::Identity,
+    schema::blog_comments,
+};
+
+const COOKIE_PREFIX: &str = "comment_owner_";
+
+/// The httpOnly cookie proving that the browser posted the anonymous comment,
+/// valid for the edit window
+pub fn issue(config: &AnonymousComments, comment_id: i32) -> Result<Cookie<'static>, eyre::Error> {
+    let expires = time::OffsetDateTime::now_utc() + config.edit_window;
+    let token = signed_token::sign(
+        &config.secret,
+        &comment_id.to_string(),
+        expires.unix_timestamp(),
+    )?;
+
+    Ok(
+        Cookie::build((format!("{COOKIE_PREFIX}{comment_id}"), token))
+            .secure(true)
+            .http_only(true)
+            .expires(expires)
+            .path("/")
+            .build(),
+    )
+}
+
+/// Whether the request carries a valid, unexpired token of the comment
+pub fn verify(config: Option<&AnonymousComments>, jar: &CookieJar, comment_id: i32) -> bool {
+    let (Some(config), Some(cookie)) = (config, jar.get(&format!("{COOKIE_PREFIX}{comment_id}")))
+    else {
+        return false;
+    };
+    signed_token::verify_at(
+        &config.secret,
+        cookie.value(),
+        time::OffsetDateTime::now_utc().unix_timestamp(),
+    )
+    .is_some_and(|id| id.parse::<i32>().is_ok_and(|id| id == comment_id))
+}
+
+/// Whether the comment was posted by the logged in user, or anonymously from
+/// this browser within the edit window
+pub async fn is_owner(
+    ctx: &App,
+    conn: &mut AsyncPgConnection,
+    jar: &CookieJar,
+    auth_user: Option<&Identity>,
+    comment_id: i32,
+) -> Result<bool, diesel::result::Error> {
+    let Some(identity_id) = blog_comments::table
+        .filter(blog_comments::id.eq(comment_id))
+        .select(blog_comments::identity_id)
+        .first::<Option<i32>>(conn)
+        .await
+        .optional()?
+    else {
+        return Ok(false);
+    };
+
+    Ok(match identity_id {
+        Some(identity_id) => auth_user.is_some_and(|user| user.id == identity_id),
+        None => verify(ctx.config.anonymous_comments.as_ref(), jar, comment_id),
+    })
+}
@@ -3,26 +3,46 @@ use axum::{
     extract::{Path, State},
     http::StatusCode,
 };
+use axum_extra::extract::CookieJar;
 use diesel::prelude::*;
 use diesel_async::RunQueryDsl;
 use serde::Deserialize;
+use utoipa::ToSchema;
 
 use crate::{
     App,
-    blog::comment::Comment,
+    blog::comment::{Comment, mention, ownership},
     blog::models::UpdateBlogComment,
     error::AppError,
-    identity::AuthUser,
+    identity::MaybeAuthUser,
     real_ip::ClientIp,
     schema::{blog_comments, identities},
 };
 
+#[utoipa::path(
+    patch,
+    path = "/{slug}/comments/{id}",
+    tag = "blog",
+    params(
+        ("slug" = String, Path, description = "Slug of the blog post"),
+        ("id" = i32, Path, description = "ID of the comment"),
+    ),
+    request_body = CommentPatch,
+    responses(
+        (status = 200, description = "The updated comment", body = Comment),
+        (status = 400, description = "Empty or too long content"),
+        (status = 401, description = "Not logged in, nor the anonymous author of the comment"),
+        (status = 403, description = "Not the author of the comment"),
+    ),
+    security(("session" = [])),
+)]
 #[debug_handler]
 pub async fn patch_comment(
     State(ctx): State<App>,
     Path((_slug, id)): Path<(String, i32)>,
     ClientIp(_ip): ClientIp,
-    AuthUser(auth_user): AuthUser,
+    MaybeAuthUser(auth_user): MaybeAuthUser,
+    jar: CookieJar,
     crate::json::Json(mut comment): crate::json::Json<CommentPatch>,
 ) -> Result<Json<Comment>, AppError> {
     comment.content = comment.content.trim().to_string();
@@ -40,15 +60,9 @@ pub async fn patch_comment(
 
     let mut conn = ctx.diesel.get().await?;
 
-    let is_owner = blog_comments::table
-        .filter(blog_comments::id.eq(id))
-        .filter(blog_comments::identity_id.eq(auth_user.id))
-        .select(blog_comments::id)
-        .first::<i32>(&mut conn)
-        .await
-        .optional()?;
-
-    if is_owner.is_none() {
+    if !ownership::is_owner(&ctx, &mut conn, &jar, auth_user.as_ref().ok(), id).await? {
+        // Not logged in and without the token of an anonymous comment
+        auth_user?;
         return Err((
             "You are not the owner of this comment",
             StatusCode::FORBIDDEN,
@@ -79,6 +93,8 @@ pub async fn patch_comment(
         )>(&mut conn)
         .await?;
 
+    mention::save_mentions(&mut conn, updated_comment.0, &updated_comment.3).await?;
+
     let mut author_name = updated_comment.1.clone();
 
     if let Some(identity_id) = updated_comment.2 {
@@ -119,7 +135,7 @@ pub async fn patch_comment(
     }))
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, ToSchema)]
 pub struct CommentPatch {
     content: String,
 }
@@ -0,0 +1,213 @@
+use std::time::Duration;
+
+use axum::{
+    Json, debug_handler,
+    extract::{Path, State},
+    http::StatusCode,
+};
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+use diesel_async::RunQueryDsl;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::{
+    App,
+    blog::{models::NewBlogCommentReport, notify::CommentReportNotification},
+    error::AppError,
+    identity::{AuthUser, MaybeAuthUser},
+    rate_limit::RateLimiter,
+    real_ip::ClientIp,
+    schema::{blog_comment_reports, blog_comments, blog_posts},
+};
+
+/// Reports a reader may send per hour
+const REPORTS_PER_HOUR: usize = 5;
+const MAX_REASON_CHARS: usize = 500;
+
+pub fn rate_limiter() -> RateLimiter<std::net::IpAddr> {
+    RateLimiter::new(REPORTS_PER_HOUR, Duration::from_secs(60 * 60))
+}
+
+#[derive(Deserialize, Serialize, ToSchema)]
+pub struct ReportSubmission {
+    /// Why the comment is abusive
+    reason: String,
+}
+
+#[utoipa::path(
+    post,
+    path = "/{slug}/comments/{id}/report",
+    tag = "blog",
+    params(
+        ("slug" = String, Path, description = "Slug of the blog post"),
+        ("id" = i32, Path, description = "ID of the comment"),
+    ),
+    request_body = ReportSubmission,
+    responses(
+        (status = 200, description = "The comment was reported"),
+        (status = 400, description = "Empty or too long reason"),
+        (status = 404, description = "No such comment on the post"),
+        (status = 429, description = "Too many reports"),
+    ),
+)]
+#[debug_handler]
+pub async fn report_comment(
+    State(ctx): State<App>,
+    Path((slug, id)): Path<(String, i32)>,
+    client_ip: ClientIp,
+    auth_user: MaybeAuthUser,
+    crate::json::Json(report): crate::json::Json<ReportSubmission>,
+) -> Result<(), AppError> {
+    let reason = report.reason.trim().to_string();
+    if reason.is_empty() {
+        return Err(("No reason provided", StatusCode::BAD_REQUEST).into());
+    }
+    if reason.chars().count() > MAX_REASON_CHARS {
+        return Err((
+            "Reason too long (max 500 characters)",
+            StatusCode::BAD_REQUEST,
+        )
+            .into());
+    }
+
+    if !ctx.comment_report_limiter.check(client_ip.0) {
+        return Err((
+            "Too many reports, try again later",
+            StatusCode::TOO_MANY_REQUESTS,
+        )
+            .into());
+    }
+
+    let mut conn = ctx.diesel.get().await?;
+
+    let content = blog_comments::table
+        .inner_join(blog_posts::table)
+        .filter(blog_comments::id.eq(id))
+        .filter(blog_posts::category.eq("blog"))
+        .filter(blog_posts::slug.eq(&slug))
+        .select(blog_comments::content)
+        .first::<String>(&mut conn)
+        .await
+        .optional()?
+        .ok_or(("Comment not found", StatusCode::NOT_FOUND))?;
+
+    // A reader who already reported the comment isn't counted twice
+    let inserted = diesel::insert_into(blog_comment_reports::table)
+        .values(&NewBlogCommentReport {
+            comment_id: id,
+            reason: reason.clone(),
+            reporter_ip: client_ip.0.to_string(),
+            identity_id: auth_user.0.ok().map(|identity| identity.id),
+        })
+        .on_conflict((
+            blog_comment_reports::comment_id,
+            blog_comment_reports::reporter_ip,
+        ))
+        .do_nothing()
+        .execute(&mut conn)
+        .await?;
+
+    if inserted == 0 {
+        return Ok(());
+    }
+
+    let reports = blog_comment_reports::table
+        .filter(blog_comment_reports::comment_id.eq(id))
+        .filter(blog_comment_reports::resolved_at.is_null())
+        .count()
+        .get_result::<i64>(&mut conn)
+        .await?;
+
+    // Not waited for so that a slow notification doesn't delay the response
+    if let Some(notifier) = ctx.comment_notifier.clone() {
+        let notification = CommentReportNotification {
+            comment_id: id,
+            slug,
+            content,
+            reason,
+            reports,
+        };
+        tokio::spawn(async move {
+            let _ = notifier
+                .comment_reported(&notification)
+                .await
+                .inspect_err(|e| tracing::error!(?e, "Failed to send comment report notification"));
+        });
+    }
+
+    Ok(())
+}
+
+/// A report waiting in the moderation queue
+#[derive(Queryable, Serialize)]
+pub struct QueuedReport {
+    id: i32,
+    comment_id: i32,
+    slug: String,
+    content: String,
+    reason: String,
+    reporter_ip: String,
+    created_at: NaiveDateTime,
+}
+
+/// The unresolved reports, oldest first
+pub async fn get_report_queue(
+    State(ctx): State<App>,
+    AuthUser(i): AuthUser,
+) -> Result<Json<Vec<QueuedReport>>, AppError> {
+    const MAX_REPORTS: i64 = 500;
+
+    ensure_owner(&ctx, i.id)?;
+
+    let mut conn = ctx.diesel.get().await?;
+
+    let reports = blog_comment_reports::table
+        .inner_join(blog_comments::table.inner_join(blog_posts::table))
+        .filter(blog_comment_reports::resolved_at.is_null())
+        .order(blog_comment_reports::created_at.asc())
+        .limit(MAX_REPORTS)
+        .select((
+            blog_comment_reports::id,
+            blog_comment_reports::comment_id,
+            blog_posts::slug,
+            blog_comments::content,
+            blog_comment_reports::reason,
+            blog_comment_reports::reporter_ip,
+            blog_comment_reports::created_at,
+        ))
+        .load(&mut conn)
+        .await?;
+
+    Ok(Json(reports))
+}
+
+/// Takes every report of the comment off the queue, the comment itself is
+/// deleted separately if needed
+pub async fn resolve_reports(
+    State(ctx): State<App>,
+    AuthUser(i): AuthUser,
+    Path(comment_id): Path<i32>,
+) -> Result<(), AppError> {
+    ensure_owner(&ctx, i.id)?;
+
+    let mut conn = ctx.diesel.get().await?;
+
+    diesel::update(
+        blog_comment_reports::table
+            .filter(blog_comment_reports::comment_id.eq(comment_id))
+            .filter(blog_comment_reports::resolved_at.is_null()),
+    )
+    .set(blog_comment_reports::resolved_at.eq(diesel::dsl::now))
+    .execute(&mut conn)
+    .await?;
+
+    Ok(())
+}
+
+fn ensure_owner(ctx: &App, identity_id: i32) -> Result<(), AppError> {
+    if identity_id != ctx.config.owner_identity_id {
+        return Err(("Not permitted", StatusCode::FORBIDDEN).into());
+    }
+    Ok(())
+}
@@ -0,0 +1,107 @@
+use std::borrow::Cow;
+
+use regex::{Captures, Regex};
+
+use crate::config::CommentScrubbing;
+
+const EMAIL_PATTERN: &str = r"[A-Za-z0-9._%+-]+@[A-Za-z0-9.-]+\.[A-Za-z]{2,}";
+/// Candidates only, see [PHONE_DIGITS]
+const PHONE_PATTERN: &str = r"\+?\(?\d[\d\s().-]{6,}\d";
+/// Digits of a phone number, fewer and dates or amounts would be masked too
+const PHONE_DIGITS: std::ops::RangeInclusive<usize> = 9..=15;
+
+/// Replacement of a match
+type Mask = fn(&Captures) -> String;
+
+struct Rule {
+    pattern: Regex,
+    mask: Mask,
+}
+
+/// Masks the emails, phone numbers and words configured in
+/// [CommentScrubbing] out of the comments shown to the readers who aren't
+/// logged in
+pub struct CommentScrubber {
+    rules: Vec<Rule>,
+}
+
+impl CommentScrubber {
+    pub fn new(config: Option<&CommentScrubbing>) -> Self {
+        let Some(config) = config else {
+            return Self { rules: vec![] };
+        };
+
+        let words = (!config.words.is_empty()).then(|| {
+            let words = config
+                .words
+                .iter()
+                .map(|w| regex::escape(w))
+                .collect::<Vec<_>>();
+            format!(r"(?i)\b(?:{})\b", words.join("|"))
+        });
+
+        let rules: [(Option<String>, Mask); 3] = [
+            (config.emails.then(|| EMAIL_PATTERN.to_string()), |_| {
+                "[email hidden]".to_string()
+            }),
+            (
+                config.phone_numbers.then(|| PHONE_PATTERN.to_string()),
+                |c| {
+                    let digits = c[0].chars().filter(char::is_ascii_digit).count();
+                    if PHONE_DIGITS.contains(&digits) {
+                        "[phone hidden]".to_string()
+                    } else {
+                        c[0].to_string()
+                    }
+                },
+            ),
+            (words, |c| "*".repeat(c[0].chars().count())),
+        ];
+
+        let rules = rules
+            .into_iter()
+            .filter_map(|(pattern, mask)| {
+                let pattern = Regex::new(&pattern?)
+                    .inspect_err(|e| tracing::error!(?e, "Invalid comment scrubbing pattern"))
+                    .ok()?;
+                Some(Rule { pattern, mask })
+            })
+            .collect();
+
+        Self { rules }
+    }
+
+    pub fn scrub<'a>(&self, content: &'a str) -> Cow<'a, str> {
+        let mut content = Cow::Borrowed(content);
+        for rule in &self.rules {
+            if let Cow::Owned(scrubbed) = rule.pattern.replace_all(&content, rule.mask) {
+                content = Cow::Owned(scrubbed);
+            }
+        }
+        content
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scrubs_the_configured_kinds_only() {
+        let scrubber = CommentScrubber::new(Some(&CommentScrubbing {
+            emails: true,
+            phone_numbers: true,
+            words: vec!["darn".to_string()],
+        }));
+
+        assert_eq!(
+            scrubber.scrub("Darn, mail me@example.com or call +84 (90) 123-4567 before 2023-01-01"),
+            "****, mail [email hidden] or call [phone hidden] before 2023-01-01"
+        );
+        // Only whole words are masked
+        assert_eq!(scrubber.scrub("darning socks"), "darning socks");
+
+        let disabled = CommentScrubber::new(None);
+        assert!(matches!(disabled.scrub("me@example.com"), Cow::Borrowed(_)));
+    }
+}
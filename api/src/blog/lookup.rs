@@ -0,0 +1,251 @@
+//! Read-only lookups of the posts and their comments for the tools of the
+//! Discord agent. The comments are scrubbed like for the readers who aren't
+//! logged in, since the channels are about as public.
+
+use std::sync::Arc;
+
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+use diesel_async::{AsyncPgConnection, RunQueryDsl, pooled_connection::deadpool::Pool};
+use eyre::Context as _;
+use serde::Serialize;
+
+use super::{CommentScrubber, post_url};
+use crate::{
+    config::ServerConfig,
+    schema::{blog_comments, blog_posts, identities},
+};
+
+/// Longest part of a comment returned
+const PREVIEW_CHARS: usize = 500;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CommentInfo {
+    pub id: i32,
+    pub post_slug: String,
+    pub post_title: Option<String>,
+    pub author_name: String,
+    pub content: String,
+    pub is_reply: bool,
+    /// Where it was posted, the website or the fediverse
+    pub source: String,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PostInfo {
+    pub slug: String,
+    pub title: Option<String>,
+    pub url: String,
+    pub summary: Option<String>,
+    pub reading_time_mins: Option<i32>,
+    /// When it was announced to the fediverse followers, about when it was
+    /// published
+    pub federated_at: Option<NaiveDateTime>,
+    pub comment_count: i64,
+    pub last_comment_at: Option<NaiveDateTime>,
+}
+
+type CommentRow = (
+    i32,
+    Option<i32>,
+    Option<String>,
+    Option<serde_json::Value>,
+    String,
+    String,
+    NaiveDateTime,
+    String,
+    Option<String>,
+);
+
+#[derive(Clone)]
+pub struct BlogReader {
+    diesel: Pool<AsyncPgConnection>,
+    site_url: String,
+    scrubber: Arc<CommentScrubber>,
+}
+
+impl BlogReader {
+    pub fn new(diesel: Pool<AsyncPgConnection>, config: &ServerConfig) -> Self {
+        Self {
+            diesel,
+            site_url: config.site_url.clone(),
+            scrubber: Arc::new(CommentScrubber::new(config.comment_scrubbing.as_ref())),
+        }
+    }
+
+    async fn conn(
+        &self,
+    ) -> Result<diesel_async::pooled_connection::deadpool::Object<AsyncPgConnection>, eyre::Error>
+    {
+        self.diesel
+            .get()
+            .await
+            .wrap_err("could not get diesel pool conn")
+    }
+
+    /// The latest comments, of a single post if set, newest first
+    pub async fn recent_comments(
+        &self,
+        slug: Option<&str>,
+        since: Option<NaiveDateTime>,
+        limit: i64,
+    ) -> Result<Vec<CommentInfo>, eyre::Error> {
+        let mut conn = self.conn().await?;
+
+        let mut query = blog_comments::table
+            .inner_join(blog_posts::table)
+            .left_join(identities::table)
+            .filter(blog_posts::category.eq("blog"))
+            .select((
+                blog_comments::id,
+                blog_comments::parent_id,
+                blog_comments::author_name,
+                identities::traits.nullable(),
+                blog_comments::content,
+                blog_comments::source,
+                blog_comments::created_at,
+                blog_posts::slug,
+                blog_posts::title,
+            ))
+            .order(blog_comments::created_at.desc())
+            .limit(limit)
+            .into_boxed();
+
+        if let Some(slug) = slug {
+            query = query.filter(blog_posts::slug.eq(slug));
+        }
+        if let Some(since) = since {
+            query = query.filter(blog_comments::created_at.gt(since));
+        }
+
+        let rows: Vec<CommentRow> = query
+            .load(&mut conn)
+            .await
+            .wrap_err("failed to load blog comments")?;
+
+        Ok(rows
+            .into_iter()
+            .map(
+                |(id, parent_id, author_name, traits, content, source, created_at, slug, title)| {
+                    CommentInfo {
+                        id,
+                        post_slug: slug,
+                        post_title: title,
+                        // Like on the website, the name of the account is
+                        // used if the comment has none
+                        author_name: author_name
+                            .or_else(|| traits?.get("name")?.as_str().map(str::to_string))
+                            .unwrap_or("anonymous".to_string()),
+                        content: preview(&self.scrubber.scrub(&content)),
+                        is_reply: parent_id.is_some(),
+                        source,
+                        created_at,
+                    }
+                },
+            )
+            .collect())
+    }
+
+    /// The posts whose slug or title contain the query, the exact slug first,
+    /// or the latest posts without a query
+    pub async fn find_posts(
+        &self,
+        query: Option<&str>,
+        limit: i64,
+    ) -> Result<Vec<PostInfo>, eyre::Error> {
+        let mut conn = self.conn().await?;
+
+        let mut posts = blog_posts::table
+            .filter(blog_posts::category.eq("blog"))
+            .select((
+                blog_posts::id,
+                blog_posts::slug,
+                blog_posts::title,
+                blog_posts::summary,
+                blog_posts::reading_time_mins,
+                blog_posts::federated_at,
+            ))
+            .order(blog_posts::id.desc())
+            .limit(limit)
+            .into_boxed();
+
+        if let Some(query) = query.map(str::trim).filter(|q| !q.is_empty()) {
+            let pattern = format!(
+                "%{}%",
+                query
+                    .replace('\\', "\\\\")
+                    .replace('%', "\\%")
+                    .replace('_', "\\_")
+            );
+            posts = posts
+                .filter(
+                    blog_posts::slug
+                        .ilike(pattern.clone())
+                        .or(blog_posts::title.ilike(pattern)),
+                )
+                // The exact slug first, then the latest
+                .order((
+                    blog_posts::slug.ne(query.to_string()),
+                    blog_posts::id.desc(),
+                ));
+        }
+
+        #[allow(clippy::type_complexity)]
+        let posts: Vec<(
+            i32,
+            String,
+            Option<String>,
+            Option<String>,
+            Option<i32>,
+            Option<NaiveDateTime>,
+        )> = posts
+            .load(&mut conn)
+            .await
+            .wrap_err("failed to load blog posts")?;
+
+        let ids: Vec<i32> = posts.iter().map(|p| p.0).collect();
+        let stats: Vec<(i32, i64, Option<NaiveDateTime>)> = blog_comments::table
+            .filter(blog_comments::post_id.eq_any(&ids))
+            .group_by(blog_comments::post_id)
+            .select((
+                blog_comments::post_id,
+                diesel::dsl::count_star(),
+                diesel::dsl::max(blog_comments::created_at),
+            ))
+            .load(&mut conn)
+            .await
+            .wrap_err("failed to count blog comments")?;
+
+        Ok(posts
+            .into_iter()
+            .map(
+                |(id, slug, title, summary, reading_time_mins, federated_at)| {
+                    let (comment_count, last_comment_at) = stats
+                        .iter()
+                        .find(|(post_id, ..)| *post_id == id)
+                        .map(|(_, count, last)| (*count, *last))
+                        .unwrap_or_default();
+                    PostInfo {
+                        url: post_url(&self.site_url, &slug),
+                        slug,
+                        title,
+                        summary,
+                        reading_time_mins,
+                        federated_at,
+                        comment_count,
+                        last_comment_at,
+                    }
+                },
+            )
+            .collect())
+    }
+}
+
+fn preview(content: &str) -> String {
+    let mut preview = content.chars().take(PREVIEW_CHARS).collect::<String>();
+    if content.chars().count() > PREVIEW_CHARS {
+        preview.push('…');
+    }
+    preview
+}
@@ -0,0 +1,226 @@
+//! Reading time and summary of the posts, generated from the published page
+//! when a post is registered so that the frontend doesn't compute them. The
+//! page is embedded at the same time for the [related](super::related) reading.
+
+use axum::{
+    Json,
+    extract::{Path, State},
+    http::StatusCode,
+};
+use diesel::prelude::*;
+use diesel_async::RunQueryDsl;
+use eyre::{Context as _, eyre};
+use rig::{
+    agent::AgentBuilder, client::CompletionClient, completion::Prompt,
+    providers::openrouter::Client,
+};
+
+use crate::{
+    App, blog::models::UpdateBlogPostMeta, discord::constants::DEFAULT_MODEL, error::AppError,
+    identity::AuthUser, schema::blog_posts,
+};
+
+pub use api_models::BlogPostMeta;
+
+/// Average silent reading speed of technical prose
+const WORDS_PER_MINUTE: usize = 230;
+
+/// Content sent to the LLM at most, longer posts are summarized from their
+/// beginning
+const MAX_SUMMARIZED_CHARS: usize = 24_000;
+
+const SUMMARY_PROMPT: &str = "You summarize blog posts for their readers. \
+Reply with two or three plain sentences saying what the post is about and what the reader \
+will learn, in the language of the post. Don't use markdown, quotes or a preamble.";
+
+fn reading_time_mins(markdown: &str) -> i32 {
+    let words = markdown
+        .split_whitespace()
+        .filter(|w| w.chars().any(char::is_alphanumeric))
+        .count();
+    i32::try_from(words.div_ceil(WORDS_PER_MINUTE).max(1)).unwrap_or(i32::MAX)
+}
+
+/// Generates the meta of a newly registered post in the background
+pub fn spawn_generate(ctx: App, slug: String) {
+    tokio::spawn(async move {
+        let _ = generate(&ctx, &slug)
+            .await
+            .inspect_err(|e| tracing::error!(?e, slug, "Failed to generate blog post meta"));
+    });
+}
+
+/// Fetches the published post and stores its reading time, summary, chunks and
+/// title if it has none yet. The summary is kept as is if no LLM is configured.
+pub async fn generate(ctx: &App, slug: &str) -> Result<BlogPostMeta, eyre::Error> {
+    let url = url::Url::parse(&super::post_url(&ctx.config.site_url, slug))?;
+    let (title, markdown) = crate::recommendation::fetch_markdown(ctx, &url)
+        .await
+        .wrap_err("failed to fetch the post")?;
+
+    let summary = match &ctx.config.openai_api_key {
+        Some(api_key) => Some(summarize(api_key, &markdown).await?),
+        None => None,
+    };
+
+    let embeddings = crate::recommendation::generate_embeddings(
+        &ctx.embedder,
+        title.as_deref().unwrap_or(slug),
+        &markdown,
+    )
+    .await?;
+
+    let mut conn = ctx.diesel.get().await?;
+
+    let filter = blog_posts::category
+        .eq("blog")
+        .and(blog_posts::slug.eq(slug));
+
+    // The title the page was published with, unless it's been set already
+    if let Some(title) = title {
+        diesel::update(
+            blog_posts::table
+                .filter(filter)
+                .filter(blog_posts::title.is_null()),
+        )
+        .set(blog_posts::title.eq(title))
+        .execute(&mut conn)
+        .await?;
+    }
+
+    let post = diesel::update(blog_posts::table.filter(filter))
+        .set(&UpdateBlogPostMeta {
+            reading_time_mins: Some(reading_time_mins(&markdown)),
+            summary,
+            meta_updated_at: Some(chrono::Utc::now().naive_utc()),
+        })
+        .returning((
+            blog_posts::id,
+            (
+                blog_posts::slug,
+                blog_posts::title,
+                blog_posts::reading_time_mins,
+                blog_posts::summary,
+            ),
+        ))
+        .get_result::<(i32, (String, Option<String>, Option<i32>, Option<String>))>(&mut conn)
+        .await?;
+
+    super::related::save_chunks(&mut conn, post.0, &embeddings).await?;
+
+    Ok(into_meta(post.1))
+}
+
+async fn summarize(api_key: &str, markdown: &str) -> Result<String, eyre::Error> {
+    let client = Client::new(api_key).wrap_err("failed to create OpenRouter client")?;
+    let summarizer = AgentBuilder::new(client.completion_model(DEFAULT_MODEL))
+        .preamble(SUMMARY_PROMPT)
+        .build();
+
+    let content = markdown
+        .chars()
+        .take(MAX_SUMMARIZED_CHARS)
+        .collect::<String>();
+    let summary = summarizer
+        .prompt(content)
+        .await
+        .wrap_err("failed to summarize the post")?;
+
+    let summary = summary.trim();
+    if summary.is_empty() {
+        return Err(eyre!("the summary is empty"));
+    }
+    Ok(summary.to_string())
+}
+
+fn into_meta(
+    (slug, title, reading_time_mins, summary): (
+        String,
+        Option<String>,
+        Option<i32>,
+        Option<String>,
+    ),
+) -> BlogPostMeta {
+    BlogPostMeta {
+        slug,
+        title,
+        reading_time_mins,
+        summary,
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/posts/{slug}/meta",
+    tag = "blog",
+    params(("slug" = String, Path, description = "Slug of the blog post")),
+    responses(
+        (status = 200, description = "Reading time and summary of the post", body = BlogPostMeta),
+        (status = 404, description = "The post has no comments yet, so isn't registered"),
+    ),
+)]
+pub async fn get_post_meta(
+    State(ctx): State<App>,
+    Path(slug): Path<String>,
+) -> Result<Json<BlogPostMeta>, AppError> {
+    let mut conn = ctx.diesel.get().await?;
+
+    let post = blog_posts::table
+        .filter(blog_posts::category.eq("blog"))
+        .filter(blog_posts::slug.eq(&slug))
+        .select((
+            blog_posts::slug,
+            blog_posts::title,
+            blog_posts::reading_time_mins,
+            blog_posts::summary,
+        ))
+        .first(&mut conn)
+        .await
+        .optional()?
+        .ok_or(("Post not found", StatusCode::NOT_FOUND))?;
+
+    Ok(Json(into_meta(post)))
+}
+
+/// Regenerates the meta of a post, for the owner to backfill the posts
+/// registered before the meta existed or to pick up an edited post
+pub async fn refresh_post_meta(
+    State(ctx): State<App>,
+    AuthUser(i): AuthUser,
+    Path(slug): Path<String>,
+) -> Result<Json<BlogPostMeta>, AppError> {
+    if i.id != ctx.config.owner_identity_id {
+        return Err(("Not permitted", StatusCode::FORBIDDEN).into());
+    }
+
+    let exists = {
+        let mut conn = ctx.diesel.get().await?;
+        blog_posts::table
+            .filter(blog_posts::category.eq("blog"))
+            .filter(blog_posts::slug.eq(&slug))
+            .select(blog_posts::id)
+            .first::<i32>(&mut conn)
+            .await
+            .optional()?
+            .is_some()
+    };
+    if !exists {
+        return Err(("Post not found", StatusCode::NOT_FOUND).into());
+    }
+
+    Ok(Json(generate(&ctx, &slug).await?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reading_time_counts_words_only() {
+        assert_eq!(reading_time_mins(""), 1);
+        assert_eq!(reading_time_mins(&"word ".repeat(230)), 1);
+        assert_eq!(reading_time_mins(&"word ".repeat(231)), 2);
+        // Markdown syntax standing alone isn't read
+        assert_eq!(reading_time_mins(&"word - ## ``` ".repeat(460)), 2);
+    }
+}
@@ -15,6 +15,10 @@ pub struct BlogComment {
     pub post_id: i32,
     pub parent_id: Option<i32>,
     pub created_at: NaiveDateTime,
+    pub author_country: Option<String>,
+    pub author_asn: Option<i64>,
+    pub source: String,
+    pub source_id: Option<String>,
 }
 
 #[derive(Insertable, Debug)]
@@ -27,6 +31,12 @@ pub struct NewBlogComment {
     pub content: String,
     pub post_id: i32,
     pub parent_id: Option<i32>,
+    pub author_country: Option<String>,
+    pub author_asn: Option<i64>,
+    /// See [api_models::CommentSource]
+    pub source: String,
+    /// ID of the ActivityPub object of a reply from the fediverse
+    pub source_id: Option<String>,
 }
 
 #[derive(AsChangeset, Debug)]
@@ -0,0 +1,10 @@
+use diesel::prelude::*;
+
+#[derive(Insertable, Debug)]
+#[diesel(table_name = crate::schema::blog_comment_reports)]
+pub struct NewBlogCommentReport {
+    pub comment_id: i32,
+    pub reason: String,
+    pub reporter_ip: String,
+    pub identity_id: Option<i32>,
+}
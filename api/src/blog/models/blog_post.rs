@@ -9,6 +9,10 @@ pub struct BlogPost {
     pub category: String,
     pub slug: String,
     pub title: Option<String>,
+    pub reading_time_mins: Option<i32>,
+    pub summary: Option<String>,
+    pub meta_updated_at: Option<chrono::NaiveDateTime>,
+    pub federated_at: Option<chrono::NaiveDateTime>,
 }
 
 #[derive(Insertable, Debug)]
@@ -18,3 +22,12 @@ pub struct NewBlogPost {
     pub slug: String,
     pub title: Option<String>,
 }
+
+/// Generated meta of a post, the fields that are `None` are left unchanged
+#[derive(AsChangeset, Debug)]
+#[diesel(table_name = crate::schema::blog_posts)]
+pub struct UpdateBlogPostMeta {
+    pub reading_time_mins: Option<i32>,
+    pub summary: Option<String>,
+    pub meta_updated_at: Option<chrono::NaiveDateTime>,
+}
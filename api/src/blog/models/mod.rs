@@ -1,6 +1,8 @@
 pub mod blog_comment;
+pub mod blog_comment_report;
 pub mod blog_comment_vote;
 pub mod blog_post;
 
 pub use blog_comment::*;
+pub use blog_comment_report::*;
 pub use blog_post::*;
@@ -0,0 +1,259 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use eyre::Context as _;
+use serde_json::json;
+use serenity::all::{ChannelId, CreateEmbed, CreateEmbedFooter, CreateMessage, Http, UserId};
+
+use super::post_url;
+use crate::{
+    config::{CommentNotificationTarget, ServerConfig},
+    outbound::HttpClient,
+};
+
+/// Longest part of the comment included in a notification
+const PREVIEW_CHARS: usize = 500;
+
+/// A comment that was just posted
+#[derive(Debug, Clone)]
+pub struct NewCommentNotification {
+    pub comment_id: i32,
+    pub slug: String,
+    pub author_name: String,
+    pub content: String,
+    pub is_reply: bool,
+    pub author_country: Option<String>,
+    pub author_asn: Option<i64>,
+}
+
+impl NewCommentNotification {
+    fn preview(&self) -> String {
+        preview(&self.content)
+    }
+
+    fn title(&self) -> String {
+        format!(
+            "💬 New {} by {} on {}",
+            if self.is_reply { "reply" } else { "comment" },
+            self.author_name,
+            self.slug
+        )
+    }
+
+    /// Where the comment came from, to help spotting spam
+    fn origin(&self) -> String {
+        format!(
+            "comment #{} · {} · AS{}",
+            self.comment_id,
+            self.author_country.as_deref().unwrap_or("unknown country"),
+            self.author_asn
+                .map(|asn| asn.to_string())
+                .unwrap_or("?".to_string())
+        )
+    }
+}
+
+/// A comment a reader reported
+#[derive(Debug, Clone)]
+pub struct CommentReportNotification {
+    pub comment_id: i32,
+    pub slug: String,
+    pub content: String,
+    pub reason: String,
+    /// Unresolved reports of the comment, including this one
+    pub reports: i64,
+}
+
+impl CommentReportNotification {
+    fn title(&self) -> String {
+        format!("🚩 Comment #{} on {} reported", self.comment_id, self.slug)
+    }
+
+    fn description(&self) -> String {
+        format!(
+            "{}\n\n**Reason:** {}",
+            preview(&self.content),
+            preview(&self.reason)
+        )
+    }
+
+    fn footer(&self) -> String {
+        format!("{} unresolved report(s)", self.reports)
+    }
+}
+
+/// Tells the blog author about new comments
+#[async_trait]
+pub trait CommentNotifier: Send + Sync {
+    async fn new_comment(&self, comment: &NewCommentNotification) -> Result<(), eyre::Error>;
+
+    async fn comment_reported(&self, report: &CommentReportNotification)
+    -> Result<(), eyre::Error>;
+}
+
+/// The notifier of the configured target, `None` if notifications are disabled
+pub fn comment_notifier(
+    config: &ServerConfig,
+    http: HttpClient,
+) -> Option<Arc<dyn CommentNotifier>> {
+    let notifier: Arc<dyn CommentNotifier> = match config.comment_notifications.clone()? {
+        CommentNotificationTarget::Webhook(url) => Arc::new(WebhookNotifier {
+            url,
+            site_url: config.site_url.clone(),
+            client: http,
+        }),
+        target => {
+            let Some(token) = &config.discord_token else {
+                tracing::warn!("Comment notifications to Discord need DISCORD_TOKEN");
+                return None;
+            };
+            Arc::new(DiscordNotifier {
+                http: Http::new(token),
+                site_url: config.site_url.clone(),
+                target,
+            })
+        }
+    };
+
+    Some(notifier)
+}
+
+/// Sends the notifications through the Discord bot, as a DM or to a channel
+struct DiscordNotifier {
+    http: Http,
+    site_url: String,
+    target: CommentNotificationTarget,
+}
+
+#[async_trait]
+impl CommentNotifier for DiscordNotifier {
+    async fn new_comment(&self, comment: &NewCommentNotification) -> Result<(), eyre::Error> {
+        let channel_id = self.channel_id().await?;
+
+        let url = post_url(&self.site_url, &comment.slug);
+        channel_id
+            .send_message(
+                &self.http,
+                CreateMessage::new().embed(
+                    CreateEmbed::new()
+                        .title(comment.title())
+                        .url(&url)
+                        .description(comment.preview())
+                        .field("Moderate", format!("[Open the post]({url})"), false)
+                        .footer(CreateEmbedFooter::new(comment.origin())),
+                ),
+            )
+            .await
+            .wrap_err("failed to send comment notification")?;
+
+        Ok(())
+    }
+
+    async fn comment_reported(
+        &self,
+        report: &CommentReportNotification,
+    ) -> Result<(), eyre::Error> {
+        let channel_id = self.channel_id().await?;
+
+        let url = post_url(&self.site_url, &report.slug);
+        channel_id
+            .send_message(
+                &self.http,
+                CreateMessage::new().embed(
+                    CreateEmbed::new()
+                        .title(report.title())
+                        .url(&url)
+                        .description(report.description())
+                        .field("Moderate", format!("[Open the post]({url})"), false)
+                        .footer(CreateEmbedFooter::new(report.footer())),
+                ),
+            )
+            .await
+            .wrap_err("failed to send comment report notification")?;
+
+        Ok(())
+    }
+}
+
+impl DiscordNotifier {
+    async fn channel_id(&self) -> Result<ChannelId, eyre::Error> {
+        Ok(match &self.target {
+            CommentNotificationTarget::Channel(id) => ChannelId::new(*id),
+            CommentNotificationTarget::User(id) => {
+                UserId::new(*id)
+                    .create_dm_channel(&self.http)
+                    .await
+                    .wrap_err("failed to open DM channel")?
+                    .id
+            }
+            CommentNotificationTarget::Webhook(_) => {
+                eyre::bail!("webhooks are not sent through the bot")
+            }
+        })
+    }
+}
+
+/// Posts the notifications to a Discord-compatible webhook
+struct WebhookNotifier {
+    url: String,
+    site_url: String,
+    client: HttpClient,
+}
+
+#[async_trait]
+impl CommentNotifier for WebhookNotifier {
+    async fn new_comment(&self, comment: &NewCommentNotification) -> Result<(), eyre::Error> {
+        let url = post_url(&self.site_url, &comment.slug);
+        self.client
+            .post(&self.url)
+            .json(&json!({
+                "embeds": [{
+                    "title": comment.title(),
+                    "url": url,
+                    "description": comment.preview(),
+                    "fields": [{ "name": "Moderate", "value": format!("[Open the post]({url})") }],
+                    "footer": { "text": comment.origin() },
+                }]
+            }))
+            .send()
+            .await
+            .wrap_err("failed to send comment webhook")?
+            .error_for_status()
+            .wrap_err("comment webhook failed")?;
+
+        Ok(())
+    }
+
+    async fn comment_reported(
+        &self,
+        report: &CommentReportNotification,
+    ) -> Result<(), eyre::Error> {
+        let url = post_url(&self.site_url, &report.slug);
+        self.client
+            .post(&self.url)
+            .json(&json!({
+                "embeds": [{
+                    "title": report.title(),
+                    "url": url,
+                    "description": report.description(),
+                    "fields": [{ "name": "Moderate", "value": format!("[Open the post]({url})") }],
+                    "footer": { "text": report.footer() },
+                }]
+            }))
+            .send()
+            .await
+            .wrap_err("failed to send comment report webhook")?
+            .error_for_status()
+            .wrap_err("comment report webhook failed")?;
+
+        Ok(())
+    }
+}
+
+fn preview(content: &str) -> String {
+    let mut preview = content.chars().take(PREVIEW_CHARS).collect::<String>();
+    if content.chars().count() > PREVIEW_CHARS {
+        preview.push('…');
+    }
+    preview
+}
@@ -0,0 +1,258 @@
+//! Open Graph images of the posts, a card with the title and the reading time
+//! rendered from an SVG template so that every post has one without designing
+//! it by hand
+
+use std::{
+    sync::{Arc, LazyLock},
+    time::Duration,
+};
+
+use axum::{
+    Router,
+    body::Bytes,
+    extract::{Path, State},
+    http::{HeaderMap, StatusCode, header},
+    response::{IntoResponse, Response},
+    routing::get,
+};
+use diesel::prelude::*;
+use diesel_async::RunQueryDsl;
+use resvg::{tiny_skia, usvg};
+use sha2::{Digest as _, Sha256};
+
+use crate::{App, error::AppError, schema::blog_posts};
+
+const WIDTH: u32 = 1200;
+const HEIGHT: u32 = 630;
+const TITLE_FONT_SIZE: u32 = 64;
+/// Characters of a title line, the average glyph is about 0.6em wide
+const TITLE_LINE_CHARS: usize = 26;
+const TITLE_MAX_LINES: usize = 3;
+/// Changed along with the template so that the cached images are replaced
+const TEMPLATE_VERSION: &str = "1";
+/// The image only changes with the title or the reading time, and the ETag
+/// lets the clients revalidate cheaply once it expires
+const CACHE_CONTROL: &str = "public, max-age=86400, stale-while-revalidate=604800";
+const CACHE_DURATION: Duration = Duration::from_hours(24);
+
+/// DejaVu is installed in the Docker image, the others are used if available
+const FONT_FAMILY: &str = "Inter, 'Helvetica Neue', Arial, 'DejaVu Sans', sans-serif";
+
+/// Loading the system fonts takes a while, it's done once
+static FONTS: LazyLock<Arc<usvg::fontdb::Database>> = LazyLock::new(|| {
+    let mut fonts = usvg::fontdb::Database::new();
+    fonts.load_system_fonts();
+    Arc::new(fonts)
+});
+
+/// Served outside of the versioned API so that the URLs in the pages' meta
+/// tags never change
+pub fn route() -> Router<App> {
+    Router::<App>::new().route("/og/{file}", get(get_og_image))
+}
+
+struct Card {
+    title: String,
+    reading_time_mins: Option<i32>,
+    site: String,
+}
+
+impl Card {
+    /// Identifies the rendered image, for the cache and the ETag
+    fn etag(&self) -> String {
+        let hash = Sha256::digest(
+            format!(
+                "{TEMPLATE_VERSION}\0{}\0{:?}\0{}",
+                self.title, self.reading_time_mins, self.site
+            )
+            .as_bytes(),
+        );
+        let hex = hash[..8]
+            .iter()
+            .map(|b| format!("{b:02x}"))
+            .collect::<String>();
+        format!("\"{hex}\"")
+    }
+}
+
+async fn get_og_image(
+    State(ctx): State<App>,
+    Path(file): Path<String>,
+    headers: HeaderMap,
+) -> Result<Response, AppError> {
+    let slug = file
+        .strip_suffix(".png")
+        .ok_or(("Not found", StatusCode::NOT_FOUND))?;
+
+    let mut conn = ctx.diesel.get().await?;
+
+    let (title, reading_time_mins) = blog_posts::table
+        .filter(blog_posts::category.eq("blog"))
+        .filter(blog_posts::slug.eq(slug))
+        .select((blog_posts::title, blog_posts::reading_time_mins))
+        .first::<(Option<String>, Option<i32>)>(&mut conn)
+        .await
+        .optional()?
+        .ok_or(("Post not found", StatusCode::NOT_FOUND))?;
+
+    let card = Card {
+        // Until the meta is generated the slug reads well enough
+        title: title.unwrap_or_else(|| slug.replace('-', " ")),
+        reading_time_mins,
+        site: url::Url::parse(&ctx.config.site_url)
+            .ok()
+            .and_then(|url| url.host_str().map(str::to_string))
+            .unwrap_or(ctx.config.site_url.clone()),
+    };
+    let etag = card.etag();
+
+    let cache_headers = [
+        (header::CACHE_CONTROL, CACHE_CONTROL.to_string()),
+        (header::ETAG, etag.clone()),
+    ];
+
+    if headers
+        .get(header::IF_NONE_MATCH)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.split(',').any(|tag| tag.trim() == etag))
+    {
+        return Ok((StatusCode::NOT_MODIFIED, cache_headers).into_response());
+    }
+
+    let cache_key = format!("{slug}\0{etag}");
+    let png = match ctx.og_image_cache.get(&cache_key).await {
+        Some(png) => png.clone(),
+        None => {
+            let png = tokio::task::spawn_blocking(move || render(&card))
+                .await
+                .map_err(eyre::Error::from)??;
+            let png = Bytes::from(png);
+            ctx.og_image_cache
+                .insert(cache_key, png.clone(), CACHE_DURATION)
+                .await;
+            png
+        }
+    };
+
+    Ok((
+        [(header::CONTENT_TYPE, "image/png".to_string())],
+        cache_headers,
+        png,
+    )
+        .into_response())
+}
+
+fn render(card: &Card) -> Result<Vec<u8>, eyre::Error> {
+    let options = usvg::Options {
+        fontdb: FONTS.clone(),
+        ..Default::default()
+    };
+    let tree = usvg::Tree::from_str(&svg(card), &options)?;
+
+    let mut pixmap =
+        tiny_skia::Pixmap::new(WIDTH, HEIGHT).ok_or_else(|| eyre::eyre!("invalid image size"))?;
+    resvg::render(&tree, tiny_skia::Transform::default(), &mut pixmap.as_mut());
+
+    Ok(pixmap.encode_png()?)
+}
+
+fn svg(card: &Card) -> String {
+    let lines = wrap(&card.title, TITLE_LINE_CHARS, TITLE_MAX_LINES);
+    let line_height = TITLE_FONT_SIZE * 5 / 4;
+    // The title is centered vertically above the footer
+    let lines_height = u32::try_from(lines.len()).unwrap_or(1) * line_height;
+    let first_baseline = (HEIGHT - 120).saturating_sub(lines_height) / 2 + TITLE_FONT_SIZE;
+
+    let title = lines
+        .iter()
+        .enumerate()
+        .map(|(i, line)| {
+            let y = first_baseline + u32::try_from(i).unwrap_or(0) * line_height;
+            format!(r#"<tspan x="80" y="{y}">{}</tspan>"#, escape(line))
+        })
+        .collect::<String>();
+
+    let reading_time = card
+        .reading_time_mins
+        .map(|mins| format!("{mins} min read"))
+        .unwrap_or_default();
+
+    format!(
+        r##"<svg xmlns="http://www.w3.org/2000/svg" width="{WIDTH}" height="{HEIGHT}" viewBox="0 0 {WIDTH} {HEIGHT}">
+  <defs>
+    <linearGradient id="background" x1="0" y1="0" x2="1" y2="1">
+      <stop offset="0" stop-color="#0f172a"/>
+      <stop offset="1" stop-color="#1e293b"/>
+    </linearGradient>
+  </defs>
+  <rect width="{WIDTH}" height="{HEIGHT}" fill="url(#background)"/>
+  <rect x="80" y="60" width="96" height="8" rx="4" fill="#f97316"/>
+  <text font-family="{FONT_FAMILY}" font-size="{TITLE_FONT_SIZE}" font-weight="bold" fill="#f8fafc">{title}</text>
+  <text x="80" y="{footer}" font-family="{FONT_FAMILY}" font-size="32" fill="#94a3b8">{reading_time}</text>
+  <text x="{right}" y="{footer}" text-anchor="end" font-family="{FONT_FAMILY}" font-size="32" font-weight="bold" fill="#f8fafc">{site}</text>
+</svg>"##,
+        footer = HEIGHT - 70,
+        right = WIDTH - 80,
+        site = escape(&card.site),
+    )
+}
+
+/// Breaks the text into lines at the word boundaries, the last line ends with
+/// an ellipsis if the text doesn't fit
+fn wrap(text: &str, line_chars: usize, max_lines: usize) -> Vec<String> {
+    let mut lines: Vec<String> = Vec::new();
+    for word in text.split_whitespace() {
+        let fits = lines
+            .last()
+            .is_some_and(|line| line.chars().count() + 1 + word.chars().count() <= line_chars);
+        let full = lines.len() == max_lines;
+        match lines.last_mut() {
+            Some(line) if fits => {
+                line.push(' ');
+                line.push_str(word);
+            }
+            Some(line) if full => {
+                let kept = line.chars().take(line_chars - 1).collect::<String>();
+                *line = format!("{}…", kept.trim_end());
+                break;
+            }
+            _ => lines.push(word.chars().take(line_chars).collect()),
+        }
+    }
+
+    lines
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cards_render_with_wrapped_titles() {
+        assert_eq!(
+            wrap("Writing a database in Rust from scratch", 26, 3),
+            vec!["Writing a database in Rust", "from scratch"]
+        );
+        assert_eq!(
+            wrap("one two three four five six", 9, 2),
+            vec!["one two", "three…"]
+        );
+
+        let png = render(&Card {
+            title: "Tom & Jerry's <guide>".to_string(),
+            reading_time_mins: Some(7),
+            site: "wrx.sh".to_string(),
+        })
+        .expect("renderable");
+        let image = image::load_from_memory(&png).expect("a valid PNG");
+        assert_eq!((image.width(), image.height()), (WIDTH, HEIGHT));
+    }
+}
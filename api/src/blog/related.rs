@@ -0,0 +1,184 @@
+//! Further reading of a post: the other posts and the crawled articles whose
+//! chunks are the most similar to the ones of the post
+
+use axum::{
+    Json,
+    extract::{Path, Query, State},
+    http::StatusCode,
+};
+use diesel::prelude::*;
+use diesel::sql_types::{BigInt, Float8, Integer, Nullable, Text};
+use diesel_async::{AsyncPgConnection, RunQueryDsl};
+use pgvector::Vector;
+use serde::Deserialize;
+use utoipa::IntoParams;
+
+use crate::{App, error::AppError, schema::blog_posts, utils::RECOMMENDER_EMBEDDING_BITS};
+
+pub use api_models::{RelatedArticle, RelatedPost, RelatedReading};
+
+const DEFAULT_LIMIT: i64 = 5;
+const MAX_LIMIT: i64 = 20;
+
+/// Latest crawled articles compared with the post, so that the cost of the
+/// comparison doesn't grow with the whole crawl history
+const ARTICLE_CANDIDATE_POOL: i64 = 2000;
+
+/// Replaces the chunks of the post with the embeddings of its new content
+pub async fn save_chunks(
+    conn: &mut AsyncPgConnection,
+    post_id: i32,
+    embeddings: &[Vector],
+) -> Result<(), diesel::result::Error> {
+    use crate::schema::blog_post_chunks;
+    use diesel_async::AsyncConnection as _;
+
+    let insert_sql = format!(
+        "INSERT INTO blog_post_chunks (post_id, embedding) VALUES ($1, binary_quantize($2)::BIT({RECOMMENDER_EMBEDDING_BITS}))"
+    );
+
+    conn.transaction(async move |conn| {
+        diesel::delete(blog_post_chunks::table.filter(blog_post_chunks::post_id.eq(post_id)))
+            .execute(conn)
+            .await?;
+
+        for embedding in embeddings {
+            diesel::sql_query(&insert_sql)
+                .bind::<Integer, _>(post_id)
+                .bind::<crate::schema::PgVector, _>(embedding)
+                .execute(conn)
+                .await?;
+        }
+
+        Ok(())
+    })
+    .await
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct Queries {
+    /// Posts and articles returned each, 5 by default and 20 at most
+    limit: Option<i64>,
+}
+
+#[derive(QueryableByName)]
+struct PostRow {
+    #[diesel(sql_type = Text)]
+    slug: String,
+    #[diesel(sql_type = Nullable<Text>)]
+    title: Option<String>,
+    #[diesel(sql_type = Float8)]
+    similarity: f64,
+}
+
+#[derive(QueryableByName)]
+struct ArticleRow {
+    #[diesel(sql_type = Integer)]
+    id: i32,
+    #[diesel(sql_type = Text)]
+    title: String,
+    #[diesel(sql_type = Text)]
+    url: String,
+    #[diesel(sql_type = Float8)]
+    similarity: f64,
+}
+
+#[utoipa::path(
+    get,
+    path = "/{slug}/related",
+    tag = "blog",
+    params(("slug" = String, Path, description = "Slug of the blog post"), Queries),
+    responses(
+        (status = 200, description = "The most similar posts and crawled articles, empty until the post is embedded", body = RelatedReading),
+        (status = 404, description = "The post isn't registered"),
+    ),
+)]
+pub async fn get_related(
+    State(ctx): State<App>,
+    Path(slug): Path<String>,
+    Query(query): Query<Queries>,
+) -> Result<Json<RelatedReading>, AppError> {
+    let limit = query.limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT);
+
+    let mut conn = ctx.diesel.get().await?;
+
+    let post_id = blog_posts::table
+        .filter(blog_posts::category.eq("blog"))
+        .filter(blog_posts::slug.eq(&slug))
+        .select(blog_posts::id)
+        .first::<i32>(&mut conn)
+        .await
+        .optional()?
+        .ok_or(("Post not found", StatusCode::NOT_FOUND))?;
+
+    // Similarity of two documents is the one of their most similar chunks,
+    // the same as between the feed and the history
+    let similarity =
+        format!("MAX(1.0 - ((c.embedding <~> own.embedding) / {RECOMMENDER_EMBEDDING_BITS}.0))");
+
+    let posts = diesel::sql_query(format!(
+        r#"
+        WITH own AS (SELECT embedding FROM blog_post_chunks WHERE post_id = $1)
+        SELECT p.slug, p.title, {similarity}::FLOAT8 AS similarity
+        FROM blog_post_chunks c
+        JOIN blog_posts p ON p.id = c.post_id
+        CROSS JOIN own
+        WHERE p.id <> $1 AND p.category = 'blog'
+        GROUP BY p.id
+        ORDER BY similarity DESC
+        LIMIT $2
+        "#
+    ))
+    .bind::<Integer, _>(post_id)
+    .bind::<BigInt, _>(limit)
+    .load::<PostRow>(&mut conn)
+    .await?;
+
+    // The site's own pages may have been crawled too, they're the posts above
+    let articles = diesel::sql_query(format!(
+        r#"
+        WITH own AS (SELECT embedding FROM blog_post_chunks WHERE post_id = $1),
+        pool AS (
+            SELECT id, title, url
+            FROM online_articles
+            WHERE NOT starts_with(url, $2)
+            ORDER BY created_at DESC
+            LIMIT $3
+        )
+        SELECT a.id, a.title, a.url, {similarity}::FLOAT8 AS similarity
+        FROM pool a
+        JOIN online_article_chunks c ON c.online_article_id = a.id
+        CROSS JOIN own
+        GROUP BY a.id, a.title, a.url
+        ORDER BY similarity DESC
+        LIMIT $4
+        "#
+    ))
+    .bind::<Integer, _>(post_id)
+    .bind::<Text, _>(ctx.config.site_url.trim_end_matches('/'))
+    .bind::<BigInt, _>(ARTICLE_CANDIDATE_POOL)
+    .bind::<BigInt, _>(limit)
+    .load::<ArticleRow>(&mut conn)
+    .await?;
+
+    Ok(Json(RelatedReading {
+        posts: posts
+            .into_iter()
+            .map(|row| RelatedPost {
+                slug: row.slug,
+                title: row.title,
+                similarity: row.similarity,
+            })
+            .collect(),
+        articles: articles
+            .into_iter()
+            .map(|row| RelatedArticle {
+                id: row.id,
+                title: row.title,
+                url: row.url,
+                similarity: row.similarity,
+            })
+            .collect(),
+    }))
+}
@@ -1,19 +1,79 @@
 use axum::{
     Router,
+    extract::DefaultBodyLimit,
     routing::{delete, get, patch, post},
 };
 
-use crate::App;
+use utoipa::OpenApi;
+
+use crate::{App, config::BodyLimits};
+
+use super::{
+    meta::{__path_get_post_meta, get_post_meta, refresh_post_meta},
+    related::{__path_get_related, get_related},
+};
 
 use super::comment::{
-    create::create_comment, delete::delete_comment, get::get_comments, patch::patch_comment,
+    create::{__path_create_comment, create_comment},
+    delete::{__path_delete_comment, delete_comment},
+    get::{__path_get_comments, __path_get_thread, get_comments, get_thread},
+    mention::{__path_get_mentions, __path_mark_mentions_seen, get_mentions, mark_mentions_seen},
+    patch::{__path_patch_comment, patch_comment},
+    report::{__path_report_comment, get_report_queue, report_comment, resolve_reports},
 };
 
-pub fn route() -> Router<App> {
+#[derive(OpenApi)]
+#[openapi(paths(
+    get_comments,
+    get_thread,
+    create_comment,
+    patch_comment,
+    delete_comment,
+    report_comment,
+    get_mentions,
+    mark_mentions_seen,
+    get_post_meta,
+    get_related
+))]
+pub struct ApiDoc;
+
+pub fn route(limits: &BodyLimits) -> Router<App> {
+    let comment_limit = DefaultBodyLimit::max(limits.comments);
+
     // TODO rate limit these public endpoints
     Router::<App>::new()
+        .route("/mentions", get(get_mentions))
+        .route("/mentions/seen", post(mark_mentions_seen))
+        .route("/posts/{slug}/meta", get(get_post_meta))
         .route("/{slug}/comments", get(get_comments))
-        .route("/{slug}/comments", post(create_comment))
-        .route("/{slug}/comments/{id}", patch(patch_comment))
+        .route(
+            "/{slug}/comments",
+            post(create_comment).layer(comment_limit),
+        )
+        .route(
+            "/{slug}/comments/{id}",
+            patch(patch_comment).layer(comment_limit),
+        )
         .route("/{slug}/comments/{id}", delete(delete_comment))
+        .route("/{slug}/comments/{id}/thread", get(get_thread))
+        .route("/{slug}/related", get(get_related))
+        .route(
+            "/{slug}/comments/{id}/report",
+            post(report_comment).layer(comment_limit),
+        )
+}
+
+/// The moderation queue of the reported comments and the regeneration of the
+/// post meta, for the owner
+pub fn admin_route() -> Router<App> {
+    Router::<App>::new()
+        .route("/admin/blog/reports", get(get_report_queue))
+        .route(
+            "/admin/blog/reports/{comment_id}/resolve",
+            post(resolve_reports),
+        )
+        .route(
+            "/admin/blog/posts/{slug}/meta/refresh",
+            post(refresh_post_meta),
+        )
 }
@@ -0,0 +1,65 @@
+use std::path::PathBuf;
+
+use clap::{Parser, Subcommand};
+
+/// The wonrax.com API server. The settings are read from the config file, then
+/// the environment (and `.env`), then the `--set` overrides, each layer
+/// overriding the previous one.
+#[derive(Parser, Debug)]
+#[command(version)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
+    /// TOML config file, `config.toml` is read if it exists
+    #[arg(long, global = true)]
+    pub config: Option<PathBuf>,
+
+    /// Overrides a setting, e.g. `--set SITE_URL=https://example.com`
+    #[arg(long = "set", value_name = "KEY=VALUE", value_parser = parse_override, global = true)]
+    pub overrides: Vec<(String, String)>,
+
+    /// Report the configuration problems and exit without serving
+    #[arg(long)]
+    pub check_config: bool,
+
+    /// Fake the third-party APIs to work without network access
+    #[arg(long)]
+    pub offline: bool,
+
+    /// Run only the HTTP server or only the background jobs and the Discord
+    /// bot, same as `--set RUN_MODE=<mode>`
+    #[arg(long, value_parser = ["all", "server", "worker"])]
+    pub mode: Option<String>,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Copy the Discord bot's memories from Chroma to Qdrant
+    MigrateMemories,
+    /// Fill a development database with fake data
+    Seed,
+    /// Rebuild the HNSW index of the article embeddings and report its recall
+    /// and latency before and after
+    ReindexEmbeddings {
+        /// Connections per layer of the graph
+        #[arg(long, default_value_t = 16)]
+        m: u32,
+        /// Candidates kept while building the graph, at least twice `m`
+        #[arg(long, default_value_t = 64)]
+        ef_construction: u32,
+        /// Chunks sampled as the queries to measure the index with
+        #[arg(long, default_value_t = 100)]
+        samples: u32,
+        /// Neighbours searched per query
+        #[arg(long, default_value_t = 10)]
+        k: u32,
+    },
+}
+
+fn parse_override(s: &str) -> Result<(String, String), String> {
+    let (key, value) = s
+        .split_once('=')
+        .ok_or_else(|| format!("expected KEY=VALUE, got `{s}`"))?;
+    Ok((key.trim().to_uppercase(), value.to_string()))
+}
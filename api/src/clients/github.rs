@@ -0,0 +1,185 @@
+use async_trait::async_trait;
+use eyre::Context as _;
+use serde::Deserialize;
+
+use crate::{config::GitHubOauth, outbound::HttpClient};
+
+const AUTHORIZE_URL: &str = "https://github.com/login/oauth/authorize";
+const ACCESS_TOKEN_URL: &str = "https://github.com/login/oauth/access_token";
+const API_URL: &str = "https://api.github.com";
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct GitHubUser {
+    pub id: i64,
+    pub login: String,
+    /// Some users don't have a name set
+    pub name: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct GitHubEmail {
+    pub email: String,
+    pub primary: bool,
+    pub verified: bool,
+}
+
+/// The head commit of a push to a public repository
+#[derive(Debug, Clone)]
+pub struct GitHubPush {
+    /// `owner/name`
+    pub repo: String,
+    pub sha: String,
+    /// Left out of the events of some pushes
+    pub message: Option<String>,
+    pub pushed_at: chrono::DateTime<chrono::Utc>,
+}
+
+/// The OAuth flow and the user endpoints of GitHub
+#[async_trait]
+pub trait GitHubClient: Send + Sync {
+    /// Where to send the user to authorize the app
+    fn authorize_url(&self, client_id: &str, redirect_uri: &str) -> Result<String, eyre::Error>;
+
+    /// Exchange the code of the OAuth callback for an access token, `None` if
+    /// GitHub refused the code
+    async fn access_token(
+        &self,
+        oauth: &GitHubOauth,
+        code: &str,
+    ) -> Result<Option<String>, eyre::Error>;
+
+    async fn user(&self, access_token: &str) -> Result<GitHubUser, eyre::Error>;
+
+    async fn emails(&self, access_token: &str) -> Result<Vec<GitHubEmail>, eyre::Error>;
+
+    /// The latest push of the user to a public repository among their recent
+    /// public events
+    async fn latest_push(&self, user_id: i64) -> Result<Option<GitHubPush>, eyre::Error>;
+}
+
+pub(super) struct HttpGitHub {
+    http: HttpClient,
+}
+
+impl HttpGitHub {
+    pub fn new(http: HttpClient) -> Self {
+        Self { http }
+    }
+
+    async fn get<T: serde::de::DeserializeOwned>(
+        &self,
+        path: &str,
+        access_token: Option<&str>,
+    ) -> Result<T, eyre::Error> {
+        let mut request = self
+            .http
+            .get(format!("{API_URL}{path}"))
+            .header("Accept", "application/json");
+        // The public endpoints are called without one
+        if let Some(access_token) = access_token {
+            request = request.bearer_auth(access_token);
+        }
+        request
+            .send()
+            .await
+            .wrap_err_with(|| format!("failed to request GitHub {path}"))?
+            .error_for_status()
+            .wrap_err_with(|| format!("GitHub {path} failed"))?
+            .json()
+            .await
+            .wrap_err_with(|| format!("GitHub returned unexpected response for {path}"))
+    }
+}
+
+#[derive(Deserialize)]
+struct AccessTokenResponse {
+    access_token: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct Event {
+    #[serde(rename = "type")]
+    kind: String,
+    repo: EventRepo,
+    payload: serde_json::Value,
+    created_at: chrono::DateTime<chrono::Utc>,
+}
+
+#[derive(Deserialize)]
+struct EventRepo {
+    name: String,
+}
+
+#[async_trait]
+impl GitHubClient for HttpGitHub {
+    fn authorize_url(&self, client_id: &str, redirect_uri: &str) -> Result<String, eyre::Error> {
+        Ok(reqwest::Url::parse_with_params(
+            AUTHORIZE_URL,
+            &[
+                ("client_id", client_id),
+                ("scope", "user:email"),
+                ("redirect_uri", redirect_uri),
+            ],
+        )
+        .wrap_err("failed to build GitHub authorize URL")?
+        .to_string())
+    }
+
+    async fn access_token(
+        &self,
+        oauth: &GitHubOauth,
+        code: &str,
+    ) -> Result<Option<String>, eyre::Error> {
+        let response: AccessTokenResponse = self
+            .http
+            .post(ACCESS_TOKEN_URL)
+            .header("Accept", "application/json")
+            .json(&serde_json::json!({
+                "client_id": oauth.client_id,
+                "client_secret": oauth.client_secret,
+                "code": code
+            }))
+            .send()
+            .await
+            .wrap_err("failed to exchange GitHub OAuth code")?
+            .json()
+            .await
+            .wrap_err("failed to parse GitHub access token response")?;
+
+        Ok(response.access_token)
+    }
+
+    async fn user(&self, access_token: &str) -> Result<GitHubUser, eyre::Error> {
+        self.get("/user", Some(access_token)).await
+    }
+
+    async fn emails(&self, access_token: &str) -> Result<Vec<GitHubEmail>, eyre::Error> {
+        self.get("/user/emails", Some(access_token)).await
+    }
+
+    async fn latest_push(&self, user_id: i64) -> Result<Option<GitHubPush>, eyre::Error> {
+        // The events are only listed by login, which can change
+        let user: GitHubUser = self.get(&format!("/user/{user_id}"), None).await?;
+        let events: Vec<Event> = self
+            .get(&format!("/users/{}/events/public", user.login), None)
+            .await?;
+
+        Ok(events
+            .into_iter()
+            .filter(|event| event.kind == "PushEvent")
+            .find_map(|event| {
+                let sha = event.payload.get("head")?.as_str()?.to_string();
+                let message = event
+                    .payload
+                    .get("commits")
+                    .and_then(|commits| commits.as_array()?.last()?.get("message")?.as_str())
+                    .map(str::to_string);
+                Some(GitHubPush {
+                    repo: event.repo.name,
+                    sha,
+                    message,
+                    pushed_at: event.created_at,
+                })
+            }))
+    }
+}
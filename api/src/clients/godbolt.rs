@@ -0,0 +1,156 @@
+use async_trait::async_trait;
+use eyre::{Context as _, eyre};
+use reqwest::header::ACCEPT;
+use serde_json::{Value, json};
+
+use crate::outbound::HttpClient;
+
+const BASE_URL: &str = "https://godbolt.org";
+/// Errors come as plain text
+const ACCEPTED: &str = "application/json, text/plain;q=0.8, */*;q=0.5";
+
+/// The response of a compilation, Compiler Explorer answers with plain text
+/// when the request itself is wrong
+pub enum CompileResponse {
+    Json(Value),
+    Text(String),
+}
+
+/// The Compiler Explorer API
+#[async_trait]
+pub trait GodboltClient: Send + Sync {
+    async fn compile(
+        &self,
+        compiler_id: &str,
+        payload: &Value,
+    ) -> Result<CompileResponse, eyre::Error>;
+
+    async fn formatters(&self) -> Result<Value, eyre::Error>;
+
+    async fn format(&self, formatter: &str, source: &str) -> Result<Value, eyre::Error>;
+
+    async fn languages(&self) -> Result<Value, eyre::Error>;
+
+    async fn compilers(&self, language_id: &str) -> Result<Value, eyre::Error>;
+
+    async fn libraries(&self, language_id: &str) -> Result<Value, eyre::Error>;
+
+    async fn asm_doc(&self, instruction_set: &str, opcode: &str) -> Result<String, eyre::Error>;
+
+    async fn version(&self) -> Result<String, eyre::Error>;
+}
+
+pub(super) struct HttpGodbolt {
+    http: HttpClient,
+}
+
+impl HttpGodbolt {
+    pub fn new(http: HttpClient) -> Self {
+        Self { http }
+    }
+
+    async fn get(&self, path: &str) -> Result<reqwest::Response, eyre::Error> {
+        self.http
+            .get(format!("{BASE_URL}{path}"))
+            .header(ACCEPT, ACCEPTED)
+            .send()
+            .await
+            .wrap_err_with(|| format!("failed to request Compiler Explorer {path}"))
+    }
+
+    async fn get_json(&self, path: &str) -> Result<Value, eyre::Error> {
+        self.get(path)
+            .await?
+            .json()
+            .await
+            .wrap_err_with(|| format!("failed to parse Compiler Explorer {path}"))
+    }
+
+    async fn get_text(&self, path: &str) -> Result<String, eyre::Error> {
+        self.get(path)
+            .await?
+            .text()
+            .await
+            .wrap_err_with(|| format!("failed to read Compiler Explorer {path}"))
+    }
+}
+
+#[async_trait]
+impl GodboltClient for HttpGodbolt {
+    async fn compile(
+        &self,
+        compiler_id: &str,
+        payload: &Value,
+    ) -> Result<CompileResponse, eyre::Error> {
+        let res = self
+            .http
+            .post(format!("{BASE_URL}/api/compiler/{compiler_id}/compile"))
+            .header(ACCEPT, ACCEPTED)
+            .json(payload)
+            .send()
+            .await
+            .wrap_err("failed to request the compilation")?;
+
+        let is_json = res
+            .headers()
+            .get(reqwest::header::CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .is_some_and(|ct| ct.starts_with("application/json"));
+
+        if !is_json {
+            return Ok(CompileResponse::Text(res.text().await.unwrap_or_default()));
+        }
+
+        let bytes = res
+            .bytes()
+            .await
+            .wrap_err("failed to read the compilation")?;
+        let data = serde_json::from_slice(&bytes).map_err(|e| {
+            eyre!(
+                "failed to parse CE json: {e}: {}",
+                String::from_utf8_lossy(&bytes)
+            )
+        })?;
+        Ok(CompileResponse::Json(data))
+    }
+
+    async fn formatters(&self) -> Result<Value, eyre::Error> {
+        self.get_json("/api/formats").await
+    }
+
+    async fn format(&self, formatter: &str, source: &str) -> Result<Value, eyre::Error> {
+        self.http
+            .post(format!("{BASE_URL}/api/format/{formatter}"))
+            .header(ACCEPT, ACCEPTED)
+            .json(&json!({ "source": source }))
+            .send()
+            .await
+            .wrap_err("failed to request the formatting")?
+            .json()
+            .await
+            .wrap_err("failed to parse the formatting")
+    }
+
+    async fn languages(&self) -> Result<Value, eyre::Error> {
+        self.get_json("/api/languages").await
+    }
+
+    async fn compilers(&self, language_id: &str) -> Result<Value, eyre::Error> {
+        self.get_json(&format!("/api/compilers/{language_id}"))
+            .await
+    }
+
+    async fn libraries(&self, language_id: &str) -> Result<Value, eyre::Error> {
+        self.get_json(&format!("/api/libraries/{language_id}"))
+            .await
+    }
+
+    async fn asm_doc(&self, instruction_set: &str, opcode: &str) -> Result<String, eyre::Error> {
+        self.get_text(&format!("/api/asm/{instruction_set}/{opcode}"))
+            .await
+    }
+
+    async fn version(&self) -> Result<String, eyre::Error> {
+        self.get_text("/api/version").await
+    }
+}
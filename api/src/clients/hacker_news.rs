@@ -0,0 +1,75 @@
+use async_trait::async_trait;
+use eyre::Context as _;
+use serde::Deserialize;
+
+use crate::outbound::HttpClient;
+
+const API_URL: &str = "https://hacker-news.firebaseio.com/v0";
+
+/// A story or a comment, the comments have no title nor score
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct HackerNewsItem {
+    pub id: i64,
+    #[serde(default)]
+    pub title: String,
+    pub url: Option<String>,
+    #[serde(default)]
+    pub score: i64,
+    pub r#type: String,
+    pub time: i64,
+    /// HTML text of the submission or comment, if it has one
+    #[serde(default)]
+    pub text: Option<String>,
+    /// The replies, in their ranked order
+    #[serde(default)]
+    pub kids: Vec<i64>,
+    /// Comments of the story in all
+    #[serde(default)]
+    pub descendants: Option<i64>,
+    /// Flagged or killed
+    #[serde(default)]
+    pub dead: bool,
+}
+
+#[async_trait]
+pub trait HackerNewsClient: Send + Sync {
+    /// IDs of the current top stories, best first
+    async fn top_stories(&self) -> Result<Vec<i64>, eyre::Error>;
+
+    async fn item(&self, id: i64) -> Result<HackerNewsItem, eyre::Error>;
+}
+
+pub(super) struct HttpHackerNews {
+    http: HttpClient,
+}
+
+impl HttpHackerNews {
+    pub fn new(http: HttpClient) -> Self {
+        Self { http }
+    }
+}
+
+#[async_trait]
+impl HackerNewsClient for HttpHackerNews {
+    async fn top_stories(&self) -> Result<Vec<i64>, eyre::Error> {
+        self.http
+            .get(format!("{API_URL}/topstories.json"))
+            .send()
+            .await
+            .wrap_err("failed to fetch Hacker News top stories")?
+            .json()
+            .await
+            .wrap_err("failed to parse Hacker News top stories")
+    }
+
+    async fn item(&self, id: i64) -> Result<HackerNewsItem, eyre::Error> {
+        self.http
+            .get(format!("{API_URL}/item/{id}.json"))
+            .send()
+            .await
+            .wrap_err("failed to fetch Hacker News item")?
+            .json()
+            .await
+            .wrap_err("failed to parse Hacker News item")
+    }
+}
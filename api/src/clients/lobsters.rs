@@ -0,0 +1,84 @@
+use async_trait::async_trait;
+use eyre::Context as _;
+use serde::Deserialize;
+
+use crate::outbound::HttpClient;
+
+const HOTTEST_URL: &str = "https://lobste.rs/hottest.json";
+const STORY_URL: &str = "https://lobste.rs/s";
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct LobstersStory {
+    pub short_id: String,
+    pub title: String,
+    pub url: String,
+    pub score: i64,
+    pub created_at: String,
+    /// HTML text of the submission, empty if it has none
+    #[serde(default)]
+    pub description: String,
+    #[serde(default)]
+    pub comment_count: i64,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct LobstersComment {
+    /// Markdown of the comment
+    #[serde(default)]
+    pub comment_plain: String,
+    pub score: i64,
+    #[serde(default)]
+    pub is_deleted: bool,
+}
+
+#[derive(Deserialize)]
+struct LobstersStoryComments {
+    #[serde(default)]
+    comments: Vec<LobstersComment>,
+}
+
+#[async_trait]
+pub trait LobstersClient: Send + Sync {
+    /// A page of the hottest stories, starting from 1
+    async fn hottest(&self, page: u32) -> Result<Vec<LobstersStory>, eyre::Error>;
+
+    /// The comments of a story, threaded in the order of the page
+    async fn comments(&self, short_id: &str) -> Result<Vec<LobstersComment>, eyre::Error>;
+}
+
+pub(super) struct HttpLobsters {
+    http: HttpClient,
+}
+
+impl HttpLobsters {
+    pub fn new(http: HttpClient) -> Self {
+        Self { http }
+    }
+}
+
+#[async_trait]
+impl LobstersClient for HttpLobsters {
+    async fn hottest(&self, page: u32) -> Result<Vec<LobstersStory>, eyre::Error> {
+        self.http
+            .get(format!("{HOTTEST_URL}/?page={page}"))
+            .send()
+            .await
+            .wrap_err("failed to fetch Lobsters stories")?
+            .json()
+            .await
+            .wrap_err("failed to parse Lobsters stories")
+    }
+
+    async fn comments(&self, short_id: &str) -> Result<Vec<LobstersComment>, eyre::Error> {
+        Ok(self
+            .http
+            .get(format!("{STORY_URL}/{short_id}.json"))
+            .send()
+            .await
+            .wrap_err("failed to fetch Lobsters comments")?
+            .json::<LobstersStoryComments>()
+            .await
+            .wrap_err("failed to parse Lobsters comments")?
+            .comments)
+    }
+}
@@ -0,0 +1,63 @@
+//! Thin clients of the third-party APIs. They are behind traits so that tests
+//! and the `--offline` mode can use the fakes of [offline] instead.
+
+mod github;
+mod godbolt;
+mod hacker_news;
+mod lobsters;
+pub mod offline;
+mod raindrop;
+mod s3;
+
+use std::sync::Arc;
+
+pub use github::{GitHubClient, GitHubEmail, GitHubPush, GitHubUser};
+pub use godbolt::{CompileResponse, GodboltClient};
+pub use hacker_news::{HackerNewsClient, HackerNewsItem};
+pub use lobsters::{LobstersClient, LobstersComment, LobstersStory};
+pub use raindrop::{RaindropClient, RaindropHighlight};
+pub use s3::ObjectStorage;
+
+use crate::{config::ServerConfig, outbound::HttpClient};
+
+#[derive(Clone)]
+pub struct Clients {
+    pub raindrop: Arc<dyn RaindropClient>,
+    pub github: Arc<dyn GitHubClient>,
+    pub hacker_news: Arc<dyn HackerNewsClient>,
+    pub lobsters: Arc<dyn LobstersClient>,
+    pub godbolt: Arc<dyn GodboltClient>,
+    /// Bucket of the uploaded assets, uploads are disabled if not configured
+    pub storage: Option<Arc<dyn ObjectStorage>>,
+}
+
+impl Clients {
+    pub fn live(config: &ServerConfig, http: HttpClient) -> Self {
+        Self {
+            raindrop: Arc::new(raindrop::HttpRaindrop::new(
+                http.clone(),
+                config.raindrop_api_token.clone(),
+            )),
+            github: Arc::new(github::HttpGitHub::new(http.clone())),
+            hacker_news: Arc::new(hacker_news::HttpHackerNews::new(http.clone())),
+            lobsters: Arc::new(lobsters::HttpLobsters::new(http.clone())),
+            godbolt: Arc::new(godbolt::HttpGodbolt::new(http.clone())),
+            storage: config
+                .asset_storage
+                .clone()
+                .map(|storage| Arc::new(s3::HttpS3::new(http, storage)) as Arc<dyn ObjectStorage>),
+        }
+    }
+
+    /// Fakes answering without any network access, for developing offline
+    pub fn offline() -> Self {
+        Self {
+            raindrop: Arc::new(offline::OfflineRaindrop::sample()),
+            github: Arc::new(offline::OfflineGitHub),
+            hacker_news: Arc::new(offline::OfflineHackerNews::default()),
+            lobsters: Arc::new(offline::OfflineLobsters::default()),
+            godbolt: Arc::new(offline::OfflineGodbolt),
+            storage: None,
+        }
+    }
+}
@@ -0,0 +1,205 @@
+//! Fakes of the clients answering with canned data
+
+use async_trait::async_trait;
+use axum::body::Bytes;
+use eyre::{Context as _, OptionExt as _};
+use serde_json::Value;
+
+use super::{
+    CompileResponse, GitHubClient, GitHubEmail, GitHubPush, GitHubUser, GodboltClient,
+    HackerNewsClient, HackerNewsItem, LobstersClient, LobstersComment, LobstersStory,
+    RaindropClient, RaindropHighlight,
+};
+use crate::config::GitHubOauth;
+
+pub struct OfflineRaindrop {
+    pub highlights: Vec<RaindropHighlight>,
+}
+
+impl OfflineRaindrop {
+    /// A couple of highlights to have something to render
+    pub fn sample() -> Self {
+        let highlight = |id: &str, text: &str, note: &str, created_at: &str| RaindropHighlight {
+            id: id.to_string(),
+            title: "Offline sample".to_string(),
+            text: text.to_string(),
+            note: note.to_string(),
+            color: "yellow".to_string(),
+            created_at: created_at.to_string(),
+            link: "https://example.com/".to_string(),
+            tags: vec!["offline".to_string()],
+            raindrop_ref: 1,
+        };
+
+        Self {
+            highlights: vec![
+                highlight(
+                    "offline-1",
+                    "This highlight is served by the offline mode.",
+                    "",
+                    "2026-01-01T00:00:00.000Z",
+                ),
+                highlight(
+                    "offline-2",
+                    "Highlights can have notes too.",
+                    "A note",
+                    "2026-01-02T00:00:00.000Z",
+                ),
+            ],
+        }
+    }
+}
+
+#[async_trait]
+impl RaindropClient for OfflineRaindrop {
+    async fn highlights(
+        &self,
+        _collection_id: u64,
+        page: usize,
+        per_page: usize,
+    ) -> Result<Vec<RaindropHighlight>, eyre::Error> {
+        Ok(self
+            .highlights
+            .iter()
+            .skip(page * per_page)
+            .take(per_page)
+            .cloned()
+            .collect())
+    }
+
+    async fn collection_rss(&self, _collection_id: u64) -> Result<Bytes, eyre::Error> {
+        Ok(Bytes::from_static(
+            br#"<?xml version="1.0" encoding="UTF-8"?><rss version="2.0"><channel><title>Offline</title></channel></rss>"#,
+        ))
+    }
+}
+
+/// Logs in anyone as the same user, skipping the GitHub authorization page
+pub struct OfflineGitHub;
+
+const OFFLINE_CODE: &str = "offline";
+
+#[async_trait]
+impl GitHubClient for OfflineGitHub {
+    fn authorize_url(&self, _client_id: &str, redirect_uri: &str) -> Result<String, eyre::Error> {
+        let mut url = reqwest::Url::parse(redirect_uri).wrap_err("invalid redirect URI")?;
+        url.query_pairs_mut().append_pair("code", OFFLINE_CODE);
+        Ok(url.to_string())
+    }
+
+    async fn access_token(
+        &self,
+        _oauth: &GitHubOauth,
+        code: &str,
+    ) -> Result<Option<String>, eyre::Error> {
+        Ok((code == OFFLINE_CODE).then(|| OFFLINE_CODE.to_string()))
+    }
+
+    async fn user(&self, _access_token: &str) -> Result<GitHubUser, eyre::Error> {
+        Ok(GitHubUser {
+            id: 1,
+            login: "offline".to_string(),
+            name: Some("Offline Developer".to_string()),
+        })
+    }
+
+    async fn emails(&self, _access_token: &str) -> Result<Vec<GitHubEmail>, eyre::Error> {
+        Ok(vec![GitHubEmail {
+            email: "offline@localhost".to_string(),
+            primary: true,
+            verified: true,
+        }])
+    }
+
+    async fn latest_push(&self, _user_id: i64) -> Result<Option<GitHubPush>, eyre::Error> {
+        Ok(Some(GitHubPush {
+            repo: "offline/website".to_string(),
+            sha: "0000000000000000000000000000000000000000".to_string(),
+            message: Some("Work offline".to_string()),
+            pushed_at: chrono::Utc::now(),
+        }))
+    }
+}
+
+/// No stories by default, so that the crawler has nothing to fetch
+#[derive(Default)]
+pub struct OfflineHackerNews {
+    pub items: Vec<HackerNewsItem>,
+}
+
+#[async_trait]
+impl HackerNewsClient for OfflineHackerNews {
+    async fn top_stories(&self) -> Result<Vec<i64>, eyre::Error> {
+        Ok(self.items.iter().map(|item| item.id).collect())
+    }
+
+    async fn item(&self, id: i64) -> Result<HackerNewsItem, eyre::Error> {
+        self.items
+            .iter()
+            .find(|item| item.id == id)
+            .cloned()
+            .ok_or_eyre("no such Hacker News item")
+    }
+}
+
+/// No stories by default, all of them are on the first page otherwise
+#[derive(Default)]
+pub struct OfflineLobsters {
+    pub stories: Vec<LobstersStory>,
+}
+
+#[async_trait]
+impl LobstersClient for OfflineLobsters {
+    async fn hottest(&self, page: u32) -> Result<Vec<LobstersStory>, eyre::Error> {
+        Ok(if page == 1 {
+            self.stories.clone()
+        } else {
+            Vec::new()
+        })
+    }
+
+    async fn comments(&self, _short_id: &str) -> Result<Vec<LobstersComment>, eyre::Error> {
+        Ok(Vec::new())
+    }
+}
+
+pub struct OfflineGodbolt;
+
+fn godbolt_unavailable<T>() -> Result<T, eyre::Error> {
+    eyre::bail!("Compiler Explorer is not available offline")
+}
+
+#[async_trait]
+impl GodboltClient for OfflineGodbolt {
+    async fn compile(&self, _: &str, _: &Value) -> Result<CompileResponse, eyre::Error> {
+        godbolt_unavailable()
+    }
+
+    async fn formatters(&self) -> Result<Value, eyre::Error> {
+        godbolt_unavailable()
+    }
+
+    async fn format(&self, _: &str, _: &str) -> Result<Value, eyre::Error> {
+        godbolt_unavailable()
+    }
+
+    async fn languages(&self) -> Result<Value, eyre::Error> {
+        godbolt_unavailable()
+    }
+
+    async fn compilers(&self, _: &str) -> Result<Value, eyre::Error> {
+        godbolt_unavailable()
+    }
+
+    async fn libraries(&self, _: &str) -> Result<Value, eyre::Error> {
+        godbolt_unavailable()
+    }
+
+    async fn asm_doc(&self, _: &str, _: &str) -> Result<String, eyre::Error> {
+        godbolt_unavailable()
+    }
+
+    async fn version(&self) -> Result<String, eyre::Error> {
+        godbolt_unavailable()
+    }
+}
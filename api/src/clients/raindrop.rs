@@ -0,0 +1,110 @@
+use async_trait::async_trait;
+use axum::body::Bytes;
+use eyre::{Context as _, OptionExt as _};
+use serde::Deserialize;
+
+use crate::outbound::HttpClient;
+
+const API_URL: &str = "https://api.raindrop.io/rest/v1";
+const RSS_URL: &str = "https://bg.raindrop.io/rss/public";
+
+#[derive(Debug, Clone, Deserialize)]
+#[allow(dead_code)]
+pub struct RaindropHighlight {
+    #[serde(rename = "_id")]
+    pub id: String,
+    pub title: String,
+    pub text: String,
+    pub note: String, // Always a string (empty string if no note)
+    #[serde(default = "default_color")]
+    pub color: String, // Default to yellow if missing
+    #[serde(rename = "created")]
+    pub created_at: String,
+    pub link: String,
+    pub tags: Vec<String>,
+    #[serde(rename = "raindropRef")]
+    pub raindrop_ref: u64,
+}
+
+fn default_color() -> String {
+    "yellow".to_string()
+}
+
+#[async_trait]
+pub trait RaindropClient: Send + Sync {
+    /// A page of the highlights of a collection, starting from 0
+    async fn highlights(
+        &self,
+        collection_id: u64,
+        page: usize,
+        per_page: usize,
+    ) -> Result<Vec<RaindropHighlight>, eyre::Error>;
+
+    /// The public RSS feed of a collection
+    async fn collection_rss(&self, collection_id: u64) -> Result<Bytes, eyre::Error>;
+}
+
+pub(super) struct HttpRaindrop {
+    http: HttpClient,
+    token: Option<String>,
+}
+
+impl HttpRaindrop {
+    pub fn new(http: HttpClient, token: Option<String>) -> Self {
+        Self { http, token }
+    }
+}
+
+#[derive(Deserialize)]
+struct HighlightsResponse {
+    result: bool,
+    items: Vec<RaindropHighlight>,
+}
+
+#[async_trait]
+impl RaindropClient for HttpRaindrop {
+    async fn highlights(
+        &self,
+        collection_id: u64,
+        page: usize,
+        per_page: usize,
+    ) -> Result<Vec<RaindropHighlight>, eyre::Error> {
+        let token = self
+            .token
+            .as_ref()
+            .ok_or_eyre("Raindrop API token not configured")?;
+
+        let response = self
+            .http
+            .get(format!(
+                "{API_URL}/highlights/{collection_id}?page={page}&perpage={per_page}"
+            ))
+            .bearer_auth(token)
+            .send()
+            .await
+            .wrap_err("failed to fetch highlights from Raindrop")?
+            .error_for_status()
+            .wrap_err("failed to fetch highlights from Raindrop")?
+            .json::<HighlightsResponse>()
+            .await
+            .wrap_err("failed to parse highlights response")?;
+
+        if !response.result {
+            eyre::bail!("Raindrop API returned error result");
+        }
+        Ok(response.items)
+    }
+
+    async fn collection_rss(&self, collection_id: u64) -> Result<Bytes, eyre::Error> {
+        self.http
+            .get(format!("{RSS_URL}/{collection_id}"))
+            .send()
+            .await
+            .wrap_err("failed to fetch RSS feed")?
+            .error_for_status()
+            .wrap_err("failed to fetch RSS feed")?
+            .bytes()
+            .await
+            .wrap_err("failed to read RSS feed")
+    }
+}
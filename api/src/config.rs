@@ -1,220 +0,0 @@
-#[derive(Clone)]
-pub enum Env {
-    Dev,
-    Staging,
-    Production,
-}
-
-#[derive(Clone)]
-pub struct ServerConfig {
-    pub env: Env,
-
-    /// Website URL (i.e. frontend) in full form without trailing slash
-    /// e.g. https://example.com
-    pub site_url: String,
-
-    pub github_oauth: Option<GitHubOauth>,
-    pub spotify_oauth: Option<SpotifyOauth>,
-
-    // My ID in the identities table
-    pub owner_identity_id: i32,
-
-    pub discord_token: Option<String>,
-    pub discord_whitelist_channels: Option<Vec<u64>>,
-    pub discord_mention_only: bool,
-    pub openai_api_key: Option<String>,
-    pub raindrop_api_token: Option<String>,
-    pub vector_db: Option<VectorDbConfig>,
-    pub recommender_raindrop_collections: Vec<RecommenderRaindropCollection>,
-}
-
-#[derive(Clone)]
-pub struct GitHubOauth {
-    pub client_id: String,
-    pub client_secret: String,
-}
-
-#[derive(Clone)]
-pub struct SpotifyOauth {
-    pub client_id: String,
-    pub client_secret: String,
-}
-
-#[derive(Clone)]
-pub struct VectorDbConfig {
-    pub url: String,
-    pub token: String,
-    pub database: String,
-    pub default_collection: Option<String>,
-}
-
-#[derive(Clone)]
-pub struct RecommenderRaindropCollection {
-    pub collection_id: String,
-    pub _name: String,
-    pub weight: f32,
-}
-
-fn var(key: &str) -> Result<Option<String>, String> {
-    match std::env::var(key) {
-        Ok(env) => Ok(Some(env)),
-        Err(e) => {
-            tracing::warn!("Mising environment variable `{key}`");
-            match e {
-                std::env::VarError::NotPresent => Ok(None),
-                std::env::VarError::NotUnicode(_) => Err(format!(
-                    "Could not get the environment variable `{key}` due to unicode error"
-                )),
-            }
-        }
-    }
-}
-
-#[allow(dead_code)]
-fn required_var(key: &str) -> String {
-    let val = var(key);
-    match val {
-        Ok(val) => match val {
-            Some(val) => val,
-            None => {
-                tracing::error!("Environment variable `{key}` is required");
-                std::process::exit(1)
-            }
-        },
-        Err(e) => {
-            tracing::error!(
-                "Environment variable `{key}` is required, but could not retrieve: {e}"
-            );
-            std::process::exit(1)
-        }
-    }
-}
-
-/// Either all or none variables are set, otherwise panics
-fn all_or_none_vars(keys: Vec<&str>) -> Option<Vec<String>> {
-    keys.iter().fold(None, |accum, k| match var(k) {
-        Ok(Some(val)) => match accum {
-            Some(mut l) => {
-                l.push(val);
-                Some(l)
-            }
-            None => Some(vec![val]),
-        },
-        _ => match accum {
-            Some(_) => {
-                let mut rest = keys.clone();
-                rest.retain(|_k| _k != k);
-                tracing::error!(
-                    "Environment variable `{k}` is required if variables {rest:?} are present"
-                );
-                std::process::exit(1);
-            }
-            None => None,
-        },
-    })
-}
-
-impl ServerConfig {
-    pub fn new_from_env() -> Self {
-        let github_oauth =
-            all_or_none_vars(vec!["GITHUB_OAUTH_CLIENT_ID", "GITHUB_OAUTH_CLIENT_SECRET"]).map(
-                |mut vars| GitHubOauth {
-                    client_id: vars.remove(0),
-                    client_secret: vars.remove(0),
-                },
-            );
-
-        let spotify_oauth = all_or_none_vars(vec![
-            "SPOTIFY_OAUTH_CLIENT_ID",
-            "SPOTIFY_OAUTH_CLIENT_SECRET",
-        ])
-        .map(|mut vars| SpotifyOauth {
-            client_id: vars.remove(0),
-            client_secret: vars.remove(0),
-        });
-
-        let env = match var("ENVIRONMENT") {
-            Ok(Some(env)) => match env.as_str() {
-                "dev" => Env::Dev,
-                "staging" => Env::Staging,
-                "production" => Env::Production,
-                _ => Env::Dev,
-            },
-            _ => Env::Dev,
-        };
-
-        let site_url = var("SITE_URL")
-            .unwrap_or(Some("http://localhost:4321".to_string()))
-            .unwrap_or("http://localhost:4321".to_string());
-
-        let vector_db = var("CHROMADB_URL")
-            .unwrap_or(None)
-            .map(|url| VectorDbConfig {
-                url,
-                token: var("CHROMADB_API_TOKEN")
-                    .unwrap_or(None)
-                    .unwrap_or("".to_string()),
-                database: var("CHROMADB_DATABASE")
-                    .unwrap_or(Some("wrx-sh-discord-memory".to_string()))
-                    .unwrap_or("wrx-sh".to_string()),
-                default_collection: var("CHROMADB_DEFAULT_COLLECTION").unwrap_or(None),
-            });
-
-        let recommender_raindrop_collections = var("RECOMMENDER_RAINDROP_COLLECTIONS")
-            .unwrap_or(None)
-            .map(|s| {
-                s.split(',')
-                    .filter_map(|part| {
-                        let mut pieces = part.split(':');
-                        let name = pieces.next()?;
-                        let collection_id = pieces.next()?;
-                        let weight = pieces.next().and_then(|w| w.parse().ok()).unwrap_or(0.1f32);
-                        Some(RecommenderRaindropCollection {
-                            collection_id: collection_id.to_string(),
-                            _name: name.to_string(),
-                            weight,
-                        })
-                    })
-                    .collect::<Vec<_>>()
-            })
-            .unwrap_or(vec![
-                RecommenderRaindropCollection {
-                    collection_id: "55948413".to_string(),
-                    _name: "great-reads".to_string(),
-                    weight: 0.8,
-                },
-                RecommenderRaindropCollection {
-                    collection_id: "62896998".to_string(),
-                    _name: "interesting-reads".to_string(),
-                    weight: 0.4,
-                },
-            ]);
-
-        ServerConfig {
-            env,
-            site_url,
-            github_oauth,
-            spotify_oauth,
-            owner_identity_id: 1,
-            discord_token: var("DISCORD_TOKEN").unwrap_or(None),
-            discord_mention_only: var("DISCORD_MENTION_ONLY")
-                .unwrap_or(None)
-                .and_then(|s| s.parse::<bool>().ok())
-                .unwrap_or(true),
-            openai_api_key: var("OPENAI_API_KEY").unwrap_or(None),
-            raindrop_api_token: var("RAINDROP_API_TOKEN").unwrap_or(None),
-            discord_whitelist_channels: var("DISCORD_WHITELIST_CHANNELS").unwrap_or(None).and_then(
-                |s| {
-                    s.split(',')
-                        .map(|s| s.trim().parse::<u64>())
-                        .collect::<Result<Vec<_>, _>>()
-                        .ok()
-                },
-            ),
-            vector_db,
-            recommender_raindrop_collections,
-        }
-    }
-}
-
-pub const FASTEMBED_CACHE_DIR: &str = "./.fastembed_cache";
@@ -0,0 +1,1498 @@
+mod sources;
+mod tunables;
+
+pub use sources::Sources;
+pub use tunables::{RankingK, Tunables, start_reload_watcher};
+
+#[derive(Clone)]
+pub enum Env {
+    Dev,
+    Staging,
+    Production,
+}
+
+/// The parts of the server a process runs, so that the web and the workers
+/// can be scaled apart
+#[derive(Clone, Copy, Default, PartialEq, Eq)]
+pub enum RunMode {
+    /// The HTTP server, the background jobs and the Discord bot
+    #[default]
+    All,
+    /// Only the HTTP server
+    Server,
+    /// Only the background jobs and the Discord bot
+    Worker,
+}
+
+impl RunMode {
+    pub fn serves_http(self) -> bool {
+        self != RunMode::Worker
+    }
+
+    pub fn runs_workers(self) -> bool {
+        self != RunMode::Server
+    }
+}
+
+#[derive(Clone)]
+pub struct ServerConfig {
+    pub env: Env,
+    pub run_mode: RunMode,
+
+    pub database_url: String,
+    /// Statements of the requests running longer than this are cancelled by
+    /// Postgres, the background jobs have no timeout
+    pub db_statement_timeout: std::time::Duration,
+    /// Queries taking longer than this are logged
+    pub db_slow_query: std::time::Duration,
+    /// Name of this instance in the leases of the background jobs, the host
+    /// name or a random one if not set
+    pub instance_id: String,
+
+    /// Website URL (i.e. frontend) in full form without trailing slash
+    /// e.g. https://example.com
+    pub site_url: String,
+
+    pub github_oauth: Option<GitHubOauth>,
+    pub spotify_oauth: Option<SpotifyOauth>,
+    pub strava_oauth: Option<StravaOauth>,
+    /// My Steam account, shown on the now page
+    pub steam: Option<SteamConfig>,
+
+    // My ID in the identities table
+    pub owner_identity_id: i32,
+    /// Maximum request body sizes
+    pub body_limits: BodyLimits,
+    /// Pressure past which the requests are shed
+    pub load_limits: LoadLimits,
+    /// How long each cache of the hot endpoints is warmed for at most at
+    /// startup before `/ready` reports ready, none if they aren't warmed
+    pub cache_warmup: Option<std::time::Duration>,
+    /// Timeouts, retries and user agents of the requests to other servers
+    pub outbound_http: OutboundHttpConfig,
+    /// Where new blog comments are announced, disabled if not set
+    pub comment_notifications: Option<CommentNotificationTarget>,
+    /// Lets readers comment without logging in, disabled if not set
+    pub anonymous_comments: Option<AnonymousComments>,
+    /// Masks parts of the comments served to readers who aren't logged in,
+    /// disabled if not set
+    pub comment_scrubbing: Option<CommentScrubbing>,
+    /// The blog's ActivityPub actor, disabled if not set
+    pub activitypub: Option<ActivityPubConfig>,
+    /// Bearer token of the Micropub clients posting notes, the endpoint is
+    /// disabled if not set
+    pub micropub_token: Option<String>,
+
+    pub discord_token: Option<String>,
+    pub discord_whitelist_channels: Option<Vec<u64>>,
+    /// Lets users talk to the bot in DMs once they consent, DMs are ignored if
+    /// not set
+    pub discord_direct_messages: bool,
+    pub openai_api_key: Option<String>,
+    /// Agent runs executing at once across all channels
+    pub discord_max_concurrent_runs: usize,
+    /// Messages a channel queues while waiting before dropping the oldest ones
+    pub discord_max_queued_messages: usize,
+    /// Maximum input + output tokens the bot may use per channel per day (UTC)
+    pub discord_daily_token_budget: Option<u64>,
+    /// USD per million input and output tokens, for cost estimation only
+    pub discord_token_prices: Option<TokenPrices>,
+    /// Whisper-compatible transcription endpoint for voice channels, voice
+    /// support is disabled if not set
+    pub discord_voice_transcription: Option<TranscriptionConfig>,
+    /// Store 👎 reactions on the bot's messages as memories of what to avoid
+    pub discord_feedback_memories: bool,
+    /// Days after which memories decay unless a guild or channel overrides it,
+    /// memories are kept forever if not set
+    pub discord_memory_retention_days: Option<u32>,
+    /// Days after which the messages of archived channels are deleted unless a
+    /// channel overrides it, messages are kept forever if 0
+    pub discord_archive_retention_days: Option<u32>,
+    /// Classifier of the incoming Discord messages, moderation is disabled if
+    /// not set
+    pub discord_moderation: Option<ModerationConfig>,
+    /// Where the agent runs are recorded for replaying, they aren't if not set
+    pub discord_transcripts: Option<TranscriptSink>,
+    /// Posts the top recommendations to a Discord channel, disabled if not set
+    pub discord_feed_publisher: Option<FeedPublisherConfig>,
+    /// Search providers of the agent's web_search tool
+    pub web_search: WebSearchConfig,
+    /// Sandbox of the agent's code_run tool, the tool is disabled if not set
+    pub code_sandbox: Option<CodeSandboxConfig>,
+    /// Enables the agent's github_search tool
+    pub github_search: Option<GitHubSearchConfig>,
+    /// Lets the agent's calculate tool query Wolfram|Alpha
+    pub wolfram_alpha_app_id: Option<String>,
+    pub raindrop_api_token: Option<String>,
+    /// Store of the Discord bot's memories
+    pub vector_db: Option<VectorDbConfig>,
+    /// Both stores if configured, regardless of which one is used, to migrate
+    /// memories from Chroma to Qdrant
+    pub chromadb: Option<ChromaConfig>,
+    pub qdrant: Option<QdrantConfig>,
+    pub recommender_raindrop_collections: Vec<RecommenderRaindropCollection>,
+    /// Articles older than this that nobody saved or was shown are pruned,
+    /// kept forever if none
+    pub recommender_article_retention_days: Option<u32>,
+    /// ISO 639-3 codes of the languages the feed shows unless asked
+    /// otherwise, all of them if empty
+    pub recommender_feed_languages: Vec<String>,
+    /// Lets any logged in reader summarize the feed articles, only the owner
+    /// can if not set
+    pub recommender_summaries_public: bool,
+    /// Key signing the links of the shared feed, sharing is disabled if not
+    /// set. Changing it revokes the links given out.
+    pub recommender_share_secret: Option<String>,
+    pub geoip: Option<GeoIpConfig>,
+    /// Bucket of the uploaded assets, uploads are disabled if not set
+    pub asset_storage: Option<AssetStorageConfig>,
+}
+
+#[derive(Clone)]
+pub struct GitHubOauth {
+    pub client_id: String,
+    pub client_secret: String,
+}
+
+#[derive(Clone)]
+pub struct SpotifyOauth {
+    pub client_id: String,
+    pub client_secret: String,
+}
+
+#[derive(Clone)]
+pub struct StravaOauth {
+    pub client_id: String,
+    pub client_secret: String,
+}
+
+#[derive(Clone)]
+pub struct SteamConfig {
+    /// Key of the Steam Web API
+    pub api_key: String,
+    /// SteamID64 of the account, its profile must be public
+    pub steam_id: String,
+}
+
+/// Maximum request body sizes in bytes, per kind of endpoint
+#[derive(Clone, Copy, Debug)]
+pub struct BodyLimits {
+    pub default: usize,
+    /// Posting and editing blog comments
+    pub comments: usize,
+    /// Imports such as the memory imports and the readers' history imports
+    pub imports: usize,
+    /// Uploaded assets such as photos
+    pub assets: usize,
+}
+
+/// Limits of the load shedding, the expensive endpoints are shed at half of
+/// them already
+#[derive(Clone, Copy, Debug)]
+pub struct LoadLimits {
+    /// Requests being handled at once
+    pub max_in_flight: usize,
+    /// Expensive requests being handled at once, the others wait in line
+    pub max_expensive: usize,
+    /// Average wait for a database connection
+    pub max_pool_wait: std::time::Duration,
+}
+
+/// Requests to other servers, the hosts with a policy override the defaults
+#[derive(Clone, Debug)]
+pub struct OutboundHttpConfig {
+    pub user_agent: String,
+    pub timeout: std::time::Duration,
+    pub connect_timeout: std::time::Duration,
+    /// Retries of the idempotent requests that time out, can't connect or get
+    /// a 429 or 5xx
+    pub retries: u32,
+    /// Idle connections kept open per host
+    pub pool_max_idle_per_host: usize,
+    /// How long an idle connection is kept open
+    pub pool_idle_timeout: std::time::Duration,
+    pub hosts: Vec<HostPolicy>,
+}
+
+impl Default for OutboundHttpConfig {
+    fn default() -> Self {
+        Self {
+            user_agent: "wrxsh-bot/1.0 (+https://wrx.sh)".to_string(),
+            timeout: std::time::Duration::from_secs(30),
+            connect_timeout: std::time::Duration::from_secs(10),
+            retries: 1,
+            pool_max_idle_per_host: 8,
+            pool_idle_timeout: std::time::Duration::from_secs(90),
+            hosts: vec![
+                // Rather tell the agent the compilation failed than keep the
+                // channel waiting
+                HostPolicy {
+                    host: "godbolt.org".to_string(),
+                    timeout: Some(std::time::Duration::from_secs(20)),
+                    retries: None,
+                    user_agent: None,
+                },
+            ],
+        }
+    }
+}
+
+/// Overrides for a host and its subdomains, the unset ones are the defaults
+#[derive(Clone, Debug, PartialEq)]
+pub struct HostPolicy {
+    pub host: String,
+    pub timeout: Option<std::time::Duration>,
+    pub retries: Option<u32>,
+    pub user_agent: Option<String>,
+}
+
+impl HostPolicy {
+    /// `host[:timeout_secs[:retries[:user agent]]]`, the empty parts are the
+    /// defaults
+    fn parse(value: &str) -> Option<Self> {
+        let mut pieces = value.trim().splitn(4, ':').map(str::trim);
+        let host = pieces.next()?.to_lowercase();
+        if host.is_empty() {
+            return None;
+        }
+        let timeout = match pieces.next() {
+            None | Some("") => None,
+            Some(secs) => Some(std::time::Duration::from_secs(
+                secs.parse().ok().filter(|s| *s > 0)?,
+            )),
+        };
+        let retries = match pieces.next() {
+            None | Some("") => None,
+            Some(retries) => Some(retries.parse().ok()?),
+        };
+        let user_agent = pieces
+            .next()
+            .filter(|ua| !ua.is_empty())
+            .map(str::to_string);
+
+        Some(HostPolicy {
+            host,
+            timeout,
+            retries,
+            user_agent,
+        })
+    }
+}
+
+#[derive(Clone)]
+pub struct AnonymousComments {
+    /// Key signing the tokens that let an anonymous commenter edit or delete
+    /// their comment from the same browser
+    pub secret: String,
+    /// How long after posting the comment can be edited or deleted
+    pub edit_window: std::time::Duration,
+}
+
+/// The blog's actor on the fediverse, `@username@<SITE_URL's host>`
+#[derive(Clone)]
+pub struct ActivityPubConfig {
+    pub username: String,
+    /// PKCS#8 PEM RSA key the activities are signed with, e.g. from
+    /// `openssl genpkey -algorithm RSA -pkeyopt rsa_keygen_bits:2048`
+    pub private_key_pem: String,
+}
+
+#[derive(Clone, Debug)]
+pub struct CommentScrubbing {
+    pub emails: bool,
+    pub phone_numbers: bool,
+    /// Slurs and other words masked case-insensitively
+    pub words: Vec<String>,
+}
+
+/// A Discord webhook URL, or a channel or user the bot sends the notifications to
+#[derive(Clone, Debug)]
+pub enum CommentNotificationTarget {
+    Webhook(String),
+    Channel(u64),
+    User(u64),
+}
+
+#[derive(Clone, Copy)]
+pub struct FeedPublisherConfig {
+    pub channel_id: u64,
+    /// Items posted per run, at most 10 to fit an embed
+    pub top_n: usize,
+    pub interval: std::time::Duration,
+}
+
+#[derive(Clone, Copy)]
+pub struct TokenPrices {
+    pub input_per_million: f64,
+    pub output_per_million: f64,
+}
+
+/// An OpenAI-compatible `/audio/transcriptions` endpoint, e.g.
+/// https://api.openai.com/v1/audio/transcriptions or a self-hosted
+/// faster-whisper server
+#[derive(Clone)]
+pub struct TranscriptionConfig {
+    pub url: String,
+    pub api_key: Option<String>,
+    pub model: String,
+}
+
+/// Search providers tried in order, the next one is used if a provider fails or
+/// is rate limited. One of `searxng`, `brave` or `duckduckgo`.
+#[derive(Clone)]
+pub struct WebSearchConfig {
+    pub providers: Vec<String>,
+    /// Base URL of a SearxNG instance with the JSON format enabled
+    pub searxng_url: Option<String>,
+    pub brave_api_key: Option<String>,
+}
+
+#[derive(Clone)]
+pub struct CodeSandboxConfig {
+    pub backend: CodeSandboxBackend,
+    pub timeout: std::time::Duration,
+    pub memory_limit_mb: u64,
+}
+
+#[derive(Clone)]
+pub enum CodeSandboxBackend {
+    /// A Piston instance, e.g. https://emkc.org/api/v2/piston
+    Piston { url: String },
+    /// Local interpreters run with firejail, which must be installed
+    Firejail,
+}
+
+#[derive(Clone)]
+pub struct ModerationConfig {
+    pub classifier: ModerationClassifier,
+    /// Score from 0 to 1 from which messages are flagged unless a guild
+    /// overrides it
+    pub threshold: f64,
+}
+
+#[derive(Clone)]
+pub enum ModerationClassifier {
+    /// An OpenAI-compatible `/moderations` endpoint, e.g.
+    /// https://api.openai.com/v1/moderations
+    OpenAi {
+        url: String,
+        api_key: Option<String>,
+        model: String,
+    },
+    /// Flags messages containing any of the words, case insensitive
+    Local { blocklist: Vec<String> },
+}
+
+#[derive(Clone)]
+pub enum TranscriptSink {
+    /// The `discord_transcripts` table
+    Table,
+    /// One JSON file per transcript in the directory
+    File { dir: std::path::PathBuf },
+}
+
+#[derive(Clone)]
+pub struct GitHubSearchConfig {
+    pub token: String,
+    /// Accounts (`owner`) and repos (`owner/name`) the agent may search
+    pub scopes: Vec<String>,
+}
+
+#[derive(Clone)]
+pub struct VectorDbConfig {
+    pub backend: VectorDbBackend,
+    /// Prefix of the memory collection names
+    pub default_collection: Option<String>,
+}
+
+#[derive(Clone)]
+pub enum VectorDbBackend {
+    Chroma(ChromaConfig),
+    Qdrant(QdrantConfig),
+}
+
+#[derive(Clone)]
+pub struct ChromaConfig {
+    pub url: String,
+    pub token: String,
+    pub database: String,
+}
+
+#[derive(Clone)]
+pub struct QdrantConfig {
+    pub url: String,
+    pub api_key: Option<String>,
+}
+
+#[derive(Clone)]
+pub struct RecommenderRaindropCollection {
+    pub collection_id: String,
+    pub _name: String,
+    pub weight: f32,
+}
+
+/// Paths to MaxMind (GeoLite2) mmdb files, either may be omitted
+#[derive(Clone)]
+pub struct GeoIpConfig {
+    pub country_database_path: Option<String>,
+    pub asn_database_path: Option<String>,
+}
+
+/// An S3-compatible bucket, e.g. Cloudflare R2
+#[derive(Clone)]
+pub struct AssetStorageConfig {
+    /// e.g. https://<account id>.r2.cloudflarestorage.com
+    pub endpoint: String,
+    pub bucket: String,
+    /// `auto` for R2
+    pub region: String,
+    pub access_key_id: String,
+    pub secret_access_key: String,
+    /// How long the signed URLs of the assets are valid
+    pub url_expiry: std::time::Duration,
+}
+
+fn var(key: &str) -> Result<Option<String>, String> {
+    match sources::lookup(key) {
+        Ok(Some(value)) => Ok(Some(value)),
+        Ok(None) => {
+            tracing::warn!("Mising environment variable `{key}`");
+            Ok(None)
+        }
+        Err(_) => Err(format!(
+            "Could not get the environment variable `{key}` due to unicode error"
+        )),
+    }
+}
+
+#[allow(dead_code)]
+fn required_var(key: &str) -> String {
+    let val = var(key);
+    match val {
+        Ok(val) => match val {
+            Some(val) => val,
+            None => {
+                tracing::error!("Environment variable `{key}` is required");
+                std::process::exit(1)
+            }
+        },
+        Err(e) => {
+            tracing::error!(
+                "Environment variable `{key}` is required, but could not retrieve: {e}"
+            );
+            std::process::exit(1)
+        }
+    }
+}
+
+/// Either all or none variables are set, the feature is disabled otherwise and
+/// [ServerConfig::validate] reports the missing ones
+fn all_or_none_vars(keys: Vec<&str>) -> Option<Vec<String>> {
+    keys.iter()
+        .map(|k| var(k).ok().flatten())
+        .collect::<Option<Vec<_>>>()
+}
+
+impl ServerConfig {
+    pub fn new_from_env() -> Self {
+        let github_oauth =
+            all_or_none_vars(vec!["GITHUB_OAUTH_CLIENT_ID", "GITHUB_OAUTH_CLIENT_SECRET"]).map(
+                |mut vars| GitHubOauth {
+                    client_id: vars.remove(0),
+                    client_secret: vars.remove(0),
+                },
+            );
+
+        let spotify_oauth = all_or_none_vars(vec![
+            "SPOTIFY_OAUTH_CLIENT_ID",
+            "SPOTIFY_OAUTH_CLIENT_SECRET",
+        ])
+        .map(|mut vars| SpotifyOauth {
+            client_id: vars.remove(0),
+            client_secret: vars.remove(0),
+        });
+
+        let strava_oauth =
+            all_or_none_vars(vec!["STRAVA_OAUTH_CLIENT_ID", "STRAVA_OAUTH_CLIENT_SECRET"]).map(
+                |mut vars| StravaOauth {
+                    client_id: vars.remove(0),
+                    client_secret: vars.remove(0),
+                },
+            );
+
+        let steam =
+            all_or_none_vars(vec!["STEAM_API_KEY", "STEAM_ID"]).map(|mut vars| SteamConfig {
+                api_key: vars.remove(0),
+                steam_id: vars.remove(0),
+            });
+
+        let env = match var("ENVIRONMENT") {
+            Ok(Some(env)) => match env.as_str() {
+                "dev" => Env::Dev,
+                "staging" => Env::Staging,
+                "production" => Env::Production,
+                _ => Env::Dev,
+            },
+            _ => Env::Dev,
+        };
+
+        let run_mode = match var("RUN_MODE") {
+            Ok(Some(mode)) => match mode.trim().to_lowercase().as_str() {
+                "server" => RunMode::Server,
+                "worker" => RunMode::Worker,
+                _ => RunMode::All,
+            },
+            _ => RunMode::All,
+        };
+
+        let site_url = var("SITE_URL")
+            .unwrap_or(Some("http://localhost:4321".to_string()))
+            .unwrap_or("http://localhost:4321".to_string());
+
+        let chromadb = var("CHROMADB_URL").unwrap_or(None).map(|url| ChromaConfig {
+            url,
+            token: var("CHROMADB_API_TOKEN")
+                .unwrap_or(None)
+                .unwrap_or("".to_string()),
+            database: var("CHROMADB_DATABASE")
+                .unwrap_or(Some("wrx-sh-discord-memory".to_string()))
+                .unwrap_or("wrx-sh".to_string()),
+        });
+
+        let qdrant = var("QDRANT_URL").unwrap_or(None).map(|url| QdrantConfig {
+            url,
+            api_key: var("QDRANT_API_KEY").unwrap_or(None),
+        });
+
+        // Chroma unless only Qdrant is configured
+        let backend = match var("VECTOR_DB_BACKEND").unwrap_or(None).as_deref() {
+            Some("qdrant") => qdrant.clone().map(VectorDbBackend::Qdrant),
+            Some("chroma") => chromadb.clone().map(VectorDbBackend::Chroma),
+            _ => chromadb
+                .clone()
+                .map(VectorDbBackend::Chroma)
+                .or(qdrant.clone().map(VectorDbBackend::Qdrant)),
+        };
+        let vector_db = backend.map(|backend| VectorDbConfig {
+            backend,
+            default_collection: var("CHROMADB_DEFAULT_COLLECTION").unwrap_or(None),
+        });
+
+        let recommender_raindrop_collections = var("RECOMMENDER_RAINDROP_COLLECTIONS")
+            .unwrap_or(None)
+            .map(|s| {
+                s.split(',')
+                    .filter_map(|part| {
+                        let mut pieces = part.split(':');
+                        let name = pieces.next()?;
+                        let collection_id = pieces.next()?;
+                        let weight = pieces.next().and_then(|w| w.parse().ok()).unwrap_or(0.1f32);
+                        Some(RecommenderRaindropCollection {
+                            collection_id: collection_id.to_string(),
+                            _name: name.to_string(),
+                            weight,
+                        })
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .unwrap_or(vec![
+                RecommenderRaindropCollection {
+                    collection_id: "55948413".to_string(),
+                    _name: "great-reads".to_string(),
+                    weight: 0.8,
+                },
+                RecommenderRaindropCollection {
+                    collection_id: "62896998".to_string(),
+                    _name: "interesting-reads".to_string(),
+                    weight: 0.4,
+                },
+            ]);
+
+        let geoip = match (
+            var("GEOIP_COUNTRY_DATABASE_PATH").unwrap_or(None),
+            var("GEOIP_ASN_DATABASE_PATH").unwrap_or(None),
+        ) {
+            (None, None) => None,
+            (country_database_path, asn_database_path) => Some(GeoIpConfig {
+                country_database_path,
+                asn_database_path,
+            }),
+        };
+
+        let asset_storage = all_or_none_vars(vec![
+            "S3_ENDPOINT",
+            "S3_BUCKET",
+            "S3_ACCESS_KEY_ID",
+            "S3_SECRET_ACCESS_KEY",
+        ])
+        .map(|mut vars| AssetStorageConfig {
+            endpoint: vars.remove(0),
+            bucket: vars.remove(0),
+            access_key_id: vars.remove(0),
+            secret_access_key: vars.remove(0),
+            region: var("S3_REGION")
+                .unwrap_or(None)
+                .unwrap_or("auto".to_string()),
+            url_expiry: std::time::Duration::from_mins(
+                var("ASSET_URL_EXPIRY_MINS")
+                    .unwrap_or(None)
+                    .and_then(|s| s.trim().parse::<u64>().ok())
+                    .filter(|m| *m > 0)
+                    .unwrap_or(60),
+            ),
+        });
+
+        ServerConfig {
+            env,
+            run_mode,
+            database_url: var("DATABASE_URL").unwrap_or(None).unwrap_or_default(),
+            db_statement_timeout: std::time::Duration::from_millis(
+                var("DB_STATEMENT_TIMEOUT_MS")
+                    .unwrap_or(None)
+                    .and_then(|s| s.trim().parse().ok())
+                    .unwrap_or(30_000),
+            ),
+            db_slow_query: std::time::Duration::from_millis(
+                var("DB_SLOW_QUERY_MS")
+                    .unwrap_or(None)
+                    .and_then(|s| s.trim().parse().ok())
+                    .unwrap_or(500),
+            ),
+            instance_id: var("INSTANCE_ID")
+                .unwrap_or(None)
+                .or_else(|| std::env::var("HOSTNAME").ok().filter(|h| !h.is_empty()))
+                .unwrap_or_else(|| uuid::Uuid::new_v4().simple().to_string()[..8].to_string()),
+            site_url,
+            github_oauth,
+            spotify_oauth,
+            strava_oauth,
+            steam,
+            owner_identity_id: 1,
+            body_limits: BodyLimits {
+                default: var("MAX_BODY_BYTES")
+                    .unwrap_or(None)
+                    .and_then(|s| s.trim().parse().ok())
+                    .unwrap_or(256 * 1024),
+                comments: var("MAX_COMMENT_BODY_BYTES")
+                    .unwrap_or(None)
+                    .and_then(|s| s.trim().parse().ok())
+                    .unwrap_or(16 * 1024),
+                imports: var("MAX_IMPORT_BODY_BYTES")
+                    .unwrap_or(None)
+                    .and_then(|s| s.trim().parse().ok())
+                    .unwrap_or(64 * 1024 * 1024),
+                assets: var("MAX_ASSET_BODY_BYTES")
+                    .unwrap_or(None)
+                    .and_then(|s| s.trim().parse().ok())
+                    .unwrap_or(20 * 1024 * 1024),
+            },
+            load_limits: LoadLimits {
+                max_in_flight: var("MAX_IN_FLIGHT_REQUESTS")
+                    .unwrap_or(None)
+                    .and_then(|s| s.trim().parse().ok())
+                    .unwrap_or(512),
+                max_expensive: var("MAX_EXPENSIVE_REQUESTS")
+                    .unwrap_or(None)
+                    .and_then(|s| s.trim().parse().ok())
+                    .unwrap_or(16),
+                max_pool_wait: std::time::Duration::from_millis(
+                    var("MAX_DB_POOL_WAIT_MS")
+                        .unwrap_or(None)
+                        .and_then(|s| s.trim().parse().ok())
+                        .unwrap_or(1000),
+                ),
+            },
+            cache_warmup: var("CACHE_WARMUP")
+                .unwrap_or(None)
+                .and_then(|s| s.parse::<bool>().ok())
+                .unwrap_or(true)
+                .then(|| {
+                    std::time::Duration::from_secs(
+                        var("CACHE_WARMUP_TIMEOUT_SECS")
+                            .unwrap_or(None)
+                            .and_then(|s| s.trim().parse().ok())
+                            .unwrap_or(20),
+                    )
+                }),
+            outbound_http: {
+                let defaults = OutboundHttpConfig::default();
+                let mut hosts = var("HTTP_HOST_POLICIES")
+                    .unwrap_or(None)
+                    .map(|s| {
+                        s.split(',')
+                            .filter_map(HostPolicy::parse)
+                            .collect::<Vec<_>>()
+                    })
+                    .unwrap_or_default();
+                for policy in defaults.hosts {
+                    if !hosts.iter().any(|p| p.host == policy.host) {
+                        hosts.push(policy);
+                    }
+                }
+                OutboundHttpConfig {
+                    user_agent: var("HTTP_USER_AGENT")
+                        .unwrap_or(None)
+                        .unwrap_or(defaults.user_agent),
+                    timeout: var("HTTP_TIMEOUT_SECS")
+                        .unwrap_or(None)
+                        .and_then(|s| s.trim().parse().ok())
+                        .map(std::time::Duration::from_secs)
+                        .unwrap_or(defaults.timeout),
+                    connect_timeout: var("HTTP_CONNECT_TIMEOUT_SECS")
+                        .unwrap_or(None)
+                        .and_then(|s| s.trim().parse().ok())
+                        .map(std::time::Duration::from_secs)
+                        .unwrap_or(defaults.connect_timeout),
+                    retries: var("HTTP_RETRIES")
+                        .unwrap_or(None)
+                        .and_then(|s| s.trim().parse().ok())
+                        .unwrap_or(defaults.retries),
+                    pool_max_idle_per_host: var("HTTP_POOL_MAX_IDLE_PER_HOST")
+                        .unwrap_or(None)
+                        .and_then(|s| s.trim().parse().ok())
+                        .unwrap_or(defaults.pool_max_idle_per_host),
+                    pool_idle_timeout: var("HTTP_POOL_IDLE_TIMEOUT_SECS")
+                        .unwrap_or(None)
+                        .and_then(|s| s.trim().parse().ok())
+                        .map(std::time::Duration::from_secs)
+                        .unwrap_or(defaults.pool_idle_timeout),
+                    hosts,
+                }
+            },
+            comment_notifications: var("COMMENT_NOTIFY_WEBHOOK_URL")
+                .unwrap_or(None)
+                .map(CommentNotificationTarget::Webhook)
+                .or_else(|| {
+                    var("COMMENT_NOTIFY_DISCORD_CHANNEL")
+                        .unwrap_or(None)
+                        .and_then(|s| s.trim().parse().ok())
+                        .map(CommentNotificationTarget::Channel)
+                })
+                .or_else(|| {
+                    var("COMMENT_NOTIFY_DISCORD_USER")
+                        .unwrap_or(None)
+                        .and_then(|s| s.trim().parse().ok())
+                        .map(CommentNotificationTarget::User)
+                }),
+            anonymous_comments: var("ANONYMOUS_COMMENTS_SECRET")
+                .unwrap_or(None)
+                .filter(|s| !s.trim().is_empty())
+                .map(|secret| AnonymousComments {
+                    secret,
+                    edit_window: std::time::Duration::from_mins(
+                        var("ANONYMOUS_COMMENTS_EDIT_WINDOW_MINS")
+                            .unwrap_or(None)
+                            .and_then(|s| s.trim().parse::<u64>().ok())
+                            .filter(|m| *m > 0)
+                            .unwrap_or(15),
+                    ),
+                }),
+            comment_scrubbing: {
+                let kinds = var("COMMENT_SCRUB")
+                    .unwrap_or(None)
+                    .unwrap_or_default()
+                    .split(',')
+                    .map(|s| s.trim().to_lowercase())
+                    .collect::<Vec<_>>();
+                let words = var("COMMENT_SCRUB_WORDS")
+                    .unwrap_or(None)
+                    .unwrap_or_default()
+                    .split(',')
+                    .map(|s| s.trim().to_string())
+                    .filter(|s| !s.is_empty())
+                    .collect::<Vec<_>>();
+                let scrubbing = CommentScrubbing {
+                    emails: kinds.iter().any(|k| k == "emails"),
+                    phone_numbers: kinds.iter().any(|k| k == "phone_numbers"),
+                    words,
+                };
+                (scrubbing.emails || scrubbing.phone_numbers || !scrubbing.words.is_empty())
+                    .then_some(scrubbing)
+            },
+            activitypub: var("ACTIVITYPUB_PRIVATE_KEY")
+                .unwrap_or(None)
+                .filter(|s| !s.trim().is_empty())
+                .map(|key| ActivityPubConfig {
+                    username: var("ACTIVITYPUB_USERNAME")
+                        .unwrap_or(None)
+                        .map(|s| s.trim().to_string())
+                        .filter(|s| !s.is_empty())
+                        .unwrap_or("blog".to_string()),
+                    // A single line in the .env file
+                    private_key_pem: key.replace("\\n", "\n"),
+                }),
+            micropub_token: var("MICROPUB_TOKEN")
+                .unwrap_or(None)
+                .filter(|s| !s.trim().is_empty()),
+            discord_token: var("DISCORD_TOKEN").unwrap_or(None),
+            openai_api_key: var("OPENAI_API_KEY").unwrap_or(None),
+            discord_daily_token_budget: var("DISCORD_DAILY_TOKEN_BUDGET")
+                .unwrap_or(None)
+                .and_then(|s| s.trim().parse::<u64>().ok()),
+            discord_token_prices: var("DISCORD_TOKEN_PRICES").unwrap_or(None).and_then(|s| {
+                let (input, output) = s.split_once(':')?;
+                Some(TokenPrices {
+                    input_per_million: input.trim().parse().ok()?,
+                    output_per_million: output.trim().parse().ok()?,
+                })
+            }),
+            discord_voice_transcription: var("DISCORD_VOICE_TRANSCRIPTION_URL")
+                .unwrap_or(None)
+                .map(|url| TranscriptionConfig {
+                    url,
+                    api_key: var("DISCORD_VOICE_TRANSCRIPTION_API_KEY").unwrap_or(None),
+                    model: var("DISCORD_VOICE_TRANSCRIPTION_MODEL")
+                        .unwrap_or(None)
+                        .unwrap_or("whisper-1".to_string()),
+                }),
+            discord_direct_messages: var("DISCORD_DIRECT_MESSAGES")
+                .unwrap_or(None)
+                .and_then(|s| s.parse::<bool>().ok())
+                .unwrap_or(false),
+            discord_feed_publisher: var("DISCORD_FEED_CHANNEL")
+                .unwrap_or(None)
+                .and_then(|s| s.trim().parse::<u64>().ok())
+                .map(|channel_id| FeedPublisherConfig {
+                    channel_id,
+                    top_n: var("DISCORD_FEED_TOP_N")
+                        .unwrap_or(None)
+                        .and_then(|s| s.trim().parse::<usize>().ok())
+                        .unwrap_or(5)
+                        .clamp(1, 10),
+                    interval: std::time::Duration::from_hours(
+                        var("DISCORD_FEED_INTERVAL_HOURS")
+                            .unwrap_or(None)
+                            .and_then(|s| s.trim().parse::<u64>().ok())
+                            .filter(|h| *h > 0)
+                            .unwrap_or(24),
+                    ),
+                }),
+            discord_max_concurrent_runs: var("DISCORD_MAX_CONCURRENT_RUNS")
+                .unwrap_or(None)
+                .and_then(|s| s.trim().parse::<usize>().ok())
+                .filter(|n| *n > 0)
+                .unwrap_or(4),
+            discord_max_queued_messages: var("DISCORD_MAX_QUEUED_MESSAGES")
+                .unwrap_or(None)
+                .and_then(|s| s.trim().parse::<usize>().ok())
+                .filter(|n| *n > 0)
+                .unwrap_or(crate::discord::constants::MESSAGE_CONTEXT_SIZE),
+            discord_feedback_memories: var("DISCORD_FEEDBACK_MEMORIES")
+                .unwrap_or(None)
+                .and_then(|s| s.parse::<bool>().ok())
+                .unwrap_or(false),
+            discord_memory_retention_days: var("DISCORD_MEMORY_RETENTION_DAYS")
+                .unwrap_or(None)
+                .and_then(|s| s.trim().parse::<u32>().ok())
+                .filter(|days| *days > 0),
+            discord_archive_retention_days: var("DISCORD_ARCHIVE_RETENTION_DAYS")
+                .unwrap_or(None)
+                .and_then(|s| s.trim().parse::<u32>().ok())
+                .or(Some(90))
+                .filter(|days| *days > 0),
+            discord_moderation: var("DISCORD_MODERATION")
+                .unwrap_or(None)
+                .and_then(
+                    |classifier| match classifier.trim().to_lowercase().as_str() {
+                        "openai" => Some(ModerationClassifier::OpenAi {
+                            url: var("DISCORD_MODERATION_URL")
+                                .unwrap_or(None)
+                                .unwrap_or("https://api.openai.com/v1/moderations".to_string()),
+                            api_key: var("DISCORD_MODERATION_API_KEY").unwrap_or(None),
+                            model: var("DISCORD_MODERATION_MODEL")
+                                .unwrap_or(None)
+                                .unwrap_or("omni-moderation-latest".to_string()),
+                        }),
+                        "local" => Some(ModerationClassifier::Local {
+                            blocklist: var("DISCORD_MODERATION_BLOCKLIST")
+                                .unwrap_or(None)
+                                .unwrap_or_default()
+                                .split(',')
+                                .map(|s| s.trim().to_lowercase())
+                                .filter(|s| !s.is_empty())
+                                .collect(),
+                        }),
+                        other => {
+                            tracing::warn!(
+                                "Unknown moderation classifier `{other}`, moderation is disabled"
+                            );
+                            None
+                        }
+                    },
+                )
+                .map(|classifier| ModerationConfig {
+                    classifier,
+                    threshold: var("DISCORD_MODERATION_THRESHOLD")
+                        .unwrap_or(None)
+                        .and_then(|s| s.trim().parse::<f64>().ok())
+                        .filter(|t| (0.0..=1.0).contains(t))
+                        .unwrap_or(0.8),
+                }),
+            discord_transcripts: var("DISCORD_TRANSCRIPTS").unwrap_or(None).and_then(|sink| {
+                match sink.trim().to_lowercase().as_str() {
+                    "table" => Some(TranscriptSink::Table),
+                    "file" => Some(TranscriptSink::File {
+                        dir: var("DISCORD_TRANSCRIPTS_DIR")
+                            .unwrap_or(None)
+                            .unwrap_or("./transcripts".to_string())
+                            .into(),
+                    }),
+                    other => {
+                        tracing::warn!(
+                            "Unknown transcript sink `{other}`, transcripts are not recorded"
+                        );
+                        None
+                    }
+                }
+            }),
+            web_search: WebSearchConfig {
+                providers: var("WEB_SEARCH_PROVIDERS")
+                    .unwrap_or(None)
+                    .unwrap_or("duckduckgo".to_string())
+                    .split(',')
+                    .map(|s| s.trim().to_lowercase())
+                    .filter(|s| !s.is_empty())
+                    .collect(),
+                searxng_url: var("SEARXNG_URL").unwrap_or(None),
+                brave_api_key: var("BRAVE_SEARCH_API_KEY").unwrap_or(None),
+            },
+            code_sandbox: var("CODE_SANDBOX")
+                .unwrap_or(None)
+                .and_then(|backend| match backend.trim().to_lowercase().as_str() {
+                    "piston" => Some(CodeSandboxBackend::Piston {
+                        url: var("CODE_SANDBOX_PISTON_URL")
+                            .unwrap_or(None)
+                            .unwrap_or("https://emkc.org/api/v2/piston".to_string()),
+                    }),
+                    "firejail" => Some(CodeSandboxBackend::Firejail),
+                    other => {
+                        tracing::warn!("Unknown code sandbox `{other}`, code_run is disabled");
+                        None
+                    }
+                })
+                .map(|backend| CodeSandboxConfig {
+                    backend,
+                    timeout: std::time::Duration::from_secs(
+                        var("CODE_SANDBOX_TIMEOUT_SECS")
+                            .unwrap_or(None)
+                            .and_then(|s| s.trim().parse().ok())
+                            .unwrap_or(10),
+                    ),
+                    memory_limit_mb: var("CODE_SANDBOX_MEMORY_MB")
+                        .unwrap_or(None)
+                        .and_then(|s| s.trim().parse().ok())
+                        .unwrap_or(256),
+                }),
+            github_search: var("GITHUB_SEARCH_TOKEN")
+                .unwrap_or(None)
+                .map(|token| GitHubSearchConfig {
+                    token,
+                    scopes: var("GITHUB_SEARCH_SCOPES")
+                        .unwrap_or(None)
+                        .unwrap_or_default()
+                        .split(',')
+                        .map(|s| s.trim().to_string())
+                        .filter(|s| !s.is_empty())
+                        .collect(),
+                })
+                .filter(|c| {
+                    if c.scopes.is_empty() {
+                        tracing::warn!("GITHUB_SEARCH_SCOPES is empty, github_search is disabled");
+                    }
+                    !c.scopes.is_empty()
+                }),
+            wolfram_alpha_app_id: var("WOLFRAM_ALPHA_APP_ID").unwrap_or(None),
+            raindrop_api_token: var("RAINDROP_API_TOKEN").unwrap_or(None),
+            discord_whitelist_channels: var("DISCORD_WHITELIST_CHANNELS").unwrap_or(None).and_then(
+                |s| {
+                    s.split(',')
+                        .map(|s| s.trim().parse::<u64>())
+                        .collect::<Result<Vec<_>, _>>()
+                        .ok()
+                },
+            ),
+            vector_db,
+            chromadb,
+            qdrant,
+            recommender_raindrop_collections,
+            recommender_article_retention_days: var("RECOMMENDER_ARTICLE_RETENTION_DAYS")
+                .unwrap_or(None)
+                .and_then(|s| s.trim().parse::<u32>().ok())
+                .or(Some(180))
+                .filter(|days| *days > 0),
+            recommender_feed_languages: var("RECOMMENDER_FEED_LANGUAGES")
+                .unwrap_or(None)
+                .map(|s| {
+                    crate::utils::parse_languages(&s).unwrap_or_else(|| {
+                        tracing::warn!("Unknown language in `RECOMMENDER_FEED_LANGUAGES`");
+                        Vec::new()
+                    })
+                })
+                .unwrap_or_default(),
+            recommender_summaries_public: var("RECOMMENDER_SUMMARIES_PUBLIC")
+                .unwrap_or(None)
+                .and_then(|s| s.parse::<bool>().ok())
+                .unwrap_or(false),
+            recommender_share_secret: var("RECOMMENDER_SHARE_SECRET")
+                .unwrap_or(None)
+                .filter(|s| !s.trim().is_empty()),
+            geoip,
+            asset_storage,
+        }
+    }
+
+    /// Every configured secret, to be redacted from anything recorded
+    pub fn secrets(&self) -> Vec<String> {
+        [
+            Some(self.database_url.clone()),
+            self.discord_token.clone(),
+            self.openai_api_key.clone(),
+            self.anonymous_comments.as_ref().map(|a| a.secret.clone()),
+            self.activitypub.as_ref().map(|a| a.private_key_pem.clone()),
+            self.micropub_token.clone(),
+            self.github_oauth.as_ref().map(|o| o.client_secret.clone()),
+            self.spotify_oauth.as_ref().map(|o| o.client_secret.clone()),
+            self.strava_oauth.as_ref().map(|o| o.client_secret.clone()),
+            self.steam.as_ref().map(|s| s.api_key.clone()),
+            self.discord_voice_transcription
+                .as_ref()
+                .and_then(|t| t.api_key.clone()),
+            self.discord_moderation
+                .as_ref()
+                .and_then(|m| match &m.classifier {
+                    ModerationClassifier::OpenAi { api_key, .. } => api_key.clone(),
+                    ModerationClassifier::Local { .. } => None,
+                }),
+            self.web_search.brave_api_key.clone(),
+            self.github_search.as_ref().map(|g| g.token.clone()),
+            self.wolfram_alpha_app_id.clone(),
+            self.raindrop_api_token.clone(),
+            self.recommender_share_secret.clone(),
+            self.chromadb.as_ref().map(|c| c.token.clone()),
+            self.qdrant.as_ref().and_then(|q| q.api_key.clone()),
+            self.asset_storage
+                .as_ref()
+                .map(|s| s.secret_access_key.clone()),
+        ]
+        .into_iter()
+        .flatten()
+        .filter(|s| !s.trim().is_empty())
+        .collect()
+    }
+}
+
+pub const FASTEMBED_CACHE_DIR: &str = "./.fastembed_cache";
+
+/// Problems found in the environment, reported at once rather than one by one
+#[derive(Debug, Default)]
+pub struct ConfigReport {
+    /// Missing or invalid variables, the server doesn't start with any
+    pub errors: Vec<String>,
+    /// Settings that work but are likely mistakes or insecure
+    pub warnings: Vec<String>,
+}
+
+impl ConfigReport {
+    pub fn is_ok(&self) -> bool {
+        self.errors.is_empty()
+    }
+
+    pub fn log(&self) {
+        for error in &self.errors {
+            tracing::error!("Configuration error: {error}");
+        }
+        for warning in &self.warnings {
+            tracing::warn!("Configuration warning: {warning}");
+        }
+    }
+}
+
+impl std::fmt::Display for ConfigReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.errors.is_empty() && self.warnings.is_empty() {
+            return writeln!(f, "Configuration is valid");
+        }
+        for (title, items) in [("Errors", &self.errors), ("Warnings", &self.warnings)] {
+            if items.is_empty() {
+                continue;
+            }
+            writeln!(f, "{title}:")?;
+            for item in items {
+                writeln!(f, "  - {item}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// What the value of a variable must look like
+enum Expect {
+    Integer,
+    /// An integer greater than 0
+    Positive,
+    Bool,
+    /// A number between 0 and 1
+    Fraction,
+    /// A number greater than 0
+    PositiveNumber,
+    /// One of the values, case insensitive
+    OneOf(&'static [&'static str]),
+    /// Comma separated values, each one of them
+    ListOf(&'static [&'static str]),
+    /// Comma separated integers
+    Integers,
+    /// `input:output` prices
+    Prices,
+    /// Comma separated `name:collection_id[:weight]`
+    Collections,
+    /// Comma separated `host[:timeout_secs[:retries[:user agent]]]`
+    HostPolicies,
+}
+
+impl Expect {
+    fn check(&self, value: &str) -> Result<(), String> {
+        let valid = match self {
+            Expect::Integer => value.trim().parse::<u64>().is_ok(),
+            Expect::Positive => value.trim().parse::<u64>().is_ok_and(|n| n > 0),
+            Expect::Bool => value.parse::<bool>().is_ok(),
+            Expect::Fraction => value
+                .trim()
+                .parse::<f64>()
+                .is_ok_and(|n| (0.0..=1.0).contains(&n)),
+            Expect::PositiveNumber => value.trim().parse::<f64>().is_ok_and(|n| n > 0.0),
+            Expect::OneOf(values) => values.contains(&value.trim().to_lowercase().as_str()),
+            Expect::ListOf(values) => value
+                .split(',')
+                .map(|s| s.trim().to_lowercase())
+                .filter(|s| !s.is_empty())
+                .all(|s| values.contains(&s.as_str())),
+            Expect::Integers => value.split(',').all(|s| s.trim().parse::<u64>().is_ok()),
+            Expect::Prices => value.split_once(':').is_some_and(|(input, output)| {
+                input.trim().parse::<f64>().is_ok() && output.trim().parse::<f64>().is_ok()
+            }),
+            Expect::Collections => value.split(',').all(|part| {
+                let mut pieces = part.split(':');
+                pieces.next().is_some()
+                    && pieces.next().is_some()
+                    && pieces.next().is_none_or(|w| w.parse::<f32>().is_ok())
+            }),
+            Expect::HostPolicies => value.split(',').all(|p| HostPolicy::parse(p).is_some()),
+        };
+        if valid {
+            return Ok(());
+        }
+
+        Err(match self {
+            Expect::Integer => "a non-negative integer".to_string(),
+            Expect::Positive => "an integer greater than 0".to_string(),
+            Expect::Bool => "`true` or `false`".to_string(),
+            Expect::Fraction => "a number between 0 and 1".to_string(),
+            Expect::PositiveNumber => "a number greater than 0".to_string(),
+            Expect::OneOf(values) => format!("one of {}", values.join(", ")),
+            Expect::ListOf(values) => format!("a comma separated list of {}", values.join(", ")),
+            Expect::Integers => "a comma separated list of IDs".to_string(),
+            Expect::Prices => "`input:output` USD per million tokens".to_string(),
+            Expect::Collections => "a comma separated list of `name:id[:weight]`".to_string(),
+            Expect::HostPolicies => {
+                "a comma separated list of `host[:timeout_secs[:retries[:user agent]]]`".to_string()
+            }
+        })
+    }
+}
+
+/// The variables with a format, an invalid value would otherwise silently fall
+/// back to the default
+const TYPED_VARS: &[(&str, Expect)] = &[
+    (
+        "ENVIRONMENT",
+        Expect::OneOf(&["dev", "staging", "production"]),
+    ),
+    ("RUN_MODE", Expect::OneOf(&["all", "server", "worker"])),
+    ("MAX_BODY_BYTES", Expect::Positive),
+    ("MAX_COMMENT_BODY_BYTES", Expect::Positive),
+    ("MAX_IMPORT_BODY_BYTES", Expect::Positive),
+    ("MAX_ASSET_BODY_BYTES", Expect::Positive),
+    ("MAX_IN_FLIGHT_REQUESTS", Expect::Positive),
+    ("MAX_EXPENSIVE_REQUESTS", Expect::Positive),
+    ("MAX_DB_POOL_WAIT_MS", Expect::Positive),
+    ("DB_STATEMENT_TIMEOUT_MS", Expect::Positive),
+    ("DB_SLOW_QUERY_MS", Expect::Positive),
+    ("CACHE_WARMUP", Expect::Bool),
+    ("CACHE_WARMUP_TIMEOUT_SECS", Expect::Positive),
+    ("HTTP_TIMEOUT_SECS", Expect::Positive),
+    ("HTTP_CONNECT_TIMEOUT_SECS", Expect::Positive),
+    ("HTTP_RETRIES", Expect::Integer),
+    ("HTTP_POOL_MAX_IDLE_PER_HOST", Expect::Integer),
+    ("HTTP_POOL_IDLE_TIMEOUT_SECS", Expect::Positive),
+    ("HTTP_HOST_POLICIES", Expect::HostPolicies),
+    ("ASSET_URL_EXPIRY_MINS", Expect::Positive),
+    ("COMMENT_NOTIFY_DISCORD_CHANNEL", Expect::Integer),
+    ("COMMENT_NOTIFY_DISCORD_USER", Expect::Integer),
+    ("ANONYMOUS_COMMENTS_EDIT_WINDOW_MINS", Expect::Positive),
+    (
+        "COMMENT_SCRUB",
+        Expect::ListOf(&["emails", "phone_numbers"]),
+    ),
+    ("DISCORD_MENTION_ONLY", Expect::Bool),
+    ("DISCORD_WHITELIST_CHANNELS", Expect::Integers),
+    ("DISCORD_DIRECT_MESSAGES", Expect::Bool),
+    ("DISCORD_DM_MESSAGES_PER_HOUR", Expect::Positive),
+    ("DISCORD_USER_MESSAGES_PER_10_MINS", Expect::Integer),
+    ("DISCORD_MAX_CONCURRENT_RUNS", Expect::Positive),
+    ("DISCORD_MAX_QUEUED_MESSAGES", Expect::Positive),
+    ("DISCORD_DAILY_TOKEN_BUDGET", Expect::Integer),
+    ("DISCORD_TOKEN_PRICES", Expect::Prices),
+    ("DISCORD_FEEDBACK_MEMORIES", Expect::Bool),
+    ("DISCORD_MEMORY_RETENTION_DAYS", Expect::Integer),
+    ("DISCORD_ARCHIVE_RETENTION_DAYS", Expect::Integer),
+    ("DISCORD_MODERATION", Expect::OneOf(&["openai", "local"])),
+    ("DISCORD_MODERATION_THRESHOLD", Expect::Fraction),
+    ("DISCORD_TRANSCRIPTS", Expect::OneOf(&["table", "file"])),
+    ("DISCORD_FEED_CHANNEL", Expect::Integer),
+    ("DISCORD_FEED_TOP_N", Expect::Positive),
+    ("DISCORD_FEED_INTERVAL_HOURS", Expect::Positive),
+    (
+        "WEB_SEARCH_PROVIDERS",
+        Expect::ListOf(&["searxng", "brave", "duckduckgo"]),
+    ),
+    ("CODE_SANDBOX", Expect::OneOf(&["piston", "firejail"])),
+    ("CODE_SANDBOX_TIMEOUT_SECS", Expect::Positive),
+    ("CODE_SANDBOX_MEMORY_MB", Expect::Positive),
+    ("VECTOR_DB_BACKEND", Expect::OneOf(&["chroma", "qdrant"])),
+    ("RECOMMENDER_RAINDROP_COLLECTIONS", Expect::Collections),
+    ("RECOMMENDER_CRAWL_INTERVAL_MINS", Expect::Positive),
+    ("RECOMMENDER_MIN_CRAWL_INTERVAL_MINS", Expect::Positive),
+    (
+        "RECOMMENDER_RANKING_BALANCED_SIMILARITY_K",
+        Expect::PositiveNumber,
+    ),
+    (
+        "RECOMMENDER_RANKING_BALANCED_EXTERNAL_K",
+        Expect::PositiveNumber,
+    ),
+    (
+        "RECOMMENDER_RANKING_NEWER_FIRST_SIMILARITY_K",
+        Expect::PositiveNumber,
+    ),
+    (
+        "RECOMMENDER_RANKING_NEWER_FIRST_EXTERNAL_K",
+        Expect::PositiveNumber,
+    ),
+    (
+        "RECOMMENDER_RANKING_TOP_FIRST_SIMILARITY_K",
+        Expect::PositiveNumber,
+    ),
+    (
+        "RECOMMENDER_RANKING_TOP_FIRST_EXTERNAL_K",
+        Expect::PositiveNumber,
+    ),
+    (
+        "RECOMMENDER_RANKING_SIMILAR_FIRST_SIMILARITY_K",
+        Expect::PositiveNumber,
+    ),
+    (
+        "RECOMMENDER_RANKING_SIMILAR_FIRST_EXTERNAL_K",
+        Expect::PositiveNumber,
+    ),
+];
+
+/// Variables without which the server can't start
+const REQUIRED_VARS: &[&str] = &["DATABASE_URL"];
+
+/// Variables that only work together
+const PAIRED_VARS: &[&[&str]] = &[
+    &["GITHUB_OAUTH_CLIENT_ID", "GITHUB_OAUTH_CLIENT_SECRET"],
+    &["SPOTIFY_OAUTH_CLIENT_ID", "SPOTIFY_OAUTH_CLIENT_SECRET"],
+    &["STRAVA_OAUTH_CLIENT_ID", "STRAVA_OAUTH_CLIENT_SECRET"],
+    &["STEAM_API_KEY", "STEAM_ID"],
+    &[
+        "S3_ENDPOINT",
+        "S3_BUCKET",
+        "S3_ACCESS_KEY_ID",
+        "S3_SECRET_ACCESS_KEY",
+    ],
+];
+
+/// Checks the raw variables, `lookup` returns the value of a variable or
+/// `None` if it's unset or empty
+fn check_vars(lookup: impl Fn(&str) -> Result<Option<String>, String>, report: &mut ConfigReport) {
+    let mut errors = Vec::new();
+    let mut lookup = |key: &str| match lookup(key) {
+        Ok(value) => value.filter(|v| !v.trim().is_empty()),
+        Err(e) => {
+            errors.push(e);
+            None
+        }
+    };
+
+    let required = REQUIRED_VARS
+        .iter()
+        .filter(|key| lookup(key).is_none())
+        .map(|key| format!("`{key}` is required"))
+        .collect::<Vec<_>>();
+
+    let invalid = TYPED_VARS
+        .iter()
+        .filter_map(|(key, expect)| {
+            let value = lookup(key)?;
+            let expected = expect.check(&value).err()?;
+            Some(format!("`{key}` must be {expected}, got `{value}`"))
+        })
+        .collect::<Vec<_>>();
+
+    let incomplete = PAIRED_VARS
+        .iter()
+        .filter_map(|keys| {
+            let (set, unset): (Vec<&str>, Vec<&str>) =
+                keys.iter().partition(|key| lookup(key).is_some());
+            (!set.is_empty() && !unset.is_empty()).then(|| {
+                format!(
+                    "`{}` must be set since `{}` is",
+                    unset.join("`, `"),
+                    set.join("`, `")
+                )
+            })
+        })
+        .collect::<Vec<_>>();
+
+    report.errors.extend(errors);
+    report.errors.extend(required);
+    report.errors.extend(invalid);
+    report.errors.extend(incomplete);
+}
+
+impl ServerConfig {
+    /// Every problem of the environment and of the resulting configuration
+    pub fn validate(&self) -> ConfigReport {
+        let mut report = ConfigReport::default();
+        check_vars(
+            |key| sources::lookup(key).map_err(|_| format!("`{key}` is not valid unicode")),
+            &mut report,
+        );
+        self.check_combinations(&mut report);
+        report
+    }
+
+    /// Settings that are valid on their own but not together
+    fn check_combinations(&self, report: &mut ConfigReport) {
+        let warnings = &mut report.warnings;
+        let production = matches!(self.env, Env::Production);
+
+        if production {
+            if !self.site_url.starts_with("https://") {
+                warnings.push(format!(
+                    "SITE_URL `{}` is not HTTPS in production, the session cookie is only sent over HTTPS",
+                    self.site_url
+                ));
+            }
+            // The CORS layer always lets localhost origins through, and there
+            // is no CSRF token to stop them
+            warnings.push(
+                "credentialed CORS requests are allowed from any http://localhost origin, \
+                 without CSRF protection"
+                    .to_string(),
+            );
+            if self.github_oauth.is_none() {
+                warnings.push("GitHub OAuth is not configured, nobody can log in".to_string());
+            }
+            if self
+                .anonymous_comments
+                .as_ref()
+                .is_some_and(|a| a.secret.len() < 32)
+            {
+                warnings.push(
+                    "ANONYMOUS_COMMENTS_SECRET is shorter than 32 characters, \
+                     the comment ownership tokens are easier to forge"
+                        .to_string(),
+                );
+            }
+            if matches!(
+                self.code_sandbox.as_ref().map(|c| &c.backend),
+                Some(CodeSandboxBackend::Firejail)
+            ) {
+                warnings.push(
+                    "the firejail code sandbox runs the agent's code on the server itself"
+                        .to_string(),
+                );
+            }
+        }
+
+        if self.discord_token.is_none() {
+            for (enabled, feature) in [
+                (self.discord_direct_messages, "DISCORD_DIRECT_MESSAGES"),
+                (
+                    self.discord_feed_publisher.is_some(),
+                    "DISCORD_FEED_CHANNEL",
+                ),
+                (
+                    matches!(
+                        self.comment_notifications,
+                        Some(
+                            CommentNotificationTarget::Channel(_)
+                                | CommentNotificationTarget::User(_)
+                        )
+                    ),
+                    "Discord comment notifications",
+                ),
+            ] {
+                if enabled {
+                    warnings.push(format!("{feature} has no effect without DISCORD_TOKEN"));
+                }
+            }
+        } else if self.openai_api_key.is_none() {
+            warnings.push("the Discord bot can't reply without OPENAI_API_KEY".to_string());
+        }
+
+        if let Some(ModerationConfig {
+            classifier: ModerationClassifier::OpenAi { api_key: None, .. },
+            ..
+        }) = &self.discord_moderation
+        {
+            warnings.push(
+                "DISCORD_MODERATION_API_KEY is not set for the OpenAI classifier".to_string(),
+            );
+        }
+
+        if self.web_search.providers.iter().any(|p| p == "brave")
+            && self.web_search.brave_api_key.is_none()
+        {
+            warnings.push("the brave search provider needs BRAVE_SEARCH_API_KEY".to_string());
+        }
+        if self.web_search.providers.iter().any(|p| p == "searxng")
+            && self.web_search.searxng_url.is_none()
+        {
+            warnings.push("the searxng search provider needs SEARXNG_URL".to_string());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_vars_reports_every_problem_at_once() {
+        let vars = std::collections::HashMap::from([
+            ("MAX_BODY_BYTES", "0"),
+            ("DISCORD_MENTION_ONLY", "yes"),
+            ("DISCORD_MODERATION_THRESHOLD", "0.5"),
+            ("WEB_SEARCH_PROVIDERS", "brave, bing"),
+            ("GITHUB_OAUTH_CLIENT_ID", "id"),
+            // Empty values from the .env template count as unset
+            ("SPOTIFY_OAUTH_CLIENT_ID", " "),
+        ]);
+        let mut report = ConfigReport::default();
+        check_vars(|key| Ok(vars.get(key).map(|v| v.to_string())), &mut report);
+
+        assert_eq!(
+            report.errors,
+            vec![
+                "`DATABASE_URL` is required",
+                "`MAX_BODY_BYTES` must be an integer greater than 0, got `0`",
+                "`DISCORD_MENTION_ONLY` must be `true` or `false`, got `yes`",
+                "`WEB_SEARCH_PROVIDERS` must be a comma separated list of searxng, brave, duckduckgo, got `brave, bing`",
+                "`GITHUB_OAUTH_CLIENT_SECRET` must be set since `GITHUB_OAUTH_CLIENT_ID` is",
+            ]
+        );
+    }
+}
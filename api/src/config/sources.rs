@@ -0,0 +1,252 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::OnceLock,
+};
+
+use eyre::Context as _;
+
+/// Suffix of the variables naming a file to read the value from, e.g.
+/// `DISCORD_TOKEN_FILE=/run/secrets/discord_token` for Docker and Kubernetes
+/// secrets
+const FILE_SUFFIX: &str = "_FILE";
+
+/// The file read when `--config` isn't passed, skipped if it doesn't exist
+const DEFAULT_CONFIG_FILE: &str = "config.toml";
+
+static SOURCES: OnceLock<Sources> = OnceLock::new();
+
+/// Where the settings come from, the first layer defining a variable wins: the
+/// command line overrides, then the environment (including `.env`), then the
+/// config file
+#[derive(Debug, Default)]
+pub struct Sources {
+    layers: Vec<HashMap<String, String>>,
+    config_file: ConfigFile,
+}
+
+#[derive(Debug, Default, Clone)]
+struct ConfigFile {
+    path: PathBuf,
+    /// Passed with `--config`, rather than the default one which may not exist
+    required: bool,
+}
+
+impl ConfigFile {
+    fn read(&self) -> Result<HashMap<String, String>, eyre::Error> {
+        if !self.required && !self.path.exists() {
+            return Ok(HashMap::new());
+        }
+        with_secret_files(read_config_file(&self.path)?)
+    }
+}
+
+impl Sources {
+    pub fn load(
+        config_file: Option<&Path>,
+        overrides: &[(String, String)],
+    ) -> Result<Self, eyre::Error> {
+        let config_file = ConfigFile {
+            path: config_file.unwrap_or(Path::new(DEFAULT_CONFIG_FILE)).into(),
+            required: config_file.is_some(),
+        };
+
+        Ok(Sources {
+            layers: vec![
+                with_secret_files(overrides.iter().cloned().collect())?,
+                with_secret_files(std::env::vars().collect())?,
+                config_file.read()?,
+            ],
+            config_file,
+        })
+    }
+
+    /// The sources with the config file read again, the command line and the
+    /// environment can't change while running
+    pub fn reload(&self) -> Result<Self, eyre::Error> {
+        let mut layers = self.layers.clone();
+        if let Some(file) = layers.last_mut() {
+            *file = self.config_file.read()?;
+        }
+        Ok(Sources {
+            layers,
+            config_file: self.config_file.clone(),
+        })
+    }
+
+    pub fn config_file(&self) -> &Path {
+        &self.config_file.path
+    }
+
+    /// The sources passed to [Sources::install]
+    pub fn installed() -> Option<&'static Sources> {
+        SOURCES.get()
+    }
+
+    /// Makes the sources the ones [super::ServerConfig::new_from_env] reads
+    pub fn install(self) {
+        if SOURCES.set(self).is_err() {
+            tracing::warn!("Configuration sources were already installed");
+        }
+    }
+
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.layers
+            .iter()
+            .find_map(|layer| layer.get(key))
+            .map(String::as_str)
+    }
+}
+
+/// Value of a variable from the installed sources, or from the environment
+/// only if none were installed
+pub(super) fn lookup(key: &str) -> Result<Option<String>, std::env::VarError> {
+    match SOURCES.get() {
+        Some(sources) => Ok(sources.get(key).map(str::to_string)),
+        None => match std::env::var(key) {
+            Ok(value) => Ok(Some(value)),
+            Err(std::env::VarError::NotPresent) => Ok(None),
+            Err(e) => Err(e),
+        },
+    }
+}
+
+/// Reads the `KEY_FILE` variables of a layer into `KEY`, unless the layer
+/// sets `KEY` itself
+fn with_secret_files(
+    mut layer: HashMap<String, String>,
+) -> Result<HashMap<String, String>, eyre::Error> {
+    let files = layer
+        .iter()
+        .filter_map(|(key, path)| {
+            let key = key.strip_suffix(FILE_SUFFIX)?;
+            (!key.is_empty() && !layer.contains_key(key) && !path.trim().is_empty())
+                .then(|| (key.to_string(), PathBuf::from(path.trim())))
+        })
+        .collect::<Vec<_>>();
+
+    for (key, path) in files {
+        let value = std::fs::read_to_string(&path)
+            .wrap_err_with(|| format!("failed to read `{key}{FILE_SUFFIX}` {}", path.display()))?;
+        layer.insert(key, value.trim_end_matches(['\r', '\n']).to_string());
+    }
+
+    Ok(layer)
+}
+
+fn read_config_file(path: &Path) -> Result<HashMap<String, String>, eyre::Error> {
+    let content = std::fs::read_to_string(path)
+        .wrap_err_with(|| format!("failed to read config file {}", path.display()))?;
+    parse_config_file(&content).wrap_err_with(|| format!("invalid config file {}", path.display()))
+}
+
+/// Flattens a TOML file into the environment variables it stands for, tables
+/// prefix their keys and arrays become comma separated lists:
+///
+/// ```toml
+/// site_url = "https://example.com"
+///
+/// [discord]
+/// token_file = "/run/secrets/discord_token"
+/// whitelist_channels = [123, 456]
+/// ```
+///
+/// sets `SITE_URL`, `DISCORD_TOKEN_FILE` and `DISCORD_WHITELIST_CHANNELS`
+fn parse_config_file(content: &str) -> Result<HashMap<String, String>, eyre::Error> {
+    let table = content.parse::<toml::Table>()?;
+    let mut vars = HashMap::new();
+    flatten("", &table, &mut vars)?;
+    Ok(vars)
+}
+
+fn flatten(
+    prefix: &str,
+    table: &toml::Table,
+    vars: &mut HashMap<String, String>,
+) -> Result<(), eyre::Error> {
+    for (key, value) in table {
+        let key = format!("{prefix}{}", key.to_uppercase());
+        match value {
+            toml::Value::Table(table) => flatten(&format!("{key}_"), table, vars)?,
+            toml::Value::Array(items) => {
+                let items = items
+                    .iter()
+                    .map(|item| scalar(&key, item))
+                    .collect::<Result<Vec<_>, _>>()?;
+                vars.insert(key, items.join(","));
+            }
+            value => {
+                let value = scalar(&key, value)?;
+                vars.insert(key, value);
+            }
+        }
+    }
+    Ok(())
+}
+
+fn scalar(key: &str, value: &toml::Value) -> Result<String, eyre::Error> {
+    Ok(match value {
+        toml::Value::String(s) => s.clone(),
+        toml::Value::Integer(n) => n.to_string(),
+        toml::Value::Float(n) => n.to_string(),
+        toml::Value::Boolean(b) => b.to_string(),
+        toml::Value::Datetime(d) => d.to_string(),
+        toml::Value::Array(_) | toml::Value::Table(_) => {
+            eyre::bail!("`{key}` must be a value or a list of values")
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn layers_override_the_config_file() {
+        let file = parse_config_file(
+            r#"
+            site_url = "https://file.example"
+            environment = "staging"
+
+            [discord]
+            mention_only = false
+            whitelist_channels = [1, 2]
+            "#,
+        )
+        .expect("valid config file");
+        assert_eq!(file["DISCORD_WHITELIST_CHANNELS"], "1,2");
+        assert_eq!(file["DISCORD_MENTION_ONLY"], "false");
+
+        let sources = Sources {
+            config_file: ConfigFile::default(),
+            layers: vec![
+                HashMap::from([("ENVIRONMENT".to_string(), "production".to_string())]),
+                HashMap::from([("SITE_URL".to_string(), "https://env.example".to_string())]),
+                file,
+            ],
+        };
+        assert_eq!(sources.get("ENVIRONMENT"), Some("production"));
+        assert_eq!(sources.get("SITE_URL"), Some("https://env.example"));
+        assert_eq!(sources.get("DISCORD_MENTION_ONLY"), Some("false"));
+        assert_eq!(sources.get("DISCORD_TOKEN"), None);
+    }
+
+    #[test]
+    fn secret_files_fill_the_variables() {
+        let path = std::env::temp_dir().join(format!("api-secret-{}", std::process::id()));
+        std::fs::write(&path, "hunter2\n").expect("temp file is writable");
+        let path = path.display().to_string();
+
+        let layer = with_secret_files(HashMap::from([
+            ("DISCORD_TOKEN_FILE".to_string(), path.clone()),
+            ("OPENAI_API_KEY".to_string(), "set".to_string()),
+            ("OPENAI_API_KEY_FILE".to_string(), path.clone()),
+        ]))
+        .expect("secret file is readable");
+        let _ = std::fs::remove_file(&path);
+
+        assert_eq!(layer["DISCORD_TOKEN"], "hunter2");
+        // The variable itself wins over its file
+        assert_eq!(layer["OPENAI_API_KEY"], "set");
+    }
+}
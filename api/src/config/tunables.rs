@@ -0,0 +1,291 @@
+use std::{
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime},
+};
+
+use arc_swap::ArcSwap;
+use eyre::Context as _;
+use tokio::signal::unix::{SignalKind, signal};
+
+use super::{ConfigReport, Sources, check_vars};
+use crate::recommendation::RankingPreset;
+
+/// How often the config file is checked for modifications
+const RELOAD_CHECK_INTERVAL: Duration = Duration::from_secs(30);
+
+/// RRF k constants of a ranking preset, a lower k gives more weight to the
+/// top-ranked items of that signal
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RankingK {
+    pub similarity: f64,
+    pub external: f64,
+}
+
+/// Settings that take effect without a restart, read from the same variables
+/// as the rest of the configuration
+#[derive(Clone, Debug, PartialEq)]
+pub struct TunableValues {
+    /// Time between two crawls of the recommendation sources that don't set
+    /// their own interval
+    pub crawl_interval: Duration,
+    /// A crawl isn't started if the last one was more recent than this
+    pub min_crawl_interval: Duration,
+    /// Default of the guilds and channels that don't override it
+    pub discord_mention_only: bool,
+    /// Messages a user may send the bot per hour in DMs
+    pub discord_dm_messages_per_hour: u32,
+    /// Messages a user may get the agent to answer per 10 minutes in a
+    /// channel that doesn't override it, 0 for no limit
+    pub discord_user_messages_per_10_mins: u32,
+    pub ranking_balanced: RankingK,
+    pub ranking_newer_first: RankingK,
+    pub ranking_top_first: RankingK,
+    pub ranking_similar_first: RankingK,
+}
+
+impl TunableValues {
+    /// Reads the values, the ones unset or invalid keep their defaults. Invalid
+    /// values are reported by [check_vars].
+    fn from_lookup(lookup: impl Fn(&str) -> Option<String>) -> Self {
+        let parse = |key: &str| lookup(key).and_then(|s| s.trim().parse::<f64>().ok());
+        let minutes = |key: &str, default: u64| {
+            Duration::from_mins(
+                lookup(key)
+                    .and_then(|s| s.trim().parse::<u64>().ok())
+                    .filter(|m| *m > 0)
+                    .unwrap_or(default),
+            )
+        };
+        let ranking = |preset: &str, similarity: f64, external: f64| RankingK {
+            similarity: parse(&format!("RECOMMENDER_RANKING_{preset}_SIMILARITY_K"))
+                .filter(|k| *k > 0.0)
+                .unwrap_or(similarity),
+            external: parse(&format!("RECOMMENDER_RANKING_{preset}_EXTERNAL_K"))
+                .filter(|k| *k > 0.0)
+                .unwrap_or(external),
+        };
+
+        TunableValues {
+            crawl_interval: minutes("RECOMMENDER_CRAWL_INTERVAL_MINS", 8 * 60),
+            min_crawl_interval: minutes("RECOMMENDER_MIN_CRAWL_INTERVAL_MINS", 10),
+            discord_mention_only: lookup("DISCORD_MENTION_ONLY")
+                .and_then(|s| s.parse::<bool>().ok())
+                .unwrap_or(true),
+            discord_dm_messages_per_hour: lookup("DISCORD_DM_MESSAGES_PER_HOUR")
+                .and_then(|s| s.trim().parse::<u32>().ok())
+                .filter(|n| *n > 0)
+                .unwrap_or(20),
+            discord_user_messages_per_10_mins: lookup("DISCORD_USER_MESSAGES_PER_10_MINS")
+                .and_then(|s| s.trim().parse::<u32>().ok())
+                .unwrap_or(10),
+            ranking_balanced: ranking("BALANCED", 12.0, 6.0),
+            ranking_newer_first: ranking("NEWER_FIRST", 20.0, 15.0),
+            ranking_top_first: ranking("TOP_FIRST", 25.0, 1.0),
+            ranking_similar_first: ranking("SIMILAR_FIRST", 1.0, 25.0),
+        }
+    }
+
+    pub fn ranking_k(&self, preset: RankingPreset) -> RankingK {
+        match preset {
+            RankingPreset::Balanced => self.ranking_balanced,
+            RankingPreset::NewerFirst => self.ranking_newer_first,
+            RankingPreset::TopFirst => self.ranking_top_first,
+            RankingPreset::SimilarFirst => self.ranking_similar_first,
+        }
+    }
+
+    /// `name: old -> new` of every value that differs
+    fn changes(&self, new: &Self) -> Vec<String> {
+        let fields: [(&str, String, String); 9] = [
+            (
+                "crawl_interval",
+                format!("{:?}", self.crawl_interval),
+                format!("{:?}", new.crawl_interval),
+            ),
+            (
+                "min_crawl_interval",
+                format!("{:?}", self.min_crawl_interval),
+                format!("{:?}", new.min_crawl_interval),
+            ),
+            (
+                "discord_mention_only",
+                self.discord_mention_only.to_string(),
+                new.discord_mention_only.to_string(),
+            ),
+            (
+                "discord_dm_messages_per_hour",
+                self.discord_dm_messages_per_hour.to_string(),
+                new.discord_dm_messages_per_hour.to_string(),
+            ),
+            (
+                "discord_user_messages_per_10_mins",
+                self.discord_user_messages_per_10_mins.to_string(),
+                new.discord_user_messages_per_10_mins.to_string(),
+            ),
+            (
+                "ranking_balanced",
+                format!("{:?}", self.ranking_balanced),
+                format!("{:?}", new.ranking_balanced),
+            ),
+            (
+                "ranking_newer_first",
+                format!("{:?}", self.ranking_newer_first),
+                format!("{:?}", new.ranking_newer_first),
+            ),
+            (
+                "ranking_top_first",
+                format!("{:?}", self.ranking_top_first),
+                format!("{:?}", new.ranking_top_first),
+            ),
+            (
+                "ranking_similar_first",
+                format!("{:?}", self.ranking_similar_first),
+                format!("{:?}", new.ranking_similar_first),
+            ),
+        ];
+
+        fields
+            .into_iter()
+            .filter(|(_, old, new)| old != new)
+            .map(|(name, old, new)| format!("{name}: {old} -> {new}"))
+            .collect()
+    }
+}
+
+struct Inner {
+    values: ArcSwap<TunableValues>,
+    /// Modification time of the config file when it was last read
+    modified: Mutex<Option<SystemTime>>,
+}
+
+/// The hot-reloadable settings, reloaded from the config file on SIGHUP or
+/// when the file changes
+#[derive(Clone)]
+pub struct Tunables(Arc<Inner>);
+
+impl Tunables {
+    /// The values of the installed [Sources]
+    pub fn new() -> Self {
+        let values = match Sources::installed() {
+            Some(sources) => TunableValues::from_lookup(|key| sources.get(key).map(str::to_string)),
+            None => TunableValues::from_lookup(|key| std::env::var(key).ok()),
+        };
+        Self(Arc::new(Inner {
+            values: ArcSwap::from_pointee(values),
+            modified: Mutex::new(config_file_modified()),
+        }))
+    }
+
+    pub fn get(&self) -> arc_swap::Guard<Arc<TunableValues>> {
+        self.0.values.load()
+    }
+
+    /// Reads the config file again. Nothing changes if any variable is invalid.
+    pub fn reload(&self) -> Result<(), eyre::Error> {
+        let sources = Sources::installed()
+            .ok_or(eyre::eyre!("configuration sources are not installed"))?
+            .reload()
+            .wrap_err("failed to reload the configuration")?;
+
+        let mut report = ConfigReport::default();
+        check_vars(|key| Ok(sources.get(key).map(str::to_string)), &mut report);
+        if !report.is_ok() {
+            eyre::bail!("invalid configuration, keeping the current one:\n{report}");
+        }
+
+        let new = TunableValues::from_lookup(|key| sources.get(key).map(str::to_string));
+        let changes = self.get().changes(&new);
+        if changes.is_empty() {
+            tracing::info!("Configuration reloaded, nothing changed");
+            return Ok(());
+        }
+
+        for change in changes {
+            tracing::info!(change, "Configuration changed");
+        }
+        self.0.values.store(Arc::new(new));
+        Ok(())
+    }
+
+    fn reload_if_changed(&self) {
+        let modified = config_file_modified();
+        let Ok(mut last) = self.0.modified.lock() else {
+            return;
+        };
+        if *last == modified {
+            return;
+        }
+        *last = modified;
+        drop(last);
+
+        if let Err(e) = self.reload() {
+            tracing::error!("Failed to reload the configuration: {e:?}");
+        }
+    }
+}
+
+impl Default for Tunables {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn config_file_modified() -> Option<SystemTime> {
+    let path = Sources::installed()?.config_file();
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+/// Reloads the tunables on SIGHUP and when the config file is modified
+pub fn start_reload_watcher(tunables: Tunables) {
+    tokio::spawn(async move {
+        let mut hangup = match signal(SignalKind::hangup()) {
+            Ok(hangup) => Some(hangup),
+            Err(e) => {
+                tracing::warn!("Failed to listen for SIGHUP, only watching the config file: {e}");
+                None
+            }
+        };
+        let mut interval = tokio::time::interval(RELOAD_CHECK_INTERVAL);
+        // The first tick completes immediately and the file was just read
+        interval.tick().await;
+
+        loop {
+            tokio::select! {
+                Some(()) = async { hangup.as_mut()?.recv().await } => {
+                    tracing::info!("Received SIGHUP, reloading the configuration");
+                    if let Err(e) = tunables.reload() {
+                        tracing::error!("Failed to reload the configuration: {e:?}");
+                    }
+                }
+                _ = interval.tick() => tunables.reload_if_changed(),
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn changes_lists_the_updated_values() {
+        let old = TunableValues::from_lookup(|_| None);
+        assert_eq!(old.crawl_interval, Duration::from_hours(8));
+        assert_eq!(old.ranking_k(RankingPreset::TopFirst).external, 1.0);
+
+        let new = TunableValues::from_lookup(|key| match key {
+            "RECOMMENDER_CRAWL_INTERVAL_MINS" => Some("60".to_string()),
+            "RECOMMENDER_RANKING_BALANCED_EXTERNAL_K" => Some("3".to_string()),
+            // Invalid values keep the default
+            "DISCORD_DM_MESSAGES_PER_HOUR" => Some("0".to_string()),
+            _ => None,
+        });
+        assert_eq!(
+            old.changes(&new),
+            vec![
+                "crawl_interval: 28800s -> 3600s",
+                "ranking_balanced: RankingK { similarity: 12.0, external: 6.0 } -> RankingK { similarity: 12.0, external: 3.0 }",
+            ]
+        );
+    }
+}
@@ -1 +1,2 @@
 pub mod random;
+pub mod signed_token;
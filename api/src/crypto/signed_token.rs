@@ -0,0 +1,58 @@
+//! Tokens proving that the server issued them for a subject until they expire,
+//! `<subject>.<expiry unix timestamp>.<HMAC-SHA256 of the two>`. The subject
+//! and its dot are left out when it's empty.
+
+use base64::{Engine as _, engine::general_purpose::URL_SAFE_NO_PAD};
+use hmac::{Hmac, KeyInit as _, Mac as _};
+use sha2::Sha256;
+
+pub fn sign(secret: &str, subject: &str, expires: i64) -> Result<String, eyre::Error> {
+    let payload = match subject {
+        "" => expires.to_string(),
+        subject => format!("{subject}.{expires}"),
+    };
+    let signature = mac(secret, &payload)?.finalize().into_bytes();
+    Ok(format!("{payload}.{}", URL_SAFE_NO_PAD.encode(signature)))
+}
+
+/// The subject of the token if it was signed with the secret and hasn't
+/// expired by `now`
+pub fn verify_at<'a>(secret: &str, token: &'a str, now: i64) -> Option<&'a str> {
+    let (payload, signature) = token.rsplit_once('.')?;
+    let signature = URL_SAFE_NO_PAD.decode(signature).ok()?;
+    // Checked in constant time before looking at the payload
+    mac(secret, payload).ok()?.verify_slice(&signature).ok()?;
+
+    let (subject, expires) = payload.rsplit_once('.').unwrap_or(("", payload));
+    expires
+        .parse::<i64>()
+        .is_ok_and(|expires| now < expires)
+        .then_some(subject)
+}
+
+fn mac(secret: &str, payload: &str) -> Result<Hmac<Sha256>, eyre::Error> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes())?;
+    mac.update(payload.as_bytes());
+    Ok(mac)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tokens_are_valid_for_their_subject_until_expiry() {
+        let token = sign("secret", "42", 1_000).expect("any key length is valid");
+
+        assert_eq!(verify_at("secret", &token, 999), Some("42"));
+        assert_eq!(verify_at("secret", &token, 1_000), None);
+        assert_eq!(verify_at("other secret", &token, 999), None);
+
+        // Extending the expiry invalidates the signature
+        let forged = token.replacen("1000", "9000", 1);
+        assert_eq!(verify_at("secret", &forged, 999), None);
+
+        let token = sign("secret", "", 1_000).expect("any key length is valid");
+        assert_eq!(verify_at("secret", &token, 999), Some(""));
+    }
+}
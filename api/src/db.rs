@@ -0,0 +1,261 @@
+//! The database pool. The connections checked out for a request cancel the
+//! statements running longer than `DB_STATEMENT_TIMEOUT_MS` so that a runaway
+//! query can't hang it, the background jobs check out theirs without the
+//! timeout. Every connection logs the queries slower than `DB_SLOW_QUERY_MS`
+//! in the span of whatever ran them. The waits for a connection are counted
+//! for `/admin/db`.
+
+use std::{
+    sync::{
+        Arc,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::{Duration, Instant},
+};
+
+use axum::{Json, Router, extract::State, http::StatusCode, routing::get};
+use diesel::connection::{Instrumentation, InstrumentationEvent};
+use diesel_async::{
+    AsyncConnection as _, AsyncPgConnection, SimpleAsyncConnection as _,
+    pooled_connection::{
+        AsyncDieselConnectionManager, PoolError as ConnectionPoolError,
+        deadpool::{Hook, HookError, Object, Pool, PoolError},
+    },
+};
+use serde::Serialize;
+
+use crate::{App, config::ServerConfig, error::AppError, identity::AuthUser};
+
+const MAX_POOL_SIZE: usize = 7;
+const POOL_WAIT_TIMEOUT: Duration = Duration::from_secs(15);
+/// Waits for a connection longer than this are counted apart
+const SLOW_WAIT: Duration = Duration::from_millis(100);
+const MAX_LOGGED_QUERY_CHARS: usize = 2000;
+
+pub fn admin_route() -> Router<App> {
+    Router::<App>::new().route("/admin/db", get(get_pool_stats))
+}
+
+/// The pool of the app, used like the deadpool one
+pub struct Db {
+    pool: Pool<AsyncPgConnection>,
+    counters: Arc<Counters>,
+    statement_timeout: Duration,
+}
+
+#[derive(Default)]
+struct Counters {
+    checkouts: AtomicU64,
+    wait_micros: AtomicU64,
+    max_wait_micros: AtomicU64,
+    slow_waits: AtomicU64,
+    slow_queries: AtomicU64,
+    timed_out_queries: AtomicU64,
+}
+
+impl Db {
+    pub fn new(config: &ServerConfig) -> Self {
+        let counters = Arc::new(Counters::default());
+        let manager =
+            AsyncDieselConnectionManager::<AsyncPgConnection>::new(config.database_url.clone());
+        let statement_timeout = config.db_statement_timeout;
+        let slow_query = config.db_slow_query;
+        let log_counters = counters.clone();
+
+        // TODO consider using bb8 pool since it has more features (min_idle, max_lifetime etc.)
+        let pool = Pool::builder(manager)
+            .max_size(MAX_POOL_SIZE)
+            .wait_timeout(Some(POOL_WAIT_TIMEOUT))
+            .runtime(deadpool_runtime::Runtime::Tokio1)
+            .post_create(Hook::async_fn(move |conn: &mut AsyncPgConnection, _| {
+                let counters = log_counters.clone();
+                Box::pin(async move {
+                    conn.set_instrumentation(SlowQueryLog {
+                        threshold: slow_query,
+                        started: None,
+                        counters,
+                    });
+                    Ok(())
+                })
+            }))
+            // The timeout set for a request is left on the connection
+            .post_recycle(Hook::async_fn(|conn: &mut AsyncPgConnection, _| {
+                Box::pin(async move {
+                    conn.batch_execute("RESET statement_timeout")
+                        .await
+                        .map_err(|e| HookError::Backend(ConnectionPoolError::QueryError(e)))
+                })
+            }))
+            .build()
+            .expect("could not build Diesel pool");
+
+        Self {
+            pool,
+            counters,
+            statement_timeout,
+        }
+    }
+
+    /// A connection of the pool for a request, its statements time out. The
+    /// wait for it is counted.
+    pub async fn get(&self) -> Result<Object<AsyncPgConnection>, PoolError> {
+        let mut conn = self.get_unbounded().await?;
+        conn.batch_execute(&format!(
+            "SET statement_timeout = {}",
+            self.statement_timeout.as_millis()
+        ))
+        .await
+        .map_err(|e| PoolError::Backend(ConnectionPoolError::QueryError(e)))?;
+        Ok(conn)
+    }
+
+    /// A connection of the pool without the statement timeout, for the
+    /// background jobs whose queries may take long. The wait for it is counted.
+    pub async fn get_unbounded(&self) -> Result<Object<AsyncPgConnection>, PoolError> {
+        let started = Instant::now();
+        let conn = self.pool.get().await;
+        self.counters.record_wait(started.elapsed());
+        conn
+    }
+
+    /// The pool itself, for the services that share it and for checking out
+    /// connections without counting the wait
+    pub fn pool(&self) -> &Pool<AsyncPgConnection> {
+        &self.pool
+    }
+}
+
+impl Counters {
+    fn record_wait(&self, wait: Duration) {
+        let micros = wait.as_micros() as u64;
+        self.checkouts.fetch_add(1, Ordering::Relaxed);
+        self.wait_micros.fetch_add(micros, Ordering::Relaxed);
+        self.max_wait_micros.fetch_max(micros, Ordering::Relaxed);
+        if wait >= SLOW_WAIT {
+            self.slow_waits.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+}
+
+/// Logs the queries of a connection taking longer than the threshold. The
+/// queries of a connection run one at a time, so the start of the running one
+/// is enough.
+struct SlowQueryLog {
+    threshold: Duration,
+    started: Option<Instant>,
+    counters: Arc<Counters>,
+}
+
+impl Instrumentation for SlowQueryLog {
+    fn on_connection_event(&mut self, event: InstrumentationEvent<'_>) {
+        match event {
+            InstrumentationEvent::StartQuery { .. } => self.started = Some(Instant::now()),
+            InstrumentationEvent::FinishQuery { query, error, .. } => {
+                let Some(started) = self.started.take() else {
+                    return;
+                };
+                let elapsed = started.elapsed();
+                let timed_out = error.is_some_and(|e| {
+                    e.to_string()
+                        .contains("canceling statement due to statement timeout")
+                });
+
+                if timed_out {
+                    self.counters
+                        .timed_out_queries
+                        .fetch_add(1, Ordering::Relaxed);
+                    tracing::error!(
+                        elapsed_ms = elapsed.as_millis(),
+                        query = logged_sql(&query.to_string()),
+                        "Query cancelled by the statement timeout"
+                    );
+                } else if elapsed >= self.threshold {
+                    self.counters.slow_queries.fetch_add(1, Ordering::Relaxed);
+                    tracing::warn!(
+                        elapsed_ms = elapsed.as_millis(),
+                        query = logged_sql(&query.to_string()),
+                        "Slow query"
+                    );
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// The SQL of a debug query without its binds, which may be tokens or
+/// personal data
+fn logged_sql(query: &str) -> String {
+    let sql = query.split(" -- binds: ").next().unwrap_or_default().trim();
+    match sql.char_indices().nth(MAX_LOGGED_QUERY_CHARS) {
+        Some((end, _)) => format!("{}…", &sql[..end]),
+        None => sql.to_string(),
+    }
+}
+
+#[derive(Serialize)]
+struct PoolStats {
+    size: u64,
+    max_size: u64,
+    available: u64,
+    /// Callers waiting for a connection right now
+    waiting: u64,
+    checkouts: u64,
+    average_wait_ms: f64,
+    max_wait_ms: f64,
+    /// Checkouts that waited longer than 100ms
+    slow_waits: u64,
+    slow_queries: u64,
+    timed_out_queries: u64,
+}
+
+/// The connections of the pool and the counters since the start
+async fn get_pool_stats(
+    State(ctx): State<App>,
+    AuthUser(i): AuthUser,
+) -> Result<Json<PoolStats>, AppError> {
+    if i.id != ctx.config.owner_identity_id {
+        return Err(("Not permitted", StatusCode::FORBIDDEN).into());
+    }
+
+    let status = ctx.diesel.pool.status();
+    let counters = &ctx.diesel.counters;
+    let checkouts = counters.checkouts.load(Ordering::Relaxed);
+    let wait_ms = counters.wait_micros.load(Ordering::Relaxed) as f64 / 1000.0;
+
+    Ok(Json(PoolStats {
+        size: status.size as u64,
+        max_size: status.max_size as u64,
+        available: status.available as u64,
+        waiting: status.waiting as u64,
+        checkouts,
+        average_wait_ms: if checkouts == 0 {
+            0.0
+        } else {
+            wait_ms / checkouts as f64
+        },
+        max_wait_ms: counters.max_wait_micros.load(Ordering::Relaxed) as f64 / 1000.0,
+        slow_waits: counters.slow_waits.load(Ordering::Relaxed),
+        slow_queries: counters.slow_queries.load(Ordering::Relaxed),
+        timed_out_queries: counters.timed_out_queries.load(Ordering::Relaxed),
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_binds_are_left_out_of_the_logged_queries() {
+        assert_eq!(
+            logged_sql(r#"SELECT * FROM "sessions" WHERE "token" = $1 -- binds: ["secret"]"#),
+            r#"SELECT * FROM "sessions" WHERE "token" = $1"#
+        );
+
+        let long = "x".repeat(MAX_LOGGED_QUERY_CHARS + 10);
+        assert_eq!(
+            logged_sql(&long).chars().count(),
+            MAX_LOGGED_QUERY_CHARS + 1
+        );
+    }
+}
@@ -1,46 +1,151 @@
+use crate::blog::lookup::BlogReader;
+use crate::clients::GodboltClient;
 use crate::discord::{
-    constants::{MAX_AGENT_TURNS, MESSAGE_CONTEXT_SIZE, SYSTEM_PROMPT},
-    tools::{DiscordSendMessageTool, FetchPageContentTool, WebSearchTool},
+    constants::{
+        HISTORY_TOKEN_BUDGET, MAX_AGENT_TURNS, MAX_RUN_RETRIES, MESSAGE_CONTEXT_SIZE,
+        RUN_RETRY_BASE_DELAY, SUMMARY_PROMPT,
+    },
+    context_budget::{excess_over_budget, history_tokens, truncate_message},
+    message::{deleted_message, message_id_of},
+    moderation::Moderation,
+    prompt::system_prompt,
+    reminders::Reminders,
+    repetition::RecentReplies,
+    tools::{
+        BlogPostInfoTool, BlogRecentCommentsTool, CalculateTool, CancelReminderTool, CodeRunTool,
+        CodeRunner, DiscordSendMessageTool, FeedTopTool, FetchPageContentTool, GitHubSearchTool,
+        ListRemindersTool, MemoryScopes, ModerationDeleteTool, ModerationTarget,
+        ModerationTimeoutTool, ModerationWarnTool, RemindMeTool, WebSearch, WebSearchTool,
+    },
 };
+use crate::outbound::HttpClient;
+use crate::recommendation::FeedReader;
+use axum::http::StatusCode;
 use eyre::Context as _;
+use futures::StreamExt as _;
 use rig::{
-    agent::Agent,
+    OneOrMany,
+    agent::{Agent, AgentBuilder, HookAction, MultiTurnStreamItem, PromptHook},
     client::CompletionClient,
-    completion::{Message as RigMessage, Prompt},
+    completion::{Message as RigMessage, Prompt, Usage},
+    message::{AssistantContent, ToolResultContent, UserContent},
     providers::openrouter::{Client, CompletionModel},
+    streaming::{StreamedAssistantContent, StreamingPrompt},
+    tool::{Tool as _, ToolDyn},
+};
+use serenity::all::{ChannelId, Context, GuildId, MessageId, UserId};
+use std::{
+    collections::HashSet,
+    sync::{Arc, Mutex},
 };
-use serenity::all::{ChannelId, Context};
-use std::sync::Arc;
 use tracing::instrument;
 
-use super::tools::SharedVectorClient;
+use super::{
+    archive::{ChannelArchive, MessageArchive},
+    concurrency::ExecutionLimiter,
+    settings::ChannelSettings,
+    streaming::StreamingReplies,
+    tools::SharedVectorClient,
+    transcripts::{AgentTranscript, ChannelTranscripts, TranscriptRecorder, TranscriptTurn},
+    usage::UsageTracker,
+};
+
+/// The result of a single prompt of the agent, which may span multiple completion requests due
+/// to tool calls
+struct RunOutput {
+    output: String,
+    usage: Usage,
+    requests: usize,
+    messages: Option<Vec<RigMessage>>,
+}
 
 /// Agent session for persistent multi-turn conversations
 pub struct AgentSession {
     pub agent: Agent<CompletionModel>,
     pub conversation_history: Vec<RigMessage>,
+
+    /// Tool-less agent used to compress the trimmed history into the synopsis
+    summarizer: Agent<CompletionModel>,
+    /// Summary of the conversation trimmed from the history. It's placed before
+    /// the history and only changes when the history is compacted, so together
+    /// with the system prompt it forms a stable prefix for prompt caching.
+    synopsis: Option<String>,
+    /// Messages trimmed from the history but not yet folded into the synopsis
+    unsummarized: Vec<RigMessage>,
+
+    /// Set if the responses are streamed into placeholder messages
+    streaming: Option<StreamingReplies>,
+
+    /// The channel the token usage is accounted to, the parent channel for threads
+    channel_id: ChannelId,
+    model: String,
+    usage: UsageTracker,
+    /// Set if the channel's conversations are archived
+    archive: Option<ChannelArchive>,
+    /// Set if the agent runs are recorded
+    transcripts: Option<ChannelTranscripts>,
 }
 
 impl AgentSession {
-    pub fn new(agent: Agent<CompletionModel>, initial_history: Vec<RigMessage>) -> Self {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        agent: Agent<CompletionModel>,
+        summarizer: Agent<CompletionModel>,
+        initial_history: Vec<RigMessage>,
+        streaming: Option<StreamingReplies>,
+        channel_id: ChannelId,
+        model: String,
+        usage: UsageTracker,
+        archive: Option<ChannelArchive>,
+        transcripts: Option<ChannelTranscripts>,
+    ) -> Self {
         Self {
             agent,
             conversation_history: initial_history,
+            summarizer,
+            synopsis: None,
+            unsummarized: Vec::new(),
+            streaming,
+            channel_id,
+            model,
+            usage,
+            archive,
+            transcripts,
         }
     }
 
     /// Add messages to the conversation history, trimming excess if needed but new messages are
-    /// always kept
-    pub fn add_messages(&mut self, messages: Vec<RigMessage>) {
+    /// always kept. Only the new messages are appended to the history, which is compacted in one
+    /// go once it grows past the limit in messages or tokens, so the prompt prefix stays the same
+    /// between most turns and can be served from the provider's prompt cache.
+    pub fn add_messages(&mut self, mut messages: Vec<RigMessage>) {
+        // Messages already in the history, e.g. loaded from the channel when the session was
+        // created while they were queued, aren't added twice
+        let seen: HashSet<_> = self
+            .conversation_history
+            .iter()
+            .filter_map(message_id_of)
+            .collect();
+        messages.retain(|m| message_id_of(m).is_none_or(|id| !seen.contains(&id)));
+
+        let new_messages = messages.len();
         let max_history =
-            ((MESSAGE_CONTEXT_SIZE as f32 * 1.5f32).floor() as usize).max(messages.len());
+            ((MESSAGE_CONTEXT_SIZE as f32 * 1.5f32).floor() as usize).max(new_messages);
+        let retained_history = MESSAGE_CONTEXT_SIZE.max(new_messages);
+        let max_tokens = HISTORY_TOKEN_BUDGET * 3 / 2;
 
+        messages.iter_mut().for_each(truncate_message);
         self.conversation_history.extend(messages);
 
-        // TODO: leverage prompt caching to reduce cost
-        // https://platform.openai.com/docs/guides/prompt-caching
-        if self.conversation_history.len() > max_history {
-            let mut excess = self.conversation_history.len() - max_history;
+        let len = self.conversation_history.len();
+        if len > max_history || history_tokens(&self.conversation_history) > max_tokens {
+            // Trim down to MESSAGE_CONTEXT_SIZE and HISTORY_TOKEN_BUDGET instead of the maximums
+            // so that the next compaction, which invalidates the cached prefix, is several
+            // batches away
+            let mut excess = len.saturating_sub(retained_history).max(
+                excess_over_budget(&self.conversation_history, HISTORY_TOKEN_BUDGET)
+                    .min(len - new_messages),
+            );
 
             // Resume trimming at a clean turn boundary: the first User message
             // that isn't a tool result. Starting on a tool result would orphan
@@ -62,10 +167,100 @@ impl AgentSession {
                 break;
             }
 
-            self.conversation_history.drain(0..excess);
+            self.unsummarized
+                .extend(self.conversation_history.drain(0..excess));
         }
     }
 
+    /// Replaces a message of the history that was edited, returns whether it was there
+    pub fn replace_message(&mut self, message_id: MessageId, mut message: RigMessage) -> bool {
+        let Some(existing) = self
+            .conversation_history
+            .iter_mut()
+            .find(|m| message_id_of(m) == Some(message_id))
+        else {
+            return false;
+        };
+        truncate_message(&mut message);
+        *existing = message;
+        true
+    }
+
+    /// Leaves a tombstone in place of a message of the history that was deleted, so that the
+    /// agent doesn't answer it or quote it
+    pub fn tombstone_message(&mut self, message_id: MessageId) {
+        if let Some(existing) = self
+            .conversation_history
+            .iter_mut()
+            .find(|m| message_id_of(m) == Some(message_id))
+        {
+            *existing = deleted_message(existing, message_id);
+        }
+    }
+
+    /// Fold the trimmed messages into the synopsis. On failure the messages are kept around and
+    /// the summarization is retried on the next run.
+    async fn summarize_trimmed_history(&mut self) -> Result<(), eyre::Error> {
+        if self.unsummarized.is_empty() {
+            return Ok(());
+        }
+
+        let transcript = self
+            .unsummarized
+            .iter()
+            .map(render_message_for_summary)
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        let prompt = format!(
+            "[PREVIOUS SYNOPSIS]\n{}\n\n[NEW MESSAGES]\n{transcript}",
+            self.synopsis.as_deref().unwrap_or("None")
+        );
+
+        let response = self
+            .summarizer
+            .prompt(prompt)
+            .extended_details()
+            .await
+            .wrap_err("failed to summarize the conversation history")?;
+
+        let _ = self
+            .usage
+            .record(
+                self.channel_id,
+                &self.model,
+                &response.usage,
+                response.completion_calls.len(),
+            )
+            .await
+            .inspect_err(|e| tracing::error!(?e, "Failed to record token usage"));
+
+        tracing::debug!(
+            messages = self.unsummarized.len(),
+            "Compacted trimmed conversation history into the synopsis"
+        );
+
+        self.synopsis = Some(response.output.trim().to_string());
+        self.unsummarized.clear();
+
+        Ok(())
+    }
+
+    /// The history sent to the model: the synopsis (if any) followed by the recent messages
+    fn prompt_history(&self) -> Vec<RigMessage> {
+        self.synopsis
+            .as_ref()
+            .map(|synopsis| {
+                RigMessage::user(format!(
+                    "[SYSTEM]: Synopsis of the earlier conversation in this channel, for \
+                    context only:\n{synopsis}"
+                ))
+            })
+            .into_iter()
+            .chain(self.conversation_history.iter().cloned())
+            .collect()
+    }
+
     /// Execute agent multi-turn conversation
     #[instrument(skip(self))]
     pub async fn execute_agent_multi_turn(&mut self) -> Result<(), eyre::Error> {
@@ -73,35 +268,125 @@ impl AgentSession {
             return Err(eyre::eyre!("Empty conversation history"));
         }
 
+        if let Err(e) = self.summarize_trimmed_history().await {
+            tracing::error!(
+                ?e,
+                "Failed to summarize trimmed history, continuing without it"
+            );
+        }
+
+        let mut transcript = match &self.transcripts {
+            Some(transcripts) => Some(
+                transcripts
+                    .start(&self.agent, &self.model, self.prompt_history())
+                    .await,
+            ),
+            None => None,
+        };
+
+        let result = self.run_turns(transcript.as_mut()).await;
+
+        if let (Some(transcripts), Some(transcript)) = (&self.transcripts, transcript) {
+            transcripts.record(transcript);
+        }
+
+        result
+    }
+
+    async fn run_turns(
+        &mut self,
+        mut transcript: Option<&mut AgentTranscript>,
+    ) -> Result<(), eyre::Error> {
+        const NEW_MESSAGES_PROMPT: &str = "[SYSTEM]: New messages are added, respond appropriately. Output [END] if no further action is needed.";
+        const CONTINUE_PROMPT: &str = "[SYSTEM]: Continue processing the conversation. Output [END] if no further action is needed.";
+
         for i in 0..MAX_AGENT_TURNS {
-            let response = self
-                .agent
-                .prompt(if i == 0 {
-                    "[SYSTEM]: New messages are added, respond appropriately. Output [END] if no further action is needed."
-                } else {
-                    "[SYSTEM]: Continue processing the conversation. Output [END] if no further action is needed."
-                })
-                .with_history(&self.conversation_history)
-                .max_turns(MAX_AGENT_TURNS)
-                .extended_details()
-                .await
-                .inspect_err(|_| {
-                    // remove all tool calls and tool results in case of this error:
-                    // "The following tool_call_ids did not have response messages: call_UZH253hv9o9RYVHjRxS"
-                    self.conversation_history.retain(|msg| match msg {
-                        RigMessage::System { .. } => true,
-                        RigMessage::User { content } => {
-                            !content.iter().any(|c| {
-                                matches!(c, rig::message::UserContent::ToolResult(_))
-                            })
-                        }
-                        RigMessage::Assistant { content, .. } => {
-                            !content.iter().any(|c| {
-                                matches!(c, rig::message::AssistantContent::ToolCall(_))
-                            })
+            let mut prompt = if i == 0 {
+                NEW_MESSAGES_PROMPT
+            } else {
+                CONTINUE_PROMPT
+            };
+
+            let mut attempt = 0;
+            let response = loop {
+                let history = self.prompt_history();
+                let checkpoint = Checkpoint::new(history.len());
+
+                let response = match &self.streaming {
+                    Some(streaming) => {
+                        self.run_streaming(prompt, history, streaming, &checkpoint)
+                            .await
+                    }
+                    None => self.run(prompt, history, &checkpoint).await,
+                };
+
+                let e = match response {
+                    Ok(response) => {
+                        if let Some(transcript) = transcript.as_deref_mut() {
+                            transcript.turns.push(TranscriptTurn {
+                                prompt: prompt.to_string(),
+                                messages: response.messages.clone().unwrap_or_default(),
+                                output: response.output.clone(),
+                                usage: Some(response.usage),
+                                requests: response.requests,
+                                error: None,
+                            });
                         }
+                        break response;
+                    }
+                    Err(e) => e,
+                };
+
+                // Only the failed completion request is rolled back, the tool
+                // calls completed before it are kept so that the agent knows
+                // what it already did, e.g. the replies it posted
+                let completed = checkpoint.completed();
+
+                if let Some(transcript) = transcript.as_deref_mut() {
+                    transcript.turns.push(TranscriptTurn {
+                        prompt: prompt.to_string(),
+                        messages: completed.clone(),
+                        output: String::new(),
+                        usage: None,
+                        requests: 0,
+                        error: Some(format!("{e:#}")),
                     });
-                })?;
+                }
+
+                if !completed.is_empty() {
+                    if let Some(archive) = &self.archive {
+                        archive.run(&completed);
+                    }
+                    self.conversation_history.extend(completed);
+                    prompt = CONTINUE_PROMPT;
+                }
+
+                if attempt < MAX_RUN_RETRIES && is_transient(&e) {
+                    let delay = RUN_RETRY_BASE_DELAY * 2u32.pow(attempt);
+                    attempt += 1;
+                    tracing::warn!(?e, attempt, ?delay, "Transient agent error, retrying");
+                    tokio::time::sleep(delay).await;
+                    continue;
+                }
+
+                // The provider rejects the history if a tool call lost its
+                // result, e.g. "The following tool_call_ids did not have
+                // response messages"
+                drop_orphaned_tool_calls(&mut self.conversation_history);
+
+                return Err(e);
+            };
+
+            let _ = self
+                .usage
+                .record(
+                    self.channel_id,
+                    &self.model,
+                    &response.usage,
+                    response.requests,
+                )
+                .await
+                .inspect_err(|e| tracing::error!(?e, "Failed to record token usage"));
 
             // As of rig 0.39, `with_history` no longer folds the run's messages
             // back into the passed history; the prompt, assistant replies, and
@@ -109,6 +394,9 @@ impl AgentSession {
             // them ourselves so the next round — and the next Discord message —
             // can see what the agent did, including the replies it already posted.
             if let Some(messages) = response.messages {
+                if let Some(archive) = &self.archive {
+                    archive.run(&messages);
+                }
                 self.conversation_history.extend(messages);
             }
 
@@ -119,82 +407,426 @@ impl AgentSession {
 
         Ok(())
     }
+
+    async fn run(
+        &self,
+        prompt: &str,
+        history: Vec<RigMessage>,
+        checkpoint: &Checkpoint,
+    ) -> Result<RunOutput, eyre::Error> {
+        let response = self
+            .agent
+            .prompt(prompt)
+            .with_history(&history)
+            .with_hook(checkpoint.clone())
+            .max_turns(MAX_AGENT_TURNS)
+            .extended_details()
+            .await?;
+
+        Ok(RunOutput {
+            output: response.output,
+            usage: response.usage,
+            requests: response.completion_calls.len(),
+            messages: response.messages,
+        })
+    }
+
+    /// Same as [Self::run] but streams the `send_discord_message` calls into placeholder
+    /// messages as the tokens arrive
+    async fn run_streaming(
+        &self,
+        prompt: &str,
+        history: Vec<RigMessage>,
+        streaming: &StreamingReplies,
+        checkpoint: &Checkpoint,
+    ) -> Result<RunOutput, eyre::Error> {
+        let mut writer = streaming.writer();
+
+        let mut stream = self
+            .agent
+            .stream_prompt(prompt)
+            .with_history(history)
+            .with_hook(checkpoint.clone())
+            .multi_turn(MAX_AGENT_TURNS)
+            .await;
+
+        let mut result = Err(eyre::eyre!("Agent stream ended without a final response"));
+
+        while let Some(item) = stream.next().await {
+            match item {
+                Ok(MultiTurnStreamItem::StreamAssistantItem(
+                    StreamedAssistantContent::ToolCallDelta {
+                        internal_call_id,
+                        content,
+                        ..
+                    },
+                )) => {
+                    writer.on_tool_call_delta(&internal_call_id, &content).await;
+                }
+                Ok(MultiTurnStreamItem::FinalResponse(response)) => {
+                    result = Ok(RunOutput {
+                        output: response.response().to_string(),
+                        usage: response.usage(),
+                        requests: response.requests(),
+                        messages: response.history().map(<[RigMessage]>::to_vec),
+                    });
+                }
+                Ok(_) => {}
+                Err(e) => {
+                    result = Err(eyre::eyre!(e).wrap_err("Agent stream failed"));
+                    break;
+                }
+            }
+        }
+
+        streaming.discard_placeholders().await;
+
+        result
+    }
+}
+
+/// Records the history sent with every completion request of a run. Since a
+/// request is only sent once the previous tool calls have their results, the
+/// last one holds the consistent part of the run that completed before a
+/// failure.
+#[derive(Clone)]
+struct Checkpoint {
+    /// Length of the history the run started from
+    base: usize,
+    history: Arc<Mutex<Vec<RigMessage>>>,
+}
+
+impl Checkpoint {
+    fn new(base: usize) -> Self {
+        Self {
+            base,
+            history: Arc::new(Mutex::new(Vec::new())),
+        }
+    }
+
+    /// The messages of the run up to the last completion request, empty if
+    /// the first request failed
+    fn completed(&self) -> Vec<RigMessage> {
+        let history = self.history.lock().map(|h| h.clone()).unwrap_or_default();
+
+        match history.get(self.base..) {
+            // More than the prompt itself
+            Some(messages) if messages.len() > 1 => messages.to_vec(),
+            _ => Vec::new(),
+        }
+    }
 }
 
-/// Create a new agent session for a channel
+impl PromptHook<CompletionModel> for Checkpoint {
+    async fn on_completion_call(&self, prompt: &RigMessage, history: &[RigMessage]) -> HookAction {
+        if let Ok(mut checkpoint) = self.history.lock() {
+            *checkpoint = history
+                .iter()
+                .cloned()
+                .chain(std::iter::once(prompt.clone()))
+                .collect();
+        }
+        HookAction::cont()
+    }
+}
+
+/// Whether a failed run is worth retrying: rate limits, provider outages and
+/// network errors
+fn is_transient(error: &eyre::Error) -> bool {
+    const TRANSIENT_MESSAGES: &[&str] = &[
+        "rate limit",
+        "too many requests",
+        "overloaded",
+        "timed out",
+        "timeout",
+        "temporarily unavailable",
+        "connection",
+        "stream ended",
+    ];
+
+    error.chain().any(|e| {
+        if let Some(http_error) = e.downcast_ref::<rig::http_client::Error>() {
+            return match http_error {
+                rig::http_client::Error::InvalidStatusCode(status)
+                | rig::http_client::Error::InvalidStatusCodeWithMessage(status, _) => {
+                    *status == StatusCode::TOO_MANY_REQUESTS
+                        || *status == StatusCode::REQUEST_TIMEOUT
+                        || status.is_server_error()
+                }
+                _ => true,
+            };
+        }
+
+        let message = e.to_string().to_lowercase();
+        TRANSIENT_MESSAGES.iter().any(|m| message.contains(m))
+    })
+}
+
+/// Remove the tool calls without a result and the results without a call
+fn drop_orphaned_tool_calls(history: &mut Vec<RigMessage>) {
+    let calls = history
+        .iter()
+        .filter_map(|m| match m {
+            RigMessage::Assistant { content, .. } => Some(content.iter()),
+            _ => None,
+        })
+        .flatten()
+        .filter_map(|c| match c {
+            AssistantContent::ToolCall(call) => Some(call.id.clone()),
+            _ => None,
+        })
+        .collect::<HashSet<_>>();
+    let results = history
+        .iter()
+        .filter_map(|m| match m {
+            RigMessage::User { content } => Some(content.iter()),
+            _ => None,
+        })
+        .flatten()
+        .filter_map(|c| match c {
+            UserContent::ToolResult(result) => Some(result.id.clone()),
+            _ => None,
+        })
+        .collect::<HashSet<_>>();
+
+    let before = history.len();
+    *history = std::mem::take(history)
+        .into_iter()
+        .filter_map(|message| match message {
+            RigMessage::Assistant { id, content } => OneOrMany::many(content.into_iter().filter(
+                |c| !matches!(c, AssistantContent::ToolCall(call) if !results.contains(&call.id)),
+            ))
+            .ok()
+            .map(|content| RigMessage::Assistant { id, content }),
+            RigMessage::User { content } => OneOrMany::many(content.into_iter().filter(
+                |c| !matches!(c, UserContent::ToolResult(result) if !calls.contains(&result.id)),
+            ))
+            .ok()
+            .map(|content| RigMessage::User { content }),
+            message => Some(message),
+        })
+        .collect();
+
+    if history.len() != before {
+        tracing::debug!(
+            removed = before - history.len(),
+            "Removed orphaned tool calls from the history"
+        );
+    }
+}
+
+/// Services shared by the agent sessions of all channels
+#[derive(Clone)]
+pub struct AgentServices {
+    pub openai_api_key: String,
+    pub vectordb: Option<SharedVectorClient>,
+    pub usage: UsageTracker,
+    pub limiter: ExecutionLimiter,
+    pub archive: MessageArchive,
+    pub moderation: Moderation,
+    pub transcripts: TranscriptRecorder,
+    pub reminders: Reminders,
+    pub web_search: WebSearch,
+    pub fetch_page: FetchPageContentTool,
+    pub calculate: CalculateTool,
+    pub code_runner: Option<CodeRunner>,
+    pub github_search: Option<GitHubSearchTool>,
+    pub godbolt: Arc<dyn GodboltClient>,
+    pub replies: RecentReplies,
+    pub feed: FeedReader,
+    pub blog: BlogReader,
+    pub http: HttpClient,
+}
+
+/// Create a new agent session for a channel. Threads pass their parent channel,
+/// whose memories and token budget they share.
+#[allow(clippy::too_many_arguments)]
 pub fn create_agent_session(
     discord_ctx: &Context,
     channel_id: ChannelId,
-    openai_api_key: &str,
-    shared_vectordb_client: Option<SharedVectorClient>,
+    parent_id: Option<ChannelId>,
+    guild_id: Option<GuildId>,
+    dm_user: Option<UserId>,
+    services: &AgentServices,
     initial_history: Vec<RigMessage>,
+    settings: &ChannelSettings,
 ) -> Result<AgentSession, eyre::Error> {
     // Create OpenRouter client (OpenAI-compatible) and build agent
-    let llm_client = Client::new(openai_api_key).context("Failed to create OpenRouter client")?;
+    let llm_client =
+        Client::new(&services.openai_api_key).context("Failed to create OpenRouter client")?;
 
     // Create tools with shared context
     let ctx_arc = Arc::new(discord_ctx.clone());
+    let home_channel_id = parent_id.unwrap_or(channel_id);
+    let streaming = settings
+        .streaming
+        .then(|| StreamingReplies::new(discord_ctx.http.clone(), channel_id));
     let discord_tool = DiscordSendMessageTool {
         ctx: ctx_arc.clone(),
         channel_id,
+        streaming: streaming.clone(),
+        replies: services.replies.clone(),
     };
-    let fetch_tool = FetchPageContentTool;
-    let web_search_tool = WebSearchTool;
-
-    // Godbolt tools
-    let gb_compile = crate::discord::tools::Godbolt;
-    let gb_langs = crate::discord::tools::GodboltLanguages;
-    let gb_compilers = crate::discord::tools::GodboltCompilers;
-    let gb_libs = crate::discord::tools::GodboltLibraries;
-    let gb_formats = crate::discord::tools::GodboltFormats;
-    let gb_format = crate::discord::tools::GodboltFormat;
-    let gb_asm = crate::discord::tools::GodboltAsmDoc;
-    let gb_ver = crate::discord::tools::GodboltVersion;
-
-    // Create memory tools if Qdrant is configured
-    let mut agent_builder = llm_client
-        .agent("x-ai/grok-4.5")
-        .preamble(SYSTEM_PROMPT)
-        .tool(discord_tool)
-        .tool(fetch_tool)
-        .tool(web_search_tool)
-        .tool(gb_compile)
-        .tool(gb_langs)
-        .tool(gb_compilers)
-        .tool(gb_libs)
-        .tool(gb_formats)
-        .tool(gb_format)
-        .tool(gb_asm)
-        .tool(gb_ver);
-
-    if let Some(shared_vectordb_client) = shared_vectordb_client {
-        let store_tool = crate::discord::tools::MemoryStoreTool::new_with_client(
-            shared_vectordb_client.clone(),
-            channel_id.get(),
-        );
-        let find_tool = crate::discord::tools::MemoryFindTool::new_with_client(
-            shared_vectordb_client.clone(),
-            channel_id.get(),
-            None,
-        );
-        let update_tool = crate::discord::tools::MemoryUpdateTool::new_with_client(
-            shared_vectordb_client.clone(),
-            channel_id.get(),
-        );
-        let delete_tool = crate::discord::tools::MemoryDeleteTool::new_with_client(
-            shared_vectordb_client,
-            channel_id.get(),
-        );
+    let mut tools: Vec<Box<dyn ToolDyn>> = vec![
+        Box::new(services.fetch_page.clone()),
+        Box::new(WebSearchTool {
+            search: services.web_search.clone(),
+        }),
+        // Godbolt tools
+        Box::new(crate::discord::tools::Godbolt {
+            client: services.godbolt.clone(),
+        }),
+        Box::new(crate::discord::tools::GodboltLanguages {
+            client: services.godbolt.clone(),
+        }),
+        Box::new(crate::discord::tools::GodboltCompilers {
+            client: services.godbolt.clone(),
+        }),
+        Box::new(crate::discord::tools::GodboltLibraries {
+            client: services.godbolt.clone(),
+        }),
+        Box::new(crate::discord::tools::GodboltFormats {
+            client: services.godbolt.clone(),
+        }),
+        Box::new(crate::discord::tools::GodboltFormat {
+            client: services.godbolt.clone(),
+        }),
+        Box::new(crate::discord::tools::GodboltAsmDoc {
+            client: services.godbolt.clone(),
+        }),
+        Box::new(crate::discord::tools::GodboltVersion {
+            client: services.godbolt.clone(),
+        }),
+        Box::new(crate::discord::tools::RustPlayground {
+            client: services.http.clone(),
+        }),
+        Box::new(services.calculate.clone()),
+        Box::new(FeedTopTool {
+            feed: services.feed.clone(),
+        }),
+        Box::new(BlogRecentCommentsTool {
+            blog: services.blog.clone(),
+        }),
+        Box::new(BlogPostInfoTool {
+            blog: services.blog.clone(),
+        }),
+        // Reminders are delivered in the conversation they were made in
+        Box::new(RemindMeTool {
+            reminders: services.reminders.clone(),
+            channel_id,
+        }),
+        Box::new(ListRemindersTool {
+            reminders: services.reminders.clone(),
+            channel_id,
+        }),
+        Box::new(CancelReminderTool {
+            reminders: services.reminders.clone(),
+            channel_id,
+        }),
+    ];
 
-        agent_builder = agent_builder
-            .tool(store_tool)
-            .tool(find_tool)
-            .tool(update_tool)
-            .tool(delete_tool);
+    if let Some(runner) = &services.code_runner {
+        tools.push(Box::new(CodeRunTool {
+            runner: runner.clone(),
+        }));
+    }
+
+    if let Some(github_search) = &services.github_search {
+        tools.push(Box::new(github_search.clone()));
+    }
+
+    // Moderation tools act on the whole guild, so they must be enabled by it
+    if let Some(guild_id) = guild_id.filter(|_| settings.moderation_enforcement) {
+        let target = ModerationTarget {
+            ctx: ctx_arc.clone(),
+            moderation: services.moderation.clone(),
+            guild_id,
+            channel_id,
+        };
+        tools.push(Box::new(ModerationWarnTool {
+            target: target.clone(),
+        }));
+        tools.push(Box::new(ModerationTimeoutTool {
+            target: target.clone(),
+        }));
+        tools.push(Box::new(ModerationDeleteTool { target }));
+    }
 
-        tracing::info!("Memory tools enabled for channel {}", channel_id,);
+    // Create memory tools if a vector database is configured
+    if let Some(shared_vectordb_client) = services.vectordb.clone() {
+        let scopes = MemoryScopes {
+            channel_id: home_channel_id.get(),
+            guild_id: guild_id.map(|g| g.get()),
+            dm_user_id: dm_user.map(|u| u.get()),
+        };
+        tools.push(Box::new(
+            crate::discord::tools::MemoryStoreTool::new_with_client(
+                shared_vectordb_client.clone(),
+                scopes,
+            ),
+        ));
+        tools.push(Box::new(
+            crate::discord::tools::MemoryFindTool::new_with_client(
+                shared_vectordb_client.clone(),
+                scopes,
+                None,
+            ),
+        ));
+        tools.push(Box::new(
+            crate::discord::tools::MemoryUpdateTool::new_with_client(
+                shared_vectordb_client.clone(),
+                scopes,
+            ),
+        ));
+        tools.push(Box::new(
+            crate::discord::tools::MemoryDeleteTool::new_with_client(
+                shared_vectordb_client,
+                scopes,
+            ),
+        ));
+
+        tracing::info!("Memory tools enabled for channel {}", home_channel_id,);
     };
 
+    let total_tools = tools.len();
+    tools.retain(|tool| settings.allows_tool(&tool.name()));
+
+    let available = std::iter::once(DiscordSendMessageTool::NAME.to_string())
+        .chain(tools.iter().map(|t| t.name()))
+        .collect::<Vec<_>>();
+    let mut preamble = system_prompt(&settings.profile, &available);
+
+    if let Some(persona) = &settings.persona {
+        preamble.push_str(&format!(
+            "\n\n[PERSONA]\nThe server admins configured the persona below for this channel. \
+            It takes precedence over [TONE & STYLE].\n{persona}"
+        ));
+    }
+
+    if tools.len() < total_tools {
+        preamble.push_str(&format!(
+            "\n\n[TOOL POLICY]\nThe server admins restricted the tools in this channel, only \
+            these are available: [{}]. If a request needs another tool, tell the user it's \
+            disabled in this channel.",
+            available.join(", ")
+        ));
+    }
+
+    // Explicit cache breakpoint on the system prompt for providers that need one, others (e.g.
+    // OpenAI) cache the stable prefix automatically
+    let completion_model = llm_client
+        .completion_model(&settings.model)
+        .with_prompt_caching();
+
+    let summarizer = AgentBuilder::new(completion_model.clone())
+        .preamble(SUMMARY_PROMPT)
+        .build();
+
+    let agent_builder = AgentBuilder::new(completion_model)
+        .preamble(&preamble)
+        .tool(discord_tool)
+        .tools(tools);
+
     let agent = agent_builder
         // OpenAI params
         // .additional_params(json!({
@@ -210,5 +842,100 @@ pub fn create_agent_session(
         initial_history.len()
     );
 
-    Ok(AgentSession::new(agent, initial_history))
+    Ok(AgentSession::new(
+        agent,
+        summarizer,
+        initial_history,
+        streaming,
+        home_channel_id,
+        settings.model.clone(),
+        services.usage.clone(),
+        settings
+            .archive
+            .then(|| services.archive.channel(channel_id, parent_id, guild_id)),
+        services.transcripts.channel(home_channel_id, guild_id),
+    ))
+}
+
+/// Plain text rendering of a message for the summarizer, tool results are truncated since they
+/// can be whole web pages
+fn render_message_for_summary(message: &RigMessage) -> String {
+    const MAX_TOOL_RESULT_LEN: usize = 500;
+
+    let truncate = |text: &str| match text.char_indices().nth(MAX_TOOL_RESULT_LEN) {
+        Some((n, _)) => format!("{}...", &text[..n]),
+        None => text.to_string(),
+    };
+
+    match message {
+        RigMessage::System { content } => format!("[system] {content}"),
+        RigMessage::User { content } => content
+            .iter()
+            .map(|c| match c {
+                UserContent::Text(t) => t.text.clone(),
+                UserContent::ToolResult(r) => format!(
+                    "[tool result] {}",
+                    r.content
+                        .iter()
+                        .map(|c| match c {
+                            ToolResultContent::Text(t) => truncate(&t.text),
+                            _ => "[image]".to_string(),
+                        })
+                        .collect::<Vec<_>>()
+                        .join(" ")
+                ),
+                UserContent::Image(_) => "[image]".to_string(),
+                _ => "[attachment]".to_string(),
+            })
+            .collect::<Vec<_>>()
+            .join("\n"),
+        RigMessage::Assistant { content, .. } => content
+            .iter()
+            .filter_map(|c| match c {
+                AssistantContent::Text(t) => Some(format!("[you] {}", t.text)),
+                AssistantContent::ToolCall(call) => Some(format!(
+                    "[you called {}] {}",
+                    call.function.name, call.function.arguments
+                )),
+                _ => None,
+            })
+            .collect::<Vec<_>>()
+            .join("\n"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn drop_orphaned_tool_calls_keeps_paired_calls() {
+        let mut history = vec![
+            RigMessage::user("hi"),
+            RigMessage::Assistant {
+                id: None,
+                content: OneOrMany::one(AssistantContent::tool_call(
+                    "paired",
+                    "calculate",
+                    serde_json::json!({}),
+                )),
+            },
+            RigMessage::tool_result("paired", "42"),
+            RigMessage::Assistant {
+                id: None,
+                content: OneOrMany::one(AssistantContent::tool_call(
+                    "orphan",
+                    "web_search",
+                    serde_json::json!({}),
+                )),
+            },
+            RigMessage::tool_result("lost", "result without a call"),
+        ];
+
+        drop_orphaned_tool_calls(&mut history);
+
+        assert_eq!(history.len(), 3);
+        assert!(matches!(&history[2], RigMessage::User { content }
+            if matches!(content.first(), UserContent::ToolResult(r) if r.id == "paired")));
+    }
 }
@@ -0,0 +1,438 @@
+use std::{collections::HashMap, time::Duration};
+
+use chrono::{NaiveDate, NaiveDateTime};
+use diesel::{
+    prelude::*,
+    sql_types::{BigInt, Date, Nullable, Text, Timestamp},
+};
+use diesel_async::{AsyncPgConnection, RunQueryDsl, pooled_connection::deadpool::Pool};
+use eyre::Context as _;
+use rig::{completion::Message as RigMessage, message::AssistantContent, tool::Tool as _};
+use serde::Serialize;
+use serenity::all::{ChannelId, GuildId, Message};
+
+use crate::{
+    config::ServerConfig,
+    discord::{settings::DiscordSettings, settings::to_db_id, tools::DiscordSendMessageTool},
+    jobs,
+    models::discord::NewDiscordArchivedMessage,
+    schema::discord_archived_messages,
+    utils::extract_recommender_terms,
+};
+
+/// How often the archived messages past their retention are deleted
+const RETENTION_INTERVAL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// A user message counts as answered if the bot responded within this window
+const RESPONSE_WINDOW_MINUTES: i32 = 10;
+
+/// Most recent messages the topics are extracted from
+const MAX_TOPIC_MESSAGES: i64 = 5000;
+
+pub const KIND_MESSAGE: &str = "message";
+pub const KIND_RESPONSE: &str = "response";
+pub const KIND_TOOL_CALL: &str = "tool_call";
+
+/// Archive of the conversations in the channels that opted in, for analytics
+#[derive(Clone)]
+pub struct MessageArchive {
+    diesel: Pool<AsyncPgConnection>,
+}
+
+#[derive(QueryableByName, Serialize, Debug)]
+pub struct DailyVolume {
+    #[diesel(sql_type = Date)]
+    pub day: NaiveDate,
+    #[diesel(sql_type = Text)]
+    pub kind: String,
+    #[diesel(sql_type = BigInt)]
+    pub count: i64,
+}
+
+#[derive(QueryableByName, Debug)]
+struct ResponseRow {
+    #[diesel(sql_type = BigInt)]
+    channel_id: i64,
+    #[diesel(sql_type = BigInt)]
+    messages: i64,
+    #[diesel(sql_type = BigInt)]
+    answered: i64,
+}
+
+#[derive(Serialize, Debug)]
+pub struct ResponseRate {
+    pub channel_id: i64,
+    pub messages: i64,
+    /// Messages the bot responded to within [RESPONSE_WINDOW_MINUTES]
+    pub answered: i64,
+    pub rate: f64,
+}
+
+#[derive(Serialize, Debug)]
+pub struct Topic {
+    pub term: String,
+    /// Number of messages mentioning the term
+    pub messages: usize,
+}
+
+impl MessageArchive {
+    pub fn new(diesel: Pool<AsyncPgConnection>) -> Self {
+        Self { diesel }
+    }
+
+    async fn conn(
+        &self,
+    ) -> Result<diesel_async::pooled_connection::deadpool::Object<AsyncPgConnection>, eyre::Error>
+    {
+        self.diesel
+            .get()
+            .await
+            .wrap_err("could not get diesel pool conn")
+    }
+
+    /// The archive of a channel, threads are archived under their parent
+    pub fn channel(
+        &self,
+        channel_id: ChannelId,
+        parent_id: Option<ChannelId>,
+        guild_id: Option<GuildId>,
+    ) -> ChannelArchive {
+        ChannelArchive {
+            archive: self.clone(),
+            channel_id: to_db_id(parent_id.unwrap_or(channel_id).get()),
+            thread_id: parent_id.map(|_| to_db_id(channel_id.get())),
+            guild_id: guild_id.map(|g| to_db_id(g.get())),
+        }
+    }
+
+    /// Insert the messages in the background so that the conversation isn't
+    /// held up by the database
+    fn insert(&self, messages: Vec<NewDiscordArchivedMessage>) {
+        if messages.is_empty() {
+            return;
+        }
+
+        let archive = self.clone();
+        tokio::spawn(async move {
+            let result = async {
+                let mut conn = archive.conn().await?;
+                diesel::insert_into(discord_archived_messages::table)
+                    .values(&messages)
+                    .execute(&mut conn)
+                    .await
+                    .wrap_err("failed to archive Discord messages")
+            }
+            .await;
+
+            if let Err(e) = result {
+                tracing::error!(?e, "Failed to archive messages");
+            }
+        });
+    }
+
+    /// Delete the archived messages past the retention of their channel once a
+    /// day, from one instance
+    pub fn start_retention_worker(&self, settings: DiscordSettings, config: &ServerConfig) {
+        let archive = self.clone();
+        jobs::spawn_exclusive(config, "discord-archive-retention", move || {
+            let (archive, settings) = (archive.clone(), settings.clone());
+            async move {
+                let mut interval = tokio::time::interval(RETENTION_INTERVAL);
+                interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+                loop {
+                    interval.tick().await;
+
+                    match archive.apply_retention(&settings).await {
+                        Ok(0) => {}
+                        Ok(deleted) => {
+                            tracing::info!(deleted, "Deleted expired archived messages")
+                        }
+                        Err(e) => {
+                            tracing::error!(?e, "Failed to delete expired archived messages")
+                        }
+                    }
+                }
+            }
+        });
+    }
+
+    async fn apply_retention(&self, settings: &DiscordSettings) -> Result<usize, eyre::Error> {
+        let mut conn = self.conn().await?;
+
+        let channels: Vec<i64> = discord_archived_messages::table
+            .select(discord_archived_messages::channel_id)
+            .distinct()
+            .load(&mut conn)
+            .await
+            .wrap_err("failed to load archived channels")?;
+
+        let mut deleted = 0;
+        for channel_id in channels {
+            let Some(id) = u64::try_from(channel_id).ok().filter(|id| *id != 0) else {
+                continue;
+            };
+            let Some(days) = settings
+                .channel(ChannelId::new(id), None)
+                .archive_retention_days
+            else {
+                continue;
+            };
+
+            let cutoff = (chrono::Utc::now() - chrono::Days::new(u64::from(days))).naive_utc();
+            deleted += diesel::delete(
+                discord_archived_messages::table
+                    .filter(discord_archived_messages::channel_id.eq(channel_id))
+                    .filter(discord_archived_messages::created_at.lt(cutoff)),
+            )
+            .execute(&mut conn)
+            .await
+            .wrap_err("failed to delete expired archived messages")?;
+        }
+
+        Ok(deleted)
+    }
+
+    /// Number of archived messages per day and kind
+    pub async fn volume(
+        &self,
+        since: NaiveDateTime,
+        channel_id: Option<i64>,
+    ) -> Result<Vec<DailyVolume>, eyre::Error> {
+        let mut conn = self.conn().await?;
+
+        diesel::sql_query(
+            r#"
+            SELECT created_at::DATE AS day, kind, COUNT(*) AS count
+            FROM discord_archived_messages
+            WHERE created_at >= $1 AND ($2::BIGINT IS NULL OR channel_id = $2)
+            GROUP BY day, kind
+            ORDER BY day, kind
+        "#,
+        )
+        .bind::<Timestamp, _>(since)
+        .bind::<Nullable<BigInt>, _>(channel_id)
+        .load(&mut conn)
+        .await
+        .wrap_err("failed to query archived message volume")
+    }
+
+    /// Share of the user messages per channel the bot responded to
+    pub async fn response_rates(
+        &self,
+        since: NaiveDateTime,
+        channel_id: Option<i64>,
+    ) -> Result<Vec<ResponseRate>, eyre::Error> {
+        let mut conn = self.conn().await?;
+
+        let rows: Vec<ResponseRow> = diesel::sql_query(
+            r#"
+            SELECT
+                m.channel_id,
+                COUNT(*) AS messages,
+                COUNT(*) FILTER (WHERE EXISTS (
+                    SELECT 1 FROM discord_archived_messages r
+                    WHERE r.kind = $3
+                        AND r.channel_id = m.channel_id
+                        AND r.thread_id IS NOT DISTINCT FROM m.thread_id
+                        AND r.created_at >= m.created_at
+                        AND r.created_at < m.created_at + make_interval(mins => $4)
+                )) AS answered
+            FROM discord_archived_messages m
+            WHERE m.kind = $5
+                AND m.created_at >= $1
+                AND ($2::BIGINT IS NULL OR m.channel_id = $2)
+            GROUP BY m.channel_id
+            ORDER BY m.channel_id
+        "#,
+        )
+        .bind::<Timestamp, _>(since)
+        .bind::<Nullable<BigInt>, _>(channel_id)
+        .bind::<Text, _>(KIND_RESPONSE)
+        .bind::<diesel::sql_types::Integer, _>(RESPONSE_WINDOW_MINUTES)
+        .bind::<Text, _>(KIND_MESSAGE)
+        .load(&mut conn)
+        .await
+        .wrap_err("failed to query archived response rates")?;
+
+        Ok(rows
+            .into_iter()
+            .map(|r| ResponseRate {
+                channel_id: r.channel_id,
+                messages: r.messages,
+                answered: r.answered,
+                rate: if r.messages > 0 {
+                    r.answered as f64 / r.messages as f64
+                } else {
+                    0.0
+                },
+            })
+            .collect())
+    }
+
+    /// The terms mentioned by the most user messages
+    pub async fn top_topics(
+        &self,
+        since: NaiveDateTime,
+        channel_id: Option<i64>,
+        limit: usize,
+    ) -> Result<Vec<Topic>, eyre::Error> {
+        let mut conn = self.conn().await?;
+
+        let mut query = discord_archived_messages::table
+            .filter(discord_archived_messages::kind.eq(KIND_MESSAGE))
+            .filter(discord_archived_messages::created_at.ge(since))
+            .select(discord_archived_messages::content)
+            .order(discord_archived_messages::created_at.desc())
+            .limit(MAX_TOPIC_MESSAGES)
+            .into_boxed();
+
+        if let Some(channel_id) = channel_id {
+            query = query.filter(discord_archived_messages::channel_id.eq(channel_id));
+        }
+
+        let contents: Vec<String> = query
+            .load(&mut conn)
+            .await
+            .wrap_err("failed to load archived messages")?;
+
+        Ok(top_topics(&contents, limit))
+    }
+}
+
+/// Count the messages mentioning each term, most mentioned first
+fn top_topics(contents: &[String], limit: usize) -> Vec<Topic> {
+    let mut counts = HashMap::<String, usize>::new();
+    for content in contents {
+        for term in extract_recommender_terms(content, None) {
+            *counts.entry(term).or_default() += 1;
+        }
+    }
+
+    let mut topics = counts
+        .into_iter()
+        .map(|(term, messages)| Topic { term, messages })
+        .collect::<Vec<_>>();
+    topics.sort_by(|a, b| {
+        b.messages
+            .cmp(&a.messages)
+            .then_with(|| a.term.cmp(&b.term))
+    });
+    topics.truncate(limit);
+    topics
+}
+
+/// The archive of a single channel or thread
+#[derive(Clone)]
+pub struct ChannelArchive {
+    archive: MessageArchive,
+    channel_id: i64,
+    thread_id: Option<i64>,
+    guild_id: Option<i64>,
+}
+
+impl ChannelArchive {
+    fn record(
+        &self,
+        kind: &str,
+        content: String,
+        message_id: Option<i64>,
+        user_id: Option<i64>,
+        tool_name: Option<String>,
+    ) -> NewDiscordArchivedMessage {
+        NewDiscordArchivedMessage {
+            channel_id: self.channel_id,
+            thread_id: self.thread_id,
+            guild_id: self.guild_id,
+            message_id,
+            user_id,
+            kind: kind.to_string(),
+            tool_name,
+            content,
+        }
+    }
+
+    /// Archive a message sent to the channel
+    pub fn message(&self, message: &Message) {
+        let content = std::iter::once(message.content.clone())
+            .chain(
+                message
+                    .attachments
+                    .iter()
+                    .map(|a| format!("[attachment: {}]", a.filename)),
+            )
+            .filter(|s| !s.trim().is_empty())
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        self.archive.insert(vec![self.record(
+            KIND_MESSAGE,
+            content,
+            Some(to_db_id(message.id.get())),
+            Some(to_db_id(message.author.id.get())),
+            None,
+        )]);
+    }
+
+    /// Archive the responses and tool calls of an agent run
+    pub fn run(&self, messages: &[RigMessage]) {
+        self.archive.insert(self.tool_calls(messages));
+    }
+
+    fn tool_calls(&self, messages: &[RigMessage]) -> Vec<NewDiscordArchivedMessage> {
+        messages
+            .iter()
+            .filter_map(|m| match m {
+                RigMessage::Assistant { content, .. } => Some(content.iter()),
+                _ => None,
+            })
+            .flatten()
+            .filter_map(|c| match c {
+                AssistantContent::ToolCall(call) => Some(&call.function),
+                _ => None,
+            })
+            .map(|function| {
+                // The responses are sent through the tool, archive their text
+                if function.name == DiscordSendMessageTool::NAME {
+                    let content = function
+                        .arguments
+                        .get("content")
+                        .and_then(|c| c.as_str())
+                        .unwrap_or_default()
+                        .to_string();
+                    self.record(KIND_RESPONSE, content, None, None, None)
+                } else {
+                    self.record(
+                        KIND_TOOL_CALL,
+                        function.arguments.to_string(),
+                        None,
+                        None,
+                        Some(function.name.clone()),
+                    )
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn top_topics_counts_messages_per_term() {
+        let contents = vec![
+            "How do I configure tokio runtime?".to_string(),
+            "The tokio runtime panics".to_string(),
+            "Borrow checker question".to_string(),
+        ];
+
+        let topics = top_topics(&contents, 2);
+
+        assert_eq!(topics.len(), 2);
+        assert_eq!(topics[0].term, "runtime");
+        assert_eq!(topics[0].messages, 2);
+        assert_eq!(topics[1].term, "tokio");
+        assert_eq!(topics[1].messages, 2);
+    }
+}
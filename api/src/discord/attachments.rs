@@ -0,0 +1,314 @@
+//! Reading the text, markdown and PDF files attached to messages. The files are
+//! read once when the message is queued: the text is split into chunks, the
+//! agent is shown a summary of it with the message and, if the channel opted
+//! in, the chunks are stored in its memory so that the agent can look up the
+//! rest of the file later.
+
+use std::ops::Range;
+
+use eyre::Context as _;
+use futures::StreamExt as _;
+use serde_json::json;
+use serenity::all::{Attachment, Message};
+use text_splitter::{MarkdownSplitter, TextSplitter};
+
+use super::{
+    context_budget::count_tokens,
+    tools::{MemoryScope, SharedVectorClient},
+};
+use crate::outbound::HttpClient;
+
+/// Larger files are mentioned by name only
+const MAX_DOCUMENT_BYTES: u32 = 4 * 1024 * 1024;
+const CHUNK_CHARS: Range<usize> = 1000..1500;
+/// Tokens of the leading chunks given to the summarizer
+const SUMMARIZED_TOKENS: usize = 20_000;
+/// Chunks of a file stored in the memory, each one is embedded
+const MAX_REMEMBERED_CHUNKS: usize = 50;
+
+const TEXT_EXTENSIONS: &[&str] = &[
+    "txt", "log", "csv", "json", "toml", "yaml", "yml", "xml", "html", "css", "sql", "sh", "rs",
+    "py", "js", "ts", "go", "c", "h", "cpp", "java", "kt", "rb",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Kind {
+    Text,
+    Markdown,
+    Pdf,
+}
+
+impl Kind {
+    fn of(attachment: &Attachment) -> Option<Self> {
+        let mime = attachment
+            .content_type
+            .as_deref()
+            .and_then(|ct| ct.split(';').next())
+            .map(str::trim)
+            .unwrap_or_default();
+        let extension = attachment
+            .filename
+            .rsplit_once('.')
+            .map(|(_, ext)| ext.to_ascii_lowercase())
+            .unwrap_or_default();
+
+        match (mime, extension.as_str()) {
+            ("application/pdf", _) | (_, "pdf") => Some(Self::Pdf),
+            ("text/markdown", _) | (_, "md" | "markdown") => Some(Self::Markdown),
+            (mime, _) if mime.starts_with("text/") || mime == "application/json" => {
+                Some(Self::Text)
+            }
+            (_, ext) if TEXT_EXTENSIONS.contains(&ext) => Some(Self::Text),
+            _ => None,
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            Self::Text => "text",
+            Self::Markdown => "markdown",
+            Self::Pdf => "PDF",
+        }
+    }
+}
+
+/// The text of an attached file split into chunks
+pub struct Document {
+    filename: String,
+    kind: Kind,
+    /// Unset for the files of past messages, which aren't read again
+    chunks: Option<Vec<String>>,
+    summary: Option<String>,
+    /// Leading chunks stored in the memory of the channel
+    stored: usize,
+}
+
+/// Reads the supported files attached to a message, the ones that can't be
+/// downloaded or read are logged and skipped
+pub async fn read_documents(http: &HttpClient, message: &Message) -> Vec<Document> {
+    let attachments = message
+        .attachments
+        .iter()
+        .filter_map(|a| Kind::of(a).map(|kind| (a, kind)))
+        .filter(|(a, _)| a.size <= MAX_DOCUMENT_BYTES);
+
+    futures::stream::iter(attachments)
+        .filter_map(async |(attachment, kind)| {
+            read_document(http, attachment, kind)
+                .await
+                .inspect_err(|error| {
+                    tracing::error!(
+                        ?error,
+                        filename = attachment.filename,
+                        "Failed to read the file attached to a Discord message"
+                    )
+                })
+                .ok()
+        })
+        .collect()
+        .await
+}
+
+/// The supported files attached to a past message, mentioned without reading
+/// them since the agent was shown them when the message was sent
+pub fn past_documents(message: &Message) -> Vec<Document> {
+    message
+        .attachments
+        .iter()
+        .filter_map(|a| Kind::of(a).map(|kind| (a, kind)))
+        .map(|(attachment, kind)| Document {
+            filename: attachment.filename.clone(),
+            kind,
+            chunks: None,
+            summary: None,
+            stored: 0,
+        })
+        .collect()
+}
+
+async fn read_document(
+    http: &HttpClient,
+    attachment: &Attachment,
+    kind: Kind,
+) -> Result<Document, eyre::Error> {
+    let mut response = http.get(&attachment.url).send().await?.error_for_status()?;
+
+    // The size of the attachment may not match what's served
+    let mut bytes = Vec::new();
+    while let Some(chunk) = response.chunk().await? {
+        if bytes.len() + chunk.len() > MAX_DOCUMENT_BYTES as usize {
+            eyre::bail!("the file is larger than {MAX_DOCUMENT_BYTES} bytes");
+        }
+        bytes.extend_from_slice(&chunk);
+    }
+
+    let text = match kind {
+        // PDFs are parsed on the blocking pool since large ones take a while
+        Kind::Pdf => {
+            tokio::task::spawn_blocking(move || pdf_extract::extract_text_from_mem(&bytes))
+                .await??
+        }
+        Kind::Text | Kind::Markdown => String::from_utf8_lossy(&bytes).into_owned(),
+    };
+
+    Ok(Document {
+        filename: attachment.filename.clone(),
+        kind,
+        chunks: Some(split(&text, kind)),
+        summary: None,
+        stored: 0,
+    })
+}
+
+fn split(text: &str, kind: Kind) -> Vec<String> {
+    let chunks: Vec<_> = match kind {
+        Kind::Markdown => MarkdownSplitter::new(CHUNK_CHARS)
+            .chunks(text)
+            .map(str::to_string)
+            .collect(),
+        Kind::Text | Kind::Pdf => TextSplitter::new(CHUNK_CHARS)
+            .chunks(text)
+            .map(str::to_string)
+            .collect(),
+    };
+    chunks
+        .into_iter()
+        .filter(|chunk| !chunk.trim().is_empty())
+        .collect()
+}
+
+impl Document {
+    /// What the summarizer is given of the file: its leading chunks that fit
+    /// in [`SUMMARIZED_TOKENS`], unset if there's no text to summarize
+    pub fn summary_prompt(&self) -> Option<String> {
+        let chunks = self.chunks.as_deref().filter(|c| !c.is_empty())?;
+        let mut tokens = 0;
+        let text: Vec<_> = chunks
+            .iter()
+            .take_while(|chunk| {
+                tokens += count_tokens(chunk);
+                tokens <= SUMMARIZED_TOKENS
+            })
+            .map(String::as_str)
+            .collect();
+
+        let mut prompt = format!(
+            "[File: {} ({})]\n{}",
+            self.filename,
+            self.kind.label(),
+            text.join("\n")
+        );
+        if text.len() < chunks.len() {
+            prompt.push_str("\n[The rest of the file is left out]");
+        }
+        Some(prompt)
+    }
+
+    pub fn set_summary(&mut self, summary: String) {
+        self.summary = Some(summary);
+    }
+
+    /// What the agent is shown of the file: its summary and whether it can be
+    /// looked up in the memory
+    pub fn render(&self) -> String {
+        let label = self.kind.label();
+        let Some(chunks) = &self.chunks else {
+            return format!(
+                "[File: {} ({label}), shown when it was sent, search the channel memory with memory_find in case it was stored]",
+                self.filename
+            );
+        };
+
+        let mut rendered = format!(
+            "[File: {} ({label}, {} chunks)]",
+            self.filename,
+            chunks.len()
+        );
+        match &self.summary {
+            Some(summary) => rendered.push_str(&format!("\nSummary: {summary}")),
+            None if chunks.is_empty() => rendered.push_str("\n[No text could be extracted]"),
+            None => rendered.push_str("\n[The file could not be summarized]"),
+        }
+        match self.stored {
+            0 => {}
+            n if n == chunks.len() => rendered.push_str(
+                "\n[The file is stored in the channel memory, search it with memory_find]",
+            ),
+            n => rendered.push_str(&format!(
+                "\n[Only the first {n} of the {} chunks are stored in the channel memory, search them with memory_find]",
+                chunks.len()
+            )),
+        }
+        rendered
+    }
+
+    /// Stores the leading chunks in the memory of the channel. The ones
+    /// stored before a failure are still shown to the agent as stored.
+    pub async fn remember(
+        &mut self,
+        client: &SharedVectorClient,
+        scope: MemoryScope,
+        message: &Message,
+    ) -> Result<(), eyre::Error> {
+        let Some(chunks) = &self.chunks else {
+            return Ok(());
+        };
+        let metadata = json!({
+            "source": "attachment",
+            "filename": self.filename,
+            "message_id": message.id.to_string(),
+            "author_id": message.author.id.to_string(),
+            "timestamp": chrono::Utc::now().to_rfc3339(),
+        });
+
+        for (i, chunk) in chunks.iter().take(MAX_REMEMBERED_CHUNKS).enumerate() {
+            let mut metadata = metadata.clone();
+            metadata["chunk"] = json!(i);
+            let information = format!("From the file {}, part {}:\n{chunk}", self.filename, i + 1);
+            client
+                .store(&information, scope, Some(metadata))
+                .await
+                .wrap_err_with(|| format!("failed to store part {} of {}", i + 1, self.filename))?;
+            self.stored = i + 1;
+        }
+        tracing::debug!(
+            filename = self.filename,
+            chunks = self.stored,
+            "Stored an attached file"
+        );
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn long_files_are_summarized_and_partly_stored() {
+        let text = "Some paragraph of the report.\n\n".repeat(5000);
+        let mut document = Document {
+            filename: "report.txt".to_string(),
+            kind: Kind::Text,
+            chunks: Some(split(&text, Kind::Text)),
+            summary: None,
+            stored: MAX_REMEMBERED_CHUNKS,
+        };
+        assert!(
+            document
+                .chunks
+                .as_ref()
+                .is_some_and(|c| c.len() > MAX_REMEMBERED_CHUNKS)
+        );
+
+        let prompt = document.summary_prompt().expect("the file has text");
+        assert!(prompt.ends_with("[The rest of the file is left out]"));
+        assert!(count_tokens(&prompt) < SUMMARIZED_TOKENS + 50);
+
+        document.set_summary("A report.".to_string());
+        let rendered = document.render();
+        assert!(rendered.starts_with("[File: report.txt (text, "));
+        assert!(rendered.contains("\nSummary: A report.\n"));
+        assert!(rendered.contains(&format!("Only the first {MAX_REMEMBERED_CHUNKS} of the")));
+    }
+}
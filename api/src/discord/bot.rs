@@ -1,19 +1,47 @@
+use crate::blog::lookup::BlogReader;
+use crate::clients::GodboltClient;
 use crate::discord::{
+    agent::AgentServices,
+    archive::MessageArchive,
     channel::{ChannelEvent, ChannelHandle},
-    constants::{MESSAGE_CONTEXT_SIZE, WHITELIST_CHANNELS},
+    commands::{SETTINGS_SLASH_COMMAND, handle_settings_command, handle_settings_interaction},
+    concurrency::ExecutionLimiter,
+    constants::MESSAGE_CONTEXT_SIZE,
+    direct_messages::{DirectMessageGate, DirectMessages},
+    feedback::{self, FeedbackStore},
+    memory_maintenance::MemoryMaintenance,
     message::QueuedMessage,
+    moderation::Moderation,
+    onboarding,
+    reminders::Reminders,
+    repetition::RecentReplies,
+    settings::DiscordSettings,
+    summaries::ChannelSummaries,
+    supervisor::ConnectionStatus,
+    transcripts::TranscriptRecorder,
+    usage::UsageTracker,
+    voice::{Transcript, VoiceTranscriber, handle_voice_command},
 };
+use crate::embedding::Embedder;
+use crate::outbound::HttpClient;
+use crate::recommendation::FeedReader;
 use arc_swap::ArcSwap;
 use async_trait::async_trait;
+use futures::{StreamExt as _, channel::mpsc::UnboundedSender};
 use scc::hash_map::OccupiedEntry;
 use serenity::all::{
-    Activity, ChannelId, GuildId, Message, Presence, Ready, TypingStartEvent, UserId,
+    Activity, ChannelId, GuildChannel, GuildId, Interaction, Message, MessageId,
+    MessageUpdateEvent, PartialGuildChannel, Presence, Reaction, Ready, ShardStageUpdateEvent,
+    TypingStartEvent, UserId,
 };
 use serenity::prelude::*;
 use std::sync::Arc;
 use tracing::instrument;
 
-use super::tools::SharedVectorClient;
+use super::tools::{
+    CalculateTool, CodeRunner, FetchPageContentTool, GitHubSearchTool, SharedVectorClient,
+    WebSearch,
+};
 
 pub(crate) struct Guild {
     pub presences: scc::HashMap<UserId, Vec<Activity>>,
@@ -21,42 +49,117 @@ pub(crate) struct Guild {
 
 pub struct DiscordEventHandler {
     channel_handles: Arc<scc::HashMap<ChannelId, ChannelHandle>>,
+    // Channels seen so far, mapped to their parent channel if they're threads
+    thread_parents: scc::HashMap<ChannelId, Option<ChannelId>>,
     guilds: Arc<scc::HashMap<GuildId, Guild>>,
 
-    shared_vectordb_client: Option<SharedVectorClient>,
-    openai_api_key: String,
-    whitelist_channels: Vec<ChannelId>,
+    services: AgentServices,
+    settings: DiscordSettings,
+    feedback: FeedbackStore,
+    dms: DirectMessages,
+    voice: Option<VoiceTranscriber>,
+    transcripts: UnboundedSender<Transcript>,
     bot_user_id: ArcSwap<Option<serenity::model::id::UserId>>,
-    discord_bot_mention_only: bool,
+    status: ConnectionStatus,
 }
 
 impl DiscordEventHandler {
-    pub async fn new(server_config: crate::config::ServerConfig) -> Self {
-        let shared_vectordb_client = match &server_config.vector_db {
-            Some(conf) => SharedVectorClient::new(conf.clone())
-                .await
-                .inspect_err(|e| {
-                    tracing::error!(
-                        "Failed to create shared vector client, defaulting to None: {}",
-                        e
-                    );
-                })
-                .ok(),
-            None => None,
-        };
+    #[allow(clippy::too_many_arguments)]
+    pub async fn new(
+        server_config: crate::config::ServerConfig,
+        settings: DiscordSettings,
+        usage: UsageTracker,
+        limiter: ExecutionLimiter,
+        archive: MessageArchive,
+        moderation: Moderation,
+        transcript_recorder: TranscriptRecorder,
+        feedback: FeedbackStore,
+        reminders: Reminders,
+        summaries: ChannelSummaries,
+        dms: DirectMessages,
+        shared_vectordb_client: Option<SharedVectorClient>,
+        godbolt: Arc<dyn GodboltClient>,
+        embedder: Embedder,
+        http: HttpClient,
+        feed: FeedReader,
+        blog: BlogReader,
+        status: ConnectionStatus,
+    ) -> Self {
+        archive.start_retention_worker(settings.clone(), &server_config);
+        summaries.start_worker(settings.clone(), &server_config);
+        reminders.start_delivery_worker(&server_config);
+
+        if let (Some(vectordb), Some(api_key)) =
+            (&shared_vectordb_client, &server_config.openai_api_key)
+        {
+            match MemoryMaintenance::new(vectordb.clone(), settings.clone(), api_key) {
+                Ok(maintenance) => maintenance.start_worker(&server_config),
+                Err(e) => tracing::error!(?e, "Failed to start memory maintenance"),
+            }
+        }
+
+        let channel_handles: Arc<scc::HashMap<ChannelId, ChannelHandle>> =
+            Arc::new(scc::HashMap::new());
+
+        // Voice transcripts are fed to the agent of the channel the bot was
+        // summoned from, which exists since the command was sent there
+        let (transcripts, mut transcripts_recv) = futures::channel::mpsc::unbounded::<Transcript>();
+        let handles = channel_handles.clone();
+        tokio::spawn(async move {
+            while let Some(transcript) = transcripts_recv.next().await {
+                let Some(mut handle) = handles.get_async(&transcript.channel_id).await else {
+                    continue;
+                };
+                let _ = handle
+                    .send_event(ChannelEvent::Transcript(transcript.message))
+                    .await
+                    .inspect_err(|e| tracing::error!(?e, "Failed to send Transcript event"));
+            }
+        });
 
         Self {
-            channel_handles: Arc::new(scc::HashMap::new()),
+            channel_handles,
+            thread_parents: scc::HashMap::new(),
             guilds: Arc::new(scc::HashMap::new()),
-            whitelist_channels: (server_config.discord_whitelist_channels.as_ref())
-                .unwrap_or(&WHITELIST_CHANNELS.to_vec())
-                .iter()
-                .map(|id| ChannelId::new(*id))
-                .collect(),
-            shared_vectordb_client,
+            settings,
+            feedback,
+            dms,
+            voice: server_config
+                .discord_voice_transcription
+                .clone()
+                .map(|config| VoiceTranscriber::new(config, http.clone())),
+            transcripts,
+            services: AgentServices {
+                openai_api_key: server_config.openai_api_key.clone().unwrap_or_default(),
+                vectordb: shared_vectordb_client,
+                usage,
+                limiter,
+                archive,
+                moderation,
+                transcripts: transcript_recorder,
+                reminders,
+                web_search: WebSearch::new(&server_config.web_search, http.clone()),
+                fetch_page: FetchPageContentTool::new(http.clone()),
+                calculate: CalculateTool::new(
+                    server_config.wolfram_alpha_app_id.clone(),
+                    http.clone(),
+                ),
+                code_runner: server_config
+                    .code_sandbox
+                    .clone()
+                    .map(|config| CodeRunner::new(config, http.clone())),
+                github_search: server_config
+                    .github_search
+                    .clone()
+                    .map(|config| GitHubSearchTool::new(config, http.clone())),
+                godbolt,
+                replies: RecentReplies::new(embedder),
+                feed,
+                blog,
+                http,
+            },
             bot_user_id: ArcSwap::from_pointee(None),
-            openai_api_key: server_config.openai_api_key.clone().unwrap_or_default(),
-            discord_bot_mention_only: server_config.discord_mention_only,
+            status,
         }
     }
 
@@ -66,12 +169,12 @@ impl DiscordEventHandler {
     pub async fn initialize_channels(&self, ctx: &Context) -> Result<(), eyre::Error> {
         tracing::info!("Initializing agent sessions for whitelisted channels on startup...");
 
-        for channel_id in &self.whitelist_channels {
-            let channel_id = *channel_id;
+        for channel_id in self.settings.enabled_channels() {
+            let mention_only = self.settings.channel(channel_id, None).mention_only;
 
             // In mention-only mode, check if bot was mentioned in recent messages
             // In auto mode, check if channel has recent activity (messages in the last hour)
-            let should_process = if self.discord_bot_mention_only {
+            let should_process = if mention_only {
                 self.has_recent_mention(ctx, channel_id).await
             } else {
                 self.has_recent_activity(ctx, channel_id).await
@@ -79,7 +182,7 @@ impl DiscordEventHandler {
 
             match should_process {
                 Ok(true) => {
-                    self.get_or_create_channel(channel_id, ctx.clone())
+                    self.get_or_create_channel(channel_id, None, None, None, ctx.clone())
                         .send_event(ChannelEvent::ForceProcess)
                         .await
                         .inspect_err(|e| {
@@ -95,21 +198,13 @@ impl DiscordEventHandler {
                     tracing::debug!(
                         "Skipping channel {} - no recent {}",
                         channel_id,
-                        if self.discord_bot_mention_only {
-                            "mentions"
-                        } else {
-                            "activity"
-                        }
+                        if mention_only { "mentions" } else { "activity" }
                     );
                 }
                 Err(e) => {
                     tracing::error!(
                         "Failed to check recent {} for channel {}: {}",
-                        if self.discord_bot_mention_only {
-                            "mentions"
-                        } else {
-                            "activity"
-                        },
+                        if mention_only { "mentions" } else { "activity" },
                         channel_id,
                         e
                     );
@@ -205,9 +300,155 @@ impl DiscordEventHandler {
         Ok(has_recent)
     }
 
+    /// The parent channel if the channel is a thread
+    async fn thread_parent(
+        &self,
+        ctx: &Context,
+        channel_id: ChannelId,
+        guild_id: Option<GuildId>,
+    ) -> Option<ChannelId> {
+        if let Some(parent_id) = self
+            .thread_parents
+            .read_async(&channel_id, |_, parent_id| *parent_id)
+            .await
+        {
+            return parent_id;
+        }
+
+        // DMs can't have threads
+        let guild_id = guild_id?;
+
+        // Regular channels and active threads are in the guild cache, fall back
+        // to the API for the rest, e.g. threads unarchived after startup
+        let cached = ctx.cache.guild(guild_id).and_then(|g| {
+            if g.channels.contains_key(&channel_id) {
+                Some(None)
+            } else {
+                g.threads
+                    .iter()
+                    .find(|t| t.id == channel_id)
+                    .map(|t| t.parent_id)
+            }
+        });
+
+        let parent_id = match cached {
+            Some(parent_id) => parent_id,
+            None => match channel_id.to_channel(&ctx.http).await {
+                Ok(channel) => channel
+                    .guild()
+                    .filter(|c| c.thread_metadata.is_some())
+                    .and_then(|c| c.parent_id),
+                Err(e) => {
+                    tracing::error!(?e, "Failed to fetch channel to resolve its parent");
+                    // Don't cache so that it's retried on the next message
+                    return None;
+                }
+            },
+        };
+
+        let _ = self
+            .thread_parents
+            .upsert_async(channel_id, parent_id)
+            .await;
+
+        parent_id
+    }
+
+    /// The vote of a 👍/👎 reaction on one of the bot's messages in an enabled
+    /// channel along with the channel the feedback is accounted to
+    async fn reaction_vote(
+        &self,
+        ctx: &Context,
+        reaction: &Reaction,
+    ) -> Option<(bool, UserId, ChannelId)> {
+        let positive = feedback::vote(&reaction.emoji)?;
+        let user_id = reaction.user_id?;
+
+        let bot_user_id = self.bot_user_id.load();
+        if bot_user_id.as_ref().is_none_or(|id| id == user_id)
+            || reaction.member.as_ref().is_some_and(|m| m.user.bot)
+        {
+            return None;
+        }
+
+        let channel_id = self
+            .thread_parent(ctx, reaction.channel_id, reaction.guild_id)
+            .await
+            .unwrap_or(reaction.channel_id);
+
+        self.settings
+            .channel(channel_id, reaction.guild_id)
+            .enabled
+            .then_some((positive, user_id, channel_id))
+    }
+
+    /// Drop the conversation of a thread that was archived or deleted, the main
+    /// loop exits once its event sender is dropped
+    async fn close_thread(&self, thread_id: ChannelId) {
+        if self
+            .channel_handles
+            .remove_async(&thread_id)
+            .await
+            .is_some()
+        {
+            tracing::info!(thread_id = thread_id.get(), "Closed thread conversation");
+        }
+        self.thread_parents.remove_async(&thread_id).await;
+    }
+
+    /// Forward a DM to the user's conversation if they consented and are within
+    /// the rate limit
+    async fn direct_message(&self, ctx: Context, msg: Message) {
+        if msg.author.bot || self.dms.handle_command(&ctx, &msg).await {
+            return;
+        }
+
+        let notice = match self.dms.gate(msg.author.id).await {
+            Ok(DirectMessageGate::Allow) => {
+                let _ = self
+                    .get_or_create_channel(
+                        msg.channel_id,
+                        None,
+                        None,
+                        Some(msg.author.id),
+                        ctx.clone(),
+                    )
+                    .send_event(ChannelEvent::Message(QueuedMessage { message: msg }, ctx))
+                    .await
+                    .inspect_err(|e| tracing::error!(?e, "Failed to send DM Message event"));
+                return;
+            }
+            Ok(DirectMessageGate::Onboard(notice)) => notice.map(str::to_string),
+            Ok(DirectMessageGate::Throttle(notice)) => notice,
+            Err(e) => {
+                tracing::error!(?e, "Failed to check DM consent");
+                None
+            }
+        };
+
+        if let Some(notice) = notice {
+            let _ = msg
+                .reply(&ctx.http, notice)
+                .await
+                .inspect_err(|e| tracing::error!(?e, "Failed to reply to DM"));
+        }
+    }
+
+    async fn messages_deleted(&self, channel_id: ChannelId, message_ids: Vec<MessageId>) {
+        if let Some(mut handle) = self.channel_handles.get_async(&channel_id).await {
+            let _ = handle
+                .send_event(ChannelEvent::Delete(message_ids))
+                .await
+                .inspect_err(|e| tracing::error!(?e, "Failed to send Delete event"));
+        }
+    }
+
     fn get_or_create_channel<'a>(
         &'a self,
         channel_id: ChannelId,
+        parent_id: Option<ChannelId>,
+        guild_id: Option<GuildId>,
+        dm_user: Option<UserId>,
         discord_ctx: Context,
     ) -> OccupiedEntry<'a, ChannelId, ChannelHandle> {
         self.channel_handles
@@ -216,9 +457,11 @@ impl DiscordEventHandler {
                 ChannelHandle::new(
                     discord_ctx,
                     channel_id,
-                    self.openai_api_key.clone(),
-                    self.shared_vectordb_client.clone(),
-                    self.discord_bot_mention_only,
+                    parent_id,
+                    self.services.clone(),
+                    self.settings.clone(),
+                    guild_id,
+                    dm_user,
                     self.guilds.clone(),
                 )
             })
@@ -228,12 +471,43 @@ impl DiscordEventHandler {
 #[async_trait]
 impl EventHandler for DiscordEventHandler {
     async fn message(&self, ctx: Context, msg: Message) {
-        if !self.whitelist_channels.contains(&msg.channel_id) {
+        if msg.guild_id.is_none() && self.dms.enabled() {
+            self.direct_message(ctx, msg).await;
+            return;
+        }
+
+        let parent_id = self.thread_parent(&ctx, msg.channel_id, msg.guild_id).await;
+        // Threads inherit the settings of their parent channel
+        let settings_channel_id = parent_id.unwrap_or(msg.channel_id);
+
+        if !msg.author.bot
+            && self
+                .settings
+                .channel(settings_channel_id, msg.guild_id)
+                .enabled
+            && handle_voice_command(&ctx, &msg, self.voice.as_ref(), &self.transcripts).await
+        {
+            // Make sure there's an agent to feed the transcripts to
+            let _ = self.get_or_create_channel(msg.channel_id, parent_id, msg.guild_id, None, ctx);
+            return;
+        }
+
+        if !msg.author.bot
+            && handle_settings_command(&ctx, &msg, settings_channel_id, &self.settings).await
+        {
+            return;
+        }
+
+        if !self
+            .settings
+            .channel(settings_channel_id, msg.guild_id)
+            .enabled
+        {
             return;
         }
 
         let _ = self
-            .get_or_create_channel(msg.channel_id, ctx.clone())
+            .get_or_create_channel(msg.channel_id, parent_id, msg.guild_id, None, ctx.clone())
             .send_event(ChannelEvent::Message(QueuedMessage { message: msg }, ctx))
             .await
             .inspect_err(|e| {
@@ -241,13 +515,95 @@ impl EventHandler for DiscordEventHandler {
             });
     }
 
+    async fn message_update(
+        &self,
+        ctx: Context,
+        _old_if_available: Option<Message>,
+        new: Option<Message>,
+        event: MessageUpdateEvent,
+    ) {
+        // Discord also sends updates when it adds the embeds of the links,
+        // only the edits of the content matter. The channels that aren't
+        // running have nothing to update.
+        if event.content.is_none() {
+            return;
+        }
+        let Some(mut handle) = self.channel_handles.get_async(&event.channel_id).await else {
+            return;
+        };
+
+        // The new message is only given if the old one was cached
+        let message = match new {
+            Some(message) => message,
+            None => match event.channel_id.message(&ctx.http, event.id).await {
+                Ok(message) => message,
+                Err(e) => {
+                    tracing::error!(?e, "Failed to fetch an edited message");
+                    return;
+                }
+            },
+        };
+
+        let _ = handle
+            .send_event(ChannelEvent::Edit(message, ctx))
+            .await
+            .inspect_err(|e| tracing::error!(?e, "Failed to send Edit event"));
+    }
+
+    async fn message_delete(
+        &self,
+        _ctx: Context,
+        channel_id: ChannelId,
+        deleted_message_id: MessageId,
+        _guild_id: Option<GuildId>,
+    ) {
+        self.messages_deleted(channel_id, vec![deleted_message_id])
+            .await;
+    }
+
+    async fn message_delete_bulk(
+        &self,
+        _ctx: Context,
+        channel_id: ChannelId,
+        multiple_deleted_messages_ids: Vec<MessageId>,
+        _guild_id: Option<GuildId>,
+    ) {
+        self.messages_deleted(channel_id, multiple_deleted_messages_ids)
+            .await;
+    }
+
     async fn typing_start(&self, ctx: Context, event: TypingStartEvent) {
-        if !self.whitelist_channels.contains(&event.channel_id) {
+        // DM conversations are only started by a message that passed the gate
+        if event.guild_id.is_none() && self.dms.enabled() {
+            if let Some(mut handle) = self.channel_handles.get_async(&event.channel_id).await {
+                let _ = handle
+                    .send_event(ChannelEvent::Typing(event.user_id, ctx))
+                    .await
+                    .inspect_err(|e| tracing::error!(?e, "Failed to send Typing event"));
+            }
+            return;
+        }
+
+        let parent_id = self
+            .thread_parent(&ctx, event.channel_id, event.guild_id)
+            .await;
+
+        if !self
+            .settings
+            .channel(parent_id.unwrap_or(event.channel_id), event.guild_id)
+            .enabled
+        {
             return;
         }
 
         let _ = self
-            .get_or_create_channel(event.channel_id, ctx.clone())
+            .get_or_create_channel(
+                event.channel_id,
+                parent_id,
+                event.guild_id,
+                None,
+                ctx.clone(),
+            )
             .send_event(ChannelEvent::Typing(event.user_id, ctx))
             .await
             .inspect_err(|e| {
@@ -259,6 +615,70 @@ impl EventHandler for DiscordEventHandler {
             });
     }
 
+    async fn reaction_add(&self, ctx: Context, reaction: Reaction) {
+        let Some((positive, user_id, channel_id)) = self.reaction_vote(&ctx, &reaction).await
+        else {
+            return;
+        };
+
+        let message = match reaction.message(&ctx.http).await {
+            Ok(message) => message,
+            Err(e) => {
+                tracing::error!(?e, "Failed to fetch reacted message");
+                return;
+            }
+        };
+
+        if self
+            .bot_user_id
+            .load()
+            .as_ref()
+            .is_none_or(|id| message.author.id != id)
+        {
+            return;
+        }
+
+        let _ = self
+            .feedback
+            .record(
+                &message,
+                channel_id,
+                user_id,
+                positive,
+                self.services.vectordb.as_ref(),
+            )
+            .await
+            .inspect_err(|e| tracing::error!(?e, "Failed to record reaction feedback"));
+    }
+
+    async fn reaction_remove(&self, ctx: Context, reaction: Reaction) {
+        let Some((positive, user_id, _)) = self.reaction_vote(&ctx, &reaction).await else {
+            return;
+        };
+
+        // Only votes on the bot's messages were recorded, so no need to check the author
+        let _ = self
+            .feedback
+            .remove(reaction.message_id, user_id, positive)
+            .await
+            .inspect_err(|e| tracing::error!(?e, "Failed to remove reaction feedback"));
+    }
+
+    async fn thread_update(&self, _ctx: Context, _old: Option<GuildChannel>, new: GuildChannel) {
+        if new.thread_metadata.is_some_and(|m| m.archived) {
+            self.close_thread(new.id).await;
+        }
+    }
+
+    async fn thread_delete(
+        &self,
+        _ctx: Context,
+        thread: PartialGuildChannel,
+        _full_thread_data: Option<GuildChannel>,
+    ) {
+        self.close_thread(thread.id).await;
+    }
+
     async fn presence_update(&self, _ctx: Context, new_presence: Presence) {
         // TODO: add whitelist guild config and check here
 
@@ -282,17 +702,77 @@ impl EventHandler for DiscordEventHandler {
             .upsert_sync(new_presence.user.id, new_presence.activities);
     }
 
+    async fn guild_create(&self, ctx: Context, guild: serenity::all::Guild, is_new: Option<bool>) {
+        if let Err(e) = onboarding::register_commands(&ctx, guild.id).await {
+            tracing::warn!(
+                ?e,
+                guild_id = guild.id.get(),
+                "Failed to register the slash commands"
+            );
+        }
+
+        // Only set for the guilds joined while connected, not the ones
+        // received when connecting
+        if is_new == Some(true) {
+            tracing::info!(
+                guild_id = guild.id.get(),
+                name = guild.name,
+                "Added to a server"
+            );
+            if let Err(e) = onboarding::onboard(&ctx, &guild, &self.settings).await {
+                tracing::error!(
+                    ?e,
+                    guild_id = guild.id.get(),
+                    "Failed to onboard the server"
+                );
+            }
+        }
+    }
+
+    async fn interaction_create(&self, ctx: Context, interaction: Interaction) {
+        let Interaction::Command(command) = interaction else {
+            return;
+        };
+        if command.data.name != SETTINGS_SLASH_COMMAND {
+            return;
+        }
+
+        // Threads inherit the settings of their parent channel
+        let parent_id = self
+            .thread_parent(&ctx, command.channel_id, command.guild_id)
+            .await;
+        handle_settings_interaction(
+            &ctx,
+            &command,
+            parent_id.unwrap_or(command.channel_id),
+            &self.settings,
+        )
+        .await;
+    }
+
+    async fn shard_stage_update(&self, _ctx: Context, event: ShardStageUpdateEvent) {
+        if event.new != event.old {
+            tracing::info!(
+                shard = event.shard_id.0,
+                old = %event.old,
+                new = %event.new,
+                "Discord shard changed stage"
+            );
+        }
+        self.status.shard_stage(event.shard_id.0, event.new);
+    }
+
     async fn ready(&self, ctx: Context, ready: Ready) {
         tracing::info!("Discord bot {} is connected!", ready.user.name);
+        self.status.connected();
 
         // Store bot user ID for mention detection
         self.bot_user_id.store(Arc::new(Some(ready.user.id)));
 
-        if self.discord_bot_mention_only {
-            tracing::info!("Bot is in mention-only mode - will only respond to mentions");
-        } else {
-            tracing::info!("Bot is in auto mode - will process all messages");
-        }
+        tracing::info!(
+            channels = self.settings.enabled_channels().len(),
+            "Bot is enabled in the configured channels"
+        );
 
         // Initialize agent sessions for active channels after startup
         if let Err(e) = self.initialize_channels(&ctx).await {
@@ -7,19 +7,31 @@ use futures::{
     SinkExt as _, StreamExt,
     channel::mpsc::{UnboundedReceiver, UnboundedSender},
 };
-use rig::message::Message as RigMessage;
-use serenity::all::{ChannelId, Context, Typing, UserId};
+use rig::{
+    agent::AgentBuilder, client::CompletionClient as _, completion::Prompt as _,
+    message::Message as RigMessage, providers::openrouter::Client, tool::Tool as _,
+};
+use serenity::all::{ChannelId, Context, GuildId, Message, MessageId, Typing, UserId};
 use tracing::{Instrument as _, instrument};
 
-use crate::discord::{
-    agent::{self, AgentSession},
-    bot::Guild,
-    constants::{
-        AGENT_SESSION_TIMEOUT, MESSAGE_CONTEXT_SIZE, MESSAGE_DEBOUNCE_TIMEOUT,
-        TYPING_DEBOUNCE_TIMEOUT,
+use crate::{
+    discord::{
+        agent::{self, AgentServices, AgentSession},
+        attachments::{Document, past_documents, read_documents},
+        bot::Guild,
+        constants::{
+            AGENT_SESSION_TIMEOUT, DOCUMENT_SUMMARY_PROMPT, HISTORY_TOKEN_BUDGET,
+            MESSAGE_CONTEXT_SIZE, MESSAGE_DEBOUNCE_TIMEOUT, TYPING_DEBOUNCE_TIMEOUT,
+        },
+        context_budget::{excess_over_budget, truncate_message},
+        cooldown::{Cooldown, UserCooldowns},
+        links::preview_links,
+        message::{Enrichment, QueuedMessage, discord_message_to_rig_message, message_id_of},
+        moderation::ACTION_FLAG,
+        settings::{ChannelSettings, DiscordSettings, to_db_id},
+        tools::{FetchPageContentTool, MemoryFindTool, MemoryScope},
     },
-    message::{QueuedMessage, discord_message_to_rig_message},
-    tools,
+    models::discord::NewDiscordModerationAction,
 };
 
 /// Dual-timestamp activity tracker for proper debouncing
@@ -73,12 +85,38 @@ impl ChannelActivity {
     }
 }
 
+/// Whether a queued message gets the agent to run
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Trigger {
+    /// Runs the agent even in mention-only mode
+    Mention,
+    /// Runs the agent unless it's in mention-only mode
+    Message,
+    /// Only read as context, e.g. the messages of a user on cooldown
+    Context,
+}
+
+/// A message waiting for the agent
+struct Pending {
+    /// The Discord message it was converted from, unset for the notes and
+    /// transcripts
+    message_id: Option<MessageId>,
+    message: RigMessage,
+    trigger: Trigger,
+}
+
 #[allow(clippy::large_enum_variant)]
 #[derive(Debug)]
 pub enum ChannelEvent {
     /// A new message has been received in the channel
     Message(QueuedMessage, Context),
 
+    /// A message of the channel was edited
+    Edit(Message, Context),
+
+    /// Messages of the channel were deleted
+    Delete(Vec<MessageId>),
+
     /// A typing event has been received in the channel
     Typing(UserId, Context),
 
@@ -86,12 +124,18 @@ pub enum ChannelEvent {
     /// are no new messages. Useful for service startup when we want to process any awaiting
     /// messages right away.
     ForceProcess,
+
+    /// An utterance transcribed from the voice channel the bot was summoned to from this channel
+    Transcript(RigMessage),
 }
 
 struct ChannelState {
     activity: ChannelActivity,
     event_recv: UnboundedReceiver<ChannelEvent>,
     agent: Option<AgentSession>,
+    // The settings the current agent session was created with, so that the
+    // session can be recreated when they change
+    agent_settings: Option<ChannelSettings>,
 
     // The latest discord context received from the event handler.
     // Note that each discord context is bound to a specific event and is destroyed after event
@@ -99,54 +143,450 @@ struct ChannelState {
     discord_ctx: Context,
     bot_user_id: serenity::model::id::UserId,
     channel_id: ChannelId,
+    // Set if the channel is a thread. Threads are conversations of their own but
+    // inherit the settings, memories and token budget of the parent channel.
+    parent_id: Option<ChannelId>,
+    guild_id: Option<GuildId>,
+    // The user of a DM conversation
+    dm_user: Option<UserId>,
     // All guilds the bot is in
     guilds: Arc<scc::HashMap<serenity::model::id::GuildId, Guild>>,
 
-    // Only process messages when a message mentions the bot, otherwise still queue incoming
-    // messages.
-    discord_bot_mention_only: bool,
+    // Per-channel settings such as mention-only mode, which only processes messages when a
+    // message mentions the bot but still queues incoming messages.
+    settings: DiscordSettings,
+
+    services: AgentServices,
+    // The day the daily token budget was exceeded on, to only announce the pause once a day
+    budget_exceeded_on: Option<chrono::NaiveDate>,
 
     // Queue the incoming messages and only add them to the agent when debounced. This is because
     // the AgentSession::add_messages handles context trimming which retains at most N new messages.
     // We want to avoid trimming unhandled messages if called repeatedly.
-    message_queue: Vec<(RigMessage, bool)>,
+    message_queue: Vec<Pending>,
+    cooldowns: UserCooldowns,
 }
 
 impl ChannelState {
     /// Build conversation history for agent context
     #[instrument(skip(self))]
     async fn build_conversation_history(&self) -> Vec<RigMessage> {
-        self.channel_id
+        let mut history: Vec<_> = self
+            .channel_id
             .messages_iter(&self.discord_ctx.http)
             .filter_map(|m| async {
                 m.ok()
                     .filter(|msg| !msg.content.trim().is_empty() || !msg.attachments.is_empty())
             })
             .take(MESSAGE_CONTEXT_SIZE)
-            .then(async |m| discord_message_to_rig_message(&m, self.bot_user_id, &None).await)
+            .then(async |m| {
+                // The files and links of past messages aren't read again, the agent already saw
+                // them
+                let enrichment = Enrichment {
+                    documents: past_documents(&m),
+                    links: Vec::new(),
+                };
+                discord_message_to_rig_message(
+                    &self.services.http,
+                    &m,
+                    self.bot_user_id,
+                    &None,
+                    &enrichment,
+                )
+                .await
+            })
             .collect::<Vec<_>>()
             .await
             .into_iter()
             .rev()
-            .collect()
+            .collect();
+
+        history.iter_mut().for_each(truncate_message);
+        let excess = excess_over_budget(&history, HISTORY_TOKEN_BUDGET);
+        history.drain(..excess);
+        history
+    }
+
+    /// Classify a guild message and record it in the audit trail if it's
+    /// flagged. Returns the note telling the agent about it.
+    async fn moderate(&self, message: &Message, settings: &ChannelSettings) -> Option<String> {
+        let guild_id = self.guild_id.or(message.guild_id)?;
+
+        let verdict = self
+            .services
+            .moderation
+            .check(&message.content, settings.moderation_threshold)
+            .await
+            .inspect_err(|e| tracing::error!(?e, "Failed to classify message"))
+            .ok()??;
+
+        let _ = self
+            .services
+            .moderation
+            .audit(NewDiscordModerationAction {
+                guild_id: to_db_id(guild_id.get()),
+                channel_id: to_db_id(self.channel_id.get()),
+                user_id: Some(to_db_id(message.author.id.get())),
+                message_id: Some(to_db_id(message.id.get())),
+                action: ACTION_FLAG.to_string(),
+                reason: verdict.summary(),
+                categories: Some(verdict.to_json()),
+            })
+            .await
+            .inspect_err(|e| tracing::error!(?e, "Failed to audit flagged message"));
+
+        Some(format!(
+            "[MODERATION]: Message {} from @{} was flagged by the classifier: {}.{}",
+            message.id,
+            message.author.id,
+            verdict.summary(),
+            if settings.moderation_enforcement {
+                " Use the moderation tools if it breaks the rules."
+            } else {
+                ""
+            }
+        ))
+    }
+
+    /// Queue a channel event, returns whether the agent should run right away
+    async fn handle_event(&mut self, event: ChannelEvent, settings: &ChannelSettings) -> bool {
+        match event {
+            ChannelEvent::Message(msg, ctx) => {
+                self.discord_ctx = ctx;
+                if msg.message.author.id == self.bot_user_id {
+                    // No need to process messages from the bot itself, it will
+                    // be represented as a tool call in the conversation
+                    // history, so duplicating it here would be redundant.
+                    return false;
+                }
+                // The gateway replays the events missed while resuming
+                if self
+                    .message_queue
+                    .iter()
+                    .any(|p| p.message_id == Some(msg.message.id))
+                {
+                    return false;
+                }
+                self.activity.update_message();
+
+                if settings.archive {
+                    self.services
+                        .archive
+                        .channel(self.channel_id, self.parent_id, self.guild_id)
+                        .message(&msg.message);
+                }
+
+                let moderation_note = self.moderate(&msg.message, settings).await;
+
+                // Flagged messages are handled right away if the agent can act
+                // on them, whoever sent them
+                let mentions_bot = msg.message.mentions_user_id(self.bot_user_id);
+                let trigger = if moderation_note.is_some() && settings.moderation_enforcement {
+                    Trigger::Mention
+                } else if (mentions_bot || !settings.mention_only)
+                    && !self.within_rate_limit(&msg.message, settings).await
+                {
+                    Trigger::Context
+                } else if mentions_bot {
+                    Trigger::Mention
+                } else {
+                    Trigger::Message
+                };
+
+                let enrichment = self.enrich(&msg.message, settings, true).await;
+                self.message_queue.push(Pending {
+                    message_id: Some(msg.message.id),
+                    message: self.convert(&msg.message, &enrichment).await,
+                    trigger,
+                });
+                if let Some(note) = moderation_note {
+                    self.message_queue.push(Pending {
+                        message_id: None,
+                        message: RigMessage::user(note),
+                        trigger,
+                    });
+                }
+                self.truncate_queue();
+
+                false
+            }
+            ChannelEvent::Edit(message, ctx) => {
+                self.discord_ctx = ctx;
+                // The bot edits its own messages while streaming them
+                if message.author.id != self.bot_user_id {
+                    self.handle_edit(message, settings).await;
+                }
+                false
+            }
+            ChannelEvent::Delete(message_ids) => {
+                // The agent never saw the queued ones, the ones it did are
+                // left as tombstones so that it doesn't answer or quote them
+                self.message_queue
+                    .retain(|p| p.message_id.is_none_or(|id| !message_ids.contains(&id)));
+                if let Some(agent) = &mut self.agent {
+                    for id in message_ids {
+                        agent.tombstone_message(id);
+                    }
+                }
+                false
+            }
+            ChannelEvent::Typing(uid, ctx) => {
+                self.discord_ctx = ctx;
+                if uid != self.bot_user_id {
+                    // Ignore typing events from the bot itself
+                    self.activity.update_typing();
+                }
+                false
+            }
+            ChannelEvent::ForceProcess => true,
+            ChannelEvent::Transcript(msg) => {
+                self.activity.update_message();
+                self.message_queue.push(Pending {
+                    message_id: None,
+                    message: msg,
+                    trigger: Trigger::Message,
+                });
+                self.truncate_queue();
+                false
+            }
+        }
+    }
+
+    /// Reads the attached files and previews the shared links of a message. The files of new
+    /// messages are read and summarized once here, the channels that opted in keep them in their
+    /// memory for the agent to look up past the summary.
+    async fn enrich(
+        &self,
+        message: &Message,
+        settings: &ChannelSettings,
+        remember: bool,
+    ) -> Enrichment {
+        let links = async {
+            if settings.allows_tool(FetchPageContentTool::NAME) {
+                preview_links(&self.services.fetch_page, &message.content).await
+            } else {
+                Vec::new()
+            }
+        };
+        let (mut documents, links) =
+            tokio::join!(read_documents(&self.services.http, message), links);
+        self.summarize(&mut documents, settings).await;
+
+        if remember
+            && settings.store_attachments
+            && settings.allows_tool(MemoryFindTool::NAME)
+            && let Some(vectordb) = &self.services.vectordb
+        {
+            let scope = MemoryScope::Channel(self.parent_id.unwrap_or(self.channel_id).get());
+            for document in &mut documents {
+                let _ = document
+                    .remember(vectordb, scope, message)
+                    .await
+                    .inspect_err(|error| {
+                        tracing::error!(?error, "Failed to store an attached file")
+                    });
+            }
+        }
+
+        Enrichment { documents, links }
+    }
+
+    /// Summarizes the files read from a message with the model of the channel, the ones that
+    /// can't be summarized are shown without a summary
+    async fn summarize(&self, documents: &mut [Document], settings: &ChannelSettings) {
+        let prompts: Vec<_> = documents
+            .iter_mut()
+            .filter_map(|document| Some((document.summary_prompt()?, document)))
+            .collect();
+        if prompts.is_empty() {
+            return;
+        }
+        let summarizer = match Client::new(&self.services.openai_api_key) {
+            Ok(client) => AgentBuilder::new(client.completion_model(&settings.model))
+                .preamble(DOCUMENT_SUMMARY_PROMPT)
+                .build(),
+            Err(error) => {
+                tracing::error!(?error, "Failed to create OpenRouter client");
+                return;
+            }
+        };
+
+        for (prompt, document) in prompts {
+            let response = match summarizer.prompt(prompt).extended_details().await {
+                Ok(response) => response,
+                Err(error) => {
+                    tracing::error!(?error, "Failed to summarize an attached file");
+                    continue;
+                }
+            };
+            let _ = self
+                .services
+                .usage
+                .record(
+                    self.parent_id.unwrap_or(self.channel_id),
+                    &settings.model,
+                    &response.usage,
+                    response.completion_calls.len(),
+                )
+                .await
+                .inspect_err(|e| tracing::error!(?e, "Failed to record token usage"));
+            document.set_summary(response.output.trim().to_string());
+        }
+    }
+
+    /// Converts a message for the agent, with the presence of its author
+    async fn convert(&self, message: &Message, enrichment: &Enrichment) -> RigMessage {
+        // Released right away since the entry locks the guild
+        let guild = self
+            .channel_id
+            .to_channel(self.discord_ctx.http.clone())
+            .await
+            .inspect_err(|e| {
+                tracing::error!(?e, "Failed to fetch channel for guild ID lookup");
+            })
+            .ok()
+            .and_then(|c| c.guild())
+            .and_then(|g| self.guilds.get_sync(&g.guild_id));
+        discord_message_to_rig_message(
+            &self.services.http,
+            message,
+            self.bot_user_id,
+            &guild,
+            enrichment,
+        )
+        .await
+    }
+
+    /// Updates the queued or seen copy of an edited message. The edit is moderated like a new
+    /// message, but only makes the agent run if it now mentions the bot while still queued.
+    async fn handle_edit(&mut self, message: Message, settings: &ChannelSettings) {
+        let queued = self
+            .message_queue
+            .iter()
+            .position(|p| p.message_id == Some(message.id));
+        let seen = self.agent.as_ref().is_some_and(|agent| {
+            agent
+                .conversation_history
+                .iter()
+                .any(|m| message_id_of(m) == Some(message.id))
+        });
+        // Older messages are read fresh from the channel by the next session
+        if queued.is_none() && !seen {
+            return;
+        }
+
+        let moderation_note = self.moderate(&message, settings).await;
+        let enrichment = self.enrich(&message, settings, false).await;
+        let converted = self.convert(&message, &enrichment).await;
+
+        let trigger = match queued {
+            Some(i) => {
+                let pending = &mut self.message_queue[i];
+                pending.message = converted;
+                if pending.trigger == Trigger::Message && message.mentions_user_id(self.bot_user_id)
+                {
+                    pending.trigger = Trigger::Mention;
+                }
+                pending.trigger
+            }
+            None => {
+                if let Some(agent) = &mut self.agent {
+                    agent.replace_message(message.id, converted);
+                }
+                Trigger::Context
+            }
+        };
+
+        if let Some(note) = moderation_note {
+            let trigger = if settings.moderation_enforcement {
+                Trigger::Mention
+            } else {
+                trigger
+            };
+            self.message_queue.push(Pending {
+                message_id: None,
+                message: RigMessage::user(note),
+                trigger,
+            });
+            self.truncate_queue();
+        }
+    }
+
+    /// Takes a message from the author's bucket, telling them once when they
+    /// run out. The owner of the server isn't limited.
+    async fn within_rate_limit(&mut self, message: &Message, settings: &ChannelSettings) -> bool {
+        let Some(limit) = settings.user_rate_limit else {
+            return true;
+        };
+        let is_owner = self
+            .guild_id
+            .and_then(|guild_id| self.discord_ctx.cache.guild(guild_id).map(|g| g.owner_id))
+            .is_some_and(|owner_id| owner_id == message.author.id);
+        if is_owner {
+            return true;
+        }
+
+        let wait = match self
+            .cooldowns
+            .check(message.author.id, limit, Instant::now())
+        {
+            Cooldown::Allow => return true,
+            Cooldown::Throttle(wait) => wait,
+        };
+        tracing::debug!(user_id = %message.author.id, limit, "User on cooldown");
+
+        if let Some(wait) = wait {
+            let minutes = wait.as_secs().div_ceil(60).max(1);
+            let _ = message
+                .reply(
+                    &self.discord_ctx.http,
+                    format!(
+                        "⏳ You're sending me more than I can keep up with, I'll get back to your messages in about {minutes} min"
+                    ),
+                )
+                .await
+                .inspect_err(|e| tracing::error!(?e, "Failed to announce the cooldown"));
+        }
+        false
+    }
+
+    /// Drop the oldest queued messages past the queue depth to avoid
+    /// accumulating too many, e.g. in case of no mentions or while waiting for
+    /// an execution slot
+    fn truncate_queue(&mut self) {
+        let dropped = self
+            .services
+            .limiter
+            .truncate_queue(&mut self.message_queue);
+        if dropped > 0 {
+            tracing::debug!(dropped, "Message queue full, dropped the oldest messages");
+        }
+    }
+
+    fn resolve_settings(&self) -> ChannelSettings {
+        match self.dm_user {
+            Some(_) => self.settings.direct_message(self.channel_id),
+            None => self
+                .settings
+                .channel(self.parent_id.unwrap_or(self.channel_id), self.guild_id),
+        }
     }
 
-    async fn main_loop(
-        mut self,
-        shared_vectordb_client: Option<tools::SharedVectorClient>,
-        openai_api_key: String,
-    ) {
+    async fn main_loop(mut self) {
         loop {
-            let timer = if !self.message_queue.is_empty()
-                && (!self.discord_bot_mention_only
-                    || self
-                        .message_queue
-                        .iter()
-                        // Check if any queued message mentions the bot and not just the last
-                        // one because the user can send immediate subsequent messages after mentioning
-                        // the bot
-                        .any(|m| m.1))
-            {
+            let settings = self.resolve_settings();
+
+            let timer = if self
+                .message_queue
+                .iter()
+                // Check if any queued message mentions the bot and not just the last
+                // one because the user can send immediate subsequent messages after mentioning
+                // the bot
+                .any(|p| match p.trigger {
+                    Trigger::Mention => true,
+                    Trigger::Message => !settings.mention_only,
+                    Trigger::Context => false,
+                }) {
                 tokio::time::sleep_until(
                     self.activity
                         .next_processing_time()
@@ -159,65 +599,11 @@ impl ChannelState {
 
             let (timer_expired, force_process) = tokio::select! {
                 event = self.event_recv.next() => {
-                    if let Some(event) = event {
-                        match event {
-                            ChannelEvent::Message(msg, ctx) => {
-                                self.discord_ctx = ctx;
-                                if msg.message.author.id == self.bot_user_id {
-                                    // No need to process messages from the bot itself, it will
-                                    // be represented as a tool call in the conversation
-                                    // history, so duplicating it here would be redundant.
-                                    continue;
-                                }
-                                self.activity.update_message();
-
-                                let guild = self
-                                    .channel_id
-                                    .to_channel(self.discord_ctx.http.clone())
-                                    .await
-                                    .inspect_err(|e| {
-                                        tracing::error!(?e, "Failed to fetch channel for guild ID lookup");
-                                    })
-                                    .ok()
-                                    .and_then(|c| c.guild())
-                                    .and_then(|g| self.guilds.get_sync(&g.guild_id));
-
-                                let mentions_bot = msg.message.mentions_user_id(self.bot_user_id);
-
-                                let msg = discord_message_to_rig_message(
-                                    &msg.message,
-                                    self.bot_user_id,
-                                    &guild,
-                                ).await;
-
-
-                                self.message_queue.push((msg, mentions_bot));
-                                // truncate to MESSAGE_CONTEXT_SIZE to avoid accumulating too many
-                                // messages in case of no mentions
-                                if self.message_queue.len() > MESSAGE_CONTEXT_SIZE {
-                                    self.message_queue.drain(0..self.message_queue.len() - MESSAGE_CONTEXT_SIZE);
-                                }
-
-                                (false, false)
-                            }
-                            ChannelEvent::Typing(uid, ctx) => {
-                                self.discord_ctx = ctx;
-                                if uid == self.bot_user_id {
-                                    // Ignore typing events from the bot itself
-                                    continue;
-                                }
-                                self.activity.update_typing();
-                                (false, false)
-                            }
-                            ChannelEvent::ForceProcess => {
-                                (false, true)
-                            }
-                        }
-                    }
-                    else {
+                    let Some(event) = event else {
                         tracing::info!("Channel event receiver closed, exiting main loop");
                         break;
-                    }
+                    };
+                    (false, self.handle_event(event, &settings).await)
                 }
                 _ = timer => (true, false),
             };
@@ -232,6 +618,53 @@ impl ChannelState {
                 }
             }
 
+            if self
+                .services
+                .usage
+                .is_over_budget(self.parent_id.unwrap_or(self.channel_id))
+                .await
+            {
+                let today = chrono::Utc::now().date_naive();
+                if self.budget_exceeded_on != Some(today) {
+                    self.budget_exceeded_on = Some(today);
+                    tracing::warn!("Daily token budget exceeded, pausing until tomorrow (UTC)");
+                    let _ = self
+                        .channel_id
+                        .say(
+                            &self.discord_ctx.http,
+                            "❗️ Daily token budget exceeded, I'll be back tomorrow (UTC)",
+                        )
+                        .await
+                        .inspect_err(|e| tracing::error!(?e, "Failed to announce budget pause"));
+                }
+                self.message_queue.clear();
+                continue;
+            }
+
+            // Keep queueing the events while waiting for a slot, the messages
+            // that arrive meanwhile are handled by the same run
+            let acquire = self.services.limiter.acquire();
+            tokio::pin!(acquire);
+            let permit = loop {
+                tokio::select! {
+                    permit = &mut acquire => break permit,
+                    event = self.event_recv.next() => {
+                        let Some(event) = event else {
+                            tracing::info!("Channel event receiver closed, exiting main loop");
+                            return;
+                        };
+                        self.handle_event(event, &settings).await;
+                    }
+                }
+            };
+            let _permit = match permit {
+                Ok(permit) => permit,
+                Err(e) => {
+                    tracing::error!(?e, "Failed to acquire an agent execution slot");
+                    continue;
+                }
+            };
+
             let span = tracing::span!(tracing::Level::INFO, "process_discord_message");
             let _ = span.enter();
 
@@ -245,16 +678,24 @@ impl ChannelState {
                 self.agent = None;
             }
 
+            if self.agent_settings.as_ref() != Some(&settings) {
+                self.agent = None;
+            }
+
             if self.agent.is_none() {
                 match agent::create_agent_session(
                     &self.discord_ctx,
                     self.channel_id,
-                    &openai_api_key,
-                    shared_vectordb_client.clone(),
+                    self.parent_id,
+                    self.guild_id,
+                    self.dm_user,
+                    &self.services,
                     self.build_conversation_history().await,
+                    &settings,
                 ) {
                     Ok(session) => {
                         self.agent = Some(session);
+                        self.agent_settings = Some(settings);
                     }
                     Err(e) => {
                         tracing::error!(?e, "Failed to create agent session for channel");
@@ -276,15 +717,25 @@ impl ChannelState {
                     })
                     .1
                     .iter()
-                    .map(|(m, _)| m.to_owned())
+                    .map(|p| p.message.to_owned())
                     .collect(),
             );
 
             self.message_queue.clear();
 
-            let _ = agent.execute_agent_multi_turn().await.inspect_err(|e| {
+            // Transient errors are retried by the session, so this is only
+            // announced once it gave up
+            if let Err(e) = agent.execute_agent_multi_turn().await {
                 tracing::error!(?e, "Error executing agent session in channel main loop",);
-            });
+                let _ = self
+                    .channel_id
+                    .say(
+                        &self.discord_ctx.http,
+                        "❗️ Something went wrong on my end, try again in a bit",
+                    )
+                    .await
+                    .inspect_err(|e| tracing::error!(?e, "Failed to announce agent error"));
+            }
         }
     }
 }
@@ -297,12 +748,15 @@ pub struct ChannelHandle {
 }
 
 impl ChannelHandle {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         discord_ctx: Context,
         channel_id: ChannelId,
-        openai_api_key: String,
-        shared_vectordb_client: Option<tools::SharedVectorClient>,
-        discord_bot_mention_only: bool,
+        parent_id: Option<ChannelId>,
+        services: AgentServices,
+        settings: DiscordSettings,
+        guild_id: Option<GuildId>,
+        dm_user: Option<UserId>,
         guilds: Arc<scc::HashMap<serenity::model::id::GuildId, Guild>>,
     ) -> Self {
         let (event_send, event_recv) = futures::channel::mpsc::unbounded();
@@ -313,23 +767,25 @@ impl ChannelHandle {
             activity: ChannelActivity::new(),
             event_recv,
             agent: None,
+            agent_settings: None,
             bot_user_id,
             discord_ctx: discord_ctx.clone(),
             message_queue: vec![],
+            cooldowns: UserCooldowns::default(),
             channel_id,
-            discord_bot_mention_only,
+            parent_id,
+            guild_id,
+            dm_user,
+            settings,
+            services,
+            budget_exceeded_on: None,
             guilds,
         };
 
-        let main_loop_handle = tokio::spawn(
-            state
-                .main_loop(shared_vectordb_client, openai_api_key)
-                .instrument(tracing::info_span!(
-                    "channel_main_loop",
-                    channel_id = channel_id.get(),
-                    discord_bot_mention_only
-                )),
-        );
+        let main_loop_handle = tokio::spawn(state.main_loop().instrument(tracing::info_span!(
+            "channel_main_loop",
+            channel_id = channel_id.get(),
+        )));
 
         Self {
             event_send,
@@ -0,0 +1,329 @@
+use serenity::all::{
+    ChannelId, CommandInteraction, CommandOptionType, Context, CreateCommand, CreateCommandOption,
+    CreateInteractionResponse, CreateInteractionResponseMessage, GuildId, Message, Permissions,
+};
+
+use crate::{
+    discord::{
+        settings::{DiscordSettings, to_db_id},
+        summaries::SummarySchedule,
+    },
+    models::discord::{NewDiscordChannelSettings, NewDiscordGuildSettings},
+};
+
+/// Prefix of the settings commands handled by the bot itself rather than the
+/// agent, e.g. `!bot mention-only on`
+pub const SETTINGS_COMMAND_PREFIX: &str = "!bot";
+/// Name of the slash command running the same commands, e.g.
+/// `/bot command:mention-only on`
+pub const SETTINGS_SLASH_COMMAND: &str = "bot";
+
+const USAGE: &str = "usage: `!bot <command>` or `/bot command:<command>`
+- `settings` show the settings of this channel
+- `enable` / `disable` respond in this channel
+- `mention-only <on|off|default>`
+- `streaming <on|off|default>` edit the reply as it's being written
+- `tools <tool or group, ...|all|default>` e.g. `tools godbolt, memory`
+- `memory-retention <days|forever|default>` forget memories not updated for this long
+- `archive <on|off>` archive the conversations of this channel for analytics
+- `archive-retention <days|forever|default>` delete archived messages after this long
+- `rate-limit <messages|off|default>` messages a user can get answered per 10 minutes
+- `attachment-memory <on|off>` store the text and PDF files posted in this channel in its memory
+- `summary <daily|weekly|off>` post a summary of the activity of this channel
+- `moderation <on|off>` let the bot warn, time out and delete messages in this server
+- `moderation-threshold <0-1|default>` classifier score from which messages are flagged
+- `model <model id|default>`
+- `profile <name|default>` persona profile setting the tone, slang and safety level
+- `profiles` list the persona profiles
+- `persona <text|default>` custom instructions on top of the profile";
+
+/// Handle a settings command if the message is one. Returns whether the message
+/// was consumed, in which case it should not be forwarded to the agent. The
+/// settings apply to `channel_id`, which is the parent channel for threads.
+pub async fn handle_settings_command(
+    ctx: &Context,
+    msg: &Message,
+    channel_id: ChannelId,
+    settings: &DiscordSettings,
+) -> bool {
+    let Some(args) = msg.content.trim().strip_prefix(SETTINGS_COMMAND_PREFIX) else {
+        return false;
+    };
+
+    // Make sure it's not just a message that happens to start with "!bot"
+    if !args.is_empty() && !args.starts_with(char::is_whitespace) {
+        return false;
+    }
+
+    // The guild cache is populated by the GUILDS intent
+    let can_manage = msg
+        .author_permissions(&ctx.cache)
+        .is_some_and(|p| p.manage_guild() || p.administrator());
+    let reply = run_or_report(msg.guild_id, can_manage, channel_id, settings, args.trim()).await;
+
+    let _ = msg
+        .reply(&ctx.http, reply)
+        .await
+        .inspect_err(|e| tracing::error!(?e, "Failed to reply to settings command"));
+
+    true
+}
+
+/// The slash command, registered in each guild the bot is in. It's only shown
+/// to the members who can manage the server, like the settings require.
+pub fn settings_slash_command() -> CreateCommand {
+    CreateCommand::new(SETTINGS_SLASH_COMMAND)
+        .description("Show or change the settings of the bot in this channel")
+        .default_member_permissions(Permissions::MANAGE_GUILD)
+        .add_option(CreateCommandOption::new(
+            CommandOptionType::String,
+            "command",
+            "e.g. `settings`, `enable` or `mention-only on`, all of them are listed without one",
+        ))
+}
+
+/// Runs the settings command of a slash command, the reply is only shown to
+/// the member who ran it
+pub async fn handle_settings_interaction(
+    ctx: &Context,
+    command: &CommandInteraction,
+    channel_id: ChannelId,
+    settings: &DiscordSettings,
+) {
+    let args = command
+        .data
+        .options
+        .iter()
+        .find(|option| option.name == "command")
+        .and_then(|option| option.value.as_str())
+        .unwrap_or_default();
+    let can_manage = command
+        .member
+        .as_ref()
+        .and_then(|member| member.permissions)
+        .is_some_and(|p| p.manage_guild() || p.administrator());
+    let reply = run_or_report(
+        command.guild_id,
+        can_manage,
+        channel_id,
+        settings,
+        args.trim(),
+    )
+    .await;
+
+    let response = CreateInteractionResponseMessage::new()
+        .content(reply)
+        .ephemeral(true);
+    let _ = command
+        .create_response(&ctx.http, CreateInteractionResponse::Message(response))
+        .await
+        .inspect_err(|e| tracing::error!(?e, "Failed to reply to settings slash command"));
+}
+
+async fn run_or_report(
+    guild_id: Option<GuildId>,
+    can_manage: bool,
+    channel_id: ChannelId,
+    settings: &DiscordSettings,
+    args: &str,
+) -> String {
+    match run(guild_id, can_manage, channel_id, settings, args).await {
+        Ok(reply) => reply,
+        Err(e) => {
+            tracing::error!(?e, "Failed to run Discord settings command");
+            format!("❗️ Failed to update settings: {e}")
+        }
+    }
+}
+
+async fn run(
+    guild_id: Option<GuildId>,
+    can_manage: bool,
+    channel_id: ChannelId,
+    settings: &DiscordSettings,
+    args: &str,
+) -> Result<String, eyre::Error> {
+    let Some(guild_id) = guild_id else {
+        return Ok("settings can only be changed in a server".to_string());
+    };
+
+    if !can_manage {
+        return Ok("you need the Manage Server permission to do that".to_string());
+    }
+
+    let (command, value) = args
+        .split_once(char::is_whitespace)
+        .map(|(c, v)| (c, v.trim()))
+        .unwrap_or((args, ""));
+
+    // Moderation is enforced server wide
+    if command.starts_with("moderation") {
+        let mut guild = settings
+            .stored_guild(guild_id)
+            .map(NewDiscordGuildSettings::from)
+            .unwrap_or(NewDiscordGuildSettings {
+                guild_id: to_db_id(guild_id.get()),
+                mention_only: None,
+                persona: None,
+                model: None,
+                streaming: None,
+                allowed_tools: None,
+                memory_retention_days: None,
+                persona_profile: None,
+                moderation_enforcement: None,
+                moderation_threshold: None,
+            });
+
+        match (command, value) {
+            ("moderation", "on") => guild.moderation_enforcement = Some(true),
+            ("moderation", "off") => guild.moderation_enforcement = None,
+            ("moderation-threshold", "default") => guild.moderation_threshold = None,
+            ("moderation-threshold", value)
+                if value.parse::<f64>().is_ok_and(|t| (0.0..=1.0).contains(&t)) =>
+            {
+                guild.moderation_threshold = value.parse().ok()
+            }
+            _ => return Ok(USAGE.to_string()),
+        }
+
+        settings.upsert_guild(guild).await?;
+
+        return Ok("server settings updated".to_string());
+    }
+
+    let mut channel = settings
+        .stored_channel(channel_id)
+        .map(NewDiscordChannelSettings::from)
+        .unwrap_or_else(|| {
+            let current = settings.channel(channel_id, Some(guild_id));
+            NewDiscordChannelSettings {
+                channel_id: to_db_id(channel_id.get()),
+                guild_id: None,
+                enabled: current.enabled,
+                mention_only: None,
+                persona: None,
+                model: None,
+                streaming: None,
+                allowed_tools: None,
+                memory_retention_days: None,
+                archive: None,
+                archive_retention_days: None,
+                persona_profile: None,
+                user_rate_limit: None,
+                store_attachments: None,
+                summary_schedule: None,
+            }
+        });
+    channel.guild_id = Some(to_db_id(guild_id.get()));
+
+    let default_or = |value: &str| (value != "default").then(|| value.to_string());
+
+    match (command, value) {
+        ("settings", _) => {
+            let current = settings.channel(channel_id, Some(guild_id));
+            return Ok(format!(
+                "enabled: `{}`\nmention-only: `{}`\nstreaming: `{}`\ntools: `{}`\nmemory-retention: `{}`\narchive: `{}`\narchive-retention: `{}`\nrate-limit: `{}`\nattachment-memory: `{}`\nsummary: `{}`\nmoderation: `{}`\nmoderation-threshold: `{}`\nmodel: `{}`\nprofile: `{}`\npersona: {}",
+                current.enabled,
+                current.mention_only,
+                current.streaming,
+                current
+                    .allowed_tools
+                    .map(|t| t.join(", "))
+                    .unwrap_or("all".to_string()),
+                current
+                    .memory_retention_days
+                    .map(|d| format!("{d} days"))
+                    .unwrap_or("forever".to_string()),
+                current.archive,
+                current
+                    .archive_retention_days
+                    .map(|d| format!("{d} days"))
+                    .unwrap_or("forever".to_string()),
+                current
+                    .user_rate_limit
+                    .map(|n| format!("{n} messages per 10 minutes"))
+                    .unwrap_or("off".to_string()),
+                current.store_attachments,
+                current
+                    .summary_schedule
+                    .map(SummarySchedule::as_str)
+                    .unwrap_or("off"),
+                current.moderation_enforcement,
+                current
+                    .moderation_threshold
+                    .map(|t| t.to_string())
+                    .unwrap_or("default".to_string()),
+                current.model,
+                current.profile.name,
+                current.persona.as_deref().unwrap_or("default"),
+            ));
+        }
+        ("profiles", _) => {
+            return Ok(settings
+                .profiles()
+                .iter()
+                .map(|p| format!("- `{}`: {}", p.name, p.tone))
+                .collect::<Vec<_>>()
+                .join("\n"));
+        }
+        ("enable", _) => channel.enabled = true,
+        ("disable", _) => channel.enabled = false,
+        ("mention-only", "on") => channel.mention_only = Some(true),
+        ("mention-only", "off") => channel.mention_only = Some(false),
+        ("mention-only", "default") => channel.mention_only = None,
+        ("streaming", "on") => channel.streaming = Some(true),
+        ("streaming", "off") => channel.streaming = Some(false),
+        ("streaming", "default") => channel.streaming = None,
+        ("tools", "default") => channel.allowed_tools = None,
+        // Overrides a restriction of the guild
+        ("tools", "all") => channel.allowed_tools = Some(vec!["*".to_string()]),
+        ("tools", value) if !value.is_empty() => {
+            channel.allowed_tools = Some(
+                value
+                    .split(',')
+                    .map(|t| t.trim().to_string())
+                    .filter(|t| !t.is_empty())
+                    .collect(),
+            )
+        }
+        ("memory-retention", "default") => channel.memory_retention_days = None,
+        ("memory-retention", "forever") => channel.memory_retention_days = Some(0),
+        ("memory-retention", value) if value.parse::<u16>().is_ok_and(|d| d > 0) => {
+            channel.memory_retention_days = value.parse::<i32>().ok()
+        }
+        ("archive", "on") => channel.archive = Some(true),
+        ("archive", "off") => channel.archive = None,
+        ("archive-retention", "default") => channel.archive_retention_days = None,
+        ("archive-retention", "forever") => channel.archive_retention_days = Some(0),
+        ("archive-retention", value) if value.parse::<u16>().is_ok_and(|d| d > 0) => {
+            channel.archive_retention_days = value.parse::<i32>().ok()
+        }
+        ("rate-limit", "default") => channel.user_rate_limit = None,
+        ("rate-limit", "off") => channel.user_rate_limit = Some(0),
+        ("rate-limit", value) if value.parse::<u16>().is_ok_and(|n| n > 0) => {
+            channel.user_rate_limit = value.parse::<i32>().ok()
+        }
+        ("attachment-memory", "on") => channel.store_attachments = Some(true),
+        ("attachment-memory", "off") => channel.store_attachments = None,
+        ("summary", "off") => channel.summary_schedule = None,
+        ("summary", value) if SummarySchedule::parse(value).is_some() => {
+            channel.summary_schedule = SummarySchedule::parse(value).map(|s| s.as_str().to_string())
+        }
+        ("model", value) if !value.is_empty() => channel.model = default_or(value),
+        ("profile", "default") => channel.persona_profile = None,
+        ("profile", value) if settings.has_profile(value) => {
+            channel.persona_profile = Some(value.to_string())
+        }
+        ("profile", value) if !value.is_empty() => {
+            return Ok(format!(
+                "unknown profile `{value}`, see `{SETTINGS_COMMAND_PREFIX} profiles`"
+            ));
+        }
+        ("persona", value) if !value.is_empty() => channel.persona = default_or(value),
+        _ => return Ok(USAGE.to_string()),
+    }
+
+    settings.upsert_channel(channel).await?;
+
+    Ok("settings updated".to_string())
+}
@@ -0,0 +1,175 @@
+use std::{
+    collections::VecDeque,
+    future::Future,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+    },
+    time::Duration,
+};
+
+use eyre::Context as _;
+use serde::Serialize;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+use crate::config::ServerConfig;
+
+/// Number of recent queue waits the percentiles are computed from
+const WAIT_SAMPLES: usize = 1000;
+
+struct Inner {
+    semaphore: Arc<Semaphore>,
+    max_concurrent: usize,
+    max_queue_depth: usize,
+    waiting: AtomicUsize,
+    runs: AtomicU64,
+    dropped_messages: AtomicU64,
+    waits: Mutex<VecDeque<Duration>>,
+}
+
+/// Caps the agent runs executing at once across all channels. Channels wait for
+/// a slot in FIFO order and keep queueing their messages meanwhile.
+#[derive(Clone)]
+pub struct ExecutionLimiter(Arc<Inner>);
+
+/// Held for the duration of an agent run
+pub struct ExecutionPermit {
+    _permit: OwnedSemaphorePermit,
+}
+
+#[derive(Serialize, Debug)]
+pub struct LimiterStats {
+    pub max_concurrent: usize,
+    pub max_queue_depth: usize,
+    pub running: usize,
+    /// Channels waiting for a slot
+    pub waiting: usize,
+    pub runs: u64,
+    /// Messages dropped because a channel's queue was full
+    pub dropped_messages: u64,
+    /// Queue wait of the recent runs in milliseconds
+    pub wait_p50_ms: u64,
+    pub wait_p95_ms: u64,
+    pub wait_max_ms: u64,
+}
+
+impl ExecutionLimiter {
+    pub fn new(config: &ServerConfig) -> Self {
+        Self(Arc::new(Inner {
+            semaphore: Arc::new(Semaphore::new(config.discord_max_concurrent_runs)),
+            max_concurrent: config.discord_max_concurrent_runs,
+            max_queue_depth: config.discord_max_queued_messages,
+            waiting: AtomicUsize::new(0),
+            runs: AtomicU64::new(0),
+            dropped_messages: AtomicU64::new(0),
+            waits: Mutex::new(VecDeque::with_capacity(WAIT_SAMPLES)),
+        }))
+    }
+
+    /// Wait for a slot. The future doesn't borrow the limiter so that the
+    /// channel can keep handling its events while polling it, and keeps its
+    /// place in the queue as long as it isn't dropped.
+    pub fn acquire(&self) -> impl Future<Output = Result<ExecutionPermit, eyre::Error>> + 'static {
+        let limiter = self.clone();
+        async move {
+            let started = tokio::time::Instant::now();
+            let waiting = Waiting::new(&limiter.0.waiting);
+            let permit = limiter.0.semaphore.clone().acquire_owned().await;
+            drop(waiting);
+
+            let permit = permit.wrap_err("agent execution semaphore closed")?;
+            limiter.record_wait(started.elapsed());
+
+            Ok(ExecutionPermit { _permit: permit })
+        }
+    }
+
+    fn record_wait(&self, wait: Duration) {
+        self.0.runs.fetch_add(1, Ordering::Relaxed);
+
+        if wait > Duration::from_secs(1) {
+            tracing::debug!(?wait, "Agent run waited for an execution slot");
+        }
+
+        if let Ok(mut waits) = self.0.waits.lock() {
+            if waits.len() == WAIT_SAMPLES {
+                waits.pop_front();
+            }
+            waits.push_back(wait);
+        }
+    }
+
+    /// Drop the oldest messages past the queue depth of a channel, returns how
+    /// many
+    pub fn truncate_queue<T>(&self, queue: &mut Vec<T>) -> usize {
+        let excess = queue.len().saturating_sub(self.0.max_queue_depth);
+        if excess > 0 {
+            queue.drain(0..excess);
+            self.0
+                .dropped_messages
+                .fetch_add(excess as u64, Ordering::Relaxed);
+        }
+        excess
+    }
+
+    pub fn stats(&self) -> LimiterStats {
+        let mut waits = self
+            .0
+            .waits
+            .lock()
+            .map(|w| w.iter().copied().collect::<Vec<_>>())
+            .unwrap_or_default();
+        waits.sort();
+
+        LimiterStats {
+            max_concurrent: self.0.max_concurrent,
+            max_queue_depth: self.0.max_queue_depth,
+            running: self.0.max_concurrent - self.0.semaphore.available_permits(),
+            waiting: self.0.waiting.load(Ordering::Relaxed),
+            runs: self.0.runs.load(Ordering::Relaxed),
+            dropped_messages: self.0.dropped_messages.load(Ordering::Relaxed),
+            wait_p50_ms: percentile_ms(&waits, 0.5),
+            wait_p95_ms: percentile_ms(&waits, 0.95),
+            wait_max_ms: waits.last().map_or(0, |w| w.as_millis() as u64),
+        }
+    }
+}
+
+/// Counts a waiting channel until dropped, even if the wait is cancelled
+struct Waiting<'a>(&'a AtomicUsize);
+
+impl<'a> Waiting<'a> {
+    fn new(counter: &'a AtomicUsize) -> Self {
+        counter.fetch_add(1, Ordering::Relaxed);
+        Self(counter)
+    }
+}
+
+impl Drop for Waiting<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// Nearest-rank percentile of sorted durations
+fn percentile_ms(sorted: &[Duration], p: f64) -> u64 {
+    if sorted.is_empty() {
+        return 0;
+    }
+    let rank = ((sorted.len() as f64 * p).ceil() as usize).clamp(1, sorted.len());
+    sorted[rank - 1].as_millis() as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn percentile_uses_nearest_rank() {
+        let waits = (1..=10).map(Duration::from_millis).collect::<Vec<_>>();
+
+        assert_eq!(percentile_ms(&waits, 0.5), 5);
+        assert_eq!(percentile_ms(&waits, 0.95), 10);
+        assert_eq!(percentile_ms(&[], 0.5), 0);
+    }
+}
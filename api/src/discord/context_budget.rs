@@ -0,0 +1,159 @@
+//! Keeping the conversation history of the agent within a token budget. A
+//! single pasted log can be larger than the rest of the conversation, so long
+//! texts are cut with a marker telling how much was left out, and the oldest
+//! messages are dropped by their tokens rather than their count.
+
+use std::{borrow::Cow, sync::LazyLock};
+
+use rig::{
+    completion::Message as RigMessage,
+    message::{AssistantContent, ToolResultContent, UserContent},
+};
+use tiktoken_rs::CoreBPE;
+
+use super::constants::MAX_MESSAGE_TOKENS;
+
+/// Rough cost of an image, the providers count them by their size
+const IMAGE_TOKENS: usize = 800;
+/// Role and separators of every message
+const MESSAGE_OVERHEAD_TOKENS: usize = 4;
+
+/// The encoding of the recent OpenAI models, close enough to estimate the
+/// tokens of the other providers
+static BPE: LazyLock<CoreBPE> =
+    LazyLock::new(|| tiktoken_rs::o200k_base().expect("the o200k encoding is bundled"));
+
+pub fn count_tokens(text: &str) -> usize {
+    BPE.encode_ordinary(text).len()
+}
+
+/// Cuts the text to at most `max` tokens and marks the cut
+pub fn truncate_text(text: &str, max: usize) -> Cow<'_, str> {
+    let tokens = count_tokens(text);
+    if tokens <= max {
+        return Cow::Borrowed(text);
+    }
+
+    // Start from the share of the text matching the share of the tokens, then
+    // back off until the prefix fits since tokens aren't spread evenly
+    let mut end = text.len() * max / tokens;
+    let kept = loop {
+        while !text.is_char_boundary(end) {
+            end -= 1;
+        }
+        let kept = count_tokens(&text[..end]);
+        if end == 0 || kept <= max {
+            break kept;
+        }
+        end = end * 9 / 10;
+    };
+
+    Cow::Owned(format!(
+        "{}… [truncated, {} more tokens]",
+        text[..end].trim_end(),
+        tokens - kept
+    ))
+}
+
+/// Cuts the long texts of a message, tool calls and their results are left
+/// alone since the tools bound their own output
+pub fn truncate_message(message: &mut RigMessage) {
+    let truncate = |text: &mut String| {
+        if let Cow::Owned(truncated) = truncate_text(text, MAX_MESSAGE_TOKENS) {
+            *text = truncated;
+        }
+    };
+
+    match message {
+        RigMessage::User { content } => content.iter_mut().for_each(|c| {
+            if let UserContent::Text(t) = c {
+                truncate(&mut t.text);
+            }
+        }),
+        RigMessage::Assistant { content, .. } => content.iter_mut().for_each(|c| {
+            if let AssistantContent::Text(t) = c {
+                truncate(&mut t.text);
+            }
+        }),
+        RigMessage::System { .. } => {}
+    }
+}
+
+pub fn message_tokens(message: &RigMessage) -> usize {
+    let content = match message {
+        RigMessage::System { content } => count_tokens(content),
+        RigMessage::User { content } => content
+            .iter()
+            .map(|c| match c {
+                UserContent::Text(t) => count_tokens(&t.text),
+                UserContent::ToolResult(r) => r
+                    .content
+                    .iter()
+                    .map(|c| match c {
+                        ToolResultContent::Text(t) => count_tokens(&t.text),
+                        _ => IMAGE_TOKENS,
+                    })
+                    .sum(),
+                _ => IMAGE_TOKENS,
+            })
+            .sum(),
+        RigMessage::Assistant { content, .. } => content
+            .iter()
+            .map(|c| match c {
+                AssistantContent::Text(t) => count_tokens(&t.text),
+                AssistantContent::ToolCall(call) => {
+                    count_tokens(&call.function.name)
+                        + count_tokens(&call.function.arguments.to_string())
+                }
+                _ => 0,
+            })
+            .sum(),
+    };
+    content + MESSAGE_OVERHEAD_TOKENS
+}
+
+pub fn history_tokens(messages: &[RigMessage]) -> usize {
+    messages.iter().map(message_tokens).sum()
+}
+
+/// Number of the oldest messages to drop for the rest to fit the budget. The
+/// newest message is always kept.
+pub fn excess_over_budget(messages: &[RigMessage], budget: usize) -> usize {
+    let mut total = 0;
+    for (i, message) in messages.iter().enumerate().rev() {
+        total += message_tokens(message);
+        if total > budget {
+            return (i + 1).min(messages.len() - 1);
+        }
+    }
+    0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn long_texts_are_cut_with_a_marker() {
+        assert_eq!(truncate_text("short enough", 10), "short enough");
+
+        let pasted = "lorem ipsum dolor sit amet ".repeat(500);
+        let truncated = truncate_text(&pasted, 100);
+        let (kept, marker) = truncated.split_once('…').unwrap();
+        assert!(count_tokens(kept) <= 100);
+        assert!(marker.starts_with(" [truncated, "));
+    }
+
+    #[test]
+    fn the_oldest_messages_are_dropped_first() {
+        let history = vec![
+            RigMessage::user("word ".repeat(200)),
+            RigMessage::user("hello"),
+            RigMessage::user("hi"),
+        ];
+        assert_eq!(excess_over_budget(&history, 1000), 0);
+        assert_eq!(excess_over_budget(&history, 50), 1);
+        // The newest message stays even if it alone is over the budget
+        assert_eq!(excess_over_budget(&history, 1), 2);
+    }
+}
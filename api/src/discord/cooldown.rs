@@ -0,0 +1,94 @@
+//! Per-user cooldowns of a channel, so that one chatty user can't keep the
+//! agent to themselves. Each user has a bucket holding as many messages as
+//! the limit, refilled at the limit per window.
+
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+use serenity::all::UserId;
+
+pub const WINDOW: Duration = Duration::from_secs(10 * 60);
+
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+    /// Whether the user was told about the cooldown since their last answered
+    /// message
+    notified: bool,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Cooldown {
+    Allow,
+    /// Over the limit, the wait until the next message is answered is set if
+    /// the user wasn't told yet
+    Throttle(Option<Duration>),
+}
+
+#[derive(Default)]
+pub struct UserCooldowns(HashMap<UserId, Bucket>);
+
+impl UserCooldowns {
+    /// Takes a message from the bucket of the user
+    pub fn check(&mut self, user_id: UserId, limit: u32, now: Instant) -> Cooldown {
+        let limit = f64::from(limit.max(1));
+        let refill = |bucket: &Bucket| {
+            let elapsed = now.saturating_duration_since(bucket.updated);
+            (bucket.tokens + elapsed.as_secs_f64() / WINDOW.as_secs_f64() * limit).min(limit)
+        };
+
+        // The buckets full again are forgotten so that the map doesn't grow
+        // with every user ever seen
+        self.0.retain(|_, bucket| refill(bucket) < limit);
+
+        let bucket = self.0.entry(user_id).or_insert(Bucket {
+            tokens: limit,
+            updated: now,
+            notified: false,
+        });
+        bucket.tokens = refill(bucket);
+        bucket.updated = now;
+
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            bucket.notified = false;
+            return Cooldown::Allow;
+        }
+
+        let wait = WINDOW.mul_f64((1.0 - bucket.tokens) / limit);
+        let first = !std::mem::replace(&mut bucket.notified, true);
+        Cooldown::Throttle(first.then_some(wait))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_bucket_refills_over_the_window() {
+        let mut cooldowns = UserCooldowns::default();
+        let (alice, bob) = (UserId::new(1), UserId::new(2));
+        let start = Instant::now();
+
+        assert_eq!(cooldowns.check(alice, 2, start), Cooldown::Allow);
+        assert_eq!(cooldowns.check(alice, 2, start), Cooldown::Allow);
+        assert_eq!(
+            cooldowns.check(alice, 2, start),
+            Cooldown::Throttle(Some(Duration::from_secs(5 * 60)))
+        );
+        // Told once
+        assert_eq!(cooldowns.check(alice, 2, start), Cooldown::Throttle(None));
+        assert_eq!(cooldowns.check(bob, 2, start), Cooldown::Allow);
+
+        // Half of the window gives one message back
+        let later = start + WINDOW / 2;
+        assert_eq!(cooldowns.check(alice, 2, later), Cooldown::Allow);
+        assert!(matches!(
+            cooldowns.check(alice, 2, later),
+            Cooldown::Throttle(Some(_))
+        ));
+    }
+}
@@ -0,0 +1,264 @@
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use diesel::prelude::*;
+use diesel_async::{AsyncPgConnection, RunQueryDsl, pooled_connection::deadpool::Pool};
+use eyre::Context as _;
+use serenity::all::{Context, Message, UserId};
+
+use crate::{
+    config::{ServerConfig, Tunables},
+    discord::{commands::SETTINGS_COMMAND_PREFIX, constants::DISCORD_BOT_NAME, settings::to_db_id},
+    models::discord::DiscordDmConsent,
+    schema::discord_dm_consents,
+};
+
+const RATE_LIMIT_WINDOW: Duration = Duration::from_secs(60 * 60);
+
+struct Inner {
+    diesel: Pool<AsyncPgConnection>,
+    enabled: bool,
+    /// Holds the rate limit, which can change while running
+    tunables: Tunables,
+    // Users known to have consented or not, to avoid a query per message
+    consents: Mutex<HashMap<UserId, bool>>,
+    // Users who were sent the onboarding message since the start
+    onboarded: Mutex<HashSet<UserId>>,
+    // Times of the recent messages of each user, within the rate limit window
+    recent: Mutex<HashMap<UserId, VecDeque<Instant>>>,
+    // Users who were told they hit the rate limit in the current window
+    throttled: Mutex<HashSet<UserId>>,
+}
+
+/// Gates the DM conversations: users have to agree to talk to the bot first,
+/// and are rate limited more strictly than in servers.
+#[derive(Clone)]
+pub struct DirectMessages(Arc<Inner>);
+
+/// What to do with an incoming DM
+#[derive(Debug, PartialEq)]
+pub enum DirectMessageGate {
+    Allow,
+    /// The user hasn't consented yet, the onboarding message is sent if set
+    Onboard(Option<&'static str>),
+    /// Over the rate limit, the notice is sent if set
+    Throttle(Option<String>),
+}
+
+impl DirectMessages {
+    pub fn new(config: &ServerConfig, tunables: Tunables, diesel: Pool<AsyncPgConnection>) -> Self {
+        Self(Arc::new(Inner {
+            diesel,
+            enabled: config.discord_direct_messages,
+            tunables,
+            consents: Mutex::new(HashMap::new()),
+            onboarded: Mutex::new(HashSet::new()),
+            recent: Mutex::new(HashMap::new()),
+            throttled: Mutex::new(HashSet::new()),
+        }))
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.0.enabled
+    }
+
+    async fn conn(
+        &self,
+    ) -> Result<diesel_async::pooled_connection::deadpool::Object<AsyncPgConnection>, eyre::Error>
+    {
+        self.0
+            .diesel
+            .get()
+            .await
+            .wrap_err("could not get diesel pool conn")
+    }
+
+    fn cache_consent(&self, user_id: UserId, consented: bool) {
+        if let Ok(mut consents) = self.0.consents.lock() {
+            consents.insert(user_id, consented);
+        }
+    }
+
+    pub async fn has_consented(&self, user_id: UserId) -> Result<bool, eyre::Error> {
+        let cached = self
+            .0
+            .consents
+            .lock()
+            .ok()
+            .and_then(|c| c.get(&user_id).copied());
+        if let Some(consented) = cached {
+            return Ok(consented);
+        }
+
+        let mut conn = self.conn().await?;
+        let consented = discord_dm_consents::table
+            .find(to_db_id(user_id.get()))
+            .select(DiscordDmConsent::as_select())
+            .first(&mut conn)
+            .await
+            .optional()
+            .wrap_err("failed to query Discord DM consent")?
+            .is_some();
+
+        self.cache_consent(user_id, consented);
+        Ok(consented)
+    }
+
+    pub async fn consent(&self, user_id: UserId) -> Result<(), eyre::Error> {
+        let mut conn = self.conn().await?;
+
+        diesel::insert_into(discord_dm_consents::table)
+            .values(discord_dm_consents::user_id.eq(to_db_id(user_id.get())))
+            .on_conflict_do_nothing()
+            .execute(&mut conn)
+            .await
+            .wrap_err("failed to store Discord DM consent")?;
+
+        self.cache_consent(user_id, true);
+        Ok(())
+    }
+
+    pub async fn revoke(&self, user_id: UserId) -> Result<(), eyre::Error> {
+        let mut conn = self.conn().await?;
+
+        diesel::delete(discord_dm_consents::table.find(to_db_id(user_id.get())))
+            .execute(&mut conn)
+            .await
+            .wrap_err("failed to revoke Discord DM consent")?;
+
+        self.cache_consent(user_id, false);
+        Ok(())
+    }
+
+    /// Decide whether a DM is forwarded to the agent
+    pub async fn gate(&self, user_id: UserId) -> Result<DirectMessageGate, eyre::Error> {
+        if !self.0.enabled {
+            return Ok(DirectMessageGate::Onboard(None));
+        }
+
+        if !self.has_consented(user_id).await? {
+            // Only onboard once per user until restarted, so that the bot
+            // doesn't answer every message of someone who doesn't want to
+            let first = self.0.onboarded.lock().is_ok_and(|mut o| o.insert(user_id));
+            return Ok(DirectMessageGate::Onboard(first.then_some(ONBOARDING)));
+        }
+
+        let messages_per_hour = self.0.tunables.get().discord_dm_messages_per_hour;
+        let allowed = self
+            .0
+            .recent
+            .lock()
+            .map(|mut recent| {
+                allow_message(
+                    recent.entry(user_id).or_default(),
+                    messages_per_hour,
+                    Instant::now(),
+                )
+            })
+            .unwrap_or(true);
+
+        let Ok(mut throttled) = self.0.throttled.lock() else {
+            return Ok(DirectMessageGate::Allow);
+        };
+        if allowed {
+            throttled.remove(&user_id);
+            return Ok(DirectMessageGate::Allow);
+        }
+
+        let notice = throttled.insert(user_id).then(|| {
+            format!(
+                "⏳ You've reached the limit of {} messages per hour, talk to you later",
+                messages_per_hour
+            )
+        });
+        Ok(DirectMessageGate::Throttle(notice))
+    }
+
+    /// Handle the consent commands if the DM is one. Returns whether the message
+    /// was consumed.
+    pub async fn handle_command(&self, ctx: &Context, msg: &Message) -> bool {
+        let reply = match msg
+            .content
+            .trim()
+            .strip_prefix(SETTINGS_COMMAND_PREFIX)
+            .map(str::trim)
+        {
+            Some("agree") => self
+                .consent(msg.author.id)
+                .await
+                .map(|_| "👋 Thanks! Go ahead, I'm listening."),
+            Some("stop") => self.revoke(msg.author.id).await.map(|_| {
+                "👋 Got it, I won't respond to your DMs anymore. Send `!bot agree` if you change your mind."
+            }),
+            Some(_) => Ok(ONBOARDING),
+            None => return false,
+        };
+
+        let reply = reply
+            .inspect_err(|e| tracing::error!(?e, "Failed to update Discord DM consent"))
+            .unwrap_or("❗️ Something went wrong, try again later");
+
+        let _ = msg
+            .reply(&ctx.http, reply)
+            .await
+            .inspect_err(|e| tracing::error!(?e, "Failed to reply to DM command"));
+
+        true
+    }
+}
+
+const ONBOARDING: &str = const_format::formatcp!(
+    "Hi, I'm {DISCORD_BOT_NAME}! Before we chat in DMs, a few things to know:
+- Your messages are sent to a third-party AI provider to generate my replies
+- I may remember things you tell me to be more helpful later, only in our DMs
+- The number of messages per hour is limited
+
+Reply `{SETTINGS_COMMAND_PREFIX} agree` to start, or `{SETTINGS_COMMAND_PREFIX} stop` at any time to opt out."
+);
+
+/// Sliding window rate limit over the times of the recent messages, records the
+/// message if it's allowed
+fn allow_message(recent: &mut VecDeque<Instant>, limit: u32, now: Instant) -> bool {
+    while recent
+        .front()
+        .is_some_and(|t| now.duration_since(*t) >= RATE_LIMIT_WINDOW)
+    {
+        recent.pop_front();
+    }
+
+    if recent.len() >= limit as usize {
+        return false;
+    }
+
+    recent.push_back(now);
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn allow_message_slides_window() {
+        let start = Instant::now();
+        let mut recent = VecDeque::new();
+
+        assert!(allow_message(&mut recent, 2, start));
+        assert!(allow_message(
+            &mut recent,
+            2,
+            start + Duration::from_secs(60)
+        ));
+        assert!(!allow_message(
+            &mut recent,
+            2,
+            start + Duration::from_secs(120)
+        ));
+        // The first message left the window
+        assert!(allow_message(&mut recent, 2, start + RATE_LIMIT_WINDOW));
+        assert!(!allow_message(&mut recent, 2, start + RATE_LIMIT_WINDOW));
+    }
+}
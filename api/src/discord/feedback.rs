@@ -0,0 +1,167 @@
+use std::sync::Arc;
+
+use diesel::prelude::*;
+use diesel_async::{AsyncPgConnection, RunQueryDsl, pooled_connection::deadpool::Pool};
+use eyre::Context as _;
+use serenity::all::{ChannelId, Message, MessageId, ReactionType, UserId};
+
+use crate::{
+    config::ServerConfig, discord::settings::to_db_id, models::discord::NewDiscordMessageFeedback,
+    schema::discord_message_feedback,
+};
+
+use super::tools::{MemoryScope, SharedVectorClient};
+
+/// Long responses are cut when remembered as negative feedback
+const MAX_MEMORY_CONTENT_LEN: usize = 500;
+
+/// Whether a reaction is a 👍 (`true`) or a 👎 (`false`), `None` for any other
+/// reaction
+pub fn vote(emoji: &ReactionType) -> Option<bool> {
+    match emoji {
+        ReactionType::Unicode(emoji) => match emoji.trim_end_matches(|c| {
+            // Skin tone modifiers
+            ('\u{1F3FB}'..='\u{1F3FF}').contains(&c)
+        }) {
+            "👍" => Some(true),
+            "👎" => Some(false),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+struct Inner {
+    diesel: Pool<AsyncPgConnection>,
+    remember_negative: bool,
+}
+
+/// Records the reactions of users on the bot's messages as feedback
+#[derive(Clone)]
+pub struct FeedbackStore(Arc<Inner>);
+
+impl FeedbackStore {
+    pub fn new(config: &ServerConfig, diesel: Pool<AsyncPgConnection>) -> Self {
+        Self(Arc::new(Inner {
+            diesel,
+            remember_negative: config.discord_feedback_memories,
+        }))
+    }
+
+    /// Record a vote on a bot message. `channel_id` is the channel the
+    /// feedback is accounted to, i.e. the parent channel for threads. If
+    /// enabled, the first 👎 on a message is stored as a memory so that the
+    /// agent can avoid similar responses.
+    pub async fn record(
+        &self,
+        message: &Message,
+        channel_id: ChannelId,
+        user_id: UserId,
+        positive: bool,
+        vectordb: Option<&SharedVectorClient>,
+    ) -> Result<(), eyre::Error> {
+        let mut conn = self
+            .0
+            .diesel
+            .get()
+            .await
+            .wrap_err("could not get diesel pool conn")?;
+
+        let inserted = diesel::insert_into(discord_message_feedback::table)
+            .values(&NewDiscordMessageFeedback {
+                message_id: to_db_id(message.id.get()),
+                channel_id: to_db_id(channel_id.get()),
+                user_id: to_db_id(user_id.get()),
+                positive,
+                content: message.content.clone(),
+            })
+            .on_conflict_do_nothing()
+            .execute(&mut conn)
+            .await
+            .wrap_err("failed to record Discord message feedback")?;
+
+        if positive || inserted == 0 || !self.0.remember_negative {
+            return Ok(());
+        }
+
+        let Some(vectordb) = vectordb else {
+            return Ok(());
+        };
+
+        let negative_votes: i64 = discord_message_feedback::table
+            .filter(discord_message_feedback::message_id.eq(to_db_id(message.id.get())))
+            .filter(discord_message_feedback::positive.eq(false))
+            .count()
+            .get_result(&mut conn)
+            .await
+            .wrap_err("failed to count negative feedback")?;
+
+        if negative_votes > 1 {
+            return Ok(());
+        }
+
+        let content = match message.content.char_indices().nth(MAX_MEMORY_CONTENT_LEN) {
+            Some((n, _)) => format!("{}...", &message.content[..n]),
+            None => message.content.clone(),
+        };
+
+        vectordb
+            .store(
+                &format!(
+                    "Users reacted 👎 to this response of mine, avoid responding like this: \
+                    \"{content}\""
+                ),
+                MemoryScope::Channel(channel_id.get()),
+                Some(serde_json::json!({
+                    "kind": "negative_feedback",
+                    "message_id": message.id.get().to_string(),
+                    "timestamp": chrono::Utc::now().to_rfc3339(),
+                })),
+            )
+            .await
+            .map_err(|e| eyre::eyre!(e))
+            .wrap_err("failed to remember negative feedback")?;
+
+        Ok(())
+    }
+
+    /// Remove a vote when the reaction is removed
+    pub async fn remove(
+        &self,
+        message_id: MessageId,
+        user_id: UserId,
+        positive: bool,
+    ) -> Result<(), eyre::Error> {
+        let mut conn = self
+            .0
+            .diesel
+            .get()
+            .await
+            .wrap_err("could not get diesel pool conn")?;
+
+        diesel::delete(
+            discord_message_feedback::table
+                .filter(discord_message_feedback::message_id.eq(to_db_id(message_id.get())))
+                .filter(discord_message_feedback::user_id.eq(to_db_id(user_id.get())))
+                .filter(discord_message_feedback::positive.eq(positive)),
+        )
+        .execute(&mut conn)
+        .await
+        .wrap_err("failed to remove Discord message feedback")?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn vote_recognizes_thumbs_with_skin_tones() {
+        assert_eq!(vote(&ReactionType::Unicode("👍".to_string())), Some(true));
+        assert_eq!(vote(&ReactionType::Unicode("👍🏽".to_string())), Some(true));
+        assert_eq!(vote(&ReactionType::Unicode("👎".to_string())), Some(false));
+        assert_eq!(vote(&ReactionType::Unicode("🎉".to_string())), None);
+    }
+}
@@ -0,0 +1,138 @@
+//! Previews of the links shared in messages. They're read with the page tool
+//! and added to the context block of the message, so the agent knows what a
+//! link is about without spending a turn on it and finds the page cached if it
+//! reads it in full.
+
+use std::{fmt, sync::LazyLock, time::Duration};
+
+use regex::Regex;
+use url::Url;
+
+use super::tools::FetchPageContentTool;
+
+const MAX_PREVIEWS: usize = 3;
+/// Shorter than the timeout of the tool since the message waits for them
+const PREVIEW_TIMEOUT: Duration = Duration::from_secs(5);
+const SUMMARY_CHARS: usize = 300;
+/// Links to messages, attachments and invites that the page tool can't read
+const SKIPPED_HOSTS: &[&str] = &[
+    "discord.com",
+    "discord.gg",
+    "discordapp.com",
+    "discordapp.net",
+];
+
+static URL_PATTERN: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"https?://[^\s<>|`]+").expect("URL regex is valid"));
+
+pub struct LinkPreview {
+    url: Url,
+    title: Option<String>,
+    summary: String,
+}
+
+impl fmt::Display for LinkPreview {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.title {
+            Some(title) => write!(f, "{} \"{title}\": {}", self.url, self.summary),
+            None => write!(f, "{}: {}", self.url, self.summary),
+        }
+    }
+}
+
+/// Previews the first links of a message, the ones that fail or take too
+/// long are left to the agent
+pub async fn preview_links(fetch: &FetchPageContentTool, content: &str) -> Vec<LinkPreview> {
+    let previews = shared_links(content).into_iter().map(async |url| {
+        match tokio::time::timeout(PREVIEW_TIMEOUT, fetch.fetch(url.as_str())).await {
+            Ok(Ok(page)) => Some(preview(url, &page)),
+            Ok(Err(error)) => {
+                tracing::debug!(?error, %url, "Failed to preview a shared link");
+                None
+            }
+            Err(_) => {
+                tracing::debug!(%url, "Timed out previewing a shared link");
+                None
+            }
+        }
+    });
+
+    futures::future::join_all(previews)
+        .await
+        .into_iter()
+        .flatten()
+        .collect()
+}
+
+fn shared_links(content: &str) -> Vec<Url> {
+    let mut links: Vec<Url> = Vec::new();
+    for found in URL_PATTERN.find_iter(content) {
+        // Punctuation ending the sentence, and the parenthesis around the link
+        // unless it's part of it like in Wikipedia links
+        let mut link = found
+            .as_str()
+            .trim_end_matches(['.', ',', ';', ':', '!', '?', '\'', '"']);
+        while link.ends_with(')') && link.matches(')').count() > link.matches('(').count() {
+            link = &link[..link.len() - 1];
+        }
+
+        let Ok(url) = Url::parse(link) else {
+            continue;
+        };
+        let skipped = url.host_str().is_some_and(|host| {
+            SKIPPED_HOSTS
+                .iter()
+                .any(|skipped| host == *skipped || host.ends_with(&format!(".{skipped}")))
+        });
+        if !skipped && !links.contains(&url) {
+            links.push(url);
+        }
+        if links.len() == MAX_PREVIEWS {
+            break;
+        }
+    }
+    links
+}
+
+/// The title and the opening of a page read by the tool, which puts the title
+/// of articles in a leading heading
+fn preview(url: Url, page: &str) -> LinkPreview {
+    let (title, body) = match page.strip_prefix("# ").and_then(|p| p.split_once('\n')) {
+        Some((title, body)) => (Some(title.trim().to_string()), body),
+        None => (None, page),
+    };
+
+    let body = body.split_whitespace().collect::<Vec<_>>().join(" ");
+    let summary = match body.char_indices().nth(SUMMARY_CHARS) {
+        Some((end, _)) => format!("{}…", &body[..end]),
+        None => body,
+    };
+
+    LinkPreview {
+        url,
+        title,
+        summary,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn links_are_found_in_the_message() {
+        let links = shared_links(
+            "see https://example.com/post. and (https://en.wikipedia.org/wiki/Rust_(programming_language)) \
+             or <https://example.com/post> https://discord.com/channels/1/2/3 https://a.org/x),",
+        );
+        let links: Vec<_> = links.iter().map(Url::as_str).collect();
+        assert_eq!(
+            links,
+            [
+                "https://example.com/post",
+                "https://en.wikipedia.org/wiki/Rust_(programming_language)",
+                "https://a.org/x",
+            ]
+        );
+    }
+}
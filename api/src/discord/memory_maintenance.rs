@@ -0,0 +1,264 @@
+//! Keeps the agent's memories from accumulating forever: memories that weren't
+//! updated within the retention of their scope decay, and near-duplicates are
+//! consolidated into one memory by the LLM.
+
+use std::{iter::zip, time::Duration};
+
+use eyre::Context as _;
+use rig::{
+    agent::{Agent, AgentBuilder},
+    client::CompletionClient,
+    completion::Prompt,
+    providers::openrouter::{Client, CompletionModel},
+};
+use serenity::all::{ChannelId, GuildId};
+
+use crate::{config::ServerConfig, embedding::cosine_similarity, jobs};
+
+use super::{
+    constants::{CONSOLIDATION_PROMPT, DEFAULT_MODEL},
+    settings::DiscordSettings,
+    tools::{MemoryScope, SharedVectorClient, StoredMemory},
+};
+
+const MAINTENANCE_INTERVAL: Duration = Duration::from_hours(24);
+
+/// Cosine similarity above which memories are considered duplicates
+const DUPLICATE_SIMILARITY: f32 = 0.9;
+
+/// Memories merged at once at most, so that the summary stays focused
+const MAX_CLUSTER_SIZE: usize = 8;
+
+#[derive(Clone)]
+pub struct MemoryMaintenance {
+    vectordb: SharedVectorClient,
+    settings: DiscordSettings,
+    consolidator: Agent<CompletionModel>,
+}
+
+#[derive(Debug, Default)]
+struct MaintenanceReport {
+    decayed: usize,
+    consolidated: usize,
+}
+
+impl MemoryMaintenance {
+    pub fn new(
+        vectordb: SharedVectorClient,
+        settings: DiscordSettings,
+        openai_api_key: &str,
+    ) -> Result<Self, eyre::Error> {
+        let llm_client =
+            Client::new(openai_api_key).context("Failed to create OpenRouter client")?;
+        let consolidator = AgentBuilder::new(llm_client.completion_model(DEFAULT_MODEL))
+            .preamble(CONSOLIDATION_PROMPT)
+            .build();
+
+        Ok(Self {
+            vectordb,
+            settings,
+            consolidator,
+        })
+    }
+
+    /// Maintain all memories periodically, from one instance
+    pub fn start_worker(self, config: &ServerConfig) {
+        jobs::spawn_exclusive(config, "memory-maintenance", move || {
+            let maintenance = self.clone();
+            async move {
+                let mut interval = tokio::time::interval(MAINTENANCE_INTERVAL);
+                interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+                // The first tick completes immediately, before the settings are
+                // loaded and while the bot is busy starting up
+                interval.tick().await;
+
+                loop {
+                    interval.tick().await;
+
+                    if let Err(e) = maintenance.run().await {
+                        tracing::error!(?e, "Failed to maintain memories");
+                    }
+                }
+            }
+        });
+    }
+
+    async fn run(&self) -> Result<(), eyre::Error> {
+        let scopes = self
+            .vectordb
+            .list_scopes()
+            .await
+            .wrap_err("failed to list memory scopes")?;
+
+        for scope in scopes {
+            // One broken scope shouldn't stop the others from being maintained
+            match self.maintain(scope).await {
+                Ok(report) if report.decayed > 0 || report.consolidated > 0 => {
+                    tracing::info!(?scope, ?report, "Maintained memories");
+                }
+                Ok(_) => {}
+                Err(e) => tracing::error!(?e, ?scope, "Failed to maintain memories"),
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Days after which the memories of a scope decay, if they do at all
+    fn retention_days(&self, scope: MemoryScope) -> Option<u32> {
+        match scope {
+            MemoryScope::Channel(id) => {
+                self.settings
+                    .channel(ChannelId::new(id), None)
+                    .memory_retention_days
+            }
+            MemoryScope::Guild(id) => self.settings.guild_memory_retention_days(GuildId::new(id)),
+            MemoryScope::User(_) | MemoryScope::Global => self.settings.memory_retention_days(None),
+        }
+    }
+
+    async fn maintain(&self, scope: MemoryScope) -> Result<MaintenanceReport, eyre::Error> {
+        let mut report = MaintenanceReport::default();
+        let mut memories = self.vectordb.all_memories(scope).await?;
+
+        if let Some(days) = self.retention_days(scope) {
+            let cutoff = chrono::Utc::now() - chrono::Duration::days(i64::from(days));
+            // Memories without a timestamp predate it and are kept
+            let (stale, fresh): (Vec<_>, Vec<_>) = memories
+                .into_iter()
+                .partition(|m| m.timestamp.is_some_and(|t| t < cutoff));
+
+            if !stale.is_empty() {
+                let ids: Vec<&str> = stale.iter().map(|m| m.point_id.as_str()).collect();
+                self.vectordb.delete(scope, &ids).await?;
+                report.decayed = stale.len();
+            }
+            memories = fresh;
+        }
+
+        let embeddings: Vec<Vec<f32>> = memories.iter().map(|m| m.embedding.clone()).collect();
+        let clusters = tokio::task::spawn_blocking(move || {
+            duplicate_clusters(&embeddings, DUPLICATE_SIMILARITY)
+        })
+        .await?;
+
+        for cluster in clusters {
+            let mut cluster: Vec<&StoredMemory> =
+                cluster.iter().filter_map(|i| memories.get(*i)).collect();
+            cluster.sort_by_key(|m| m.timestamp);
+
+            match self.consolidate(scope, &cluster).await {
+                Ok(()) => report.consolidated += cluster.len(),
+                Err(e) => tracing::warn!(?e, ?scope, "Failed to consolidate memories"),
+            }
+        }
+
+        Ok(report)
+    }
+
+    /// Replace the memories, oldest first, with a summary of them
+    async fn consolidate(
+        &self,
+        scope: MemoryScope,
+        memories: &[&StoredMemory],
+    ) -> Result<(), eyre::Error> {
+        let prompt = memories
+            .iter()
+            .enumerate()
+            .map(|(i, m)| format!("{}. {}", i + 1, m.content))
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        let summary = self
+            .consolidator
+            .prompt(prompt)
+            .await
+            .wrap_err("failed to summarize memories")?;
+        let summary = summary.trim();
+        if summary.is_empty() {
+            eyre::bail!("the summary is empty");
+        }
+
+        // Keep the newest timestamp so that consolidating doesn't postpone decay
+        let timestamp = memories
+            .iter()
+            .filter_map(|m| m.timestamp)
+            .max()
+            .unwrap_or_else(chrono::Utc::now);
+        let metadata = serde_json::json!({
+            "timestamp": timestamp.to_rfc3339(),
+            "consolidated_from": memories.len(),
+        });
+
+        self.vectordb
+            .store(summary, scope, Some(metadata))
+            .await
+            .wrap_err("failed to store the consolidated memory")?;
+        let ids: Vec<&str> = memories.iter().map(|m| m.point_id.as_str()).collect();
+        self.vectordb
+            .delete(scope, &ids)
+            .await
+            .wrap_err("failed to delete the consolidated memories")?;
+
+        Ok(())
+    }
+}
+
+/// Groups of at least two embeddings that are all similar to the group's first
+/// one, each embedding in at most one group
+fn duplicate_clusters(embeddings: &[Vec<f32>], threshold: f32) -> Vec<Vec<usize>> {
+    let mut assigned = vec![false; embeddings.len()];
+    let mut clusters = Vec::new();
+
+    for (i, first) in embeddings.iter().enumerate() {
+        if assigned.get(i) == Some(&true) || first.is_empty() {
+            continue;
+        }
+
+        let cluster: Vec<usize> = std::iter::once(i)
+            .chain(
+                zip(&assigned, embeddings)
+                    .enumerate()
+                    .skip(i + 1)
+                    .filter(|(_, (assigned, embedding))| {
+                        !**assigned && cosine_similarity(first, embedding) >= threshold
+                    })
+                    .map(|(j, _)| j),
+            )
+            .take(MAX_CLUSTER_SIZE)
+            .collect();
+
+        if cluster.len() > 1 {
+            for j in &cluster {
+                if let Some(assigned) = assigned.get_mut(*j) {
+                    *assigned = true;
+                }
+            }
+            clusters.push(cluster);
+        }
+    }
+
+    clusters
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn duplicate_clusters_groups_similar_embeddings() {
+        let embeddings = vec![
+            vec![1.0, 0.0],
+            vec![0.0, 1.0],
+            vec![0.99, 0.05],
+            vec![-1.0, 0.0],
+            vec![0.05, 0.99],
+            vec![],
+        ];
+
+        assert_eq!(
+            duplicate_clusters(&embeddings, DUPLICATE_SIMILARITY),
+            vec![vec![0, 2], vec![1, 4]]
+        );
+    }
+}
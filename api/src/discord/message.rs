@@ -5,12 +5,15 @@ use futures::StreamExt;
 use rig::{
     OneOrMany,
     completion::Message as RigMessage,
-    message::{ImageDetail, ImageMediaType, MimeType, UserContent},
+    message::{AssistantContent, ImageDetail, ImageMediaType, MimeType, UserContent},
 };
 use scc::hash_map::OccupiedEntry;
-use serenity::all::{GuildId, Message};
+use serenity::all::{GuildId, Message, MessageId};
 
-use crate::discord::bot::Guild;
+use crate::{
+    discord::{attachments::Document, bot::Guild, links::LinkPreview},
+    outbound::HttpClient,
+};
 
 // Message queue item for debouncing
 #[derive(Debug, Clone)]
@@ -18,16 +21,29 @@ pub struct QueuedMessage {
     pub message: Message,
 }
 
+/// What was read from the attachments and links of a message
+#[derive(Default)]
+pub struct Enrichment {
+    pub documents: Vec<Document>,
+    pub links: Vec<LinkPreview>,
+}
+
 /// Helper function to format Discord message content with message ID, timestamp, and username
 /// with optional bot user ID for accurate mention detection
 fn format_message_content_with_bot_id(
     msg: &Message,
     bot_user_id: Option<serenity::model::id::UserId>,
     guild: &Option<OccupiedEntry<'_, GuildId, Guild>>,
+    links: &[LinkPreview],
 ) -> String {
     const MAX_REF_MSG_LEN: usize = 100; // Maximum length for referenced message preview
 
     let timestamp_str = msg.timestamp.to_rfc3339();
+    let edited = if msg.edited_timestamp.is_some() {
+        " (edited)"
+    } else {
+        ""
+    };
     let author_name = &msg.author.name;
     let message_id = msg.id.get();
 
@@ -88,6 +104,16 @@ fn format_message_content_with_bot_id(
         "None".to_string()
     };
 
+    let link_previews = if links.is_empty() {
+        "None".to_string()
+    } else {
+        links
+            .iter()
+            .map(|l| l.to_string())
+            .collect::<Vec<_>>()
+            .join("; ")
+    };
+
     // Build context block
     let context_block = format!(
         "\n
@@ -96,16 +122,18 @@ fn format_message_content_with_bot_id(
 * Mentions/Replies Bot: [{}]
 * Users mentioned in message: [{}]
 * User presence info: [{}]
+* Link previews: [{}]
 <</context>>",
-        referenced_message_preview, mentions_bot, user_mentions, user_presence
+        referenced_message_preview, mentions_bot, user_mentions, user_presence, link_previews
     );
 
     let base_message = if msg.content.is_empty() && !msg.attachments.is_empty() {
         // Handle attachments (images, files, etc.)
         format!(
-            "[Message ID: {}] [{}] {} (@{}): [Attachment: {}]",
+            "[Message ID: {}] [{}]{} {} (@{}): [Attachment: {}]",
             message_id,
             timestamp_str.unwrap_or_else(|| "N/A".to_string()),
+            edited,
             author_name,
             msg.author.id,
             msg.attachments
@@ -116,9 +144,10 @@ fn format_message_content_with_bot_id(
         )
     } else {
         format!(
-            "[Message ID: {}] [{}] {} (@{}): {}",
+            "[Message ID: {}] [{}]{} {} (@{}): {}",
             message_id,
             timestamp_str.unwrap_or_else(|| "N/A".to_string()),
+            edited,
             author_name,
             msg.author.id,
             msg.content
@@ -128,24 +157,56 @@ fn format_message_content_with_bot_id(
     format!("{}{}", base_message, context_block)
 }
 
-/// Helper function to convert a Discord message to a RigMessage
+/// The Discord message a converted message was made of, read from the ID it starts with
+pub fn message_id_of(message: &RigMessage) -> Option<MessageId> {
+    let text = match message {
+        RigMessage::User { content } => content.iter().find_map(|c| match c {
+            UserContent::Text(t) => Some(&t.text),
+            _ => None,
+        }),
+        RigMessage::Assistant { content, .. } => content.iter().find_map(|c| match c {
+            AssistantContent::Text(t) => Some(&t.text),
+            _ => None,
+        }),
+        RigMessage::System { .. } => None,
+    }?;
+
+    let (id, _) = text.strip_prefix("[Message ID: ")?.split_once(']')?;
+    id.parse().ok().map(MessageId::new)
+}
+
+/// What's left of a deleted message in the history
+pub fn deleted_message(message: &RigMessage, message_id: MessageId) -> RigMessage {
+    let tombstone = format!("[Message ID: {message_id}] [deleted]");
+    match message {
+        RigMessage::Assistant { .. } => RigMessage::assistant(tombstone),
+        _ => RigMessage::user(tombstone),
+    }
+}
+
+/// Helper function to convert a Discord message to a RigMessage, with what was read from its
+/// attachments and links
 pub async fn discord_message_to_rig_message(
+    http: &HttpClient,
     msg: &Message,
     bot_user_id: serenity::model::id::UserId,
     guild: &Option<OccupiedEntry<'_, GuildId, Guild>>,
+    enrichment: &Enrichment,
 ) -> RigMessage {
     let is_bot_message = msg.author.id == bot_user_id;
 
     if is_bot_message {
         // For bot messages, just use text content
-        let content = format_message_content_with_bot_id(msg, Some(bot_user_id), guild);
+        let content =
+            format_message_content_with_bot_id(msg, Some(bot_user_id), guild, &enrichment.links);
         RigMessage::assistant(content)
     } else {
         // For user messages, handle both text and images
         let mut content_parts = Vec::new();
 
         // Add text content first
-        let text_content = format_message_content_with_bot_id(msg, Some(bot_user_id), guild);
+        let text_content =
+            format_message_content_with_bot_id(msg, Some(bot_user_id), guild, &enrichment.links);
         content_parts.push(UserContent::text(text_content.clone()));
 
         // fetch images in batch
@@ -158,7 +219,7 @@ pub async fn discord_message_to_rig_message(
         });
 
         let images: Vec<_> = futures::stream::iter(images_iter.clone())
-            .then(|(url, _)| reqwest::get(url))
+            .then(|(url, _)| http.get(url).send())
             .filter_map(async |resp| match resp {
                 Ok(r) if r.status().is_success() => Some(r.bytes()),
                 Ok(r) => {
@@ -202,9 +263,31 @@ pub async fn discord_message_to_rig_message(
             }),
         );
 
+        content_parts.extend(
+            enrichment
+                .documents
+                .iter()
+                .map(|d| UserContent::text(d.render())),
+        );
+
         match OneOrMany::many(content_parts) {
             Ok(content) => RigMessage::from(content),
             Err(_) => RigMessage::user(text_content), // Fallback to text-only if content list is empty
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn converted_messages_are_found_by_their_id() {
+        let message = RigMessage::user("[Message ID: 42] [2026-10-17T00:00:00Z] alice (@1): hi");
+        assert_eq!(message_id_of(&message), Some(MessageId::new(42)));
+        assert_eq!(message_id_of(&RigMessage::user("[SYSTEM]: Continue")), None);
+
+        let deleted = deleted_message(&message, MessageId::new(42));
+        assert_eq!(message_id_of(&deleted), Some(MessageId::new(42)));
+    }
+}
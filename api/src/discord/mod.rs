@@ -1,8 +1,31 @@
 pub mod agent;
+pub mod archive;
+mod attachments;
 pub mod bot;
 mod channel;
+pub mod commands;
+pub mod concurrency;
 pub mod constants;
+mod context_budget;
+mod cooldown;
+pub mod direct_messages;
+pub mod feedback;
+mod links;
+pub mod memory_maintenance;
 pub mod message;
+pub mod moderation;
+pub mod onboarding;
+pub mod prompt;
+pub mod reminders;
+mod repetition;
+pub mod routes;
+pub mod settings;
+pub mod streaming;
+pub mod summaries;
+pub mod supervisor;
 pub mod tools;
+pub mod transcripts;
+pub mod usage;
+pub mod voice;
 
 pub use bot::DiscordEventHandler;
@@ -0,0 +1,222 @@
+use std::{collections::HashMap, sync::Arc};
+
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+use diesel_async::{AsyncPgConnection, RunQueryDsl, pooled_connection::deadpool::Pool};
+use eyre::Context as _;
+use serde_json::json;
+use serenity::all::{GuildId, UserId};
+
+use crate::{
+    config::{ModerationClassifier, ModerationConfig, ServerConfig},
+    discord::settings::to_db_id,
+    models::discord::NewDiscordModerationAction,
+    outbound::HttpClient,
+    schema::discord_moderation_actions,
+};
+
+pub const ACTION_FLAG: &str = "flag";
+pub const ACTION_WARN: &str = "warn";
+pub const ACTION_TIMEOUT: &str = "timeout";
+pub const ACTION_DELETE: &str = "delete";
+
+/// Categories of a flagged message whose scores reached the threshold
+#[derive(Debug, Clone, PartialEq)]
+pub struct Verdict {
+    pub categories: Vec<(String, f64)>,
+}
+
+impl Verdict {
+    /// e.g. `harassment (0.93), hate (0.85)`
+    pub fn summary(&self) -> String {
+        self.categories
+            .iter()
+            .map(|(category, score)| format!("{category} ({score:.2})"))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    pub fn to_json(&self) -> serde_json::Value {
+        json!(
+            self.categories
+                .iter()
+                .cloned()
+                .collect::<HashMap<String, f64>>()
+        )
+    }
+}
+
+struct Inner {
+    diesel: Pool<AsyncPgConnection>,
+    client: HttpClient,
+    config: Option<ModerationConfig>,
+}
+
+/// Classifies the incoming messages and keeps the audit trail of the
+/// moderation actions. The actions themselves are taken by the agent.
+#[derive(Clone)]
+pub struct Moderation(Arc<Inner>);
+
+impl Moderation {
+    pub fn new(config: &ServerConfig, diesel: Pool<AsyncPgConnection>, client: HttpClient) -> Self {
+        Self(Arc::new(Inner {
+            diesel,
+            client,
+            config: config.discord_moderation.clone(),
+        }))
+    }
+
+    /// Classify a message, `None` if it's not flagged or no classifier is
+    /// configured. The threshold overrides the configured one.
+    pub async fn check(
+        &self,
+        text: &str,
+        threshold: Option<f64>,
+    ) -> Result<Option<Verdict>, eyre::Error> {
+        let Some(config) = &self.0.config else {
+            return Ok(None);
+        };
+
+        if text.trim().is_empty() {
+            return Ok(None);
+        }
+
+        let scores = match &config.classifier {
+            ModerationClassifier::OpenAi {
+                url,
+                api_key,
+                model,
+            } => {
+                self.openai_scores(url, api_key.as_deref(), model, text)
+                    .await?
+            }
+            ModerationClassifier::Local { blocklist } => local_scores(blocklist, text),
+        };
+
+        let threshold = threshold.unwrap_or(config.threshold);
+        let mut categories = scores
+            .into_iter()
+            .filter(|(_, score)| *score >= threshold)
+            .collect::<Vec<_>>();
+        categories.sort_by(|a, b| b.1.total_cmp(&a.1));
+
+        Ok((!categories.is_empty()).then_some(Verdict { categories }))
+    }
+
+    async fn openai_scores(
+        &self,
+        url: &str,
+        api_key: Option<&str>,
+        model: &str,
+        text: &str,
+    ) -> Result<Vec<(String, f64)>, eyre::Error> {
+        #[derive(serde::Deserialize)]
+        struct ModerationResponse {
+            results: Vec<ModerationResult>,
+        }
+
+        #[derive(serde::Deserialize)]
+        struct ModerationResult {
+            category_scores: HashMap<String, f64>,
+        }
+
+        let mut request = self
+            .0
+            .client
+            .post(url)
+            .json(&json!({ "model": model, "input": text }));
+        if let Some(api_key) = api_key {
+            request = request.bearer_auth(api_key);
+        }
+
+        let response: ModerationResponse = request
+            .send()
+            .await
+            .wrap_err("failed to send moderation request")?
+            .error_for_status()
+            .wrap_err("moderation request failed")?
+            .json()
+            .await
+            .wrap_err("failed to parse moderation response")?;
+
+        Ok(response
+            .results
+            .into_iter()
+            .flat_map(|r| r.category_scores)
+            .collect())
+    }
+
+    async fn conn(
+        &self,
+    ) -> Result<diesel_async::pooled_connection::deadpool::Object<AsyncPgConnection>, eyre::Error>
+    {
+        self.0
+            .diesel
+            .get()
+            .await
+            .wrap_err("could not get diesel pool conn")
+    }
+
+    /// Add an entry to the audit trail
+    pub async fn audit(&self, action: NewDiscordModerationAction) -> Result<(), eyre::Error> {
+        let mut conn = self.conn().await?;
+
+        diesel::insert_into(discord_moderation_actions::table)
+            .values(&action)
+            .execute(&mut conn)
+            .await
+            .wrap_err("failed to record Discord moderation action")?;
+
+        Ok(())
+    }
+
+    /// Number of warnings a user got in a guild since the given time
+    pub async fn warnings(
+        &self,
+        guild_id: GuildId,
+        user_id: UserId,
+        since: NaiveDateTime,
+    ) -> Result<i64, eyre::Error> {
+        let mut conn = self.conn().await?;
+
+        discord_moderation_actions::table
+            .filter(discord_moderation_actions::guild_id.eq(to_db_id(guild_id.get())))
+            .filter(discord_moderation_actions::user_id.eq(to_db_id(user_id.get())))
+            .filter(discord_moderation_actions::action.eq(ACTION_WARN))
+            .filter(discord_moderation_actions::created_at.ge(since))
+            .count()
+            .get_result(&mut conn)
+            .await
+            .wrap_err("failed to count Discord moderation warnings")
+    }
+}
+
+/// Scores 1 in the `blocklist` category if the text contains a blocked word
+fn local_scores(blocklist: &[String], text: &str) -> Vec<(String, f64)> {
+    let text = text.to_lowercase();
+    let blocked = text
+        .split(|c: char| !c.is_alphanumeric())
+        .any(|word| blocklist.iter().any(|b| b == word))
+        // Phrases can't be matched word by word
+        || blocklist
+            .iter()
+            .filter(|b| b.contains(' '))
+            .any(|b| text.contains(b.as_str()));
+
+    vec![("blocklist".to_string(), if blocked { 1.0 } else { 0.0 })]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn local_scores_match_whole_words_and_phrases() {
+        let blocklist = vec!["scam".to_string(), "free nitro".to_string()];
+
+        assert_eq!(local_scores(&blocklist, "this is a SCAM!")[0].1, 1.0);
+        assert_eq!(local_scores(&blocklist, "get Free  Nitro")[0].1, 0.0);
+        assert_eq!(local_scores(&blocklist, "get free nitro here")[0].1, 1.0);
+        assert_eq!(local_scores(&blocklist, "scampi for dinner")[0].1, 0.0);
+    }
+}
@@ -0,0 +1,107 @@
+//! Setting up the servers the bot is added to: the default settings of the
+//! server are stored, the slash commands registered, and whoever added the
+//! bot is told how to enable it since it stays quiet until then.
+
+use serenity::all::{Context, CreateMessage, Guild, GuildId, UserId};
+use serenity::model::guild::audit_log::{Action, AuditLogEntry, MemberAction};
+
+use crate::{
+    discord::{
+        commands::{SETTINGS_COMMAND_PREFIX, SETTINGS_SLASH_COMMAND, settings_slash_command},
+        settings::{DiscordSettings, to_db_id},
+    },
+    models::discord::NewDiscordGuildSettings,
+};
+
+/// Recent additions of bots looked through for the one of this bot
+const AUDIT_LOG_ENTRIES: u8 = 10;
+
+/// Registers the slash commands of the guild, done whenever the guild is
+/// seen since the registration replaces the previous one
+pub async fn register_commands(ctx: &Context, guild_id: GuildId) -> Result<(), eyre::Error> {
+    guild_id
+        .set_commands(&ctx.http, vec![settings_slash_command()])
+        .await?;
+    Ok(())
+}
+
+/// Stores the default settings of a guild the bot was just added to and sends
+/// the setup instructions to whoever added it
+pub async fn onboard(
+    ctx: &Context,
+    guild: &Guild,
+    settings: &DiscordSettings,
+) -> Result<(), eyre::Error> {
+    // A guild the bot is added back to keeps its settings
+    if settings.stored_guild(guild.id).is_none() {
+        settings
+            .upsert_guild(NewDiscordGuildSettings {
+                guild_id: to_db_id(guild.id.get()),
+                mention_only: None,
+                persona: None,
+                model: None,
+                streaming: None,
+                allowed_tools: None,
+                memory_retention_days: None,
+                persona_profile: None,
+                moderation_enforcement: None,
+                moderation_threshold: None,
+            })
+            .await?;
+    }
+
+    let message = CreateMessage::new().content(welcome(&guild.name));
+    let inviter = inviter(ctx, guild).await;
+    match inviter.direct_message(ctx, message.clone()).await {
+        Ok(_) => return Ok(()),
+        Err(err) => tracing::info!(
+            ?err,
+            guild_id = guild.id.get(),
+            "Could not DM the setup instructions, posting them in the server"
+        ),
+    }
+
+    let Some(channel_id) = guild.system_channel_id else {
+        eyre::bail!("the inviter has their DMs closed and the server has no system channel");
+    };
+    channel_id.send_message(&ctx.http, message).await?;
+    Ok(())
+}
+
+/// Who added the bot according to the audit log, the owner of the guild if
+/// the bot can't read it
+async fn inviter(ctx: &Context, guild: &Guild) -> UserId {
+    let bot_id = ctx.cache.current_user().id;
+    let is_bot = |entry: &AuditLogEntry| entry.target_id.is_some_and(|id| id.get() == bot_id.get());
+
+    guild
+        .id
+        .audit_logs(
+            &ctx.http,
+            Some(Action::Member(MemberAction::BotAdd)),
+            None,
+            None,
+            Some(AUDIT_LOG_ENTRIES),
+        )
+        .await
+        .inspect_err(|err| tracing::debug!(?err, "Could not read the audit log"))
+        .ok()
+        .and_then(|logs| logs.entries.into_iter().find(is_bot))
+        .map(|entry| entry.user_id)
+        .unwrap_or(guild.owner_id)
+}
+
+fn welcome(guild_name: &str) -> String {
+    let slash = format!("/{SETTINGS_SLASH_COMMAND} command:");
+    format!(
+        "👋 Thanks for adding me to **{guild_name}**!
+
+I stay quiet until I'm enabled in a channel. Run `{slash}enable` (or `{SETTINGS_COMMAND_PREFIX} enable`) in each channel I should talk in, it takes the Manage Server permission. Then in that channel:
+- `{slash}settings` shows its settings
+- `{slash}mention-only on` makes me answer only when mentioned
+- `{slash}profiles` lists the persona profiles, `{slash}profile <name>` picks one
+- `{slash}moderation on` lets me warn, time out and delete messages in the server
+
+`/{SETTINGS_SLASH_COMMAND}` without a command lists all of them."
+    )
+}
@@ -0,0 +1,203 @@
+use std::{num::NonZeroU64, sync::Arc, time::Duration};
+
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+use diesel_async::{AsyncPgConnection, RunQueryDsl, pooled_connection::deadpool::Pool};
+use eyre::Context as _;
+use serenity::all::{ChannelId, CreateAllowedMentions, CreateMessage, Http, UserId};
+
+use crate::{
+    config::ServerConfig,
+    discord::settings::to_db_id,
+    jobs,
+    models::discord::{DiscordReminder, NewDiscordReminder},
+    schema::discord_reminders,
+};
+
+/// How often the due reminders are checked
+const DELIVERY_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Reminders delivered per check, the rest are picked up by the next one
+const DELIVERY_BATCH_SIZE: i64 = 50;
+
+/// Pending reminders a user may have per channel, to keep the agent from
+/// spamming the table
+pub const MAX_PENDING_PER_USER: i64 = 25;
+
+/// Reminders scheduled by the agent, persisted so that they survive restarts
+#[derive(Clone)]
+pub struct Reminders {
+    diesel: Pool<AsyncPgConnection>,
+}
+
+impl Reminders {
+    pub fn new(diesel: Pool<AsyncPgConnection>) -> Self {
+        Self { diesel }
+    }
+
+    async fn conn(
+        &self,
+    ) -> Result<diesel_async::pooled_connection::deadpool::Object<AsyncPgConnection>, eyre::Error>
+    {
+        self.diesel
+            .get()
+            .await
+            .wrap_err("could not get diesel pool conn")
+    }
+
+    pub async fn schedule(
+        &self,
+        channel_id: ChannelId,
+        user_id: UserId,
+        message: String,
+        remind_at: NaiveDateTime,
+    ) -> Result<DiscordReminder, eyre::Error> {
+        let mut conn = self.conn().await?;
+
+        let pending: i64 = discord_reminders::table
+            .filter(discord_reminders::channel_id.eq(to_db_id(channel_id.get())))
+            .filter(discord_reminders::user_id.eq(to_db_id(user_id.get())))
+            .filter(discord_reminders::delivered_at.is_null())
+            .count()
+            .get_result(&mut conn)
+            .await
+            .wrap_err("failed to count pending reminders")?;
+
+        if pending >= MAX_PENDING_PER_USER {
+            eyre::bail!(
+                "the user already has {pending} pending reminders in this channel, cancel some first"
+            );
+        }
+
+        diesel::insert_into(discord_reminders::table)
+            .values(&NewDiscordReminder {
+                channel_id: to_db_id(channel_id.get()),
+                user_id: to_db_id(user_id.get()),
+                message,
+                remind_at,
+            })
+            .returning(DiscordReminder::as_returning())
+            .get_result(&mut conn)
+            .await
+            .wrap_err("failed to schedule reminder")
+    }
+
+    /// Pending reminders of a channel, optionally of a single user, soonest first
+    pub async fn pending(
+        &self,
+        channel_id: ChannelId,
+        user_id: Option<UserId>,
+    ) -> Result<Vec<DiscordReminder>, eyre::Error> {
+        let mut conn = self.conn().await?;
+
+        let mut query = discord_reminders::table
+            .filter(discord_reminders::channel_id.eq(to_db_id(channel_id.get())))
+            .filter(discord_reminders::delivered_at.is_null())
+            .select(DiscordReminder::as_select())
+            .order(discord_reminders::remind_at)
+            .into_boxed();
+
+        if let Some(user_id) = user_id {
+            query = query.filter(discord_reminders::user_id.eq(to_db_id(user_id.get())));
+        }
+
+        query
+            .load(&mut conn)
+            .await
+            .wrap_err("failed to load pending reminders")
+    }
+
+    /// Cancel a pending reminder of a channel. Returns whether there was one.
+    pub async fn cancel(&self, channel_id: ChannelId, id: i32) -> Result<bool, eyre::Error> {
+        let mut conn = self.conn().await?;
+
+        let deleted = diesel::delete(
+            discord_reminders::table
+                .filter(discord_reminders::id.eq(id))
+                .filter(discord_reminders::channel_id.eq(to_db_id(channel_id.get())))
+                .filter(discord_reminders::delivered_at.is_null()),
+        )
+        .execute(&mut conn)
+        .await
+        .wrap_err("failed to cancel reminder")?;
+
+        Ok(deleted > 0)
+    }
+
+    /// Deliver the due reminders periodically from one instance, including the
+    /// ones that came due while the bot was down
+    pub fn start_delivery_worker(&self, config: &ServerConfig) {
+        let Some(token) = &config.discord_token else {
+            return;
+        };
+        let reminders = self.clone();
+        let http = Arc::new(Http::new(token));
+        jobs::spawn_exclusive(config, "discord-reminders", move || {
+            let (reminders, http) = (reminders.clone(), http.clone());
+            async move {
+                let mut interval = tokio::time::interval(DELIVERY_INTERVAL);
+                interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+                loop {
+                    interval.tick().await;
+
+                    if let Err(e) = reminders.deliver_due(&http).await {
+                        tracing::error!(?e, "Failed to deliver reminders");
+                    }
+                }
+            }
+        });
+    }
+
+    async fn deliver_due(&self, http: &Http) -> Result<(), eyre::Error> {
+        let mut conn = self.conn().await?;
+
+        let due = discord_reminders::table
+            .filter(discord_reminders::delivered_at.is_null())
+            .filter(discord_reminders::remind_at.le(diesel::dsl::now))
+            .select(DiscordReminder::as_select())
+            .order(discord_reminders::remind_at)
+            .limit(DELIVERY_BATCH_SIZE)
+            .load(&mut conn)
+            .await
+            .wrap_err("failed to load due reminders")?;
+
+        let id = |id: i64| u64::try_from(id).ok().and_then(NonZeroU64::new);
+        for reminder in due {
+            let target = id(reminder.channel_id)
+                .map(ChannelId::from)
+                .zip(id(reminder.user_id).map(UserId::from));
+
+            let result = match target {
+                Some((channel_id, user_id)) => channel_id
+                    .send_message(
+                        http,
+                        CreateMessage::new()
+                            .content(format!("⏰ <@{user_id}> {}", reminder.message))
+                            // Only ping the user the reminder is for
+                            .allowed_mentions(CreateAllowedMentions::new().users([user_id])),
+                    )
+                    .await
+                    .map(|_| ())
+                    .map_err(eyre::Error::new),
+                None => Err(eyre::eyre!(
+                    "the reminder has an invalid channel or user ID"
+                )),
+            };
+
+            // Mark as delivered even if sending failed, e.g. because the
+            // channel is gone, so that it's not retried forever
+            if let Err(e) = result {
+                tracing::error!(?e, reminder_id = reminder.id, "Failed to send reminder");
+            }
+
+            diesel::update(discord_reminders::table.find(reminder.id))
+                .set(discord_reminders::delivered_at.eq(diesel::dsl::now))
+                .execute(&mut conn)
+                .await
+                .wrap_err("failed to mark reminder as delivered")?;
+        }
+
+        Ok(())
+    }
+}
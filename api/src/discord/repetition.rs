@@ -0,0 +1,147 @@
+//! Keeps the bot from repeating itself. The replies it's about to send are
+//! compared with its last ones in the channel by their embeddings, and the
+//! ones too close to a recent reply are sent back to the agent to say
+//! something else.
+
+use std::{
+    collections::{HashMap, VecDeque},
+    fmt,
+    sync::{Arc, Mutex},
+};
+
+use serenity::all::{ChannelId, GetMessages, Http, UserId};
+
+use crate::embedding::{Embedder, cosine_similarity};
+
+/// Replies of the bot compared with in each channel
+const RECENT_REPLIES: usize = 10;
+/// Messages looked through for the replies of the bot the first time a
+/// channel is checked
+const SEEDED_MESSAGES: u8 = 50;
+const REPEAT_SIMILARITY: f32 = 0.9;
+/// Short replies like "lol" or "thanks" are fine to repeat
+const MIN_COMPARED_CHARS: usize = 20;
+
+pub struct Reply {
+    content: String,
+    embedding: Vec<f32>,
+}
+
+pub enum Check {
+    /// Too close to this recent reply
+    Repeated(String),
+    /// To be recorded once sent, unset if it's not compared
+    New(Option<Reply>),
+}
+
+/// The recent replies of the bot in each channel, shared by the sessions
+#[derive(Clone)]
+pub struct RecentReplies {
+    embedder: Embedder,
+    channels: Arc<Mutex<HashMap<ChannelId, VecDeque<Reply>>>>,
+}
+
+impl RecentReplies {
+    pub fn new(embedder: Embedder) -> Self {
+        Self {
+            embedder,
+            channels: Arc::default(),
+        }
+    }
+
+    /// Compares a reply with the recent ones of the bot in the channel, which
+    /// are read from the channel the first time
+    pub async fn check(
+        &self,
+        http: &Http,
+        bot_user_id: UserId,
+        channel_id: ChannelId,
+        content: &str,
+    ) -> Result<Check, eyre::Error> {
+        if content.trim().chars().count() < MIN_COMPARED_CHARS {
+            return Ok(Check::New(None));
+        }
+
+        if !self.lock().contains_key(&channel_id) {
+            let seeded = self.seed(http, bot_user_id, channel_id).await?;
+            self.lock().entry(channel_id).or_insert(seeded);
+        }
+
+        let embedding = self
+            .embed(vec![content.to_string()])
+            .await?
+            .pop()
+            .ok_or_else(|| eyre::eyre!("no embedding generated"))?;
+        let repeated = self.lock().get(&channel_id).and_then(|replies| {
+            replies
+                .iter()
+                .map(|r| (r, cosine_similarity(&r.embedding, &embedding)))
+                .filter(|(_, similarity)| *similarity >= REPEAT_SIMILARITY)
+                .max_by(|(_, a), (_, b)| a.total_cmp(b))
+                .map(|(r, _)| r.content.clone())
+        });
+
+        Ok(match repeated {
+            Some(previous) => Check::Repeated(previous),
+            None => Check::New(Some(Reply {
+                content: content.to_string(),
+                embedding,
+            })),
+        })
+    }
+
+    pub fn record(&self, channel_id: ChannelId, reply: Reply) {
+        let mut channels = self.lock();
+        let replies = channels.entry(channel_id).or_default();
+        replies.push_back(reply);
+        if replies.len() > RECENT_REPLIES {
+            replies.pop_front();
+        }
+    }
+
+    async fn seed(
+        &self,
+        http: &Http,
+        bot_user_id: UserId,
+        channel_id: ChannelId,
+    ) -> Result<VecDeque<Reply>, eyre::Error> {
+        let mut contents: Vec<_> = channel_id
+            .messages(http, GetMessages::new().limit(SEEDED_MESSAGES))
+            .await?
+            .into_iter()
+            .filter(|m| m.author.id == bot_user_id)
+            .map(|m| m.content)
+            .filter(|c| c.trim().chars().count() >= MIN_COMPARED_CHARS)
+            .take(RECENT_REPLIES)
+            .collect();
+        // The messages come newest first
+        contents.reverse();
+        if contents.is_empty() {
+            return Ok(VecDeque::new());
+        }
+
+        let embeddings = self.embed(contents.clone()).await?;
+        Ok(contents
+            .into_iter()
+            .zip(embeddings)
+            .map(|(content, embedding)| Reply { content, embedding })
+            .collect())
+    }
+
+    async fn embed(&self, texts: Vec<String>) -> Result<Vec<Vec<f32>>, eyre::Error> {
+        Ok(self.embedder.embed(texts).await?)
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<ChannelId, VecDeque<Reply>>> {
+        self.channels.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+// The embedder isn't `Debug`, which the send message tool needs
+impl fmt::Debug for RecentReplies {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RecentReplies")
+            .field("channels", &self.lock().len())
+            .finish()
+    }
+}
@@ -0,0 +1,881 @@
+use axum::{
+    Json, Router,
+    extract::{DefaultBodyLimit, Path, Query, State},
+    http::{StatusCode, header},
+    response::IntoResponse,
+    routing::{get, post, put},
+};
+use diesel::prelude::*;
+use diesel_async::RunQueryDsl;
+use eyre::Context as _;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    App,
+    config::BodyLimits,
+    discord::{
+        archive::{DailyVolume, ResponseRate, Topic},
+        concurrency::LimiterStats,
+        prompt::{PersonaProfile, SafetyLevel, SlangLevel},
+        summaries::SummarySchedule,
+        supervisor::ConnectionReport,
+        tools::{MemoryRecord, MemoryScope, SharedVectorClient},
+        transcripts::{AgentTranscript, Replay, TranscriptSummary},
+    },
+    error::AppError,
+    identity::AuthUser,
+    models::discord::{
+        DiscordChannelSettings, DiscordChannelSummary, DiscordGuildSettings,
+        DiscordMessageFeedback, DiscordModerationAction, DiscordPersona, DiscordTokenUsage,
+        NewDiscordChannelSettings, NewDiscordGuildSettings, NewDiscordPersona,
+    },
+    schema::{discord_message_feedback, discord_moderation_actions, discord_token_usage},
+};
+
+pub fn route(limits: &BodyLimits) -> Router<App> {
+    Router::<App>::new()
+        .route("/discord/settings", get(get_settings))
+        .route(
+            "/discord/settings/guilds/{guild_id}",
+            put(put_guild_settings).delete(delete_guild_settings),
+        )
+        .route(
+            "/discord/settings/channels/{channel_id}",
+            put(put_channel_settings).delete(delete_channel_settings),
+        )
+        .route("/discord/personas", get(get_personas))
+        .route(
+            "/discord/personas/{name}",
+            put(put_persona).delete(delete_persona),
+        )
+        .route("/admin/discord/usage", get(get_usage))
+        .route("/admin/discord/feedback", get(get_feedback))
+        .route("/admin/discord/moderation", get(get_moderation_actions))
+        .route("/admin/discord/summaries", get(get_summaries))
+        .route("/admin/discord/concurrency", get(get_concurrency))
+        .route("/admin/discord/status", get(get_connection_status))
+        .route("/admin/discord/analytics/volume", get(get_message_volume))
+        .route(
+            "/admin/discord/analytics/responses",
+            get(get_response_rates),
+        )
+        .route("/admin/discord/analytics/topics", get(get_top_topics))
+        .route("/admin/discord/transcripts", get(get_transcripts))
+        .route("/admin/discord/transcripts/{id}", get(get_transcript))
+        .route(
+            "/admin/discord/transcripts/{id}/replay",
+            post(replay_transcript),
+        )
+        .route("/admin/discord/memories", get(get_memory_collections))
+        .route("/admin/discord/memories/{collection}", get(get_memories))
+        .route(
+            "/admin/discord/memories/{collection}/{id}",
+            put(put_memory).delete(delete_memory),
+        )
+        .route(
+            "/admin/discord/memories/{collection}/export",
+            get(export_memories),
+        )
+        .route(
+            "/admin/discord/memories/{collection}/import",
+            post(import_memories).layer(DefaultBodyLimit::max(limits.imports)),
+        )
+}
+
+#[derive(Serialize)]
+struct SettingsResponse {
+    guilds: Vec<DiscordGuildSettings>,
+    channels: Vec<DiscordChannelSettings>,
+}
+
+fn ensure_owner(ctx: &App, identity_id: i32) -> Result<(), AppError> {
+    if identity_id != ctx.config.owner_identity_id {
+        return Err(("Not permitted", StatusCode::FORBIDDEN).into());
+    }
+    Ok(())
+}
+
+async fn get_settings(
+    State(ctx): State<App>,
+    AuthUser(i): AuthUser,
+) -> Result<Json<SettingsResponse>, AppError> {
+    ensure_owner(&ctx, i.id)?;
+
+    Ok(Json(SettingsResponse {
+        guilds: ctx.discord_settings.guild_settings(),
+        channels: ctx.discord_settings.channel_settings(),
+    }))
+}
+
+async fn put_guild_settings(
+    State(ctx): State<App>,
+    AuthUser(i): AuthUser,
+    Path(guild_id): Path<i64>,
+    crate::json::Json(mut settings): crate::json::Json<NewDiscordGuildSettings>,
+) -> Result<Json<DiscordGuildSettings>, AppError> {
+    ensure_owner(&ctx, i.id)?;
+
+    settings.guild_id = guild_id;
+
+    Ok(Json(ctx.discord_settings.upsert_guild(settings).await?))
+}
+
+async fn delete_guild_settings(
+    State(ctx): State<App>,
+    AuthUser(i): AuthUser,
+    Path(guild_id): Path<i64>,
+) -> Result<StatusCode, AppError> {
+    ensure_owner(&ctx, i.id)?;
+
+    if !ctx.discord_settings.delete_guild(guild_id).await? {
+        return Err(("Guild settings not found", StatusCode::NOT_FOUND).into());
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn put_channel_settings(
+    State(ctx): State<App>,
+    AuthUser(i): AuthUser,
+    Path(channel_id): Path<i64>,
+    crate::json::Json(mut settings): crate::json::Json<NewDiscordChannelSettings>,
+) -> Result<Json<DiscordChannelSettings>, AppError> {
+    ensure_owner(&ctx, i.id)?;
+
+    if settings
+        .summary_schedule
+        .as_deref()
+        .is_some_and(|s| SummarySchedule::parse(s).is_none())
+    {
+        return Err((
+            "Summary schedule must be daily or weekly",
+            StatusCode::BAD_REQUEST,
+        )
+            .into());
+    }
+
+    settings.channel_id = channel_id;
+
+    Ok(Json(ctx.discord_settings.upsert_channel(settings).await?))
+}
+
+async fn delete_channel_settings(
+    State(ctx): State<App>,
+    AuthUser(i): AuthUser,
+    Path(channel_id): Path<i64>,
+) -> Result<StatusCode, AppError> {
+    ensure_owner(&ctx, i.id)?;
+
+    if !ctx.discord_settings.delete_channel(channel_id).await? {
+        return Err(("Channel settings not found", StatusCode::NOT_FOUND).into());
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn get_personas(
+    State(ctx): State<App>,
+    AuthUser(i): AuthUser,
+) -> Result<Json<Vec<PersonaProfile>>, AppError> {
+    ensure_owner(&ctx, i.id)?;
+
+    Ok(Json(ctx.discord_settings.profiles()))
+}
+
+async fn put_persona(
+    State(ctx): State<App>,
+    AuthUser(i): AuthUser,
+    Path(name): Path<String>,
+    crate::json::Json(mut persona): crate::json::Json<NewDiscordPersona>,
+) -> Result<Json<DiscordPersona>, AppError> {
+    ensure_owner(&ctx, i.id)?;
+
+    if SlangLevel::parse(&persona.slang).is_none() {
+        return Err((
+            "Slang must be none, light or heavy",
+            StatusCode::BAD_REQUEST,
+        )
+            .into());
+    }
+    if SafetyLevel::parse(&persona.safety).is_none() {
+        return Err((
+            "Safety must be strict, standard or edgy",
+            StatusCode::BAD_REQUEST,
+        )
+            .into());
+    }
+
+    persona.name = name;
+
+    Ok(Json(ctx.discord_settings.upsert_persona(persona).await?))
+}
+
+async fn delete_persona(
+    State(ctx): State<App>,
+    AuthUser(i): AuthUser,
+    Path(name): Path<String>,
+) -> Result<StatusCode, AppError> {
+    ensure_owner(&ctx, i.id)?;
+
+    if !ctx.discord_settings.delete_persona(&name).await? {
+        return Err(("Persona not found", StatusCode::NOT_FOUND).into());
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[derive(Deserialize)]
+struct UsageQuery {
+    /// Number of days to look back, including today
+    days: Option<u32>,
+    channel_id: Option<i64>,
+}
+
+#[derive(Serialize)]
+struct UsageEntry {
+    #[serde(flatten)]
+    usage: DiscordTokenUsage,
+    estimated_cost_usd: Option<f64>,
+}
+
+#[derive(Serialize)]
+struct UsageResponse {
+    daily_token_budget: Option<u64>,
+    input_tokens: i64,
+    output_tokens: i64,
+    estimated_cost_usd: Option<f64>,
+    entries: Vec<UsageEntry>,
+}
+
+async fn get_usage(
+    State(ctx): State<App>,
+    AuthUser(i): AuthUser,
+    Query(q): Query<UsageQuery>,
+) -> Result<Json<UsageResponse>, AppError> {
+    ensure_owner(&ctx, i.id)?;
+
+    let days = q.days.unwrap_or(30).clamp(1, 366);
+    let since = chrono::Utc::now().date_naive() - chrono::Days::new(u64::from(days - 1));
+
+    let mut conn = ctx.diesel.get().await?;
+
+    let mut query = discord_token_usage::table
+        .filter(discord_token_usage::day.ge(since))
+        .select(DiscordTokenUsage::as_select())
+        .order((
+            discord_token_usage::day.desc(),
+            discord_token_usage::channel_id,
+            discord_token_usage::model,
+        ))
+        .into_boxed();
+
+    if let Some(channel_id) = q.channel_id {
+        query = query.filter(discord_token_usage::channel_id.eq(channel_id));
+    }
+
+    let rows = query.load(&mut conn).await?;
+
+    let prices = ctx.config.discord_token_prices;
+    let cost = |input: i64, output: i64| {
+        prices.map(|p| {
+            (input as f64 * p.input_per_million + output as f64 * p.output_per_million)
+                / 1_000_000f64
+        })
+    };
+
+    let input_tokens = rows.iter().map(|r| r.input_tokens).sum();
+    let output_tokens = rows.iter().map(|r| r.output_tokens).sum();
+
+    Ok(Json(UsageResponse {
+        daily_token_budget: ctx.config.discord_daily_token_budget,
+        input_tokens,
+        output_tokens,
+        estimated_cost_usd: cost(input_tokens, output_tokens),
+        entries: rows
+            .into_iter()
+            .map(|usage| UsageEntry {
+                estimated_cost_usd: cost(usage.input_tokens, usage.output_tokens),
+                usage,
+            })
+            .collect(),
+    }))
+}
+
+#[derive(Deserialize)]
+struct FeedbackQuery {
+    /// Number of days to look back, including today
+    days: Option<u32>,
+    channel_id: Option<i64>,
+}
+
+#[derive(Serialize, Default)]
+struct FeedbackStats {
+    positive: usize,
+    negative: usize,
+}
+
+#[derive(Serialize)]
+struct ChannelFeedbackStats {
+    channel_id: i64,
+    #[serde(flatten)]
+    stats: FeedbackStats,
+}
+
+#[derive(Serialize)]
+struct FeedbackResponse {
+    #[serde(flatten)]
+    total: FeedbackStats,
+    channels: Vec<ChannelFeedbackStats>,
+    /// Most recent first
+    recent_negative: Vec<DiscordMessageFeedback>,
+}
+
+async fn get_feedback(
+    State(ctx): State<App>,
+    AuthUser(i): AuthUser,
+    Query(q): Query<FeedbackQuery>,
+) -> Result<Json<FeedbackResponse>, AppError> {
+    const MAX_RECENT_NEGATIVE: usize = 20;
+
+    ensure_owner(&ctx, i.id)?;
+
+    let days = q.days.unwrap_or(30).clamp(1, 366);
+    let since = (chrono::Utc::now() - chrono::Days::new(u64::from(days))).naive_utc();
+
+    let mut conn = ctx.diesel.get().await?;
+
+    let mut query = discord_message_feedback::table
+        .filter(discord_message_feedback::created_at.ge(since))
+        .select(DiscordMessageFeedback::as_select())
+        .order(discord_message_feedback::created_at.desc())
+        .into_boxed();
+
+    if let Some(channel_id) = q.channel_id {
+        query = query.filter(discord_message_feedback::channel_id.eq(channel_id));
+    }
+
+    let rows = query.load(&mut conn).await?;
+
+    let mut total = FeedbackStats::default();
+    let mut channels = std::collections::BTreeMap::<i64, FeedbackStats>::new();
+    for row in &rows {
+        let channel = channels.entry(row.channel_id).or_default();
+        if row.positive {
+            total.positive += 1;
+            channel.positive += 1;
+        } else {
+            total.negative += 1;
+            channel.negative += 1;
+        }
+    }
+
+    Ok(Json(FeedbackResponse {
+        total,
+        channels: channels
+            .into_iter()
+            .map(|(channel_id, stats)| ChannelFeedbackStats { channel_id, stats })
+            .collect(),
+        recent_negative: rows
+            .into_iter()
+            .filter(|r| !r.positive)
+            .take(MAX_RECENT_NEGATIVE)
+            .collect(),
+    }))
+}
+
+#[derive(Deserialize)]
+struct ModerationQuery {
+    /// Number of days to look back
+    days: Option<u32>,
+    guild_id: Option<i64>,
+    user_id: Option<i64>,
+}
+
+/// The audit trail of the flagged messages and moderation actions, most
+/// recent first
+async fn get_moderation_actions(
+    State(ctx): State<App>,
+    AuthUser(i): AuthUser,
+    Query(q): Query<ModerationQuery>,
+) -> Result<Json<Vec<DiscordModerationAction>>, AppError> {
+    const MAX_ACTIONS: i64 = 500;
+
+    ensure_owner(&ctx, i.id)?;
+
+    let days = q.days.unwrap_or(30).clamp(1, 366);
+    let since = (chrono::Utc::now() - chrono::Days::new(u64::from(days))).naive_utc();
+
+    let mut conn = ctx.diesel.get().await?;
+
+    let mut query = discord_moderation_actions::table
+        .filter(discord_moderation_actions::created_at.ge(since))
+        .select(DiscordModerationAction::as_select())
+        .order(discord_moderation_actions::created_at.desc())
+        .limit(MAX_ACTIONS)
+        .into_boxed();
+
+    if let Some(guild_id) = q.guild_id {
+        query = query.filter(discord_moderation_actions::guild_id.eq(guild_id));
+    }
+
+    if let Some(user_id) = q.user_id {
+        query = query.filter(discord_moderation_actions::user_id.eq(user_id));
+    }
+
+    Ok(Json(query.load(&mut conn).await?))
+}
+
+#[derive(Deserialize)]
+struct SummariesQuery {
+    channel_id: Option<i64>,
+    limit: Option<i64>,
+}
+
+/// The summaries posted in the channels, most recent first
+async fn get_summaries(
+    State(ctx): State<App>,
+    AuthUser(i): AuthUser,
+    Query(q): Query<SummariesQuery>,
+) -> Result<Json<Vec<DiscordChannelSummary>>, AppError> {
+    ensure_owner(&ctx, i.id)?;
+
+    let limit = q.limit.unwrap_or(20).clamp(1, 100);
+
+    Ok(Json(
+        ctx.discord_summaries.recent(q.channel_id, limit).await?,
+    ))
+}
+
+/// Agent runs in flight, channels waiting for a slot and their queue wait
+async fn get_concurrency(
+    State(ctx): State<App>,
+    AuthUser(i): AuthUser,
+) -> Result<Json<LimiterStats>, AppError> {
+    ensure_owner(&ctx, i.id)?;
+
+    Ok(Json(ctx.discord_limiter.stats()))
+}
+
+/// The connection of the bot to the gateway, and the restarts of the client
+async fn get_connection_status(
+    State(ctx): State<App>,
+    AuthUser(i): AuthUser,
+) -> Result<Json<ConnectionReport>, AppError> {
+    ensure_owner(&ctx, i.id)?;
+
+    Ok(Json(ctx.discord_status.report()))
+}
+
+#[derive(Deserialize)]
+struct AnalyticsQuery {
+    /// Number of days to look back
+    days: Option<u32>,
+    channel_id: Option<i64>,
+    /// Number of topics
+    limit: Option<usize>,
+}
+
+impl AnalyticsQuery {
+    fn since(&self) -> chrono::NaiveDateTime {
+        let days = self.days.unwrap_or(30).clamp(1, 366);
+        (chrono::Utc::now() - chrono::Days::new(u64::from(days))).naive_utc()
+    }
+}
+
+/// Archived messages, responses and tool calls per day
+async fn get_message_volume(
+    State(ctx): State<App>,
+    AuthUser(i): AuthUser,
+    Query(q): Query<AnalyticsQuery>,
+) -> Result<Json<Vec<DailyVolume>>, AppError> {
+    ensure_owner(&ctx, i.id)?;
+
+    Ok(Json(
+        ctx.discord_archive.volume(q.since(), q.channel_id).await?,
+    ))
+}
+
+async fn get_response_rates(
+    State(ctx): State<App>,
+    AuthUser(i): AuthUser,
+    Query(q): Query<AnalyticsQuery>,
+) -> Result<Json<Vec<ResponseRate>>, AppError> {
+    ensure_owner(&ctx, i.id)?;
+
+    Ok(Json(
+        ctx.discord_archive
+            .response_rates(q.since(), q.channel_id)
+            .await?,
+    ))
+}
+
+async fn get_top_topics(
+    State(ctx): State<App>,
+    AuthUser(i): AuthUser,
+    Query(q): Query<AnalyticsQuery>,
+) -> Result<Json<Vec<Topic>>, AppError> {
+    ensure_owner(&ctx, i.id)?;
+
+    let limit = q.limit.unwrap_or(20).clamp(1, 100);
+
+    Ok(Json(
+        ctx.discord_archive
+            .top_topics(q.since(), q.channel_id, limit)
+            .await?,
+    ))
+}
+
+#[derive(Deserialize)]
+struct TranscriptsQuery {
+    channel_id: Option<i64>,
+    limit: Option<usize>,
+}
+
+/// The most recent agent transcripts, newest first
+async fn get_transcripts(
+    State(ctx): State<App>,
+    AuthUser(i): AuthUser,
+    Query(q): Query<TranscriptsQuery>,
+) -> Result<Json<Vec<TranscriptSummary>>, AppError> {
+    ensure_owner(&ctx, i.id)?;
+
+    let limit = q.limit.unwrap_or(50).clamp(1, 500);
+
+    Ok(Json(
+        ctx.discord_transcripts.list(q.channel_id, limit).await?,
+    ))
+}
+
+async fn get_transcript(
+    State(ctx): State<App>,
+    AuthUser(i): AuthUser,
+    Path(id): Path<String>,
+) -> Result<Json<AgentTranscript>, AppError> {
+    ensure_owner(&ctx, i.id)?;
+
+    ctx.discord_transcripts
+        .get(&id)
+        .await?
+        .map(Json)
+        .ok_or(("Transcript not found", StatusCode::NOT_FOUND).into())
+}
+
+#[derive(Deserialize)]
+struct ReplayRequest {
+    /// OpenRouter model ID to replay the transcript against
+    model: String,
+}
+
+/// Re-run the transcript against another model to compare their responses
+async fn replay_transcript(
+    State(ctx): State<App>,
+    AuthUser(i): AuthUser,
+    Path(id): Path<String>,
+    crate::json::Json(body): crate::json::Json<ReplayRequest>,
+) -> Result<Json<Replay>, AppError> {
+    ensure_owner(&ctx, i.id)?;
+
+    if body.model.trim().is_empty() {
+        return Err(("Model is required", StatusCode::BAD_REQUEST).into());
+    }
+
+    let transcript = ctx
+        .discord_transcripts
+        .get(&id)
+        .await?
+        .ok_or(("Transcript not found", StatusCode::NOT_FOUND))?;
+
+    Ok(Json(
+        ctx.discord_transcripts
+            .replay(&transcript, body.model.trim())
+            .await?,
+    ))
+}
+
+fn memories(ctx: &App) -> Result<&SharedVectorClient, AppError> {
+    ctx.discord_memories
+        .as_ref()
+        .ok_or(("Memories are not configured", StatusCode::NOT_FOUND).into())
+}
+
+/// The memory client and the scope of a collection name, e.g.
+/// `discord_memory_channel_123`
+fn memory_collection<'a>(
+    ctx: &'a App,
+    collection: &str,
+) -> Result<(&'a SharedVectorClient, MemoryScope), AppError> {
+    let client = memories(ctx)?;
+    let scope = client
+        .parse_collection_name(collection)
+        .ok_or(("Not a memory collection", StatusCode::NOT_FOUND))?;
+    Ok((client, scope))
+}
+
+#[derive(Serialize)]
+struct MemoryCollection {
+    collection: String,
+    scope: &'static str,
+    /// The user, channel or guild ID of the scope
+    id: Option<u64>,
+    count: usize,
+}
+
+async fn get_memory_collections(
+    State(ctx): State<App>,
+    AuthUser(i): AuthUser,
+) -> Result<Json<Vec<MemoryCollection>>, AppError> {
+    ensure_owner(&ctx, i.id)?;
+
+    let client = memories(&ctx)?;
+    let scopes = client
+        .list_scopes()
+        .await
+        .wrap_err("failed to list memory collections")?;
+
+    let mut collections = Vec::with_capacity(scopes.len());
+    for scope in scopes {
+        collections.push(MemoryCollection {
+            collection: client.get_collection_name(scope),
+            scope: scope.label(),
+            id: match scope {
+                MemoryScope::User(id) | MemoryScope::Channel(id) | MemoryScope::Guild(id) => {
+                    Some(id)
+                }
+                MemoryScope::Global => None,
+            },
+            count: client
+                .count(scope)
+                .await
+                .wrap_err("failed to count memories")?,
+        });
+    }
+
+    Ok(Json(collections))
+}
+
+#[derive(Deserialize)]
+struct MemoriesQuery {
+    /// Search the memories instead of listing them
+    q: Option<String>,
+    limit: Option<usize>,
+    offset: Option<usize>,
+}
+
+#[derive(Serialize)]
+struct MemoryEntry {
+    #[serde(flatten)]
+    record: MemoryRecord,
+    /// Relevance to the search query
+    #[serde(skip_serializing_if = "Option::is_none")]
+    score: Option<f32>,
+}
+
+#[derive(Serialize)]
+struct MemoriesResponse {
+    total: usize,
+    /// Most recent first, or most relevant first when searching
+    memories: Vec<MemoryEntry>,
+}
+
+async fn get_memories(
+    State(ctx): State<App>,
+    AuthUser(i): AuthUser,
+    Path(collection): Path<String>,
+    Query(q): Query<MemoriesQuery>,
+) -> Result<Json<MemoriesResponse>, AppError> {
+    ensure_owner(&ctx, i.id)?;
+
+    let (client, scope) = memory_collection(&ctx, &collection)?;
+    let limit = q.limit.unwrap_or(50).clamp(1, 500);
+
+    if let Some(query) = q.q.as_deref().filter(|q| !q.trim().is_empty()) {
+        let results = client
+            .search(query, &[scope], limit as u64)
+            .await
+            .wrap_err("failed to search memories")?;
+
+        return Ok(Json(MemoriesResponse {
+            total: results.len(),
+            memories: results
+                .into_iter()
+                .map(|r| MemoryEntry {
+                    record: MemoryRecord {
+                        id: r.point_id,
+                        content: r.content,
+                        metadata: r.metadata.and_then(|m| match m {
+                            serde_json::Value::Object(m) => Some(m),
+                            _ => None,
+                        }),
+                    },
+                    score: Some(r.score),
+                })
+                .collect(),
+        }));
+    }
+
+    let mut all = client
+        .all_memories(scope)
+        .await
+        .wrap_err("failed to load memories")?;
+    all.sort_by_key(|m| std::cmp::Reverse(m.timestamp));
+
+    Ok(Json(MemoriesResponse {
+        total: all.len(),
+        memories: all
+            .into_iter()
+            .skip(q.offset.unwrap_or(0))
+            .take(limit)
+            .map(|m| MemoryEntry {
+                record: m.into(),
+                score: None,
+            })
+            .collect(),
+    }))
+}
+
+#[derive(Deserialize)]
+struct MemoryUpdate {
+    content: String,
+    /// Replaces the metadata of the memory, kept as is if not given
+    #[serde(default)]
+    metadata: Option<serde_json::Map<String, serde_json::Value>>,
+}
+
+/// Rewrite a memory, e.g. to correct what the bot remembered. Like the
+/// updates of the agent, this resets its timestamp and so its decay.
+async fn put_memory(
+    State(ctx): State<App>,
+    AuthUser(i): AuthUser,
+    Path((collection, id)): Path<(String, String)>,
+    crate::json::Json(update): crate::json::Json<MemoryUpdate>,
+) -> Result<Json<MemoryRecord>, AppError> {
+    ensure_owner(&ctx, i.id)?;
+
+    let (client, scope) = memory_collection(&ctx, &collection)?;
+    let content = update.content.trim();
+    if content.is_empty() {
+        return Err(("Content is required", StatusCode::BAD_REQUEST).into());
+    }
+
+    let existing = client
+        .get(scope, &id)
+        .await
+        .wrap_err("failed to load memory")?
+        .ok_or(("Memory not found", StatusCode::NOT_FOUND))?;
+
+    let mut metadata = update.metadata.or(existing.metadata).unwrap_or_default();
+    metadata.insert(
+        "timestamp".to_string(),
+        chrono::Utc::now().to_rfc3339().into(),
+    );
+
+    client
+        .update(
+            &id,
+            content,
+            scope,
+            Some(serde_json::Value::Object(metadata.clone())),
+        )
+        .await
+        .wrap_err("failed to update memory")?;
+
+    Ok(Json(MemoryRecord {
+        id,
+        content: content.to_string(),
+        metadata: Some(metadata),
+    }))
+}
+
+/// Forget a memory
+async fn delete_memory(
+    State(ctx): State<App>,
+    AuthUser(i): AuthUser,
+    Path((collection, id)): Path<(String, String)>,
+) -> Result<StatusCode, AppError> {
+    ensure_owner(&ctx, i.id)?;
+
+    let (client, scope) = memory_collection(&ctx, &collection)?;
+    client
+        .get(scope, &id)
+        .await
+        .wrap_err("failed to load memory")?
+        .ok_or(("Memory not found", StatusCode::NOT_FOUND))?;
+    client
+        .delete(scope, &[id.as_str()])
+        .await
+        .wrap_err("failed to delete memory")?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// All memories of a collection as JSONL, one [MemoryRecord] per line
+async fn export_memories(
+    State(ctx): State<App>,
+    AuthUser(i): AuthUser,
+    Path(collection): Path<String>,
+) -> Result<impl IntoResponse, AppError> {
+    ensure_owner(&ctx, i.id)?;
+
+    let (client, scope) = memory_collection(&ctx, &collection)?;
+    let memories = client
+        .all_memories(scope)
+        .await
+        .wrap_err("failed to load memories")?;
+
+    let body = crate::json::ndjson_body(
+        memories
+            .into_iter()
+            .map(MemoryRecord::from)
+            .collect::<Vec<_>>(),
+    );
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, "application/x-ndjson".to_string()),
+            (
+                header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"{collection}.jsonl\""),
+            ),
+        ],
+        body,
+    ))
+}
+
+#[derive(Serialize)]
+struct ImportResponse {
+    imported: usize,
+}
+
+/// Import memories exported by [export_memories], possibly of another
+/// collection. Memories with the same ID are replaced.
+async fn import_memories(
+    State(ctx): State<App>,
+    AuthUser(i): AuthUser,
+    Path(collection): Path<String>,
+    body: String,
+) -> Result<Json<ImportResponse>, AppError> {
+    ensure_owner(&ctx, i.id)?;
+
+    let (client, scope) = memory_collection(&ctx, &collection)?;
+
+    let records = body
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(n, line)| {
+            serde_json::from_str::<MemoryRecord>(line).map_err(|e| {
+                (
+                    format!("Invalid memory on line {}: {e}", n + 1),
+                    StatusCode::BAD_REQUEST,
+                )
+            })
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let imported = client
+        .import(scope, &records)
+        .await
+        .wrap_err("failed to import memories")?;
+
+    Ok(Json(ImportResponse { imported }))
+}
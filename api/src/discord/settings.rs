@@ -0,0 +1,568 @@
+use std::{collections::HashMap, sync::Arc};
+
+use arc_swap::ArcSwap;
+use diesel::prelude::*;
+use diesel_async::{AsyncPgConnection, RunQueryDsl, pooled_connection::deadpool::Pool};
+use eyre::Context as _;
+use rig::tool::Tool as _;
+use serenity::all::{ChannelId, GuildId};
+
+use crate::{
+    config::{ServerConfig, Tunables},
+    discord::{
+        constants::DEFAULT_MODEL,
+        prompt::{DEFAULT_PERSONA_PROFILE, PersonaProfile},
+        summaries::SummarySchedule,
+        tools::DiscordSendMessageTool,
+    },
+    models::discord::{
+        DiscordChannelSettings, DiscordGuildSettings, DiscordPersona, NewDiscordChannelSettings,
+        NewDiscordGuildSettings, NewDiscordPersona,
+    },
+    schema::{discord_channel_settings, discord_guild_settings, discord_personas},
+};
+
+/// The resolved settings of a channel, channel settings take precedence over
+/// guild settings, which take precedence over the environment defaults.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChannelSettings {
+    pub enabled: bool,
+    pub mention_only: bool,
+    /// Extra persona instructions on top of the profile
+    pub persona: Option<String>,
+    pub profile: PersonaProfile,
+    pub model: String,
+    /// Edit a placeholder message as tokens arrive instead of sending complete messages
+    pub streaming: bool,
+    /// Tool names or groups the agent may use, all tools if `None`
+    pub allowed_tools: Option<Vec<String>>,
+    /// Days after which the channel's memories decay, kept forever if `None`
+    pub memory_retention_days: Option<u32>,
+    /// Archive the conversations to Postgres for analytics
+    pub archive: bool,
+    /// Days after which archived messages are deleted, kept forever if `None`
+    pub archive_retention_days: Option<u32>,
+    /// Whether the agent may warn and time out users and delete messages, set
+    /// per guild
+    pub moderation_enforcement: bool,
+    /// Classifier score from which messages are flagged, the configured one if
+    /// `None`
+    pub moderation_threshold: Option<f64>,
+    /// Messages a user can get the agent to answer per 10 minutes, no limit
+    /// if `None`
+    pub user_rate_limit: Option<u32>,
+    /// Store the files attached to messages in the channel memory
+    pub store_attachments: bool,
+    /// How often a summary of the channel activity is posted, never if `None`
+    pub summary_schedule: Option<SummarySchedule>,
+}
+
+impl ChannelSettings {
+    /// Whether the agent may use a tool. An entry allows the tool of the same
+    /// name, or a group of tools, e.g. `godbolt` allows all `godbolt_*` tools,
+    /// and `*` allows everything. Sending messages is always allowed since the
+    /// agent can't respond otherwise.
+    pub fn allows_tool(&self, name: &str) -> bool {
+        let Some(allowed_tools) = &self.allowed_tools else {
+            return true;
+        };
+
+        name == DiscordSendMessageTool::NAME
+            || allowed_tools.iter().any(|entry| {
+                entry == "*"
+                    || name == entry
+                    || name
+                        .strip_prefix(entry.as_str())
+                        .is_some_and(|rest| rest.starts_with('_'))
+            })
+    }
+}
+
+#[derive(Default)]
+struct Snapshot {
+    guilds: HashMap<i64, DiscordGuildSettings>,
+    channels: HashMap<i64, DiscordChannelSettings>,
+    personas: HashMap<String, DiscordPersona>,
+}
+
+struct Inner {
+    diesel: Pool<AsyncPgConnection>,
+    default_whitelist_channels: Vec<u64>,
+    /// Holds the default mention-only mode, which can change while running
+    tunables: Tunables,
+    default_memory_retention_days: Option<u32>,
+    default_archive_retention_days: Option<u32>,
+    snapshot: ArcSwap<Snapshot>,
+}
+
+/// Per-guild and per-channel bot settings stored in Postgres. Reads are served
+/// from an in-memory snapshot which is refreshed after every write.
+#[derive(Clone)]
+pub struct DiscordSettings(Arc<Inner>);
+
+impl DiscordSettings {
+    pub fn new(config: &ServerConfig, tunables: Tunables, diesel: Pool<AsyncPgConnection>) -> Self {
+        Self(Arc::new(Inner {
+            diesel,
+            default_whitelist_channels: config
+                .discord_whitelist_channels
+                .clone()
+                .unwrap_or_default(),
+            tunables,
+            default_memory_retention_days: config.discord_memory_retention_days,
+            default_archive_retention_days: config.discord_archive_retention_days,
+            snapshot: ArcSwap::from_pointee(Snapshot::default()),
+        }))
+    }
+
+    fn snapshot(&self) -> arc_swap::Guard<Arc<Snapshot>> {
+        // Not to be confused with `RunQueryDsl::load`
+        ArcSwap::load(&self.0.snapshot)
+    }
+
+    /// Reload all settings from the database into the cache
+    pub async fn reload(&self) -> Result<(), eyre::Error> {
+        let mut conn = self
+            .0
+            .diesel
+            .get()
+            .await
+            .wrap_err("could not get diesel pool conn")?;
+
+        let guilds = discord_guild_settings::table
+            .select(DiscordGuildSettings::as_select())
+            .load(&mut conn)
+            .await
+            .wrap_err("failed to load Discord guild settings")?;
+
+        let channels = discord_channel_settings::table
+            .select(DiscordChannelSettings::as_select())
+            .load(&mut conn)
+            .await
+            .wrap_err("failed to load Discord channel settings")?;
+
+        let personas = discord_personas::table
+            .select(DiscordPersona::as_select())
+            .load(&mut conn)
+            .await
+            .wrap_err("failed to load Discord personas")?;
+
+        tracing::info!(
+            guilds = guilds.len(),
+            channels = channels.len(),
+            personas = personas.len(),
+            "Loaded Discord bot settings"
+        );
+
+        self.0.snapshot.store(Arc::new(Snapshot {
+            guilds: guilds.into_iter().map(|g| (g.guild_id, g)).collect(),
+            channels: channels.into_iter().map(|c| (c.channel_id, c)).collect(),
+            personas: personas.into_iter().map(|p| (p.name.clone(), p)).collect(),
+        }));
+
+        Ok(())
+    }
+
+    /// Resolve the effective settings of a channel. If the guild is unknown
+    /// (e.g. on startup), the guild stored along with the channel settings is
+    /// used instead.
+    pub fn channel(&self, channel_id: ChannelId, guild_id: Option<GuildId>) -> ChannelSettings {
+        let snapshot = self.snapshot();
+
+        let channel = snapshot.channels.get(&to_db_id(channel_id.get()));
+        let guild = guild_id
+            .map(|id| to_db_id(id.get()))
+            .or(channel.and_then(|c| c.guild_id))
+            .and_then(|id| snapshot.guilds.get(&id));
+
+        ChannelSettings {
+            enabled: channel.map(|c| c.enabled).unwrap_or_else(|| {
+                self.0
+                    .default_whitelist_channels
+                    .contains(&channel_id.get())
+            }),
+            mention_only: channel
+                .and_then(|c| c.mention_only)
+                .or(guild.and_then(|g| g.mention_only))
+                .unwrap_or(self.0.tunables.get().discord_mention_only),
+            persona: channel
+                .and_then(|c| c.persona.clone())
+                .or(guild.and_then(|g| g.persona.clone())),
+            profile: Self::profile(
+                &snapshot,
+                channel
+                    .and_then(|c| c.persona_profile.as_deref())
+                    .or(guild.and_then(|g| g.persona_profile.as_deref()))
+                    .unwrap_or(DEFAULT_PERSONA_PROFILE),
+            ),
+            model: channel
+                .and_then(|c| c.model.clone())
+                .or(guild.and_then(|g| g.model.clone()))
+                .unwrap_or(DEFAULT_MODEL.to_string()),
+            streaming: channel
+                .and_then(|c| c.streaming)
+                .or(guild.and_then(|g| g.streaming))
+                .unwrap_or(false),
+            allowed_tools: channel
+                .and_then(|c| c.allowed_tools.clone())
+                .or(guild.and_then(|g| g.allowed_tools.clone())),
+            memory_retention_days: self.memory_retention_days(
+                channel
+                    .and_then(|c| c.memory_retention_days)
+                    .or(guild.and_then(|g| g.memory_retention_days)),
+            ),
+            // Archival is opt-in per channel and not inherited from the guild
+            archive: channel.and_then(|c| c.archive).unwrap_or(false),
+            archive_retention_days: channel
+                .and_then(|c| c.archive_retention_days)
+                .map(|days| u32::try_from(days).unwrap_or(0))
+                .or(self.0.default_archive_retention_days)
+                .filter(|days| *days > 0),
+            moderation_enforcement: guild
+                .and_then(|g| g.moderation_enforcement)
+                .unwrap_or(false),
+            moderation_threshold: guild.and_then(|g| g.moderation_threshold),
+            // Not inherited from the guild, the chattiness differs by channel
+            user_rate_limit: Some(
+                channel
+                    .and_then(|c| c.user_rate_limit)
+                    .map(|limit| u32::try_from(limit).unwrap_or(0))
+                    .unwrap_or(self.0.tunables.get().discord_user_messages_per_10_mins),
+            )
+            .filter(|limit| *limit > 0),
+            // Opt-in per channel like the archival
+            store_attachments: channel.and_then(|c| c.store_attachments).unwrap_or(false),
+            summary_schedule: channel
+                .and_then(|c| c.summary_schedule.as_deref())
+                .and_then(SummarySchedule::parse),
+        }
+    }
+
+    /// The settings of a DM conversation. DMs can't be configured with the
+    /// settings commands, so they always respond without a mention and are
+    /// never archived, moderated or summarized, and their files aren't stored.
+    /// Their rate limit is the one of the DMs.
+    pub fn direct_message(&self, channel_id: ChannelId) -> ChannelSettings {
+        ChannelSettings {
+            enabled: true,
+            mention_only: false,
+            archive: false,
+            moderation_enforcement: false,
+            user_rate_limit: None,
+            store_attachments: false,
+            summary_schedule: None,
+            ..self.channel(channel_id, None)
+        }
+    }
+
+    /// A stored profile, or the built-in one of the same name. Unknown profiles,
+    /// e.g. deleted ones, fall back to the default.
+    fn profile(snapshot: &Snapshot, name: &str) -> PersonaProfile {
+        snapshot
+            .personas
+            .get(name)
+            .map(PersonaProfile::from)
+            .or_else(|| {
+                PersonaProfile::builtin()
+                    .into_iter()
+                    .find(|p| p.name == name)
+            })
+            .or_else(|| {
+                snapshot
+                    .personas
+                    .get(DEFAULT_PERSONA_PROFILE)
+                    .map(PersonaProfile::from)
+            })
+            .unwrap_or_default()
+    }
+
+    /// All persona profiles, the stored ones overriding the built-in ones
+    pub fn profiles(&self) -> Vec<PersonaProfile> {
+        let snapshot = self.snapshot();
+        let mut profiles = PersonaProfile::builtin()
+            .into_iter()
+            .filter(|p| !snapshot.personas.contains_key(&p.name))
+            .chain(snapshot.personas.values().map(PersonaProfile::from))
+            .collect::<Vec<_>>();
+        profiles.sort_by(|a, b| a.name.cmp(&b.name));
+        profiles
+    }
+
+    /// Whether a profile of the name exists
+    pub fn has_profile(&self, name: &str) -> bool {
+        self.snapshot().personas.contains_key(name)
+            || PersonaProfile::builtin().iter().any(|p| p.name == name)
+    }
+
+    pub async fn upsert_persona(
+        &self,
+        persona: NewDiscordPersona,
+    ) -> Result<DiscordPersona, eyre::Error> {
+        let mut conn = self
+            .0
+            .diesel
+            .get()
+            .await
+            .wrap_err("could not get diesel pool conn")?;
+
+        let row = diesel::insert_into(discord_personas::table)
+            .values(&persona)
+            .on_conflict(discord_personas::name)
+            .do_update()
+            .set((&persona, discord_personas::updated_at.eq(diesel::dsl::now)))
+            .returning(DiscordPersona::as_returning())
+            .get_result(&mut conn)
+            .await
+            .wrap_err("failed to upsert Discord persona")?;
+
+        self.reload().await?;
+
+        Ok(row)
+    }
+
+    /// Returns whether there was a row to delete
+    pub async fn delete_persona(&self, name: &str) -> Result<bool, eyre::Error> {
+        let mut conn = self
+            .0
+            .diesel
+            .get()
+            .await
+            .wrap_err("could not get diesel pool conn")?;
+
+        let deleted =
+            diesel::delete(discord_personas::table.filter(discord_personas::name.eq(name)))
+                .execute(&mut conn)
+                .await
+                .wrap_err("failed to delete Discord persona")?;
+
+        self.reload().await?;
+
+        Ok(deleted > 0)
+    }
+
+    /// Memory retention of a guild, for memories that aren't tied to a channel
+    pub fn guild_memory_retention_days(&self, guild_id: GuildId) -> Option<u32> {
+        let snapshot = self.snapshot();
+        self.memory_retention_days(
+            snapshot
+                .guilds
+                .get(&to_db_id(guild_id.get()))
+                .and_then(|g| g.memory_retention_days),
+        )
+    }
+
+    /// Resolve a stored retention against the default, 0 means forever
+    pub fn memory_retention_days(&self, stored: Option<i32>) -> Option<u32> {
+        stored
+            .map(|days| u32::try_from(days).unwrap_or(0))
+            .or(self.0.default_memory_retention_days)
+            .filter(|days| *days > 0)
+    }
+
+    /// All channels the bot is enabled in, from both the environment whitelist
+    /// and the database
+    pub fn enabled_channels(&self) -> Vec<ChannelId> {
+        let snapshot = self.snapshot();
+
+        let mut channels: Vec<ChannelId> = self
+            .0
+            .default_whitelist_channels
+            .iter()
+            .filter(|id| {
+                snapshot
+                    .channels
+                    .get(&to_db_id(**id))
+                    .is_none_or(|c| c.enabled)
+            })
+            .map(|id| ChannelId::new(*id))
+            .collect();
+
+        channels.extend(
+            snapshot
+                .channels
+                .values()
+                .filter(|c| c.enabled)
+                .filter_map(|c| u64::try_from(c.channel_id).ok())
+                .filter(|id| *id != 0 && !self.0.default_whitelist_channels.contains(id))
+                .map(ChannelId::new),
+        );
+
+        channels
+    }
+
+    pub fn guild_settings(&self) -> Vec<DiscordGuildSettings> {
+        self.snapshot().guilds.values().cloned().collect()
+    }
+
+    pub fn channel_settings(&self) -> Vec<DiscordChannelSettings> {
+        self.snapshot().channels.values().cloned().collect()
+    }
+
+    /// The stored settings row of a guild, if any, for read-modify-write
+    pub fn stored_guild(&self, guild_id: GuildId) -> Option<DiscordGuildSettings> {
+        self.snapshot()
+            .guilds
+            .get(&to_db_id(guild_id.get()))
+            .cloned()
+    }
+
+    /// The stored settings row of a channel, if any, for read-modify-write
+    pub fn stored_channel(&self, channel_id: ChannelId) -> Option<DiscordChannelSettings> {
+        self.snapshot()
+            .channels
+            .get(&to_db_id(channel_id.get()))
+            .cloned()
+    }
+
+    pub async fn upsert_guild(
+        &self,
+        settings: NewDiscordGuildSettings,
+    ) -> Result<DiscordGuildSettings, eyre::Error> {
+        let mut conn = self
+            .0
+            .diesel
+            .get()
+            .await
+            .wrap_err("could not get diesel pool conn")?;
+
+        let row = diesel::insert_into(discord_guild_settings::table)
+            .values(&settings)
+            .on_conflict(discord_guild_settings::guild_id)
+            .do_update()
+            .set((
+                &settings,
+                discord_guild_settings::updated_at.eq(diesel::dsl::now),
+            ))
+            .returning(DiscordGuildSettings::as_returning())
+            .get_result(&mut conn)
+            .await
+            .wrap_err("failed to upsert Discord guild settings")?;
+
+        self.reload().await?;
+
+        Ok(row)
+    }
+
+    pub async fn upsert_channel(
+        &self,
+        settings: NewDiscordChannelSettings,
+    ) -> Result<DiscordChannelSettings, eyre::Error> {
+        let mut conn = self
+            .0
+            .diesel
+            .get()
+            .await
+            .wrap_err("could not get diesel pool conn")?;
+
+        let row = diesel::insert_into(discord_channel_settings::table)
+            .values(&settings)
+            .on_conflict(discord_channel_settings::channel_id)
+            .do_update()
+            .set((
+                &settings,
+                discord_channel_settings::updated_at.eq(diesel::dsl::now),
+            ))
+            .returning(DiscordChannelSettings::as_returning())
+            .get_result(&mut conn)
+            .await
+            .wrap_err("failed to upsert Discord channel settings")?;
+
+        self.reload().await?;
+
+        Ok(row)
+    }
+
+    /// Returns whether there was a row to delete
+    pub async fn delete_guild(&self, guild_id: i64) -> Result<bool, eyre::Error> {
+        let mut conn = self
+            .0
+            .diesel
+            .get()
+            .await
+            .wrap_err("could not get diesel pool conn")?;
+
+        let deleted = diesel::delete(
+            discord_guild_settings::table.filter(discord_guild_settings::guild_id.eq(guild_id)),
+        )
+        .execute(&mut conn)
+        .await
+        .wrap_err("failed to delete Discord guild settings")?;
+
+        self.reload().await?;
+
+        Ok(deleted > 0)
+    }
+
+    /// Returns whether there was a row to delete
+    pub async fn delete_channel(&self, channel_id: i64) -> Result<bool, eyre::Error> {
+        let mut conn = self
+            .0
+            .diesel
+            .get()
+            .await
+            .wrap_err("could not get diesel pool conn")?;
+
+        let deleted = diesel::delete(
+            discord_channel_settings::table
+                .filter(discord_channel_settings::channel_id.eq(channel_id)),
+        )
+        .execute(&mut conn)
+        .await
+        .wrap_err("failed to delete Discord channel settings")?;
+
+        self.reload().await?;
+
+        Ok(deleted > 0)
+    }
+}
+
+/// Discord snowflakes are 64-bit unsigned integers but always fit in the signed
+/// BIGINT column
+pub fn to_db_id(id: u64) -> i64 {
+    i64::try_from(id).unwrap_or(i64::MAX)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn allows_tool_matches_names_and_groups() {
+        let settings = ChannelSettings {
+            enabled: true,
+            mention_only: false,
+            persona: None,
+            profile: PersonaProfile::default(),
+            model: DEFAULT_MODEL.to_string(),
+            streaming: false,
+            allowed_tools: Some(vec!["godbolt".to_string(), "memory_find".to_string()]),
+            memory_retention_days: None,
+            archive: false,
+            archive_retention_days: None,
+            moderation_enforcement: false,
+            moderation_threshold: None,
+            user_rate_limit: None,
+            store_attachments: false,
+            summary_schedule: None,
+        };
+
+        assert!(settings.allows_tool("godbolt_compile"));
+        assert!(settings.allows_tool("memory_find"));
+        assert!(settings.allows_tool(DiscordSendMessageTool::NAME));
+        assert!(!settings.allows_tool("memory_store"));
+        assert!(!settings.allows_tool("web_search"));
+        assert!(!settings.allows_tool("godboltx"));
+
+        let all = ChannelSettings {
+            allowed_tools: Some(vec!["*".to_string()]),
+            ..settings.clone()
+        };
+        assert!(all.allows_tool("web_search"));
+
+        let unrestricted = ChannelSettings {
+            allowed_tools: None,
+            ..settings
+        };
+        assert!(unrestricted.allows_tool("web_search"));
+    }
+}
@@ -0,0 +1,234 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::{Arc, Mutex},
+    time::Instant,
+};
+
+use rig::{streaming::ToolCallDeltaContent, tool::Tool as _};
+use serenity::all::{ChannelId, CreateMessage, EditMessage, Http, MessageId};
+
+use crate::discord::{constants::STREAM_EDIT_INTERVAL, tools::DiscordSendMessageTool};
+
+/// Discord rejects messages longer than this
+const MAX_MESSAGE_LEN: usize = 2000;
+
+/// Appended to the placeholder while the message is still being written
+const STREAMING_CURSOR: &str = " ▌";
+
+/// Placeholder messages posted while `send_discord_message` tool calls are being streamed. The
+/// stream consumer posts and edits the placeholders, the tool then finalizes them with the
+/// complete content instead of sending a new message.
+#[derive(Debug, Clone)]
+pub struct StreamingReplies {
+    http: Arc<Http>,
+    channel_id: ChannelId,
+    // Tool calls are executed in the order they are streamed, so the tool takes the oldest one
+    placeholders: Arc<Mutex<VecDeque<MessageId>>>,
+}
+
+impl StreamingReplies {
+    pub fn new(http: Arc<Http>, channel_id: ChannelId) -> Self {
+        Self {
+            http,
+            channel_id,
+            placeholders: Arc::new(Mutex::new(VecDeque::new())),
+        }
+    }
+
+    /// The placeholder of the tool call being executed, if it was streamed
+    pub fn take_placeholder(&self) -> Option<MessageId> {
+        self.placeholders
+            .lock()
+            .inspect_err(|e| tracing::error!(?e, "Streaming placeholders lock poisoned"))
+            .ok()?
+            .pop_front()
+    }
+
+    fn push_placeholder(&self, message_id: MessageId) {
+        if let Ok(mut placeholders) = self.placeholders.lock() {
+            placeholders.push_back(message_id);
+        }
+    }
+
+    /// Delete the placeholders whose tool calls never got executed, e.g. because the arguments
+    /// failed to parse or the run errored
+    pub async fn discard_placeholders(&self) {
+        let orphans: Vec<MessageId> = match self.placeholders.lock() {
+            Ok(mut placeholders) => placeholders.drain(..).collect(),
+            Err(_) => return,
+        };
+
+        for message_id in orphans {
+            let _ = self
+                .channel_id
+                .delete_message(&self.http, message_id)
+                .await
+                .inspect_err(|e| tracing::error!(?e, "Failed to delete orphan placeholder"));
+        }
+    }
+
+    pub fn writer(&self) -> StreamWriter {
+        StreamWriter {
+            replies: self.clone(),
+            calls: HashMap::new(),
+        }
+    }
+}
+
+#[derive(Default)]
+struct StreamedCall {
+    is_send_message: bool,
+    arguments: String,
+    message_id: Option<MessageId>,
+    last_edit: Option<Instant>,
+    last_content: String,
+}
+
+/// Follows the tool call deltas of a single agent run
+pub struct StreamWriter {
+    replies: StreamingReplies,
+    calls: HashMap<String, StreamedCall>,
+}
+
+impl StreamWriter {
+    pub async fn on_tool_call_delta(
+        &mut self,
+        internal_call_id: &str,
+        delta: &ToolCallDeltaContent,
+    ) {
+        let call = self.calls.entry(internal_call_id.to_string()).or_default();
+
+        match delta {
+            ToolCallDeltaContent::Name(name) => {
+                call.is_send_message = name == DiscordSendMessageTool::NAME;
+                return;
+            }
+            ToolCallDeltaContent::Delta(delta) if call.is_send_message => {
+                call.arguments.push_str(delta);
+            }
+            ToolCallDeltaContent::Delta(_) => return,
+        }
+
+        let Some(content) = partial_json_string_field(&call.arguments, "content") else {
+            return;
+        };
+
+        if content.trim().is_empty() || content == call.last_content {
+            return;
+        }
+
+        // Stay well within Discord's rate limit of 5 edits per 5 seconds
+        if call
+            .last_edit
+            .is_some_and(|t| t.elapsed() < STREAM_EDIT_INTERVAL)
+        {
+            return;
+        }
+
+        let preview = preview(&content);
+
+        match call.message_id {
+            None => {
+                match self
+                    .replies
+                    .channel_id
+                    .send_message(&self.replies.http, CreateMessage::new().content(preview))
+                    .await
+                {
+                    Ok(message) => {
+                        call.message_id = Some(message.id);
+                        self.replies.push_placeholder(message.id);
+                    }
+                    Err(e) => tracing::error!(?e, "Failed to post streaming placeholder"),
+                }
+            }
+            Some(message_id) => {
+                let _ = self
+                    .replies
+                    .channel_id
+                    .edit_message(
+                        &self.replies.http,
+                        message_id,
+                        EditMessage::new().content(preview),
+                    )
+                    .await
+                    .inspect_err(|e| tracing::error!(?e, "Failed to edit streaming placeholder"));
+            }
+        }
+
+        call.last_edit = Some(Instant::now());
+        call.last_content = content;
+    }
+}
+
+fn preview(content: &str) -> String {
+    let max_len = MAX_MESSAGE_LEN - STREAMING_CURSOR.chars().count();
+    let end = content
+        .char_indices()
+        .nth(max_len)
+        .map(|(n, _)| n)
+        .unwrap_or(content.len());
+    format!("{}{STREAMING_CURSOR}", &content[..end])
+}
+
+/// Extract the (possibly incomplete) value of a top-level string field from a JSON object that
+/// is still being streamed, e.g. `{"content": "hello wor` gives `hello wor`
+fn partial_json_string_field(json: &str, field: &str) -> Option<String> {
+    let key = format!("\"{field}\"");
+    let after_key = &json[json.find(&key)? + key.len()..];
+    let after_colon = after_key.trim_start().strip_prefix(':')?;
+    let mut chars = after_colon.trim_start().strip_prefix('"')?.chars();
+
+    let mut value = String::new();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => break,
+            '\\' => match chars.next() {
+                Some('n') => value.push('\n'),
+                Some('t') => value.push('\t'),
+                Some('r') => value.push('\r'),
+                Some('b') => value.push('\u{8}'),
+                Some('f') => value.push('\u{c}'),
+                Some('u') => {
+                    let hex: String = chars.by_ref().take(4).collect();
+                    // Incomplete escape at the end of the stream, or a surrogate which needs
+                    // its pair; either way wait for the complete string
+                    match u32::from_str_radix(&hex, 16).ok().and_then(char::from_u32) {
+                        Some(c) if hex.len() == 4 => value.push(c),
+                        _ => break,
+                    }
+                }
+                Some(c) => value.push(c),
+                None => break,
+            },
+            c => value.push(c),
+        }
+    }
+
+    Some(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn partial_json_string_field_handles_incomplete_values() {
+        assert_eq!(partial_json_string_field("{\"cont", "content"), None);
+        assert_eq!(
+            partial_json_string_field("{\"content\": \"hello wor", "content"),
+            Some("hello wor".to_string())
+        );
+        assert_eq!(
+            partial_json_string_field(
+                "{\"content\":\"line\\nnext \\\"quoted\\\"\",\"reply_to_message_id\":null}",
+                "content"
+            ),
+            Some("line\nnext \"quoted\"".to_string())
+        );
+        assert_eq!(
+            partial_json_string_field("{\"content\": \"caf\\u00e9 \\u00", "content"),
+            Some("café ".to_string())
+        );
+    }
+}
@@ -0,0 +1,413 @@
+//! Summaries of the activity of the channels, posted by the bot daily or
+//! weekly as set per channel. A period is summarized once it's over, from its
+//! messages read back from the channel, and the summaries are stored so that
+//! they can be fetched through the API as well.
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use chrono::{DateTime, Datelike as _, Days, NaiveDateTime, NaiveTime, Utc};
+use diesel::prelude::*;
+use diesel_async::{AsyncPgConnection, RunQueryDsl, pooled_connection::deadpool::Pool};
+use eyre::Context as _;
+use rig::{
+    agent::{Agent, AgentBuilder},
+    client::CompletionClient,
+    completion::Prompt,
+    providers::openrouter::{Client, CompletionModel},
+};
+use serenity::all::{
+    ChannelId, CreateEmbed, CreateEmbedFooter, CreateMessage, GetMessages, Http, Message, MessageId,
+};
+
+use crate::{
+    config::ServerConfig,
+    jobs,
+    models::discord::{DiscordChannelSummary, NewDiscordChannelSummary},
+    schema::discord_channel_summaries,
+};
+
+use super::{
+    constants::{CHANNEL_SUMMARY_PROMPT, DEFAULT_MODEL},
+    context_budget::truncate_text,
+    settings::{DiscordSettings, to_db_id},
+};
+
+/// How often the channels are checked for a period to summarize
+const CHECK_INTERVAL: Duration = Duration::from_secs(60 * 60);
+/// Messages of a period summarized at most, the latest ones are left out
+const MAX_SUMMARIZED_MESSAGES: usize = 500;
+/// Quieter periods aren't worth a summary
+const MIN_SUMMARIZED_MESSAGES: usize = 5;
+const MESSAGE_TOKENS: usize = 300;
+const TRANSCRIPT_TOKENS: usize = 30_000;
+/// Limit of the description of an embed
+const MAX_EMBED_CHARS: usize = 4096;
+/// Milliseconds since the Unix epoch at which the snowflake IDs start
+const DISCORD_EPOCH_MS: i64 = 1_420_070_400_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SummarySchedule {
+    Daily,
+    Weekly,
+}
+
+impl SummarySchedule {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_lowercase().as_str() {
+            "daily" => Some(Self::Daily),
+            "weekly" => Some(Self::Weekly),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Daily => "daily",
+            Self::Weekly => "weekly",
+        }
+    }
+
+    /// The last period that's over: the previous UTC day, or the previous
+    /// week starting on Monday
+    fn last_period(self, now: DateTime<Utc>) -> (NaiveDateTime, NaiveDateTime) {
+        let today = now.date_naive();
+        let (end, length) = match self {
+            Self::Daily => (today, Days::new(1)),
+            Self::Weekly => (
+                today - Days::new(u64::from(today.weekday().num_days_from_monday())),
+                Days::new(7),
+            ),
+        };
+        (
+            (end - length).and_time(NaiveTime::MIN),
+            end.and_time(NaiveTime::MIN),
+        )
+    }
+
+    fn title(self, period_start: NaiveDateTime) -> String {
+        match self {
+            Self::Daily => format!("📋 Summary of {}", period_start.format("%B %-d")),
+            Self::Weekly => format!(
+                "📋 Summary of the week of {}",
+                period_start.format("%B %-d")
+            ),
+        }
+    }
+}
+
+/// The summaries posted in the channels
+#[derive(Clone)]
+pub struct ChannelSummaries {
+    diesel: Pool<AsyncPgConnection>,
+}
+
+impl ChannelSummaries {
+    pub fn new(diesel: Pool<AsyncPgConnection>) -> Self {
+        Self { diesel }
+    }
+
+    async fn conn(
+        &self,
+    ) -> Result<diesel_async::pooled_connection::deadpool::Object<AsyncPgConnection>, eyre::Error>
+    {
+        self.diesel
+            .get()
+            .await
+            .wrap_err("could not get diesel pool conn")
+    }
+
+    /// The summaries of a channel, or of all channels, most recent first
+    pub async fn recent(
+        &self,
+        channel_id: Option<i64>,
+        limit: i64,
+    ) -> Result<Vec<DiscordChannelSummary>, eyre::Error> {
+        let mut conn = self.conn().await?;
+
+        let mut query = discord_channel_summaries::table
+            .select(DiscordChannelSummary::as_select())
+            .order(discord_channel_summaries::period_end.desc())
+            .limit(limit)
+            .into_boxed();
+
+        if let Some(channel_id) = channel_id {
+            query = query.filter(discord_channel_summaries::channel_id.eq(channel_id));
+        }
+
+        query
+            .load(&mut conn)
+            .await
+            .wrap_err("failed to load channel summaries")
+    }
+
+    async fn is_summarized(
+        &self,
+        channel_id: ChannelId,
+        period_start: NaiveDateTime,
+    ) -> Result<bool, eyre::Error> {
+        let mut conn = self.conn().await?;
+
+        diesel::select(diesel::dsl::exists(
+            discord_channel_summaries::table
+                .filter(discord_channel_summaries::channel_id.eq(to_db_id(channel_id.get())))
+                .filter(discord_channel_summaries::period_start.eq(period_start)),
+        ))
+        .get_result(&mut conn)
+        .await
+        .wrap_err("failed to check for a channel summary")
+    }
+
+    async fn store(&self, summary: NewDiscordChannelSummary) -> Result<(), eyre::Error> {
+        let mut conn = self.conn().await?;
+
+        diesel::insert_into(discord_channel_summaries::table)
+            .values(&summary)
+            .on_conflict_do_nothing()
+            .execute(&mut conn)
+            .await
+            .wrap_err("failed to store channel summary")?;
+
+        Ok(())
+    }
+
+    /// Post the summaries of the periods that are over, from one instance,
+    /// including the ones that ended while the bot was down
+    pub fn start_worker(&self, settings: DiscordSettings, config: &ServerConfig) {
+        let (Some(token), Some(api_key)) = (&config.discord_token, &config.openai_api_key) else {
+            return;
+        };
+        let summarizer = match Client::new(api_key) {
+            Ok(client) => AgentBuilder::new(client.completion_model(DEFAULT_MODEL))
+                .preamble(CHANNEL_SUMMARY_PROMPT)
+                .build(),
+            Err(e) => {
+                tracing::error!(?e, "Failed to start the channel summaries");
+                return;
+            }
+        };
+
+        let worker = SummaryWorker {
+            summaries: self.clone(),
+            settings,
+            summarizer,
+            http: Arc::new(Http::new(token)),
+            quiet: Arc::default(),
+        };
+        jobs::spawn_exclusive(config, "discord-channel-summaries", move || {
+            let worker = worker.clone();
+            async move {
+                let mut interval = tokio::time::interval(CHECK_INTERVAL);
+                interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+                loop {
+                    interval.tick().await;
+                    worker.run().await;
+                }
+            }
+        });
+    }
+}
+
+#[derive(Clone)]
+struct SummaryWorker {
+    summaries: ChannelSummaries,
+    settings: DiscordSettings,
+    summarizer: Agent<CompletionModel>,
+    http: Arc<Http>,
+    /// The last period of each channel found too quiet to summarize, so that
+    /// it's not read again on every check
+    quiet: Arc<Mutex<HashMap<ChannelId, NaiveDateTime>>>,
+}
+
+impl SummaryWorker {
+    async fn run(&self) {
+        let now = Utc::now();
+
+        for stored in self.settings.channel_settings() {
+            let Some(channel_id) = u64::try_from(stored.channel_id)
+                .ok()
+                .filter(|id| *id != 0)
+                .map(ChannelId::new)
+            else {
+                continue;
+            };
+            let settings = self.settings.channel(channel_id, None);
+            let (true, Some(schedule)) = (settings.enabled, settings.summary_schedule) else {
+                continue;
+            };
+
+            // One failing channel shouldn't keep the others from their summary
+            let (start, end) = schedule.last_period(now);
+            if let Err(e) = self
+                .summarize(channel_id, stored.guild_id, schedule, start, end)
+                .await
+            {
+                tracing::error!(?e, %channel_id, "Failed to summarize the channel");
+            }
+        }
+    }
+
+    async fn summarize(
+        &self,
+        channel_id: ChannelId,
+        guild_id: Option<i64>,
+        schedule: SummarySchedule,
+        start: NaiveDateTime,
+        end: NaiveDateTime,
+    ) -> Result<(), eyre::Error> {
+        let known_quiet = self.lock_quiet().get(&channel_id) == Some(&start);
+        if known_quiet || self.summaries.is_summarized(channel_id, start).await? {
+            return Ok(());
+        }
+
+        let messages = self.read_period(channel_id, start, end).await?;
+        if messages.len() < MIN_SUMMARIZED_MESSAGES {
+            self.lock_quiet().insert(channel_id, start);
+            return Ok(());
+        }
+
+        let content = self
+            .summarizer
+            .prompt(transcript(&messages))
+            .await
+            .wrap_err("failed to summarize the messages")?;
+        let content = content.trim();
+        if content.is_empty() {
+            eyre::bail!("the summary is empty");
+        }
+
+        let description = match content.char_indices().nth(MAX_EMBED_CHARS - 1) {
+            Some((cut, _)) => format!("{}…", &content[..cut]),
+            None => content.to_string(),
+        };
+        // Stored even if it couldn't be posted, so that it's not generated
+        // again every hour
+        let posted = channel_id
+            .send_message(
+                &self.http,
+                CreateMessage::new().embed(
+                    CreateEmbed::new()
+                        .title(schedule.title(start))
+                        .description(description)
+                        .footer(CreateEmbedFooter::new(format!(
+                            "{} messages",
+                            messages.len()
+                        ))),
+                ),
+            )
+            .await
+            .inspect_err(|e| tracing::error!(?e, %channel_id, "Failed to post channel summary"))
+            .ok();
+
+        self.summaries
+            .store(NewDiscordChannelSummary {
+                channel_id: to_db_id(channel_id.get()),
+                guild_id,
+                schedule: schedule.as_str().to_string(),
+                period_start: start,
+                period_end: end,
+                content: content.to_string(),
+                message_count: i32::try_from(messages.len()).unwrap_or(i32::MAX),
+                message_id: posted.map(|m| to_db_id(m.id.get())),
+            })
+            .await?;
+
+        tracing::info!(%channel_id, messages = messages.len(), "Posted channel summary");
+        Ok(())
+    }
+
+    /// The messages with text sent within the period, oldest first
+    async fn read_period(
+        &self,
+        channel_id: ChannelId,
+        start: NaiveDateTime,
+        end: NaiveDateTime,
+    ) -> Result<Vec<Message>, eyre::Error> {
+        const PAGE_SIZE: u8 = 100;
+
+        let end_id = snowflake_at(end);
+        let mut after = snowflake_at(start);
+        let mut messages = Vec::new();
+
+        while messages.len() < MAX_SUMMARIZED_MESSAGES {
+            // The messages right after the ID, newest first
+            let page = channel_id
+                .messages(&self.http, GetMessages::new().after(after).limit(PAGE_SIZE))
+                .await
+                .wrap_err("failed to read the channel messages")?;
+            let Some(newest) = page.as_slice().first().map(|m| m.id) else {
+                break;
+            };
+            let last_page = page.len() < usize::from(PAGE_SIZE) || newest >= end_id;
+
+            messages.extend(
+                page.into_iter()
+                    .rev()
+                    .filter(|m| m.id < end_id && !m.content.trim().is_empty()),
+            );
+            if last_page {
+                break;
+            }
+            after = newest;
+        }
+
+        messages.truncate(MAX_SUMMARIZED_MESSAGES);
+        Ok(messages)
+    }
+
+    fn lock_quiet(&self) -> std::sync::MutexGuard<'_, HashMap<ChannelId, NaiveDateTime>> {
+        self.quiet.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// The messages as the summarizer reads them, the long ones cut
+fn transcript(messages: &[Message]) -> String {
+    let lines: Vec<_> = messages
+        .iter()
+        .map(|m| {
+            format!(
+                "[{}] {} (<@{}>): {}",
+                m.timestamp.format("%Y-%m-%d %H:%M"),
+                m.author.name,
+                m.author.id,
+                truncate_text(&m.content, MESSAGE_TOKENS)
+            )
+        })
+        .collect();
+    truncate_text(&lines.join("\n"), TRANSCRIPT_TOKENS).into_owned()
+}
+
+/// The smallest message ID of the time, to page the messages by their time
+fn snowflake_at(time: NaiveDateTime) -> MessageId {
+    let ms = time.and_utc().timestamp_millis() - DISCORD_EPOCH_MS;
+    MessageId::new((u64::try_from(ms).unwrap_or(0) << 22).max(1))
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone as _;
+
+    use super::*;
+
+    #[test]
+    fn the_last_period_is_the_previous_day_or_week() {
+        // A Saturday
+        let now = Utc.with_ymd_and_hms(2026, 10, 17, 10, 30, 0).unwrap();
+        let at = |d| {
+            chrono::NaiveDate::from_ymd_opt(2026, 10, d)
+                .unwrap()
+                .and_time(NaiveTime::MIN)
+        };
+
+        assert_eq!(SummarySchedule::Daily.last_period(now), (at(16), at(17)));
+        assert_eq!(SummarySchedule::Weekly.last_period(now), (at(5), at(12)));
+
+        // On Monday the week that just ended is the one summarized
+        let monday = Utc.with_ymd_and_hms(2026, 10, 12, 0, 5, 0).unwrap();
+        assert_eq!(SummarySchedule::Weekly.last_period(monday), (at(5), at(12)));
+    }
+}
@@ -0,0 +1,214 @@
+//! Keeps the Discord client running. Serenity reconnects the shards on its
+//! own when the gateway drops them, but the client stops for good when a
+//! shard can't be brought back, so it's restarted with an exponential
+//! backoff unless the error means that retrying can't help, like a revoked
+//! token.
+
+use std::{
+    collections::BTreeMap,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use serenity::{all::ConnectionStage, gateway::GatewayError};
+
+const MIN_BACKOFF: Duration = Duration::from_secs(5);
+const MAX_BACKOFF: Duration = Duration::from_secs(10 * 60);
+/// A client that ran this long before stopping is restarted without backoff
+const HEALTHY_RUN: Duration = Duration::from_secs(10 * 60);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConnectionState {
+    NotStarted,
+    Connecting,
+    Connected,
+    /// A shard lost the connection, serenity is resuming it
+    Reconnecting,
+    /// The client stopped and will be restarted
+    BackingOff,
+    /// The client stopped and won't be restarted
+    Stopped,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct ConnectionReport {
+    pub state: ConnectionState,
+    /// Stage of each shard as last reported by the gateway
+    pub shards: BTreeMap<u32, String>,
+    pub connected_since: Option<DateTime<Utc>>,
+    /// Times the client was restarted since the server started
+    pub restarts: u32,
+    pub next_restart_at: Option<DateTime<Utc>>,
+    pub last_error: Option<String>,
+    pub last_error_at: Option<DateTime<Utc>>,
+}
+
+/// The state of the connection to the gateway, shared by the supervisor and
+/// the event handler
+#[derive(Clone)]
+pub struct ConnectionStatus(Arc<Mutex<ConnectionReport>>);
+
+impl ConnectionStatus {
+    pub fn new() -> Self {
+        Self(Arc::new(Mutex::new(ConnectionReport {
+            state: ConnectionState::NotStarted,
+            shards: BTreeMap::new(),
+            connected_since: None,
+            restarts: 0,
+            next_restart_at: None,
+            last_error: None,
+            last_error_at: None,
+        })))
+    }
+
+    pub fn report(&self) -> ConnectionReport {
+        self.update(|report| report.clone())
+    }
+
+    fn update<T>(&self, f: impl FnOnce(&mut ConnectionReport) -> T) -> T {
+        let mut report = self.0.lock().unwrap_or_else(|e| e.into_inner());
+        f(&mut report)
+    }
+
+    fn connecting(&self) {
+        self.update(|report| {
+            report.state = ConnectionState::Connecting;
+            report.shards.clear();
+            report.next_restart_at = None;
+        });
+    }
+
+    /// The bot is ready, dispatched again after the shards reconnect
+    pub fn connected(&self) {
+        self.update(|report| {
+            if report.state != ConnectionState::Connected {
+                report.connected_since = Some(Utc::now());
+            }
+            report.state = ConnectionState::Connected;
+        });
+    }
+
+    pub fn shard_stage(&self, shard_id: u32, stage: ConnectionStage) {
+        self.update(|report| {
+            report.shards.insert(shard_id, stage.to_string());
+            match stage {
+                ConnectionStage::Connected => {
+                    if report.state != ConnectionState::Connected {
+                        report.connected_since = Some(Utc::now());
+                    }
+                    report.state = ConnectionState::Connected;
+                }
+                _ if report.state == ConnectionState::Connected => {
+                    report.state = ConnectionState::Reconnecting;
+                    report.connected_since = None;
+                }
+                _ => {}
+            }
+        });
+    }
+
+    fn failed(&self, error: String, restart_in: Option<Duration>) {
+        self.update(|report| {
+            report.state = match restart_in {
+                Some(_) => ConnectionState::BackingOff,
+                None => ConnectionState::Stopped,
+            };
+            report.connected_since = None;
+            report.next_restart_at = restart_in
+                .and_then(|delay| chrono::Duration::from_std(delay).ok())
+                .map(|delay| Utc::now() + delay);
+            report.last_error = Some(error);
+            report.last_error_at = Some(Utc::now());
+        });
+    }
+
+    /// The bot won't run, e.g. because it isn't configured
+    pub fn stopped(&self, reason: impl Into<String>) {
+        self.failed(reason.into(), None);
+    }
+}
+
+impl Default for ConnectionStatus {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Runs the client until it fails with an error that retrying can't fix,
+/// restarting it with a backoff whenever it stops otherwise
+pub async fn supervise<F, Fut>(status: &ConnectionStatus, mut run: F) -> Result<(), eyre::Error>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<(), serenity::Error>>,
+{
+    let mut failures = 0;
+    loop {
+        status.connecting();
+        let started = Instant::now();
+        let result = run().await;
+
+        if started.elapsed() >= HEALTHY_RUN {
+            failures = 0;
+        }
+        let error = match result {
+            Err(err) if is_fatal(&err) => {
+                status.stopped(err.to_string());
+                return Err(eyre::eyre!("the Discord client stopped for good: {err}"));
+            }
+            Err(err) => err.to_string(),
+            Ok(()) => "the client stopped".to_string(),
+        };
+
+        let delay = backoff(failures);
+        failures += 1;
+        tracing::warn!(
+            error,
+            ?delay,
+            failures,
+            "The Discord client stopped, restarting it"
+        );
+        status.failed(error, Some(delay));
+        tokio::time::sleep(delay).await;
+        status.update(|report| report.restarts += 1);
+    }
+}
+
+/// Errors that another attempt would run into again
+fn is_fatal(err: &serenity::Error) -> bool {
+    match err {
+        serenity::Error::Gateway(
+            GatewayError::InvalidAuthentication
+            | GatewayError::NoAuthentication
+            | GatewayError::InvalidGatewayIntents
+            | GatewayError::DisallowedGatewayIntents,
+        ) => true,
+        serenity::Error::Http(serenity::http::HttpError::UnsuccessfulRequest(response)) => {
+            response.status_code.as_u16() == 401
+        }
+        _ => false,
+    }
+}
+
+/// Wait before the restart following `failures` consecutive ones
+fn backoff(failures: u32) -> Duration {
+    MIN_BACKOFF
+        .saturating_mul(2u32.saturating_pow(failures))
+        .min(MAX_BACKOFF)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_backoff_doubles_up_to_the_max() {
+        assert_eq!(backoff(0), Duration::from_secs(5));
+        assert_eq!(backoff(1), Duration::from_secs(10));
+        assert_eq!(backoff(3), Duration::from_secs(40));
+        assert_eq!(backoff(7), MAX_BACKOFF);
+        assert_eq!(backoff(u32::MAX), MAX_BACKOFF);
+    }
+}
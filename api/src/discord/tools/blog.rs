@@ -0,0 +1,281 @@
+use rig::{completion::ToolDefinition, tool::Tool};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use thiserror::Error;
+
+use crate::blog::lookup::{BlogReader, CommentInfo, PostInfo};
+
+const DEFAULT_COMMENTS: u8 = 10;
+const MAX_COMMENTS: u8 = 25;
+const MAX_POSTS: i64 = 5;
+/// Comments of the post returned along with it when a single post is found
+const POST_COMMENTS: i64 = 20;
+
+#[derive(Debug, Error)]
+#[error("Blog error: {0}")]
+pub struct BlogError(String);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BlogComment {
+    pub id: i32,
+    pub post_slug: String,
+    pub post_title: Option<String>,
+    pub author: String,
+    /// Scrubbed, and cut if it's long
+    pub content: String,
+    pub is_reply: bool,
+    pub source: String,
+    /// RFC 3339 in UTC
+    pub created_at: String,
+}
+
+impl From<CommentInfo> for BlogComment {
+    fn from(value: CommentInfo) -> Self {
+        Self {
+            id: value.id,
+            post_slug: value.post_slug,
+            post_title: value.post_title,
+            author: value.author_name,
+            content: value.content,
+            is_reply: value.is_reply,
+            source: value.source,
+            created_at: value.created_at.and_utc().to_rfc3339(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BlogPost {
+    pub slug: String,
+    pub title: Option<String>,
+    pub url: String,
+    pub summary: Option<String>,
+    pub reading_time_mins: Option<i32>,
+    /// RFC 3339 in UTC, about when it was published
+    pub published_at: Option<String>,
+    pub comment_count: i64,
+    pub last_comment_at: Option<String>,
+}
+
+impl From<PostInfo> for BlogPost {
+    fn from(value: PostInfo) -> Self {
+        Self {
+            slug: value.slug,
+            title: value.title,
+            url: value.url,
+            summary: value.summary,
+            reading_time_mins: value.reading_time_mins,
+            published_at: value.federated_at.map(|t| t.and_utc().to_rfc3339()),
+            comment_count: value.comment_count,
+            last_comment_at: value.last_comment_at.map(|t| t.and_utc().to_rfc3339()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BlogRecentCommentsArgs {
+    #[serde(default)]
+    pub slug: Option<String>,
+    /// RFC 3339
+    #[serde(default)]
+    pub since: Option<String>,
+    #[serde(default)]
+    pub limit: Option<u8>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BlogRecentCommentsOutput {
+    pub success: bool,
+    pub comments: Vec<BlogComment>,
+    pub error: Option<String>,
+}
+
+impl BlogRecentCommentsOutput {
+    fn failure(error: impl Into<String>) -> Self {
+        Self {
+            success: false,
+            comments: Vec::new(),
+            error: Some(error.into()),
+        }
+    }
+}
+
+/// The latest comments left on the blog posts of the website
+#[derive(Clone)]
+pub struct BlogRecentCommentsTool {
+    pub blog: BlogReader,
+}
+
+impl Tool for BlogRecentCommentsTool {
+    const NAME: &'static str = "blog_recent_comments";
+    type Error = BlogError;
+    type Args = BlogRecentCommentsArgs;
+    type Output = BlogRecentCommentsOutput;
+
+    async fn definition(&self, _prompt: String) -> ToolDefinition {
+        ToolDefinition {
+            name: Self::NAME.to_string(),
+            description: "Get the latest comments on the owner's blog posts, newest first, from \
+                the website and the fediverse. Use it to tell what people are saying about a post \
+                or what's new since a time."
+                .to_string(),
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "slug": {
+                        "type": "string",
+                        "description": "Only the comments of this post, see blog_post_info to find its slug"
+                    },
+                    "since": {
+                        "type": "string",
+                        "description": "Only the comments after this RFC 3339 timestamp"
+                    },
+                    "limit": {
+                        "type": "integer",
+                        "description": format!("Number of comments, {DEFAULT_COMMENTS} by default and at most {MAX_COMMENTS}")
+                    }
+                }
+            }),
+        }
+    }
+
+    async fn call(&self, args: Self::Args) -> Result<Self::Output, Self::Error> {
+        let limit = args
+            .limit
+            .unwrap_or(DEFAULT_COMMENTS)
+            .clamp(1, MAX_COMMENTS);
+        let since = match args.since.as_deref().map(str::trim) {
+            Some(since) if !since.is_empty() => match chrono::DateTime::parse_from_rfc3339(since) {
+                Ok(t) => Some(t.naive_utc()),
+                Err(e) => {
+                    return Ok(BlogRecentCommentsOutput::failure(format!(
+                        "invalid since: {e}"
+                    )));
+                }
+            },
+            _ => None,
+        };
+        let slug = args
+            .slug
+            .as_deref()
+            .map(str::trim)
+            .filter(|s| !s.is_empty());
+
+        match self
+            .blog
+            .recent_comments(slug, since, i64::from(limit))
+            .await
+        {
+            Ok(comments) => Ok(BlogRecentCommentsOutput {
+                success: true,
+                comments: comments.into_iter().map(BlogComment::from).collect(),
+                error: None,
+            }),
+            Err(e) => {
+                tracing::error!(?e, "Failed to load blog comments");
+                Ok(BlogRecentCommentsOutput::failure(
+                    "failed to load the comments",
+                ))
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BlogPostInfoArgs {
+    #[serde(default)]
+    pub query: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BlogPostInfoOutput {
+    pub success: bool,
+    pub posts: Vec<BlogPost>,
+    /// Comments of the post, oldest first, when a single post is found
+    pub comments: Vec<BlogComment>,
+    pub error: Option<String>,
+}
+
+impl BlogPostInfoOutput {
+    fn failure(error: impl Into<String>) -> Self {
+        Self {
+            success: false,
+            posts: Vec::new(),
+            comments: Vec::new(),
+            error: Some(error.into()),
+        }
+    }
+}
+
+/// Finds the blog posts of the website by slug or title
+#[derive(Clone)]
+pub struct BlogPostInfoTool {
+    pub blog: BlogReader,
+}
+
+impl Tool for BlogPostInfoTool {
+    const NAME: &'static str = "blog_post_info";
+    type Error = BlogError;
+    type Args = BlogPostInfoArgs;
+    type Output = BlogPostInfoOutput;
+
+    async fn definition(&self, _prompt: String) -> ToolDefinition {
+        ToolDefinition {
+            name: Self::NAME.to_string(),
+            description: "Find the owner's blog posts by slug or part of the title, or list the \
+                latest posts without a query. When a single post matches, or the query is its \
+                slug, its comments are returned too."
+                .to_string(),
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "query": {
+                        "type": "string",
+                        "description": "Slug or part of the title of the post"
+                    }
+                }
+            }),
+        }
+    }
+
+    async fn call(&self, args: Self::Args) -> Result<Self::Output, Self::Error> {
+        let query = args
+            .query
+            .as_deref()
+            .map(str::trim)
+            .filter(|q| !q.is_empty());
+
+        let posts = match self.blog.find_posts(query, MAX_POSTS).await {
+            Ok(posts) => posts,
+            Err(e) => {
+                tracing::error!(?e, "Failed to find blog posts");
+                return Ok(BlogPostInfoOutput::failure("failed to find the posts"));
+            }
+        };
+
+        // The exact slug comes first
+        let found = match (query, posts.as_slice()) {
+            (Some(_), [post]) => Some(post),
+            (Some(query), [post, ..]) if post.slug == query => Some(post),
+            _ => None,
+        };
+        let mut comments = Vec::new();
+        if let Some(post) = found {
+            match self
+                .blog
+                .recent_comments(Some(&post.slug), None, POST_COMMENTS)
+                .await
+            {
+                Ok(recent) => comments = recent.into_iter().rev().map(BlogComment::from).collect(),
+                Err(e) => tracing::error!(?e, "Failed to load the comments of a blog post"),
+            }
+        }
+
+        Ok(BlogPostInfoOutput {
+            success: true,
+            posts: posts.into_iter().map(BlogPost::from).collect(),
+            comments,
+            error: None,
+        })
+    }
+}
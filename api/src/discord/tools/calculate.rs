@@ -0,0 +1,193 @@
+use rig::{completion::ToolDefinition, tool::Tool};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use thiserror::Error;
+
+use crate::outbound::HttpClient;
+
+/// Wolfram|Alpha answers are cut to this many characters
+const MAX_WOLFRAM_CHARS: u32 = 2000;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CalculateBackend {
+    /// Evaluate locally, falling back to Wolfram|Alpha if that fails
+    #[default]
+    Auto,
+    Local,
+    Wolfram,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CalculateArgs {
+    pub expression: String,
+    #[serde(default)]
+    pub backend: CalculateBackend,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CalculateOutput {
+    pub success: bool,
+    pub result: Option<String>,
+    /// The backend that produced the result
+    pub backend: Option<String>,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Error)]
+#[error("Calculate error: {0}")]
+pub struct CalculateError(String);
+
+/// Evaluates math expressions locally, and unit conversions or symbolic queries
+/// with Wolfram|Alpha if an app ID is configured
+#[derive(Clone)]
+pub struct CalculateTool {
+    wolfram_app_id: Option<String>,
+    client: HttpClient,
+}
+
+impl CalculateTool {
+    pub fn new(wolfram_app_id: Option<String>, client: HttpClient) -> Self {
+        Self {
+            wolfram_app_id,
+            client,
+        }
+    }
+
+    async fn wolfram(&self, app_id: &str, query: &str) -> Result<String, eyre::Error> {
+        let mut url = url::Url::parse("https://www.wolframalpha.com/api/v1/llm-api")?;
+        url.query_pairs_mut()
+            .append_pair("input", query)
+            .append_pair("appid", app_id)
+            .append_pair("maxchars", &MAX_WOLFRAM_CHARS.to_string());
+
+        let response = self
+            .client
+            .get(url)
+            .timeout(std::time::Duration::from_secs(20))
+            .send()
+            .await
+            // The URL has the app ID in it
+            .map_err(reqwest::Error::without_url)?;
+
+        let status = response.status();
+        let body = response.text().await.map_err(reqwest::Error::without_url)?;
+        if !status.is_success() {
+            // The body explains why, e.g. that the input wasn't understood
+            eyre::bail!("Wolfram|Alpha returned {status}: {body}");
+        }
+
+        Ok(body)
+    }
+}
+
+/// Evaluate an expression with meval, e.g. `2^10 / sqrt(4) + sin(pi)`
+fn evaluate_locally(expression: &str) -> Result<String, eyre::Error> {
+    // `**` is a common way to write powers that meval doesn't know
+    let value = meval::eval_str(expression.replace("**", "^"))?;
+
+    if !value.is_finite() {
+        eyre::bail!("result is not a finite number: {value}");
+    }
+
+    // Integers are printed without the noise of a float
+    if value.fract() == 0.0 && value.abs() < 1e15 {
+        Ok(format!("{}", value as i64))
+    } else {
+        Ok(format!("{value}"))
+    }
+}
+
+impl Tool for CalculateTool {
+    const NAME: &'static str = "calculate";
+    type Error = CalculateError;
+    type Args = CalculateArgs;
+    type Output = CalculateOutput;
+
+    async fn definition(&self, _prompt: String) -> ToolDefinition {
+        let wolfram = match self.wolfram_app_id {
+            Some(_) => {
+                " Unit conversions, symbolic math (e.g. integrals, solving equations) and other \
+                factual computations go to Wolfram|Alpha with the wolfram backend or as a fallback \
+                of auto, phrase those as natural language or Wolfram syntax."
+            }
+            None => "",
+        };
+
+        ToolDefinition {
+            name: Self::NAME.to_string(),
+            description: format!(
+                "Calculate a math expression exactly instead of doing the arithmetic yourself. \
+                Supports + - * / ^ %, parentheses, the constants pi and e and functions like \
+                sqrt, exp, ln, abs, sin, cos, tan, floor, ceil, round, min, max.{wolfram}"
+            ),
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "expression": {"type": "string", "description": "e.g. (1.07^10 - 1) * 100"},
+                    "backend": {"type": "string", "enum": ["auto", "local", "wolfram"], "description": "Defaults to auto"}
+                },
+                "required": ["expression"]
+            }),
+        }
+    }
+
+    async fn call(&self, args: Self::Args) -> Result<Self::Output, Self::Error> {
+        let success = |result: String, backend: &str| CalculateOutput {
+            success: true,
+            result: Some(result),
+            backend: Some(backend.to_string()),
+            error: None,
+        };
+        let failure = |error: String| CalculateOutput {
+            success: false,
+            result: None,
+            backend: None,
+            error: Some(error),
+        };
+
+        let local = match args.backend {
+            CalculateBackend::Auto | CalculateBackend::Local => {
+                match evaluate_locally(&args.expression) {
+                    Ok(result) => return Ok(success(result, "local")),
+                    Err(e) => Some(e),
+                }
+            }
+            CalculateBackend::Wolfram => None,
+        };
+
+        let wolfram_app_id = match (&self.wolfram_app_id, args.backend, local) {
+            (_, CalculateBackend::Local, Some(e)) | (None, _, Some(e)) => {
+                return Ok(failure(format!("could not evaluate expression: {e}")));
+            }
+            (None, _, None) => {
+                return Ok(failure("Wolfram|Alpha is not configured".to_string()));
+            }
+            (Some(app_id), _, _) => app_id,
+        };
+
+        match self.wolfram(wolfram_app_id, &args.expression).await {
+            Ok(result) => Ok(success(result, "wolfram")),
+            Err(e) => {
+                tracing::warn!(?e, "Wolfram|Alpha query failed");
+                Ok(failure("Wolfram|Alpha query failed".to_string()))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn evaluate_locally_formats_results() {
+        assert_eq!(
+            evaluate_locally("2 ** 10 / 4").ok(),
+            Some("256".to_string())
+        );
+        assert_eq!(evaluate_locally("1 / 4").ok(), Some("0.25".to_string()));
+        assert!(evaluate_locally("1 / 0").is_err());
+        assert!(evaluate_locally("5 miles in km").is_err());
+    }
+}
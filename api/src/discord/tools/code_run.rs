@@ -0,0 +1,360 @@
+use std::{process::Stdio, sync::Arc};
+
+use eyre::Context as _;
+use rig::{completion::ToolDefinition, tool::Tool};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use thiserror::Error;
+use tokio::io::{AsyncRead, AsyncReadExt as _, AsyncWriteExt as _};
+
+use crate::{
+    config::{CodeSandboxBackend, CodeSandboxConfig},
+    outbound::HttpClient,
+};
+
+/// Output beyond this many characters per stream is cut, it'd only bloat the context
+const MAX_OUTPUT_CHARS: usize = 4000;
+/// Read of each output stream of a local run, the rest is left unread
+const MAX_STREAM_BYTES: u64 = 64 * 1024;
+/// The only environment of a local run, the server's has its secrets
+const SANDBOX_PATH: &str = "/usr/local/bin:/usr/bin:/bin";
+
+/// Languages the local runner can execute, with the interpreter and the flag to pass
+/// the code inline
+const LOCAL_INTERPRETERS: &[(&[&str], &str, &str)] = &[
+    (&["python", "python3", "py"], "python3", "-c"),
+    (&["javascript", "js", "node"], "node", "-e"),
+    (&["bash", "sh", "shell"], "bash", "-c"),
+];
+
+/// Runs code snippets in a sandbox with time and memory limits
+#[derive(Clone)]
+pub struct CodeRunner {
+    config: Arc<CodeSandboxConfig>,
+    client: HttpClient,
+}
+
+struct RunResult {
+    language: String,
+    stdout: String,
+    stderr: String,
+    exit_code: Option<i32>,
+}
+
+impl CodeRunner {
+    pub fn new(config: CodeSandboxConfig, client: HttpClient) -> Self {
+        Self {
+            config: Arc::new(config),
+            client,
+        }
+    }
+
+    async fn run(&self, language: &str, code: &str, stdin: &str) -> Result<RunResult, eyre::Error> {
+        match &self.config.backend {
+            CodeSandboxBackend::Piston { url } => self.run_piston(url, language, code, stdin).await,
+            CodeSandboxBackend::Firejail => self.run_firejail(language, code, stdin).await,
+        }
+    }
+
+    async fn run_piston(
+        &self,
+        url: &str,
+        language: &str,
+        code: &str,
+        stdin: &str,
+    ) -> Result<RunResult, eyre::Error> {
+        #[derive(Deserialize)]
+        struct Response {
+            language: String,
+            version: String,
+            #[serde(default)]
+            compile: Option<Stage>,
+            run: Stage,
+        }
+
+        #[derive(Deserialize)]
+        struct Stage {
+            stdout: String,
+            stderr: String,
+            code: Option<i32>,
+            signal: Option<String>,
+        }
+
+        let response = self
+            .client
+            .post(format!("{}/execute", url.trim_end_matches('/')))
+            .json(&json!({
+                "language": language,
+                "version": "*",
+                "files": [{ "content": code }],
+                "stdin": stdin,
+                "compile_timeout": self.config.timeout.as_millis(),
+                "run_timeout": self.config.timeout.as_millis(),
+                "run_memory_limit": self.config.memory_limit_mb * 1024 * 1024,
+            }))
+            // Compiling and running may both take the whole sandbox timeout
+            .timeout(self.config.timeout * 2 + std::time::Duration::from_secs(10))
+            .send()
+            .await
+            .wrap_err("failed to send Piston request")?;
+
+        if !response.status().is_success() {
+            // Piston explains e.g. unknown languages in the body
+            let status = response.status();
+            let body = response.text().await.unwrap_or_default();
+            eyre::bail!("Piston returned {status}: {body}");
+        }
+
+        let response: Response = response
+            .json()
+            .await
+            .wrap_err("failed to parse Piston response")?;
+
+        // Failed compilations don't run
+        if let Some(compile) = response.compile.filter(|c| c.code != Some(0)) {
+            return Ok(RunResult {
+                language: format!("{} {}", response.language, response.version),
+                stdout: compile.stdout,
+                stderr: compile.stderr,
+                exit_code: compile.code,
+            });
+        }
+
+        let mut stderr = response.run.stderr;
+        if let Some(signal) = response.run.signal {
+            // SIGKILL is what the time and memory limits result in
+            stderr.push_str(&format!(
+                "\n[killed by {signal}, time or memory limit exceeded?]"
+            ));
+        }
+
+        Ok(RunResult {
+            language: format!("{} {}", response.language, response.version),
+            stdout: response.run.stdout,
+            stderr,
+            exit_code: response.run.code,
+        })
+    }
+
+    /// Run the code with a local interpreter in a firejail sandbox without
+    /// network access or a view of the home directory
+    async fn run_firejail(
+        &self,
+        language: &str,
+        code: &str,
+        stdin: &str,
+    ) -> Result<RunResult, eyre::Error> {
+        let language = language.trim().to_lowercase();
+        let Some((_, interpreter, inline_flag)) = LOCAL_INTERPRETERS
+            .iter()
+            .find(|(names, _, _)| names.contains(&language.as_str()))
+        else {
+            eyre::bail!(
+                "unsupported language `{language}`, supported: {}",
+                LOCAL_INTERPRETERS
+                    .iter()
+                    .map(|(_, interpreter, _)| *interpreter)
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            );
+        };
+
+        let mut child = tokio::process::Command::new("firejail")
+            .args([
+                "--quiet",
+                "--net=none",
+                "--private",
+                "--nogroups",
+                "--noroot",
+                &format!("--rlimit-as={}", self.config.memory_limit_mb * 1024 * 1024),
+                interpreter,
+                inline_flag,
+                code,
+            ])
+            .env_clear()
+            .env("PATH", SANDBOX_PATH)
+            .env("LANG", "C.UTF-8")
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()
+            .wrap_err("failed to start firejail")?;
+
+        if let Some(mut child_stdin) = child.stdin.take() {
+            // The program may exit without reading its input
+            let _ = child_stdin.write_all(stdin.as_bytes()).await;
+        }
+
+        let (stdout, stderr) = (child.stdout.take(), child.stderr.take());
+        let (stdout, stderr, status) = tokio::time::timeout(self.config.timeout, async {
+            tokio::join!(read_capped(stdout), read_capped(stderr), child.wait())
+        })
+        .await
+        .map_err(|_| eyre::eyre!("timed out after {} seconds", self.config.timeout.as_secs()))?;
+        let status = status.wrap_err("failed to run firejail")?;
+
+        Ok(RunResult {
+            language: interpreter.to_string(),
+            stdout: String::from_utf8_lossy(&stdout).into_owned(),
+            stderr: String::from_utf8_lossy(&stderr).into_owned(),
+            exit_code: status.code(),
+        })
+    }
+}
+
+/// At most [MAX_STREAM_BYTES] of the stream, which is closed after so that a
+/// program printing more gets a broken pipe
+async fn read_capped(stream: Option<impl AsyncRead + Unpin>) -> Vec<u8> {
+    let mut output = Vec::new();
+    if let Some(stream) = stream {
+        // What was read before an error is still worth showing
+        let _ = stream.take(MAX_STREAM_BYTES).read_to_end(&mut output).await;
+    }
+    output
+}
+
+pub(super) fn truncate_output(output: &str) -> (String, bool) {
+    match output.char_indices().nth(MAX_OUTPUT_CHARS) {
+        Some((n, _)) => (format!("{}\n[output truncated]", &output[..n]), true),
+        None => (output.to_string(), false),
+    }
+}
+
+#[derive(Clone)]
+pub struct CodeRunTool {
+    pub runner: CodeRunner,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CodeRunArgs {
+    pub language: String,
+    pub code: String,
+    #[serde(default)]
+    pub stdin: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CodeRunOutput {
+    pub success: bool,
+    pub language: Option<String>,
+    pub stdout: String,
+    pub stderr: String,
+    pub exit_code: Option<i32>,
+    pub truncated: bool,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Error)]
+#[error("Code run error: {0}")]
+pub struct CodeRunError(String);
+
+impl Tool for CodeRunTool {
+    const NAME: &'static str = "code_run";
+    type Error = CodeRunError;
+    type Args = CodeRunArgs;
+    type Output = CodeRunOutput;
+
+    async fn definition(&self, _prompt: String) -> ToolDefinition {
+        let languages = match &self.runner.config.backend {
+            CodeSandboxBackend::Piston { .. } => {
+                "Most languages are supported, e.g. python, javascript, typescript, ruby, go, bash"
+                    .to_string()
+            }
+            CodeSandboxBackend::Firejail => format!(
+                "Supported languages: {}",
+                LOCAL_INTERPRETERS
+                    .iter()
+                    .filter_map(|(names, _, _)| names.first().copied())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+        };
+
+        ToolDefinition {
+            name: Self::NAME.to_string(),
+            description: format!(
+                "Run a code snippet in a sandbox without network access and return its output. \
+                Prefer this over Godbolt for scripting languages. Runs are limited to {} seconds \
+                and {} MB of memory. {languages}.",
+                self.runner.config.timeout.as_secs(),
+                self.runner.config.memory_limit_mb
+            ),
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "language": {
+                        "type": "string",
+                        "description": "Language of the code, e.g. python"
+                    },
+                    "code": {
+                        "type": "string",
+                        "description": "The complete program to run"
+                    },
+                    "stdin": {
+                        "type": "string",
+                        "description": "Input passed to the program's stdin"
+                    }
+                },
+                "required": ["language", "code"]
+            }),
+        }
+    }
+
+    async fn call(&self, args: Self::Args) -> Result<Self::Output, Self::Error> {
+        match self
+            .runner
+            .run(
+                &args.language,
+                &args.code,
+                args.stdin.as_deref().unwrap_or(""),
+            )
+            .await
+        {
+            Ok(result) => {
+                let (stdout, stdout_truncated) = truncate_output(&result.stdout);
+                let (stderr, stderr_truncated) = truncate_output(&result.stderr);
+
+                Ok(CodeRunOutput {
+                    success: result.exit_code == Some(0),
+                    language: Some(result.language),
+                    stdout,
+                    stderr,
+                    exit_code: result.exit_code,
+                    truncated: stdout_truncated || stderr_truncated,
+                    error: None,
+                })
+            }
+            Err(e) => {
+                tracing::warn!(?e, "Failed to run code");
+                Ok(CodeRunOutput {
+                    success: false,
+                    language: None,
+                    stdout: String::new(),
+                    stderr: String::new(),
+                    exit_code: None,
+                    truncated: false,
+                    error: Some(e.to_string()),
+                })
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn truncate_output_cuts_long_output() {
+        assert_eq!(truncate_output("hello"), ("hello".to_string(), false));
+
+        let (output, truncated) = truncate_output(&"é".repeat(MAX_OUTPUT_CHARS + 1));
+        assert!(truncated);
+        assert!(output.ends_with("[output truncated]"));
+        assert_eq!(
+            output.chars().filter(|c| *c == 'é').count(),
+            MAX_OUTPUT_CHARS
+        );
+    }
+}
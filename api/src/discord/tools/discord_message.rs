@@ -1,14 +1,23 @@
 use rig::{completion::ToolDefinition, tool::Tool};
 use serde::{Deserialize, Serialize};
 use serde_json::json;
-use serenity::all::{ChannelId, Context, CreateMessage, MessageId};
+use serenity::all::{ChannelId, Context, CreateMessage, EditMessage, MessageId};
 use std::sync::Arc;
 use thiserror::Error;
 
+use crate::discord::{
+    repetition::{Check, RecentReplies},
+    streaming::StreamingReplies,
+};
+
 #[derive(Debug, Clone)]
 pub struct DiscordSendMessageTool {
     pub ctx: Arc<Context>,
     pub channel_id: ChannelId,
+    /// Set if the channel streams responses, in which case the message may already exist as a
+    /// placeholder
+    pub streaming: Option<StreamingReplies>,
+    pub replies: RecentReplies,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -59,13 +68,53 @@ impl Tool for DiscordSendMessageTool {
     }
 
     async fn call(&self, args: Self::Args) -> Result<Self::Output, Self::Error> {
+        // Read before any await, the cache reference isn't Send
+        let bot_id = self.ctx.cache.current_user().id;
+        // The reply is sent anyway if it can't be compared
+        let reply = match self
+            .replies
+            .check(&self.ctx.http, bot_id, self.channel_id, &args.content)
+            .await
+        {
+            Ok(Check::Repeated(previous)) => {
+                tracing::debug!(content = args.content, "Suppressed a repeated reply");
+                return Ok(DiscordSendMessageOutput {
+                    success: false,
+                    message_id: None,
+                    error: Some(format!(
+                        "Not sent, it's too close to what you recently said: \"{previous}\". Say something new or don't reply."
+                    )),
+                });
+            }
+            Ok(Check::New(reply)) => reply,
+            Err(e) => {
+                tracing::warn!(?e, "Failed to compare the reply with the recent ones");
+                None
+            }
+        };
+
         // Clone values to move into the spawned task
         let ctx = self.ctx.clone();
         let channel_id = self.channel_id;
         let content = args.content.clone();
+        let placeholder = self.streaming.as_ref().and_then(|s| s.take_placeholder());
 
         // Spawn the Discord API operations in a separate task to avoid Sync issues
         let handle = tokio::spawn(async move {
+            if let Some(placeholder) = placeholder {
+                // A placeholder can't be turned into a reply, replace it instead
+                if args.reply_to_message_id.is_none() {
+                    return channel_id
+                        .edit_message(&ctx.http, placeholder, EditMessage::new().content(&content))
+                        .await;
+                }
+
+                let _ = channel_id
+                    .delete_message(&ctx.http, placeholder)
+                    .await
+                    .inspect_err(|e| tracing::error!(?e, "Failed to delete streaming placeholder"));
+            }
+
             let mut message_builder = CreateMessage::new().content(&content);
 
             if let Some(reply_to_message_id) = args.reply_to_message_id
@@ -90,6 +139,9 @@ impl Tool for DiscordSendMessageTool {
         match handle.await {
             Ok(Ok(sent_message)) => {
                 tracing::debug!("Sent Discord message: {}", args.content);
+                if let Some(reply) = reply {
+                    self.replies.record(self.channel_id, reply);
+                }
                 Ok(DiscordSendMessageOutput {
                     success: true,
                     message_id: Some(sent_message.id.get()),
@@ -0,0 +1,136 @@
+use rig::{completion::ToolDefinition, tool::Tool};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use thiserror::Error;
+
+use crate::recommendation::{FeedReader, RankingPreset, SourceFilter, discussion_url};
+
+const DEFAULT_ITEMS: u8 = 5;
+const MAX_ITEMS: u8 = 10;
+
+#[derive(Debug, Error)]
+#[error("Feed error: {0}")]
+pub struct FeedError(String);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FeedTopArgs {
+    #[serde(default)]
+    pub limit: Option<u8>,
+    #[serde(default)]
+    pub ranking: RankingPreset,
+    #[serde(default)]
+    pub source: SourceFilter,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FeedTopOutput {
+    pub success: bool,
+    pub items: Vec<FeedTopItem>,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FeedTopItem {
+    pub title: String,
+    pub url: String,
+    /// RFC 3339 in UTC, when it was first submitted to a source
+    pub submitted_at: Option<String>,
+    /// Only a teaser of the article can be read
+    pub paywalled: bool,
+    pub sources: Vec<FeedTopSource>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FeedTopSource {
+    pub source: String,
+    /// Points on the source
+    pub score: Option<f64>,
+    pub discussion_url: Option<String>,
+}
+
+/// The top of the reading feed of the website, ranked from the links
+/// submitted to Hacker News and Lobsters
+#[derive(Clone)]
+pub struct FeedTopTool {
+    pub feed: FeedReader,
+}
+
+impl Tool for FeedTopTool {
+    const NAME: &'static str = "feed_top";
+    type Error = FeedError;
+    type Args = FeedTopArgs;
+    type Output = FeedTopOutput;
+
+    async fn definition(&self, _prompt: String) -> ToolDefinition {
+        ToolDefinition {
+            name: Self::NAME.to_string(),
+            description: "Get the top articles of the owner's reading feed, which ranks the \
+                links submitted to Hacker News and Lobsters by freshness, points and similarity \
+                to what the owner reads. Use it for \"anything good to read?\" rather than \
+                searching the web."
+                .to_string(),
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "limit": {
+                        "type": "integer",
+                        "description": format!("Number of articles, {DEFAULT_ITEMS} by default and at most {MAX_ITEMS}")
+                    },
+                    "ranking": {
+                        "type": "string",
+                        "enum": RankingPreset::ALL.map(RankingPreset::as_str),
+                        "description": "balanced by default, newer_first for what's new, top_first for the most upvoted, similar_first for the closest to the owner's reading history"
+                    },
+                    "source": {
+                        "type": "string",
+                        "enum": ["all", "hacker_news", "lobsters"],
+                        "description": "Only rank the articles of one source, all by default"
+                    }
+                }
+            }),
+        }
+    }
+
+    async fn call(&self, args: Self::Args) -> Result<Self::Output, Self::Error> {
+        let limit = args.limit.unwrap_or(DEFAULT_ITEMS).clamp(1, MAX_ITEMS);
+
+        let items = match self
+            .feed
+            .top(i64::from(limit), args.source, args.ranking)
+            .await
+        {
+            Ok(items) => items,
+            Err(e) => {
+                tracing::error!(?e, "Failed to read the feed");
+                return Ok(FeedTopOutput {
+                    success: false,
+                    items: Vec::new(),
+                    error: Some("failed to read the feed".to_string()),
+                });
+            }
+        };
+
+        Ok(FeedTopOutput {
+            success: true,
+            items: items
+                .into_iter()
+                .map(|item| FeedTopItem {
+                    sources: item
+                        .sources
+                        .iter()
+                        .map(|source| FeedTopSource {
+                            source: source.key.clone(),
+                            score: source.score,
+                            discussion_url: discussion_url(source),
+                        })
+                        .collect(),
+                    title: item.title,
+                    url: item.url,
+                    submitted_at: item.submitted_at.map(|t| t.and_utc().to_rfc3339()),
+                    paywalled: item.paywalled,
+                })
+                .collect(),
+            error: None,
+        })
+    }
+}
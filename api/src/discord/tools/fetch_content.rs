@@ -1,11 +1,21 @@
-use crate::discord::constants::URL_FETCH_TIMEOUT_SECS;
+use std::{sync::Arc, time::Duration};
+
+use crate::{discord::constants::URL_FETCH_TIMEOUT_SECS, outbound::HttpClient, ssrf};
 use rig::{completion::ToolDefinition, tool::Tool};
 use serde::{Deserialize, Serialize};
 use serde_json::json;
 use thiserror::Error;
 
-#[derive(Debug, Clone)]
-pub struct FetchPageContentTool;
+/// Pages are cached for a while since the agent tends to fetch the same link
+/// several times in a conversation
+const CACHE_TTL: Duration = Duration::from_secs(10 * 60);
+const CACHE_CAPACITY: usize = 128;
+
+#[derive(Clone)]
+pub struct FetchPageContentTool {
+    client: HttpClient,
+    cache: Arc<retainer::Cache<String, String>>,
+}
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct FetchPageContentArgs {
@@ -31,10 +41,10 @@ impl Tool for FetchPageContentTool {
 
     async fn definition(&self, _prompt: String) -> ToolDefinition {
         ToolDefinition {
-            name: "fetch_page_content".to_string(),
-            description:
-                "Fetch and parse content from a web page URL. Returns the main content as text."
-                    .to_string(),
+            name: Self::NAME.to_string(),
+            description: "Fetch and parse content from a public web page URL. Returns the main \
+                content as text."
+                .to_string(),
             parameters: json!({
                 "type": "object",
                 "properties": {
@@ -49,7 +59,7 @@ impl Tool for FetchPageContentTool {
     }
 
     async fn call(&self, args: Self::Args) -> Result<Self::Output, Self::Error> {
-        match fetch_url_content_and_parse(&args.url).await {
+        match self.fetch(args.url.trim()).await {
             Ok(content) => Ok(FetchPageContentOutput {
                 content,
                 success: true,
@@ -64,27 +74,77 @@ impl Tool for FetchPageContentTool {
     }
 }
 
-/// Fetches content from a URL, attempts to convert HTML to Markdown.
-async fn fetch_url_content_and_parse(url_str: &str) -> Result<String, eyre::Error> {
-    use article_scraper::{ArticleScraper, Readability};
-    use reqwest::Client;
-    use url::Url;
+impl FetchPageContentTool {
+    pub fn new(client: HttpClient) -> Self {
+        Self {
+            client,
+            cache: Arc::new(retainer::Cache::new()),
+        }
+    }
+
+    /// The readable content of a page, cached for the tool and the link previews
+    pub async fn fetch(&self, url_str: &str) -> Result<String, eyre::Error> {
+        if let Some(cached) = self.cache.get(url_str).await {
+            return Ok(cached.clone());
+        }
 
-    let scraper = ArticleScraper::new(None).await;
-    let url = Url::parse(url_str)?;
-    let client = Client::builder().timeout(URL_FETCH_TIMEOUT_SECS).build()?;
+        let content = fetch_url_content_and_parse(&self.client, url_str).await?;
+
+        if self.cache.len().await >= CACHE_CAPACITY {
+            self.cache.purge(CACHE_CAPACITY, 0.0).await;
+            if self.cache.len().await >= CACHE_CAPACITY {
+                self.cache.clear().await;
+            }
+        }
+        self.cache
+            .insert(url_str.to_string(), content.clone(), CACHE_TTL)
+            .await;
 
-    let article = scraper
-        .parse(&url, &client)
+        Ok(content)
+    }
+}
+
+/// Fetches content from a URL, attempts to convert HTML to Markdown.
+async fn fetch_url_content_and_parse(
+    client: &HttpClient,
+    url_str: &str,
+) -> Result<String, eyre::Error> {
+    use article_scraper::{FullTextParser, Readability};
+
+    let url = url::Url::parse(url_str)?;
+    // Shorter than the default since the agent waits for it
+    let page = tokio::time::timeout(URL_FETCH_TIMEOUT_SECS, ssrf::fetch(client, &url))
         .await
-        .map_err(|e| eyre::eyre!("Failed to scrape article for {url_str}: {e}"))?;
+        .map_err(|_| eyre::eyre!("timed out fetching {url}"))??;
+
+    match page.content_type.as_deref() {
+        Some(ct) if ct.starts_with("text/html") || ct.starts_with("application/xhtml") => {}
+        // Plain text, JSON, etc. are readable as is
+        Some(ct) if ct.starts_with("text/") || ct.contains("json") || ct.contains("xml") => {
+            return Ok(page.body);
+        }
+        Some(ct) => eyre::bail!("unsupported content type {ct}"),
+        None => {}
+    }
+
+    // The parser can panic internally, so run it in a separate task
+    let article = {
+        let page_url = page.url.clone();
+        tokio::spawn(async move {
+            FullTextParser::new(None)
+                .await
+                .parse_offline(vec![page.body], None, Some(page_url))
+        })
+        .await?
+        .map_err(|e| eyre::eyre!("Failed to parse article for {url_str}: {e}"))?
+    };
 
     let mut result = String::new();
     if let Some(title) = article.title {
         result.push_str(&format!("# {}\n\n", title.trim()));
     }
     if let Some(html) = article.html {
-        let content = Readability::extract(&html, None).await?;
+        let content = Readability::extract(&html, Some(page.url)).await?;
         result.push_str(&content);
     }
     if result.is_empty() {
@@ -0,0 +1,394 @@
+use std::sync::Arc;
+
+use rig::{completion::ToolDefinition, tool::Tool};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use thiserror::Error;
+
+use crate::{config::GitHubSearchConfig, outbound::HttpClient};
+
+const API_URL: &str = "https://api.github.com";
+
+/// Results requested from GitHub per search
+const PER_PAGE: u8 = 10;
+
+/// Rough budget of the whole output, at ~4 characters per token
+const MAX_OUTPUT_TOKENS: usize = 2000;
+
+/// Snippets are cut to this many characters each
+const MAX_SNIPPET_CHARS: usize = 400;
+
+/// Qualifiers that would let a query escape the allowed scopes
+const SCOPE_QUALIFIERS: &[&str] = &["repo:", "user:", "org:", "owner:"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum GitHubSearchKind {
+    Code,
+    Issues,
+    Prs,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GitHubSearchArgs {
+    pub query: String,
+    pub kind: GitHubSearchKind,
+    /// Narrow the search to one of the allowed repos, as owner/name
+    #[serde(default)]
+    pub repo: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GitHubSearchResult {
+    pub repo: String,
+    /// File path for code, title for issues and PRs
+    pub title: String,
+    pub url: String,
+    /// Open or closed, for issues and PRs
+    pub state: Option<String>,
+    pub snippet: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GitHubSearchOutput {
+    pub success: bool,
+    pub total_count: u64,
+    pub results: Vec<GitHubSearchResult>,
+    /// Whether results were left out to stay within the output budget
+    pub truncated: bool,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Error)]
+#[error("GitHub search error: {0}")]
+pub struct GitHubSearchError(String);
+
+/// Searches code, issues and PRs of the allowed GitHub accounts and repos
+#[derive(Clone)]
+pub struct GitHubSearchTool {
+    config: Arc<GitHubSearchConfig>,
+    client: HttpClient,
+}
+
+#[derive(Deserialize)]
+struct SearchResponse<T> {
+    total_count: u64,
+    items: Vec<T>,
+}
+
+#[derive(Deserialize)]
+struct TextMatch {
+    fragment: String,
+}
+
+#[derive(Deserialize)]
+struct CodeItem {
+    path: String,
+    html_url: String,
+    repository: Repository,
+    #[serde(default)]
+    text_matches: Vec<TextMatch>,
+}
+
+#[derive(Deserialize)]
+struct Repository {
+    full_name: String,
+}
+
+#[derive(Deserialize)]
+struct IssueItem {
+    title: String,
+    html_url: String,
+    // e.g. https://api.github.com/repos/owner/name
+    repository_url: String,
+    state: String,
+    #[serde(default)]
+    body: Option<String>,
+    #[serde(default)]
+    text_matches: Vec<TextMatch>,
+}
+
+impl GitHubSearchTool {
+    pub fn new(config: GitHubSearchConfig, client: HttpClient) -> Self {
+        Self {
+            config: Arc::new(config),
+            client,
+        }
+    }
+
+    /// The query with the scope qualifiers of the allowed repos, or of the
+    /// requested one if it's allowed
+    fn scoped_query(&self, args: &GitHubSearchArgs) -> Result<String, String> {
+        let terms = args
+            .query
+            .split_whitespace()
+            .filter(|term| {
+                let term = term.trim_start_matches('-').to_lowercase();
+                !SCOPE_QUALIFIERS.iter().any(|q| term.starts_with(q))
+            })
+            .collect::<Vec<_>>()
+            .join(" ");
+
+        if terms.is_empty() {
+            return Err("query is empty".to_string());
+        }
+
+        let scopes = match args.repo.as_deref().map(str::trim) {
+            Some(repo) => {
+                let allowed = self.config.scopes.iter().any(|scope| {
+                    scope.eq_ignore_ascii_case(repo)
+                        || repo
+                            .split_once('/')
+                            .is_some_and(|(owner, _)| scope.eq_ignore_ascii_case(owner))
+                });
+                if !allowed || !is_repo_name(repo) {
+                    return Err(format!(
+                        "repo {repo} is not allowed, allowed: {}",
+                        self.config.scopes.join(", ")
+                    ));
+                }
+                vec![format!("repo:{repo}")]
+            }
+            None => self
+                .config
+                .scopes
+                .iter()
+                .map(|scope| {
+                    if scope.contains('/') {
+                        format!("repo:{scope}")
+                    } else {
+                        format!("user:{scope}")
+                    }
+                })
+                .collect(),
+        };
+
+        let kind = match args.kind {
+            GitHubSearchKind::Code => "",
+            GitHubSearchKind::Issues => " is:issue",
+            GitHubSearchKind::Prs => " is:pr",
+        };
+
+        Ok(format!("{terms}{kind} {}", scopes.join(" ")))
+    }
+
+    async fn search<T: serde::de::DeserializeOwned>(
+        &self,
+        endpoint: &str,
+        query: &str,
+    ) -> Result<SearchResponse<T>, eyre::Error> {
+        let mut url = url::Url::parse(&format!("{API_URL}/search/{endpoint}"))?;
+        url.query_pairs_mut()
+            .append_pair("q", query)
+            .append_pair("per_page", &PER_PAGE.to_string());
+
+        let response = self
+            .client
+            .get(url)
+            .bearer_auth(&self.config.token)
+            // Includes the matched fragments
+            .header(
+                reqwest::header::ACCEPT,
+                "application/vnd.github.text-match+json",
+            )
+            .header(reqwest::header::USER_AGENT, "wrx.sh-api/1.0")
+            .header("X-GitHub-Api-Version", "2022-11-28")
+            .send()
+            .await?;
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let body = response.text().await.unwrap_or_default();
+            eyre::bail!("GitHub returned {status}: {body}");
+        }
+
+        Ok(response.json().await?)
+    }
+}
+
+fn trim_snippet(text: &str) -> String {
+    let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    match text.char_indices().nth(MAX_SNIPPET_CHARS) {
+        Some((n, _)) => format!("{}...", &text[..n]),
+        None => text,
+    }
+}
+
+/// Keep the leading results that fit in the output budget
+fn fit_to_budget(results: Vec<GitHubSearchResult>) -> (Vec<GitHubSearchResult>, bool) {
+    let budget = MAX_OUTPUT_TOKENS * 4;
+    let total = results.len();
+
+    let mut used = 0;
+    let fitting: Vec<_> = results
+        .into_iter()
+        .take_while(|r| {
+            used += r.repo.len() + r.title.len() + r.url.len() + r.snippet.len();
+            used <= budget
+        })
+        .collect();
+
+    let truncated = fitting.len() < total;
+    (fitting, truncated)
+}
+
+impl Tool for GitHubSearchTool {
+    const NAME: &'static str = "github_search";
+    type Error = GitHubSearchError;
+    type Args = GitHubSearchArgs;
+    type Output = GitHubSearchOutput;
+
+    async fn definition(&self, _prompt: String) -> ToolDefinition {
+        ToolDefinition {
+            name: Self::NAME.to_string(),
+            description: format!(
+                "Search code, issues or pull requests on GitHub, limited to these accounts and \
+                repos: {}. Use it to answer questions about these projects, then fetch_page_content \
+                the result URLs for details. Supports GitHub search syntax except scope \
+                qualifiers (repo:, user:, org:), use the repo parameter instead.",
+                self.config.scopes.join(", ")
+            ),
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "query": {"type": "string", "description": "Search terms, e.g. `fn main language:rust` or `crash is:open`"},
+                    "kind": {"type": "string", "enum": ["code", "issues", "prs"]},
+                    "repo": {"type": "string", "description": "Only search this repo (owner/name), must be within the allowed ones"}
+                },
+                "required": ["query", "kind"]
+            }),
+        }
+    }
+
+    async fn call(&self, args: Self::Args) -> Result<Self::Output, Self::Error> {
+        let failure = |error: String| GitHubSearchOutput {
+            success: false,
+            total_count: 0,
+            results: Vec::new(),
+            truncated: false,
+            error: Some(error),
+        };
+
+        let query = match self.scoped_query(&args) {
+            Ok(query) => query,
+            Err(e) => return Ok(failure(e)),
+        };
+
+        let results = match args.kind {
+            GitHubSearchKind::Code => self.search::<CodeItem>("code", &query).await.map(|r| {
+                (
+                    r.total_count,
+                    r.items
+                        .into_iter()
+                        .map(|item| GitHubSearchResult {
+                            repo: item.repository.full_name,
+                            title: item.path,
+                            url: item.html_url,
+                            state: None,
+                            snippet: trim_snippet(
+                                &item
+                                    .text_matches
+                                    .iter()
+                                    .map(|m| m.fragment.as_str())
+                                    .collect::<Vec<_>>()
+                                    .join(" … "),
+                            ),
+                        })
+                        .collect::<Vec<_>>(),
+                )
+            }),
+            GitHubSearchKind::Issues | GitHubSearchKind::Prs => {
+                self.search::<IssueItem>("issues", &query).await.map(|r| {
+                    (
+                        r.total_count,
+                        r.items
+                            .into_iter()
+                            .map(|item| GitHubSearchResult {
+                                repo: item
+                                    .repository_url
+                                    .trim_start_matches(&format!("{API_URL}/repos/"))
+                                    .to_string(),
+                                title: item.title,
+                                url: item.html_url,
+                                state: Some(item.state),
+                                snippet: trim_snippet(&match item.text_matches.first() {
+                                    Some(m) => m.fragment.clone(),
+                                    None => item.body.unwrap_or_default(),
+                                }),
+                            })
+                            .collect::<Vec<_>>(),
+                    )
+                })
+            }
+        };
+
+        match results {
+            Ok((total_count, results)) => {
+                let (results, truncated) = fit_to_budget(results);
+                Ok(GitHubSearchOutput {
+                    success: true,
+                    total_count,
+                    results,
+                    truncated,
+                    error: None,
+                })
+            }
+            Err(e) => {
+                tracing::warn!(?e, "GitHub search failed");
+                Ok(failure(e.to_string()))
+            }
+        }
+    }
+}
+
+/// Exactly `owner/name`, anything else could add qualifiers to the query
+fn is_repo_name(repo: &str) -> bool {
+    let valid = |part: &str| {
+        !part.is_empty()
+            && part
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+    };
+    repo.split_once('/')
+        .is_some_and(|(owner, name)| valid(owner) && valid(name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scoped_query_enforces_allowed_scopes() {
+        let tool = GitHubSearchTool::new(
+            GitHubSearchConfig {
+                token: String::new(),
+                scopes: vec!["wonrax".to_string(), "rust-lang/rust".to_string()],
+            },
+            HttpClient::new(&Default::default())
+                .expect("HTTP client should be correctly constructed"),
+        );
+        let args = |query: &str, repo: Option<&str>| GitHubSearchArgs {
+            query: query.to_string(),
+            kind: GitHubSearchKind::Issues,
+            repo: repo.map(str::to_string),
+        };
+
+        assert_eq!(
+            tool.scoped_query(&args("crash repo:evil/repo -org:x", None)),
+            Ok("crash is:issue user:wonrax repo:rust-lang/rust".to_string())
+        );
+        assert_eq!(
+            tool.scoped_query(&args("crash", Some("wonrax/website"))),
+            Ok("crash is:issue repo:wonrax/website".to_string())
+        );
+        assert!(
+            tool.scoped_query(&args("crash", Some("rust-lang/cargo")))
+                .is_err()
+        );
+        assert!(
+            tool.scoped_query(&args("crash", Some("wonrax/x repo:someone/private")))
+                .is_err()
+        );
+        assert!(tool.scoped_query(&args("user:someone", None)).is_err());
+    }
+}
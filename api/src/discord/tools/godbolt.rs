@@ -1,10 +1,16 @@
+use std::sync::Arc;
+
 use rig::{completion::ToolDefinition, tool::Tool};
 use serde::{Deserialize, Serialize};
 use serde_json::json;
 use thiserror::Error;
 
-#[derive(Debug, Clone)]
-pub struct Godbolt;
+use crate::clients::{CompileResponse, GodboltClient};
+
+#[derive(Clone)]
+pub struct Godbolt {
+    pub client: Arc<dyn GodboltClient>,
+}
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CompileArgs {
@@ -83,27 +89,8 @@ pub struct InstructionArgs {
 #[error("Godbolt error: {0}")]
 pub struct GodboltError(String);
 
-const BASE_URL: &str = "https://godbolt.org";
-
-impl Godbolt {
-    fn client() -> reqwest::Client {
-        use reqwest::header::{ACCEPT, CONTENT_TYPE, HeaderMap, HeaderValue, USER_AGENT};
-        let mut headers = HeaderMap::new();
-        headers.insert(
-            ACCEPT,
-            HeaderValue::from_static("application/json, text/plain;q=0.8, */*;q=0.5"),
-        );
-        headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
-        headers.insert(
-            USER_AGENT,
-            HeaderValue::from_static("wrxsh-bot/1.0 (+https://wrx.sh)"),
-        );
-        reqwest::Client::builder()
-            .default_headers(headers)
-            .timeout(std::time::Duration::from_secs(20))
-            .build()
-            .expect("client")
-    }
+fn godbolt_error(e: eyre::Error) -> GodboltError {
+    GodboltError(format!("{e:#}"))
 }
 
 impl Tool for Godbolt {
@@ -134,7 +121,6 @@ impl Tool for Godbolt {
     }
 
     async fn call(&self, args: Self::Args) -> Result<Self::Output, Self::Error> {
-        let client = Self::client();
         let execute = args.execute;
         let default_filters = json!({
             "binary": false,
@@ -164,102 +150,86 @@ impl Tool for Godbolt {
             "userArguments": args.user_arguments.clone().unwrap_or_default()
         });
 
-        let url = format!("{BASE_URL}/api/compiler/{}/compile", args.compiler_id);
-        let res = client
-            .post(url)
-            .json(&payload)
-            .send()
+        let response = self
+            .client
+            .compile(&args.compiler_id, &payload)
             .await
-            .map_err(|e| GodboltError(e.to_string()))?;
-        let ct = res
-            .headers()
-            .get(reqwest::header::CONTENT_TYPE)
-            .and_then(|v| v.to_str().ok())
-            .unwrap_or("")
-            .to_string();
+            .map_err(godbolt_error)?;
         let compiler = args.compiler_id.clone();
         let flags = args.user_arguments.unwrap_or_default();
         let libs = args.libraries.unwrap_or_default();
 
-        if ct.starts_with("application/json") {
-            let bytes = res.bytes().await.map_err(|e| GodboltError(e.to_string()))?;
-            let data: serde_json::Value = serde_json::from_slice(&bytes).map_err(|e| {
-                GodboltError(format!(
-                    "failed to parse CE json: {e}: {}",
-                    String::from_utf8_lossy(&bytes)
-                ))
-            })?;
-
-            // Build part
-            let build = data
-                .get("buildResult")
-                .cloned()
-                .unwrap_or_else(|| json!({}));
-            // Exec part
-            let did_execute = data
-                .get("didExecute")
-                .and_then(|v| v.as_bool())
-                .unwrap_or(false);
-
-            // Helper to join
-            fn join_text(arr: &serde_json::Value) -> String {
-                match arr {
-                    serde_json::Value::Array(vs) => vs
-                        .iter()
-                        .filter_map(|v| {
-                            if let Some(s) = v.as_str() {
-                                Some(s.to_string())
-                            } else if let Some(obj) = v.as_object() {
-                                obj.get("text")
-                                    .and_then(|t| t.as_str())
-                                    .map(|s| s.to_string())
-                            } else {
-                                None
-                            }
-                        })
-                        .collect::<Vec<_>>()
-                        .join("\n"),
-                    _ => String::new(),
+        match response {
+            CompileResponse::Json(data) => {
+                // Build part
+                let build = data
+                    .get("buildResult")
+                    .cloned()
+                    .unwrap_or_else(|| json!({}));
+                // Exec part
+                let did_execute = data
+                    .get("didExecute")
+                    .and_then(|v| v.as_bool())
+                    .unwrap_or(false);
+
+                // Helper to join
+                fn join_text(arr: &serde_json::Value) -> String {
+                    match arr {
+                        serde_json::Value::Array(vs) => vs
+                            .iter()
+                            .filter_map(|v| {
+                                if let Some(s) = v.as_str() {
+                                    Some(s.to_string())
+                                } else if let Some(obj) = v.as_object() {
+                                    obj.get("text")
+                                        .and_then(|t| t.as_str())
+                                        .map(|s| s.to_string())
+                                } else {
+                                    None
+                                }
+                            })
+                            .collect::<Vec<_>>()
+                            .join("\n"),
+                        _ => String::new(),
+                    }
                 }
-            }
 
-            let structured = json!({
-                "asm": join_text(&data.get("asm").cloned().unwrap_or_else(|| json!([]))),
-                "build": {
-                    "code": build.get("code").and_then(|v| v.as_i64()).unwrap_or(-1),
-                    "execTimeMs": build.get("execTime").and_then(|v| v.as_i64()),
-                    "stdoutText": join_text(&build.get("stdout").cloned().unwrap_or_else(|| json!([]))),
-                    "stderrText": join_text(&build.get("stderr").cloned().unwrap_or_else(|| json!([]))),
-                    "timedOut": build.get("timedOut").and_then(|v| v.as_bool()).unwrap_or(false),
-                    "truncated": build.get("truncated").and_then(|v| v.as_bool()).unwrap_or(false),
-                    "options": {
-                        "compilationOptions": build.get("compilationOptions").cloned().unwrap_or_else(|| json!([])),
-                        "inputFilename": build.get("inputFilename").cloned(),
-                        "executableFilename": build.get("executableFilename").cloned(),
-                        "instructionSet": build.get("instructionSet").cloned(),
+                let structured = json!({
+                    "asm": join_text(&data.get("asm").cloned().unwrap_or_else(|| json!([]))),
+                    "build": {
+                        "code": build.get("code").and_then(|v| v.as_i64()).unwrap_or(-1),
+                        "execTimeMs": build.get("execTime").and_then(|v| v.as_i64()),
+                        "stdoutText": join_text(&build.get("stdout").cloned().unwrap_or_else(|| json!([]))),
+                        "stderrText": join_text(&build.get("stderr").cloned().unwrap_or_else(|| json!([]))),
+                        "timedOut": build.get("timedOut").and_then(|v| v.as_bool()).unwrap_or(false),
+                        "truncated": build.get("truncated").and_then(|v| v.as_bool()).unwrap_or(false),
+                        "options": {
+                            "compilationOptions": build.get("compilationOptions").cloned().unwrap_or_else(|| json!([])),
+                            "inputFilename": build.get("inputFilename").cloned(),
+                            "executableFilename": build.get("executableFilename").cloned(),
+                            "instructionSet": build.get("instructionSet").cloned(),
+                        }
+                    },
+                    "exec": if did_execute { Some(json!({
+                        "code": data.get("code").and_then(|v| v.as_i64()).unwrap_or(-1),
+                        "execTimeMs": data.get("execTime").and_then(|v| v.as_i64()),
+                        "stdoutText": join_text(&data.get("stdout").cloned().unwrap_or_else(|| json!([]))),
+                        "stderrText": join_text(&data.get("stderr").cloned().unwrap_or_else(|| json!([]))),
+                        "timedOut": data.get("timedOut").and_then(|v| v.as_bool()).unwrap_or(false),
+                        "truncated": data.get("truncated").and_then(|v| v.as_bool()).unwrap_or(false),
+                        "didExecute": true
+                    })) } else { None },
+                    "meta": {
+                        "compiler": compiler,
+                        "flags": flags,
+                        "libraries": libs,
+                        "okToCache": data.get("okToCache").and_then(|v| v.as_bool()).unwrap_or(true)
                     }
-                },
-                "exec": if did_execute { Some(json!({
-                    "code": data.get("code").and_then(|v| v.as_i64()).unwrap_or(-1),
-                    "execTimeMs": data.get("execTime").and_then(|v| v.as_i64()),
-                    "stdoutText": join_text(&data.get("stdout").cloned().unwrap_or_else(|| json!([]))),
-                    "stderrText": join_text(&data.get("stderr").cloned().unwrap_or_else(|| json!([]))),
-                    "timedOut": data.get("timedOut").and_then(|v| v.as_bool()).unwrap_or(false),
-                    "truncated": data.get("truncated").and_then(|v| v.as_bool()).unwrap_or(false),
-                    "didExecute": true
-                })) } else { None },
-                "meta": {
-                    "compiler": compiler,
-                    "flags": flags,
-                    "libraries": libs,
-                    "okToCache": data.get("okToCache").and_then(|v| v.as_bool()).unwrap_or(true)
-                }
-            });
+                });
 
-            Ok(structured)
-        } else {
-            let text = res.text().await.unwrap_or_default();
-            Ok(json!({
+                Ok(structured)
+            }
+            CompileResponse::Text(text) => Ok(json!({
                 "build": {
                     "code": -1,
                     "execTimeMs": null,
@@ -276,13 +246,15 @@ impl Tool for Godbolt {
                 },
                 "exec": null,
                 "meta": { "compiler": compiler, "flags": flags, "libraries": libs, "okToCache": false }
-            }))
+            })),
         }
     }
 }
 
-#[derive(Debug, Clone)]
-pub struct GodboltFormats;
+#[derive(Clone)]
+pub struct GodboltFormats {
+    pub client: Arc<dyn GodboltClient>,
+}
 
 impl Tool for GodboltFormats {
     const NAME: &'static str = "godbolt_formatters";
@@ -299,19 +271,14 @@ impl Tool for GodboltFormats {
     }
 
     async fn call(&self, _args: Self::Args) -> Result<Self::Output, Self::Error> {
-        let client = Godbolt::client();
-        let url = format!("{BASE_URL}/api/formats");
-        let res = client
-            .get(url)
-            .send()
-            .await
-            .map_err(|e| GodboltError(e.to_string()))?;
-        res.json().await.map_err(|e| GodboltError(e.to_string()))
+        self.client.formatters().await.map_err(godbolt_error)
     }
 }
 
-#[derive(Debug, Clone)]
-pub struct GodboltFormat;
+#[derive(Clone)]
+pub struct GodboltFormat {
+    pub client: Arc<dyn GodboltClient>,
+}
 
 impl Tool for GodboltFormat {
     const NAME: &'static str = "godbolt_format";
@@ -336,15 +303,11 @@ impl Tool for GodboltFormat {
     }
 
     async fn call(&self, args: Self::Args) -> Result<Self::Output, Self::Error> {
-        let client = Godbolt::client();
-        let url = format!("{BASE_URL}/api/format/{}", args.formatter);
-        let res = client
-            .post(url)
-            .json(&json!({"source": args.source}))
-            .send()
+        let val = self
+            .client
+            .format(&args.formatter, &args.source)
             .await
-            .map_err(|e| GodboltError(e.to_string()))?;
-        let val: serde_json::Value = res.json().await.map_err(|e| GodboltError(e.to_string()))?;
+            .map_err(godbolt_error)?;
         Ok(FormatOutput {
             formatted: val
                 .get("answer")
@@ -355,8 +318,10 @@ impl Tool for GodboltFormat {
     }
 }
 
-#[derive(Debug, Clone)]
-pub struct GodboltLanguages;
+#[derive(Clone)]
+pub struct GodboltLanguages {
+    pub client: Arc<dyn GodboltClient>,
+}
 impl Tool for GodboltLanguages {
     const NAME: &'static str = "godbolt_languages";
     type Error = GodboltError;
@@ -372,21 +337,16 @@ impl Tool for GodboltLanguages {
     }
 
     async fn call(&self, _args: Self::Args) -> Result<Self::Output, Self::Error> {
-        let client = Godbolt::client();
-        let url = format!("{BASE_URL}/api/languages");
-        let res = client
-            .get(url)
-            .send()
-            .await
-            .map_err(|e| GodboltError(e.to_string()))?;
         Ok(LangsOutput(
-            res.json().await.map_err(|e| GodboltError(e.to_string()))?,
+            self.client.languages().await.map_err(godbolt_error)?,
         ))
     }
 }
 
-#[derive(Debug, Clone)]
-pub struct GodboltCompilers;
+#[derive(Clone)]
+pub struct GodboltCompilers {
+    pub client: Arc<dyn GodboltClient>,
+}
 impl Tool for GodboltCompilers {
     const NAME: &'static str = "godbolt_compilers";
     type Error = GodboltError;
@@ -402,19 +362,17 @@ impl Tool for GodboltCompilers {
     }
 
     async fn call(&self, args: Self::Args) -> Result<Self::Output, Self::Error> {
-        let client = Godbolt::client();
-        let url = format!("{BASE_URL}/api/compilers/{}", args.language_id);
-        let res = client
-            .get(url)
-            .send()
+        self.client
+            .compilers(&args.language_id)
             .await
-            .map_err(|e| GodboltError(e.to_string()))?;
-        res.json().await.map_err(|e| GodboltError(e.to_string()))
+            .map_err(godbolt_error)
     }
 }
 
-#[derive(Debug, Clone)]
-pub struct GodboltLibraries;
+#[derive(Clone)]
+pub struct GodboltLibraries {
+    pub client: Arc<dyn GodboltClient>,
+}
 impl Tool for GodboltLibraries {
     const NAME: &'static str = "godbolt_libraries";
     type Error = GodboltError;
@@ -430,19 +388,17 @@ impl Tool for GodboltLibraries {
     }
 
     async fn call(&self, args: Self::Args) -> Result<Self::Output, Self::Error> {
-        let client = Godbolt::client();
-        let url = format!("{BASE_URL}/api/libraries/{}", args.language_id);
-        let res = client
-            .get(url)
-            .send()
+        self.client
+            .libraries(&args.language_id)
             .await
-            .map_err(|e| GodboltError(e.to_string()))?;
-        res.json().await.map_err(|e| GodboltError(e.to_string()))
+            .map_err(godbolt_error)
     }
 }
 
-#[derive(Debug, Clone)]
-pub struct GodboltAsmDoc;
+#[derive(Clone)]
+pub struct GodboltAsmDoc {
+    pub client: Arc<dyn GodboltClient>,
+}
 impl Tool for GodboltAsmDoc {
     const NAME: &'static str = "godbolt_asm_doc";
     type Error = GodboltError;
@@ -459,22 +415,17 @@ impl Tool for GodboltAsmDoc {
     }
 
     async fn call(&self, args: Self::Args) -> Result<Self::Output, Self::Error> {
-        let client = Godbolt::client();
-        let url = format!(
-            "{BASE_URL}/api/asm/{}/{}",
-            args.instruction_set, args.opcode
-        );
-        let res = client
-            .get(url)
-            .send()
+        self.client
+            .asm_doc(&args.instruction_set, &args.opcode)
             .await
-            .map_err(|e| GodboltError(e.to_string()))?;
-        res.text().await.map_err(|e| GodboltError(e.to_string()))
+            .map_err(godbolt_error)
     }
 }
 
-#[derive(Debug, Clone)]
-pub struct GodboltVersion;
+#[derive(Clone)]
+pub struct GodboltVersion {
+    pub client: Arc<dyn GodboltClient>,
+}
 impl Tool for GodboltVersion {
     const NAME: &'static str = "godbolt_version";
     type Error = GodboltError;
@@ -490,13 +441,6 @@ impl Tool for GodboltVersion {
     }
 
     async fn call(&self, _args: Self::Args) -> Result<Self::Output, Self::Error> {
-        let client = Godbolt::client();
-        let url = format!("{BASE_URL}/api/version");
-        let res = client
-            .get(url)
-            .send()
-            .await
-            .map_err(|e| GodboltError(e.to_string()))?;
-        res.text().await.map_err(|e| GodboltError(e.to_string()))
+        self.client.version().await.map_err(godbolt_error)
     }
 }
@@ -1,29 +1,29 @@
-use super::vector_client::SharedVectorClient;
+use super::vector_client::{MemoryScopes, ScopeArg, SharedVectorClient};
 use rig::{completion::ToolDefinition, tool::Tool};
 use serde::{Deserialize, Serialize};
-use serde_json::{Value, json};
+use serde_json::json;
 use thiserror::Error;
 
 #[derive(Clone)]
 pub struct MemoryDeleteTool {
     pub client: SharedVectorClient,
-    pub channel_id: u64, // Discord channel ID
+    pub scopes: MemoryScopes,
 }
 
 impl MemoryDeleteTool {
-    pub fn new_with_client(client: SharedVectorClient, channel_id: u64) -> Self {
-        Self { client, channel_id }
+    pub fn new_with_client(client: SharedVectorClient, scopes: MemoryScopes) -> Self {
+        Self { client, scopes }
     }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct MemoryDeleteArgs {
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub ids: Option<Vec<String>>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub where_metadata: Option<Value>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub where_document: Option<Value>,
+    #[serde(default)]
+    pub ids: Vec<String>,
+    #[serde(default)]
+    pub scope: ScopeArg,
+    #[serde(default)]
+    pub user_id: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -45,21 +45,20 @@ impl Tool for MemoryDeleteTool {
     type Output = MemoryDeleteOutput;
 
     async fn definition(&self, _prompt: String) -> ToolDefinition {
-        let properties = json!({
-            "ids": {
-                "type": "array",
-                "items": {
-                    "type": "string"
-                },
-                "description": "Array of memory IDs to delete (obtain via memory_find)."
-            }
+        let mut properties = MemoryScopes::parameters();
+        properties["ids"] = json!({
+            "type": "array",
+            "items": {
+                "type": "string"
+            },
+            "description": "Array of memory IDs to delete (obtain via memory_find)."
         });
 
         ToolDefinition {
             name: "memory_delete".to_string(),
             description: format!(
-                "Delete stored memories from the vector database for channel {} by specific memory IDs. Look them up first with memory_find. BE CAREFUL - deletions are permanent.",
-                self.channel_id
+                "Delete stored memories from the vector database for channel {} by specific memory IDs. Look them up first with memory_find and pass the scope (and user_id) it reported. BE CAREFUL - deletions are permanent.",
+                self.scopes.channel_id
             ),
             parameters: json!({
                 "type": "object",
@@ -71,35 +70,35 @@ impl Tool for MemoryDeleteTool {
 
     async fn call(&self, args: Self::Args) -> Result<Self::Output, Self::Error> {
         let client = self.client.clone();
-        let channel_id = self.channel_id;
-        let collection_used = client.get_collection_name(self.channel_id);
+        let scope = match self.scopes.resolve(args.scope, args.user_id.as_deref()) {
+            Ok(scope) => scope,
+            Err(e) => {
+                return Ok(MemoryDeleteOutput {
+                    success: false,
+                    deleted_count: None,
+                    message: "Invalid scope".to_string(),
+                    error: Some(e),
+                });
+            }
+        };
+        let collection_used = client.get_collection_name(scope);
 
-        // Validate that at least one deletion criteria is provided
-        if args.ids.is_none() && args.where_metadata.is_none() && args.where_document.is_none() {
+        if args.ids.is_empty() {
             return Ok(MemoryDeleteOutput {
                 success: false,
                 deleted_count: None,
-                message: "No deletion criteria provided. Must specify at least one of: ids, where_metadata, or where_document".to_string(),
+                message: "No memory IDs provided".to_string(),
                 error: Some("Missing deletion criteria".to_string()),
             });
         }
 
-        // Convert Vec<String> to Vec<&str> for the delete API - clone for thread safety
-        let ids_refs: Option<Vec<String>> = args.ids.clone();
-        let where_metadata = args.where_metadata.clone();
-        let where_document = args.where_document.clone();
+        let ids = args.ids;
 
         // Spawn the async work in a separate task to avoid Sync issues
         let handle = tokio::spawn(async move {
-            // Convert to string slices inside the async block
-            let ids_slice: Option<Vec<&str>> = ids_refs
-                .as_ref()
-                .map(|ids| ids.iter().map(|s| s.as_str()).collect());
+            let ids_slice: Vec<&str> = ids.iter().map(|s| s.as_str()).collect();
 
-            match client
-                .delete(channel_id, ids_slice, where_metadata, where_document)
-                .await
-            {
+            match client.delete(scope, &ids_slice).await {
                 Ok(_) => {
                     tracing::info!(
                         "memory_delete completed successfully: deleted from collection '{}'",
@@ -108,7 +107,7 @@ impl Tool for MemoryDeleteTool {
 
                     Ok::<MemoryDeleteOutput, MemoryDeleteError>(MemoryDeleteOutput {
                         success: true,
-                        deleted_count: None, // ChromaDB doesn't return count in current implementation
+                        deleted_count: None, // Not all stores report how many existed
                         message: format!(
                             "Memory deletion completed successfully in collection '{}'",
                             collection_used
@@ -1,4 +1,4 @@
-use super::vector_client::{SearchResult, SharedVectorClient};
+use super::vector_client::{MemoryScope, MemoryScopes, ScopeArg, SearchResult, SharedVectorClient};
 use rig::{completion::ToolDefinition, tool::Tool};
 use serde::{Deserialize, Serialize};
 use serde_json::{Value, json};
@@ -8,19 +8,19 @@ use thiserror::Error;
 pub struct MemoryFindTool {
     pub client: SharedVectorClient,
     pub limit: u64,
-    pub channel_id: u64, // Discord channel ID
+    pub scopes: MemoryScopes,
 }
 
 impl MemoryFindTool {
     pub fn new_with_client(
         client: SharedVectorClient,
-        channel_id: u64,
+        scopes: MemoryScopes,
         limit: Option<u64>,
     ) -> Self {
         Self {
             client,
             limit: limit.unwrap_or(10),
-            channel_id,
+            scopes,
         }
     }
 }
@@ -30,10 +30,16 @@ pub struct MemoryFindArgs {
     pub query: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub limit: Option<u64>,
+    /// Only search this scope instead of all the reachable ones
+    #[serde(default)]
+    pub scope: Option<ScopeArg>,
+    #[serde(default)]
+    pub user_id: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct MemoryResult {
+    pub scope: String,
     pub point_id: String,
     pub content: String,
     pub score: f32,
@@ -44,6 +50,7 @@ pub struct MemoryResult {
 impl From<SearchResult> for MemoryResult {
     fn from(result: SearchResult) -> Self {
         Self {
+            scope: result.scope.to_string(),
             point_id: result.point_id,
             content: result.content,
             score: result.score,
@@ -74,15 +81,19 @@ impl Tool for MemoryFindTool {
     type Output = MemoryFindOutput;
 
     async fn definition(&self, _prompt: String) -> ToolDefinition {
-        let properties = json!({
-            "query": {
-                "type": "string",
-                "description": "Query to search for in the vector database"
-            },
-            "limit": {
-                "type": ["integer", "null"],
-                "description": "Maximum number of results to return (default: 10, max: 20)"
-            }
+        let mut properties = MemoryScopes::parameters();
+        properties["scope"]["description"] = json!(
+            "Only search this scope. By default the channel, guild and global scopes are searched, plus the user scope if user_id is given"
+        );
+        properties["user_id"]["description"] =
+            json!("Discord user ID whose user scope to include, e.g. the author of the message");
+        properties["query"] = json!({
+            "type": "string",
+            "description": "Query to search for in the vector database"
+        });
+        properties["limit"] = json!({
+            "type": ["integer", "null"],
+            "description": "Maximum number of results to return (default: 10, max: 20)"
         });
 
         let required = vec!["query", "limit"];
@@ -90,8 +101,8 @@ impl Tool for MemoryFindTool {
         ToolDefinition {
             name: "memory_find".to_string(),
             description: format!(
-                "Retrieve relevant stored information from channel {} based on semantic similarity. Use this to find past conversations, user preferences, or relevant context. Note that the score from the result indicates the relevance of the memory to the query, with higher scores being more relevant on a scale from 0.0 to 1.0",
-                self.channel_id
+                "Retrieve relevant stored information from channel {} based on semantic similarity. Use this to find past conversations, user preferences, or relevant context. Results are labeled with the scope they're from. Note that the score from the result indicates the relevance of the memory to the query, with higher scores being more relevant on a scale from 0.0 to 1.0",
+                self.scopes.channel_id
             ),
             parameters: json!({
                 "type": "object",
@@ -105,13 +116,47 @@ impl Tool for MemoryFindTool {
         let client = self.client.clone();
         let query = args.query.clone();
         let limit = args.limit.unwrap_or(self.limit);
-        let channel_id = self.channel_id;
-        let collection_used = client.get_collection_name(self.channel_id);
+        let scopes = match args.scope {
+            Some(scope) => match self.scopes.resolve(scope, args.user_id.as_deref()) {
+                Ok(scope) => vec![scope],
+                Err(e) => {
+                    return Ok(MemoryFindOutput {
+                        success: false,
+                        results: vec![],
+                        total_found: 0,
+                        query: args.query,
+                        collection: "unknown".to_string(),
+                        error: Some(e),
+                    });
+                }
+            },
+            // Other users' and the server memories stay out of DMs, the global
+            // ones can be read there but not written
+            None if let Some(dm_user_id) = self.scopes.dm_user_id => {
+                vec![MemoryScope::User(dm_user_id), MemoryScope::Global]
+            }
+            None => {
+                let mut scopes = vec![MemoryScope::Channel(self.scopes.channel_id)];
+                if let Ok(user) = self.scopes.resolve(ScopeArg::User, args.user_id.as_deref()) {
+                    scopes.push(user);
+                }
+                if let Some(guild_id) = self.scopes.guild_id {
+                    scopes.push(MemoryScope::Guild(guild_id));
+                }
+                scopes.push(MemoryScope::Global);
+                scopes
+            }
+        };
+        let collection_used = scopes
+            .iter()
+            .map(|scope| client.get_collection_name(*scope))
+            .collect::<Vec<_>>()
+            .join(", ");
 
         // Spawn the async work in a separate task to avoid Sync issues
         let handle = tokio::spawn(async move {
             // Use None for collection_name since it's hardcoded via channel_id in the config
-            let results = match client.search(&query, channel_id, limit).await {
+            let results = match client.search(&query, &scopes, limit).await {
                 Ok(results) => results,
                 Err(e) => {
                     return Ok(MemoryFindOutput {
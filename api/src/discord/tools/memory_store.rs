@@ -1,4 +1,4 @@
-use super::vector_client::SharedVectorClient;
+use super::vector_client::{MemoryScopes, ScopeArg, SharedVectorClient};
 use chrono;
 use rig::{completion::ToolDefinition, tool::Tool};
 use serde::{Deserialize, Serialize};
@@ -8,12 +8,12 @@ use thiserror::Error;
 #[derive(Clone)]
 pub struct MemoryStoreTool {
     pub client: SharedVectorClient,
-    pub channel_id: u64, // Discord channel ID
+    pub scopes: MemoryScopes,
 }
 
 impl MemoryStoreTool {
-    pub fn new_with_client(client: SharedVectorClient, channel_id: u64) -> Self {
-        Self { client, channel_id }
+    pub fn new_with_client(client: SharedVectorClient, scopes: MemoryScopes) -> Self {
+        Self { client, scopes }
     }
 }
 
@@ -22,6 +22,10 @@ pub struct MemoryStoreArgs {
     pub information: String,
     #[serde(default)]
     pub metadata: Option<Value>,
+    #[serde(default)]
+    pub scope: ScopeArg,
+    #[serde(default)]
+    pub user_id: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -43,11 +47,10 @@ impl Tool for MemoryStoreTool {
     type Output = MemoryStoreOutput;
 
     async fn definition(&self, _prompt: String) -> ToolDefinition {
-        let properties = json!({
-            "information": {
-                "type": "string",
-                "description": "Information to store in the vector database"
-            }
+        let mut properties = MemoryScopes::parameters();
+        properties["information"] = json!({
+            "type": "string",
+            "description": "Information to store in the vector database"
         });
 
         let required = vec!["information"];
@@ -55,8 +58,8 @@ impl Tool for MemoryStoreTool {
         ToolDefinition {
             name: "memory_store".to_string(),
             description: format!(
-                "Store information in the vector database for channel {}. Use this to save important details about users, conversations, preferences, or interesting facts for future reference in this channel.",
-                self.channel_id
+                "Store information in the vector database for channel {}. Use this to save important details about users, conversations, preferences, or interesting facts for future reference. Pick the narrowest scope that fits: facts about a user go to their user scope so they're remembered in every channel.",
+                self.scopes.channel_id
             ),
             parameters: json!({
                 "type": "object",
@@ -70,8 +73,18 @@ impl Tool for MemoryStoreTool {
         let client = self.client.clone();
         let information = args.information.clone();
         let mut metadata = args.metadata.unwrap_or_else(|| serde_json::json!({}));
-        let channel_id = self.channel_id;
-        let collection_used = client.get_collection_name(self.channel_id);
+        let scope = match self.scopes.resolve(args.scope, args.user_id.as_deref()) {
+            Ok(scope) => scope,
+            Err(e) => {
+                return Ok(MemoryStoreOutput {
+                    success: false,
+                    point_id: None,
+                    message: "Invalid scope".to_string(),
+                    error: Some(e),
+                });
+            }
+        };
+        let collection_used = client.get_collection_name(scope);
 
         // Add timestamp to metadata
         if let serde_json::Value::Object(ref mut obj) = metadata {
@@ -85,7 +98,7 @@ impl Tool for MemoryStoreTool {
         // Spawn the async work in a separate task to avoid Sync issues
         let handle = tokio::spawn(async move {
             // Use None for collection_name since it's hardcoded via channel_id in the config
-            let point_id = match client.store(&information, channel_id, metadata).await {
+            let point_id = match client.store(&information, scope, metadata).await {
                 Ok(point_id) => {
                     tracing::debug!(
                         "Store operation completed successfully, point_id: {}",
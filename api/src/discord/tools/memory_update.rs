@@ -1,4 +1,4 @@
-use super::vector_client::SharedVectorClient;
+use super::vector_client::{MemoryScopes, ScopeArg, SharedVectorClient};
 use chrono;
 use rig::{completion::ToolDefinition, tool::Tool};
 use serde::{Deserialize, Serialize};
@@ -8,12 +8,12 @@ use thiserror::Error;
 #[derive(Clone)]
 pub struct MemoryUpdateTool {
     pub client: SharedVectorClient,
-    pub channel_id: u64, // Discord channel ID
+    pub scopes: MemoryScopes,
 }
 
 impl MemoryUpdateTool {
-    pub fn new_with_client(client: SharedVectorClient, channel_id: u64) -> Self {
-        Self { client, channel_id }
+    pub fn new_with_client(client: SharedVectorClient, scopes: MemoryScopes) -> Self {
+        Self { client, scopes }
     }
 }
 
@@ -23,6 +23,10 @@ pub struct MemoryUpdateArgs {
     pub information: String,
     #[serde(default)]
     pub metadata: Option<Value>,
+    #[serde(default)]
+    pub scope: ScopeArg,
+    #[serde(default)]
+    pub user_id: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -44,15 +48,14 @@ impl Tool for MemoryUpdateTool {
     type Output = MemoryUpdateOutput;
 
     async fn definition(&self, _prompt: String) -> ToolDefinition {
-        let properties = json!({
-            "point_id": {
-                "type": "string",
-                "description": "The point ID of the existing memory to update (obtained from memory_find results)"
-            },
-            "information": {
-                "type": "string",
-                "description": "Updated information to replace the existing memory content"
-            }
+        let mut properties = MemoryScopes::parameters();
+        properties["point_id"] = json!({
+            "type": "string",
+            "description": "The point ID of the existing memory to update (obtained from memory_find results)"
+        });
+        properties["information"] = json!({
+            "type": "string",
+            "description": "Updated information to replace the existing memory content"
         });
 
         let required = vec!["point_id", "information"];
@@ -60,8 +63,8 @@ impl Tool for MemoryUpdateTool {
         ToolDefinition {
             name: "memory_update".to_string(),
             description: format!(
-                "Update existing information in the vector database for channel {}. Use this to modify or correct previously stored memories based on new information or corrections.",
-                self.channel_id
+                "Update existing information in the vector database for channel {}. Use this to modify or correct previously stored memories based on new information or corrections. Pass the scope (and user_id) memory_find reported for the memory.",
+                self.scopes.channel_id
             ),
             parameters: json!({
                 "type": "object",
@@ -76,8 +79,18 @@ impl Tool for MemoryUpdateTool {
         let point_id = args.point_id.clone();
         let information = args.information.clone();
         let mut metadata = args.metadata.unwrap_or_else(|| serde_json::json!({}));
-        let channel_id = self.channel_id;
-        let collection_used = client.get_collection_name(self.channel_id);
+        let scope = match self.scopes.resolve(args.scope, args.user_id.as_deref()) {
+            Ok(scope) => scope,
+            Err(e) => {
+                return Ok(MemoryUpdateOutput {
+                    success: false,
+                    point_id: args.point_id,
+                    message: "Invalid scope".to_string(),
+                    error: Some(e),
+                });
+            }
+        };
+        let collection_used = client.get_collection_name(scope);
 
         // Add timestamp to metadata
         if let serde_json::Value::Object(ref mut obj) = metadata {
@@ -92,7 +105,7 @@ impl Tool for MemoryUpdateTool {
         let handle = tokio::spawn(async move {
             // Use None for collection_name since it's hardcoded via channel_id in the config
             client
-                .update(&point_id, &information, channel_id, metadata)
+                .update(&point_id, &information, scope, metadata)
                 .await
                 .map_err(|e| MemoryUpdateError(format!("Failed to update information: {}", e)))?;
 
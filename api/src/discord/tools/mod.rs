@@ -1,19 +1,38 @@
+pub mod blog;
+pub mod calculate;
+pub mod code_run;
 pub mod discord_message;
+pub mod feed;
 pub mod fetch_content;
+pub mod github_search;
 pub mod godbolt;
 pub mod memory_delete;
 pub mod memory_find;
 pub mod memory_store;
 pub mod memory_update;
+pub mod moderation;
+pub mod reminders;
+pub mod rust_playground;
+pub mod search;
 pub mod vector_client;
+pub mod vector_store;
 pub mod web_search;
 
+pub use blog::*;
+pub use calculate::*;
+pub use code_run::*;
 pub use discord_message::*;
+pub use feed::*;
 pub use fetch_content::*;
+pub use github_search::*;
 pub use godbolt::*;
 pub use memory_delete::*;
 pub use memory_find::*;
 pub use memory_store::*;
 pub use memory_update::*;
+pub use moderation::*;
+pub use reminders::*;
+pub use rust_playground::*;
+pub use search::*;
 pub use vector_client::*;
 pub use web_search::*;
@@ -0,0 +1,354 @@
+use std::sync::Arc;
+
+use rig::{completion::ToolDefinition, tool::Tool};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use serenity::all::{
+    ChannelId, Context, CreateAllowedMentions, CreateMessage, EditMember, GuildId, MessageId,
+    Timestamp, UserId,
+};
+use thiserror::Error;
+
+use super::reminders::parse_user_id;
+use crate::{
+    discord::{
+        moderation::{ACTION_DELETE, ACTION_TIMEOUT, ACTION_WARN, Moderation},
+        settings::to_db_id,
+    },
+    models::discord::NewDiscordModerationAction,
+};
+
+/// Discord caps timeouts at 28 days
+const MAX_TIMEOUT_MINUTES: u32 = 28 * 24 * 60;
+
+/// Warnings of a user within this many days are reported back to the agent so
+/// that it can escalate
+const WARNING_LOOKBACK_DAYS: u64 = 30;
+
+#[derive(Debug, Error)]
+#[error("Moderation error: {0}")]
+pub struct ModerationError(String);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ModerationOutput {
+    pub success: bool,
+    pub message: String,
+}
+
+impl ModerationOutput {
+    fn success(message: impl std::fmt::Display) -> Self {
+        Self {
+            success: true,
+            message: message.to_string(),
+        }
+    }
+
+    fn failure(message: impl std::fmt::Display) -> Self {
+        Self {
+            success: false,
+            message: message.to_string(),
+        }
+    }
+}
+
+/// What the moderation tools act on, only created for guilds that enabled
+/// enforcement
+#[derive(Clone)]
+pub struct ModerationTarget {
+    pub ctx: Arc<Context>,
+    pub moderation: Moderation,
+    pub guild_id: GuildId,
+    pub channel_id: ChannelId,
+}
+
+impl ModerationTarget {
+    async fn audit(
+        &self,
+        action: &str,
+        user_id: Option<UserId>,
+        message_id: Option<MessageId>,
+        reason: &str,
+    ) {
+        let _ = self
+            .moderation
+            .audit(NewDiscordModerationAction {
+                guild_id: to_db_id(self.guild_id.get()),
+                channel_id: to_db_id(self.channel_id.get()),
+                user_id: user_id.map(|u| to_db_id(u.get())),
+                message_id: message_id.map(|m| to_db_id(m.get())),
+                action: action.to_string(),
+                reason: reason.to_string(),
+                categories: None,
+            })
+            .await
+            .inspect_err(|e| tracing::error!(?e, "Failed to audit moderation action"));
+    }
+}
+
+fn parse_message_id(message_id: &str) -> Option<MessageId> {
+    message_id
+        .trim()
+        .parse::<u64>()
+        .ok()
+        .filter(|id| *id != 0)
+        .map(MessageId::new)
+}
+
+#[derive(Clone)]
+pub struct ModerationWarnTool {
+    pub target: ModerationTarget,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ModerationWarnArgs {
+    pub user_id: String,
+    pub reason: String,
+}
+
+impl Tool for ModerationWarnTool {
+    const NAME: &'static str = "moderation_warn";
+    type Error = ModerationError;
+    type Args = ModerationWarnArgs;
+    type Output = ModerationOutput;
+
+    async fn definition(&self, _prompt: String) -> ToolDefinition {
+        ToolDefinition {
+            name: Self::NAME.to_string(),
+            description: "Formally warn a user for breaking the server rules. The warning is \
+                posted in the channel and recorded, the result tells how many times the user was \
+                warned recently so that you can escalate to a timeout."
+                .to_string(),
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "user_id": {
+                        "type": "string",
+                        "description": "Discord user ID of the user to warn"
+                    },
+                    "reason": {
+                        "type": "string",
+                        "description": "Short reason shown to the user"
+                    }
+                },
+                "required": ["user_id", "reason"]
+            }),
+        }
+    }
+
+    async fn call(&self, args: Self::Args) -> Result<Self::Output, Self::Error> {
+        let Some(user_id) = parse_user_id(&args.user_id) else {
+            return Ok(ModerationOutput::failure("invalid user ID"));
+        };
+
+        let message = CreateMessage::new()
+            .content(format!("⚠️ <@{user_id}> warning: {}", args.reason.trim()))
+            .allowed_mentions(CreateAllowedMentions::new().users([user_id]));
+
+        if let Err(e) = self
+            .target
+            .channel_id
+            .send_message(&self.target.ctx.http, message)
+            .await
+        {
+            return Ok(ModerationOutput::failure(format!(
+                "failed to send the warning: {e}"
+            )));
+        }
+
+        self.target
+            .audit(ACTION_WARN, Some(user_id), None, &args.reason)
+            .await;
+
+        let since = (chrono::Utc::now() - chrono::Days::new(WARNING_LOOKBACK_DAYS)).naive_utc();
+        Ok(
+            match self
+                .target
+                .moderation
+                .warnings(self.target.guild_id, user_id, since)
+                .await
+            {
+                Ok(count) => ModerationOutput::success(format!(
+                    "warned, {count} warning(s) in the last {WARNING_LOOKBACK_DAYS} days"
+                )),
+                Err(e) => {
+                    tracing::error!(?e, "Failed to count warnings");
+                    ModerationOutput::success("warned")
+                }
+            },
+        )
+    }
+}
+
+#[derive(Clone)]
+pub struct ModerationTimeoutTool {
+    pub target: ModerationTarget,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ModerationTimeoutArgs {
+    pub user_id: String,
+    pub minutes: u32,
+    pub reason: String,
+}
+
+impl Tool for ModerationTimeoutTool {
+    const NAME: &'static str = "moderation_timeout";
+    type Error = ModerationError;
+    type Args = ModerationTimeoutArgs;
+    type Output = ModerationOutput;
+
+    async fn definition(&self, _prompt: String) -> ToolDefinition {
+        ToolDefinition {
+            name: Self::NAME.to_string(),
+            description: "Time out a user so they can't send messages or react for a while. \
+                Use it for repeated or severe violations, after warning them."
+                .to_string(),
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "user_id": {
+                        "type": "string",
+                        "description": "Discord user ID of the user to time out"
+                    },
+                    "minutes": {
+                        "type": "integer",
+                        "description": format!("Duration of the timeout, at most {MAX_TIMEOUT_MINUTES}")
+                    },
+                    "reason": {
+                        "type": "string",
+                        "description": "Short reason, shown in the server's audit log"
+                    }
+                },
+                "required": ["user_id", "minutes", "reason"]
+            }),
+        }
+    }
+
+    async fn call(&self, args: Self::Args) -> Result<Self::Output, Self::Error> {
+        let Some(user_id) = parse_user_id(&args.user_id) else {
+            return Ok(ModerationOutput::failure("invalid user ID"));
+        };
+
+        if args.minutes == 0 || args.minutes > MAX_TIMEOUT_MINUTES {
+            return Ok(ModerationOutput::failure(format!(
+                "minutes must be between 1 and {MAX_TIMEOUT_MINUTES}"
+            )));
+        }
+
+        let until = chrono::Utc::now() + chrono::Duration::minutes(i64::from(args.minutes));
+        let Ok(until) = Timestamp::from_unix_timestamp(until.timestamp()) else {
+            return Ok(ModerationOutput::failure("invalid timeout"));
+        };
+
+        if let Err(e) = self
+            .target
+            .guild_id
+            .edit_member(
+                &self.target.ctx.http,
+                user_id,
+                EditMember::new()
+                    .disable_communication_until_datetime(until)
+                    .audit_log_reason(&args.reason),
+            )
+            .await
+        {
+            return Ok(ModerationOutput::failure(format!(
+                "failed to time out the user, the bot may lack the Moderate Members permission: {e}"
+            )));
+        }
+
+        self.target
+            .audit(
+                ACTION_TIMEOUT,
+                Some(user_id),
+                None,
+                &format!("{} minutes: {}", args.minutes, args.reason),
+            )
+            .await;
+
+        Ok(ModerationOutput::success(format!(
+            "timed out until {}",
+            until.to_rfc3339().unwrap_or_default()
+        )))
+    }
+}
+
+#[derive(Clone)]
+pub struct ModerationDeleteTool {
+    pub target: ModerationTarget,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ModerationDeleteArgs {
+    pub message_id: String,
+    pub reason: String,
+}
+
+impl Tool for ModerationDeleteTool {
+    const NAME: &'static str = "moderation_delete_message";
+    type Error = ModerationError;
+    type Args = ModerationDeleteArgs;
+    type Output = ModerationOutput;
+
+    async fn definition(&self, _prompt: String) -> ToolDefinition {
+        ToolDefinition {
+            name: Self::NAME.to_string(),
+            description: "Delete a message in this channel that breaks the server rules, e.g. \
+                spam, scams or slurs."
+                .to_string(),
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "message_id": {
+                        "type": "string",
+                        "description": "The Discord message ID to delete"
+                    },
+                    "reason": {
+                        "type": "string",
+                        "description": "Short reason, shown in the server's audit log"
+                    }
+                },
+                "required": ["message_id", "reason"]
+            }),
+        }
+    }
+
+    async fn call(&self, args: Self::Args) -> Result<Self::Output, Self::Error> {
+        let Some(message_id) = parse_message_id(&args.message_id) else {
+            return Ok(ModerationOutput::failure("invalid message ID"));
+        };
+
+        // Fetch it first to record the author in the audit trail
+        let author = match self
+            .target
+            .channel_id
+            .message(&self.target.ctx.http, message_id)
+            .await
+        {
+            Ok(message) => message.author.id,
+            Err(e) => {
+                return Ok(ModerationOutput::failure(format!(
+                    "message not found in this channel: {e}"
+                )));
+            }
+        };
+
+        if let Err(e) = self
+            .target
+            .ctx
+            .http
+            .delete_message(self.target.channel_id, message_id, Some(&args.reason))
+            .await
+        {
+            return Ok(ModerationOutput::failure(format!(
+                "failed to delete the message, the bot may lack the Manage Messages permission: {e}"
+            )));
+        }
+
+        self.target
+            .audit(ACTION_DELETE, Some(author), Some(message_id), &args.reason)
+            .await;
+
+        Ok(ModerationOutput::success("deleted"))
+    }
+}
@@ -0,0 +1,286 @@
+use rig::{completion::ToolDefinition, tool::Tool};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use serenity::all::{ChannelId, UserId};
+use thiserror::Error;
+
+use crate::discord::reminders::Reminders;
+
+/// Reminders further out than this are most likely a mistake
+const MAX_REMINDER_DAYS: i64 = 366;
+
+#[derive(Debug, Error)]
+#[error("Reminder error: {0}")]
+pub struct ReminderError(String);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReminderOutput {
+    pub success: bool,
+    pub message: String,
+    pub reminders: Vec<ReminderInfo>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReminderInfo {
+    pub id: i32,
+    pub user_id: String,
+    pub message: String,
+    /// RFC 3339 in UTC
+    pub remind_at: String,
+}
+
+impl From<crate::models::discord::DiscordReminder> for ReminderInfo {
+    fn from(value: crate::models::discord::DiscordReminder) -> Self {
+        Self {
+            id: value.id,
+            user_id: value.user_id.to_string(),
+            message: value.message,
+            remind_at: value.remind_at.and_utc().to_rfc3339(),
+        }
+    }
+}
+
+impl ReminderOutput {
+    fn failure(message: impl std::fmt::Display) -> Self {
+        Self {
+            success: false,
+            message: message.to_string(),
+            reminders: Vec::new(),
+        }
+    }
+}
+
+pub(super) fn parse_user_id(user_id: &str) -> Option<UserId> {
+    user_id
+        .trim()
+        .trim_start_matches("<@")
+        .trim_start_matches('@')
+        .trim_end_matches('>')
+        .parse::<u64>()
+        .ok()
+        .filter(|id| *id != 0)
+        .map(UserId::new)
+}
+
+#[derive(Clone)]
+pub struct RemindMeTool {
+    pub reminders: Reminders,
+    pub channel_id: ChannelId,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RemindMeArgs {
+    pub user_id: String,
+    pub remind_at: String,
+    pub message: String,
+}
+
+impl Tool for RemindMeTool {
+    const NAME: &'static str = "remind_me";
+    type Error = ReminderError;
+    type Args = RemindMeArgs;
+    type Output = ReminderOutput;
+
+    async fn definition(&self, _prompt: String) -> ToolDefinition {
+        ToolDefinition {
+            name: Self::NAME.to_string(),
+            description:
+                "Schedule a reminder that pings a user in this channel at the given time. \
+                Reminders persist across restarts. Work out the absolute time from the message \
+                timestamps if the user gives a relative one (e.g. \"in 2 hours\"), and confirm the \
+                scheduled time to the user."
+                    .to_string(),
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "user_id": {
+                        "type": "string",
+                        "description": "Discord user ID of the user to remind"
+                    },
+                    "remind_at": {
+                        "type": "string",
+                        "description": "When to remind, RFC 3339 with a timezone offset, e.g. 2025-01-31T09:00:00+07:00"
+                    },
+                    "message": {
+                        "type": "string",
+                        "description": "What to remind the user of"
+                    }
+                },
+                "required": ["user_id", "remind_at", "message"]
+            }),
+        }
+    }
+
+    async fn call(&self, args: Self::Args) -> Result<Self::Output, Self::Error> {
+        let Some(user_id) = parse_user_id(&args.user_id) else {
+            return Ok(ReminderOutput::failure("invalid user ID"));
+        };
+
+        let remind_at = match chrono::DateTime::parse_from_rfc3339(args.remind_at.trim()) {
+            Ok(t) => t.with_timezone(&chrono::Utc),
+            Err(e) => return Ok(ReminderOutput::failure(format!("invalid remind_at: {e}"))),
+        };
+
+        let now = chrono::Utc::now();
+        if remind_at <= now {
+            return Ok(ReminderOutput::failure(format!(
+                "remind_at is in the past, it's {} now",
+                now.to_rfc3339()
+            )));
+        }
+        if remind_at > now + chrono::Duration::days(MAX_REMINDER_DAYS) {
+            return Ok(ReminderOutput::failure(format!(
+                "reminders can be at most {MAX_REMINDER_DAYS} days ahead"
+            )));
+        }
+
+        if args.message.trim().is_empty() {
+            return Ok(ReminderOutput::failure("message is empty"));
+        }
+
+        match self
+            .reminders
+            .schedule(
+                self.channel_id,
+                user_id,
+                args.message.trim().to_string(),
+                remind_at.naive_utc(),
+            )
+            .await
+        {
+            Ok(reminder) => Ok(ReminderOutput {
+                success: true,
+                message: "Reminder scheduled".to_string(),
+                reminders: vec![reminder.into()],
+            }),
+            Err(e) => {
+                tracing::error!(?e, "Failed to schedule reminder");
+                Ok(ReminderOutput::failure(e))
+            }
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct ListRemindersTool {
+    pub reminders: Reminders,
+    pub channel_id: ChannelId,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ListRemindersArgs {
+    #[serde(default)]
+    pub user_id: Option<String>,
+}
+
+impl Tool for ListRemindersTool {
+    const NAME: &'static str = "list_reminders";
+    type Error = ReminderError;
+    type Args = ListRemindersArgs;
+    type Output = ReminderOutput;
+
+    async fn definition(&self, _prompt: String) -> ToolDefinition {
+        ToolDefinition {
+            name: Self::NAME.to_string(),
+            description: "List the pending reminders in this channel, soonest first".to_string(),
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "user_id": {
+                        "type": "string",
+                        "description": "Only list the reminders of this Discord user ID"
+                    }
+                }
+            }),
+        }
+    }
+
+    async fn call(&self, args: Self::Args) -> Result<Self::Output, Self::Error> {
+        let user_id = match args.user_id.as_deref().map(parse_user_id) {
+            Some(None) => return Ok(ReminderOutput::failure("invalid user ID")),
+            Some(Some(user_id)) => Some(user_id),
+            None => None,
+        };
+
+        match self.reminders.pending(self.channel_id, user_id).await {
+            Ok(reminders) => Ok(ReminderOutput {
+                success: true,
+                message: format!("{} pending reminders", reminders.len()),
+                reminders: reminders.into_iter().map(Into::into).collect(),
+            }),
+            Err(e) => {
+                tracing::error!(?e, "Failed to list reminders");
+                Ok(ReminderOutput::failure(e))
+            }
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct CancelReminderTool {
+    pub reminders: Reminders,
+    pub channel_id: ChannelId,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CancelReminderArgs {
+    pub id: i32,
+}
+
+impl Tool for CancelReminderTool {
+    const NAME: &'static str = "cancel_reminder";
+    type Error = ReminderError;
+    type Args = CancelReminderArgs;
+    type Output = ReminderOutput;
+
+    async fn definition(&self, _prompt: String) -> ToolDefinition {
+        ToolDefinition {
+            name: Self::NAME.to_string(),
+            description:
+                "Cancel a pending reminder in this channel by its ID, see list_reminders. \
+                Only cancel reminders on behalf of the user they're for or who scheduled them."
+                    .to_string(),
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "id": {
+                        "type": "integer",
+                        "description": "ID of the reminder"
+                    }
+                },
+                "required": ["id"]
+            }),
+        }
+    }
+
+    async fn call(&self, args: Self::Args) -> Result<Self::Output, Self::Error> {
+        match self.reminders.cancel(self.channel_id, args.id).await {
+            Ok(true) => Ok(ReminderOutput {
+                success: true,
+                message: "Reminder cancelled".to_string(),
+                reminders: Vec::new(),
+            }),
+            Ok(false) => Ok(ReminderOutput::failure(
+                "no pending reminder with this ID in this channel",
+            )),
+            Err(e) => {
+                tracing::error!(?e, "Failed to cancel reminder");
+                Ok(ReminderOutput::failure(e))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_user_id_accepts_mentions() {
+        assert_eq!(parse_user_id("123"), Some(UserId::new(123)));
+        assert_eq!(parse_user_id("<@123>"), Some(UserId::new(123)));
+        assert_eq!(parse_user_id("@123"), Some(UserId::new(123)));
+        assert_eq!(parse_user_id("0"), None);
+        assert_eq!(parse_user_id("someone"), None);
+    }
+}
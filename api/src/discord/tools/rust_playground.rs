@@ -0,0 +1,245 @@
+use rig::{completion::ToolDefinition, tool::Tool};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use thiserror::Error;
+
+use super::code_run::truncate_output;
+use crate::outbound::HttpClient;
+
+const BASE_URL: &str = "https://play.rust-lang.org";
+/// Cold builds, especially miri, take a while
+const BUILD_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(60);
+
+#[derive(Debug, Clone)]
+pub struct RustPlayground {
+    pub client: HttpClient,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PlaygroundMode {
+    #[default]
+    Run,
+    Test,
+    Clippy,
+    Miri,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PlaygroundChannel {
+    #[default]
+    Stable,
+    Beta,
+    Nightly,
+}
+
+impl PlaygroundChannel {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Stable => "stable",
+            Self::Beta => "beta",
+            Self::Nightly => "nightly",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RustPlaygroundArgs {
+    pub code: String,
+    #[serde(default)]
+    pub mode: PlaygroundMode,
+    #[serde(default)]
+    pub channel: PlaygroundChannel,
+    /// Build with optimizations
+    #[serde(default)]
+    pub release: bool,
+    #[serde(default)]
+    pub edition: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RustPlaygroundOutput {
+    pub success: bool,
+    pub stdout: String,
+    pub stderr: String,
+    pub exit_detail: String,
+    pub truncated: bool,
+    /// Link to open the code in the playground
+    pub share_url: Option<String>,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Error)]
+#[error("Rust Playground error: {0}")]
+pub struct RustPlaygroundError(String);
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ExecuteResponse {
+    success: bool,
+    #[serde(default)]
+    exit_detail: String,
+    stdout: String,
+    stderr: String,
+}
+
+impl RustPlayground {
+    async fn execute(
+        client: &HttpClient,
+        args: &RustPlaygroundArgs,
+    ) -> Result<ExecuteResponse, eyre::Error> {
+        let edition = args.edition.as_deref().unwrap_or("2024");
+        // Snippets without a main are built as a library, e.g. for clippy or tests
+        let crate_type = if args.code.contains("fn main") {
+            "bin"
+        } else {
+            "lib"
+        };
+
+        let (endpoint, body) = match args.mode {
+            PlaygroundMode::Run | PlaygroundMode::Test => (
+                "execute",
+                json!({
+                    "channel": args.channel.as_str(),
+                    "mode": if args.release { "release" } else { "debug" },
+                    "edition": edition,
+                    "crateType": crate_type,
+                    "tests": args.mode == PlaygroundMode::Test,
+                    "backtrace": false,
+                    "code": args.code,
+                }),
+            ),
+            PlaygroundMode::Clippy => (
+                "clippy",
+                json!({
+                    "channel": args.channel.as_str(),
+                    "edition": edition,
+                    "crateType": crate_type,
+                    "code": args.code,
+                }),
+            ),
+            // Miri always runs on nightly
+            PlaygroundMode::Miri => (
+                "miri",
+                json!({
+                    "edition": edition,
+                    "tests": crate_type == "lib",
+                    "code": args.code,
+                }),
+            ),
+        };
+
+        let response = client
+            .post(format!("{BASE_URL}/{endpoint}"))
+            .json(&body)
+            .timeout(BUILD_TIMEOUT)
+            .send()
+            .await?;
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let body = response.text().await.unwrap_or_default();
+            eyre::bail!("playground returned {status}: {body}");
+        }
+
+        Ok(response.json().await?)
+    }
+
+    async fn share(client: &HttpClient, args: &RustPlaygroundArgs) -> Result<String, eyre::Error> {
+        #[derive(Deserialize)]
+        struct GistResponse {
+            id: String,
+        }
+
+        let gist: GistResponse = client
+            .post(format!("{BASE_URL}/meta/gist"))
+            .json(&json!({ "code": args.code }))
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        let channel = match args.mode {
+            PlaygroundMode::Miri => PlaygroundChannel::Nightly,
+            _ => args.channel,
+        };
+
+        Ok(format!(
+            "{BASE_URL}/?version={}&mode={}&edition={}&gist={}",
+            channel.as_str(),
+            if args.release { "release" } else { "debug" },
+            args.edition.as_deref().unwrap_or("2024"),
+            gist.id
+        ))
+    }
+}
+
+impl Tool for RustPlayground {
+    const NAME: &'static str = "rust_playground";
+    type Error = RustPlaygroundError;
+    type Args = RustPlaygroundArgs;
+    type Output = RustPlaygroundOutput;
+
+    async fn definition(&self, _prompt: String) -> ToolDefinition {
+        ToolDefinition {
+            name: Self::NAME.to_string(),
+            description: "Run Rust code on the Rust Playground (play.rust-lang.org) and get its \
+                output plus a share link. Modes: run, test (cargo test), clippy (lints) and miri \
+                (detects undefined behavior). Prefer this over Godbolt when the question is about \
+                what Rust code does rather than what it compiles to. The top 100 crates of \
+                crates.io are available."
+                .to_string(),
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "code": {"type": "string", "description": "Rust source, a binary with a main function or a library"},
+                    "mode": {"type": "string", "enum": ["run", "test", "clippy", "miri"], "description": "Defaults to run"},
+                    "channel": {"type": "string", "enum": ["stable", "beta", "nightly"], "description": "Defaults to stable, miri always uses nightly"},
+                    "release": {"type": "boolean", "description": "Build with optimizations, defaults to false"},
+                    "edition": {"type": "string", "enum": ["2015", "2018", "2021", "2024"], "description": "Defaults to 2024"}
+                },
+                "required": ["code"]
+            }),
+        }
+    }
+
+    async fn call(&self, args: Self::Args) -> Result<Self::Output, Self::Error> {
+        let (result, share_url) = tokio::join!(
+            Self::execute(&self.client, &args),
+            Self::share(&self.client, &args)
+        );
+
+        // The share link is a nicety, the output is what matters
+        let share_url = share_url
+            .inspect_err(|e| tracing::warn!(?e, "Failed to create playground share link"))
+            .ok();
+
+        match result {
+            Ok(response) => {
+                let (stdout, stdout_truncated) = truncate_output(&response.stdout);
+                let (stderr, stderr_truncated) = truncate_output(&response.stderr);
+
+                Ok(RustPlaygroundOutput {
+                    success: response.success,
+                    stdout,
+                    stderr,
+                    exit_detail: response.exit_detail,
+                    truncated: stdout_truncated || stderr_truncated,
+                    share_url,
+                    error: None,
+                })
+            }
+            Err(e) => Ok(RustPlaygroundOutput {
+                success: false,
+                stdout: String::new(),
+                stderr: String::new(),
+                exit_detail: String::new(),
+                truncated: false,
+                share_url,
+                error: Some(e.to_string()),
+            }),
+        }
+    }
+}
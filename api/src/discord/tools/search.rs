@@ -0,0 +1,413 @@
+use std::{num::NonZeroU32, sync::Arc};
+
+use async_trait::async_trait;
+use eyre::Context as _;
+use governor::{DefaultDirectRateLimiter, Quota, RateLimiter};
+use regex::Regex;
+use serde::Deserialize;
+
+use crate::{
+    config::WebSearchConfig,
+    discord::constants::URL_FETCH_TIMEOUT_SECS,
+    outbound::{HttpClient, RequestBuilder},
+};
+
+const USER_AGENT: &str = "Mozilla/5.0 (Macintosh; Intel Mac OS X 10_15_7) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/120.0.0.0 Safari/537.36";
+
+/// Results returned to the agent per search
+const MAX_RESULTS: usize = 10;
+
+/// A search result normalized across providers
+#[derive(Debug, Clone, PartialEq)]
+pub struct SearchResult {
+    pub title: String,
+    pub url: String,
+    pub snippet: String,
+}
+
+#[async_trait]
+pub trait SearchProvider: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    /// Requests allowed per minute, to stay within the provider's limits
+    fn requests_per_minute(&self) -> u32;
+
+    async fn search(&self, query: &str) -> Result<Vec<SearchResult>, eyre::Error>;
+}
+
+/// A search request as a browser would send it
+fn get(client: &HttpClient, url: url::Url) -> RequestBuilder {
+    client
+        .get(url)
+        .timeout(URL_FETCH_TIMEOUT_SECS)
+        .header(reqwest::header::USER_AGENT, USER_AGENT)
+}
+
+fn url_with_query(base: &str, params: &[(&str, &str)]) -> Result<url::Url, eyre::Error> {
+    let mut url = url::Url::parse(base).wrap_err_with(|| format!("invalid search URL {base}"))?;
+    url.query_pairs_mut().extend_pairs(params);
+    Ok(url)
+}
+
+/// A self-hosted SearxNG instance with the JSON format enabled
+pub struct SearxNg {
+    base_url: String,
+    client: HttpClient,
+}
+
+#[async_trait]
+impl SearchProvider for SearxNg {
+    fn name(&self) -> &'static str {
+        "searxng"
+    }
+
+    fn requests_per_minute(&self) -> u32 {
+        30
+    }
+
+    async fn search(&self, query: &str) -> Result<Vec<SearchResult>, eyre::Error> {
+        #[derive(Deserialize)]
+        struct Response {
+            results: Vec<Result>,
+        }
+
+        #[derive(Deserialize)]
+        struct Result {
+            title: String,
+            url: String,
+            #[serde(default)]
+            content: String,
+        }
+
+        let response: Response = get(
+            &self.client,
+            url_with_query(
+                &format!("{}/search", self.base_url.trim_end_matches('/')),
+                &[("q", query), ("format", "json")],
+            )?,
+        )
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await
+        .wrap_err("failed to parse SearxNG response")?;
+
+        Ok(response
+            .results
+            .into_iter()
+            .map(|r| SearchResult {
+                title: r.title,
+                url: r.url,
+                snippet: r.content,
+            })
+            .collect())
+    }
+}
+
+/// The Brave Search API
+pub struct Brave {
+    api_key: String,
+    client: HttpClient,
+}
+
+#[async_trait]
+impl SearchProvider for Brave {
+    fn name(&self) -> &'static str {
+        "brave"
+    }
+
+    fn requests_per_minute(&self) -> u32 {
+        // The free plan allows 1 request per second
+        60
+    }
+
+    async fn search(&self, query: &str) -> Result<Vec<SearchResult>, eyre::Error> {
+        #[derive(Deserialize)]
+        struct Response {
+            web: Option<Web>,
+        }
+
+        #[derive(Deserialize)]
+        struct Web {
+            results: Vec<Result>,
+        }
+
+        #[derive(Deserialize)]
+        struct Result {
+            title: String,
+            url: String,
+            #[serde(default)]
+            description: String,
+        }
+
+        let response: Response = get(
+            &self.client,
+            url_with_query(
+                "https://api.search.brave.com/res/v1/web/search",
+                &[("q", query)],
+            )?,
+        )
+        .header("Accept", "application/json")
+        .header("X-Subscription-Token", &self.api_key)
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await
+        .wrap_err("failed to parse Brave Search response")?;
+
+        Ok(response
+            .web
+            .map(|w| w.results)
+            .unwrap_or_default()
+            .into_iter()
+            .map(|r| SearchResult {
+                // Matches are highlighted with <strong>
+                title: html_to_text(&r.title),
+                url: r.url,
+                snippet: html_to_text(&r.description),
+            })
+            .collect())
+    }
+}
+
+/// Scrapes the HTML version of DuckDuckGo, no API key needed
+pub struct DuckDuckGo {
+    client: HttpClient,
+}
+
+#[async_trait]
+impl SearchProvider for DuckDuckGo {
+    fn name(&self) -> &'static str {
+        "duckduckgo"
+    }
+
+    fn requests_per_minute(&self) -> u32 {
+        // Avoid being flagged as a bot
+        10
+    }
+
+    async fn search(&self, query: &str) -> Result<Vec<SearchResult>, eyre::Error> {
+        let html = get(
+            &self.client,
+            url_with_query("https://html.duckduckgo.com/html/", &[("q", query)])?,
+        )
+        .send()
+        .await?
+        .error_for_status()?
+        .text()
+        .await?;
+
+        Ok(parse_duckduckgo_results(&html))
+    }
+}
+
+fn parse_duckduckgo_results(html: &str) -> Vec<SearchResult> {
+    let (Ok(link_regex), Ok(snippet_regex)) = (
+        Regex::new(r#"(?s)<a[^>]*class="result__a"[^>]*href="([^"]+)"[^>]*>(.*?)</a>"#),
+        Regex::new(r#"(?s)class="result__snippet"[^>]*>(.*?)</a>"#),
+    ) else {
+        return Vec::new();
+    };
+
+    let links: Vec<_> = link_regex.captures_iter(html).collect();
+
+    links
+        .iter()
+        .enumerate()
+        .filter_map(|(i, link)| {
+            let whole = link.get(0)?;
+            let href = link.get(1)?.as_str();
+            let title = link.get(2)?.as_str();
+
+            // The snippet is between this result's link and the next one
+            let end = links
+                .get(i + 1)
+                .and_then(|next| next.get(0))
+                .map(|m| m.start())
+                .unwrap_or(html.len());
+            let snippet = html
+                .get(whole.end()..end)
+                .and_then(|rest| snippet_regex.captures(rest))
+                .and_then(|c| c.get(1))
+                .map(|m| html_to_text(m.as_str()))
+                .unwrap_or_default();
+
+            Some(SearchResult {
+                title: html_to_text(title),
+                url: duckduckgo_target_url(&html_to_text(href)),
+                snippet,
+            })
+        })
+        .collect()
+}
+
+/// Result links go through a redirect, e.g. `//duckduckgo.com/l/?uddg=<url>`
+fn duckduckgo_target_url(href: &str) -> String {
+    let absolute = if href.starts_with("//") {
+        format!("https:{href}")
+    } else {
+        href.to_string()
+    };
+
+    url::Url::parse(&absolute)
+        .ok()
+        .and_then(|u| {
+            u.query_pairs()
+                .find(|(k, _)| k == "uddg")
+                .map(|(_, v)| v.into_owned())
+        })
+        .unwrap_or(absolute)
+}
+
+/// Strip tags and decode the common entities of a HTML fragment
+fn html_to_text(html: &str) -> String {
+    let without_tags = match Regex::new(r"<[^>]*>") {
+        Ok(tags) => tags.replace_all(html, "").into_owned(),
+        Err(_) => html.to_string(),
+    };
+
+    without_tags
+        .replace("&quot;", "\"")
+        .replace("&#x27;", "'")
+        .replace("&#39;", "'")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&nbsp;", " ")
+        .replace("&amp;", "&")
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+struct RateLimitedProvider {
+    provider: Box<dyn SearchProvider>,
+    limiter: DefaultDirectRateLimiter,
+}
+
+/// Searches the configured providers in order, failing over to the next one
+/// if a provider errors or is out of its rate limit
+#[derive(Clone)]
+pub struct WebSearch {
+    providers: Arc<Vec<RateLimitedProvider>>,
+}
+
+impl WebSearch {
+    pub fn new(config: &WebSearchConfig, client: HttpClient) -> Self {
+        let providers = config
+            .providers
+            .iter()
+            .filter_map(|name| -> Option<Box<dyn SearchProvider>> {
+                match name.as_str() {
+                    "searxng" => match &config.searxng_url {
+                        Some(base_url) => Some(Box::new(SearxNg {
+                            base_url: base_url.clone(),
+                            client: client.clone(),
+                        })),
+                        None => {
+                            tracing::warn!("SearxNG search provider needs SEARXNG_URL, skipping");
+                            None
+                        }
+                    },
+                    "brave" => match &config.brave_api_key {
+                        Some(api_key) => Some(Box::new(Brave {
+                            api_key: api_key.clone(),
+                            client: client.clone(),
+                        })),
+                        None => {
+                            tracing::warn!(
+                                "Brave search provider needs BRAVE_SEARCH_API_KEY, skipping"
+                            );
+                            None
+                        }
+                    },
+                    "duckduckgo" => Some(Box::new(DuckDuckGo {
+                        client: client.clone(),
+                    })),
+                    other => {
+                        tracing::warn!("Unknown search provider `{other}`, skipping");
+                        None
+                    }
+                }
+            })
+            .map(|provider| RateLimitedProvider {
+                limiter: RateLimiter::direct(Quota::per_minute(
+                    NonZeroU32::new(provider.requests_per_minute()).unwrap_or(NonZeroU32::MIN),
+                )),
+                provider,
+            })
+            .collect();
+
+        Self {
+            providers: Arc::new(providers),
+        }
+    }
+
+    pub async fn search(
+        &self,
+        query: &str,
+    ) -> Result<(&'static str, Vec<SearchResult>), eyre::Error> {
+        let mut errors = Vec::new();
+
+        for p in self.providers.iter() {
+            let name = p.provider.name();
+
+            if p.limiter.check().is_err() {
+                errors.push(format!("{name}: rate limited"));
+                continue;
+            }
+
+            match p.provider.search(query).await {
+                Ok(mut results) => {
+                    results.truncate(MAX_RESULTS);
+                    return Ok((name, results));
+                }
+                Err(e) => {
+                    tracing::warn!(?e, provider = name, "Search provider failed");
+                    errors.push(format!("{name}: {e}"));
+                }
+            }
+        }
+
+        if errors.is_empty() {
+            eyre::bail!("no search provider is configured");
+        }
+
+        eyre::bail!("all search providers failed ({})", errors.join("; "))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_duckduckgo_results_extracts_links_and_snippets() {
+        let html = r#"
+            <div class="result">
+              <a rel="nofollow" class="result__a" href="//duckduckgo.com/l/?uddg=https%3A%2F%2Fwww.rust-lang.org%2F&amp;rut=abc">Rust <b>Programming</b> Language</a>
+              <a class="result__snippet" href="x">A language empowering everyone &amp; more.</a>
+            </div>
+            <div class="result">
+              <a rel="nofollow" class="result__a" href="https://example.com/">Example</a>
+            </div>
+        "#;
+
+        assert_eq!(
+            parse_duckduckgo_results(html),
+            vec![
+                SearchResult {
+                    title: "Rust Programming Language".to_string(),
+                    url: "https://www.rust-lang.org/".to_string(),
+                    snippet: "A language empowering everyone & more.".to_string(),
+                },
+                SearchResult {
+                    title: "Example".to_string(),
+                    url: "https://example.com/".to_string(),
+                    snippet: String::new(),
+                },
+            ]
+        );
+    }
+}
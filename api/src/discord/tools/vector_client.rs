@@ -1,13 +1,16 @@
-use chromadb::client::ChromaClientOptions;
-use chromadb::collection::{CollectionEntries, QueryOptions};
-use chromadb::{ChromaClient, ChromaCollection};
+use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use std::sync::Arc;
 use thiserror::Error;
 use uuid::Uuid;
 
-use crate::config::VectorDbConfig;
-use crate::utils::embed_texts;
+use super::vector_store::{ChromaStore, QdrantStore, VectorPoint, VectorStore};
+use crate::config::{VectorDbBackend, VectorDbConfig};
+use crate::embedding::Embedder;
+use crate::outbound::HttpClient;
+
+/// Memories copied per request when migrating between stores
+const COPY_BATCH_SIZE: usize = 500;
 
 /// Type alias for the shared vector client wrapped in Arc for easy sharing across threads
 #[derive(Clone)]
@@ -23,104 +26,205 @@ impl std::ops::Deref for SharedVectorClient {
 
 impl SharedVectorClient {
     /// Create a new shared vector client wrapped in Arc for easy sharing across threads
-    pub async fn new(config: VectorDbConfig) -> Result<SharedVectorClient, VectorClientError> {
-        let client = VectorClient::new(config).await?;
+    pub async fn new(
+        config: VectorDbConfig,
+        embedder: Embedder,
+        http: HttpClient,
+    ) -> Result<SharedVectorClient, VectorClientError> {
+        let client = VectorClient::new(config, embedder, http).await?;
         Ok(SharedVectorClient(Arc::new(client)))
     }
 }
 
+/// The namespace a memory lives in, each with its own collection
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MemoryScope {
+    /// About a user, shared across all channels
+    User(u64),
+    Channel(u64),
+    Guild(u64),
+    Global,
+}
+
+impl MemoryScope {
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::User(_) => "user",
+            Self::Channel(_) => "channel",
+            Self::Guild(_) => "guild",
+            Self::Global => "global",
+        }
+    }
+}
+
+/// The `scope` parameter of the memory tools
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ScopeArg {
+    #[default]
+    Channel,
+    User,
+    Guild,
+    Global,
+}
+
+/// The scopes the memory tools of a channel can reach
+#[derive(Debug, Clone, Copy)]
+pub struct MemoryScopes {
+    pub channel_id: u64,
+    pub guild_id: Option<u64>,
+    /// The user of a DM conversation, whose memories are kept apart from the
+    /// servers' and who can't reach the shared scopes
+    pub dm_user_id: Option<u64>,
+}
+
+impl MemoryScopes {
+    pub fn resolve(&self, scope: ScopeArg, user_id: Option<&str>) -> Result<MemoryScope, String> {
+        if let Some(dm_user_id) = self.dm_user_id {
+            return match scope {
+                ScopeArg::Channel | ScopeArg::User => Ok(MemoryScope::User(dm_user_id)),
+                ScopeArg::Guild | ScopeArg::Global => {
+                    Err("only the user scope is available in DMs".to_string())
+                }
+            };
+        }
+
+        match scope {
+            ScopeArg::Channel => Ok(MemoryScope::Channel(self.channel_id)),
+            ScopeArg::User => user_id
+                .and_then(super::reminders::parse_user_id)
+                .map(|user_id| MemoryScope::User(user_id.get()))
+                .ok_or_else(|| "the user scope needs a valid user_id".to_string()),
+            ScopeArg::Guild => self
+                .guild_id
+                .map(MemoryScope::Guild)
+                .ok_or_else(|| "there is no guild scope outside of a server".to_string()),
+            ScopeArg::Global => Ok(MemoryScope::Global),
+        }
+    }
+
+    /// JSON schema of the `scope` and `user_id` parameters
+    pub fn parameters() -> Value {
+        serde_json::json!({
+            "scope": {
+                "type": "string",
+                "enum": ["channel", "user", "guild", "global"],
+                "description": "Where the memory lives: channel (default) for this channel, user for facts about a user across all channels, guild for this whole server, global for everywhere"
+            },
+            "user_id": {
+                "type": "string",
+                "description": "Discord user ID, required for the user scope"
+            }
+        })
+    }
+}
+
 #[derive(Debug, Error)]
 #[error("Vector client error: {0}")]
-pub struct VectorClientError(String);
+pub struct VectorClientError(pub(super) String);
 
 /// Shared vector database client with common functionality
 pub struct VectorClient {
-    client: ChromaClient,
-    config: VectorDbConfig,
+    store: Box<dyn VectorStore>,
+    embedder: Embedder,
+    default_collection: Option<String>,
 }
 
 impl VectorClient {
     /// Create a new vector client with configuration
-    pub async fn new(config: VectorDbConfig) -> Result<Self, VectorClientError> {
-        let client_options = ChromaClientOptions {
-            url: Some(config.url.clone()),
-            database: config.database.clone(),
-            auth: chromadb::client::ChromaAuthMethod::TokenAuth {
-                token: config.token.clone(),
-                header: chromadb::client::ChromaTokenHeader::XChromaToken,
-            },
+    pub async fn new(
+        config: VectorDbConfig,
+        embedder: Embedder,
+        http: HttpClient,
+    ) -> Result<Self, VectorClientError> {
+        let store: Box<dyn VectorStore> = match &config.backend {
+            VectorDbBackend::Chroma(chroma) => Box::new(ChromaStore::new(chroma).await?),
+            VectorDbBackend::Qdrant(qdrant) => Box::new(QdrantStore::new(qdrant, http)),
         };
 
-        let client = ChromaClient::new(client_options)
-            .await
-            .map_err(|e| VectorClientError(format!("Failed to create ChromaDB client: {}", e)))?;
+        Ok(Self {
+            store,
+            embedder,
+            default_collection: config.default_collection,
+        })
+    }
+
+    /// Name of the vector database backend
+    pub fn backend(&self) -> &'static str {
+        self.store.name()
+    }
 
-        Ok(Self { client, config })
+    fn collection_prefix(&self) -> &str {
+        self.default_collection
+            .as_deref()
+            .unwrap_or("discord_memory")
     }
 
-    /// Get the collection name to use, incorporating channel ID if available
-    pub fn get_collection_name(&self, channel_id: u64) -> String {
-        match &self.config.default_collection {
-            Some(col) => format!("{}_channel_{}", col, channel_id),
-            None => format!("discord_memory_channel_{}", channel_id),
+    /// Get the collection name of a scope
+    pub fn get_collection_name(&self, scope: MemoryScope) -> String {
+        let prefix = self.collection_prefix();
+
+        match scope {
+            MemoryScope::User(user_id) => format!("{prefix}_user_{user_id}"),
+            MemoryScope::Channel(channel_id) => format!("{prefix}_channel_{channel_id}"),
+            MemoryScope::Guild(guild_id) => format!("{prefix}_guild_{guild_id}"),
+            MemoryScope::Global => format!("{prefix}_global"),
         }
     }
 
-    /// Get or create a collection
-    async fn get_or_create_collection(
-        &self,
-        collection_name: &str,
-    ) -> Result<ChromaCollection, VectorClientError> {
-        self.client
-            .get_or_create_collection(collection_name, None)
+    /// The scope of a collection, if it's one of the memory collections
+    pub fn parse_collection_name(&self, name: &str) -> Option<MemoryScope> {
+        let rest = name
+            .strip_prefix(self.collection_prefix())?
+            .strip_prefix('_')?;
+        if rest == "global" {
+            return Some(MemoryScope::Global);
+        }
+
+        let (kind, id) = rest.split_once('_')?;
+        let id = id.parse::<u64>().ok()?;
+        match kind {
+            "user" => Some(MemoryScope::User(id)),
+            "channel" => Some(MemoryScope::Channel(id)),
+            "guild" => Some(MemoryScope::Guild(id)),
+            _ => None,
+        }
+    }
+
+    /// All scopes that have memories
+    pub async fn list_scopes(&self) -> Result<Vec<MemoryScope>, VectorClientError> {
+        Ok(self
+            .store
+            .list_collections()
+            .await?
+            .iter()
+            .filter_map(|name| self.parse_collection_name(name))
+            .collect())
+    }
+
+    /// Number of memories of a scope
+    pub async fn count(&self, scope: MemoryScope) -> Result<usize, VectorClientError> {
+        self.store.count(&self.get_collection_name(scope)).await
+    }
+
+    async fn embed(&self, information: &str) -> Result<Vec<f32>, VectorClientError> {
+        self.embedder
+            .embed(vec![information.to_string()])
             .await
-            .map_err(|e| {
-                VectorClientError(format!(
-                    "Failed to get or create collection {}: {}",
-                    collection_name, e
-                ))
-            })
+            .map_err(|e| VectorClientError(format!("Failed to generate embeddings: {}", e)))?
+            .pop()
+            .ok_or_else(|| VectorClientError("No embeddings generated".to_string()))
     }
 
     /// Store information in the vector database
     pub async fn store(
         &self,
         information: &str,
-        channel_id: u64,
+        scope: MemoryScope,
         metadata: Option<Value>,
     ) -> Result<String, VectorClientError> {
-        let collection_name = self.get_collection_name(channel_id);
-        let collection = self.get_or_create_collection(&collection_name).await?;
-
-        let embeddings = embed_texts(vec![information.to_string()])
-            .map_err(|e| VectorClientError(format!("Failed to generate embeddings: {}", e)))?;
-
-        let embedding = embeddings
-            .first()
-            .ok_or_else(|| VectorClientError("No embeddings generated".to_string()))?;
-
-        // Create point
         let point_id = Uuid::new_v4().to_string();
-
-        // Prepare metadata
-        let final_metadata = match metadata {
-            Some(Value::Object(obj)) => obj,
-            Some(_) => serde_json::Map::new(),
-            None => serde_json::Map::new(),
-        };
-
-        let collection_entries = CollectionEntries {
-            ids: vec![&point_id],
-            embeddings: Some(vec![embedding.clone()]),
-            metadatas: Some(vec![final_metadata]),
-            documents: Some(vec![information]),
-        };
-
-        // Store the point
-        collection
-            .upsert(collection_entries, None)
-            .await
-            .map_err(|e| VectorClientError(format!("Failed to store point: {}", e)))?;
+        self.update(&point_id, information, scope, metadata).await?;
 
         Ok(point_id)
     }
@@ -130,150 +234,173 @@ impl VectorClient {
         &self,
         point_id: &str,
         information: &str,
-        channel_id: u64,
+        scope: MemoryScope,
         metadata: Option<Value>,
     ) -> Result<(), VectorClientError> {
-        let collection_name = self.get_collection_name(channel_id);
-        let collection = self.get_or_create_collection(&collection_name).await?;
-
-        let embeddings = embed_texts(vec![information.to_string()])
-            .map_err(|e| VectorClientError(format!("Failed to generate embeddings: {}", e)))?;
-
-        let embedding = embeddings
-            .first()
-            .ok_or_else(|| VectorClientError("No embeddings generated".to_string()))?;
-
-        // Prepare metadata
-        let final_metadata = match metadata {
+        let metadata = match metadata {
             Some(Value::Object(obj)) => obj,
-            Some(_) => serde_json::Map::new(),
-            None => serde_json::Map::new(),
+            _ => serde_json::Map::new(),
         };
 
-        let collection_entries = CollectionEntries {
-            ids: vec![point_id],
-            embeddings: Some(vec![embedding.clone()]),
-            metadatas: Some(vec![final_metadata]),
-            documents: Some(vec![information]),
-        };
+        // Upserting replaces the existing point
+        self.store
+            .upsert(
+                &self.get_collection_name(scope),
+                vec![VectorPoint {
+                    id: point_id.to_string(),
+                    document: information.to_string(),
+                    embedding: self.embed(information).await?,
+                    metadata,
+                }],
+            )
+            .await
+    }
+
+    /// A memory by ID, none if it doesn't exist
+    pub async fn get(
+        &self,
+        scope: MemoryScope,
+        point_id: &str,
+    ) -> Result<Option<MemoryRecord>, VectorClientError> {
+        let points = self
+            .store
+            .get(&self.get_collection_name(scope), &[point_id])
+            .await?;
+
+        Ok(points.into_iter().next().map(|point| MemoryRecord {
+            id: point.id,
+            content: point.document,
+            metadata: Some(point.metadata),
+        }))
+    }
 
-        // Update the point using upsert (this will replace the existing point)
-        collection
-            .upsert(collection_entries, None)
+    /// Delete memories by ID
+    pub async fn delete(&self, scope: MemoryScope, ids: &[&str]) -> Result<(), VectorClientError> {
+        self.store
+            .delete(&self.get_collection_name(scope), ids)
             .await
-            .map_err(|e| VectorClientError(format!("Failed to update point: {}", e)))?;
+    }
+
+    /// Store memories with their IDs and metadata, replacing the ones with the
+    /// same ID. Returns the number of memories stored.
+    pub async fn import(
+        &self,
+        scope: MemoryScope,
+        records: &[MemoryRecord],
+    ) -> Result<usize, VectorClientError> {
+        const BATCH_SIZE: usize = 64;
+
+        let collection_name = self.get_collection_name(scope);
+        for batch in records.chunks(BATCH_SIZE) {
+            // Embeddings aren't exported since the model may differ between
+            // environments
+            let embeddings = self
+                .embedder
+                .embed(batch.iter().map(|r| r.content.clone()).collect())
+                .await
+                .map_err(|e| VectorClientError(format!("Failed to generate embeddings: {}", e)))?;
+
+            let points = batch
+                .iter()
+                .zip(embeddings)
+                .map(|(record, embedding)| VectorPoint {
+                    id: record.id.clone(),
+                    document: record.content.clone(),
+                    embedding,
+                    metadata: record.metadata.clone().unwrap_or_default(),
+                })
+                .collect();
+
+            self.store.upsert(&collection_name, points).await?;
+        }
 
-        Ok(())
+        Ok(records.len())
     }
 
-    /// Delete information from the vector database
-    pub async fn delete(
+    /// All memories of a scope along with their embeddings
+    pub async fn all_memories(
         &self,
-        channel_id: u64,
-        ids: Option<Vec<&str>>,
-        where_metadata: Option<Value>,
-        where_document: Option<Value>,
-    ) -> Result<(), VectorClientError> {
-        let collection_name = self.get_collection_name(channel_id);
-
-        // Try to get the collection, return error if it doesn't exist
-        let collection = match self.client.get_collection(&collection_name).await {
-            Ok(collection) => collection,
-            Err(e) => {
-                return Err(VectorClientError(format!(
-                    "Failed to get collection {}: {}",
-                    collection_name, e
-                )));
-            }
-        };
+        scope: MemoryScope,
+    ) -> Result<Vec<StoredMemory>, VectorClientError> {
+        let points = self.store.all(&self.get_collection_name(scope)).await?;
+
+        Ok(points
+            .into_iter()
+            .map(|point| StoredMemory {
+                timestamp: point
+                    .metadata
+                    .get("timestamp")
+                    .and_then(|t| t.as_str())
+                    .and_then(|t| chrono::DateTime::parse_from_rfc3339(t).ok())
+                    .map(|t| t.with_timezone(&chrono::Utc)),
+                point_id: point.id,
+                content: point.document,
+                embedding: point.embedding,
+                metadata: Some(point.metadata),
+            })
+            .collect())
+    }
 
-        // Call ChromaDB delete method
-        collection
-            .delete(ids, where_metadata, where_document)
-            .await
-            .map_err(|e| VectorClientError(format!("Failed to delete entries: {}", e)))?;
+    /// Copy all memories to another store as they are, including their
+    /// embeddings. Returns the number of memories copied.
+    pub async fn copy_to(&self, target: &VectorClient) -> Result<usize, VectorClientError> {
+        let mut copied = 0;
+        for scope in self.list_scopes().await? {
+            let points = self.store.all(&self.get_collection_name(scope)).await?;
+            copied += points.len();
+
+            for batch in points.chunks(COPY_BATCH_SIZE) {
+                target
+                    .store
+                    .upsert(&target.get_collection_name(scope), batch.to_vec())
+                    .await?;
+            }
+            tracing::info!(?scope, copied, "Copied memories");
+        }
 
-        Ok(())
+        Ok(copied)
     }
 
-    /// Search for information in the vector database
+    /// Search for information in the vector database across scopes, the most
+    /// relevant results first
     pub async fn search(
         &self,
         query: &str,
-        channel_id: u64,
+        scopes: &[MemoryScope],
         limit: u64,
     ) -> Result<Vec<SearchResult>, VectorClientError> {
-        let collection_name = self.get_collection_name(channel_id);
-
-        // Try to get the collection, return empty results if it doesn't exist
-        let collection = match self.client.get_collection(&collection_name).await {
-            Ok(collection) => collection,
-            Err(_) => {
-                return Ok(vec![]);
-            }
-        };
-
-        let embeddings = embed_texts(vec![query.to_string()]).map_err(|e| {
-            VectorClientError(format!("Failed to generate query embeddings: {}", e))
-        })?;
-
-        let query_embedding = embeddings
-            .first()
-            .ok_or_else(|| VectorClientError("No query embeddings generated".to_string()))?;
-
-        // Search for similar points using ChromaDB query
-        let query_options = QueryOptions {
-            query_texts: None,
-            query_embeddings: Some(vec![query_embedding.clone()]),
-            where_metadata: None,
-            where_document: None,
-            n_results: Some(limit as usize),
-            include: Some(vec!["documents", "metadatas", "distances"]),
-        };
-
-        let mut query_result = collection
-            .query(query_options, None)
-            .await
-            .map_err(|e| VectorClientError(format!("Failed to search points: {}", e)))?;
-
-        let results = if let (Some(ids), Some(documents), Some(distances)) = (
-            query_result.ids.pop(),
-            query_result.documents.take().and_then(|mut v| v.pop()),
-            query_result.distances.take().and_then(|mut v| v.pop()),
-        ) {
-            ids.into_iter()
-                .zip(documents)
-                .zip(distances)
-                .zip(
-                    query_result
-                        .metadatas
-                        .take()
-                        .and_then(|mut v| v.pop())
-                        .unwrap_or_else(std::vec::Vec::new)
-                        .into_iter()
-                        .chain(std::iter::repeat(None)),
+        let query_embedding = self.embed(query).await?;
+
+        let mut results = Vec::new();
+        for scope in scopes {
+            let points = self
+                .store
+                .query(
+                    &self.get_collection_name(*scope),
+                    &query_embedding,
+                    limit as usize,
                 )
-                .map(|(((id, content), distance), metadata)| {
-                    // Convert distance to similarity score (ChromaDB returns distances, we want similarity)
-                    let score = 1.0 - distance.clamp(0.0, 1.0);
-                    let timestamp = metadata
-                        .as_ref()
-                        .and_then(|m| m.get("timestamp"))
+                .await?;
+
+            results.extend(points.into_iter().map(|scored| {
+                SearchResult {
+                    scope: scope.label(),
+                    timestamp: scored
+                        .point
+                        .metadata
+                        .get("timestamp")
                         .and_then(|t| t.as_str())
-                        .map(str::to_string);
-
-                    SearchResult {
-                        point_id: id.clone(),
-                        content,
-                        score,
-                        metadata: metadata.map(serde_json::Value::Object),
-                        timestamp,
-                    }
-                })
-                .collect()
-        } else {
-            Vec::new()
-        };
+                        .map(str::to_string),
+                    point_id: scored.point.id,
+                    content: scored.point.document,
+                    score: scored.score,
+                    metadata: Some(Value::Object(scored.point.metadata)),
+                }
+            }));
+        }
+
+        results.sort_by(|a, b| b.score.total_cmp(&a.score));
+        results.truncate(limit as usize);
 
         Ok(results)
     }
@@ -281,9 +408,79 @@ impl VectorClient {
 
 #[derive(Debug, Clone)]
 pub struct SearchResult {
+    pub scope: &'static str,
     pub point_id: String,
     pub content: String,
     pub score: f32,
     pub metadata: Option<Value>,
     pub timestamp: Option<String>,
 }
+
+/// A memory as stored, for maintenance rather than retrieval
+#[derive(Debug, Clone)]
+pub struct StoredMemory {
+    pub point_id: String,
+    pub content: String,
+    pub embedding: Vec<f32>,
+    pub metadata: Option<serde_json::Map<String, Value>>,
+    /// When the memory was stored or last updated
+    pub timestamp: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+/// A memory as exported and imported, one per line of a JSONL file
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MemoryRecord {
+    pub id: String,
+    pub content: String,
+    #[serde(default)]
+    pub metadata: Option<serde_json::Map<String, Value>>,
+}
+
+impl From<StoredMemory> for MemoryRecord {
+    fn from(memory: StoredMemory) -> Self {
+        Self {
+            id: memory.point_id,
+            content: memory.content,
+            metadata: memory.metadata,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn memory_scopes_resolve() {
+        let scopes = MemoryScopes {
+            channel_id: 1,
+            guild_id: None,
+            dm_user_id: None,
+        };
+
+        assert_eq!(
+            scopes.resolve(ScopeArg::Channel, None),
+            Ok(MemoryScope::Channel(1))
+        );
+        assert_eq!(
+            scopes.resolve(ScopeArg::User, Some("<@42>")),
+            Ok(MemoryScope::User(42))
+        );
+        assert!(scopes.resolve(ScopeArg::User, None).is_err());
+        assert!(scopes.resolve(ScopeArg::Guild, None).is_err());
+
+        let dm = MemoryScopes {
+            dm_user_id: Some(7),
+            ..scopes
+        };
+        assert_eq!(
+            dm.resolve(ScopeArg::Channel, None),
+            Ok(MemoryScope::User(7))
+        );
+        assert_eq!(
+            dm.resolve(ScopeArg::User, Some("<@42>")),
+            Ok(MemoryScope::User(7))
+        );
+        assert!(dm.resolve(ScopeArg::Global, None).is_err());
+    }
+}
@@ -0,0 +1,664 @@
+use async_trait::async_trait;
+use chromadb::client::ChromaClientOptions;
+use chromadb::collection::{CollectionEntries, GetOptions, QueryOptions};
+use chromadb::{ChromaClient, ChromaCollection};
+use serde::Deserialize;
+use serde_json::{Map, Value, json};
+
+use super::vector_client::VectorClientError;
+use crate::{
+    config::{ChromaConfig, QdrantConfig},
+    outbound::HttpClient,
+};
+
+/// Points fetched per request when reading whole collections
+const PAGE_SIZE: usize = 500;
+
+/// A stored document with its embedding
+#[derive(Debug, Clone)]
+pub struct VectorPoint {
+    pub id: String,
+    pub document: String,
+    /// Empty if not requested
+    pub embedding: Vec<f32>,
+    pub metadata: Map<String, Value>,
+}
+
+/// A point matching a query, without its embedding
+#[derive(Debug, Clone)]
+pub struct ScoredPoint {
+    pub point: VectorPoint,
+    /// Similarity to the query from 0.0 to 1.0, higher is more similar
+    pub score: f32,
+}
+
+/// The operations the memories need from a vector database. Collections are
+/// created on the first write, and reading a collection that doesn't exist
+/// yields nothing rather than an error.
+#[async_trait]
+pub trait VectorStore: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    async fn list_collections(&self) -> Result<Vec<String>, VectorClientError>;
+
+    async fn count(&self, collection: &str) -> Result<usize, VectorClientError>;
+
+    /// Insert the points, replacing the ones with the same ID
+    async fn upsert(
+        &self,
+        collection: &str,
+        points: Vec<VectorPoint>,
+    ) -> Result<(), VectorClientError>;
+
+    async fn delete(&self, collection: &str, ids: &[&str]) -> Result<(), VectorClientError>;
+
+    /// The points with the IDs without their embeddings, leaving out the
+    /// missing ones
+    async fn get(
+        &self,
+        collection: &str,
+        ids: &[&str],
+    ) -> Result<Vec<VectorPoint>, VectorClientError>;
+
+    /// The points most similar to the embedding, most similar first
+    async fn query(
+        &self,
+        collection: &str,
+        embedding: &[f32],
+        limit: usize,
+    ) -> Result<Vec<ScoredPoint>, VectorClientError>;
+
+    /// All points of a collection including their embeddings
+    async fn all(&self, collection: &str) -> Result<Vec<VectorPoint>, VectorClientError>;
+}
+
+pub struct ChromaStore {
+    client: ChromaClient,
+}
+
+impl ChromaStore {
+    pub async fn new(config: &ChromaConfig) -> Result<Self, VectorClientError> {
+        let client_options = ChromaClientOptions {
+            url: Some(config.url.clone()),
+            database: config.database.clone(),
+            auth: chromadb::client::ChromaAuthMethod::TokenAuth {
+                token: config.token.clone(),
+                header: chromadb::client::ChromaTokenHeader::XChromaToken,
+            },
+        };
+
+        let client = ChromaClient::new(client_options)
+            .await
+            .map_err(|e| VectorClientError(format!("Failed to create ChromaDB client: {}", e)))?;
+
+        Ok(Self { client })
+    }
+
+    async fn get_collection(&self, collection: &str) -> Option<ChromaCollection> {
+        self.client.get_collection(collection).await.ok()
+    }
+}
+
+#[async_trait]
+impl VectorStore for ChromaStore {
+    fn name(&self) -> &'static str {
+        "chroma"
+    }
+
+    async fn list_collections(&self) -> Result<Vec<String>, VectorClientError> {
+        let collections = self
+            .client
+            .list_collections()
+            .await
+            .map_err(|e| VectorClientError(format!("Failed to list collections: {}", e)))?;
+
+        Ok(collections.iter().map(|c| c.name().to_string()).collect())
+    }
+
+    async fn count(&self, collection: &str) -> Result<usize, VectorClientError> {
+        match self.get_collection(collection).await {
+            Some(collection) => collection
+                .count()
+                .await
+                .map_err(|e| VectorClientError(format!("Failed to count points: {}", e))),
+            None => Ok(0),
+        }
+    }
+
+    async fn upsert(
+        &self,
+        collection: &str,
+        points: Vec<VectorPoint>,
+    ) -> Result<(), VectorClientError> {
+        let chroma_collection = self
+            .client
+            .get_or_create_collection(collection, None)
+            .await
+            .map_err(|e| {
+                VectorClientError(format!(
+                    "Failed to get or create collection {}: {}",
+                    collection, e
+                ))
+            })?;
+
+        let collection_entries = CollectionEntries {
+            ids: points.iter().map(|p| p.id.as_str()).collect(),
+            embeddings: Some(points.iter().map(|p| p.embedding.clone()).collect()),
+            metadatas: Some(points.iter().map(|p| p.metadata.clone()).collect()),
+            documents: Some(points.iter().map(|p| p.document.as_str()).collect()),
+        };
+
+        chroma_collection
+            .upsert(collection_entries, None)
+            .await
+            .map_err(|e| VectorClientError(format!("Failed to store points: {}", e)))?;
+
+        Ok(())
+    }
+
+    async fn delete(&self, collection: &str, ids: &[&str]) -> Result<(), VectorClientError> {
+        let chroma_collection = self.client.get_collection(collection).await.map_err(|e| {
+            VectorClientError(format!("Failed to get collection {}: {}", collection, e))
+        })?;
+
+        chroma_collection
+            .delete(Some(ids.to_vec()), None, None)
+            .await
+            .map_err(|e| VectorClientError(format!("Failed to delete points: {}", e)))
+    }
+
+    async fn get(
+        &self,
+        collection: &str,
+        ids: &[&str],
+    ) -> Result<Vec<VectorPoint>, VectorClientError> {
+        let Some(chroma_collection) = self.get_collection(collection).await else {
+            return Ok(vec![]);
+        };
+
+        let result = chroma_collection
+            .get(GetOptions {
+                ids: ids.iter().map(|id| id.to_string()).collect(),
+                include: Some(vec!["documents".to_string(), "metadatas".to_string()]),
+                ..Default::default()
+            })
+            .await
+            .map_err(|e| VectorClientError(format!("Failed to get points: {}", e)))?;
+
+        let documents = result.documents.unwrap_or_default();
+        let metadatas = result.metadatas.unwrap_or_default();
+        Ok(result
+            .ids
+            .into_iter()
+            .enumerate()
+            .map(|(i, id)| VectorPoint {
+                id,
+                document: documents.get(i).cloned().flatten().unwrap_or_default(),
+                embedding: Vec::new(),
+                metadata: metadatas.get(i).cloned().flatten().unwrap_or_default(),
+            })
+            .collect())
+    }
+
+    async fn query(
+        &self,
+        collection: &str,
+        embedding: &[f32],
+        limit: usize,
+    ) -> Result<Vec<ScoredPoint>, VectorClientError> {
+        let Some(chroma_collection) = self.get_collection(collection).await else {
+            return Ok(vec![]);
+        };
+
+        let query_options = QueryOptions {
+            query_texts: None,
+            query_embeddings: Some(vec![embedding.to_vec()]),
+            where_metadata: None,
+            where_document: None,
+            n_results: Some(limit),
+            include: Some(vec!["documents", "metadatas", "distances"]),
+        };
+
+        let mut query_result = chroma_collection
+            .query(query_options, None)
+            .await
+            .map_err(|e| VectorClientError(format!("Failed to search points: {}", e)))?;
+
+        let (Some(ids), Some(documents), Some(distances)) = (
+            query_result.ids.pop(),
+            query_result.documents.take().and_then(|mut v| v.pop()),
+            query_result.distances.take().and_then(|mut v| v.pop()),
+        ) else {
+            return Ok(vec![]);
+        };
+
+        let metadatas = query_result
+            .metadatas
+            .take()
+            .and_then(|mut v| v.pop())
+            .unwrap_or_default()
+            .into_iter()
+            .chain(std::iter::repeat(None));
+
+        Ok(ids
+            .into_iter()
+            .zip(documents)
+            .zip(distances)
+            .zip(metadatas)
+            .map(|(((id, document), distance), metadata)| ScoredPoint {
+                point: VectorPoint {
+                    id,
+                    document,
+                    embedding: Vec::new(),
+                    metadata: metadata.unwrap_or_default(),
+                },
+                // Chroma returns distances rather than similarities
+                score: 1.0 - distance.clamp(0.0, 1.0),
+            })
+            .collect())
+    }
+
+    async fn all(&self, collection: &str) -> Result<Vec<VectorPoint>, VectorClientError> {
+        let Some(chroma_collection) = self.get_collection(collection).await else {
+            return Ok(vec![]);
+        };
+
+        let mut points = Vec::new();
+        loop {
+            let page = chroma_collection
+                .get(GetOptions {
+                    limit: Some(PAGE_SIZE),
+                    offset: Some(points.len()),
+                    include: Some(vec![
+                        "documents".to_string(),
+                        "metadatas".to_string(),
+                        "embeddings".to_string(),
+                    ]),
+                    ..Default::default()
+                })
+                .await
+                .map_err(|e| VectorClientError(format!("Failed to get points: {}", e)))?;
+
+            let count = page.ids.len();
+            let documents = page.documents.unwrap_or_default();
+            let metadatas = page.metadatas.unwrap_or_default();
+            let embeddings = page.embeddings.unwrap_or_default();
+
+            for (i, id) in page.ids.into_iter().enumerate() {
+                points.push(VectorPoint {
+                    id,
+                    document: documents.get(i).cloned().flatten().unwrap_or_default(),
+                    embedding: embeddings.get(i).cloned().flatten().unwrap_or_default(),
+                    metadata: metadatas.get(i).cloned().flatten().unwrap_or_default(),
+                });
+            }
+
+            if count < PAGE_SIZE {
+                break;
+            }
+        }
+
+        Ok(points)
+    }
+}
+
+/// Qdrant over its REST API. Documents and metadata are stored in the payload,
+/// and point IDs have to be UUIDs.
+pub struct QdrantStore {
+    url: String,
+    api_key: Option<String>,
+    client: HttpClient,
+}
+
+#[derive(Deserialize)]
+struct QdrantResponse<T> {
+    result: T,
+}
+
+#[derive(Deserialize)]
+struct QdrantPoint {
+    id: Value,
+    #[serde(default)]
+    score: Option<f32>,
+    #[serde(default)]
+    payload: Option<Map<String, Value>>,
+    #[serde(default)]
+    vector: Option<Vec<f32>>,
+}
+
+impl From<QdrantPoint> for VectorPoint {
+    fn from(point: QdrantPoint) -> Self {
+        let mut payload = point.payload.unwrap_or_default();
+        let document = match payload.remove("document") {
+            Some(Value::String(document)) => document,
+            _ => String::new(),
+        };
+        let metadata = match payload.remove("metadata") {
+            Some(Value::Object(metadata)) => metadata,
+            _ => Map::new(),
+        };
+
+        Self {
+            id: match point.id {
+                Value::String(id) => id,
+                id => id.to_string(),
+            },
+            document,
+            embedding: point.vector.unwrap_or_default(),
+            metadata,
+        }
+    }
+}
+
+impl QdrantStore {
+    pub fn new(config: &QdrantConfig, client: HttpClient) -> Self {
+        Self {
+            url: config.url.trim_end_matches('/').to_string(),
+            api_key: config.api_key.clone(),
+            client,
+        }
+    }
+
+    /// Send a request, `None` if the collection doesn't exist
+    async fn request<T: serde::de::DeserializeOwned>(
+        &self,
+        method: reqwest::Method,
+        path: &str,
+        body: Option<Value>,
+    ) -> Result<Option<T>, VectorClientError> {
+        let mut request = self.client.request(method, format!("{}{path}", self.url));
+        if let Some(api_key) = &self.api_key {
+            request = request.header("api-key", api_key);
+        }
+        if let Some(body) = body {
+            request = request.json(&body);
+        }
+
+        let response = request
+            .send()
+            .await
+            .map_err(|e| VectorClientError(format!("Failed to reach Qdrant: {}", e)))?;
+
+        if response.status() == reqwest::StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        if !response.status().is_success() {
+            let status = response.status();
+            let body = response.text().await.unwrap_or_default();
+            return Err(VectorClientError(format!(
+                "Qdrant returned {status}: {body}"
+            )));
+        }
+
+        let response: QdrantResponse<T> = response
+            .json()
+            .await
+            .map_err(|e| VectorClientError(format!("Failed to parse Qdrant response: {}", e)))?;
+
+        Ok(Some(response.result))
+    }
+
+    async fn ensure_collection(
+        &self,
+        collection: &str,
+        dimensions: usize,
+    ) -> Result<(), VectorClientError> {
+        let exists: Option<Value> = self
+            .request(
+                reqwest::Method::GET,
+                &format!("/collections/{collection}"),
+                None,
+            )
+            .await?;
+        if exists.is_some() {
+            return Ok(());
+        }
+
+        let _: Option<Value> = self
+            .request(
+                reqwest::Method::PUT,
+                &format!("/collections/{collection}"),
+                Some(json!({
+                    "vectors": { "size": dimensions, "distance": "Cosine" }
+                })),
+            )
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl VectorStore for QdrantStore {
+    fn name(&self) -> &'static str {
+        "qdrant"
+    }
+
+    async fn list_collections(&self) -> Result<Vec<String>, VectorClientError> {
+        #[derive(Deserialize)]
+        struct Collections {
+            collections: Vec<Collection>,
+        }
+
+        #[derive(Deserialize)]
+        struct Collection {
+            name: String,
+        }
+
+        let collections: Option<Collections> = self
+            .request(reqwest::Method::GET, "/collections", None)
+            .await?;
+
+        Ok(collections
+            .map(|c| c.collections.into_iter().map(|c| c.name).collect())
+            .unwrap_or_default())
+    }
+
+    async fn count(&self, collection: &str) -> Result<usize, VectorClientError> {
+        #[derive(Deserialize)]
+        struct Count {
+            count: usize,
+        }
+
+        let count: Option<Count> = self
+            .request(
+                reqwest::Method::POST,
+                &format!("/collections/{collection}/points/count"),
+                Some(json!({ "exact": true })),
+            )
+            .await?;
+
+        Ok(count.map(|c| c.count).unwrap_or(0))
+    }
+
+    async fn upsert(
+        &self,
+        collection: &str,
+        points: Vec<VectorPoint>,
+    ) -> Result<(), VectorClientError> {
+        let Some(dimensions) = points.first().map(|p| p.embedding.len()) else {
+            return Ok(());
+        };
+        if let Some(point) = points
+            .iter()
+            .find(|p| uuid::Uuid::parse_str(&p.id).is_err())
+        {
+            return Err(VectorClientError(format!(
+                "Qdrant point IDs must be UUIDs, got {}",
+                point.id
+            )));
+        }
+
+        self.ensure_collection(collection, dimensions).await?;
+
+        let points: Vec<Value> = points
+            .into_iter()
+            .map(|p| {
+                json!({
+                    "id": p.id,
+                    "vector": p.embedding,
+                    "payload": { "document": p.document, "metadata": p.metadata },
+                })
+            })
+            .collect();
+
+        let _: Option<Value> = self
+            .request(
+                reqwest::Method::PUT,
+                &format!("/collections/{collection}/points?wait=true"),
+                Some(json!({ "points": points })),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn delete(&self, collection: &str, ids: &[&str]) -> Result<(), VectorClientError> {
+        let deleted: Option<Value> = self
+            .request(
+                reqwest::Method::POST,
+                &format!("/collections/{collection}/points/delete?wait=true"),
+                Some(json!({ "points": ids })),
+            )
+            .await?;
+
+        match deleted {
+            Some(_) => Ok(()),
+            None => Err(VectorClientError(format!(
+                "Collection {collection} does not exist"
+            ))),
+        }
+    }
+
+    async fn get(
+        &self,
+        collection: &str,
+        ids: &[&str],
+    ) -> Result<Vec<VectorPoint>, VectorClientError> {
+        // Other IDs are rejected by Qdrant and can't exist anyway
+        let ids: Vec<&str> = ids
+            .iter()
+            .copied()
+            .filter(|id| uuid::Uuid::parse_str(id).is_ok())
+            .collect();
+        if ids.is_empty() {
+            return Ok(vec![]);
+        }
+
+        let points: Option<Vec<QdrantPoint>> = self
+            .request(
+                reqwest::Method::POST,
+                &format!("/collections/{collection}/points"),
+                Some(json!({ "ids": ids, "with_payload": true })),
+            )
+            .await?;
+
+        Ok(points
+            .unwrap_or_default()
+            .into_iter()
+            .map(VectorPoint::from)
+            .collect())
+    }
+
+    async fn query(
+        &self,
+        collection: &str,
+        embedding: &[f32],
+        limit: usize,
+    ) -> Result<Vec<ScoredPoint>, VectorClientError> {
+        #[derive(Deserialize)]
+        struct QueryResult {
+            points: Vec<QdrantPoint>,
+        }
+
+        let result: Option<QueryResult> = self
+            .request(
+                reqwest::Method::POST,
+                &format!("/collections/{collection}/points/query"),
+                Some(json!({
+                    "query": embedding,
+                    "limit": limit,
+                    "with_payload": true,
+                })),
+            )
+            .await?;
+
+        Ok(result
+            .map(|r| r.points)
+            .unwrap_or_default()
+            .into_iter()
+            .map(|point| {
+                // Cosine similarity, which is already in the right range
+                let score = point.score.unwrap_or(0.0).clamp(0.0, 1.0);
+                ScoredPoint {
+                    point: point.into(),
+                    score,
+                }
+            })
+            .collect())
+    }
+
+    async fn all(&self, collection: &str) -> Result<Vec<VectorPoint>, VectorClientError> {
+        #[derive(Deserialize)]
+        struct ScrollResult {
+            points: Vec<QdrantPoint>,
+            next_page_offset: Option<Value>,
+        }
+
+        let mut points = Vec::new();
+        let mut offset = Value::Null;
+        loop {
+            let page: Option<ScrollResult> = self
+                .request(
+                    reqwest::Method::POST,
+                    &format!("/collections/{collection}/points/scroll"),
+                    Some(json!({
+                        "limit": PAGE_SIZE,
+                        "offset": offset,
+                        "with_payload": true,
+                        "with_vector": true,
+                    })),
+                )
+                .await?;
+
+            let Some(page) = page else {
+                break;
+            };
+            points.extend(page.points.into_iter().map(VectorPoint::from));
+
+            match page.next_page_offset {
+                Some(next) if !next.is_null() => offset = next,
+                _ => break,
+            }
+        }
+
+        Ok(points)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn qdrant_point_splits_payload() {
+        let point: QdrantPoint = serde_json::from_value(json!({
+            "id": "0b5a6e36-3f0c-4c1e-9a55-6a1c1c0e7d2a",
+            "score": 0.8,
+            "payload": {
+                "document": "likes rust",
+                "metadata": { "timestamp": "2026-01-01T00:00:00Z" }
+            }
+        }))
+        .expect("valid point");
+
+        let point = VectorPoint::from(point);
+        assert_eq!(point.id, "0b5a6e36-3f0c-4c1e-9a55-6a1c1c0e7d2a");
+        assert_eq!(point.document, "likes rust");
+        assert!(point.embedding.is_empty());
+        assert_eq!(
+            point.metadata.get("timestamp"),
+            Some(&json!("2026-01-01T00:00:00Z"))
+        );
+    }
+}
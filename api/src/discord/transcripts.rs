@@ -0,0 +1,482 @@
+use std::{path::Path, sync::Arc};
+
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+use diesel_async::{AsyncPgConnection, RunQueryDsl, pooled_connection::deadpool::Pool};
+use eyre::Context as _;
+use regex::Regex;
+use rig::{
+    agent::Agent,
+    client::CompletionClient,
+    completion::{CompletionModel as _, Message as RigMessage, ToolDefinition, Usage},
+    message::AssistantContent,
+    providers::openrouter::{Client, CompletionModel},
+};
+use serde::{Deserialize, Serialize};
+use serenity::all::{ChannelId, GuildId};
+
+use crate::{
+    config::{ServerConfig, TranscriptSink},
+    discord::settings::to_db_id,
+    models::discord::{DiscordTranscript, NewDiscordTranscript},
+    schema::discord_transcripts,
+};
+
+const REDACTED: &str = "[REDACTED]";
+
+/// Common formats of API keys and tokens, redacted even if they aren't ours
+const SECRET_PATTERNS: &[&str] = &[
+    // OpenAI, OpenRouter, Anthropic
+    r"sk-[A-Za-z0-9_-]{20,}",
+    r"gh[pousr]_[A-Za-z0-9]{36,}",
+    r"github_pat_[A-Za-z0-9_]{22,}",
+    r"xox[abprs]-[A-Za-z0-9-]{10,}",
+    r"AKIA[0-9A-Z]{16}",
+    r"(?i)bearer\s+[A-Za-z0-9._~+/-]+=*",
+    // Discord bot tokens
+    r"[MNO][A-Za-z0-9_-]{23,27}\.[A-Za-z0-9_-]{6}\.[A-Za-z0-9_-]{27,40}",
+    // JWTs
+    r"eyJ[A-Za-z0-9_-]+\.eyJ[A-Za-z0-9_-]+\.[A-Za-z0-9_-]+",
+];
+
+/// Everything needed to replay an agent run: the system prompt, the tools, the
+/// history it started from and the turns of the run
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AgentTranscript {
+    pub id: String,
+    pub channel_id: i64,
+    pub guild_id: Option<i64>,
+    pub model: String,
+    pub preamble: String,
+    pub tools: Vec<ToolDefinition>,
+    /// The history sent with the first turn, including the synopsis
+    pub history: Vec<RigMessage>,
+    pub turns: Vec<TranscriptTurn>,
+    pub started_at: NaiveDateTime,
+}
+
+/// One prompt of the agent, which may span multiple completion requests due to
+/// tool calls
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TranscriptTurn {
+    pub prompt: String,
+    /// The prompt followed by the responses, tool calls and tool results
+    pub messages: Vec<RigMessage>,
+    pub output: String,
+    pub usage: Option<Usage>,
+    pub requests: usize,
+    pub error: Option<String>,
+}
+
+impl AgentTranscript {
+    /// The history the given turn was prompted with
+    fn history_before(&self, turn: usize) -> Vec<RigMessage> {
+        self.history
+            .iter()
+            .chain(self.turns.iter().take(turn).flat_map(|t| t.messages.iter()))
+            .cloned()
+            .collect()
+    }
+}
+
+#[derive(Serialize, Debug)]
+pub struct TranscriptSummary {
+    pub id: String,
+    pub channel_id: i64,
+    pub guild_id: Option<i64>,
+    pub model: String,
+    pub turns: usize,
+    pub started_at: NaiveDateTime,
+}
+
+impl From<&AgentTranscript> for TranscriptSummary {
+    fn from(transcript: &AgentTranscript) -> Self {
+        Self {
+            id: transcript.id.clone(),
+            channel_id: transcript.channel_id,
+            guild_id: transcript.guild_id,
+            model: transcript.model.clone(),
+            turns: transcript.turns.len(),
+            started_at: transcript.started_at,
+        }
+    }
+}
+
+/// The first response of each turn of a transcript from another model. Only the
+/// first completion request of a turn is replayed since the tools act on Discord.
+#[derive(Serialize, Debug)]
+pub struct Replay {
+    pub transcript_id: String,
+    pub original_model: String,
+    pub model: String,
+    pub turns: Vec<ReplayTurn>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct ReplayTurn {
+    pub prompt: String,
+    pub original: Vec<AssistantContent>,
+    pub replayed: Vec<AssistantContent>,
+    pub usage: Option<Usage>,
+    pub error: Option<String>,
+}
+
+struct Inner {
+    diesel: Pool<AsyncPgConnection>,
+    sink: Option<TranscriptSink>,
+    openai_api_key: Option<String>,
+    secrets: Vec<String>,
+    patterns: Vec<Regex>,
+}
+
+/// Records the agent runs as structured JSON with the secrets redacted
+#[derive(Clone)]
+pub struct TranscriptRecorder(Arc<Inner>);
+
+impl TranscriptRecorder {
+    pub fn new(config: &ServerConfig, diesel: Pool<AsyncPgConnection>) -> Self {
+        let patterns = SECRET_PATTERNS
+            .iter()
+            .filter_map(|p| {
+                Regex::new(p)
+                    .inspect_err(|e| tracing::error!(?e, "Invalid secret pattern"))
+                    .ok()
+            })
+            .collect();
+
+        Self(Arc::new(Inner {
+            diesel,
+            sink: config.discord_transcripts.clone(),
+            openai_api_key: config.openai_api_key.clone(),
+            secrets: config.secrets(),
+            patterns,
+        }))
+    }
+
+    /// The transcripts of a channel, `None` if transcripts aren't recorded
+    pub fn channel(
+        &self,
+        channel_id: ChannelId,
+        guild_id: Option<GuildId>,
+    ) -> Option<ChannelTranscripts> {
+        self.0.sink.as_ref().map(|_| ChannelTranscripts {
+            recorder: self.clone(),
+            channel_id: to_db_id(channel_id.get()),
+            guild_id: guild_id.map(|g| to_db_id(g.get())),
+        })
+    }
+
+    async fn conn(
+        &self,
+    ) -> Result<diesel_async::pooled_connection::deadpool::Object<AsyncPgConnection>, eyre::Error>
+    {
+        self.0
+            .diesel
+            .get()
+            .await
+            .wrap_err("could not get diesel pool conn")
+    }
+
+    fn redact(&self, value: &mut serde_json::Value) {
+        match value {
+            serde_json::Value::String(s) => *s = redact(s, &self.0.secrets, &self.0.patterns),
+            serde_json::Value::Array(values) => values.iter_mut().for_each(|v| self.redact(v)),
+            serde_json::Value::Object(map) => map.values_mut().for_each(|v| self.redact(v)),
+            _ => {}
+        }
+    }
+
+    /// Save in the background so that the conversation isn't held up
+    fn record(&self, transcript: AgentTranscript) {
+        let recorder = self.clone();
+        tokio::spawn(async move {
+            if let Err(e) = recorder.save(transcript).await {
+                tracing::error!(?e, "Failed to record agent transcript");
+            }
+        });
+    }
+
+    async fn save(&self, transcript: AgentTranscript) -> Result<(), eyre::Error> {
+        let Some(sink) = &self.0.sink else {
+            return Ok(());
+        };
+
+        let mut value =
+            serde_json::to_value(&transcript).wrap_err("failed to serialize agent transcript")?;
+        self.redact(&mut value);
+
+        match sink {
+            TranscriptSink::Table => {
+                let mut conn = self.conn().await?;
+                diesel::insert_into(discord_transcripts::table)
+                    .values(NewDiscordTranscript {
+                        id: transcript.id,
+                        channel_id: transcript.channel_id,
+                        guild_id: transcript.guild_id,
+                        model: transcript.model,
+                        transcript: value,
+                    })
+                    .execute(&mut conn)
+                    .await
+                    .wrap_err("failed to insert agent transcript")?;
+            }
+            TranscriptSink::File { dir } => {
+                tokio::fs::create_dir_all(dir)
+                    .await
+                    .wrap_err("failed to create transcript directory")?;
+                let json = serde_json::to_vec_pretty(&value)
+                    .wrap_err("failed to serialize agent transcript")?;
+                tokio::fs::write(dir.join(format!("{}.json", transcript.id)), json)
+                    .await
+                    .wrap_err("failed to write agent transcript")?;
+            }
+        }
+
+        Ok(())
+    }
+
+    pub async fn get(&self, id: &str) -> Result<Option<AgentTranscript>, eyre::Error> {
+        let value = match &self.0.sink {
+            None => return Ok(None),
+            Some(TranscriptSink::Table) => {
+                let mut conn = self.conn().await?;
+                discord_transcripts::table
+                    .find(id)
+                    .select(DiscordTranscript::as_select())
+                    .first(&mut conn)
+                    .await
+                    .optional()
+                    .wrap_err("failed to load agent transcript")?
+                    .map(|t| t.transcript)
+            }
+            Some(TranscriptSink::File { dir }) => {
+                // The IDs are UUIDs, anything else could escape the directory
+                if uuid::Uuid::parse_str(id).is_err() {
+                    return Ok(None);
+                }
+                read_file(&dir.join(format!("{id}.json"))).await?
+            }
+        };
+
+        value
+            .map(serde_json::from_value)
+            .transpose()
+            .wrap_err("failed to parse agent transcript")
+    }
+
+    /// The most recent transcripts, newest first
+    pub async fn list(
+        &self,
+        channel_id: Option<i64>,
+        limit: usize,
+    ) -> Result<Vec<TranscriptSummary>, eyre::Error> {
+        let transcripts: Vec<AgentTranscript> = match &self.0.sink {
+            None => return Ok(Vec::new()),
+            Some(TranscriptSink::Table) => {
+                let mut conn = self.conn().await?;
+                let mut query = discord_transcripts::table
+                    .select(DiscordTranscript::as_select())
+                    .order(discord_transcripts::created_at.desc())
+                    .limit(i64::try_from(limit).unwrap_or(i64::MAX))
+                    .into_boxed();
+                if let Some(channel_id) = channel_id {
+                    query = query.filter(discord_transcripts::channel_id.eq(channel_id));
+                }
+
+                query
+                    .load::<DiscordTranscript>(&mut conn)
+                    .await
+                    .wrap_err("failed to load agent transcripts")?
+                    .into_iter()
+                    .filter_map(|t| serde_json::from_value(t.transcript).ok())
+                    .collect()
+            }
+            Some(TranscriptSink::File { dir }) => {
+                let mut entries = match tokio::fs::read_dir(dir).await {
+                    Ok(entries) => entries,
+                    Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+                    Err(e) => return Err(e).wrap_err("failed to read transcript directory"),
+                };
+
+                let mut transcripts = Vec::new();
+                while let Some(entry) = entries
+                    .next_entry()
+                    .await
+                    .wrap_err("failed to read transcript directory")?
+                {
+                    let Some(value) = read_file(&entry.path()).await? else {
+                        continue;
+                    };
+                    if let Ok(transcript) = serde_json::from_value::<AgentTranscript>(value) {
+                        transcripts.push(transcript);
+                    }
+                }
+                transcripts
+            }
+        };
+
+        let mut summaries = transcripts
+            .iter()
+            .filter(|t| channel_id.is_none_or(|c| t.channel_id == c))
+            .map(TranscriptSummary::from)
+            .collect::<Vec<_>>();
+        summaries.sort_by_key(|s| std::cmp::Reverse(s.started_at));
+        summaries.truncate(limit);
+
+        Ok(summaries)
+    }
+
+    /// Send the first completion request of every turn of the transcript to
+    /// another model, with the same system prompt, tools and history
+    pub async fn replay(
+        &self,
+        transcript: &AgentTranscript,
+        model: &str,
+    ) -> Result<Replay, eyre::Error> {
+        let api_key = self
+            .0
+            .openai_api_key
+            .as_deref()
+            .ok_or_else(|| eyre::eyre!("OPENAI_API_KEY is not set"))?;
+        let client = Client::new(api_key).wrap_err("failed to create OpenRouter client")?;
+        let completion_model: CompletionModel = client.completion_model(model);
+
+        let mut turns = Vec::with_capacity(transcript.turns.len());
+        for (i, turn) in transcript.turns.iter().enumerate() {
+            let original = turn
+                .messages
+                .iter()
+                .find_map(|m| match m {
+                    RigMessage::Assistant { content, .. } => {
+                        Some(content.iter().cloned().collect())
+                    }
+                    _ => None,
+                })
+                .unwrap_or_default();
+
+            let result = completion_model
+                .completion_request(RigMessage::user(turn.prompt.clone()))
+                .preamble(transcript.preamble.clone())
+                .messages(transcript.history_before(i))
+                .tools(transcript.tools.clone())
+                .send()
+                .await;
+
+            turns.push(match result {
+                Ok(response) => ReplayTurn {
+                    prompt: turn.prompt.clone(),
+                    original,
+                    replayed: response.choice.into_iter().collect(),
+                    usage: Some(response.usage),
+                    error: None,
+                },
+                Err(e) => ReplayTurn {
+                    prompt: turn.prompt.clone(),
+                    original,
+                    replayed: Vec::new(),
+                    usage: None,
+                    error: Some(e.to_string()),
+                },
+            });
+        }
+
+        Ok(Replay {
+            transcript_id: transcript.id.clone(),
+            original_model: transcript.model.clone(),
+            model: model.to_string(),
+            turns,
+        })
+    }
+}
+
+async fn read_file(path: &Path) -> Result<Option<serde_json::Value>, eyre::Error> {
+    match tokio::fs::read(path).await {
+        Ok(bytes) => Ok(serde_json::from_slice(&bytes).ok()),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e).wrap_err("failed to read agent transcript"),
+    }
+}
+
+fn redact(text: &str, secrets: &[String], patterns: &[Regex]) -> String {
+    let mut text = secrets.iter().fold(text.to_string(), |text, secret| {
+        text.replace(secret, REDACTED)
+    });
+    for pattern in patterns {
+        if pattern.is_match(&text) {
+            text = pattern.replace_all(&text, REDACTED).into_owned();
+        }
+    }
+    text
+}
+
+/// The transcripts of a single channel
+#[derive(Clone)]
+pub struct ChannelTranscripts {
+    recorder: TranscriptRecorder,
+    channel_id: i64,
+    guild_id: Option<i64>,
+}
+
+impl ChannelTranscripts {
+    /// Start the transcript of a run of the agent
+    pub async fn start(
+        &self,
+        agent: &Agent<CompletionModel>,
+        model: &str,
+        history: Vec<RigMessage>,
+    ) -> AgentTranscript {
+        let tools = agent
+            .tool_server_handle
+            .get_tool_defs(None)
+            .await
+            .inspect_err(|e| tracing::error!(?e, "Failed to get tool definitions"))
+            .unwrap_or_default();
+
+        AgentTranscript {
+            id: uuid::Uuid::new_v4().to_string(),
+            channel_id: self.channel_id,
+            guild_id: self.guild_id,
+            model: model.to_string(),
+            preamble: agent.preamble.clone().unwrap_or_default(),
+            tools,
+            history,
+            turns: Vec::new(),
+            started_at: chrono::Utc::now().naive_utc(),
+        }
+    }
+
+    pub fn record(&self, transcript: AgentTranscript) {
+        if transcript.turns.is_empty() {
+            return;
+        }
+
+        tracing::debug!(id = transcript.id, "Recording agent transcript");
+        self.recorder.record(transcript);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn redact_removes_configured_secrets_and_known_token_formats() {
+        let patterns = SECRET_PATTERNS
+            .iter()
+            .filter_map(|p| Regex::new(p).ok())
+            .collect::<Vec<_>>();
+        let secrets = vec!["hunter2-app-id".to_string()];
+
+        let text = "key sk-or-v1-0123456789abcdefghij, id hunter2-app-id, \
+            header Authorization: Bearer abc.def-ghi";
+
+        assert_eq!(
+            redact(text, &secrets, &patterns),
+            "key [REDACTED], id [REDACTED], header Authorization: [REDACTED]"
+        );
+        assert_eq!(
+            redact("nothing to see", &secrets, &patterns),
+            "nothing to see"
+        );
+    }
+}
@@ -0,0 +1,120 @@
+use std::sync::Arc;
+
+use diesel::prelude::*;
+use diesel_async::{AsyncPgConnection, RunQueryDsl, pooled_connection::deadpool::Pool};
+use eyre::Context as _;
+use serenity::all::ChannelId;
+
+use crate::{
+    config::ServerConfig, discord::settings::to_db_id, models::discord::NewDiscordTokenUsage,
+    schema::discord_token_usage,
+};
+
+struct Inner {
+    diesel: Pool<AsyncPgConnection>,
+    daily_budget: Option<u64>,
+}
+
+/// Persists the token usage of the agent sessions per channel per day and
+/// enforces the daily budget.
+#[derive(Clone)]
+pub struct UsageTracker(Arc<Inner>);
+
+impl UsageTracker {
+    pub fn new(config: &ServerConfig, diesel: Pool<AsyncPgConnection>) -> Self {
+        Self(Arc::new(Inner {
+            diesel,
+            daily_budget: config.discord_daily_token_budget,
+        }))
+    }
+
+    /// Add the usage of a completion run to today's counters
+    pub async fn record(
+        &self,
+        channel_id: ChannelId,
+        model: &str,
+        usage: &rig::completion::Usage,
+        requests: usize,
+    ) -> Result<(), eyre::Error> {
+        let mut conn = self
+            .0
+            .diesel
+            .get()
+            .await
+            .wrap_err("could not get diesel pool conn")?;
+
+        let new_usage = NewDiscordTokenUsage {
+            channel_id: to_db_id(channel_id.get()),
+            day: chrono::Utc::now().date_naive(),
+            model: model.to_string(),
+            input_tokens: i64::try_from(usage.input_tokens).unwrap_or(i64::MAX),
+            output_tokens: i64::try_from(usage.output_tokens).unwrap_or(i64::MAX),
+            cached_input_tokens: i64::try_from(usage.cached_input_tokens).unwrap_or(i64::MAX),
+            requests: i32::try_from(requests).unwrap_or(i32::MAX),
+        };
+
+        diesel::insert_into(discord_token_usage::table)
+            .values(&new_usage)
+            .on_conflict((
+                discord_token_usage::channel_id,
+                discord_token_usage::day,
+                discord_token_usage::model,
+            ))
+            .do_update()
+            .set((
+                discord_token_usage::input_tokens
+                    .eq(discord_token_usage::input_tokens + new_usage.input_tokens),
+                discord_token_usage::output_tokens
+                    .eq(discord_token_usage::output_tokens + new_usage.output_tokens),
+                discord_token_usage::cached_input_tokens
+                    .eq(discord_token_usage::cached_input_tokens + new_usage.cached_input_tokens),
+                discord_token_usage::requests
+                    .eq(discord_token_usage::requests + new_usage.requests),
+                discord_token_usage::updated_at.eq(diesel::dsl::now),
+            ))
+            .execute(&mut conn)
+            .await
+            .wrap_err("failed to record Discord token usage")?;
+
+        Ok(())
+    }
+
+    /// Total input + output tokens used in a channel today across all models
+    pub async fn used_today(&self, channel_id: ChannelId) -> Result<u64, eyre::Error> {
+        let mut conn = self
+            .0
+            .diesel
+            .get()
+            .await
+            .wrap_err("could not get diesel pool conn")?;
+
+        let used: Option<i64> = discord_token_usage::table
+            .filter(discord_token_usage::channel_id.eq(to_db_id(channel_id.get())))
+            .filter(discord_token_usage::day.eq(chrono::Utc::now().date_naive()))
+            .select(diesel::dsl::sql::<
+                diesel::sql_types::Nullable<diesel::sql_types::BigInt>,
+            >("SUM(input_tokens + output_tokens)::BIGINT"))
+            .first(&mut conn)
+            .await
+            .wrap_err("failed to query Discord token usage")?;
+
+        Ok(used.and_then(|u| u64::try_from(u).ok()).unwrap_or(0))
+    }
+
+    /// Whether the channel has used up its daily budget. Fails open if the
+    /// usage could not be queried so that a database hiccup doesn't silence
+    /// the bot.
+    pub async fn is_over_budget(&self, channel_id: ChannelId) -> bool {
+        let Some(budget) = self.0.daily_budget else {
+            return false;
+        };
+
+        match self.used_today(channel_id).await {
+            Ok(used) => used >= budget,
+            Err(e) => {
+                tracing::error!(?e, "Failed to check the daily token budget");
+                false
+            }
+        }
+    }
+}
@@ -0,0 +1,416 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use async_trait::async_trait;
+use eyre::Context as _;
+use futures::channel::mpsc::UnboundedSender;
+use rig::completion::Message as RigMessage;
+use serenity::all::{
+    ChannelId, Context, CreateMessage, CreateThread, GuildId, Http, Message, UserId,
+};
+use songbird::{CoreEvent, Event, EventContext, EventHandler as VoiceEventHandler};
+
+use crate::{
+    config::TranscriptionConfig, discord::commands::SETTINGS_COMMAND_PREFIX, outbound::HttpClient,
+};
+
+/// Audio is decoded as mono at this rate, which is what Whisper resamples to anyway
+pub const DECODE_SAMPLE_RATE: u32 = 16_000;
+
+/// Voice ticks are 20ms apart
+const TICKS_PER_SECOND: usize = 50;
+
+/// An utterance ends after this many ticks of silence from the speaker
+const END_OF_UTTERANCE_TICKS: usize = TICKS_PER_SECOND;
+
+/// Shorter utterances are most likely noise and not worth transcribing
+const MIN_UTTERANCE_SAMPLES: usize = DECODE_SAMPLE_RATE as usize / 2;
+
+/// Long monologues are cut so that the transcripts keep flowing
+const MAX_UTTERANCE_SAMPLES: usize = DECODE_SAMPLE_RATE as usize * 30;
+
+/// A transcribed utterance to be fed to the agent of a text channel
+#[derive(Debug)]
+pub struct Transcript {
+    pub channel_id: ChannelId,
+    pub message: RigMessage,
+}
+
+/// Transcribes the voice channels the bot was asked to join using a
+/// Whisper-compatible endpoint
+#[derive(Clone)]
+pub struct VoiceTranscriber {
+    config: TranscriptionConfig,
+    client: HttpClient,
+}
+
+impl VoiceTranscriber {
+    pub fn new(config: TranscriptionConfig, client: HttpClient) -> Self {
+        Self { config, client }
+    }
+
+    async fn transcribe(&self, samples: &[i16]) -> Result<String, eyre::Error> {
+        #[derive(serde::Deserialize)]
+        struct TranscriptionResponse {
+            text: String,
+        }
+
+        let file = reqwest::multipart::Part::bytes(encode_wav(samples, DECODE_SAMPLE_RATE))
+            .file_name("utterance.wav")
+            .mime_str("audio/wav")?;
+
+        let form = reqwest::multipart::Form::new()
+            .text("model", self.config.model.clone())
+            .text("response_format", "json")
+            .part("file", file);
+
+        let mut request = self.client.post(&self.config.url).multipart(form);
+        if let Some(api_key) = &self.config.api_key {
+            request = request.bearer_auth(api_key);
+        }
+
+        let response: TranscriptionResponse = request
+            .send()
+            .await
+            .wrap_err("failed to send transcription request")?
+            .error_for_status()
+            .wrap_err("transcription request failed")?
+            .json()
+            .await
+            .wrap_err("failed to parse transcription response")?;
+
+        Ok(response.text.trim().to_string())
+    }
+}
+
+/// Handle `!bot voice join|leave`. Returns whether the message was consumed.
+pub async fn handle_voice_command(
+    ctx: &Context,
+    msg: &Message,
+    transcriber: Option<&VoiceTranscriber>,
+    transcripts: &UnboundedSender<Transcript>,
+) -> bool {
+    let Some(args) = msg
+        .content
+        .trim()
+        .strip_prefix(SETTINGS_COMMAND_PREFIX)
+        .and_then(|args| args.trim_start().strip_prefix("voice"))
+        .filter(|args| args.is_empty() || args.starts_with(char::is_whitespace))
+    else {
+        return false;
+    };
+
+    let reply = match (transcriber, args.trim()) {
+        (None, _) => "voice transcription is not configured".to_string(),
+        (Some(transcriber), "join") => join(ctx, msg, transcriber, transcripts)
+            .await
+            .unwrap_or_else(|e| {
+                tracing::error!(?e, "Failed to join voice channel");
+                format!("❗️ Failed to join voice channel: {e}")
+            }),
+        (Some(_), "leave") => leave(ctx, msg.guild_id).await.unwrap_or_else(|e| {
+            tracing::error!(?e, "Failed to leave voice channel");
+            format!("❗️ Failed to leave voice channel: {e}")
+        }),
+        (Some(_), _) => "usage: `!bot voice <join|leave>`".to_string(),
+    };
+
+    let _ = msg
+        .reply(&ctx.http, reply)
+        .await
+        .inspect_err(|e| tracing::error!(?e, "Failed to reply to voice command"));
+
+    true
+}
+
+async fn join(
+    ctx: &Context,
+    msg: &Message,
+    transcriber: &VoiceTranscriber,
+    transcripts: &UnboundedSender<Transcript>,
+) -> Result<String, eyre::Error> {
+    let Some(guild_id) = msg.guild_id else {
+        return Ok("voice is only available in a server".to_string());
+    };
+
+    // Voice states are cached thanks to the GUILD_VOICE_STATES intent
+    let voice_channel_id = ctx
+        .cache
+        .guild(guild_id)
+        .and_then(|g| g.voice_states.get(&msg.author.id)?.channel_id);
+    let Some(voice_channel_id) = voice_channel_id else {
+        return Ok("join a voice channel first".to_string());
+    };
+
+    let manager = songbird::get(ctx)
+        .await
+        .ok_or_else(|| eyre::eyre!("songbird is not registered"))?;
+
+    let thread = msg
+        .channel_id
+        .create_thread_from_message(
+            &ctx.http,
+            msg.id,
+            CreateThread::new(format!(
+                "Voice transcript {}",
+                chrono::Utc::now().date_naive()
+            )),
+        )
+        .await
+        .wrap_err("failed to create transcript thread")?;
+
+    let call = manager
+        .join(guild_id, voice_channel_id)
+        .await
+        .wrap_err("failed to join voice channel")?;
+
+    let receiver = Receiver(Arc::new(ReceiverInner {
+        transcriber: transcriber.clone(),
+        http: ctx.http.clone(),
+        text_channel_id: msg.channel_id,
+        thread_id: thread.id,
+        transcripts: transcripts.clone(),
+        speakers: Mutex::new(HashMap::new()),
+        utterances: Mutex::new(HashMap::new()),
+        names: Mutex::new(HashMap::new()),
+    }));
+
+    let mut call = call.lock().await;
+    // Handlers of a previous session in the same guild are replaced
+    call.remove_all_global_events();
+    call.add_global_event(CoreEvent::SpeakingStateUpdate.into(), receiver.clone());
+    call.add_global_event(CoreEvent::VoiceTick.into(), receiver);
+
+    tracing::info!(
+        guild_id = guild_id.get(),
+        voice_channel_id = voice_channel_id.get(),
+        "Joined voice channel"
+    );
+
+    Ok(format!(
+        "joined <#{voice_channel_id}>, transcripts go to <#{}>",
+        thread.id
+    ))
+}
+
+async fn leave(ctx: &Context, guild_id: Option<GuildId>) -> Result<String, eyre::Error> {
+    let Some(guild_id) = guild_id else {
+        return Ok("voice is only available in a server".to_string());
+    };
+
+    let manager = songbird::get(ctx)
+        .await
+        .ok_or_else(|| eyre::eyre!("songbird is not registered"))?;
+
+    if manager.get(guild_id).is_none() {
+        return Ok("not in a voice channel".to_string());
+    }
+
+    manager
+        .remove(guild_id)
+        .await
+        .wrap_err("failed to leave voice channel")?;
+
+    Ok("left the voice channel".to_string())
+}
+
+#[derive(Default)]
+struct Utterance {
+    samples: Vec<i16>,
+    silent_ticks: usize,
+}
+
+struct ReceiverInner {
+    transcriber: VoiceTranscriber,
+    http: Arc<Http>,
+    text_channel_id: ChannelId,
+    thread_id: ChannelId,
+    transcripts: UnboundedSender<Transcript>,
+    // SSRC to user, as announced by the speaking state updates
+    speakers: Mutex<HashMap<u32, UserId>>,
+    utterances: Mutex<HashMap<u32, Utterance>>,
+    names: Mutex<HashMap<UserId, String>>,
+}
+
+/// Buffers the decoded audio of each speaker and transcribes it once they
+/// pause
+#[derive(Clone)]
+struct Receiver(Arc<ReceiverInner>);
+
+#[async_trait]
+impl VoiceEventHandler for Receiver {
+    async fn act(&self, ctx: &EventContext<'_>) -> Option<Event> {
+        match ctx {
+            EventContext::SpeakingStateUpdate(speaking) => {
+                if let (Some(user_id), Ok(mut speakers)) =
+                    (speaking.user_id, self.0.speakers.lock())
+                {
+                    speakers.insert(speaking.ssrc, UserId::new(user_id.0));
+                }
+            }
+            EventContext::VoiceTick(tick) => {
+                let finished = match self.0.utterances.lock() {
+                    Ok(mut utterances) => {
+                        for (ssrc, data) in &tick.speaking {
+                            if let Some(decoded) = &data.decoded_voice {
+                                let utterance = utterances.entry(*ssrc).or_default();
+                                utterance.samples.extend_from_slice(decoded);
+                                utterance.silent_ticks = 0;
+                            }
+                        }
+
+                        for (ssrc, utterance) in utterances.iter_mut() {
+                            if !tick.speaking.contains_key(ssrc) {
+                                utterance.silent_ticks += 1;
+                            }
+                        }
+
+                        let finished: Vec<u32> = utterances
+                            .iter()
+                            .filter(|(_, u)| {
+                                (u.silent_ticks >= END_OF_UTTERANCE_TICKS && !u.samples.is_empty())
+                                    || u.samples.len() >= MAX_UTTERANCE_SAMPLES
+                            })
+                            .map(|(ssrc, _)| *ssrc)
+                            .collect();
+
+                        finished
+                            .into_iter()
+                            .filter_map(|ssrc| Some((ssrc, utterances.remove(&ssrc)?.samples)))
+                            .filter(|(_, samples)| samples.len() >= MIN_UTTERANCE_SAMPLES)
+                            .collect::<Vec<_>>()
+                    }
+                    Err(_) => Vec::new(),
+                };
+
+                for (ssrc, samples) in finished {
+                    let receiver = self.clone();
+                    tokio::spawn(async move {
+                        if let Err(e) = receiver.handle_utterance(ssrc, samples).await {
+                            tracing::error!(?e, "Failed to transcribe voice utterance");
+                        }
+                    });
+                }
+            }
+            _ => {}
+        }
+
+        None
+    }
+}
+
+impl Receiver {
+    async fn handle_utterance(&self, ssrc: u32, samples: Vec<i16>) -> Result<(), eyre::Error> {
+        let text = self.0.transcriber.transcribe(&samples).await?;
+        if text.is_empty() {
+            return Ok(());
+        }
+
+        let user_id = self
+            .0
+            .speakers
+            .lock()
+            .ok()
+            .and_then(|speakers| speakers.get(&ssrc).copied());
+        let name = match user_id {
+            Some(user_id) => self.user_name(user_id).await,
+            None => "Unknown speaker".to_string(),
+        };
+
+        self.0
+            .thread_id
+            .send_message(
+                &self.0.http,
+                CreateMessage::new().content(format!("**{name}**: {text}")),
+            )
+            .await
+            .wrap_err("failed to post transcript")?;
+
+        let author = match user_id {
+            Some(user_id) => format!("{name} (@{user_id})"),
+            None => name,
+        };
+
+        self.0
+            .transcripts
+            .unbounded_send(Transcript {
+                channel_id: self.0.text_channel_id,
+                message: RigMessage::user(format!(
+                    "[Voice transcript] [{}] {author}: {text}",
+                    chrono::Utc::now().to_rfc3339()
+                )),
+            })
+            .wrap_err("failed to forward transcript")?;
+
+        Ok(())
+    }
+
+    async fn user_name(&self, user_id: UserId) -> String {
+        if let Some(name) = self
+            .0
+            .names
+            .lock()
+            .ok()
+            .and_then(|names| names.get(&user_id).cloned())
+        {
+            return name;
+        }
+
+        let name = match user_id.to_user(&self.0.http).await {
+            Ok(user) => user.global_name.unwrap_or(user.name),
+            Err(e) => {
+                tracing::error!(?e, "Failed to fetch voice speaker");
+                return user_id.to_string();
+            }
+        };
+
+        if let Ok(mut names) = self.0.names.lock() {
+            names.insert(user_id, name.clone());
+        }
+
+        name
+    }
+}
+
+/// Encode mono 16-bit PCM as a WAV file
+fn encode_wav(samples: &[i16], sample_rate: u32) -> Vec<u8> {
+    let data_len = u32::try_from(samples.len() * 2).unwrap_or(u32::MAX);
+
+    let mut wav = Vec::with_capacity(44 + samples.len() * 2);
+    wav.extend_from_slice(b"RIFF");
+    wav.extend_from_slice(&(36 + data_len).to_le_bytes());
+    wav.extend_from_slice(b"WAVEfmt ");
+    wav.extend_from_slice(&16u32.to_le_bytes()); // fmt chunk size
+    wav.extend_from_slice(&1u16.to_le_bytes()); // PCM
+    wav.extend_from_slice(&1u16.to_le_bytes()); // mono
+    wav.extend_from_slice(&sample_rate.to_le_bytes());
+    wav.extend_from_slice(&(sample_rate * 2).to_le_bytes()); // byte rate
+    wav.extend_from_slice(&2u16.to_le_bytes()); // block align
+    wav.extend_from_slice(&16u16.to_le_bytes()); // bits per sample
+    wav.extend_from_slice(b"data");
+    wav.extend_from_slice(&data_len.to_le_bytes());
+    for sample in samples {
+        wav.extend_from_slice(&sample.to_le_bytes());
+    }
+
+    wav
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encode_wav_writes_header_and_samples() {
+        let wav = encode_wav(&[1, -1], 16_000);
+
+        assert_eq!(wav.len(), 48);
+        assert_eq!(&wav[0..4], b"RIFF");
+        assert_eq!(&wav[4..8], &40u32.to_le_bytes());
+        assert_eq!(&wav[24..28], &16_000u32.to_le_bytes());
+        assert_eq!(&wav[40..44], &4u32.to_le_bytes());
+        assert_eq!(&wav[44..], &[1, 0, 0xff, 0xff]);
+    }
+}
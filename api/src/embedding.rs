@@ -0,0 +1,74 @@
+//! The text embedding model shared by the recommender and the Discord bot's
+//! memories. It's only loaded on first use since it takes a while and a good
+//! amount of memory.
+
+use std::sync::{Arc, Mutex, OnceLock};
+
+use fastembed::{EmbeddingModel, InitOptions, TextEmbedding};
+
+use crate::config::FASTEMBED_CACHE_DIR;
+
+/// Error type for embedding operations
+#[derive(Clone, Debug, thiserror::Error)]
+#[error("Embedding error: {0}")]
+pub struct EmbeddingError(String);
+
+type Model = Result<Mutex<TextEmbedding>, EmbeddingError>;
+
+/// Handle to the shared embedding model, cheap to clone
+#[derive(Clone, Default)]
+pub struct Embedder(Arc<OnceLock<Model>>);
+
+impl Embedder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn model(&self) -> Result<&Mutex<TextEmbedding>, EmbeddingError> {
+        self.0
+            .get_or_init(|| {
+                tracing::info!("Initializing shared FastEmbed embedding model");
+                let cache_dir = FASTEMBED_CACHE_DIR
+                    .parse()
+                    .map_err(|err| EmbeddingError(format!("invalid fastembed cache dir: {err}")))?;
+                let model = TextEmbedding::try_new(
+                    InitOptions::new(EmbeddingModel::AllMiniLML12V2).with_cache_dir(cache_dir),
+                )
+                .map_err(|err| {
+                    EmbeddingError(format!("failed to initialize embedding model: {err}"))
+                })?;
+
+                Ok(Mutex::new(model))
+            })
+            .as_ref()
+            .map_err(Clone::clone)
+    }
+
+    /// Generate embeddings for a list of texts. The model runs on the blocking
+    /// thread pool, loading it first if needed.
+    pub async fn embed(&self, texts: Vec<String>) -> Result<Vec<Vec<f32>>, EmbeddingError> {
+        let embedder = self.clone();
+        tokio::task::spawn_blocking(move || {
+            let mut model = embedder
+                .model()?
+                .lock()
+                .map_err(|_| EmbeddingError("embedding model lock poisoned".to_string()))?;
+            model
+                .embed(texts, None)
+                .map_err(|e| EmbeddingError(format!("failed to generate embeddings: {e}")))
+        })
+        .await
+        .map_err(|e| EmbeddingError(format!("embedding task failed: {e}")))?
+    }
+}
+
+pub fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
+    let dot: f32 = a.iter().zip(b).map(|(x, y)| x * y).sum();
+    let norm_a = a.iter().map(|x| x * x).sum::<f32>().sqrt();
+    let norm_b = b.iter().map(|x| x * x).sum::<f32>().sqrt();
+
+    if norm_a == 0.0 || norm_b == 0.0 {
+        return 0.0;
+    }
+    dot / (norm_a * norm_b)
+}
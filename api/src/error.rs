@@ -2,10 +2,11 @@ use std::collections::HashMap;
 
 use axum::{Json, http::StatusCode, response::IntoResponse};
 use eyre::eyre;
-use serde::Serialize;
 use serde_json::Value;
 use tracing::debug;
 
+pub use api_models::ErrorResponse;
+
 /// The main error type for the application. Every handler should return this
 /// type as the error type. This error type when created will be attached
 /// debugging info and get logged automatically. If you intent to not use this
@@ -251,47 +252,6 @@ where
     }
 }
 
-#[derive(Serialize)]
-pub struct ErrorResponse {
-    #[serde(skip_serializing_if = "Option::is_none")]
-    error: Option<String>,
-
-    msg: String,
-
-    #[serde(skip_serializing_if = "Option::is_none")]
-    reason: Option<serde_json::Value>,
-
-    #[serde(skip_serializing_if = "Option::is_none")]
-    context: Option<HashMap<String, serde_json::Value>>,
-
-    #[serde(skip_serializing_if = "Option::is_none")]
-    debug_info: Option<HashMap<&'static str, Value>>,
-}
-
-impl ErrorResponse {
-    pub fn new(msg: &str) -> Self {
-        Self {
-            error: None,
-            msg: msg.into(),
-            reason: None,
-            context: None,
-            debug_info: None,
-        }
-    }
-}
-
-impl std::fmt::Display for ErrorResponse {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(
-            f,
-            "Code {}: {} ({})",
-            self.error.as_deref().unwrap_or("UNKNOWN"),
-            self.msg,
-            self.reason.as_ref().unwrap_or(&serde_json::Value::Null)
-        )
-    }
-}
-
 impl IntoResponse for AppError {
     fn into_response(self) -> axum::response::Response {
         match self.error {
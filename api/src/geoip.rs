@@ -0,0 +1,173 @@
+use std::{
+    net::IpAddr,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime},
+};
+
+use arc_swap::ArcSwapOption;
+use maxminddb::{Reader, geoip2};
+
+pub use api_models::GeoInfo;
+
+use crate::{App, config::GeoIpConfig};
+
+/// How often the database files are checked for modifications
+const RELOAD_CHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+/// A single mmdb file that is reloaded in place whenever its modification time
+/// changes, so the databases can be updated (e.g. by `geoipupdate`) without
+/// restarting the server.
+struct GeoIpDatabase {
+    path: Option<PathBuf>,
+    reader: ArcSwapOption<Reader<Vec<u8>>>,
+    modified: Mutex<Option<SystemTime>>,
+}
+
+impl GeoIpDatabase {
+    fn new(path: Option<String>) -> Self {
+        let db = Self {
+            path: path.map(PathBuf::from),
+            reader: ArcSwapOption::empty(),
+            modified: Mutex::new(None),
+        };
+        db.reload_if_changed();
+        db
+    }
+
+    fn reload_if_changed(&self) {
+        let Some(path) = &self.path else {
+            return;
+        };
+
+        let modified = match std::fs::metadata(path).and_then(|m| m.modified()) {
+            Ok(modified) => modified,
+            Err(error) => {
+                tracing::warn!(?error, path = %path.display(), "Could not stat GeoIP database");
+                return;
+            }
+        };
+
+        let Ok(mut last_modified) = self.modified.lock() else {
+            tracing::error!("GeoIP database modification lock poisoned");
+            return;
+        };
+
+        if *last_modified == Some(modified) {
+            return;
+        }
+
+        match Reader::open_readfile(path) {
+            Ok(reader) => {
+                tracing::info!(
+                    path = %path.display(),
+                    database_type = reader.metadata.database_type,
+                    "Loaded GeoIP database"
+                );
+                self.reader.store(Some(Arc::new(reader)));
+                *last_modified = Some(modified);
+            }
+            Err(error) => {
+                // Keep serving lookups from the previously loaded database
+                tracing::error!(?error, path = %path.display(), "Failed to load GeoIP database");
+            }
+        }
+    }
+}
+
+/// Optional GeoIP lookup service. Every lookup is a no-op returning `None`
+/// when no database is configured.
+pub struct GeoIp {
+    country: GeoIpDatabase,
+    asn: GeoIpDatabase,
+}
+
+impl GeoIp {
+    pub fn new(config: Option<&GeoIpConfig>) -> Self {
+        Self {
+            country: GeoIpDatabase::new(config.and_then(|c| c.country_database_path.clone())),
+            asn: GeoIpDatabase::new(config.and_then(|c| c.asn_database_path.clone())),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.country.path.is_some() || self.asn.path.is_some()
+    }
+
+    pub fn lookup(&self, ip: IpAddr) -> Option<GeoInfo> {
+        let mut info = GeoInfo::default();
+
+        if let Some(reader) = self.country.reader.load().as_ref() {
+            // Both the Country and City databases carry the country record
+            match reader.lookup::<geoip2::Country>(ip) {
+                Ok(country) => {
+                    info.country = country.country.and_then(|c| c.iso_code).map(str::to_string);
+                }
+                Err(maxminddb::MaxMindDBError::AddressNotFoundError(_)) => {}
+                Err(error) => tracing::warn!(?error, %ip, "GeoIP country lookup failed"),
+            }
+        }
+
+        if let Some(reader) = self.asn.reader.load().as_ref() {
+            match reader.lookup::<geoip2::Asn>(ip) {
+                Ok(asn) => {
+                    info.asn = asn.autonomous_system_number;
+                    info.asn_org = asn.autonomous_system_organization.map(str::to_string);
+                }
+                Err(maxminddb::MaxMindDBError::AddressNotFoundError(_)) => {}
+                Err(error) => tracing::warn!(?error, %ip, "GeoIP ASN lookup failed"),
+            }
+        }
+
+        (!info.is_empty()).then_some(info)
+    }
+
+    fn reload_if_changed(&self) {
+        self.country.reload_if_changed();
+        self.asn.reload_if_changed();
+    }
+}
+
+/// Periodically checks the configured mmdb files and hot-reloads them when
+/// they change on disk.
+pub fn start_reload_watcher(ctx: App) {
+    if !ctx.geoip.is_enabled() {
+        return;
+    }
+
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(RELOAD_CHECK_INTERVAL);
+        // The first tick completes immediately and the databases were just
+        // loaded on startup
+        interval.tick().await;
+        loop {
+            interval.tick().await;
+            let ctx = ctx.clone();
+            let _ = tokio::task::spawn_blocking(move || ctx.geoip.reload_if_changed())
+                .await
+                .inspect_err(|error| tracing::error!(?error, "GeoIP reload task failed"));
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lookup_without_database_returns_none() {
+        let geoip = GeoIp::new(None);
+        assert!(!geoip.is_enabled());
+        assert_eq!(geoip.lookup("1.1.1.1".parse().unwrap()), None);
+    }
+
+    #[test]
+    fn missing_database_file_is_not_fatal() {
+        let geoip = GeoIp::new(Some(&GeoIpConfig {
+            country_database_path: Some("/nonexistent/GeoLite2-Country.mmdb".to_string()),
+            asn_database_path: None,
+        }));
+        assert!(geoip.is_enabled());
+        assert_eq!(geoip.lookup("1.1.1.1".parse().unwrap()), None);
+    }
+}
@@ -3,17 +3,13 @@ pub mod routes;
 use ipnetwork::IpNetwork;
 use tokio::sync::OnceCell;
 
+use crate::outbound::HttpClient;
+
 static GITHUB_PREFIXES: OnceCell<Vec<IpNetwork>> = OnceCell::const_new();
 
-async fn fetch_github_meta() -> Vec<IpNetwork> {
+async fn fetch_github_meta(http: &HttpClient) -> Vec<IpNetwork> {
     let url = "https://api.github.com/meta";
-    let client = reqwest::Client::new();
-    let resp = match client
-        .get(url)
-        .header(reqwest::header::USER_AGENT, "wrx.sh-api/1.0")
-        .send()
-        .await
-    {
+    let resp = match http.get(url).send().await {
         Ok(r) => r,
         Err(e) => {
             tracing::warn!(error = %e, "Failed to fetch GitHub meta");
@@ -56,23 +52,27 @@ async fn fetch_github_meta() -> Vec<IpNetwork> {
     out
 }
 
-pub async fn github_prefixes() -> &'static Vec<IpNetwork> {
+pub async fn github_prefixes(http: &HttpClient) -> &'static Vec<IpNetwork> {
     GITHUB_PREFIXES
-        .get_or_init(|| async { fetch_github_meta().await })
+        .get_or_init(|| async { fetch_github_meta(http).await })
         .await
 }
 
-pub async fn is_github_ip(ip: &std::net::IpAddr) -> bool {
-    github_prefixes().await.iter().any(|n| n.contains(*ip))
+pub async fn is_github_ip(http: &HttpClient, ip: &std::net::IpAddr) -> bool {
+    github_prefixes(http).await.iter().any(|n| n.contains(*ip))
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    fn http() -> HttpClient {
+        HttpClient::new(&Default::default()).expect("HTTP client should be correctly constructed")
+    }
+
     #[tokio::test]
     async fn github_meta_fetches_and_parses() {
-        let prefixes = github_prefixes().await;
+        let prefixes = github_prefixes(&http()).await;
         assert!(!prefixes.is_empty());
         // Sanity: each prefix has a sensible mask length
         assert!(prefixes.iter().all(|p| match p {
@@ -83,9 +83,10 @@ mod tests {
 
     #[tokio::test]
     async fn is_github_ip_reports_true_for_prefix_base() {
-        let prefixes = github_prefixes().await;
+        let http = http();
+        let prefixes = github_prefixes(&http).await;
         if let Some(p) = prefixes.first() {
-            assert!(is_github_ip(&p.ip()).await);
+            assert!(is_github_ip(&http, &p.ip()).await);
         }
     }
 }
@@ -46,7 +46,7 @@ async fn handle_fetch_git_hub_profile_views(
         .get(USER_AGENT)
         .map(|ua| ua.to_str().unwrap_or_default().contains("github-camo"))
         .unwrap_or(false)
-        && is_github_ip(&ip).await;
+        && is_github_ip(&ctx.http, &ip).await;
 
     // NOTE: that currently the badge is behind GitHub's proxy since it's hosted on GitHub markdown
     // renderer, so the IP address will always be GitHub's IP address. It means we're assuming that
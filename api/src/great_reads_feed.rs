@@ -1,144 +1,142 @@
-use crate::App;
-use axum::Json;
+use api_models::HighlightItem;
+
+use crate::{App, clients::RaindropClient};
 use axum::{extract::State, http::StatusCode, response::IntoResponse};
-use serde::{Deserialize, Serialize};
 use std::time::Duration;
+use utoipa::OpenApi;
 
 // Cache duration for highlights and RSS feed (1 minute)
 const CACHE_DURATION: Duration = Duration::from_secs(60);
 
-#[derive(Debug, Deserialize)]
-#[allow(dead_code)]
-struct RaindropHighlight {
-    #[serde(rename = "_id")]
-    id: String,
-    title: String,
-    text: String,
-    note: String, // Always a string (empty string if no note)
-    #[serde(default = "default_color")]
-    color: String, // Default to yellow if missing
-    #[serde(rename = "created")]
-    created_at: String,
-    link: String,
-    tags: Vec<String>,
-    #[serde(rename = "raindropRef")]
-    raindrop_ref: u64,
-}
+// The Great Reads collection on Raindrop
+const COLLECTION_ID: u64 = 55948413;
+
+// Raindrop API limit
+const HIGHLIGHTS_PER_PAGE: usize = 50;
 
-fn default_color() -> String {
-    "yellow".to_string()
+#[derive(OpenApi)]
+#[openapi(paths(get_highlights, proxy_rss))]
+pub struct ApiDoc;
+
+#[utoipa::path(
+    get,
+    path = "/great-reads-highlights",
+    tag = "great_reads",
+    responses((status = 200, description = "Highlights saved on Raindrop", body = Vec<HighlightItem>)),
+)]
+pub async fn get_highlights(State(app): State<App>) -> impl IntoResponse {
+    match cached_highlights(&app).await {
+        Ok(highlights) => (
+            [(axum::http::header::CONTENT_TYPE, "application/json")],
+            axum::body::Bytes::from(highlights),
+        )
+            .into_response(),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Failed to fetch highlights: {e:#}"),
+        )
+            .into_response(),
+    }
 }
 
-#[derive(Debug, Deserialize)]
-struct RaindropHighlightsResponse {
-    result: bool,
-    items: Vec<RaindropHighlight>,
+/// The newest highlight of the collection
+pub async fn latest_highlight(app: &App) -> Result<Option<HighlightItem>, eyre::Error> {
+    let highlights: Vec<HighlightItem> = serde_json::from_slice(&cached_highlights(app).await?)?;
+    Ok(highlights.into_iter().last())
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-pub struct HighlightItem {
-    pub id: String,
-    pub title: String,
-    pub text: String,
-    pub note: Option<String>,
-    pub color: String,
-    pub created_at: String,
-    pub link: String,
-    pub tags: Vec<String>,
+/// Fetches the highlights into the cache ahead of the first request
+pub async fn warm_cache(app: &App) -> Result<(), eyre::Error> {
+    cached_highlights(app).await.map(drop)
 }
 
-pub async fn get_highlights(State(app): State<App>) -> impl IntoResponse {
+/// The serialized highlights, the cache holds them as they are served
+async fn cached_highlights(app: &App) -> Result<Vec<u8>, eyre::Error> {
     let cache_key = "highlights";
 
+    if let Some(cached_data) = app.great_reads_cache.get(&cache_key.to_string()).await {
+        return Ok(cached_data.clone());
+    }
+
+    tracing::info!("Cache miss for highlights, fetching from Raindrop API");
+
+    let highlights = fetch_highlights(app.clients.raindrop.as_ref()).await?;
+    let serialized = serde_json::to_vec(&highlights)?;
+    app.great_reads_cache
+        .insert(cache_key.to_string(), serialized.clone(), CACHE_DURATION)
+        .await;
+
+    Ok(serialized)
+}
+
+// Keep the old RSS proxy for backwards compatibility during migration
+#[utoipa::path(
+    get,
+    path = "/great-reads-feed",
+    tag = "great_reads",
+    responses((
+        status = 200,
+        description = "RSS feed of the bookmarks saved on Raindrop",
+        content_type = "application/xml",
+        body = String,
+    )),
+)]
+pub async fn proxy_rss(State(app): State<App>) -> impl IntoResponse {
+    let cache_key = "rss_feed";
+
     // Check if we have cached data
     if let Some(cached_data) = app.great_reads_cache.get(&cache_key.to_string()).await {
-        return Json(
-            serde_json::from_slice::<Vec<HighlightItem>>(&cached_data).unwrap_or_default(),
+        let headers = [(axum::http::header::CONTENT_TYPE, "application/xml")];
+        return (
+            StatusCode::OK,
+            headers,
+            axum::body::Bytes::from(cached_data.clone()),
         )
-        .into_response();
+            .into_response();
     }
 
-    tracing::info!("Cache miss for highlights, fetching from Raindrop API");
+    tracing::info!("Cache miss for RSS feed, fetching from Raindrop");
+
+    match app.clients.raindrop.collection_rss(COLLECTION_ID).await {
+        Ok(bytes) => {
+            app.great_reads_cache
+                .insert(cache_key.to_string(), bytes.to_vec(), CACHE_DURATION)
+                .await;
 
-    let raindrop_token = match &app.config.raindrop_api_token {
-        Some(token) => token,
-        None => {
-            return (
+            let headers = [(axum::http::header::CONTENT_TYPE, "application/xml")];
+            (StatusCode::OK, headers, bytes).into_response()
+        }
+        Err(e) => {
+            tracing::warn!(?e, "Failed to fetch RSS feed");
+            (
                 StatusCode::INTERNAL_SERVER_ERROR,
-                "Raindrop API token not configured",
+                "Failed to fetch RSS feed",
             )
-                .into_response();
+                .into_response()
         }
-    };
-
-    let collection_id = "55948413"; // Great Reads collection ID
+    }
+}
 
+/// All the highlights of the collection, oldest first
+async fn fetch_highlights(
+    raindrop: &dyn RaindropClient,
+) -> Result<Vec<HighlightItem>, eyre::Error> {
     let mut all_highlights = Vec::new();
     let mut page = 0;
-    let per_page = 50; // Raindrop API limit
 
     loop {
-        let url = format!(
-            "https://api.raindrop.io/rest/v1/highlights/{}?page={}&perpage={}",
-            collection_id, page, per_page
-        );
-
-        match app
-            .http
-            .get(&url)
-            .header("Authorization", format!("Bearer {}", raindrop_token))
-            .send()
-            .await
-        {
-            Ok(resp) => {
-                if !resp.status().is_success() {
-                    return (
-                        StatusCode::INTERNAL_SERVER_ERROR,
-                        format!(
-                            "Failed to fetch highlights from Raindrop: {}",
-                            resp.status()
-                        ),
-                    )
-                        .into_response();
-                }
-
-                match resp.json::<RaindropHighlightsResponse>().await {
-                    Ok(highlights_response) => {
-                        if !highlights_response.result {
-                            return (
-                                StatusCode::INTERNAL_SERVER_ERROR,
-                                "Raindrop API returned error result",
-                            )
-                                .into_response();
-                        }
-
-                        let current_count = highlights_response.items.len();
-                        all_highlights.extend(highlights_response.items);
-
-                        // If we got fewer items than per_page, we've reached the end
-                        if current_count < per_page {
-                            break;
-                        }
-
-                        page += 1;
-                    }
-                    Err(e) => {
-                        return (
-                            StatusCode::INTERNAL_SERVER_ERROR,
-                            format!("Failed to parse highlights response: {e:?}"),
-                        )
-                            .into_response();
-                    }
-                }
-            }
-            Err(e) => {
-                return (
-                    StatusCode::INTERNAL_SERVER_ERROR,
-                    format!("Failed to fetch highlights: {e:?}"),
-                )
-                    .into_response();
-            }
+        let highlights = raindrop
+            .highlights(COLLECTION_ID, page, HIGHLIGHTS_PER_PAGE)
+            .await?;
+        let current_count = highlights.len();
+        all_highlights.extend(highlights);
+
+        // If we got fewer items than per_page, we've reached the end
+        if current_count < HIGHLIGHTS_PER_PAGE {
+            break;
         }
+
+        page += 1;
     }
 
     let mut highlights: Vec<HighlightItem> = all_highlights
@@ -164,53 +162,37 @@ pub async fn get_highlights(State(app): State<App>) -> impl IntoResponse {
         a.created_at.cmp(&b.created_at)
     });
 
-    // Cache the result
-    if let Ok(serialized) = serde_json::to_vec(&highlights) {
-        app.great_reads_cache
-            .insert(cache_key.to_string(), serialized, CACHE_DURATION)
-            .await;
-    }
-
-    Json(highlights).into_response()
+    Ok(highlights)
 }
 
-// Keep the old RSS proxy for backwards compatibility during migration
-pub async fn proxy_rss(State(app): State<App>) -> impl IntoResponse {
-    let cache_key = "rss_feed";
-
-    // Check if we have cached data
-    if let Some(cached_data) = app.great_reads_cache.get(&cache_key.to_string()).await {
-        let headers = [(axum::http::header::CONTENT_TYPE, "application/xml")];
-        return (
-            StatusCode::OK,
-            headers,
-            axum::body::Bytes::from(cached_data.clone()),
-        )
-            .into_response();
-    }
-
-    tracing::info!("Cache miss for RSS feed, fetching from Raindrop");
-
-    let url = "https://bg.raindrop.io/rss/public/55948413";
-    match app.http.get(url).send().await {
-        Ok(resp) => {
-            let status = resp.status();
-            let headers = [(axum::http::header::CONTENT_TYPE, "application/xml")];
-            let bytes = resp.bytes().await.unwrap_or_default();
-
-            // Cache the result if successful
-            if status.is_success() {
-                app.great_reads_cache
-                    .insert(cache_key.to_string(), bytes.to_vec(), CACHE_DURATION)
-                    .await;
-            }
-
-            (status, headers, bytes).into_response()
-        }
-        Err(_) => (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            "Failed to fetch RSS feed",
-        )
-            .into_response(),
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clients::{RaindropHighlight, offline::OfflineRaindrop};
+
+    #[tokio::test]
+    async fn fetch_highlights_pages_and_sorts() {
+        let mut raindrop = OfflineRaindrop::sample();
+        // Enough for a second page, newest first
+        let template = raindrop.highlights[0].clone();
+        raindrop.highlights = (0..HIGHLIGHTS_PER_PAGE + 1)
+            .rev()
+            .map(|i| RaindropHighlight {
+                id: i.to_string(),
+                note: if i == 0 {
+                    "note".to_string()
+                } else {
+                    String::new()
+                },
+                created_at: format!("2026-01-01T00:{i:02}:00.000Z"),
+                ..template.clone()
+            })
+            .collect();
+
+        let highlights = fetch_highlights(&raindrop).await.expect("highlights");
+        assert_eq!(highlights.len(), HIGHLIGHTS_PER_PAGE + 1);
+        assert_eq!(highlights[0].id, "0");
+        assert_eq!(highlights[0].note.as_deref(), Some("note"));
+        assert_eq!(highlights[1].note, None);
     }
 }
@@ -1,8 +1,14 @@
-use axum::{Json, extract::State, response::IntoResponse};
+use axum::{
+    Json,
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+};
 use chrono::{DateTime, Utc};
 use diesel::prelude::*;
 use diesel_async::RunQueryDsl;
 use serde::Serialize;
+use utoipa::ToSchema;
 
 #[derive(Queryable, Selectable)]
 #[diesel(table_name = crate::schema::identity_credentials)]
@@ -29,29 +35,72 @@ struct IdentityCredentialTypes {
 
 use crate::{App, error::AppError};
 
-use super::{AuthUser, routes::GitHubCredentials, spotify::SpotifyCredentials};
+use super::{
+    AuthUser,
+    raindrop::{self, RaindropCredentials},
+    routes::GitHubCredentials,
+    spotify::{self, SpotifyCredentials},
+    strava::{self, StravaCredentials},
+};
 
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 struct ConnectedApps {
     #[serde(skip_serializing_if = "Option::is_none")]
     spotify: Option<Spotify>,
 
     #[serde(skip_serializing_if = "Option::is_none")]
     github: Option<GitHub>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    raindrop: Option<Raindrop>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    strava: Option<Strava>,
+
+    /// Set in the config rather than linked, shown to the owner only
+    #[serde(skip_serializing_if = "Option::is_none")]
+    steam: Option<Steam>,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 struct Spotify {
     display_name: String,
     added_on: DateTime<Utc>,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 struct GitHub {
     user_id: i64,
     added_on: DateTime<Utc>,
 }
 
+#[derive(Serialize, ToSchema)]
+struct Raindrop {
+    collection_ids: Vec<String>,
+    added_on: DateTime<Utc>,
+}
+
+#[derive(Serialize, ToSchema)]
+struct Strava {
+    athlete_name: String,
+    added_on: DateTime<Utc>,
+}
+
+#[derive(Serialize, ToSchema)]
+struct Steam {
+    steam_id: String,
+}
+
+#[utoipa::path(
+    get,
+    path = "/link/apps",
+    tag = "identity",
+    responses(
+        (status = 200, description = "Third-party apps linked to the identity", body = ConnectedApps),
+        (status = 401, description = "Not logged in"),
+    ),
+    security(("session" = [])),
+)]
 pub async fn get_connected_apps(
     State(s): State<App>,
     AuthUser(i): AuthUser,
@@ -81,6 +130,12 @@ pub async fn get_connected_apps(
                     }))
                     .or(credential.contains(serde_json::json!({
                         "provider": "github"
+                    })))
+                    .or(credential.contains(serde_json::json!({
+                        "provider": "raindrop"
+                    })))
+                    .or(credential.contains(serde_json::json!({
+                        "provider": "strava"
                     }))),
             );
 
@@ -132,5 +187,85 @@ pub async fn get_connected_apps(
         })
         .next();
 
-    Ok(Json(ConnectedApps { github, spotify }))
+    let raindrop = connections
+        .iter()
+        .filter_map(|c| {
+            let credentials =
+                serde_json::from_value::<RaindropCredentials>(c.credential.clone()?).ok()?;
+            (credentials.provider == "raindrop").then(|| Raindrop {
+                collection_ids: credentials
+                    .collections
+                    .into_iter()
+                    .map(|c| c.collection_id)
+                    .collect(),
+                added_on: c.created_at.and_utc(),
+            })
+        })
+        .next();
+
+    let strava = connections
+        .iter()
+        .filter_map(|c| {
+            let credentials =
+                serde_json::from_value::<StravaCredentials>(c.credential.clone()?).ok()?;
+            (credentials.provider == "strava").then(|| Strava {
+                athlete_name: credentials.athlete_name,
+                added_on: c.created_at.and_utc(),
+            })
+        })
+        .next();
+
+    let steam = s
+        .config
+        .steam
+        .as_ref()
+        .filter(|_| i.id == s.config.owner_identity_id)
+        .map(|steam| Steam {
+            steam_id: steam.steam_id.clone(),
+        });
+
+    Ok(Json(ConnectedApps {
+        github,
+        spotify,
+        raindrop,
+        strava,
+        steam,
+    }))
+}
+
+/// Unlinks the app and forgets its credentials. GitHub can't be unlinked
+/// since it's how the identity logs in.
+#[utoipa::path(
+    delete,
+    path = "/link/apps/{provider}",
+    tag = "identity",
+    params(("provider" = String, Path, description = "`spotify`, `strava` or `raindrop`")),
+    responses(
+        (status = 204, description = "The app is unlinked"),
+        (status = 401, description = "Not logged in"),
+        (status = 404, description = "No such app"),
+        (status = 409, description = "The app is how the identity logs in"),
+    ),
+    security(("session" = [])),
+)]
+pub async fn disconnect_app(
+    State(ctx): State<App>,
+    AuthUser(i): AuthUser,
+    Path(provider): Path<String>,
+) -> Result<StatusCode, AppError> {
+    match provider.as_str() {
+        "spotify" => spotify::unlink(&ctx, i.id).await?,
+        "strava" => strava::unlink(&ctx, i.id).await?,
+        "raindrop" => raindrop::unlink(&ctx, i.id).await?,
+        "github" => {
+            return Err((
+                "GitHub is how you log in, it can't be unlinked",
+                StatusCode::CONFLICT,
+            )
+                .into());
+        }
+        _ => return Err(("No such app", StatusCode::NOT_FOUND).into()),
+    }
+
+    Ok(StatusCode::NO_CONTENT)
 }
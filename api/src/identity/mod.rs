@@ -8,8 +8,16 @@ use self::models::identity::Identity;
 
 mod connected_apps;
 mod spotify;
+mod steam;
+mod strava;
+
+pub use spotify::now_playing;
+pub use steam::now_gaming;
+pub use strava::latest_activity;
 
 pub mod models;
+pub mod raindrop;
+pub mod roles;
 pub mod routes;
 
 pub const COOKIE_NAME: &str = "auth_token";
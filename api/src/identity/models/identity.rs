@@ -1,6 +1,8 @@
 use diesel::prelude::*;
 use serde_json::Value as JsonValue;
 
+pub use api_models::Traits;
+
 #[allow(dead_code)]
 pub enum IdentityState {
     Active,
@@ -59,34 +61,3 @@ impl Identity {
     //     self
     // }
 }
-
-#[derive(serde::Serialize, serde::Deserialize, Clone, Debug)]
-pub struct Traits {
-    pub email: Option<String>,
-    pub name: Option<String>,
-}
-
-impl Traits {
-    /// Defines rules for determining which fields are identifiers. Returns a
-    /// list of references to the fields that are considered identifiers.
-    pub fn get_identifiers(&self) -> Vec<&String> {
-        let mut ids = vec![];
-        if let Some(email) = &self.email {
-            ids.push(email);
-        }
-        ids
-    }
-}
-
-// TODO maybe write a macro for this?
-impl From<&Traits> for JsonValue {
-    fn from(t: &Traits) -> Self {
-        serde_json::to_value(t).unwrap()
-    }
-}
-
-impl From<JsonValue> for Traits {
-    fn from(value: JsonValue) -> Self {
-        serde_json::from_value(value).unwrap()
-    }
-}
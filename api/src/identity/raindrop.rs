@@ -0,0 +1,240 @@
+//! Raindrop accounts linked by the readers, their saved articles become their
+//! history that the recommendations are personalized to
+
+use std::time::Duration;
+
+use axum::{
+    Json,
+    extract::State,
+    http::{StatusCode, header},
+};
+use diesel::prelude::*;
+use diesel_async::{AsyncConnection as _, AsyncPgConnection, RunQueryDsl};
+use eyre::Context as _;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::{
+    App,
+    config::RecommenderRaindropCollection,
+    error::AppError,
+    identity::models::credential::{IdentityCredential, NewIdentityCredential},
+    schema::{identity_credential_types, identity_credentials, user_history},
+};
+
+use super::AuthUser;
+
+const PROVIDER: &str = "raindrop";
+const VERIFY_TIMEOUT: Duration = Duration::from_secs(10);
+const MAX_COLLECTIONS: usize = 20;
+/// All the raindrops but the trashed ones
+const ALL_COLLECTIONS_ID: &str = "0";
+
+/// The credentials being persisted in the database
+#[derive(Deserialize, Serialize)]
+pub struct RaindropCredentials {
+    pub token: String,
+    pub collections: Vec<RaindropCollection>,
+    pub provider: String,
+}
+
+#[derive(Deserialize, Serialize, ToSchema)]
+pub struct RaindropCollection {
+    pub collection_id: String,
+    /// How much the articles of the collection count, from -1 to 1, negative
+    /// for a collection of disliked articles
+    pub weight: f32,
+}
+
+impl From<&RaindropCollection> for RecommenderRaindropCollection {
+    fn from(collection: &RaindropCollection) -> Self {
+        RecommenderRaindropCollection {
+            collection_id: collection.collection_id.clone(),
+            _name: String::new(),
+            weight: collection.weight,
+        }
+    }
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct RaindropLink {
+    /// A test token of a Raindrop app
+    token: String,
+    /// All the raindrops if omitted
+    collections: Option<Vec<RaindropCollection>>,
+}
+
+#[utoipa::path(
+    put,
+    path = "/link/raindrop",
+    tag = "identity",
+    request_body = RaindropLink,
+    responses(
+        (status = 204, description = "Raindrop is linked, the history is imported with the next crawl"),
+        (status = 400, description = "The token was rejected by Raindrop"),
+        (status = 401, description = "Not logged in"),
+    ),
+    security(("session" = [])),
+)]
+pub async fn link_raindrop(
+    State(ctx): State<App>,
+    AuthUser(i): AuthUser,
+    Json(link): Json<RaindropLink>,
+) -> Result<StatusCode, AppError> {
+    let token = link.token.trim().to_string();
+    if token.is_empty() {
+        return Err(("The token is required", StatusCode::BAD_REQUEST).into());
+    }
+
+    let collections = link.collections.unwrap_or_else(|| {
+        vec![RaindropCollection {
+            collection_id: ALL_COLLECTIONS_ID.to_string(),
+            weight: 1.0,
+        }]
+    });
+    if collections.is_empty() || collections.len() > MAX_COLLECTIONS {
+        return Err((
+            "Between 1 and 20 collections can be linked",
+            StatusCode::BAD_REQUEST,
+        )
+            .into());
+    }
+    if collections
+        .iter()
+        .any(|c| !(-1.0..=1.0).contains(&c.weight) || c.collection_id.parse::<i64>().is_err())
+    {
+        return Err((
+            "Collections need a numeric ID and a weight from -1 to 1",
+            StatusCode::BAD_REQUEST,
+        )
+            .into());
+    }
+
+    let verified = ctx
+        .http
+        .get("https://api.raindrop.io/rest/v1/user")
+        .header(header::AUTHORIZATION, format!("Bearer {token}"))
+        .timeout(VERIFY_TIMEOUT)
+        .send()
+        .await
+        .wrap_err("could not reach Raindrop")?
+        .status()
+        .is_success();
+    if !verified {
+        return Err(("Raindrop rejected the token", StatusCode::BAD_REQUEST).into());
+    }
+
+    let credential = IdentityCredential::new_oauth_credential(
+        serde_json::to_value(RaindropCredentials {
+            token,
+            collections,
+            provider: PROVIDER.to_string(),
+        })
+        .wrap_err("couldn't serialize raindrop credentials")?,
+    );
+
+    let mut conn = ctx.diesel.get().await?;
+    conn.transaction(async move |conn| {
+        let credential_type_id = identity_credential_types::table
+            .filter(identity_credential_types::name.eq("oauth"))
+            .select(identity_credential_types::id)
+            .first::<i32>(conn)
+            .await?;
+
+        // Linking again replaces the token and the collections
+        delete_credentials(conn, i.id).await?;
+        diesel::insert_into(identity_credentials::table)
+            .values(&NewIdentityCredential {
+                credential: credential.credential,
+                credential_type_id,
+                identity_id: i.id,
+                created_at: credential.created_at,
+                updated_at: credential.updated_at,
+            })
+            .execute(conn)
+            .await?;
+        Ok::<_, diesel::result::Error>(())
+    })
+    .await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[utoipa::path(
+    delete,
+    path = "/link/raindrop",
+    tag = "identity",
+    responses(
+        (status = 204, description = "Raindrop is unlinked and the history imported from it is deleted"),
+        (status = 401, description = "Not logged in"),
+    ),
+    security(("session" = [])),
+)]
+pub async fn unlink_raindrop(
+    State(ctx): State<App>,
+    AuthUser(i): AuthUser,
+) -> Result<StatusCode, AppError> {
+    unlink(&ctx, i.id).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Deletes the credentials and the history imported from Raindrop. The tokens
+/// are the test tokens of the reader's own Raindrop app, there's nothing to
+/// revoke.
+pub async fn unlink(ctx: &App, identity_id: i32) -> Result<(), eyre::Error> {
+    let mut conn = ctx.diesel.get().await?;
+    conn.transaction(async move |conn| {
+        delete_credentials(conn, identity_id).await?;
+        diesel::delete(user_history::table.filter(user_history::identity_id.eq(identity_id)))
+            .execute(conn)
+            .await?;
+        Ok::<_, diesel::result::Error>(())
+    })
+    .await?;
+    Ok(())
+}
+
+/// The identities with a linked Raindrop account, to import their history
+pub async fn linked_accounts(
+    conn: &mut AsyncPgConnection,
+) -> Result<Vec<(i32, RaindropCredentials)>, eyre::Error> {
+    let rows = identity_credentials::table
+        .filter(
+            identity_credentials::credential.contains(serde_json::json!({
+                "provider": PROVIDER
+            })),
+        )
+        .select((
+            identity_credentials::identity_id,
+            identity_credentials::credential,
+        ))
+        .load::<(i32, Option<serde_json::Value>)>(conn)
+        .await?;
+
+    Ok(rows
+        .into_iter()
+        .filter_map(|(identity_id, credential)| {
+            let credentials = serde_json::from_value(credential?)
+                .inspect_err(|e| tracing::warn!(?e, identity_id, "Invalid Raindrop credentials"))
+                .ok()?;
+            Some((identity_id, credentials))
+        })
+        .collect())
+}
+
+async fn delete_credentials(
+    conn: &mut AsyncPgConnection,
+    identity_id: i32,
+) -> Result<usize, diesel::result::Error> {
+    diesel::delete(
+        identity_credentials::table
+            .filter(identity_credentials::identity_id.eq(identity_id))
+            .filter(
+                identity_credentials::credential.contains(serde_json::json!({
+                    "provider": PROVIDER
+                })),
+            ),
+    )
+    .execute(conn)
+    .await
+}
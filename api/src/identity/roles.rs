@@ -0,0 +1,158 @@
+use std::collections::HashMap;
+
+use axum::{
+    Json, Router,
+    extract::{Path, State},
+    http::StatusCode,
+    routing::{get, put},
+};
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+use diesel_async::{AsyncPgConnection, RunQueryDsl};
+use serde::Serialize;
+
+pub use api_models::CommentRole;
+
+use crate::{App, error::AppError, identity::AuthUser, schema::identity_roles};
+
+pub fn route() -> Router<App> {
+    Router::<App>::new()
+        .route("/admin/roles", get(get_roles))
+        .route(
+            "/admin/identities/{identity_id}/roles/{role}",
+            put(grant_role).delete(revoke_role),
+        )
+}
+
+/// Roles of the identities, the site owner is an author whether granted or not
+pub async fn roles_of(
+    ctx: &App,
+    conn: &mut AsyncPgConnection,
+    identity_ids: &[i32],
+) -> Result<HashMap<i32, Vec<CommentRole>>, eyre::Error> {
+    let rows = identity_roles::table
+        .filter(identity_roles::identity_id.eq_any(identity_ids))
+        .select((identity_roles::identity_id, identity_roles::role))
+        .load::<(i32, String)>(conn)
+        .await?;
+
+    let mut roles = HashMap::<i32, Vec<CommentRole>>::new();
+    for (identity_id, role) in rows {
+        if let Some(role) = CommentRole::parse(&role) {
+            roles.entry(identity_id).or_default().push(role);
+        }
+    }
+    if identity_ids.contains(&ctx.config.owner_identity_id) {
+        roles
+            .entry(ctx.config.owner_identity_id)
+            .or_default()
+            .push(CommentRole::Author);
+    }
+    for identity_roles in roles.values_mut() {
+        identity_roles.sort();
+        identity_roles.dedup();
+    }
+
+    Ok(roles)
+}
+
+#[derive(Queryable, Serialize)]
+struct RoleGrant {
+    identity_id: i32,
+    name: Option<String>,
+    role: String,
+    granted_at: NaiveDateTime,
+}
+
+/// Every granted role, most recent first
+async fn get_roles(
+    State(ctx): State<App>,
+    AuthUser(i): AuthUser,
+) -> Result<Json<Vec<RoleGrant>>, AppError> {
+    ensure_owner(&ctx, i.id)?;
+
+    let mut conn = ctx.diesel.get().await?;
+
+    let grants = identity_roles::table
+        .inner_join(crate::schema::identities::table)
+        .order(identity_roles::granted_at.desc())
+        .select((
+            identity_roles::identity_id,
+            crate::schema::identities::traits
+                .retrieve_as_text("name")
+                .nullable(),
+            identity_roles::role,
+            identity_roles::granted_at,
+        ))
+        .load(&mut conn)
+        .await?;
+
+    Ok(Json(grants))
+}
+
+async fn grant_role(
+    State(ctx): State<App>,
+    AuthUser(i): AuthUser,
+    Path((identity_id, role)): Path<(i32, String)>,
+) -> Result<(), AppError> {
+    ensure_owner(&ctx, i.id)?;
+    let role = parse_role(&role)?;
+
+    let mut conn = ctx.diesel.get().await?;
+
+    diesel::insert_into(identity_roles::table)
+        .values((
+            identity_roles::identity_id.eq(identity_id),
+            identity_roles::role.eq(role.as_str()),
+        ))
+        .on_conflict_do_nothing()
+        .execute(&mut conn)
+        .await
+        .map_err(|e| match e {
+            diesel::result::Error::DatabaseError(
+                diesel::result::DatabaseErrorKind::ForeignKeyViolation,
+                _,
+            ) => AppError::from(("Identity not found", StatusCode::NOT_FOUND)),
+            e => e.into(),
+        })?;
+
+    Ok(())
+}
+
+async fn revoke_role(
+    State(ctx): State<App>,
+    AuthUser(i): AuthUser,
+    Path((identity_id, role)): Path<(i32, String)>,
+) -> Result<(), AppError> {
+    ensure_owner(&ctx, i.id)?;
+    let role = parse_role(&role)?;
+
+    let mut conn = ctx.diesel.get().await?;
+
+    diesel::delete(
+        identity_roles::table
+            .filter(identity_roles::identity_id.eq(identity_id))
+            .filter(identity_roles::role.eq(role.as_str())),
+    )
+    .execute(&mut conn)
+    .await?;
+
+    Ok(())
+}
+
+fn parse_role(role: &str) -> Result<CommentRole, AppError> {
+    CommentRole::parse(role).ok_or_else(|| {
+        (
+            format!("Unknown role `{role}`, expected author, moderator or verified"),
+            StatusCode::BAD_REQUEST,
+        )
+            .into()
+    })
+}
+
+fn ensure_owner(ctx: &App, identity_id: i32) -> Result<(), AppError> {
+    if identity_id != ctx.config.owner_identity_id {
+        return Err(("Not permitted", StatusCode::FORBIDDEN).into());
+    }
+    Ok(())
+}
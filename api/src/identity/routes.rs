@@ -1,11 +1,12 @@
 use std::collections::HashMap;
 
+use api_models::IsAuth;
 use axum::{
     Json, Router,
     extract::{Query, State},
     http::{StatusCode, header},
     response::IntoResponse,
-    routing::{get, post},
+    routing::{delete, get, post, put},
 };
 use axum_extra::extract::CookieJar;
 use diesel::prelude::*;
@@ -13,6 +14,7 @@ use diesel_async::RunQueryDsl;
 use serde::{Deserialize, Serialize};
 use serde_json::json;
 use time::Duration;
+use utoipa::{OpenApi, ToSchema};
 
 use crate::{
     App,
@@ -27,25 +29,63 @@ use crate::{
 
 use super::{
     AuthenticationError, COOKIE_NAME, MaybeAuthUser,
-    connected_apps::get_connected_apps,
-    spotify::{get_currently_playing, handle_spotify_callback, handle_spotify_connect_request},
+    connected_apps::{
+        __path_disconnect_app, __path_get_connected_apps, disconnect_app, get_connected_apps,
+    },
+    raindrop::{__path_link_raindrop, __path_unlink_raindrop, link_raindrop, unlink_raindrop},
+    spotify::{
+        __path_get_currently_playing, __path_handle_spotify_callback,
+        __path_handle_spotify_connect_request, get_currently_playing, handle_spotify_callback,
+        handle_spotify_connect_request,
+    },
+    strava::{
+        __path_get_recent_activities, __path_handle_strava_callback,
+        __path_handle_strava_connect_request, get_recent_activities, handle_strava_callback,
+        handle_strava_connect_request,
+    },
 };
 
+#[derive(OpenApi)]
+#[openapi(paths(
+    handle_whoami,
+    get_connected_apps,
+    disconnect_app,
+    is_auth,
+    logout,
+    handle_oauth_github_request,
+    handle_github_oauth_callback,
+    handle_spotify_connect_request,
+    handle_spotify_callback,
+    get_currently_playing,
+    handle_strava_connect_request,
+    handle_strava_callback,
+    get_recent_activities,
+    link_raindrop,
+    unlink_raindrop,
+))]
+pub struct ApiDoc;
+
 pub fn route() -> Router<App> {
     // TODO rate limit these public endpoints
     Router::<App>::new()
         .route("/me", get(handle_whoami))
         .route("/link/apps", get(get_connected_apps))
+        .route("/link/apps/{provider}", delete(disconnect_app))
         .route("/is_auth", get(is_auth))
         .route("/logout", post(logout))
         .route("/login/github", get(handle_oauth_github_request))
         .route("/login/github/callback", get(handle_github_oauth_callback))
         .route("/link/spotify", get(handle_spotify_connect_request))
         .route("/link/spotify/callback", get(handle_spotify_callback))
+        .route("/link/raindrop", put(link_raindrop).delete(unlink_raindrop))
         .route("/currently-playing", get(get_currently_playing))
+        .route("/link/strava", get(handle_strava_connect_request))
+        .route("/link/strava/callback", get(handle_strava_callback))
+        .route("/recent-activities", get(get_recent_activities))
+        .merge(super::roles::route())
 }
 
-#[derive(serde::Serialize)]
+#[derive(serde::Serialize, ToSchema)]
 pub struct WhoamiRespose {
     traits: Traits,
 }
@@ -59,20 +99,12 @@ impl ApiRequestError for AuthenticationError {
     }
 }
 
-#[derive(serde::Serialize)]
-struct IsAuth {
-    is_auth: bool,
-
-    #[serde(skip_serializing_if = "Option::is_none")]
-    id: Option<i32>,
-
-    #[serde(skip_serializing_if = "Option::is_none")]
-    traits: Option<Traits>,
-
-    #[serde(skip_serializing_if = "std::ops::Not::not")]
-    site_owner: bool,
-}
-
+#[utoipa::path(
+    get,
+    path = "/is_auth",
+    tag = "identity",
+    responses((status = 200, description = "Whether the request is logged in, and as who", body = IsAuth)),
+)]
 async fn is_auth(
     State(ctx): State<App>,
     MaybeAuthUser(identity): MaybeAuthUser,
@@ -92,6 +124,16 @@ async fn is_auth(
     }))
 }
 
+#[utoipa::path(
+    get,
+    path = "/me",
+    tag = "identity",
+    responses(
+        (status = 200, description = "Traits of the logged in identity", body = WhoamiRespose),
+        (status = 401, description = "Not logged in"),
+    ),
+    security(("session" = [])),
+)]
 async fn handle_whoami(
     MaybeAuthUser(identity): MaybeAuthUser,
 ) -> Result<axum::Json<WhoamiRespose>, AppError> {
@@ -107,6 +149,13 @@ pub struct GitHubCredentials {
     pub provider: String,
 }
 
+#[utoipa::path(
+    get,
+    path = "/login/github/callback",
+    tag = "identity",
+    params(("code" = String, Query, description = "Authorization code returned by GitHub")),
+    responses((status = 200, description = "Logged in, the session cookie is set")),
+)]
 #[axum::debug_handler]
 pub async fn handle_github_oauth_callback(
     State(ctx): State<App>,
@@ -116,97 +165,37 @@ pub async fn handle_github_oauth_callback(
         .get("code")
         .ok_or(("No `code` in query parameters", StatusCode::BAD_REQUEST))?;
 
-    let GitHubOauth {
-        client_id: github_client_id,
-        client_secret: github_client_secret,
-    } = ctx
+    let github_oauth = ctx
         .config
         .github_oauth
         .as_ref()
         .expect("GitHub Oauth credentials is not set");
 
-    let code_verify: serde_json::Value = ctx
-        .http
-        .post("https://github.com/login/oauth/access_token")
-        .header("Accept", "application/json")
-        .json(&serde_json::json!({
-            "client_id": github_client_id,
-            "client_secret": github_client_secret,
-            "code": code
-        }))
-        .send()
-        .await?
-        .json()
-        .await?;
-
-    let access_token = code_verify["access_token"].as_str();
-
-    if access_token.is_none() {
+    let github = &ctx.clients.github;
+    let Some(access_token) = github.access_token(github_oauth, code).await? else {
         Err(AuthenticationError::Unauthorized)?
-    }
-
-    let user_info: serde_json::Value = ctx
-        .http
-        .get("https://api.github.com/user")
-        .header("User-Agent", "reqwest")
-        .header("Accept", "application/json")
-        .header(
-            "Authorization",
-            "Bearer ".to_string() + access_token.unwrap(),
-        )
-        .send()
-        .await?
-        .json()
-        .await?;
-
-    const MISSING_EXPECTED_FIELD: &str = "GitHub returned unexpected response";
+    };
 
-    // TODO use a struct to deserialize into instead of this
-    let user = user_info["login"].as_str().ok_or(MISSING_EXPECTED_FIELD)?;
-    let user_id = user_info["id"].as_i64().ok_or(MISSING_EXPECTED_FIELD)?;
+    let user_info = github.user(&access_token).await?;
+    let user_id = user_info.id;
 
     // NOTE: some users don't have a name set
-    let full_name = user_info["name"].as_str().unwrap_or(user);
-
-    let emails: serde_json::Value = ctx
-        .http
-        .get("https://api.github.com/user/emails")
-        .header("User-Agent", "reqwest")
-        .header("Accept", "application/json")
-        .header(
-            "Authorization",
-            "Bearer ".to_string() + access_token.unwrap(),
-        )
-        .send()
+    let full_name = user_info.name.as_deref().unwrap_or(&user_info.login);
+
+    let email = github
+        .emails(&access_token)
         .await?
-        .json()
-        .await?;
-
-    let email = emails
-        .as_array()
-        .ok_or("emails is not an array")?
-        .iter()
-        .find(|email| {
-            email["primary"].as_bool().unwrap_or(false)
-                && email["verified"].as_bool().unwrap_or(false)
-        })
+        .into_iter()
+        .find(|email| email.primary && email.verified)
         .ok_or((
             "No valid email found for this github account",
             StatusCode::BAD_GATEWAY,
         ))?
-        .get("email")
-        .ok_or(
-            "valid email found, but couldn't extract it because the field `email` does not exist",
-        )?
-        .as_str()
-        .ok_or(format!(
-            "valid email found, but couldn't extract it because the field `email` is not a string: {}",
-            emails
-        ))?;
+        .email;
 
     let i = Identity::new_with_traits(Traits {
         name: Some(full_name.to_owned()),
-        email: Some(email.to_owned()),
+        email: Some(email),
     });
 
     let mut identity = {
@@ -305,6 +294,13 @@ pub async fn handle_github_oauth_callback(
     Ok(CookieJar::new().add(auth_cookie))
 }
 
+#[utoipa::path(
+    get,
+    path = "/login/github",
+    tag = "identity",
+    params(("return_to" = Option<String>, Query, description = "Where to go back to once logged in")),
+    responses((status = 302, description = "Redirect to the GitHub authorization page")),
+)]
 #[axum::debug_handler]
 pub async fn handle_oauth_github_request(
     State(ctx): State<App>,
@@ -328,20 +324,20 @@ pub async fn handle_oauth_github_request(
         .as_ref()
         .expect("GitHub Oauth credentials is not set");
 
-    let url = reqwest::Url::parse_with_params(
-        "https://github.com/login/oauth/authorize",
-        &[
-            ("client_id", github_client_id.as_str()),
-            ("scope", "user:email"),
-            ("redirect_uri", redirect_uri.as_str()),
-        ],
-    )
-    .map_err(|e| format!("Failed to parse url: {}", e))?
-    .to_string();
+    let url = ctx
+        .clients
+        .github
+        .authorize_url(github_client_id, &redirect_uri)?;
 
     Ok((axum::http::StatusCode::FOUND, [(header::LOCATION, url)]).into_response())
 }
 
+#[utoipa::path(
+    post,
+    path = "/logout",
+    tag = "identity",
+    responses((status = 200, description = "The session cookie is cleared")),
+)]
 #[axum::debug_handler]
 pub async fn logout() -> impl IntoResponse {
     let auth_cookie = axum_extra::extract::cookie::Cookie::build(COOKIE_NAME)
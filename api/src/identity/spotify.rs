@@ -21,7 +21,7 @@ use rspotify::{
     model::Id,
 };
 use serde::{Deserialize, Serialize};
-use tokio::sync::{OnceCell, RwLock};
+use tokio::sync::RwLock;
 
 use crate::{
     App,
@@ -30,6 +30,8 @@ use crate::{
     identity::models::credential::{IdentityCredential, NewIdentityCredential},
 };
 
+use api_models::NowPlaying;
+
 use super::AuthUser;
 
 /// The credentials being persisted in the database
@@ -56,6 +58,13 @@ pub enum SpotifyConnectError {
 
 impl ApiRequestError for SpotifyConnectError {}
 
+#[utoipa::path(
+    get,
+    path = "/link/spotify",
+    tag = "identity",
+    params(("return_to" = Option<String>, Query, description = "Where to go back to once linked")),
+    responses((status = 302, description = "Redirect to the Spotify authorization page")),
+)]
 #[axum::debug_handler]
 pub async fn handle_spotify_connect_request(
     State(ctx): State<App>,
@@ -79,6 +88,17 @@ pub async fn handle_spotify_connect_request(
     Ok((axum::http::StatusCode::FOUND, [(header::LOCATION, url)]).into_response())
 }
 
+#[utoipa::path(
+    get,
+    path = "/link/spotify/callback",
+    tag = "identity",
+    params(("code" = String, Query, description = "Authorization code returned by Spotify")),
+    responses(
+        (status = 200, description = "Spotify is linked to the owner identity"),
+        (status = 403, description = "Not the site owner"),
+    ),
+    security(("session" = [])),
+)]
 #[axum::debug_handler]
 pub async fn handle_spotify_callback(
     State(ctx): State<App>,
@@ -174,14 +194,41 @@ pub async fn handle_spotify_callback(
     Ok(())
 }
 
+/// Deletes the Spotify credentials of the identity. Spotify has no API to
+/// revoke the tokens, the refresh token is forgotten and the access token
+/// expires within the hour.
+pub async fn unlink(ctx: &App, identity_id: i32) -> Result<(), eyre::Error> {
+    use crate::schema::identity_credentials;
+
+    let mut conn = ctx.diesel.get().await?;
+    diesel::delete(
+        identity_credentials::table
+            .filter(identity_credentials::identity_id.eq(identity_id))
+            .filter(
+                identity_credentials::credential.contains(serde_json::json!({
+                    "provider": "spotify"
+                })),
+            ),
+    )
+    .execute(&mut conn)
+    .await?;
+
+    // Only my account is cached
+    if identity_id == ctx.config.owner_identity_id {
+        *SPOTIFY_CLIENT.write().await = None;
+        *CURRENTLY_PLAYING_CACHE.write().await = None;
+    }
+    Ok(())
+}
+
 /// Cache spotify client that ties to my account, so that you don't have to
 /// create a new client for every request. It also reuses the access token and
 /// only refreshed when expired, contrast to having to request for access token
-/// every time the client is newly created.
-static SPOTIFY_CLIENT: OnceCell<AuthCodeSpotify> = OnceCell::const_new();
+/// every time the client is newly created. Reset when I unlink Spotify.
+static SPOTIFY_CLIENT: RwLock<Option<Arc<AuthCodeSpotify>>> = RwLock::const_new(None);
 
-static CURRENTLY_PLAYING_CACHE: OnceCell<RwLock<(Arc<CurrentlyPlaying>, std::time::Instant)>> =
-    OnceCell::const_new();
+static CURRENTLY_PLAYING_CACHE: RwLock<Option<(Arc<CurrentlyPlaying>, std::time::Instant)>> =
+    RwLock::const_new(None);
 
 #[derive(Clone, Serialize)]
 struct CurrentlyPlaying {
@@ -190,14 +237,69 @@ struct CurrentlyPlaying {
     currently_playing_type: Option<String>,
 }
 
+#[utoipa::path(
+    get,
+    path = "/currently-playing",
+    tag = "identity",
+    responses((
+        status = 200,
+        description = "What the site owner is listening to on Spotify",
+        content_type = "application/json",
+    )),
+)]
 #[axum::debug_handler]
 pub async fn get_currently_playing(State(s): State<App>) -> Result<impl IntoResponse, AppError> {
+    Ok(Json(currently_playing(&s).await?))
+}
+
+/// The track or the episode playing on my Spotify, none if Spotify isn't
+/// configured or nothing was played lately
+pub async fn now_playing(s: &App) -> Result<Option<NowPlaying>, AppError> {
+    if s.config.spotify_oauth.is_none() {
+        return Ok(None);
+    }
+    let cp = currently_playing(s).await?;
+    let Some(item) = cp.item else {
+        return Ok(None);
+    };
+
+    // Read from the JSON of the item, which is the same for the tracks and
+    // the episodes but for the artists
+    let item = serde_json::to_value(item).map_err(|e| eyre!(e))?;
+    let text = |value: &serde_json::Value| value.as_str().map(str::to_string);
+    let artists = match item.get("artists").and_then(|a| a.as_array()) {
+        Some(artists) => artists.iter().filter_map(|a| text(&a["name"])).collect(),
+        None => text(&item["show"]["name"]).into_iter().collect(),
+    };
+
+    Ok(text(&item["name"]).map(|title| NowPlaying {
+        is_playing: cp.is_playing,
+        title,
+        artists,
+        url: text(&item["external_urls"]["spotify"]),
+    }))
+}
+
+/// The currently playing of my account, fetched at most once a second
+async fn currently_playing(s: &App) -> Result<CurrentlyPlaying, AppError> {
     async fn fetch_cp(s: &App) -> Result<CurrentlyPlaying, AppError> {
         let user_id = s.config.owner_identity_id;
 
-        let client = SPOTIFY_CLIENT
-            .get_or_try_init(|| async { create_my_authorized_spotify_client(s, user_id).await })
-            .await?;
+        let client = match SPOTIFY_CLIENT.read().await.clone() {
+            Some(client) => client,
+            None => {
+                let mut cached = SPOTIFY_CLIENT.write().await;
+                match cached.as_ref() {
+                    Some(client) => client.clone(),
+                    None => {
+                        let client =
+                            Arc::new(create_my_authorized_spotify_client(s, user_id).await?);
+                        *cached = Some(client.clone());
+                        client
+                    }
+                }
+            }
+        };
 
         let cp = client
             .current_playing(None, None::<&[_]>)
@@ -220,27 +322,22 @@ pub async fn get_currently_playing(State(s): State<App>) -> Result<impl IntoResp
         Ok(cp)
     }
 
-    let lock = CURRENTLY_PLAYING_CACHE
-        .get_or_try_init(|| async {
-            Ok::<_, AppError>(RwLock::new((
-                Arc::new(fetch_cp(&s).await?),
-                std::time::Instant::now(),
-            )))
-        })
-        .await?;
-
-    let cache = lock.read().await;
-
-    let cp = if cache.1.elapsed() > Duration::from_secs(1) {
-        drop(cache);
-        let mut cache = lock.write().await;
-        *cache = (Arc::new(fetch_cp(&s).await?), std::time::Instant::now());
-        cache.0.deref().clone()
-    } else {
-        cache.0.deref().clone()
+    let cache = CURRENTLY_PLAYING_CACHE.read().await;
+
+    let cp = match cache.as_ref() {
+        Some((cp, fetched_at)) if fetched_at.elapsed() <= Duration::from_secs(1) => {
+            cp.deref().clone()
+        }
+        _ => {
+            drop(cache);
+            let mut cache = CURRENTLY_PLAYING_CACHE.write().await;
+            let cp = Arc::new(fetch_cp(s).await?);
+            *cache = Some((cp.clone(), std::time::Instant::now()));
+            cp.deref().clone()
+        }
     };
 
-    Ok(Json(cp))
+    Ok(cp)
 }
 
 fn create_spotify_client(ctx: App, redirect_uri: Option<String>) -> rspotify::AuthCodeSpotify {
@@ -0,0 +1,201 @@
+//! My Steam account, for the now page. There's nothing to link, the account
+//! is set with `STEAM_ID` and read with the key of the Steam Web API.
+
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use eyre::Context as _;
+use serde::{Deserialize, de::DeserializeOwned};
+use tokio::sync::RwLock;
+
+use crate::{App, config::SteamConfig};
+
+use api_models::{NowGaming, SteamGame};
+
+const API_URL: &str = "https://api.steampowered.com";
+/// Steam updates the playtime every few minutes, a minute is recent enough
+const CACHE_TTL: Duration = Duration::from_secs(60);
+const MAX_RECENT_GAMES: usize = 5;
+
+static NOW_GAMING_CACHE: RwLock<Option<(Arc<NowGaming>, Instant)>> = RwLock::const_new(None);
+
+#[derive(Deserialize)]
+struct Response<T> {
+    response: T,
+}
+
+#[derive(Deserialize)]
+struct PlayerSummaries {
+    #[serde(default)]
+    players: Vec<PlayerSummary>,
+}
+
+#[derive(Deserialize)]
+struct PlayerSummary {
+    /// Only set while in a game
+    gameid: Option<String>,
+    gameextrainfo: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct RecentlyPlayedGames {
+    /// Missing if nothing was played lately or the game details are private
+    #[serde(default)]
+    games: Vec<RecentlyPlayedGame>,
+}
+
+#[derive(Deserialize)]
+struct RecentlyPlayedGame {
+    appid: u32,
+    name: String,
+    #[serde(default)]
+    playtime_2weeks: u32,
+    #[serde(default)]
+    playtime_forever: u32,
+}
+
+/// The game I'm playing on Steam and the ones I played lately, none if Steam
+/// isn't configured. Fetched at most once a minute.
+pub async fn now_gaming(ctx: &App) -> Result<Option<NowGaming>, eyre::Error> {
+    let Some(steam) = ctx.config.steam.as_ref() else {
+        return Ok(None);
+    };
+
+    if let Some((gaming, fetched_at)) = NOW_GAMING_CACHE.read().await.as_ref()
+        && fetched_at.elapsed() < CACHE_TTL
+    {
+        return Ok(Some(gaming.as_ref().clone()));
+    }
+
+    let mut cache = NOW_GAMING_CACHE.write().await;
+    let (summaries, recent) = tokio::try_join!(
+        get::<PlayerSummaries>(ctx, steam, "ISteamUser/GetPlayerSummaries/v2", "steamids"),
+        get::<RecentlyPlayedGames>(
+            ctx,
+            steam,
+            "IPlayerService/GetRecentlyPlayedGames/v1",
+            "steamid",
+        ),
+    )?;
+    let gaming = Arc::new(to_now_gaming(summaries, recent));
+    *cache = Some((gaming.clone(), Instant::now()));
+
+    Ok(Some(gaming.as_ref().clone()))
+}
+
+/// A method of the Web API for the account, whose parameter for the SteamID
+/// differs between the methods
+async fn get<T: DeserializeOwned>(
+    ctx: &App,
+    steam: &SteamConfig,
+    method: &str,
+    steam_id_param: &str,
+) -> Result<T, eyre::Error> {
+    let url = reqwest::Url::parse_with_params(
+        &format!("{API_URL}/{method}/"),
+        [
+            ("key", steam.api_key.as_str()),
+            (steam_id_param, steam.steam_id.as_str()),
+        ],
+    )?;
+    let response = ctx
+        .http
+        .get(url)
+        .send()
+        .await
+        .and_then(|r| r.error_for_status())
+        .wrap_err_with(|| format!("could not call {method} of Steam"))?;
+
+    Ok(response
+        .json::<Response<T>>()
+        .await
+        .wrap_err_with(|| format!("unexpected response of {method} of Steam"))?
+        .response)
+}
+
+fn to_now_gaming(summaries: PlayerSummaries, recent: RecentlyPlayedGames) -> NowGaming {
+    let mut recent = recent
+        .games
+        .into_iter()
+        .map(|game| SteamGame {
+            url: store_url(game.appid),
+            app_id: game.appid,
+            name: game.name,
+            recent_minutes: game.playtime_2weeks,
+            total_minutes: game.playtime_forever,
+        })
+        .collect::<Vec<_>>();
+    recent.sort_by_key(|game| std::cmp::Reverse(game.recent_minutes));
+    recent.truncate(MAX_RECENT_GAMES);
+
+    let playing = summaries.players.into_iter().next().and_then(|player| {
+        let app_id = player.gameid?.parse::<u32>().ok()?;
+        // The playtime is only known if it was also played in the last two
+        // weeks, which it usually was
+        Some(
+            recent
+                .iter()
+                .find(|game| game.app_id == app_id)
+                .cloned()
+                .unwrap_or_else(|| SteamGame {
+                    app_id,
+                    name: player.gameextrainfo.unwrap_or_default(),
+                    recent_minutes: 0,
+                    total_minutes: 0,
+                    url: store_url(app_id),
+                }),
+        )
+    });
+
+    NowGaming { playing, recent }
+}
+
+fn store_url(app_id: u32) -> String {
+    format!("https://store.steampowered.com/app/{app_id}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_playing_game_is_read_with_its_playtime() {
+        let summaries = serde_json::from_str::<Response<PlayerSummaries>>(
+            r#"{"response": {"players": [{"steamid": "1", "gameid": "570", "gameextrainfo": "Dota 2"}]}}"#,
+        )
+        .expect("summaries")
+        .response;
+        let recent = serde_json::from_str::<Response<RecentlyPlayedGames>>(
+            r#"{"response": {"total_count": 2, "games": [
+                {"appid": 620, "name": "Portal 2", "playtime_2weeks": 30, "playtime_forever": 900},
+                {"appid": 570, "name": "Dota 2", "playtime_2weeks": 120, "playtime_forever": 6000}
+            ]}}"#,
+        )
+        .expect("recent games")
+        .response;
+
+        let gaming = to_now_gaming(summaries, recent);
+        let playing = gaming.playing.expect("playing");
+        assert_eq!(playing.name, "Dota 2");
+        assert_eq!(playing.total_minutes, 6000);
+        assert_eq!(
+            gaming.recent.iter().map(|g| g.app_id).collect::<Vec<_>>(),
+            vec![570, 620]
+        );
+
+        // Private game details or nothing played lately
+        let idle = serde_json::from_str::<Response<PlayerSummaries>>(
+            r#"{"response": {"players": [{"steamid": "1"}]}}"#,
+        )
+        .expect("summaries")
+        .response;
+        let none = serde_json::from_str::<Response<RecentlyPlayedGames>>(r#"{"response": {}}"#)
+            .expect("recent games")
+            .response;
+        let gaming = to_now_gaming(idle, none);
+        assert!(gaming.playing.is_none());
+        assert!(gaming.recent.is_empty());
+    }
+}
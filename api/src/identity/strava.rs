@@ -0,0 +1,654 @@
+//! My Strava account, for the recent runs on the now page. It's linked with
+//! OAuth like Spotify, the access token is kept in memory and refreshed once
+//! it expires. Strava may rotate the refresh token, the new one is saved.
+
+use std::{
+    collections::HashMap,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use axum::{
+    Json,
+    extract::{Query, State},
+    http::{StatusCode, header},
+    response::IntoResponse,
+};
+use chrono::{DateTime, Utc};
+use diesel::prelude::*;
+use diesel_async::{AsyncConnection as _, AsyncPgConnection, RunQueryDsl};
+use eyre::Context as _;
+use serde::{Deserialize, Serialize};
+use tokio::sync::RwLock;
+
+use crate::{
+    App,
+    config::StravaOauth,
+    error::{ApiRequestError, AppError},
+    identity::models::credential::{IdentityCredential, NewIdentityCredential},
+    schema::{identity_credential_types, identity_credentials},
+};
+
+use api_models::StravaActivity;
+
+use super::AuthUser;
+
+const PROVIDER: &str = "strava";
+const AUTHORIZE_URL: &str = "https://www.strava.com/oauth/authorize";
+const TOKEN_URL: &str = "https://www.strava.com/oauth/token";
+const DEAUTHORIZE_URL: &str = "https://www.strava.com/oauth/deauthorize";
+const API_URL: &str = "https://www.strava.com/api/v3";
+/// The public activities are enough, `activity:read_all` also works
+const SCOPE: &str = "activity:read";
+/// Strava allows an app 100 requests every 15 minutes
+const ACTIVITIES_TTL: Duration = Duration::from_secs(10 * 60);
+const RECENT_ACTIVITIES: usize = 10;
+/// The token is refreshed this early, so that it doesn't expire mid-request
+const TOKEN_EXPIRY_MARGIN: i64 = 60;
+/// About 10 metres, the maps are drawn small
+const MAP_TOLERANCE_DEGREES: f64 = 0.0001;
+
+/// The credentials being persisted in the database
+#[derive(Deserialize, Serialize)]
+pub struct StravaCredentials {
+    pub athlete_id: i64,
+    pub athlete_name: String,
+    pub refresh_token: String,
+    pub scopes: Vec<String>,
+    pub provider: String,
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum StravaConnectError {
+    #[error("You are not permitted to link Strava account")]
+    NotPermitted,
+
+    #[error("Strava is not configured")]
+    NotConfigured,
+
+    #[error("Reading your activities is needed to show them, please allow it")]
+    MissingScope,
+
+    #[error("We failed to verify your Strava connection")]
+    ConnectFailed,
+}
+
+impl ApiRequestError for StravaConnectError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            StravaConnectError::NotPermitted => StatusCode::FORBIDDEN,
+            StravaConnectError::NotConfigured => StatusCode::SERVICE_UNAVAILABLE,
+            _ => StatusCode::BAD_REQUEST,
+        }
+    }
+}
+
+/// The access token of my account, reused until it expires
+static STRAVA_TOKEN: RwLock<Option<AccessToken>> = RwLock::const_new(None);
+
+static ACTIVITIES_CACHE: RwLock<Option<(Arc<Vec<StravaActivity>>, Instant)>> =
+    RwLock::const_new(None);
+
+struct AccessToken {
+    token: String,
+    /// Unix timestamp
+    expires_at: i64,
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    access_token: String,
+    refresh_token: String,
+    expires_at: i64,
+    /// Only returned when exchanging the authorization code
+    athlete: Option<Athlete>,
+}
+
+#[derive(Deserialize)]
+struct Athlete {
+    id: i64,
+    firstname: Option<String>,
+    lastname: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct SummaryActivity {
+    id: i64,
+    name: String,
+    sport_type: String,
+    /// Metres
+    distance: f64,
+    /// Seconds
+    moving_time: u32,
+    start_date: DateTime<Utc>,
+    map: Option<ActivityMap>,
+}
+
+#[derive(Deserialize)]
+struct ActivityMap {
+    summary_polyline: Option<String>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/link/strava",
+    tag = "identity",
+    params(("return_to" = Option<String>, Query, description = "Where to go back to once linked")),
+    responses(
+        (status = 302, description = "Redirect to the Strava authorization page"),
+        (status = 503, description = "Strava is not configured"),
+    ),
+)]
+pub async fn handle_strava_connect_request(
+    State(ctx): State<App>,
+    Query(queries): Query<HashMap<String, String>>,
+) -> Result<impl IntoResponse, AppError> {
+    let oauth = ctx
+        .config
+        .strava_oauth
+        .as_ref()
+        .ok_or(StravaConnectError::NotConfigured)?;
+
+    let redirect_uri = redirect_uri(&ctx, queries.get("return_to"));
+    let url = reqwest::Url::parse_with_params(
+        AUTHORIZE_URL,
+        [
+            ("client_id", oauth.client_id.as_str()),
+            ("redirect_uri", redirect_uri.as_str()),
+            ("response_type", "code"),
+            ("approval_prompt", "auto"),
+            ("scope", SCOPE),
+        ],
+    )
+    .wrap_err("couldn't build strava authorize url")?;
+
+    Ok((StatusCode::FOUND, [(header::LOCATION, url.to_string())]).into_response())
+}
+
+#[utoipa::path(
+    get,
+    path = "/link/strava/callback",
+    tag = "identity",
+    params(
+        ("code" = String, Query, description = "Authorization code returned by Strava"),
+        ("scope" = String, Query, description = "The scopes the athlete accepted"),
+    ),
+    responses(
+        (status = 200, description = "Strava is linked to the owner identity"),
+        (status = 400, description = "Reading the activities wasn't allowed"),
+        (status = 403, description = "Not the site owner"),
+    ),
+    security(("session" = [])),
+)]
+pub async fn handle_strava_callback(
+    State(ctx): State<App>,
+    Query(queries): Query<HashMap<String, String>>,
+    AuthUser(i): AuthUser,
+) -> Result<impl IntoResponse, AppError> {
+    if i.id != ctx.config.owner_identity_id {
+        Err(StravaConnectError::NotPermitted)?
+    }
+    let oauth = ctx
+        .config
+        .strava_oauth
+        .as_ref()
+        .ok_or(StravaConnectError::NotConfigured)?;
+
+    let code = queries
+        .get("code")
+        .ok_or(("No `code` in query parameters", StatusCode::BAD_REQUEST))?;
+    // The athlete may untick the scopes on the authorization page
+    let scopes = queries
+        .get("scope")
+        .map(|s| s.split(',').map(str::to_string).collect::<Vec<_>>())
+        .unwrap_or_default();
+    if !scopes
+        .iter()
+        .any(|s| s == SCOPE || s == "activity:read_all")
+    {
+        Err(StravaConnectError::MissingScope)?
+    }
+
+    let token = request_token(
+        &ctx,
+        oauth,
+        &[("grant_type", "authorization_code"), ("code", code)],
+    )
+    .await
+    .inspect_err(|err| tracing::warn!(?err, "Failed to exchange the Strava code"))
+    .map_err(|_| StravaConnectError::ConnectFailed)?;
+    let athlete = token.athlete.ok_or(StravaConnectError::ConnectFailed)?;
+
+    let credential = IdentityCredential::new_oauth_credential(
+        serde_json::to_value(StravaCredentials {
+            athlete_id: athlete.id,
+            athlete_name: [athlete.firstname, athlete.lastname]
+                .into_iter()
+                .flatten()
+                .collect::<Vec<_>>()
+                .join(" "),
+            refresh_token: token.refresh_token,
+            scopes,
+            provider: PROVIDER.to_string(),
+        })
+        .wrap_err("couldn't serialize strava credentials")?,
+    );
+
+    let mut conn = ctx.diesel.get().await?;
+    conn.transaction(async move |conn| {
+        let credential_type_id = identity_credential_types::table
+            .filter(identity_credential_types::name.eq("oauth"))
+            .select(identity_credential_types::id)
+            .first::<i32>(conn)
+            .await?;
+
+        // Linking again replaces the account
+        delete_credentials(conn, i.id).await?;
+        diesel::insert_into(identity_credentials::table)
+            .values(&NewIdentityCredential {
+                credential: credential.credential,
+                credential_type_id,
+                identity_id: i.id,
+                created_at: credential.created_at,
+                updated_at: credential.updated_at,
+            })
+            .execute(conn)
+            .await?;
+        Ok::<_, diesel::result::Error>(())
+    })
+    .await?;
+
+    *STRAVA_TOKEN.write().await = Some(AccessToken {
+        token: token.access_token,
+        expires_at: token.expires_at,
+    });
+    *ACTIVITIES_CACHE.write().await = None;
+
+    Ok(())
+}
+
+/// Deletes the Strava credentials of the identity, and revokes the access of
+/// the site if it's my account
+pub async fn unlink(ctx: &App, identity_id: i32) -> Result<(), eyre::Error> {
+    if identity_id == ctx.config.owner_identity_id {
+        // Unlinked anyway if Strava can't be reached, the athlete can still
+        // revoke the access on Strava
+        let _ = deauthorize(ctx)
+            .await
+            .inspect_err(|err| tracing::warn!(?err, "Failed to deauthorize Strava"));
+        *STRAVA_TOKEN.write().await = None;
+        *ACTIVITIES_CACHE.write().await = None;
+    }
+
+    let mut conn = ctx.diesel.get().await?;
+    delete_credentials(&mut conn, identity_id).await?;
+    Ok(())
+}
+
+async fn deauthorize(ctx: &App) -> Result<(), eyre::Error> {
+    let Some(token) = access_token(ctx).await? else {
+        return Ok(());
+    };
+    ctx.http
+        .post(DEAUTHORIZE_URL)
+        .bearer_auth(token)
+        .send()
+        .await
+        .and_then(|r| r.error_for_status())
+        .wrap_err("could not deauthorize Strava")?;
+    Ok(())
+}
+
+#[utoipa::path(
+    get,
+    path = "/recent-activities",
+    tag = "identity",
+    responses((
+        status = 200,
+        description = "The latest activities of the site owner on Strava, none if Strava isn't linked",
+        body = Vec<StravaActivity>,
+    )),
+)]
+pub async fn get_recent_activities(State(ctx): State<App>) -> Result<impl IntoResponse, AppError> {
+    Ok((
+        [(header::CACHE_CONTROL, "public, max-age=600")],
+        Json(recent_activities(&ctx).await?.as_ref().clone()),
+    ))
+}
+
+/// My latest activity on Strava, none if Strava isn't linked
+pub async fn latest_activity(ctx: &App) -> Result<Option<StravaActivity>, eyre::Error> {
+    Ok(recent_activities(ctx).await?.as_slice().first().cloned())
+}
+
+/// My latest activities, fetched at most every 10 minutes
+async fn recent_activities(ctx: &App) -> Result<Arc<Vec<StravaActivity>>, eyre::Error> {
+    if let Some((activities, fetched_at)) = ACTIVITIES_CACHE.read().await.as_ref()
+        && fetched_at.elapsed() < ACTIVITIES_TTL
+    {
+        return Ok(activities.clone());
+    }
+
+    let mut cache = ACTIVITIES_CACHE.write().await;
+    let activities = Arc::new(fetch_activities(ctx).await?);
+    *cache = Some((activities.clone(), Instant::now()));
+    Ok(activities)
+}
+
+async fn fetch_activities(ctx: &App) -> Result<Vec<StravaActivity>, eyre::Error> {
+    let Some(token) = access_token(ctx).await? else {
+        return Ok(Vec::new());
+    };
+
+    let activities = ctx
+        .http
+        .get(format!(
+            "{API_URL}/athlete/activities?per_page={RECENT_ACTIVITIES}"
+        ))
+        .bearer_auth(token)
+        .send()
+        .await
+        .and_then(|r| r.error_for_status())
+        .wrap_err("could not get the Strava activities")?
+        .json::<Vec<SummaryActivity>>()
+        .await
+        .wrap_err("unexpected Strava activities")?;
+
+    Ok(activities.into_iter().map(to_activity).collect())
+}
+
+fn to_activity(activity: SummaryActivity) -> StravaActivity {
+    let pace_seconds_per_km = (activity.distance > 0.0)
+        .then(|| (f64::from(activity.moving_time) * 1000.0 / activity.distance).round() as u32);
+
+    StravaActivity {
+        url: format!("https://www.strava.com/activities/{}", activity.id),
+        id: activity.id,
+        name: activity.name,
+        sport_type: activity.sport_type,
+        distance_meters: activity.distance,
+        moving_seconds: activity.moving_time,
+        pace_seconds_per_km,
+        started_at: activity.start_date.naive_utc(),
+        polyline: activity
+            .map
+            .and_then(|map| map.summary_polyline)
+            .filter(|polyline| !polyline.is_empty())
+            .and_then(|polyline| {
+                let points = decode_polyline(&polyline)?;
+                Some(encode_polyline(&simplify(&points, MAP_TOLERANCE_DEGREES)))
+            }),
+    }
+}
+
+/// The access token of my account, refreshed if it expired. None if Strava
+/// isn't configured or linked.
+async fn access_token(ctx: &App) -> Result<Option<String>, eyre::Error> {
+    let Some(oauth) = ctx.config.strava_oauth.as_ref() else {
+        return Ok(None);
+    };
+    let valid = |token: &AccessToken| {
+        token.expires_at - TOKEN_EXPIRY_MARGIN > chrono::Utc::now().timestamp()
+    };
+
+    if let Some(token) = STRAVA_TOKEN.read().await.as_ref()
+        && valid(token)
+    {
+        return Ok(Some(token.token.clone()));
+    }
+
+    let mut cached = STRAVA_TOKEN.write().await;
+    // Refreshed by another request meanwhile
+    if let Some(token) = cached.as_ref()
+        && valid(token)
+    {
+        return Ok(Some(token.token.clone()));
+    }
+
+    let owner_id = ctx.config.owner_identity_id;
+    let mut conn = ctx.diesel.get().await?;
+    let Some(mut credentials) = credentials(&mut conn, owner_id).await? else {
+        return Ok(None);
+    };
+    // Release connection before the refresh
+    drop(conn);
+
+    let token = request_token(
+        ctx,
+        oauth,
+        &[
+            ("grant_type", "refresh_token"),
+            ("refresh_token", &credentials.refresh_token),
+        ],
+    )
+    .await?;
+
+    if token.refresh_token != credentials.refresh_token {
+        credentials.refresh_token = token.refresh_token;
+        let mut conn = ctx.diesel.get().await?;
+        diesel::update(
+            identity_credentials::table
+                .filter(identity_credentials::identity_id.eq(owner_id))
+                .filter(
+                    identity_credentials::credential.contains(serde_json::json!({
+                        "provider": PROVIDER
+                    })),
+                ),
+        )
+        .set((
+            identity_credentials::credential.eq(serde_json::to_value(&credentials)?),
+            identity_credentials::updated_at.eq(chrono::Utc::now().naive_utc()),
+        ))
+        .execute(&mut conn)
+        .await?;
+    }
+
+    *cached = Some(AccessToken {
+        token: token.access_token.clone(),
+        expires_at: token.expires_at,
+    });
+    Ok(Some(token.access_token))
+}
+
+async fn request_token(
+    ctx: &App,
+    oauth: &StravaOauth,
+    grant: &[(&str, &str)],
+) -> Result<TokenResponse, eyre::Error> {
+    let mut body = serde_json::Map::new();
+    body.insert("client_id".into(), oauth.client_id.clone().into());
+    body.insert("client_secret".into(), oauth.client_secret.clone().into());
+    for (key, value) in grant {
+        body.insert(key.to_string(), value.to_string().into());
+    }
+
+    ctx.http
+        .post(TOKEN_URL)
+        .json(&body)
+        .send()
+        .await
+        .and_then(|r| r.error_for_status())
+        .wrap_err("could not request a Strava token")?
+        .json::<TokenResponse>()
+        .await
+        .wrap_err("unexpected Strava token response")
+}
+
+async fn credentials(
+    conn: &mut AsyncPgConnection,
+    identity_id: i32,
+) -> Result<Option<StravaCredentials>, eyre::Error> {
+    let credential = identity_credentials::table
+        .filter(identity_credentials::identity_id.eq(identity_id))
+        .filter(
+            identity_credentials::credential.contains(serde_json::json!({
+                "provider": PROVIDER
+            })),
+        )
+        .select(identity_credentials::credential)
+        .first::<Option<serde_json::Value>>(conn)
+        .await
+        .optional()?
+        .flatten();
+
+    credential
+        .map(serde_json::from_value)
+        .transpose()
+        .wrap_err("invalid Strava credentials")
+}
+
+async fn delete_credentials(
+    conn: &mut AsyncPgConnection,
+    identity_id: i32,
+) -> Result<usize, diesel::result::Error> {
+    diesel::delete(
+        identity_credentials::table
+            .filter(identity_credentials::identity_id.eq(identity_id))
+            .filter(
+                identity_credentials::credential.contains(serde_json::json!({
+                    "provider": PROVIDER
+                })),
+            ),
+    )
+    .execute(conn)
+    .await
+}
+
+fn redirect_uri(ctx: &App, return_to: Option<&String>) -> String {
+    let mut uri = ctx.config.site_url.clone() + "/link/strava";
+    if let Some(return_to) = return_to {
+        uri += &format!("?return_to={return_to}");
+    }
+    uri
+}
+
+/// The points of a polyline in Google's encoded format, as (lat, lng)
+fn decode_polyline(encoded: &str) -> Option<Vec<(f64, f64)>> {
+    let mut values = Vec::new();
+    let (mut value, mut shift) = (0i64, 0);
+    for byte in encoded.bytes() {
+        let chunk = i64::from(byte.checked_sub(63)?);
+        value |= (chunk & 0x1f) << shift;
+        shift += 5;
+        if chunk < 0x20 {
+            values.push(if value & 1 == 1 {
+                !(value >> 1)
+            } else {
+                value >> 1
+            });
+            (value, shift) = (0, 0);
+        } else if shift > 60 {
+            return None;
+        }
+    }
+    if shift != 0 || !values.len().is_multiple_of(2) {
+        return None;
+    }
+
+    // Every point but the first is the difference from the previous one
+    let (mut lat, mut lng) = (0i64, 0i64);
+    Some(
+        values
+            .chunks(2)
+            .map(|delta| {
+                lat += delta[0];
+                lng += delta[1];
+                (lat as f64 / 1e5, lng as f64 / 1e5)
+            })
+            .collect(),
+    )
+}
+
+fn encode_polyline(points: &[(f64, f64)]) -> String {
+    let mut encoded = String::new();
+    let mut encode = |value: i64| {
+        let mut value = if value < 0 { !(value << 1) } else { value << 1 };
+        while value >= 0x20 {
+            encoded.push(char::from(((0x20 | (value & 0x1f)) + 63) as u8));
+            value >>= 5;
+        }
+        encoded.push(char::from((value + 63) as u8));
+    };
+
+    let (mut previous_lat, mut previous_lng) = (0i64, 0i64);
+    for (lat, lng) in points {
+        let (lat, lng) = ((lat * 1e5).round() as i64, (lng * 1e5).round() as i64);
+        encode(lat - previous_lat);
+        encode(lng - previous_lng);
+        (previous_lat, previous_lng) = (lat, lng);
+    }
+    encoded
+}
+
+/// The points that keep the shape within the tolerance, by Douglas-Peucker
+fn simplify(points: &[(f64, f64)], tolerance: f64) -> Vec<(f64, f64)> {
+    if points.len() < 3 {
+        return points.to_vec();
+    }
+
+    let mut kept = vec![false; points.len()];
+    kept[0] = true;
+    kept[points.len() - 1] = true;
+    let mut segments = vec![(0, points.len() - 1)];
+    while let Some((start, end)) = segments.pop() {
+        let farthest = (start + 1..end)
+            .map(|i| {
+                (
+                    i,
+                    distance_to_segment(points[i], points[start], points[end]),
+                )
+            })
+            .max_by(|a, b| a.1.total_cmp(&b.1));
+        if let Some((i, distance)) = farthest
+            && distance > tolerance
+        {
+            kept[i] = true;
+            segments.push((start, i));
+            segments.push((i, end));
+        }
+    }
+
+    points
+        .iter()
+        .zip(kept)
+        .filter_map(|(point, kept)| kept.then_some(*point))
+        .collect()
+}
+
+/// In degrees, the routes are small enough for the earth to be flat
+fn distance_to_segment(point: (f64, f64), start: (f64, f64), end: (f64, f64)) -> f64 {
+    let (dx, dy) = (end.0 - start.0, end.1 - start.1);
+    let length = dx * dx + dy * dy;
+    let t = if length == 0.0 {
+        0.0
+    } else {
+        (((point.0 - start.0) * dx + (point.1 - start.1) * dy) / length).clamp(0.0, 1.0)
+    };
+    let (x, y) = (start.0 + t * dx, start.1 + t * dy);
+    ((point.0 - x).powi(2) + (point.1 - y).powi(2)).sqrt()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn polylines_are_simplified_and_encoded_again() {
+        // The example of Google's documentation
+        let encoded = "_p~iF~ps|U_ulLnnqC_mqNvxq`@";
+        let points = decode_polyline(encoded).expect("valid polyline");
+        assert_eq!(
+            points,
+            vec![(38.5, -120.2), (40.7, -120.95), (43.252, -126.453)]
+        );
+        assert_eq!(encode_polyline(&points), encoded);
+        assert_eq!(decode_polyline("_p~iF~ps|U_ulL"), None);
+
+        // The middle point barely bends the line
+        let straight = [(0.0, 0.0), (0.5, 0.00001), (1.0, 0.0), (1.0, 1.0)];
+        assert_eq!(
+            simplify(&straight, MAP_TOLERANCE_DEGREES),
+            vec![(0.0, 0.0), (1.0, 0.0), (1.0, 1.0)]
+        );
+    }
+}
@@ -0,0 +1,217 @@
+//! Background jobs that only one instance runs at a time when several are
+//! deployed. A job is held with a Postgres advisory lock on a connection of
+//! its own, so the lock goes with the connection however the job ends, and
+//! the holder renews its lease in `background_job_leases` meanwhile to show
+//! which instance runs what.
+
+use std::time::Duration;
+
+use axum::{Json, Router, extract::State, http::StatusCode, routing::get};
+use diesel::prelude::*;
+use diesel::sql_types::{Bool, Integer, Text, Timestamp};
+use diesel::upsert::excluded;
+use diesel_async::{AsyncConnection as _, AsyncPgConnection, RunQueryDsl};
+use eyre::{Context as _, eyre};
+use serde::Serialize;
+
+use crate::{
+    App, config::ServerConfig, error::AppError, identity::AuthUser, schema::background_job_leases,
+};
+
+/// First key of the advisory locks, the second one is the hash of the job
+const LOCK_NAMESPACE: i32 = 0x6a6f6273;
+const RENEW_INTERVAL: Duration = Duration::from_secs(30);
+/// How often an instance standing by checks whether the job is free
+const STANDBY_INTERVAL: Duration = Duration::from_secs(60);
+
+const LEASES_QUERY: &str = r#"
+SELECT l.job, l.instance, l.acquired_at, l.renewed_at,
+    EXISTS (
+        SELECT 1 FROM pg_locks p
+        WHERE p.locktype = 'advisory'
+            AND p.granted
+            AND p.classid = $1::oid
+            AND p.objid = hashtext(l.job)::oid
+            AND p.objsubid = 2
+    ) AS held
+FROM background_job_leases l
+ORDER BY l.job
+"#;
+
+pub fn admin_route() -> Router<App> {
+    Router::<App>::new().route("/admin/jobs", get(list_jobs))
+}
+
+/// The lock of a job held by this instance, until it's released or dropped
+pub struct Lease {
+    job: &'static str,
+    instance: String,
+    conn: AsyncPgConnection,
+}
+
+#[derive(QueryableByName)]
+struct Locked {
+    #[diesel(sql_type = Bool)]
+    locked: bool,
+}
+
+/// The lock of the job, none if another instance holds it
+pub async fn try_acquire(
+    config: &ServerConfig,
+    job: &'static str,
+) -> Result<Option<Lease>, eyre::Error> {
+    let mut conn = AsyncPgConnection::establish(&config.database_url)
+        .await
+        .wrap_err("could not connect to lock the job")?;
+
+    let locked = diesel::sql_query("SELECT pg_try_advisory_lock($1, hashtext($2)) AS locked")
+        .bind::<Integer, _>(LOCK_NAMESPACE)
+        .bind::<Text, _>(job)
+        .get_result::<Locked>(&mut conn)
+        .await
+        .wrap_err("could not lock the job")?
+        .locked;
+    if !locked {
+        return Ok(None);
+    }
+
+    // The lease of a holder that went away is taken over
+    diesel::insert_into(background_job_leases::table)
+        .values((
+            background_job_leases::job.eq(job),
+            background_job_leases::instance.eq(&config.instance_id),
+        ))
+        .on_conflict(background_job_leases::job)
+        .do_update()
+        .set((
+            background_job_leases::instance.eq(excluded(background_job_leases::instance)),
+            background_job_leases::acquired_at.eq(diesel::dsl::now),
+            background_job_leases::renewed_at.eq(diesel::dsl::now),
+        ))
+        .execute(&mut conn)
+        .await
+        .wrap_err("could not record the lease of the job")?;
+
+    Ok(Some(Lease {
+        job,
+        instance: config.instance_id.clone(),
+        conn,
+    }))
+}
+
+impl Lease {
+    /// Runs the job while renewing the lease, then releases it. The job is
+    /// stopped if the lock is lost, none is returned then.
+    pub async fn hold<T>(mut self, job: impl Future<Output = T>) -> Option<T> {
+        let name = self.job;
+        let result = tokio::select! {
+            result = job => Some(result),
+            err = renew(&mut self.conn, self.job, &self.instance) => {
+                tracing::error!(job = name, ?err, "Lost the lock of the job, stopping it");
+                None
+            }
+        };
+
+        if result.is_some() {
+            self.release().await;
+        }
+        result
+    }
+
+    async fn release(mut self) {
+        let _ = diesel::delete(
+            background_job_leases::table
+                .filter(background_job_leases::job.eq(self.job))
+                .filter(background_job_leases::instance.eq(&self.instance)),
+        )
+        .execute(&mut self.conn)
+        .await
+        .inspect_err(|err| tracing::warn!(job = self.job, ?err, "Failed to delete the lease"));
+        // Closing the connection unlocks the job
+    }
+}
+
+/// Renews the lease until it fails, which means the connection holding the
+/// lock is gone or another instance took the job over
+async fn renew(conn: &mut AsyncPgConnection, job: &str, instance: &str) -> eyre::Error {
+    loop {
+        tokio::time::sleep(RENEW_INTERVAL).await;
+
+        let renewed = diesel::update(
+            background_job_leases::table
+                .filter(background_job_leases::job.eq(job))
+                .filter(background_job_leases::instance.eq(instance)),
+        )
+        .set(background_job_leases::renewed_at.eq(diesel::dsl::now))
+        .execute(conn)
+        .await;
+        match renewed {
+            Ok(0) => return eyre!("the lease was taken over"),
+            Ok(_) => {}
+            Err(err) => return eyre::Error::new(err).wrap_err("could not renew the lease"),
+        }
+    }
+}
+
+/// Runs the worker on one instance at a time, the others stand by and take
+/// it over if that instance goes away
+pub fn spawn_exclusive<F, Fut>(config: &ServerConfig, job: &'static str, worker: F)
+where
+    F: Fn() -> Fut + Send + 'static,
+    Fut: Future<Output = ()> + Send + 'static,
+{
+    let config = config.clone();
+    tokio::spawn(async move {
+        loop {
+            match try_acquire(&config, job).await {
+                Ok(Some(lease)) => {
+                    tracing::info!(job, "Running the background job on this instance");
+                    lease.hold(worker()).await;
+                }
+                Ok(None) => tracing::debug!(job, "The background job runs on another instance"),
+                Err(err) => tracing::warn!(job, ?err, "Failed to lock the background job"),
+            }
+            tokio::time::sleep(STANDBY_INTERVAL).await;
+        }
+    });
+}
+
+#[derive(QueryableByName, Serialize)]
+struct JobLease {
+    #[diesel(sql_type = Text)]
+    job: String,
+    #[diesel(sql_type = Text)]
+    instance: String,
+    #[diesel(sql_type = Timestamp)]
+    acquired_at: chrono::NaiveDateTime,
+    #[diesel(sql_type = Timestamp)]
+    renewed_at: chrono::NaiveDateTime,
+    /// False if the holder went away without deleting the lease
+    #[diesel(sql_type = Bool)]
+    held: bool,
+}
+
+#[derive(Serialize)]
+struct Jobs {
+    /// The instance answering
+    instance: String,
+    leases: Vec<JobLease>,
+}
+
+/// Which instance holds which job
+async fn list_jobs(State(ctx): State<App>, AuthUser(i): AuthUser) -> Result<Json<Jobs>, AppError> {
+    if i.id != ctx.config.owner_identity_id {
+        return Err(("Not permitted", StatusCode::FORBIDDEN).into());
+    }
+
+    let mut conn = ctx.diesel.get().await?;
+    let leases = diesel::sql_query(LEASES_QUERY)
+        .bind::<Integer, _>(LOCK_NAMESPACE)
+        .load::<JobLease>(&mut conn)
+        .await?;
+
+    Ok(Json(Jobs {
+        instance: ctx.config.instance_id.clone(),
+        leases,
+    }))
+}
@@ -1,10 +1,25 @@
+use std::{collections::HashSet, fmt};
+
 use axum::{
+    body::{Body, Bytes},
     extract::{FromRequest, Request, rejection::JsonRejection},
     http::StatusCode,
 };
+use futures::{StreamExt as _, stream};
+use serde::{
+    Serialize,
+    de::{self, DeserializeSeed, Deserializer, MapAccess, SeqAccess, Visitor},
+};
 
 use crate::error::AppError;
 
+/// Deepest nesting of objects and arrays accepted in a request body, no
+/// endpoint needs anywhere near this
+pub const MAX_JSON_DEPTH: usize = 32;
+
+/// Items serialized per chunk of a streamed body
+const STREAM_CHUNK_ITEMS: usize = 64;
+
 // We define our own `Json` extractor that customizes the error from `axum::Json`
 pub struct Json<T>(pub T);
 
@@ -18,7 +33,19 @@ where
     async fn from_request(req: Request, state: &S) -> Result<Self, Self::Rejection> {
         let (parts, body) = req.into_parts();
 
-        let req = Request::from_parts(parts, body);
+        // Buffer the body first, this enforces the body limit of the route
+        let bytes = Bytes::from_request(Request::from_parts(parts.clone(), body), state)
+            .await
+            .map_err(|rejection| match rejection.status() {
+                StatusCode::PAYLOAD_TOO_LARGE => {
+                    AppError::from(("Request body is too large", StatusCode::PAYLOAD_TOO_LARGE))
+                }
+                status => AppError::from((rejection.body_text(), status)),
+            })?;
+
+        check_structure(&bytes).map_err(|e| (e, StatusCode::BAD_REQUEST))?;
+
+        let req = Request::from_parts(parts, Body::from(bytes));
 
         match axum::Json::<T>::from_request(req, state).await {
             Ok(value) => Ok(Self(value.0)),
@@ -27,3 +54,167 @@ where
         }
     }
 }
+
+/// A body streaming a JSON array wrapped in `prefix` and `suffix`, e.g.
+/// `{"items":[` and `]}`. The items are serialized as the body is sent rather
+/// than into one buffer holding the whole payload.
+pub fn array_body<T>(prefix: &'static str, items: Vec<T>, suffix: &'static str) -> Body
+where
+    T: Serialize + Send + 'static,
+{
+    items_body(prefix, b',', items, suffix)
+}
+
+/// A body streaming the items as newline delimited JSON
+pub fn ndjson_body<T>(items: Vec<T>) -> Body
+where
+    T: Serialize + Send + 'static,
+{
+    items_body("", b'\n', items, "\n")
+}
+
+fn items_body<T>(prefix: &'static str, separator: u8, items: Vec<T>, suffix: &'static str) -> Body
+where
+    T: Serialize + Send + 'static,
+{
+    let chunks = stream::iter(items)
+        .chunks(STREAM_CHUNK_ITEMS)
+        .enumerate()
+        .map(move |(i, chunk)| {
+            let mut buf = Vec::new();
+            for (j, item) in chunk.iter().enumerate() {
+                if i > 0 || j > 0 {
+                    buf.push(separator);
+                }
+                serde_json::to_writer(&mut buf, item)?;
+            }
+            Ok::<_, serde_json::Error>(Bytes::from(buf))
+        });
+
+    Body::from_stream(
+        stream::iter([Ok(Bytes::from_static(prefix.as_bytes()))])
+            .chain(chunks)
+            .chain(stream::iter([Ok(Bytes::from_static(suffix.as_bytes()))])),
+    )
+}
+
+/// Reject payloads nested deeper than [MAX_JSON_DEPTH] or with duplicate keys,
+/// which serde would otherwise silently resolve to the last value. Malformed
+/// JSON is left for the deserialization to report.
+fn check_structure(bytes: &[u8]) -> Result<(), String> {
+    let mut deserializer = serde_json::Deserializer::from_slice(bytes);
+    match (StructureGuard { depth: 0 }).deserialize(&mut deserializer) {
+        Err(e) if e.classify() == serde_json::error::Category::Data => Err(e.to_string()),
+        _ => Ok(()),
+    }
+}
+
+/// Walks a JSON value without keeping it, checking the depth and the keys
+#[derive(Clone, Copy)]
+struct StructureGuard {
+    depth: usize,
+}
+
+impl StructureGuard {
+    fn nested<E: de::Error>(&self) -> Result<Self, E> {
+        if self.depth >= MAX_JSON_DEPTH {
+            return Err(E::custom(format!(
+                "JSON nested deeper than {MAX_JSON_DEPTH} levels"
+            )));
+        }
+        Ok(Self {
+            depth: self.depth + 1,
+        })
+    }
+}
+
+impl<'de> DeserializeSeed<'de> for StructureGuard {
+    type Value = ();
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<(), D::Error> {
+        deserializer.deserialize_any(self)
+    }
+}
+
+impl<'de> Visitor<'de> for StructureGuard {
+    type Value = ();
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("any JSON value")
+    }
+
+    fn visit_bool<E>(self, _: bool) -> Result<(), E> {
+        Ok(())
+    }
+
+    fn visit_i64<E>(self, _: i64) -> Result<(), E> {
+        Ok(())
+    }
+
+    fn visit_u64<E>(self, _: u64) -> Result<(), E> {
+        Ok(())
+    }
+
+    fn visit_f64<E>(self, _: f64) -> Result<(), E> {
+        Ok(())
+    }
+
+    fn visit_str<E>(self, _: &str) -> Result<(), E> {
+        Ok(())
+    }
+
+    fn visit_unit<E>(self) -> Result<(), E> {
+        Ok(())
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<(), A::Error> {
+        let nested = self.nested()?;
+        while seq.next_element_seed(nested)?.is_some() {}
+        Ok(())
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<(), A::Error> {
+        let nested = self.nested()?;
+        let mut keys = HashSet::new();
+        while let Some(key) = map.next_key::<String>()? {
+            if keys.contains(&key) {
+                return Err(de::Error::custom(format!("duplicate key `{key}`")));
+            }
+            keys.insert(key);
+            map.next_value_seed(nested)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_structure_rejects_deep_and_duplicate_payloads() {
+        assert!(check_structure(br#"{"a": [1, {"b": null}], "c": "d"}"#).is_ok());
+        // Malformed JSON is reported by the deserialization instead
+        assert!(check_structure(br#"{"a": "#).is_ok());
+
+        let deep = "[".repeat(MAX_JSON_DEPTH + 1) + &"]".repeat(MAX_JSON_DEPTH + 1);
+        assert!(check_structure(deep.as_bytes()).is_err());
+        let shallow = "[".repeat(MAX_JSON_DEPTH) + &"]".repeat(MAX_JSON_DEPTH);
+        assert!(check_structure(shallow.as_bytes()).is_ok());
+
+        assert!(check_structure(br#"{"a": 1, "a": 2}"#).is_err());
+        assert!(check_structure(br#"{"a": {"b": 1}, "b": 2}"#).is_ok());
+    }
+
+    #[tokio::test]
+    async fn array_body_separates_items_across_chunks() {
+        let items = (0..STREAM_CHUNK_ITEMS + 2).collect::<Vec<_>>();
+        let body =
+            axum::body::to_bytes(array_body("{\"items\":[", items.clone(), "]}"), usize::MAX)
+                .await
+                .expect("body should be readable");
+
+        let parsed: serde_json::Value = serde_json::from_slice(&body).expect("valid JSON");
+        assert_eq!(parsed, serde_json::json!({ "items": items }));
+    }
+}
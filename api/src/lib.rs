@@ -0,0 +1,504 @@
+use axum::{
+    Json, Router,
+    extract::{DefaultBodyLimit, MatchedPath, Query},
+    http::{
+        Method, Request,
+        header::{AUTHORIZATION, CONTENT_TYPE},
+    },
+    middleware,
+    response::Response,
+    routing::get,
+};
+use config::ServerConfig;
+use serde_json::json;
+use std::{collections::HashMap, ops::Deref, sync::Arc, time::Duration};
+use tower_http::{
+    classify::ServerErrorsFailureClass,
+    compression::CompressionLayer,
+    cors::{AllowOrigin, CorsLayer},
+    trace::TraceLayer,
+};
+use tracing::{Span, debug, error, info, info_span};
+
+use crate::error::AppError;
+#[cfg(debug_assertions)]
+use crate::real_ip::ClientIp;
+
+mod activitypub;
+mod assets;
+mod blog;
+mod clients;
+pub mod config;
+mod crypto;
+mod db;
+mod discord;
+mod embedding;
+mod error;
+mod geoip;
+mod github;
+mod great_reads_feed;
+mod identity;
+mod jobs;
+mod json;
+mod load_shed;
+mod models;
+mod notes;
+mod now;
+mod openapi;
+mod outbound;
+mod rate_limit;
+mod real_ip;
+mod recommendation;
+mod schema;
+mod seed;
+mod short_link;
+mod ssrf;
+mod status;
+mod utils;
+mod versioning;
+mod warmup;
+
+pub use recommendation::{IndexParams, reindex_embeddings};
+pub use seed::seed;
+
+/// Name of the span of each request, left out of the span logs since the
+/// responses are logged
+pub const HTTP_REQUEST_SPAN: &str = "http_request";
+
+#[derive(Clone)]
+pub struct App(Arc<Inner>);
+
+impl Deref for App {
+    type Target = Inner;
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+pub struct Inner {
+    counters_ttl_cache: retainer::Cache<String, bool>,
+    great_reads_cache: retainer::Cache<String, Vec<u8>>,
+    og_image_cache: retainer::Cache<String, axum::body::Bytes>,
+    load: load_shed::LoadShedder,
+    readiness: warmup::Readiness,
+    recommendation: recommendation::RecommendationSystem,
+    now: now::NowCache,
+    config: ServerConfig,
+    /// Settings that can change while running, see [config::Tunables]
+    tunables: config::Tunables,
+    discord_settings: discord::settings::DiscordSettings,
+    discord_usage: discord::usage::UsageTracker,
+    discord_archive: discord::archive::MessageArchive,
+    discord_limiter: discord::concurrency::ExecutionLimiter,
+    discord_moderation: discord::moderation::Moderation,
+    discord_transcripts: discord::transcripts::TranscriptRecorder,
+    discord_feedback: discord::feedback::FeedbackStore,
+    discord_reminders: discord::reminders::Reminders,
+    discord_summaries: discord::summaries::ChannelSummaries,
+    discord_dms: discord::direct_messages::DirectMessages,
+    discord_status: discord::supervisor::ConnectionStatus,
+    geoip: geoip::GeoIp,
+    diesel: db::Db,
+    /// Every request to other servers goes through it
+    http: outbound::HttpClient,
+    /// Clients of the third-party APIs, faked in the offline mode
+    clients: clients::Clients,
+    comment_notifier: Option<Arc<dyn blog::notify::CommentNotifier>>,
+    comment_report_limiter: rate_limit::RateLimiter<std::net::IpAddr>,
+    comment_scrubber: blog::CommentScrubber,
+    /// The blog's fediverse actor, if a key is configured
+    activitypub: Option<activitypub::ActivityPub>,
+    embedder: embedding::Embedder,
+    /// The Discord bot's memories, if a vector database is configured
+    discord_memories: Option<discord::tools::SharedVectorClient>,
+}
+
+/// Builds the [App] from the configuration. The background jobs and the bot
+/// don't run until started, and the database is connected to when first used.
+pub struct AppBuilder {
+    config: ServerConfig,
+    offline: bool,
+}
+
+impl AppBuilder {
+    pub fn new(config: ServerConfig) -> Self {
+        Self {
+            config,
+            offline: false,
+        }
+    }
+
+    /// Fake the third-party APIs to work without network access
+    pub fn offline(mut self, offline: bool) -> Self {
+        self.offline = offline;
+        self
+    }
+
+    pub async fn build(self) -> App {
+        let AppBuilder { config, offline } = self;
+
+        let db = db::Db::new(&config);
+        // Shared with the Discord services, which don't count their waits
+        let diesel_pool = db.pool().clone();
+
+        // Also used by the crawler for URLs from feeds and bookmarks
+        let http_client = outbound::HttpClient::new(&config.outbound_http)
+            .expect("HTTP client should be correctly constructed");
+
+        let clients = if offline {
+            info!("Running offline, the third-party APIs are faked");
+            clients::Clients::offline()
+        } else {
+            clients::Clients::live(&config, http_client.clone())
+        };
+
+        let tunables = config::Tunables::new();
+        config::start_reload_watcher(tunables.clone());
+
+        let discord_settings =
+            discord::settings::DiscordSettings::new(&config, tunables.clone(), diesel_pool.clone());
+        if let Err(e) = discord_settings.reload().await {
+            error!("Failed to load Discord settings, using the defaults: {e:?}");
+        }
+
+        let embedder = embedding::Embedder::new();
+        let discord_memories = match &config.vector_db {
+            Some(conf) => discord::tools::SharedVectorClient::new(
+                conf.clone(),
+                embedder.clone(),
+                http_client.clone(),
+            )
+            .await
+            .inspect(|client| info!("Storing Discord memories in {}", client.backend()))
+            .inspect_err(|e| {
+                error!("Failed to create shared vector client, defaulting to None: {e}");
+            })
+            .ok(),
+            None => None,
+        };
+
+        let app = App(Arc::new(Inner {
+            counters_ttl_cache: retainer::Cache::new(),
+            great_reads_cache: retainer::Cache::new(),
+            og_image_cache: retainer::Cache::new(),
+            load: load_shed::LoadShedder::new(config.load_limits),
+            readiness: warmup::Readiness::new(&config),
+            recommendation: recommendation::RecommendationSystem::new(),
+            now: now::NowCache::new(),
+            tunables: tunables.clone(),
+            discord_settings,
+            discord_usage: discord::usage::UsageTracker::new(&config, diesel_pool.clone()),
+            discord_archive: discord::archive::MessageArchive::new(diesel_pool.clone()),
+            discord_limiter: discord::concurrency::ExecutionLimiter::new(&config),
+            discord_moderation: discord::moderation::Moderation::new(
+                &config,
+                diesel_pool.clone(),
+                http_client.clone(),
+            ),
+            discord_transcripts: discord::transcripts::TranscriptRecorder::new(
+                &config,
+                diesel_pool.clone(),
+            ),
+            discord_status: discord::supervisor::ConnectionStatus::new(),
+            discord_feedback: discord::feedback::FeedbackStore::new(&config, diesel_pool.clone()),
+            discord_reminders: discord::reminders::Reminders::new(diesel_pool.clone()),
+            discord_summaries: discord::summaries::ChannelSummaries::new(diesel_pool.clone()),
+            discord_dms: discord::direct_messages::DirectMessages::new(
+                &config,
+                tunables,
+                diesel_pool.clone(),
+            ),
+            geoip: geoip::GeoIp::new(config.geoip.as_ref()),
+            diesel: db,
+            http: http_client.clone(),
+            clients,
+            comment_notifier: blog::notify::comment_notifier(&config, http_client),
+            comment_report_limiter: blog::report_rate_limiter(),
+            comment_scrubber: blog::CommentScrubber::new(config.comment_scrubbing.as_ref()),
+            activitypub: config.activitypub.as_ref().and_then(|conf| {
+                activitypub::ActivityPub::new(&config.site_url, conf)
+                    .inspect_err(|e| {
+                        error!("Failed to set up ActivityPub, federation is disabled: {e:?}")
+                    })
+                    .ok()
+            }),
+            embedder,
+            discord_memories,
+            config,
+        }));
+        geoip::start_reload_watcher(app.clone());
+        app
+    }
+}
+
+impl App {
+    /// Starts the background jobs. The ones that must not run twice are
+    /// coordinated with the other instances through the database.
+    pub fn start_workers(&self) {
+        recommendation::start_background_crawl(self.clone());
+        recommendation::start_discord_publisher(self.clone());
+        recommendation::start_retention_worker(self.clone());
+        recommendation::start_link_checker(self.clone());
+        status::start_probes(self.clone());
+    }
+
+    /// Connects the Discord bot and keeps it connected, restarting the client
+    /// when it stops. Fails if no token is configured or if the client stops
+    /// with an error that a restart can't fix.
+    pub async fn run_discord_bot(&self) -> Result<(), eyre::Error> {
+        use serenity::all::GatewayIntents;
+        use songbird::SerenityInit as _;
+
+        let Some(discord_token) = self.config.discord_token.clone() else {
+            self.discord_status
+                .stopped("no Discord token is configured");
+            eyre::bail!("Discord token or OpenAI API key not set in environment variables");
+        };
+
+        // GUILDS populates the cache used for permission checks of the settings commands
+        let intents = GatewayIntents::GUILDS
+            | GatewayIntents::GUILD_MESSAGES
+            | GatewayIntents::DIRECT_MESSAGES
+            | GatewayIntents::MESSAGE_CONTENT
+            | GatewayIntents::GUILD_MESSAGE_TYPING
+            | GatewayIntents::DIRECT_MESSAGE_TYPING
+            | GatewayIntents::GUILD_PRESENCES
+            | GatewayIntents::GUILD_VOICE_STATES
+            | GatewayIntents::GUILD_MESSAGE_REACTIONS
+            | GatewayIntents::DIRECT_MESSAGE_REACTIONS;
+
+        // Kept across the restarts of the client, along with the agents of
+        // the channels and the workers it started
+        let handler = Arc::new(
+            discord::DiscordEventHandler::new(
+                self.config.clone(),
+                self.discord_settings.clone(),
+                self.discord_usage.clone(),
+                self.discord_limiter.clone(),
+                self.discord_archive.clone(),
+                self.discord_moderation.clone(),
+                self.discord_transcripts.clone(),
+                self.discord_feedback.clone(),
+                self.discord_reminders.clone(),
+                self.discord_summaries.clone(),
+                self.discord_dms.clone(),
+                self.discord_memories.clone(),
+                self.clients.godbolt.clone(),
+                self.embedder.clone(),
+                self.http.clone(),
+                recommendation::FeedReader::new(self.clone()),
+                blog::lookup::BlogReader::new(self.diesel.pool().clone(), &self.config),
+                self.discord_status.clone(),
+            )
+            .await,
+        );
+
+        discord::supervisor::supervise(&self.discord_status, || async {
+            // Create a new instance of the Client, logging in as a bot. This will automatically prepend
+            // your bot token with "Bot ", which is a requirement by Discord for bot users.
+            let mut discord_client = serenity::Client::builder(&discord_token, intents)
+                .event_handler_arc(handler.clone())
+                .register_songbird_from_config(
+                    songbird::Config::default()
+                        .decode_mode(songbird::driver::DecodeMode::Decode)
+                        .decode_channels(songbird::driver::Channels::Mono)
+                        .decode_sample_rate(songbird::driver::SampleRate::Hz16000),
+                )
+                .await?;
+
+            discord_client.start().await
+        })
+        .await
+    }
+}
+
+/// All the routes with their middlewares, ready to be served with the
+/// connect info which the clients' IPs are read from. Starts sampling the
+/// database pool for the load shedding.
+pub fn build_router(app: App) -> Router {
+    load_shed::start_pool_sampler(app.clone());
+
+    let site_url = app.config.site_url.clone();
+    let cors = CorsLayer::new()
+        // allow `GET` and `POST` when accessing the resource
+        .allow_methods(vec![
+            Method::GET,
+            Method::POST,
+            Method::PUT,
+            Method::PATCH,
+            Method::DELETE,
+        ])
+        .allow_headers([CONTENT_TYPE, AUTHORIZATION])
+        .allow_credentials(true)
+        .allow_origin(AllowOrigin::predicate(move |_, request| {
+            // Saving to the history is authenticated with a token rather than
+            // the cookie, so it's open to the extensions of any origin
+            if is_history_path(request.uri.path()) {
+                return true;
+            }
+            request
+                .headers
+                .get("origin")
+                .map(|origin| {
+                    if let Ok(origin) = origin.to_str() {
+                        origin.starts_with("http://localhost:") || origin.starts_with(&site_url)
+                    } else {
+                        false
+                    }
+                })
+                .unwrap_or(false)
+        }));
+
+    let body_limits = &app.config.body_limits;
+    let api = Router::new()
+        .nest("/blog", blog::routes::route(body_limits))
+        .merge(blog::routes::admin_route())
+        .merge(short_link::admin_route())
+        .merge(activitypub::admin_route())
+        .merge(notes::route())
+        .merge(now::route())
+        .merge(status::route())
+        .merge(status::admin_route())
+        .merge(jobs::admin_route())
+        .merge(db::admin_route())
+        .merge(outbound::admin_route())
+        .nest("/public", github::routes::route())
+        .merge(identity::routes::route())
+        .route("/great-reads-feed", get(great_reads_feed::proxy_rss))
+        .route(
+            "/great-reads-highlights",
+            get(great_reads_feed::get_highlights),
+        )
+        .merge(recommendation::route())
+        .merge(recommendation::import_route(body_limits))
+        .merge(recommendation::save_route())
+        .merge(recommendation::admin_route())
+        .merge(assets::route(body_limits))
+        .merge(discord::routes::route(body_limits))
+        .merge(openapi::route());
+
+    Router::new()
+        .route("/health", get(heath))
+        .route("/ready", get(warmup::ready))
+        .merge(short_link::route())
+        .merge(blog::og_image::route())
+        .merge(activitypub::route())
+        .merge(notes::micropub_route())
+        .merge(versioning::versioned(api))
+        .layer(DefaultBodyLimit::max(body_limits.default))
+        // Inside the CORS layer so that the browsers can read the 503s
+        .layer(middleware::from_fn_with_state(
+            app.clone(),
+            load_shed::shed_load,
+        ))
+        .layer(cors)
+        // Negotiates br, zstd or gzip, event streams are left uncompressed
+        .layer(CompressionLayer::new())
+        .with_state(app.clone())
+        .layer(
+            TraceLayer::new_for_http()
+                .make_span_with(|request: &Request<_>| {
+                    // Log the matched route's path (with placeholders not filled in).
+                    // Use request.uri() or OriginalUri if you want the real path.
+                    let matched_path = request
+                        .extensions()
+                        .get::<MatchedPath>()
+                        .map(MatchedPath::as_str);
+
+                    info_span!(
+                        HTTP_REQUEST_SPAN,
+                        method = ?request.method(),
+                        matched_path,
+                    )
+                })
+                .on_response(|response: &Response, latency: Duration, _span: &Span| {
+                    debug!(
+                        time = ?latency,
+                        status = ?response.status(),
+                        path = response
+                            .extensions()
+                            .get::<MatchedPath>()
+                            .map(MatchedPath::as_str),
+                        "response",
+                    );
+                })
+                .on_failure(
+                    |_error: ServerErrorsFailureClass, _latency: Duration, _span: &Span| {
+                        // TODO when encouter an error, add it to the span so
+                        // we can log it here
+                        error!(
+                            time = ?_latency,
+                            error = ?_error,
+                            "request failed",
+                        );
+                    },
+                ),
+        )
+}
+
+/// Copy the Discord bot's memories from Chroma to Qdrant, existing memories in
+/// Qdrant with the same IDs are replaced
+pub async fn migrate_memories(config: &ServerConfig) -> Result<(), eyre::Error> {
+    use discord::tools::VectorClient;
+
+    let (Some(chromadb), Some(qdrant)) = (&config.chromadb, &config.qdrant) else {
+        eyre::bail!("both CHROMADB_URL and QDRANT_URL need to be set");
+    };
+    let default_collection = config
+        .vector_db
+        .as_ref()
+        .and_then(|c| c.default_collection.clone());
+
+    // Embeddings are copied as they are, so the model is never loaded
+    let embedder = embedding::Embedder::new();
+    let http = outbound::HttpClient::new(&config.outbound_http)?;
+    let source = VectorClient::new(
+        config::VectorDbConfig {
+            backend: config::VectorDbBackend::Chroma(chromadb.clone()),
+            default_collection: default_collection.clone(),
+        },
+        embedder.clone(),
+        http.clone(),
+    )
+    .await?;
+    let target = VectorClient::new(
+        config::VectorDbConfig {
+            backend: config::VectorDbBackend::Qdrant(qdrant.clone()),
+            default_collection,
+        },
+        embedder,
+        http,
+    )
+    .await?;
+
+    let copied = source.copy_to(&target).await?;
+    info!(copied, "Migrated memories from Chroma to Qdrant");
+
+    Ok(())
+}
+
+/// The endpoints saving pages to the history, versioned or not
+fn is_history_path(path: &str) -> bool {
+    let path = path.strip_prefix("/v1").unwrap_or(path);
+    path == "/history" || path.starts_with("/history/")
+}
+
+async fn heath(
+    #[cfg(debug_assertions)] ClientIp(ip): ClientIp,
+    Query(query): Query<HashMap<String, String>>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    #[cfg(debug_assertions)]
+    tracing::debug!(ip = %ip, "Health check request received");
+
+    if query.contains_key("fail") {
+        return Err(AppError::from(eyre::eyre!(
+            "Simulated failure for health check"
+        )));
+    }
+
+    Ok(Json(json!({
+        "status": 200,
+        "msg": "OK",
+        "detail": None::<String>,
+    })))
+}
@@ -0,0 +1,215 @@
+//! Shedding load before the server falls over. The requests in flight and the
+//! wait for a database connection are tracked, and past their limits the
+//! requests are answered with 503 and `Retry-After` right away rather than
+//! piling up. The expensive endpoints wait in line for a slot and are shed
+//! first, `/health` and `/ready` never are.
+
+use std::{
+    sync::atomic::{AtomicU64, AtomicUsize, Ordering},
+    time::{Duration, Instant},
+};
+
+use axum::{
+    extract::{Request, State},
+    http::{StatusCode, header},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use tokio::sync::Semaphore;
+
+use crate::{App, config::LoadLimits, error::AppError};
+
+const SAMPLE_INTERVAL: Duration = Duration::from_secs(1);
+/// A sample waiting longer than this counts as this long
+const MAX_SAMPLE: Duration = Duration::from_secs(5);
+/// Weight of the latest sample in the average wait for a connection
+const SAMPLE_WEIGHT: f64 = 0.3;
+/// How long the expensive requests wait in line before being shed
+const QUEUE_TIMEOUT: Duration = Duration::from_secs(2);
+const RETRY_AFTER_SECS: u64 = 5;
+
+pub struct LoadShedder {
+    limits: LoadLimits,
+    in_flight: AtomicUsize,
+    /// Moving average of the wait for a database connection
+    pool_wait_micros: AtomicU64,
+    expensive: Semaphore,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Priority {
+    /// Never shed
+    Essential,
+    Normal,
+    /// Queued, and shed at half of the limits
+    Expensive,
+}
+
+impl LoadShedder {
+    pub fn new(limits: LoadLimits) -> Self {
+        Self {
+            limits,
+            in_flight: AtomicUsize::new(0),
+            pool_wait_micros: AtomicU64::new(0),
+            expensive: Semaphore::new(limits.max_expensive),
+        }
+    }
+
+    fn pool_wait(&self) -> Duration {
+        Duration::from_micros(self.pool_wait_micros.load(Ordering::Relaxed))
+    }
+
+    fn record_pool_wait(&self, wait: Duration) {
+        let average = self.pool_wait().as_micros() as f64 * (1.0 - SAMPLE_WEIGHT)
+            + wait.as_micros() as f64 * SAMPLE_WEIGHT;
+        self.pool_wait_micros
+            .store(average as u64, Ordering::Relaxed);
+    }
+}
+
+/// Samples the wait for a database connection every second
+pub fn start_pool_sampler(ctx: App) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(SAMPLE_INTERVAL);
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+        loop {
+            interval.tick().await;
+
+            let started = Instant::now();
+            let wait = match tokio::time::timeout(MAX_SAMPLE, ctx.diesel.pool().get()).await {
+                Ok(Ok(_conn)) => started.elapsed(),
+                // The database being down isn't load, the handlers fail fast
+                Ok(Err(_)) => continue,
+                Err(_) => MAX_SAMPLE,
+            };
+            ctx.load.record_pool_wait(wait);
+        }
+    });
+}
+
+/// Sheds the request if the server is under too much pressure for its
+/// priority, and queues the expensive ones
+pub async fn shed_load(State(ctx): State<App>, request: Request, next: Next) -> Response {
+    let load = &ctx.load;
+    let priority = priority(request.uri().path());
+
+    let in_flight = load.in_flight.load(Ordering::Relaxed);
+    let pool_wait = load.pool_wait();
+    if over_limits(&load.limits, priority, in_flight, pool_wait) {
+        tracing::debug!(
+            path = request.uri().path(),
+            ?priority,
+            in_flight,
+            ?pool_wait,
+            "Shedding a request"
+        );
+        return overloaded();
+    }
+
+    let _in_flight = InFlight::enter(&load.in_flight);
+    let _permit = match priority {
+        Priority::Expensive => {
+            match tokio::time::timeout(QUEUE_TIMEOUT, load.expensive.acquire()).await {
+                Ok(Ok(permit)) => Some(permit),
+                _ => return overloaded(),
+            }
+        }
+        Priority::Essential | Priority::Normal => None,
+    };
+
+    next.run(request).await
+}
+
+/// Counts a request in flight until it's dropped, however the request ends
+struct InFlight<'a>(&'a AtomicUsize);
+
+impl<'a> InFlight<'a> {
+    fn enter(count: &'a AtomicUsize) -> Self {
+        count.fetch_add(1, Ordering::Relaxed);
+        Self(count)
+    }
+}
+
+impl Drop for InFlight<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+fn priority(path: &str) -> Priority {
+    let path = path.strip_prefix("/v1").unwrap_or(path);
+    if path == "/health" || path == "/ready" {
+        return Priority::Essential;
+    }
+
+    // Ranking the feed, embedding, summarizing with the LLM and bulk imports
+    let expensive = matches!(path, "/feed" | "/feed/digest" | "/me/import")
+        || path.starts_with("/admin/discord/analytics/")
+        || (path.starts_with("/feed/items/") && path.ends_with("/summary"))
+        || (path.starts_with("/blog/") && path.ends_with("/related"));
+    if expensive {
+        Priority::Expensive
+    } else {
+        Priority::Normal
+    }
+}
+
+fn over_limits(
+    limits: &LoadLimits,
+    priority: Priority,
+    in_flight: usize,
+    pool_wait: Duration,
+) -> bool {
+    match priority {
+        Priority::Essential => false,
+        Priority::Normal => in_flight >= limits.max_in_flight || pool_wait >= limits.max_pool_wait,
+        Priority::Expensive => {
+            in_flight >= limits.max_in_flight / 2 || pool_wait >= limits.max_pool_wait / 2
+        }
+    }
+}
+
+fn overloaded() -> Response {
+    (
+        [(header::RETRY_AFTER, RETRY_AFTER_SECS.to_string())],
+        AppError::from((
+            "The server is busy, try again shortly",
+            StatusCode::SERVICE_UNAVAILABLE,
+        )),
+    )
+        .into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_expensive_requests_are_shed_first() {
+        let limits = LoadLimits {
+            max_in_flight: 100,
+            max_expensive: 4,
+            max_pool_wait: Duration::from_secs(1),
+        };
+        let feed = priority("/v1/feed");
+        let comments = priority("/blog/hello/comments");
+        assert_eq!(feed, Priority::Expensive);
+        assert_eq!(priority("/feed/items/1/summary"), Priority::Expensive);
+        assert_eq!(comments, Priority::Normal);
+        assert_eq!(priority("/health"), Priority::Essential);
+        assert_eq!(priority("/ready"), Priority::Essential);
+
+        let calm = Duration::from_millis(10);
+        assert!(!over_limits(&limits, feed, 49, calm));
+        assert!(over_limits(&limits, feed, 50, calm));
+        assert!(!over_limits(&limits, comments, 50, calm));
+        assert!(over_limits(&limits, comments, 100, calm));
+        assert!(!over_limits(&limits, Priority::Essential, 1000, calm));
+
+        // A slow pool sheds the feed before the rest
+        let slow = Duration::from_millis(600);
+        assert!(over_limits(&limits, feed, 0, slow));
+        assert!(!over_limits(&limits, comments, 0, slow));
+    }
+}
@@ -1,64 +1,17 @@
-use axum::{
-    Json, Router,
-    extract::{MatchedPath, Query},
-    http::{Method, Request, header::CONTENT_TYPE},
-    response::Response,
-    routing::get,
-};
+use api::{AppBuilder, HTTP_REQUEST_SPAN, build_router, config};
+use clap::Parser as _;
 use config::ServerConfig;
 use dotenv::dotenv;
 use mimalloc::MiMalloc;
-use serde_json::json;
-use std::{collections::HashMap, net::SocketAddr, ops::Deref, sync::Arc, time::Duration};
-use tower_http::{
-    classify::ServerErrorsFailureClass,
-    cors::{AllowOrigin, CorsLayer},
-    trace::TraceLayer,
-};
-use tracing::{Span, debug, error, info, info_span};
+use std::net::SocketAddr;
+use tracing::{error, info};
 use tracing_subscriber::{Layer, layer::SubscriberExt, util::SubscriberInitExt};
 
-use crate::error::AppError;
-#[cfg(debug_assertions)]
-use crate::real_ip::ClientIp;
-
-mod blog;
-mod config;
-mod crypto;
-mod discord;
-mod error;
-mod github;
-mod great_reads_feed;
-mod identity;
-mod json;
-mod models;
-mod real_ip;
-mod recommendation;
-mod schema;
-mod utils;
+mod cli;
 
 #[global_allocator]
 static GLOBAL: MiMalloc = MiMalloc;
 
-#[derive(Clone)]
-pub struct App(Arc<Inner>);
-
-impl Deref for App {
-    type Target = Inner;
-    fn deref(&self) -> &Self::Target {
-        &self.0
-    }
-}
-
-pub struct Inner {
-    counters_ttl_cache: retainer::Cache<String, bool>,
-    great_reads_cache: retainer::Cache<String, Vec<u8>>,
-    recommendation: recommendation::RecommendationSystem,
-    config: ServerConfig,
-    diesel: diesel_async::pooled_connection::deadpool::Pool<diesel_async::AsyncPgConnection>,
-    http: reqwest::Client,
-}
-
 #[tokio::main]
 async fn main() {
     // temp subscriber for logging in the configuration loading phase
@@ -67,11 +20,31 @@ async fn main() {
         .compact()
         .finish();
 
+    let cli = cli::Cli::parse();
+
     dotenv().ok();
 
-    let config = tracing::subscriber::with_default(d, ServerConfig::new_from_env);
+    let (config, report) = tracing::subscriber::with_default(d, || {
+        let mut overrides = cli.overrides.clone();
+        overrides.extend(cli.mode.clone().map(|mode| ("RUN_MODE".to_string(), mode)));
+        match config::Sources::load(cli.config.as_deref(), &overrides) {
+            Ok(sources) => sources.install(),
+            Err(e) => {
+                error!("Failed to load the configuration: {e:?}");
+                std::process::exit(1);
+            }
+        }
+        let config = ServerConfig::new_from_env();
+        let report = config.validate();
+        (config, report)
+    });
+
+    // Only check the configuration, e.g. before deploying
+    if cli.check_config {
+        print!("{report}");
+        std::process::exit(if report.is_ok() { 0 } else { 1 });
+    }
 
-    const HTTP_REQUEST_SPAN: &str = "http_request";
     let (json, pretty, json_span, pretty_span) = match config.env {
         config::Env::Dev => (
             None,
@@ -143,175 +116,74 @@ async fn main() {
         .with(pretty_span)
         .init();
 
-    let postgres_url = std::env::var("DATABASE_URL").expect("DATABASE_URL is not set in .env file");
-
-    let diesel_manager = diesel_async::pooled_connection::AsyncDieselConnectionManager::<
-        diesel_async::AsyncPgConnection,
-    >::new(postgres_url);
-    // TODO consider using bb8 pool since it has more features (min_idle, max_lifetime etc.)
-    let diesel_pool = diesel_async::pooled_connection::deadpool::Pool::builder(diesel_manager)
-        .max_size(7)
-        .wait_timeout(Some(Duration::from_secs(15)))
-        .runtime(deadpool_runtime::Runtime::Tokio1)
-        .build()
-        .expect("could not build Diesel pool");
-
-    let http_client = reqwest::ClientBuilder::new()
-        .timeout(Duration::from_secs(30))
-        .build()
-        .expect("HTTP client should be correctly constructed");
-
-    let shared_state = App(Arc::new(Inner {
-        counters_ttl_cache: retainer::Cache::new(),
-        great_reads_cache: retainer::Cache::new(),
-        recommendation: recommendation::RecommendationSystem::new(),
-        config: config.clone(),
-        diesel: diesel_pool,
-        http: http_client,
-    }));
+    report.log();
+    if !report.is_ok() {
+        error!("Invalid configuration, run with --check-config for the full report");
+        std::process::exit(1);
+    }
 
-    recommendation::start_background_crawl(shared_state.clone());
+    match cli.command {
+        Some(cli::Command::MigrateMemories) => {
+            if let Err(e) = api::migrate_memories(&config).await {
+                error!("Failed to migrate memories: {e:?}");
+                std::process::exit(1);
+            }
+            return;
+        }
+        Some(cli::Command::Seed) => {
+            if let Err(e) = api::seed(&config).await {
+                error!("Failed to seed the database: {e:?}");
+                std::process::exit(1);
+            }
+            return;
+        }
+        Some(cli::Command::ReindexEmbeddings {
+            m,
+            ef_construction,
+            samples,
+            k,
+        }) => {
+            let params = api::IndexParams { m, ef_construction };
+            match api::reindex_embeddings(&config, params, samples, k).await {
+                Ok(report) => print!("{report}"),
+                Err(e) => {
+                    error!("Failed to reindex the embeddings: {e:?}");
+                    std::process::exit(1);
+                }
+            }
+            return;
+        }
+        None => {}
+    }
 
-    let site_url = config.site_url.clone();
-    let cors = CorsLayer::new()
-        // allow `GET` and `POST` when accessing the resource
-        .allow_methods(vec![
-            Method::GET,
-            Method::POST,
-            Method::PATCH,
-            Method::DELETE,
-        ])
-        .allow_headers([CONTENT_TYPE])
-        .allow_credentials(true)
-        .allow_origin(AllowOrigin::predicate(move |_, request| {
-            request
-                .headers
-                .get("origin")
-                .map(|origin| {
-                    if let Ok(origin) = origin.to_str() {
-                        origin.starts_with("http://localhost:") || origin.starts_with(&site_url)
-                    } else {
-                        false
-                    }
-                })
-                .unwrap_or(false)
-        }));
+    let mode = config.run_mode;
+    let app = AppBuilder::new(config).offline(cli.offline).build().await;
 
-    // build our application with a route
-    let app = Router::new()
-        .route("/health", get(heath))
-        .nest("/blog", blog::routes::route())
-        .nest("/public", github::routes::route())
-        .merge(identity::routes::route())
-        .route("/great-reads-feed", get(great_reads_feed::proxy_rss))
-        .route(
-            "/great-reads-highlights",
-            get(great_reads_feed::get_highlights),
-        )
-        .merge(recommendation::route())
-        .layer(cors)
-        .with_state(shared_state)
-        .layer(
-            TraceLayer::new_for_http()
-                .make_span_with(|request: &Request<_>| {
-                    // Log the matched route's path (with placeholders not filled in).
-                    // Use request.uri() or OriginalUri if you want the real path.
-                    let matched_path = request
-                        .extensions()
-                        .get::<MatchedPath>()
-                        .map(MatchedPath::as_str);
+    if mode.runs_workers() {
+        app.start_workers();
 
-                    info_span!(
-                        HTTP_REQUEST_SPAN,
-                        method = ?request.method(),
-                        matched_path,
-                    )
-                })
-                .on_response(|response: &Response, latency: Duration, _span: &Span| {
-                    debug!(
-                        time = ?latency,
-                        status = ?response.status(),
-                        path = response
-                            .extensions()
-                            .get::<MatchedPath>()
-                            .map(MatchedPath::as_str),
-                        "response",
-                    );
-                })
-                .on_failure(
-                    |_error: ServerErrorsFailureClass, _latency: Duration, _span: &Span| {
-                        // TODO when encouter an error, add it to the span so
-                        // we can log it here
-                        error!(
-                            time = ?_latency,
-                            error = ?_error,
-                            "request failed",
-                        );
-                    },
-                ),
-        );
+        let bot = app.clone();
+        tokio::spawn(async move {
+            if let Err(e) = bot.run_discord_bot().await {
+                error!("Error starting Discord service: {e:?}");
+            }
+        });
+    }
+    if !mode.serves_http() {
+        info!("Running the background jobs without the HTTP server");
+        std::future::pending::<()>().await;
+    }
 
-    tokio::spawn(async move {
-        if let Err(e) = start_discord_service(config).await {
-            error!("Error starting Discord service: {e:?}");
-        }
-    });
+    // Served right away, /ready tells the load balancer once the caches are warm
+    let warming = app.clone();
+    tokio::spawn(async move { warming.warm_caches().await });
 
     let listener = tokio::net::TcpListener::bind("0.0.0.0:3000").await.unwrap();
     info!("listening on http://0.0.0.0:3000");
     axum::serve(
         listener,
-        app.into_make_service_with_connect_info::<SocketAddr>(),
+        build_router(app).into_make_service_with_connect_info::<SocketAddr>(),
     )
     .await
     .unwrap();
 }
-
-async fn start_discord_service(config: ServerConfig) -> Result<(), eyre::Error> {
-    use serenity::all::GatewayIntents;
-
-    if let Some(discord_token) = config.discord_token.clone() {
-        let intents = GatewayIntents::GUILD_MESSAGES
-            | GatewayIntents::DIRECT_MESSAGES
-            | GatewayIntents::MESSAGE_CONTENT
-            | GatewayIntents::GUILD_MESSAGE_TYPING
-            | GatewayIntents::DIRECT_MESSAGE_TYPING
-            | GatewayIntents::GUILD_PRESENCES;
-
-        // Create a new instance of the Client, logging in as a bot. This will automatically prepend
-        // your bot token with "Bot ", which is a requirement by Discord for bot users.
-        let mut discord_client = serenity::Client::builder(&discord_token, intents)
-            .event_handler(discord::DiscordEventHandler::new(config.clone()).await)
-            .await
-            .map_err(|e| eyre::eyre!("Error creating Discord client: {e:?}"))?;
-
-        discord_client
-            .start()
-            .await
-            .map_err(|e| eyre::eyre!("Error starting Discord client: {e:?}"))?;
-
-        Ok(())
-    } else {
-        eyre::bail!("Discord token or OpenAI API key not set in environment variables");
-    }
-}
-
-async fn heath(
-    #[cfg(debug_assertions)] ClientIp(ip): ClientIp,
-    Query(query): Query<HashMap<String, String>>,
-) -> Result<Json<serde_json::Value>, AppError> {
-    #[cfg(debug_assertions)]
-    tracing::debug!(ip = %ip, "Health check request received");
-
-    if query.contains_key("fail") {
-        return Err(AppError::from(eyre::eyre!(
-            "Simulated failure for health check"
-        )));
-    }
-
-    Ok(Json(json!({
-        "status": 200,
-        "msg": "OK",
-        "detail": None::<String>,
-    })))
-}
@@ -0,0 +1,9 @@
+use diesel::prelude::*;
+
+#[derive(Insertable, AsChangeset, Debug)]
+#[diesel(table_name = crate::schema::activitypub_followers)]
+pub struct NewActivityPubFollower {
+    pub actor_id: String,
+    pub inbox: String,
+    pub shared_inbox: Option<String>,
+}
@@ -0,0 +1,37 @@
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+
+#[derive(Queryable, Selectable, Debug, Clone)]
+#[diesel(table_name = crate::schema::assets)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct Asset {
+    pub id: i32,
+    pub key: String,
+    pub filename: String,
+    pub content_type: String,
+    pub size_bytes: i64,
+    pub width: Option<i32>,
+    pub height: Option<i32>,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Insertable, Debug)]
+#[diesel(table_name = crate::schema::assets)]
+pub struct NewAsset {
+    pub key: String,
+    pub identity_id: i32,
+    pub filename: String,
+    pub content_type: String,
+    pub size_bytes: i64,
+}
+
+#[derive(Queryable, Selectable, Insertable, Debug, Clone)]
+#[diesel(table_name = crate::schema::asset_variants)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct AssetVariant {
+    pub asset_id: i32,
+    pub name: String,
+    pub key: String,
+    pub width: i32,
+    pub height: i32,
+}
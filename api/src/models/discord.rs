@@ -0,0 +1,349 @@
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+
+#[derive(Queryable, Selectable, Debug, Serialize, Clone)]
+#[diesel(table_name = crate::schema::discord_guild_settings)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct DiscordGuildSettings {
+    pub guild_id: i64,
+    pub mention_only: Option<bool>,
+    pub persona: Option<String>,
+    pub model: Option<String>,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+    pub streaming: Option<bool>,
+    pub allowed_tools: Option<Vec<String>>,
+    pub memory_retention_days: Option<i32>,
+    pub moderation_enforcement: Option<bool>,
+    pub moderation_threshold: Option<f64>,
+    pub persona_profile: Option<String>,
+}
+
+/// Also used as the changeset when upserting, `None` resets the setting to the
+/// default value
+#[derive(Insertable, AsChangeset, Debug, Deserialize, Clone)]
+#[diesel(table_name = crate::schema::discord_guild_settings)]
+#[diesel(primary_key(guild_id))]
+#[diesel(treat_none_as_null = true)]
+pub struct NewDiscordGuildSettings {
+    #[serde(skip_deserializing)]
+    pub guild_id: i64,
+    pub mention_only: Option<bool>,
+    pub persona: Option<String>,
+    pub model: Option<String>,
+    /// Stream responses by editing a placeholder message as tokens arrive
+    pub streaming: Option<bool>,
+    /// Tool names or groups (e.g. `godbolt` for all `godbolt_*` tools) the
+    /// agent may use
+    pub allowed_tools: Option<Vec<String>>,
+    /// Days after which memories decay, 0 keeps them forever
+    pub memory_retention_days: Option<i32>,
+    /// Let the agent warn and time out users and delete messages
+    pub moderation_enforcement: Option<bool>,
+    /// Classifier score from 0 to 1 from which messages are flagged
+    pub moderation_threshold: Option<f64>,
+    /// Name of the persona profile
+    pub persona_profile: Option<String>,
+}
+
+impl From<DiscordGuildSettings> for NewDiscordGuildSettings {
+    fn from(value: DiscordGuildSettings) -> Self {
+        Self {
+            guild_id: value.guild_id,
+            mention_only: value.mention_only,
+            persona: value.persona,
+            model: value.model,
+            streaming: value.streaming,
+            allowed_tools: value.allowed_tools,
+            memory_retention_days: value.memory_retention_days,
+            moderation_enforcement: value.moderation_enforcement,
+            moderation_threshold: value.moderation_threshold,
+            persona_profile: value.persona_profile,
+        }
+    }
+}
+
+#[derive(Queryable, Selectable, Debug, Serialize, Clone)]
+#[diesel(table_name = crate::schema::discord_channel_settings)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct DiscordChannelSettings {
+    pub channel_id: i64,
+    pub guild_id: Option<i64>,
+    pub enabled: bool,
+    pub mention_only: Option<bool>,
+    pub persona: Option<String>,
+    pub model: Option<String>,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+    pub streaming: Option<bool>,
+    pub allowed_tools: Option<Vec<String>>,
+    pub memory_retention_days: Option<i32>,
+    pub archive: Option<bool>,
+    pub archive_retention_days: Option<i32>,
+    pub persona_profile: Option<String>,
+    pub user_rate_limit: Option<i32>,
+    pub store_attachments: Option<bool>,
+    pub summary_schedule: Option<String>,
+}
+
+/// Also used as the changeset when upserting, `None` resets the setting to the
+/// guild or default value
+#[derive(Insertable, AsChangeset, Debug, Deserialize, Clone)]
+#[diesel(table_name = crate::schema::discord_channel_settings)]
+#[diesel(primary_key(channel_id))]
+#[diesel(treat_none_as_null = true)]
+pub struct NewDiscordChannelSettings {
+    #[serde(skip_deserializing)]
+    pub channel_id: i64,
+    pub guild_id: Option<i64>,
+    pub enabled: bool,
+    pub mention_only: Option<bool>,
+    pub persona: Option<String>,
+    pub model: Option<String>,
+    /// Stream responses by editing a placeholder message as tokens arrive
+    pub streaming: Option<bool>,
+    /// Tool names or groups (e.g. `godbolt` for all `godbolt_*` tools) the
+    /// agent may use
+    pub allowed_tools: Option<Vec<String>>,
+    /// Days after which memories decay, 0 keeps them forever
+    pub memory_retention_days: Option<i32>,
+    /// Archive the conversations for analytics
+    pub archive: Option<bool>,
+    /// Days after which archived messages are deleted, 0 keeps them forever
+    pub archive_retention_days: Option<i32>,
+    /// Name of the persona profile
+    pub persona_profile: Option<String>,
+    /// Messages a user can get answered per 10 minutes, 0 for no limit
+    pub user_rate_limit: Option<i32>,
+    /// Store the attached files in the channel memory
+    pub store_attachments: Option<bool>,
+    /// Post a summary of the channel activity, `daily` or `weekly`
+    pub summary_schedule: Option<String>,
+}
+
+impl From<DiscordChannelSettings> for NewDiscordChannelSettings {
+    fn from(value: DiscordChannelSettings) -> Self {
+        Self {
+            channel_id: value.channel_id,
+            guild_id: value.guild_id,
+            enabled: value.enabled,
+            mention_only: value.mention_only,
+            persona: value.persona,
+            model: value.model,
+            streaming: value.streaming,
+            allowed_tools: value.allowed_tools,
+            memory_retention_days: value.memory_retention_days,
+            archive: value.archive,
+            archive_retention_days: value.archive_retention_days,
+            persona_profile: value.persona_profile,
+            user_rate_limit: value.user_rate_limit,
+            store_attachments: value.store_attachments,
+            summary_schedule: value.summary_schedule,
+        }
+    }
+}
+
+#[derive(Queryable, Selectable, Debug, Serialize, Clone)]
+#[diesel(table_name = crate::schema::discord_token_usage)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct DiscordTokenUsage {
+    pub id: i32,
+    pub channel_id: i64,
+    pub day: chrono::NaiveDate,
+    pub model: String,
+    pub input_tokens: i64,
+    pub output_tokens: i64,
+    pub cached_input_tokens: i64,
+    pub requests: i32,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+}
+
+#[derive(Insertable, Debug)]
+#[diesel(table_name = crate::schema::discord_token_usage)]
+pub struct NewDiscordTokenUsage {
+    pub channel_id: i64,
+    pub day: chrono::NaiveDate,
+    pub model: String,
+    pub input_tokens: i64,
+    pub output_tokens: i64,
+    pub cached_input_tokens: i64,
+    pub requests: i32,
+}
+
+#[derive(Queryable, Selectable, Debug, Serialize, Clone)]
+#[diesel(table_name = crate::schema::discord_message_feedback)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct DiscordMessageFeedback {
+    pub id: i32,
+    pub message_id: i64,
+    pub channel_id: i64,
+    pub user_id: i64,
+    pub positive: bool,
+    pub content: String,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Insertable, Debug)]
+#[diesel(table_name = crate::schema::discord_message_feedback)]
+pub struct NewDiscordMessageFeedback {
+    pub message_id: i64,
+    pub channel_id: i64,
+    pub user_id: i64,
+    pub positive: bool,
+    pub content: String,
+}
+
+#[derive(Queryable, Selectable, Debug, Serialize, Clone)]
+#[diesel(table_name = crate::schema::discord_reminders)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct DiscordReminder {
+    pub id: i32,
+    pub channel_id: i64,
+    pub user_id: i64,
+    pub message: String,
+    pub remind_at: NaiveDateTime,
+    pub delivered_at: Option<NaiveDateTime>,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Insertable, Debug)]
+#[diesel(table_name = crate::schema::discord_reminders)]
+pub struct NewDiscordReminder {
+    pub channel_id: i64,
+    pub user_id: i64,
+    pub message: String,
+    pub remind_at: NaiveDateTime,
+}
+
+#[derive(Queryable, Selectable, Debug, Serialize, Clone)]
+#[diesel(table_name = crate::schema::discord_channel_summaries)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct DiscordChannelSummary {
+    pub id: i32,
+    pub channel_id: i64,
+    pub guild_id: Option<i64>,
+    pub schedule: String,
+    pub period_start: NaiveDateTime,
+    pub period_end: NaiveDateTime,
+    pub content: String,
+    pub message_count: i32,
+    pub message_id: Option<i64>,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Insertable, Debug)]
+#[diesel(table_name = crate::schema::discord_channel_summaries)]
+pub struct NewDiscordChannelSummary {
+    pub channel_id: i64,
+    pub guild_id: Option<i64>,
+    pub schedule: String,
+    pub period_start: NaiveDateTime,
+    pub period_end: NaiveDateTime,
+    pub content: String,
+    pub message_count: i32,
+    pub message_id: Option<i64>,
+}
+
+#[derive(Insertable, Debug)]
+#[diesel(table_name = crate::schema::discord_archived_messages)]
+pub struct NewDiscordArchivedMessage {
+    pub channel_id: i64,
+    pub thread_id: Option<i64>,
+    pub guild_id: Option<i64>,
+    pub message_id: Option<i64>,
+    pub user_id: Option<i64>,
+    /// `message`, `response` or `tool_call`
+    pub kind: String,
+    pub tool_name: Option<String>,
+    pub content: String,
+}
+
+#[derive(Queryable, Selectable, Debug, Serialize, Clone)]
+#[diesel(table_name = crate::schema::discord_moderation_actions)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct DiscordModerationAction {
+    pub id: i32,
+    pub guild_id: i64,
+    pub channel_id: i64,
+    pub user_id: Option<i64>,
+    pub message_id: Option<i64>,
+    pub action: String,
+    pub reason: String,
+    pub categories: Option<serde_json::Value>,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Insertable, Debug)]
+#[diesel(table_name = crate::schema::discord_moderation_actions)]
+pub struct NewDiscordModerationAction {
+    pub guild_id: i64,
+    pub channel_id: i64,
+    pub user_id: Option<i64>,
+    pub message_id: Option<i64>,
+    /// `flag`, `warn`, `timeout` or `delete`
+    pub action: String,
+    pub reason: String,
+    pub categories: Option<serde_json::Value>,
+}
+
+#[derive(Queryable, Selectable, Debug, Serialize, Clone)]
+#[diesel(table_name = crate::schema::discord_personas)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct DiscordPersona {
+    pub name: String,
+    pub tone: String,
+    pub language: Option<String>,
+    pub slang: String,
+    pub safety: String,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+}
+
+/// Also used as the changeset when upserting
+#[derive(Insertable, AsChangeset, Debug, Deserialize, Clone)]
+#[diesel(table_name = crate::schema::discord_personas)]
+#[diesel(primary_key(name))]
+#[diesel(treat_none_as_null = true)]
+pub struct NewDiscordPersona {
+    #[serde(skip_deserializing)]
+    pub name: String,
+    pub tone: String,
+    /// Language to always respond in, the user's language if `None`
+    pub language: Option<String>,
+    /// `none`, `light` or `heavy`
+    pub slang: String,
+    /// `strict`, `standard` or `edgy`
+    pub safety: String,
+}
+
+#[derive(Queryable, Selectable, Debug, Serialize, Clone)]
+#[diesel(table_name = crate::schema::discord_transcripts)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct DiscordTranscript {
+    pub id: String,
+    pub channel_id: i64,
+    pub guild_id: Option<i64>,
+    pub model: String,
+    pub transcript: serde_json::Value,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Insertable, Debug)]
+#[diesel(table_name = crate::schema::discord_transcripts)]
+pub struct NewDiscordTranscript {
+    pub id: String,
+    pub channel_id: i64,
+    pub guild_id: Option<i64>,
+    pub model: String,
+    pub transcript: serde_json::Value,
+}
+
+#[derive(Queryable, Selectable, Debug, Serialize, Clone)]
+#[diesel(table_name = crate::schema::discord_dm_consents)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct DiscordDmConsent {
+    pub user_id: i64,
+    pub consented_at: NaiveDateTime,
+}
@@ -1,2 +1,7 @@
+pub mod activitypub;
+pub mod asset;
 pub mod counter;
+pub mod discord;
+pub mod note;
 pub mod recommendation;
+pub mod short_link;
@@ -0,0 +1,26 @@
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+
+#[derive(Queryable, Selectable, Debug, Clone)]
+#[diesel(table_name = crate::schema::notes)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct Note {
+    pub id: i32,
+    pub kind: String,
+    pub content: Option<String>,
+    pub name: Option<String>,
+    pub url: Option<String>,
+    pub categories: Vec<String>,
+    pub published_at: NaiveDateTime,
+}
+
+#[derive(Insertable, Debug)]
+#[diesel(table_name = crate::schema::notes)]
+pub struct NewNote {
+    pub kind: String,
+    pub content: Option<String>,
+    pub name: Option<String>,
+    pub url: Option<String>,
+    pub categories: Vec<String>,
+    pub published_at: Option<NaiveDateTime>,
+}
@@ -11,6 +11,12 @@ pub struct OnlineArticleSource {
     pub name: String,
     pub base_url: Option<String>,
     pub created_at: NaiveDateTime,
+    pub enabled: bool,
+    pub crawl_interval_mins: Option<i32>,
+    pub max_items: Option<i32>,
+    pub last_crawled_at: Option<NaiveDateTime>,
+    /// Set for the sources crawled from an RSS or Atom feed
+    pub feed_url: Option<String>,
 }
 
 #[derive(Insertable, Debug)]
@@ -31,6 +37,20 @@ pub struct OnlineArticle {
     pub content_text: Option<String>,
     pub recommender_terms: Option<serde_json::Value>,
     pub created_at: NaiveDateTime,
+    pub link_status: String,
+    /// Failed checks in a row
+    pub link_failures: i32,
+    pub link_checked_at: Option<NaiveDateTime>,
+    /// Due right away if unset
+    pub link_next_check_at: Option<NaiveDateTime>,
+    /// The ETag or Last-Modified of the page when its content was compared
+    pub link_validator: Option<String>,
+    pub archive_url: Option<String>,
+    pub content_refreshed_at: Option<NaiveDateTime>,
+    /// ISO 639-3 code of the detected language
+    pub lang: Option<String>,
+    /// Embedded from the submission, only a teaser of the page could be read
+    pub paywalled: bool,
 }
 
 #[derive(Insertable, Debug)]
@@ -40,6 +60,8 @@ pub struct NewOnlineArticle {
     pub title: String,
     pub content_text: Option<String>,
     pub recommender_terms: Option<serde_json::Value>,
+    pub lang: Option<String>,
+    pub paywalled: bool,
 }
 
 #[derive(Insertable, Debug)]
@@ -60,6 +82,9 @@ pub struct UserHistory {
     pub online_article_id: i32,
     pub weight: Option<f64>,
     pub added_at: NaiveDateTime,
+    /// `None` for the default history
+    pub identity_id: Option<i32>,
+    pub source: String,
 }
 
 #[derive(Insertable, Debug)]
@@ -67,4 +92,71 @@ pub struct UserHistory {
 pub struct NewUserHistory {
     pub online_article_id: i32,
     pub weight: Option<f64>,
+    pub identity_id: Option<i32>,
+    pub source: String,
+}
+
+#[derive(Queryable, Selectable, Insertable, Debug, Clone)]
+#[diesel(table_name = crate::schema::history_imports)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct HistoryImport {
+    pub identity_id: i32,
+    pub source: String,
+    pub weight: f64,
+    pub item_count: i32,
+    pub imported_at: NaiveDateTime,
+}
+
+#[derive(Queryable, Selectable, Debug, Clone)]
+#[diesel(table_name = crate::schema::history_tokens)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct HistoryToken {
+    pub id: i32,
+    pub name: String,
+    pub last_used_at: Option<NaiveDateTime>,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Insertable, Debug)]
+#[diesel(table_name = crate::schema::history_tokens)]
+pub struct NewHistoryToken {
+    pub identity_id: i32,
+    pub name: String,
+    pub token_hash: String,
+}
+
+#[derive(Queryable, Selectable, Debug, Clone)]
+#[diesel(table_name = crate::schema::history_jobs)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct HistoryJob {
+    pub id: i32,
+    pub url: String,
+    pub status: String,
+    pub error: Option<String>,
+    pub created_at: NaiveDateTime,
+    pub finished_at: Option<NaiveDateTime>,
+}
+
+#[derive(Insertable, Debug)]
+#[diesel(table_name = crate::schema::history_jobs)]
+pub struct NewHistoryJob {
+    pub identity_id: i32,
+    pub url: String,
+    pub weight: Option<f64>,
+}
+
+#[derive(Queryable, Selectable, Debug, Clone)]
+#[diesel(table_name = crate::schema::crawl_runs)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct CrawlRun {
+    pub id: i32,
+    pub status: String,
+    pub trigger: String,
+    pub fetched: i32,
+    pub embedded: i32,
+    pub failed: i32,
+    pub failures: serde_json::Value,
+    pub started_at: NaiveDateTime,
+    pub finished_at: Option<NaiveDateTime>,
+    pub timings: serde_json::Value,
 }
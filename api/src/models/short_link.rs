@@ -0,0 +1,49 @@
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+use serde::Serialize;
+
+#[derive(Queryable, Selectable, Debug, Serialize, Clone)]
+#[diesel(table_name = crate::schema::short_links)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct ShortLink {
+    pub id: i32,
+    pub code: String,
+    pub url: String,
+    pub expires_at: Option<NaiveDateTime>,
+    pub max_clicks: Option<i32>,
+    pub click_count: i32,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Insertable, Debug)]
+#[diesel(table_name = crate::schema::short_links)]
+pub struct NewShortLink {
+    pub code: String,
+    pub url: String,
+    pub expires_at: Option<NaiveDateTime>,
+    pub max_clicks: Option<i32>,
+}
+
+#[derive(Queryable, Selectable, Debug, Serialize, Clone)]
+#[diesel(table_name = crate::schema::short_link_clicks)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct ShortLinkClick {
+    pub id: i32,
+    pub ip: String,
+    pub country: Option<String>,
+    pub asn: Option<i64>,
+    pub referrer: Option<String>,
+    pub user_agent: Option<String>,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Insertable, Debug)]
+#[diesel(table_name = crate::schema::short_link_clicks)]
+pub struct NewShortLinkClick {
+    pub short_link_id: i32,
+    pub ip: String,
+    pub country: Option<String>,
+    pub asn: Option<i64>,
+    pub referrer: Option<String>,
+    pub user_agent: Option<String>,
+}
@@ -0,0 +1,487 @@
+//! Notes, bookmarks and likes posted from a Micropub client
+//! (https://www.w3.org/TR/micropub/) and listed in a public feed
+
+use axum::{
+    Json, Router,
+    body::Bytes,
+    extract::{Path, Query, State},
+    http::{HeaderMap, StatusCode, header},
+    response::{IntoResponse, Response},
+    routing::get,
+};
+use diesel::prelude::*;
+use diesel_async::RunQueryDsl;
+use serde::Deserialize;
+use serde_json::{Value, json};
+use sha2::{Digest as _, Sha256};
+use utoipa::{IntoParams, OpenApi};
+
+use crate::{
+    App,
+    error::AppError,
+    models::note::{NewNote, Note as NoteRow},
+    schema::notes,
+    versioning::ApiVersion,
+};
+
+pub use api_models::{Note, NoteKind};
+
+const MAX_CONTENT_CHARS: usize = 10_000;
+const MAX_NAME_CHARS: usize = 200;
+const MAX_URL_CHARS: usize = 2048;
+const MAX_CATEGORIES: usize = 20;
+const MAX_CATEGORY_CHARS: usize = 50;
+const DEFAULT_LIMIT: i64 = 20;
+const MAX_LIMIT: i64 = 100;
+
+#[derive(OpenApi)]
+#[openapi(paths(list_notes, get_note))]
+pub struct ApiDoc;
+
+pub fn route() -> Router<App> {
+    Router::<App>::new()
+        .route("/notes", get(list_notes))
+        .route("/notes/{id}", get(get_note))
+}
+
+/// The Micropub endpoint, served outside of the versioned API since the
+/// clients discover it from the `rel="micropub"` link of the site
+pub fn micropub_route() -> Router<App> {
+    Router::<App>::new().route("/micropub", get(micropub_query).post(micropub_create))
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct NotesQuery {
+    /// Only the notes older than this one, to load the next page
+    before: Option<i32>,
+    limit: Option<i64>,
+}
+
+/// The notes from the newest
+#[utoipa::path(
+    get,
+    path = "/notes",
+    tag = "notes",
+    params(NotesQuery),
+    responses((status = 200, description = "The latest notes", body = Vec<Note>)),
+)]
+async fn list_notes(
+    State(ctx): State<App>,
+    Query(query): Query<NotesQuery>,
+) -> Result<Json<Vec<Note>>, AppError> {
+    let mut conn = ctx.diesel.get().await?;
+
+    let mut statement = notes::table
+        .select(NoteRow::as_select())
+        .order((notes::published_at.desc(), notes::id.desc()))
+        .limit(query.limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT))
+        .into_boxed();
+    if let Some(before) = query.before {
+        let cursor = notes::table
+            .filter(notes::id.eq(before))
+            .select(notes::published_at)
+            .first::<chrono::NaiveDateTime>(&mut conn)
+            .await
+            .optional()?
+            .ok_or(("No such note", StatusCode::NOT_FOUND))?;
+        statement = statement.filter(
+            notes::published_at
+                .lt(cursor)
+                .or(notes::published_at.eq(cursor).and(notes::id.lt(before))),
+        );
+    }
+
+    let rows = statement.load(&mut conn).await?;
+    Ok(Json(rows.into_iter().map(to_note).collect()))
+}
+
+#[utoipa::path(
+    get,
+    path = "/notes/{id}",
+    tag = "notes",
+    params(("id" = i32, Path)),
+    responses(
+        (status = 200, description = "The note", body = Note),
+        (status = 404, description = "No such note"),
+    ),
+)]
+async fn get_note(State(ctx): State<App>, Path(id): Path<i32>) -> Result<Json<Note>, AppError> {
+    let mut conn = ctx.diesel.get().await?;
+
+    let row = notes::table
+        .filter(notes::id.eq(id))
+        .select(NoteRow::as_select())
+        .first(&mut conn)
+        .await
+        .optional()?
+        .ok_or(("No such note", StatusCode::NOT_FOUND))?;
+    Ok(Json(to_note(row)))
+}
+
+/// The newest note, for the "now" page
+pub async fn latest_note(ctx: &App) -> Result<Option<Note>, AppError> {
+    let mut conn = ctx.diesel.get().await?;
+
+    let row = notes::table
+        .select(NoteRow::as_select())
+        .order((notes::published_at.desc(), notes::id.desc()))
+        .first(&mut conn)
+        .await
+        .optional()?;
+    Ok(row.map(to_note))
+}
+
+fn to_note(row: NoteRow) -> Note {
+    Note {
+        id: row.id,
+        // Only the known kinds are written
+        kind: NoteKind::parse(&row.kind).unwrap_or(NoteKind::Note),
+        content: row.content,
+        name: row.name,
+        url: row.url,
+        categories: row.categories,
+        published_at: row.published_at,
+    }
+}
+
+#[derive(Deserialize)]
+struct MicropubQuery {
+    q: Option<String>,
+}
+
+/// The configuration queries the clients make before posting
+async fn micropub_query(
+    State(ctx): State<App>,
+    headers: HeaderMap,
+    Query(query): Query<MicropubQuery>,
+) -> Result<Json<Value>, AppError> {
+    authorize(&ctx, &headers, None)?;
+
+    match query.q.as_deref() {
+        Some("config" | "syndicate-to") => Ok(Json(json!({ "syndicate-to": [] }))),
+        _ => Err(("Unsupported query", StatusCode::BAD_REQUEST).into()),
+    }
+}
+
+async fn micropub_create(
+    State(ctx): State<App>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<Response, AppError> {
+    let content_type = headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or_default();
+    let entry = if content_type.starts_with("application/json") {
+        let value = serde_json::from_slice::<Value>(&body)
+            .map_err(|_| ("Invalid JSON", StatusCode::BAD_REQUEST))?;
+        Entry::from_json(&value)
+    } else if content_type.starts_with("application/x-www-form-urlencoded") {
+        Entry::from_form(&body)
+    } else {
+        return Err((
+            "Only form and JSON requests are supported",
+            StatusCode::UNSUPPORTED_MEDIA_TYPE,
+        )
+            .into());
+    };
+
+    authorize(&ctx, &headers, entry.access_token.as_deref())?;
+
+    let new_note = entry.into_new_note()?;
+    let mut conn = ctx.diesel.get().await?;
+    let id = diesel::insert_into(notes::table)
+        .values(&new_note)
+        .returning(notes::id)
+        .get_result::<i32>(&mut conn)
+        .await?;
+
+    let location = format!(
+        "{}/api{}/notes/{id}",
+        ctx.config.site_url,
+        ApiVersion::V1.prefix()
+    );
+    Ok((StatusCode::CREATED, [(header::LOCATION, location)]).into_response())
+}
+
+/// Checks the bearer token, which the clients send either in the
+/// `Authorization` header or in the `access_token` parameter of a form
+fn authorize(ctx: &App, headers: &HeaderMap, form_token: Option<&str>) -> Result<(), AppError> {
+    let Some(expected) = &ctx.config.micropub_token else {
+        return Err(("Micropub is disabled", StatusCode::NOT_FOUND).into());
+    };
+
+    let token = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .or(form_token)
+        .ok_or(("Missing access token", StatusCode::UNAUTHORIZED))?;
+
+    // Comparing the digests so that the time taken doesn't tell how much of
+    // the token was right
+    if Sha256::digest(token.trim()) != Sha256::digest(expected.trim()) {
+        return Err(("Invalid access token", StatusCode::FORBIDDEN).into());
+    }
+    Ok(())
+}
+
+/// The properties of a create request, in either of the encodings
+#[derive(Debug, Default, PartialEq)]
+struct Entry {
+    /// The microformats type without the `h-` prefix, `entry` if omitted
+    kind: Option<String>,
+    /// Set for updates and deletions, which aren't supported
+    action: Option<String>,
+    content: Option<String>,
+    name: Option<String>,
+    categories: Vec<String>,
+    bookmark_of: Option<String>,
+    like_of: Option<String>,
+    published: Option<String>,
+    access_token: Option<String>,
+}
+
+impl Entry {
+    fn from_form(body: &[u8]) -> Self {
+        let mut entry = Entry::default();
+        for (key, value) in url::form_urlencoded::parse(body) {
+            let value = value.into_owned();
+            match key.as_ref() {
+                "h" => entry.kind = Some(value),
+                "action" => entry.action = Some(value),
+                "content" => entry.content = Some(value),
+                "name" => entry.name = Some(value),
+                "category" | "category[]" => entry.categories.push(value),
+                "bookmark-of" => entry.bookmark_of = Some(value),
+                "like-of" => entry.like_of = Some(value),
+                "published" => entry.published = Some(value),
+                "access_token" => entry.access_token = Some(value),
+                _ => {}
+            }
+        }
+        entry
+    }
+
+    fn from_json(value: &Value) -> Self {
+        let properties = &value["properties"];
+        // Values are arrays, the first one is used and it may be an object
+        // for HTML content or an embedded h-cite
+        let first = |name: &str| {
+            properties[name]
+                .as_array()
+                .and_then(|values| values.as_slice().first())
+                .and_then(|v| match v {
+                    Value::String(s) => Some(s.clone()),
+                    Value::Object(o) => o
+                        .get("html")
+                        .or_else(|| o.get("value"))
+                        .or_else(|| v.pointer("/properties/url/0"))
+                        .and_then(Value::as_str)
+                        .map(str::to_string),
+                    _ => None,
+                })
+        };
+
+        Entry {
+            kind: value["type"]
+                .as_array()
+                .and_then(|types| types.as_slice().first())
+                .and_then(Value::as_str)
+                .map(|t| t.strip_prefix("h-").unwrap_or(t).to_string()),
+            action: value["action"].as_str().map(str::to_string),
+            content: first("content"),
+            name: first("name"),
+            categories: properties["category"]
+                .as_array()
+                .map(|values| {
+                    values
+                        .iter()
+                        .filter_map(Value::as_str)
+                        .map(str::to_string)
+                        .collect()
+                })
+                .unwrap_or_default(),
+            bookmark_of: first("bookmark-of"),
+            like_of: first("like-of"),
+            published: first("published"),
+            access_token: None,
+        }
+    }
+
+    fn into_new_note(self) -> Result<NewNote, AppError> {
+        if self.action.is_some() {
+            return Err((
+                "Only creating entries is supported",
+                StatusCode::BAD_REQUEST,
+            )
+                .into());
+        }
+        if self.kind.as_deref().is_some_and(|kind| kind != "entry") {
+            return Err(("Only entries are supported", StatusCode::BAD_REQUEST).into());
+        }
+
+        let (kind, url) = match (self.like_of, self.bookmark_of) {
+            (Some(url), _) => (NoteKind::Like, Some(url)),
+            (None, Some(url)) => (NoteKind::Bookmark, Some(url)),
+            (None, None) => (NoteKind::Note, None),
+        };
+        if let Some(url) = &url {
+            validate_url(url)?;
+        }
+
+        let content = self
+            .content
+            .map(|c| c.trim().to_string())
+            .filter(|c| !c.is_empty());
+        if content.is_none() && kind == NoteKind::Note {
+            return Err(("A note must have content", StatusCode::BAD_REQUEST).into());
+        }
+        if content
+            .as_ref()
+            .is_some_and(|c| c.chars().count() > MAX_CONTENT_CHARS)
+        {
+            return Err(("The content is too long", StatusCode::BAD_REQUEST).into());
+        }
+
+        let name = self
+            .name
+            .map(|n| n.trim().to_string())
+            .filter(|n| !n.is_empty());
+        if name
+            .as_ref()
+            .is_some_and(|n| n.chars().count() > MAX_NAME_CHARS)
+        {
+            return Err(("The name is too long", StatusCode::BAD_REQUEST).into());
+        }
+
+        let mut categories = Vec::<String>::new();
+        for category in self.categories {
+            let category = category.trim().to_string();
+            if category.is_empty() || categories.contains(&category) {
+                continue;
+            }
+            if category.chars().count() > MAX_CATEGORY_CHARS {
+                return Err(("A category is too long", StatusCode::BAD_REQUEST).into());
+            }
+            categories.push(category);
+        }
+        if categories.len() > MAX_CATEGORIES {
+            return Err(("Too many categories", StatusCode::BAD_REQUEST).into());
+        }
+
+        let published_at = self
+            .published
+            .map(|p| {
+                chrono::DateTime::parse_from_rfc3339(p.trim())
+                    .map(|p| p.naive_utc())
+                    .map_err(|_| ("Invalid published date", StatusCode::BAD_REQUEST))
+            })
+            .transpose()?;
+
+        Ok(NewNote {
+            kind: kind.as_str().to_string(),
+            content,
+            name,
+            url,
+            categories,
+            published_at,
+        })
+    }
+}
+
+fn validate_url(url: &str) -> Result<(), AppError> {
+    if url.chars().count() > MAX_URL_CHARS {
+        return Err(("The URL is too long", StatusCode::BAD_REQUEST).into());
+    }
+    match url::Url::parse(url) {
+        Ok(url) if matches!(url.scheme(), "http" | "https") => Ok(()),
+        _ => Err(("The URL must be an http(s) URL", StatusCode::BAD_REQUEST).into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_form_requests() {
+        let entry = Entry::from_form(
+            b"h=entry&content=Hello+world&category[]=rust&category[]=web&access_token=abc",
+        );
+
+        assert_eq!(
+            entry,
+            Entry {
+                kind: Some("entry".to_string()),
+                content: Some("Hello world".to_string()),
+                categories: vec!["rust".to_string(), "web".to_string()],
+                access_token: Some("abc".to_string()),
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn parses_json_requests() {
+        let entry = Entry::from_json(&json!({
+            "type": ["h-entry"],
+            "properties": {
+                "content": [{ "html": "<p>Worth a read</p>" }],
+                "bookmark-of": ["https://example.com/post"],
+                "category": ["reading"],
+            },
+        }));
+
+        assert_eq!(
+            entry,
+            Entry {
+                kind: Some("entry".to_string()),
+                content: Some("<p>Worth a read</p>".to_string()),
+                categories: vec!["reading".to_string()],
+                bookmark_of: Some("https://example.com/post".to_string()),
+                ..Default::default()
+            }
+        );
+    }
+
+    fn valid(body: &[u8]) -> NewNote {
+        match Entry::from_form(body).into_new_note() {
+            Ok(note) => note,
+            Err(_) => panic!("{} is invalid", String::from_utf8_lossy(body)),
+        }
+    }
+
+    #[test]
+    fn kind_follows_the_properties() {
+        let note = valid(b"h=entry&like-of=https%3A%2F%2Fexample.com&content=Nice");
+        assert_eq!(note.kind, "like");
+        assert_eq!(note.url.as_deref(), Some("https://example.com"));
+
+        let note = valid(b"h=entry&bookmark-of=https%3A%2F%2Fexample.com");
+        assert_eq!(note.kind, "bookmark");
+        assert_eq!(note.content, None);
+
+        let note = valid(b"content=Hi&category=a&category=a&category=+");
+        assert_eq!(note.kind, "note");
+        assert_eq!(note.categories, vec!["a".to_string()]);
+    }
+
+    #[test]
+    fn invalid_entries_are_rejected() {
+        for body in [
+            &b"h=entry"[..],
+            b"h=event&content=Hi",
+            b"action=delete&url=https%3A%2F%2Fwrx.sh",
+            b"like-of=javascript%3Aalert(1)",
+            b"content=Hi&published=yesterday",
+        ] {
+            assert!(
+                Entry::from_form(body).into_new_note().is_err(),
+                "{}",
+                String::from_utf8_lossy(body)
+            );
+        }
+    }
+}
@@ -0,0 +1,171 @@
+//! What the owner is up to lately, for the "now" page: the Spotify playback,
+//! the Steam games, the latest Strava activity, commit, highlight and note in
+//! one response.
+//! The sources are fetched concurrently and cached on their own, one failing
+//! or slow source leaves only its part out.
+
+use std::{fmt::Display, time::Duration};
+
+use axum::{Json, Router, extract::State, http::header, response::IntoResponse, routing::get};
+use diesel::prelude::*;
+use diesel_async::RunQueryDsl;
+use tokio::{sync::RwLock, time::Instant};
+use utoipa::OpenApi;
+
+use crate::{
+    App, error::AppError, great_reads_feed, identity, notes, schema::identity_credentials,
+};
+
+use api_models::{Now, NowCommit};
+
+/// A source slower than this is left out of the response
+const SOURCE_TIMEOUT: Duration = Duration::from_secs(5);
+/// The public GitHub API allows 60 requests an hour without a token
+const COMMIT_TTL: Duration = Duration::from_secs(10 * 60);
+const NOTE_TTL: Duration = Duration::from_secs(60);
+
+#[derive(OpenApi)]
+#[openapi(paths(get_now))]
+pub struct ApiDoc;
+
+pub fn route() -> Router<App> {
+    Router::<App>::new().route("/now", get(get_now))
+}
+
+/// The sources without a cache of their own, Spotify, Steam, Strava and the
+/// highlights have one
+pub struct NowCache {
+    commit: Cached<Option<NowCommit>>,
+    note: Cached<Option<api_models::Note>>,
+}
+
+impl NowCache {
+    pub fn new() -> Self {
+        Self {
+            commit: Cached::new(COMMIT_TTL),
+            note: Cached::new(NOTE_TTL),
+        }
+    }
+}
+
+impl Default for NowCache {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The last value of a source and when it was fetched
+struct Cached<T> {
+    ttl: Duration,
+    value: RwLock<Option<(Instant, T)>>,
+}
+
+impl<T: Clone> Cached<T> {
+    fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            value: RwLock::new(None),
+        }
+    }
+
+    async fn get_or_fetch<E>(&self, fetch: impl Future<Output = Result<T, E>>) -> Result<T, E> {
+        if let Some((fetched_at, value)) = self.value.read().await.as_ref()
+            && fetched_at.elapsed() < self.ttl
+        {
+            return Ok(value.clone());
+        }
+
+        let value = fetch.await?;
+        *self.value.write().await = Some((Instant::now(), value.clone()));
+        Ok(value)
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/now",
+    tag = "now",
+    responses((status = 200, description = "What the owner is up to lately", body = Now)),
+)]
+async fn get_now(State(ctx): State<App>) -> impl IntoResponse {
+    let (playing, gaming, activity, commit, highlight, note) = tokio::join!(
+        source("spotify", identity::now_playing(&ctx)),
+        source("steam", identity::now_gaming(&ctx)),
+        source("strava", identity::latest_activity(&ctx)),
+        source("github", ctx.now.commit.get_or_fetch(latest_commit(&ctx))),
+        source("highlight", great_reads_feed::latest_highlight(&ctx)),
+        source("note", ctx.now.note.get_or_fetch(notes::latest_note(&ctx))),
+    );
+
+    let mut unavailable = Vec::new();
+    let now = Now {
+        playing: available(playing, &mut unavailable),
+        commit: available(commit, &mut unavailable),
+        gaming: available(gaming, &mut unavailable),
+        activity: available(activity, &mut unavailable),
+        highlight: available(highlight, &mut unavailable),
+        note: available(note, &mut unavailable),
+        unavailable,
+    };
+
+    ([(header::CACHE_CONTROL, "public, max-age=30")], Json(now))
+}
+
+/// The value of the source, its name if it failed or timed out
+async fn source<T, E: Display>(
+    name: &'static str,
+    fetch: impl Future<Output = Result<T, E>>,
+) -> Result<T, &'static str> {
+    match tokio::time::timeout(SOURCE_TIMEOUT, fetch).await {
+        Ok(Ok(value)) => Ok(value),
+        Ok(Err(err)) => {
+            tracing::warn!(source = name, %err, "Failed to fetch a source of the now page");
+            Err(name)
+        }
+        Err(_) => {
+            tracing::warn!(source = name, "A source of the now page timed out");
+            Err(name)
+        }
+    }
+}
+
+fn available<T>(
+    source: Result<Option<T>, &'static str>,
+    unavailable: &mut Vec<String>,
+) -> Option<T> {
+    source.unwrap_or_else(|name| {
+        unavailable.push(name.to_string());
+        None
+    })
+}
+
+/// The latest push of the owner's GitHub account, the one they log in with
+async fn latest_commit(ctx: &App) -> Result<Option<NowCommit>, AppError> {
+    let mut conn = ctx.diesel.get().await?;
+    let user_id = identity_credentials::table
+        .filter(identity_credentials::identity_id.eq(ctx.config.owner_identity_id))
+        .filter(
+            identity_credentials::credential.contains(serde_json::json!({
+                "provider": "github"
+            })),
+        )
+        .select(identity_credentials::credential.retrieve_as_text("user_id"))
+        .first::<Option<String>>(&mut conn)
+        .await
+        .optional()?
+        .flatten()
+        .and_then(|id| id.parse::<i64>().ok());
+    drop(conn);
+    let Some(user_id) = user_id else {
+        return Ok(None);
+    };
+
+    let push = ctx.clients.github.latest_push(user_id).await?;
+    Ok(push.map(|push| NowCommit {
+        url: format!("https://github.com/{}/commit/{}", push.repo, push.sha),
+        repo: push.repo,
+        sha: push.sha,
+        message: push.message,
+        pushed_at: push.pushed_at.naive_utc(),
+    }))
+}
@@ -0,0 +1,164 @@
+use axum::{
+    Json, Router,
+    extract::State,
+    response::{Html, IntoResponse},
+    routing::get,
+};
+use utoipa::{
+    Modify, OpenApi,
+    openapi::{
+        self, Info,
+        security::{ApiKey, ApiKeyValue, Http, HttpAuthScheme, SecurityScheme},
+        server::Server,
+    },
+};
+
+use crate::{
+    App, assets, blog,
+    config::Env,
+    error::AppError,
+    great_reads_feed,
+    identity::{self, COOKIE_NAME, MaybeAuthUser},
+    notes, now, recommendation, status,
+    versioning::ApiVersion,
+};
+
+/// Swagger UI loading the spec next to it, the assets come from a CDN so
+/// that they aren't bundled in the binary
+const SWAGGER_UI: &str = r##"<!doctype html>
+<html lang="en">
+  <head>
+    <meta charset="utf-8" />
+    <title>API docs</title>
+    <link rel="stylesheet" href="https://unpkg.com/swagger-ui-dist@5/swagger-ui.css" />
+  </head>
+  <body>
+    <div id="swagger-ui"></div>
+    <script src="https://unpkg.com/swagger-ui-dist@5/swagger-ui-bundle.js" crossorigin></script>
+    <script>
+      window.ui = SwaggerUIBundle({ url: "openapi.json", dom_id: "#swagger-ui" });
+    </script>
+  </body>
+</html>
+"##;
+
+/// The session cookie the authenticated endpoints require, and the tokens
+/// that save pages to the history
+struct SessionCookie;
+
+impl Modify for SessionCookie {
+    fn modify(&self, openapi: &mut openapi::OpenApi) {
+        openapi
+            .components
+            .get_or_insert_with(Default::default)
+            .add_security_scheme(
+                "session",
+                SecurityScheme::ApiKey(ApiKey::Cookie(ApiKeyValue::new(COOKIE_NAME))),
+            );
+        openapi
+            .components
+            .get_or_insert_with(Default::default)
+            .add_security_scheme(
+                "history_token",
+                SecurityScheme::Http(Http::new(HttpAuthScheme::Bearer)),
+            );
+    }
+}
+
+#[derive(OpenApi)]
+#[openapi(modifiers(&SessionCookie))]
+struct ApiDoc;
+
+/// The spec of the endpoints, with the paths relative to the version prefix
+pub fn spec() -> openapi::OpenApi {
+    let mut spec = ApiDoc::openapi()
+        .nest("/blog", blog::routes::ApiDoc::openapi())
+        .merge_from(identity::routes::ApiDoc::openapi())
+        .merge_from(recommendation::ApiDoc::openapi())
+        .merge_from(great_reads_feed::ApiDoc::openapi())
+        .merge_from(status::ApiDoc::openapi())
+        .merge_from(notes::ApiDoc::openapi())
+        .merge_from(now::ApiDoc::openapi())
+        .merge_from(assets::ApiDoc::openapi());
+    spec.info = Info::new("wonrax.com API", env!("CARGO_PKG_VERSION"));
+    spec
+}
+
+pub fn route() -> Router<App> {
+    Router::<App>::new()
+        .route("/openapi.json", get(get_spec))
+        .route("/docs", get(get_docs))
+}
+
+/// The docs are public in development, only the site owner can see them in
+/// production
+fn ensure_allowed(ctx: &App, auth_user: MaybeAuthUser) -> Result<(), AppError> {
+    if !matches!(ctx.config.env, Env::Production) {
+        return Ok(());
+    }
+
+    if auth_user.0?.id != ctx.config.owner_identity_id {
+        return Err(("Not permitted", axum::http::StatusCode::FORBIDDEN).into());
+    }
+    Ok(())
+}
+
+async fn get_spec(
+    State(ctx): State<App>,
+    version: ApiVersion,
+    auth_user: MaybeAuthUser,
+) -> Result<impl IntoResponse, AppError> {
+    ensure_allowed(&ctx, auth_user)?;
+
+    let mut spec = spec();
+    spec.servers = Some(vec![Server::new(version.prefix())]);
+    Ok(Json(spec))
+}
+
+async fn get_docs(
+    State(ctx): State<App>,
+    auth_user: MaybeAuthUser,
+) -> Result<impl IntoResponse, AppError> {
+    ensure_allowed(&ctx, auth_user)?;
+
+    Ok(Html(SWAGGER_UI))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn spec_covers_the_documented_routes() {
+        let spec = spec();
+
+        for path in [
+            "/blog/{slug}/comments",
+            "/blog/{slug}/comments/{id}",
+            "/me",
+            "/feed",
+            "/feed/digest",
+            "/feed/items/{id}",
+            "/feed/items/{id}/redirect",
+            "/feed/items/{id}/summary",
+            "/feed/shared/{token}",
+            "/feed/sources.opml",
+            "/me/import/{source}",
+            "/history",
+            "/me/history-tokens",
+            "/me/history/{article_id}/negative",
+            "/great-reads-highlights",
+            "/status",
+            "/assets/{id}",
+            "/notes",
+            "/now",
+            "/recent-activities",
+        ] {
+            assert!(spec.paths.paths.contains_key(path), "missing {path}");
+        }
+
+        let schemas = spec.components.expect("components").schemas;
+        assert!(schemas.contains_key("CommentTree"));
+        assert!(schemas.contains_key("FeedItem"));
+    }
+}
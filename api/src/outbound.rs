@@ -0,0 +1,334 @@
+//! The client of the requests to other servers. The timeout, retries and user
+//! agent of each request are those of its host's policy in `HTTP_HOST_POLICIES`
+//! or the defaults, and the requests are logged and counted per host for
+//! `/admin/outbound`. The requests to URLs that users control go through
+//! [HttpClient::public_only], which only connects to public addresses, see
+//! [ssrf]. The configured endpoints may be private, e.g. MinIO.
+
+use std::{
+    collections::HashMap,
+    fmt,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use axum::{Json, Router, extract::State, http::StatusCode, routing::get};
+use reqwest::{
+    Method, Response,
+    header::{HeaderName, HeaderValue, USER_AGENT},
+};
+use serde::Serialize;
+
+use crate::{
+    App,
+    config::{HostPolicy, OutboundHttpConfig},
+    error::AppError,
+    identity::AuthUser,
+    ssrf,
+};
+
+/// Doubled after each retry
+const RETRY_BASE_DELAY: Duration = Duration::from_millis(250);
+
+pub fn admin_route() -> Router<App> {
+    Router::<App>::new().route("/admin/outbound", get(get_host_stats))
+}
+
+/// Cheap to clone, the clones share the connection pool and the counters
+#[derive(Clone)]
+pub struct HttpClient {
+    client: reqwest::Client,
+    /// The client of [HttpClient::public_only]
+    public_client: reqwest::Client,
+    config: Arc<OutboundHttpConfig>,
+    stats: Arc<Mutex<HashMap<String, Counters>>>,
+}
+
+impl HttpClient {
+    pub fn new(config: &OutboundHttpConfig) -> Result<Self, reqwest::Error> {
+        let configure = |builder: reqwest::ClientBuilder| {
+            builder
+                .user_agent(config.user_agent.clone())
+                .connect_timeout(config.connect_timeout)
+                .pool_max_idle_per_host(config.pool_max_idle_per_host)
+                .pool_idle_timeout(config.pool_idle_timeout)
+                .build()
+        };
+
+        Ok(Self {
+            client: configure(reqwest::Client::builder())?,
+            public_client: configure(ssrf::client_builder())?,
+            config: Arc::new(config.clone()),
+            stats: Arc::default(),
+        })
+    }
+
+    /// The same client only connecting to public addresses, for the URLs that
+    /// users control
+    pub fn public_only(&self) -> Self {
+        Self {
+            client: self.public_client.clone(),
+            ..self.clone()
+        }
+    }
+
+    pub fn get(&self, url: impl reqwest::IntoUrl) -> RequestBuilder {
+        self.request(Method::GET, url)
+    }
+
+    pub fn head(&self, url: impl reqwest::IntoUrl) -> RequestBuilder {
+        self.request(Method::HEAD, url)
+    }
+
+    pub fn post(&self, url: impl reqwest::IntoUrl) -> RequestBuilder {
+        self.request(Method::POST, url)
+    }
+
+    pub fn request(&self, method: Method, url: impl reqwest::IntoUrl) -> RequestBuilder {
+        RequestBuilder {
+            client: self.clone(),
+            inner: self.client.request(method, url),
+        }
+    }
+
+    /// The policy of the host or of its closest parent domain
+    fn policy(&self, host: &str) -> Option<&HostPolicy> {
+        policy_of(&self.config.hosts, host)
+    }
+
+    async fn execute(&self, mut request: reqwest::Request) -> reqwest::Result<Response> {
+        let host = request.url().host_str().unwrap_or_default().to_string();
+        let policy = self.policy(&host);
+
+        // Unless the caller set them
+        if request.timeout().is_none() {
+            *request.timeout_mut() = Some(
+                policy
+                    .and_then(|p| p.timeout)
+                    .unwrap_or(self.config.timeout),
+            );
+        }
+        if let Some(user_agent) = policy.and_then(|p| p.user_agent.as_deref())
+            && !request.headers().contains_key(USER_AGENT)
+            && let Ok(user_agent) = HeaderValue::from_str(user_agent)
+        {
+            request.headers_mut().insert(USER_AGENT, user_agent);
+        }
+
+        // Retrying a POST could do it twice
+        let retries = if request.method().is_idempotent() {
+            policy
+                .and_then(|p| p.retries)
+                .unwrap_or(self.config.retries)
+        } else {
+            0
+        };
+
+        let method = request.method().clone();
+        let mut attempt = 0;
+        loop {
+            // Bodies that are streamed can't be sent again
+            let retry = if attempt < retries {
+                request.try_clone()
+            } else {
+                None
+            };
+
+            let started = Instant::now();
+            let result = self.client.execute(request).await;
+            let elapsed = started.elapsed();
+
+            let retryable = match &result {
+                Ok(response) => {
+                    let status = response.status();
+                    tracing::debug!(%method, %host, %status, elapsed_ms = elapsed.as_millis(), "Outbound request");
+                    status == reqwest::StatusCode::TOO_MANY_REQUESTS || status.is_server_error()
+                }
+                Err(error) => {
+                    tracing::debug!(%method, %host, %error, elapsed_ms = elapsed.as_millis(), "Outbound request failed");
+                    error.is_timeout() || error.is_connect()
+                }
+            };
+            self.record(&host, &result, elapsed, retryable && retry.is_some());
+
+            match retry {
+                Some(next) if retryable => {
+                    let delay = RETRY_BASE_DELAY * 2u32.pow(attempt);
+                    tracing::warn!(%method, %host, attempt, ?delay, "Retrying outbound request");
+                    tokio::time::sleep(delay).await;
+                    request = next;
+                    attempt += 1;
+                }
+                _ => return result,
+            }
+        }
+    }
+
+    fn record(
+        &self,
+        host: &str,
+        result: &reqwest::Result<Response>,
+        elapsed: Duration,
+        retried: bool,
+    ) {
+        let mut stats = self.stats.lock().unwrap_or_else(|e| e.into_inner());
+        let counters = stats.entry(host.to_string()).or_default();
+        counters.requests += 1;
+        counters.total_ms += elapsed.as_millis() as u64;
+        counters.max_ms = counters.max_ms.max(elapsed.as_millis() as u64);
+        match result {
+            Ok(response) if response.status().is_server_error() => counters.server_errors += 1,
+            Ok(_) => {}
+            Err(e) if e.is_timeout() => counters.timeouts += 1,
+            Err(_) => counters.failures += 1,
+        }
+        if retried {
+            counters.retries += 1;
+        }
+    }
+}
+
+// The client's config is long and not interesting in the logs
+impl fmt::Debug for HttpClient {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("HttpClient").finish_non_exhaustive()
+    }
+}
+
+/// A request sent with the policy of its host, built like a reqwest one
+pub struct RequestBuilder {
+    client: HttpClient,
+    inner: reqwest::RequestBuilder,
+}
+
+impl RequestBuilder {
+    pub fn header<K, V>(mut self, key: K, value: V) -> Self
+    where
+        HeaderName: TryFrom<K>,
+        <HeaderName as TryFrom<K>>::Error: Into<axum::http::Error>,
+        HeaderValue: TryFrom<V>,
+        <HeaderValue as TryFrom<V>>::Error: Into<axum::http::Error>,
+    {
+        self.inner = self.inner.header(key, value);
+        self
+    }
+
+    pub fn bearer_auth(mut self, token: impl fmt::Display) -> Self {
+        self.inner = self.inner.bearer_auth(token);
+        self
+    }
+
+    pub fn json<T: Serialize + ?Sized>(mut self, json: &T) -> Self {
+        self.inner = self.inner.json(json);
+        self
+    }
+
+    pub fn body(mut self, body: impl Into<reqwest::Body>) -> Self {
+        self.inner = self.inner.body(body);
+        self
+    }
+
+    pub fn multipart(mut self, form: reqwest::multipart::Form) -> Self {
+        self.inner = self.inner.multipart(form);
+        self
+    }
+
+    /// Instead of the one of the host's policy
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.inner = self.inner.timeout(timeout);
+        self
+    }
+
+    pub async fn send(self) -> reqwest::Result<Response> {
+        let request = self.inner.build()?;
+        self.client.execute(request).await
+    }
+}
+
+fn policy_of<'a>(policies: &'a [HostPolicy], host: &str) -> Option<&'a HostPolicy> {
+    policies
+        .iter()
+        .filter(|p| {
+            host.eq_ignore_ascii_case(&p.host)
+                || host.to_ascii_lowercase().ends_with(&format!(".{}", p.host))
+        })
+        .max_by_key(|p| p.host.len())
+}
+
+#[derive(Default)]
+struct Counters {
+    requests: u64,
+    retries: u64,
+    timeouts: u64,
+    /// Connection and other errors without a response
+    failures: u64,
+    server_errors: u64,
+    total_ms: u64,
+    max_ms: u64,
+}
+
+#[derive(Serialize)]
+struct HostStats {
+    host: String,
+    /// Counting each retry
+    requests: u64,
+    retries: u64,
+    timeouts: u64,
+    failures: u64,
+    server_errors: u64,
+    average_ms: f64,
+    max_ms: u64,
+}
+
+/// The requests to each host since the start, the busiest first
+async fn get_host_stats(
+    State(ctx): State<App>,
+    AuthUser(i): AuthUser,
+) -> Result<Json<Vec<HostStats>>, AppError> {
+    if i.id != ctx.config.owner_identity_id {
+        return Err(("Not permitted", StatusCode::FORBIDDEN).into());
+    }
+
+    let mut hosts: Vec<HostStats> = ctx
+        .http
+        .stats
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .iter()
+        .map(|(host, c)| HostStats {
+            host: host.clone(),
+            requests: c.requests,
+            retries: c.retries,
+            timeouts: c.timeouts,
+            failures: c.failures,
+            server_errors: c.server_errors,
+            average_ms: c.total_ms as f64 / c.requests.max(1) as f64,
+            max_ms: c.max_ms,
+        })
+        .collect();
+    hosts.sort_by_key(|h| std::cmp::Reverse(h.requests));
+
+    Ok(Json(hosts))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_closest_domain_policy_applies() {
+        let policy = |host: &str| HostPolicy {
+            host: host.to_string(),
+            timeout: None,
+            retries: None,
+            user_agent: None,
+        };
+        let policies = [policy("example.com"), policy("api.example.com")];
+
+        let host_of = |host| policy_of(&policies, host).map(|p| p.host.as_str());
+        assert_eq!(host_of("example.com"), Some("example.com"));
+        assert_eq!(host_of("www.Example.com"), Some("example.com"));
+        assert_eq!(host_of("v1.api.example.com"), Some("api.example.com"));
+        assert_eq!(host_of("notexample.com"), None);
+    }
+}
@@ -0,0 +1,73 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    hash::Hash,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+/// Allows `limit` events per key within a sliding window, kept in memory so
+/// the counts reset on restart
+pub struct RateLimiter<K> {
+    limit: usize,
+    window: Duration,
+    recent: Mutex<HashMap<K, VecDeque<Instant>>>,
+}
+
+impl<K: Eq + Hash> RateLimiter<K> {
+    pub fn new(limit: usize, window: Duration) -> Self {
+        Self {
+            limit,
+            window,
+            recent: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Records an event of the key, `false` if it's over the limit in which
+    /// case the event isn't recorded
+    pub fn check(&self, key: K) -> bool {
+        self.check_at(key, Instant::now())
+    }
+
+    fn check_at(&self, key: K, now: Instant) -> bool {
+        let Ok(mut recent) = self.recent.lock() else {
+            return true;
+        };
+
+        // Forget the keys without recent events so that the map doesn't grow
+        // with every key ever seen
+        recent.retain(|_, times| {
+            while times
+                .front()
+                .is_some_and(|t| now.duration_since(*t) >= self.window)
+            {
+                times.pop_front();
+            }
+            !times.is_empty()
+        });
+
+        let times = recent.entry(key).or_default();
+        if times.len() >= self.limit {
+            return false;
+        }
+        times.push_back(now);
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn limits_each_key_within_the_window() {
+        let limiter = RateLimiter::new(2, Duration::from_secs(60));
+        let start = Instant::now();
+
+        assert!(limiter.check_at("a", start));
+        assert!(limiter.check_at("a", start + Duration::from_secs(10)));
+        assert!(!limiter.check_at("a", start + Duration::from_secs(20)));
+        assert!(limiter.check_at("b", start + Duration::from_secs(20)));
+        // The first event left the window
+        assert!(limiter.check_at("a", start + Duration::from_secs(60)));
+    }
+}
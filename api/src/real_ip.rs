@@ -6,14 +6,14 @@ use ipnetwork::IpNetwork;
 use std::net::{IpAddr, SocketAddr};
 use tokio::sync::OnceCell;
 
-use crate::{App, error::AppError};
+use crate::{App, error::AppError, geoip::GeoInfo, outbound::HttpClient};
 
 static CLOUDFLARE_PREFIXES: OnceCell<Vec<IpNetwork>> = OnceCell::const_new();
 
-async fn load_cloudflare_prefixes() -> Vec<IpNetwork> {
+async fn load_cloudflare_prefixes(http: &HttpClient) -> Vec<IpNetwork> {
     // Fetch Cloudflare IPv4 and IPv6 prefix lists and parse them
-    async fn fetch_list(url: &str) -> Vec<IpNetwork> {
-        match reqwest::get(url).await {
+    async fn fetch_list(http: &HttpClient, url: &str) -> Vec<IpNetwork> {
+        match http.get(url).send().await {
             Ok(resp) => {
                 // Accept text/plain with any charset
                 if let Some(ct) = resp.headers().get(reqwest::header::CONTENT_TYPE)
@@ -51,21 +51,21 @@ async fn load_cloudflare_prefixes() -> Vec<IpNetwork> {
     }
 
     let (v4, v6) = tokio::join!(
-        fetch_list("https://www.cloudflare.com/ips-v4"),
-        fetch_list("https://www.cloudflare.com/ips-v6"),
+        fetch_list(http, "https://www.cloudflare.com/ips-v4"),
+        fetch_list(http, "https://www.cloudflare.com/ips-v6"),
     );
 
     v4.into_iter().chain(v6).collect()
 }
 
-async fn get_cloudflare_prefixes() -> &'static Vec<IpNetwork> {
+async fn get_cloudflare_prefixes(http: &HttpClient) -> &'static Vec<IpNetwork> {
     CLOUDFLARE_PREFIXES
-        .get_or_init(|| async { load_cloudflare_prefixes().await })
+        .get_or_init(|| async { load_cloudflare_prefixes(http).await })
         .await
 }
 
-async fn is_cloudflare_ip(ip: &IpAddr) -> bool {
-    get_cloudflare_prefixes()
+async fn is_cloudflare_ip(http: &HttpClient, ip: &IpAddr) -> bool {
+    get_cloudflare_prefixes(http)
         .await
         .iter()
         .any(|trusted_proxy| trusted_proxy.contains(*ip))
@@ -73,10 +73,18 @@ async fn is_cloudflare_ip(ip: &IpAddr) -> bool {
 
 pub struct ClientIp(pub IpAddr);
 
+impl ClientIp {
+    /// Country and ASN of the client, `None` if GeoIP is not configured or the
+    /// address is not in the databases
+    pub fn geo(&self, ctx: &App) -> Option<GeoInfo> {
+        ctx.geoip.lookup(self.0)
+    }
+}
+
 impl axum::extract::FromRequestParts<App> for ClientIp {
     type Rejection = AppError;
 
-    async fn from_request_parts(parts: &mut Parts, _state: &App) -> Result<Self, Self::Rejection> {
+    async fn from_request_parts(parts: &mut Parts, state: &App) -> Result<Self, Self::Rejection> {
         // Prefer Cloudflare headers first
         let cf_connecting_ip = parts
             .headers
@@ -125,7 +133,7 @@ impl axum::extract::FromRequestParts<App> for ClientIp {
         let nearest_proxy_ip = nearest_proxy_ip_from_xff.or(Some(socket_ip));
 
         if let Some(npi) = nearest_proxy_ip
-            && is_cloudflare_ip(&npi).await
+            && is_cloudflare_ip(&state.http, &npi).await
             && let Some(ip) = cf_connecting_ip.or(true_client_ip).or(client_ip_from_xff)
         {
             return Ok(ClientIp(ip));
@@ -149,9 +157,13 @@ impl axum::extract::FromRequestParts<App> for ClientIp {
 mod tests {
     use super::*;
 
+    fn http() -> HttpClient {
+        HttpClient::new(&Default::default()).expect("HTTP client should be correctly constructed")
+    }
+
     #[tokio::test]
     async fn parse_cloudflare_prefixes_handles_plain_text() {
-        let prefixes = load_cloudflare_prefixes().await; // real fetch; acceptable for smoke test
+        let prefixes = load_cloudflare_prefixes(&http()).await; // real fetch; acceptable for smoke test
         assert!(!prefixes.is_empty());
         // Ensure they look like CIDRs
         assert!(prefixes.iter().all(|p| match p {
@@ -165,6 +177,6 @@ mod tests {
         // Simulate a local address that is certainly not in Cloudflare
         let local = IpAddr::V4(std::net::Ipv4Addr::new(10, 0, 0, 1));
         // we cannot access FromRequestParts directly here; just test predicate
-        assert!(!is_cloudflare_ip(&local).await);
+        assert!(!is_cloudflare_ip(&http(), &local).await);
     }
 }
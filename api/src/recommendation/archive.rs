@@ -0,0 +1,919 @@
+//! The articles with their chunks, metadata and summaries, and the readers'
+//! history in one archive, so that moving to another server doesn't need
+//! crawling and embedding everything again. The archive is JSON lines, a
+//! header first and then the rows in the order they reference each other.
+//! The embeddings are the quantized bits packed into bytes, in base64.
+
+use std::collections::{HashMap, HashSet, hash_map::Entry};
+
+use axum::{
+    Json, Router,
+    body::{Body, Bytes},
+    extract::{DefaultBodyLimit, State},
+    http::{StatusCode, header},
+    response::IntoResponse,
+    routing::get,
+};
+use base64::{Engine as _, engine::general_purpose::STANDARD};
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+use diesel::sql_types::{Array, Integer, Text, Timestamp};
+use diesel_async::{AsyncConnection as _, AsyncPgConnection, RunQueryDsl};
+use futures::{Stream, StreamExt as _};
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
+
+use crate::{
+    App,
+    error::AppError,
+    identity::AuthUser,
+    schema::{
+        identity_credentials, online_article_metadata, online_article_sources,
+        online_article_summaries, online_articles, user_history,
+    },
+    utils::RECOMMENDER_EMBEDDING_BITS,
+};
+
+const ARCHIVE_VERSION: u32 = 2;
+/// Rows read or inserted at once, well under the bind parameters limit
+const BATCH_SIZE: usize = 500;
+/// The export is sent in pieces of about this size
+const FLUSH_BYTES: usize = 256 * 1024;
+
+pub fn admin_route() -> Router<App> {
+    Router::<App>::new().route(
+        "/admin/recommendation/archive",
+        get(export_archive)
+            // As large as the articles, and only the owner may upload it
+            .post(import_archive)
+            .layer(DefaultBodyLimit::disable()),
+    )
+}
+
+/// A line of the archive
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum Record {
+    Header(ArchiveHeader),
+    Reader(ArchivedReader),
+    Source(ArchivedSource),
+    Article(ArchivedArticle),
+    Chunk(ArchivedChunk),
+    Metadata(ArchivedMetadata),
+    Summary(ArchivedSummary),
+    History(ArchivedHistory),
+    /// The last line, an archive without it was cut short
+    End(ArchiveCounts),
+}
+
+#[derive(Serialize, Deserialize)]
+struct ArchiveHeader {
+    version: u32,
+    exported_at: NaiveDateTime,
+    /// The embeddings are only comparable with the same model and bits
+    embedding_bits: usize,
+    /// Its history is the owner's history of the importing server
+    owner_identity_id: i32,
+}
+
+/// A reader of the history by the account they log in with, since the
+/// identity IDs differ between servers
+#[derive(Serialize, Deserialize)]
+struct ArchivedReader {
+    identity_id: i32,
+    provider: String,
+    subject: String,
+}
+
+#[derive(Queryable, Selectable, Insertable, Serialize, Deserialize)]
+#[diesel(table_name = online_article_sources)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+struct ArchivedSource {
+    key: String,
+    name: String,
+    base_url: Option<String>,
+    created_at: NaiveDateTime,
+    enabled: bool,
+    crawl_interval_mins: Option<i32>,
+    max_items: Option<i32>,
+    last_crawled_at: Option<NaiveDateTime>,
+    feed_url: Option<String>,
+}
+
+/// The IDs are kept, the history and the links shared elsewhere refer to
+/// them
+#[derive(Queryable, Selectable, Insertable, Serialize, Deserialize)]
+#[diesel(table_name = online_articles)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+struct ArchivedArticle {
+    id: i32,
+    url: String,
+    title: String,
+    content_text: Option<String>,
+    recommender_terms: Option<serde_json::Value>,
+    created_at: NaiveDateTime,
+    link_status: String,
+    link_failures: i32,
+    link_checked_at: Option<NaiveDateTime>,
+    link_next_check_at: Option<NaiveDateTime>,
+    link_validator: Option<String>,
+    archive_url: Option<String>,
+    content_refreshed_at: Option<NaiveDateTime>,
+    lang: Option<String>,
+    paywalled: bool,
+}
+
+#[derive(Serialize, Deserialize)]
+struct ArchivedChunk {
+    online_article_id: i32,
+    kind: String,
+    /// The bits packed into bytes, in base64
+    embedding: String,
+    created_at: NaiveDateTime,
+}
+
+#[derive(QueryableByName)]
+struct ChunkRow {
+    #[diesel(sql_type = Integer)]
+    id: i32,
+    #[diesel(sql_type = Integer)]
+    online_article_id: i32,
+    #[diesel(sql_type = Text)]
+    kind: String,
+    /// As `0101...`
+    #[diesel(sql_type = Text)]
+    embedding: String,
+    #[diesel(sql_type = Timestamp)]
+    created_at: NaiveDateTime,
+}
+
+/// The source by its key, its ID differs between the servers
+#[derive(Serialize, Deserialize)]
+struct ArchivedMetadata {
+    online_article_id: i32,
+    source: String,
+    external_score: Option<f64>,
+    metadata: Option<serde_json::Value>,
+    submitted_at: NaiveDateTime,
+    created_at: NaiveDateTime,
+    updated_at: NaiveDateTime,
+}
+
+#[derive(Queryable, Selectable, Insertable, Serialize, Deserialize)]
+#[diesel(table_name = online_article_summaries)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+struct ArchivedSummary {
+    online_article_id: i32,
+    summary: String,
+    takeaways: serde_json::Value,
+    model: String,
+    created_at: NaiveDateTime,
+}
+
+#[derive(Queryable, Selectable, Insertable, Serialize, Deserialize)]
+#[diesel(table_name = user_history)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+struct ArchivedHistory {
+    online_article_id: i32,
+    weight: Option<f64>,
+    added_at: NaiveDateTime,
+    /// None for the default history
+    identity_id: Option<i32>,
+    source: String,
+}
+
+#[derive(Default, Serialize, Deserialize)]
+struct ArchiveCounts {
+    articles: usize,
+    chunks: usize,
+    metadata: usize,
+    summaries: usize,
+    history: usize,
+}
+
+#[derive(Default, Serialize)]
+struct ImportSummary {
+    #[serde(flatten)]
+    imported: ArchiveCounts,
+    /// The history of the readers who have no identity on this server, or
+    /// none that could be matched
+    skipped_history: usize,
+}
+
+#[derive(thiserror::Error, Debug)]
+enum ImportError {
+    #[error("{0}")]
+    Invalid(String),
+
+    #[error(transparent)]
+    Database(#[from] diesel::result::Error),
+
+    #[error(transparent)]
+    Other(#[from] eyre::Report),
+}
+
+impl From<ImportError> for AppError {
+    fn from(err: ImportError) -> Self {
+        match err {
+            ImportError::Invalid(msg) => (msg, StatusCode::BAD_REQUEST).into(),
+            ImportError::Database(err) => err.into(),
+            ImportError::Other(err) => err.into(),
+        }
+    }
+}
+
+/// Downloads the archive of the recommendations, streamed as it's read
+async fn export_archive(
+    State(ctx): State<App>,
+    AuthUser(i): AuthUser,
+) -> Result<impl IntoResponse, AppError> {
+    if i.id != ctx.config.owner_identity_id {
+        return Err(("Not permitted", StatusCode::FORBIDDEN).into());
+    }
+
+    let (tx, rx) = mpsc::channel(4);
+    tokio::spawn(async move {
+        let mut out = ArchiveWriter {
+            tx: tx.clone(),
+            buffer: Vec::new(),
+        };
+        if let Err(err) = export(&ctx, &mut out).await {
+            tracing::warn!(?err, "Failed to export the recommendations");
+            // Cut short without the end record, so that it can't be imported
+            let _ = tx.send(Err(std::io::Error::other(err.to_string()))).await;
+        }
+    });
+
+    let filename = format!(
+        "recommendations-{}.jsonl",
+        chrono::Utc::now().format("%Y-%m-%d")
+    );
+    Ok((
+        [
+            (header::CONTENT_TYPE, "application/x-ndjson".to_string()),
+            (
+                header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"{filename}\""),
+            ),
+        ],
+        Body::from_stream(ReceiverStream::new(rx)),
+    ))
+}
+
+struct ArchiveWriter {
+    tx: mpsc::Sender<Result<Bytes, std::io::Error>>,
+    buffer: Vec<u8>,
+}
+
+impl ArchiveWriter {
+    async fn write(&mut self, record: &Record) -> Result<(), eyre::Error> {
+        serde_json::to_writer(&mut self.buffer, record)?;
+        self.buffer.push(b'\n');
+        if self.buffer.len() >= FLUSH_BYTES {
+            self.flush().await?;
+        }
+        Ok(())
+    }
+
+    async fn flush(&mut self) -> Result<(), eyre::Error> {
+        if !self.buffer.is_empty() {
+            let bytes = Bytes::from(std::mem::take(&mut self.buffer));
+            self.tx
+                .send(Ok(bytes))
+                .await
+                .map_err(|_| eyre::eyre!("the download was cancelled"))?;
+        }
+        Ok(())
+    }
+}
+
+async fn export(ctx: &App, out: &mut ArchiveWriter) -> Result<(), eyre::Error> {
+    let mut conn = ctx.diesel.get_unbounded().await?;
+    let mut counts = ArchiveCounts::default();
+
+    // The articles crawled meanwhile are left out with their rows, which
+    // could otherwise reference an article that isn't in the archive
+    let last_article = online_articles::table
+        .select(diesel::dsl::max(online_articles::id))
+        .first::<Option<i32>>(&mut conn)
+        .await?
+        .unwrap_or(0);
+
+    out.write(&Record::Header(ArchiveHeader {
+        version: ARCHIVE_VERSION,
+        exported_at: chrono::Utc::now().naive_utc(),
+        embedding_bits: RECOMMENDER_EMBEDDING_BITS,
+        owner_identity_id: ctx.config.owner_identity_id,
+    }))
+    .await?;
+
+    let history_readers = user_history::table
+        .select(user_history::identity_id)
+        .distinct()
+        .load::<Option<i32>>(&mut conn)
+        .await?
+        .into_iter()
+        .flatten()
+        .collect::<HashSet<_>>();
+    for reader in readers(&mut conn).await? {
+        if history_readers.contains(&reader.identity_id) {
+            out.write(&Record::Reader(reader)).await?;
+        }
+    }
+
+    let sources = online_article_sources::table
+        .order(online_article_sources::id)
+        .select(ArchivedSource::as_select())
+        .load(&mut conn)
+        .await?;
+    for source in sources {
+        out.write(&Record::Source(source)).await?;
+    }
+
+    let mut after = 0;
+    loop {
+        let articles = online_articles::table
+            .filter(online_articles::id.gt(after))
+            .filter(online_articles::id.le(last_article))
+            .order(online_articles::id)
+            .limit(BATCH_SIZE as i64)
+            .select(ArchivedArticle::as_select())
+            .load(&mut conn)
+            .await?;
+        let Some(last) = articles.last() else {
+            break;
+        };
+        after = last.id;
+        for article in articles {
+            counts.articles += 1;
+            out.write(&Record::Article(article)).await?;
+        }
+    }
+
+    let mut after = 0;
+    loop {
+        let chunks = diesel::sql_query(
+            "SELECT id, online_article_id, kind, embedding::text AS embedding, created_at \
+             FROM online_article_chunks \
+             WHERE id > $1 AND online_article_id <= $2 \
+             ORDER BY id LIMIT $3",
+        )
+        .bind::<Integer, _>(after)
+        .bind::<Integer, _>(last_article)
+        .bind::<Integer, _>(BATCH_SIZE as i32)
+        .load::<ChunkRow>(&mut conn)
+        .await?;
+        let Some(last) = chunks.last() else {
+            break;
+        };
+        after = last.id;
+        for chunk in chunks {
+            counts.chunks += 1;
+            out.write(&Record::Chunk(ArchivedChunk {
+                online_article_id: chunk.online_article_id,
+                kind: chunk.kind,
+                embedding: STANDARD.encode(pack_bits(&chunk.embedding)),
+                created_at: chunk.created_at,
+            }))
+            .await?;
+        }
+    }
+
+    let mut after = 0;
+    loop {
+        let metadata = online_article_metadata::table
+            .inner_join(online_article_sources::table)
+            .filter(online_article_metadata::id.gt(after))
+            .filter(online_article_metadata::online_article_id.le(last_article))
+            .order(online_article_metadata::id)
+            .limit(BATCH_SIZE as i64)
+            .select((
+                online_article_metadata::id,
+                online_article_metadata::online_article_id,
+                online_article_sources::key,
+                online_article_metadata::external_score,
+                online_article_metadata::metadata,
+                online_article_metadata::submitted_at,
+                online_article_metadata::created_at,
+                online_article_metadata::updated_at,
+            ))
+            .load::<(
+                i32,
+                i32,
+                String,
+                Option<f64>,
+                Option<serde_json::Value>,
+                NaiveDateTime,
+                NaiveDateTime,
+                NaiveDateTime,
+            )>(&mut conn)
+            .await?;
+        let Some(last) = metadata.last() else {
+            break;
+        };
+        after = last.0;
+        for (
+            _,
+            online_article_id,
+            source,
+            external_score,
+            metadata,
+            submitted_at,
+            created_at,
+            updated_at,
+        ) in metadata
+        {
+            counts.metadata += 1;
+            out.write(&Record::Metadata(ArchivedMetadata {
+                online_article_id,
+                source,
+                external_score,
+                metadata,
+                submitted_at,
+                created_at,
+                updated_at,
+            }))
+            .await?;
+        }
+    }
+
+    let mut after = 0;
+    loop {
+        let summaries = online_article_summaries::table
+            .filter(online_article_summaries::online_article_id.gt(after))
+            .filter(online_article_summaries::online_article_id.le(last_article))
+            .order(online_article_summaries::online_article_id)
+            .limit(BATCH_SIZE as i64)
+            .select(ArchivedSummary::as_select())
+            .load(&mut conn)
+            .await?;
+        let Some(last) = summaries.last() else {
+            break;
+        };
+        after = last.online_article_id;
+        for summary in summaries {
+            counts.summaries += 1;
+            out.write(&Record::Summary(summary)).await?;
+        }
+    }
+
+    let mut after = 0;
+    loop {
+        let history = user_history::table
+            .filter(user_history::id.gt(after))
+            .filter(user_history::online_article_id.le(last_article))
+            .order(user_history::id)
+            .limit(BATCH_SIZE as i64)
+            .select((user_history::id, ArchivedHistory::as_select()))
+            .load::<(i32, ArchivedHistory)>(&mut conn)
+            .await?;
+        let Some((last, _)) = history.last() else {
+            break;
+        };
+        after = *last;
+        for (_, entry) in history {
+            counts.history += 1;
+            out.write(&Record::History(entry)).await?;
+        }
+    }
+
+    out.write(&Record::End(counts)).await?;
+    out.flush().await
+}
+
+/// Imports an archive into a server that has no articles yet, all of it or
+/// nothing
+async fn import_archive(
+    State(ctx): State<App>,
+    AuthUser(i): AuthUser,
+    body: Body,
+) -> Result<Json<ImportSummary>, AppError> {
+    if i.id != ctx.config.owner_identity_id {
+        return Err(("Not permitted", StatusCode::FORBIDDEN).into());
+    }
+
+    let mut reader = ArchiveReader::new(body.into_data_stream());
+    let Some(Record::Header(archive)) = reader.next().await? else {
+        return Err((
+            "Not an archive of the recommendations",
+            StatusCode::BAD_REQUEST,
+        )
+            .into());
+    };
+    if archive.version != ARCHIVE_VERSION {
+        return Err((
+            format!("Archives of version {} can't be imported", archive.version),
+            StatusCode::BAD_REQUEST,
+        )
+            .into());
+    }
+    if archive.embedding_bits != RECOMMENDER_EMBEDDING_BITS {
+        return Err((
+            "The embeddings of the archive are of another model",
+            StatusCode::BAD_REQUEST,
+        )
+            .into());
+    }
+
+    let mut conn = ctx.diesel.get_unbounded().await?;
+    let has_articles = diesel::select(diesel::dsl::exists(
+        online_articles::table.select(online_articles::id),
+    ))
+    .get_result::<bool>(&mut conn)
+    .await?;
+    if has_articles {
+        return Err((
+            "This server already has articles, the archive is imported into a fresh one",
+            StatusCode::CONFLICT,
+        )
+            .into());
+    }
+
+    let owner_identity_id = ctx.config.owner_identity_id;
+    let summary = conn
+        .transaction(async move |conn| {
+            import(
+                conn,
+                &mut reader,
+                archive.owner_identity_id,
+                owner_identity_id,
+            )
+            .await
+        })
+        .await?;
+
+    tracing::info!(
+        articles = summary.imported.articles,
+        chunks = summary.imported.chunks,
+        history = summary.imported.history,
+        "Imported the archive of the recommendations"
+    );
+    Ok(Json(summary))
+}
+
+/// The rows waiting to be inserted, in the order they reference each other
+#[derive(Default)]
+struct PendingRows {
+    articles: Vec<ArchivedArticle>,
+    chunks: Vec<ArchivedChunk>,
+    metadata: Vec<ArchivedMetadata>,
+    summaries: Vec<ArchivedSummary>,
+    history: Vec<ArchivedHistory>,
+}
+
+impl PendingRows {
+    fn is_full(&self) -> bool {
+        [
+            self.articles.len(),
+            self.chunks.len(),
+            self.metadata.len(),
+            self.summaries.len(),
+            self.history.len(),
+        ]
+        .into_iter()
+        .any(|len| len >= BATCH_SIZE)
+    }
+}
+
+async fn import<S, E>(
+    conn: &mut AsyncPgConnection,
+    reader: &mut ArchiveReader<S>,
+    archive_owner_id: i32,
+    owner_identity_id: i32,
+) -> Result<ImportSummary, ImportError>
+where
+    S: Stream<Item = Result<Bytes, E>> + Unpin,
+    E: std::fmt::Display,
+{
+    let mut sources = online_article_sources::table
+        .select((online_article_sources::key, online_article_sources::id))
+        .load::<(String, i32)>(conn)
+        .await?
+        .into_iter()
+        .collect::<HashMap<_, _>>();
+    let logins = readers(conn)
+        .await?
+        .into_iter()
+        .map(|reader| ((reader.provider, reader.subject), reader.identity_id))
+        .collect::<HashMap<_, _>>();
+    // The identity here of the archive's readers who log in the same way
+    let mut matched_readers = HashMap::new();
+
+    let mut summary = ImportSummary::default();
+    let mut pending = PendingRows::default();
+    let expected = loop {
+        let Some(record) = reader.next().await? else {
+            return Err(ImportError::Invalid(
+                "The archive was cut short, nothing was imported".to_string(),
+            ));
+        };
+
+        match record {
+            Record::Header(_) => {
+                return Err(ImportError::Invalid(
+                    "The archive has more than one header".to_string(),
+                ));
+            }
+            Record::Reader(reader) => {
+                if let Some(&id) = logins.get(&(reader.provider, reader.subject)) {
+                    matched_readers.insert(reader.identity_id, id);
+                }
+            }
+            Record::Source(source) => {
+                if let Entry::Vacant(entry) = sources.entry(source.key.clone()) {
+                    let id = diesel::insert_into(online_article_sources::table)
+                        .values(&source)
+                        .returning(online_article_sources::id)
+                        .get_result::<i32>(conn)
+                        .await?;
+                    entry.insert(id);
+                }
+            }
+            Record::Article(article) => pending.articles.push(article),
+            Record::Chunk(chunk) => pending.chunks.push(chunk),
+            Record::Metadata(metadata) => pending.metadata.push(metadata),
+            Record::Summary(s) => pending.summaries.push(s),
+            Record::History(mut entry) => {
+                // The owner's history stays theirs, a reader's stays theirs if
+                // they log in with the same account here
+                match entry.identity_id {
+                    Some(id) if id == archive_owner_id => {
+                        entry.identity_id = Some(owner_identity_id)
+                    }
+                    Some(id) => match matched_readers.get(&id) {
+                        Some(&id) => entry.identity_id = Some(id),
+                        None => {
+                            summary.skipped_history += 1;
+                            continue;
+                        }
+                    },
+                    None => {}
+                }
+                pending.history.push(entry);
+            }
+            Record::End(counts) => break counts,
+        }
+
+        if pending.is_full() {
+            insert_pending(conn, &mut pending, &sources, &mut summary.imported).await?;
+        }
+    };
+    insert_pending(conn, &mut pending, &sources, &mut summary.imported).await?;
+
+    if reader.next().await?.is_some() {
+        return Err(ImportError::Invalid(
+            "The archive goes on after its end".to_string(),
+        ));
+    }
+    let imported = &summary.imported;
+    if expected.articles != imported.articles
+        || expected.chunks != imported.chunks
+        || expected.metadata != imported.metadata
+        || expected.summaries != imported.summaries
+        || expected.history != imported.history + summary.skipped_history
+    {
+        return Err(ImportError::Invalid(
+            "The archive has fewer rows than it says, nothing was imported".to_string(),
+        ));
+    }
+
+    // The IDs were kept, the next article gets the one after the last
+    diesel::sql_query(
+        "SELECT setval(pg_get_serial_sequence('online_articles', 'id'), \
+         GREATEST((SELECT MAX(id) FROM online_articles), 1))",
+    )
+    .execute(conn)
+    .await?;
+
+    Ok(summary)
+}
+
+/// The identities by their GitHub account, the only way to log in
+async fn readers(
+    conn: &mut AsyncPgConnection,
+) -> Result<Vec<ArchivedReader>, diesel::result::Error> {
+    let credentials = identity_credentials::table
+        .filter(
+            identity_credentials::credential.contains(serde_json::json!({
+                "provider": "github"
+            })),
+        )
+        .select((
+            identity_credentials::identity_id,
+            identity_credentials::credential,
+        ))
+        .load::<(i32, Option<serde_json::Value>)>(conn)
+        .await?;
+
+    Ok(credentials
+        .into_iter()
+        .filter_map(|(identity_id, credential)| {
+            let user_id = credential?.get("user_id")?.as_i64()?;
+            Some(ArchivedReader {
+                identity_id,
+                provider: "github".to_string(),
+                subject: user_id.to_string(),
+            })
+        })
+        .collect())
+}
+
+async fn insert_pending(
+    conn: &mut AsyncPgConnection,
+    pending: &mut PendingRows,
+    sources: &HashMap<String, i32>,
+    counts: &mut ArchiveCounts,
+) -> Result<(), ImportError> {
+    let articles = std::mem::take(&mut pending.articles);
+    if !articles.is_empty() {
+        counts.articles += diesel::insert_into(online_articles::table)
+            .values(&articles)
+            .execute(conn)
+            .await?;
+    }
+
+    let chunks = std::mem::take(&mut pending.chunks);
+    if !chunks.is_empty() {
+        let mut embeddings = Vec::with_capacity(chunks.len());
+        for chunk in &chunks {
+            let bytes = STANDARD
+                .decode(&chunk.embedding)
+                .map_err(|_| ImportError::Invalid("An embedding isn't base64".to_string()))?;
+            let bits = unpack_bits(&bytes, RECOMMENDER_EMBEDDING_BITS).ok_or_else(|| {
+                ImportError::Invalid("An embedding has the wrong number of bits".to_string())
+            })?;
+            embeddings.push(bits);
+        }
+
+        counts.chunks += diesel::sql_query(format!(
+            "INSERT INTO online_article_chunks (online_article_id, embedding, kind, created_at) \
+             SELECT article_id, embedding::BIT({RECOMMENDER_EMBEDDING_BITS}), kind, created_at \
+             FROM UNNEST($1::int4[], $2::text[], $3::text[], $4::timestamp[]) \
+                 AS t(article_id, embedding, kind, created_at)"
+        ))
+        .bind::<Array<Integer>, _>(
+            chunks
+                .iter()
+                .map(|c| c.online_article_id)
+                .collect::<Vec<_>>(),
+        )
+        .bind::<Array<Text>, _>(embeddings)
+        .bind::<Array<Text>, _>(chunks.iter().map(|c| c.kind.clone()).collect::<Vec<_>>())
+        .bind::<Array<Timestamp>, _>(chunks.iter().map(|c| c.created_at).collect::<Vec<_>>())
+        .execute(conn)
+        .await?;
+    }
+
+    let metadata = std::mem::take(&mut pending.metadata);
+    if !metadata.is_empty() {
+        let rows = metadata
+            .into_iter()
+            .map(|m| {
+                let source_id = *sources.get(&m.source).ok_or_else(|| {
+                    ImportError::Invalid(format!("The archive has no source `{}`", m.source))
+                })?;
+                Ok((
+                    online_article_metadata::online_article_id.eq(m.online_article_id),
+                    online_article_metadata::source_id.eq(source_id),
+                    online_article_metadata::external_score.eq(m.external_score),
+                    online_article_metadata::metadata.eq(m.metadata),
+                    online_article_metadata::submitted_at.eq(m.submitted_at),
+                    online_article_metadata::created_at.eq(m.created_at),
+                    online_article_metadata::updated_at.eq(m.updated_at),
+                ))
+            })
+            .collect::<Result<Vec<_>, ImportError>>()?;
+        counts.metadata += diesel::insert_into(online_article_metadata::table)
+            .values(rows)
+            .execute(conn)
+            .await?;
+    }
+
+    let summaries = std::mem::take(&mut pending.summaries);
+    if !summaries.is_empty() {
+        counts.summaries += diesel::insert_into(online_article_summaries::table)
+            .values(&summaries)
+            .execute(conn)
+            .await?;
+    }
+
+    let history = std::mem::take(&mut pending.history);
+    if !history.is_empty() {
+        counts.history += diesel::insert_into(user_history::table)
+            .values(&history)
+            .execute(conn)
+            .await?;
+    }
+
+    Ok(())
+}
+
+/// The records of an uploaded archive, line by line
+struct ArchiveReader<S> {
+    stream: S,
+    buffer: Vec<u8>,
+}
+
+impl<S, E> ArchiveReader<S>
+where
+    S: Stream<Item = Result<Bytes, E>> + Unpin,
+    E: std::fmt::Display,
+{
+    fn new(stream: S) -> Self {
+        Self {
+            stream,
+            buffer: Vec::new(),
+        }
+    }
+
+    async fn next(&mut self) -> Result<Option<Record>, ImportError> {
+        loop {
+            let line = match self.buffer.iter().position(|b| *b == b'\n') {
+                Some(end) => self.buffer.drain(..=end).collect::<Vec<_>>(),
+                None => match self.stream.next().await {
+                    Some(chunk) => {
+                        let chunk =
+                            chunk.map_err(|e| eyre::eyre!("could not read the archive: {e}"))?;
+                        self.buffer.extend_from_slice(&chunk);
+                        continue;
+                    }
+                    None if self.buffer.is_empty() => return Ok(None),
+                    None => std::mem::take(&mut self.buffer),
+                },
+            };
+            if line.trim_ascii().is_empty() {
+                continue;
+            }
+
+            return serde_json::from_slice(&line)
+                .map(Some)
+                .map_err(|e| ImportError::Invalid(format!("An invalid line in the archive: {e}")));
+        }
+    }
+}
+
+/// `0101...` as bytes, the first bit the highest of the first byte
+fn pack_bits(bits: &str) -> Vec<u8> {
+    bits.as_bytes()
+        .chunks(8)
+        .map(|byte| {
+            byte.iter().enumerate().fold(0u8, |packed, (i, bit)| {
+                packed | (u8::from(*bit == b'1') << (7 - i))
+            })
+        })
+        .collect()
+}
+
+fn unpack_bits(bytes: &[u8], bits: usize) -> Option<String> {
+    if bytes.len() != bits.div_ceil(8) {
+        return None;
+    }
+    Some(
+        (0..bits)
+            .map(|i| {
+                if bytes[i / 8] & (0x80 >> (i % 8)) != 0 {
+                    '1'
+                } else {
+                    '0'
+                }
+            })
+            .collect(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn embeddings_are_packed_into_bytes() {
+        let bits = "1000000111";
+        let packed = pack_bits(bits);
+        assert_eq!(packed, vec![0b1000_0001, 0b1100_0000]);
+        assert_eq!(unpack_bits(&packed, bits.len()).as_deref(), Some(bits));
+        assert_eq!(unpack_bits(&packed, 24), None);
+    }
+
+    #[tokio::test]
+    async fn records_are_read_across_the_pieces_of_the_upload() {
+        let archive = concat!(
+            r#"{"type":"summary","online_article_id":1,"summary":"s","takeaways":[],"#,
+            r#""model":"m","created_at":"2026-01-01T00:00:00"}"#,
+            "\n\n",
+            r#"{"type":"end","articles":0,"chunks":0,"metadata":0,"summaries":1,"history":0}"#,
+        );
+        let (first, second) = archive.split_at(40);
+        let pieces = futures::stream::iter([
+            Ok::<_, std::io::Error>(Bytes::from(first)),
+            Ok(Bytes::from(second)),
+        ]);
+        let mut reader = ArchiveReader::new(pieces);
+
+        assert!(
+            matches!(reader.next().await, Ok(Some(Record::Summary(s))) if s.online_article_id == 1)
+        );
+        assert!(matches!(reader.next().await, Ok(Some(Record::End(c))) if c.summaries == 1));
+        assert!(matches!(reader.next().await, Ok(None)));
+    }
+}
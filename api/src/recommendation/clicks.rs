@@ -0,0 +1,304 @@
+//! Click-through tracking of the feed: the feed items link to a redirect that
+//! records the click, and the positions each ranking preset showed are
+//! counted per day, so that the presets can be compared by click-through rate
+
+use std::collections::BTreeMap;
+
+use axum::{
+    Json, Router,
+    extract::{Path, Query, State},
+    http::{StatusCode, header},
+    response::IntoResponse,
+    routing::get,
+};
+use diesel::prelude::*;
+use diesel::sql_types::{Array, BigInt, Date, Integer, Text};
+use diesel_async::RunQueryDsl;
+use serde::{Deserialize, Serialize};
+use utoipa::IntoParams;
+
+use crate::{
+    App,
+    error::AppError,
+    identity::AuthUser,
+    schema::{feed_clicks, online_articles},
+};
+
+use super::{LinkStatus, RankingPreset};
+
+/// Positions past this one aren't tracked, the feed pages are at most 100
+/// items and readers rarely scroll that far
+const MAX_TRACKED_POSITION: i32 = 500;
+const DEFAULT_METRICS_DAYS: u32 = 30;
+const MAX_METRICS_DAYS: u32 = 365;
+
+pub fn route() -> Router<App> {
+    Router::<App>::new().route("/feed/items/{id}/redirect", get(redirect))
+}
+
+pub fn admin_route() -> Router<App> {
+    Router::<App>::new().route("/admin/recommendation/metrics", get(get_metrics))
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ClickQuery {
+    /// The preset the feed was ranked with
+    #[serde(default)]
+    ranking: RankingPreset,
+    /// Position of the item in the feed, from 1
+    position: Option<i32>,
+}
+
+/// Records the click and redirects to the article
+#[utoipa::path(
+    get,
+    path = "/feed/items/{id}/redirect",
+    tag = "recommendation",
+    params(("id" = i32, Path), ClickQuery),
+    responses(
+        (status = 302, description = "Redirects to the article, or its archived copy if the link is dead"),
+        (status = 404, description = "No such article"),
+    ),
+)]
+pub async fn redirect(
+    State(ctx): State<App>,
+    Path(id): Path<i32>,
+    Query(query): Query<ClickQuery>,
+) -> Result<impl IntoResponse, AppError> {
+    let mut conn = ctx.diesel.get().await?;
+
+    let (url, link_status, archive_url) = online_articles::table
+        .filter(online_articles::id.eq(id))
+        .select((
+            online_articles::url,
+            online_articles::link_status,
+            online_articles::archive_url,
+        ))
+        .first::<(String, String, Option<String>)>(&mut conn)
+        .await
+        .optional()?
+        .ok_or(("No such article", StatusCode::NOT_FOUND))?;
+    // The archived copy of a dead link
+    let url = match archive_url {
+        Some(archive_url) if link_status == LinkStatus::Dead.as_str() => archive_url,
+        _ => url,
+    };
+
+    // The reader still gets to the article if the position is off
+    if let Some(position) = query
+        .position
+        .filter(|p| (1..=MAX_TRACKED_POSITION).contains(p))
+    {
+        let _ = diesel::insert_into(feed_clicks::table)
+            .values((
+                feed_clicks::online_article_id.eq(id),
+                feed_clicks::ranking.eq(query.ranking.as_str()),
+                feed_clicks::position.eq(position),
+            ))
+            .execute(&mut conn)
+            .await
+            .inspect_err(|err| tracing::warn!(?err, id, "Failed to record a feed click"));
+    }
+
+    Ok((
+        StatusCode::FOUND,
+        [
+            (header::LOCATION, url),
+            (header::CACHE_CONTROL, "no-store".to_string()),
+        ],
+    ))
+}
+
+/// Counts the positions `offset + 1..` of the served items as shown once
+pub async fn record_impressions(
+    ctx: &App,
+    ranking: RankingPreset,
+    offset: i64,
+    items: usize,
+) -> Result<(), eyre::Error> {
+    let positions = impression_positions(offset, items);
+    if positions.is_empty() {
+        return Ok(());
+    }
+
+    let mut conn = ctx.diesel.get().await?;
+    diesel::sql_query(
+        r#"
+        INSERT INTO feed_impressions (day, ranking, position, count)
+        SELECT CURRENT_DATE, $1, position, 1 FROM unnest($2::INTEGER[]) AS position
+        ON CONFLICT (day, ranking, position)
+        DO UPDATE SET count = feed_impressions.count + 1
+    "#,
+    )
+    .bind::<Text, _>(ranking.as_str())
+    .bind::<Array<Integer>, _>(positions)
+    .execute(&mut conn)
+    .await?;
+    Ok(())
+}
+
+fn impression_positions(offset: i64, items: usize) -> Vec<i32> {
+    let first = offset.max(0).saturating_add(1);
+    (first..first.saturating_add(items as i64))
+        .filter_map(|p| i32::try_from(p).ok())
+        .filter(|p| *p <= MAX_TRACKED_POSITION)
+        .collect()
+}
+
+#[derive(Deserialize)]
+struct MetricsQuery {
+    /// The last days to report, 30 by default
+    days: Option<u32>,
+}
+
+#[derive(Serialize)]
+struct RankingMetrics {
+    ranking: String,
+    impressions: i64,
+    clicks: i64,
+    ctr: f64,
+    /// Click-through rate of each position shown, from 1
+    positions: Vec<PositionMetrics>,
+}
+
+#[derive(Serialize)]
+struct PositionMetrics {
+    position: i32,
+    impressions: i64,
+    clicks: i64,
+    ctr: f64,
+}
+
+#[derive(QueryableByName)]
+struct PositionRow {
+    #[diesel(sql_type = Text)]
+    ranking: String,
+    #[diesel(sql_type = Integer)]
+    position: i32,
+    #[diesel(sql_type = BigInt)]
+    impressions: i64,
+    #[diesel(sql_type = BigInt)]
+    clicks: i64,
+}
+
+/// Impressions, clicks and click-through rate of each ranking preset
+async fn get_metrics(
+    State(ctx): State<App>,
+    AuthUser(i): AuthUser,
+    Query(query): Query<MetricsQuery>,
+) -> Result<Json<Vec<RankingMetrics>>, AppError> {
+    if i.id != ctx.config.owner_identity_id {
+        return Err(("Not permitted", StatusCode::FORBIDDEN).into());
+    }
+
+    let days = query
+        .days
+        .unwrap_or(DEFAULT_METRICS_DAYS)
+        .clamp(1, MAX_METRICS_DAYS);
+    let since = chrono::Utc::now().date_naive() - chrono::Days::new(u64::from(days - 1));
+
+    let mut conn = ctx.diesel.get().await?;
+    let rows = diesel::sql_query(
+        r#"
+        WITH impressions AS (
+            SELECT ranking, position, SUM(count)::BIGINT AS impressions
+            FROM feed_impressions
+            WHERE day >= $1
+            GROUP BY ranking, position
+        ),
+        clicks AS (
+            SELECT ranking, position, COUNT(*) AS clicks
+            FROM feed_clicks
+            WHERE clicked_at >= $1
+            GROUP BY ranking, position
+        )
+        SELECT
+            COALESCE(i.ranking, c.ranking) AS ranking,
+            COALESCE(i.position, c.position) AS position,
+            COALESCE(i.impressions, 0) AS impressions,
+            COALESCE(c.clicks, 0) AS clicks
+        FROM impressions i
+        FULL OUTER JOIN clicks c ON c.ranking = i.ranking AND c.position = i.position
+        ORDER BY ranking, position
+    "#,
+    )
+    .bind::<Date, _>(since)
+    .load::<PositionRow>(&mut conn)
+    .await?;
+
+    Ok(Json(aggregate(rows)))
+}
+
+fn aggregate(rows: Vec<PositionRow>) -> Vec<RankingMetrics> {
+    let mut rankings = BTreeMap::<String, Vec<PositionMetrics>>::new();
+    for row in rows {
+        rankings
+            .entry(row.ranking)
+            .or_default()
+            .push(PositionMetrics {
+                position: row.position,
+                impressions: row.impressions,
+                clicks: row.clicks,
+                ctr: ctr(row.clicks, row.impressions),
+            });
+    }
+
+    rankings
+        .into_iter()
+        .map(|(ranking, positions)| {
+            let impressions = positions.iter().map(|p| p.impressions).sum();
+            let clicks = positions.iter().map(|p| p.clicks).sum();
+            RankingMetrics {
+                ranking,
+                impressions,
+                clicks,
+                ctr: ctr(clicks, impressions),
+                positions,
+            }
+        })
+        .collect()
+}
+
+fn ctr(clicks: i64, impressions: i64) -> f64 {
+    if impressions == 0 {
+        return 0.0;
+    }
+    clicks as f64 / impressions as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn impressions_start_after_the_offset() {
+        assert_eq!(impression_positions(0, 3), vec![1, 2, 3]);
+        assert_eq!(impression_positions(20, 2), vec![21, 22]);
+        assert_eq!(impression_positions(-5, 1), vec![1]);
+        assert!(impression_positions(i64::from(MAX_TRACKED_POSITION), 10).is_empty());
+    }
+
+    #[test]
+    fn metrics_are_aggregated_per_ranking() {
+        let row = |ranking: &str, position, impressions, clicks| PositionRow {
+            ranking: ranking.to_string(),
+            position,
+            impressions,
+            clicks,
+        };
+
+        let metrics = aggregate(vec![
+            row("balanced", 1, 10, 3),
+            row("balanced", 2, 10, 1),
+            row("top_first", 1, 0, 2),
+        ]);
+
+        assert_eq!(metrics.len(), 2);
+        assert_eq!(metrics[0].ranking, "balanced");
+        assert_eq!(metrics[0].impressions, 20);
+        assert_eq!(metrics[0].clicks, 4);
+        assert_eq!(metrics[0].ctr, 0.2);
+        assert_eq!(metrics[1].ctr, 0.0);
+    }
+}
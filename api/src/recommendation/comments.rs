@@ -0,0 +1,208 @@
+//! The discussions of the articles on Hacker News and Lobsters. The top
+//! comments of a discussed article are embedded as an extra chunk of it, so
+//! that it's also matched by what its readers talked about.
+
+use std::collections::{HashMap, HashSet};
+
+use diesel::prelude::*;
+use diesel::sql_types::{Integer, Text};
+use diesel_async::RunQueryDsl;
+use futures::stream::StreamExt;
+
+use crate::{
+    App,
+    schema::{online_article_chunks, online_articles},
+};
+
+use super::{
+    crawler::{
+        self, CrawlProgress, HACKER_NEWS_KEY, LOBSTERS_KEY, MAX_CONCURRENT_FETCHES,
+        MAX_CONCURRENT_HACKER_NEWS_ITEMS,
+    },
+    engine::generate_comment_embedding,
+};
+
+/// `kind` of the chunks embedded from the comments
+pub const COMMENTS_CHUNK: &str = "comments";
+/// Articles with fewer comments aren't discussed enough to be embedded
+const MIN_COMMENTS: i64 = 10;
+const MAX_SUMMARIZED_COMMENTS: usize = 8;
+/// The embedding model reads about this much, the rest would be truncated
+const MAX_SUMMARY_CHARS: usize = 1000;
+/// Keeps the calls to the APIs of the sites bounded per crawl, the rest are
+/// embedded by the next ones
+const MAX_DISCUSSIONS_PER_RUN: usize = 20;
+
+/// A submission of a crawled article and its number of comments
+#[derive(Debug)]
+pub struct Discussion {
+    /// The canonical URL of the article
+    pub url: String,
+    pub source_key: String,
+    pub external_id: String,
+    pub comment_count: i64,
+}
+
+/// Embeds the comments of the discussed articles that have no comments chunk
+/// yet
+pub async fn embed_discussions(
+    ctx: &App,
+    progress: &CrawlProgress,
+    discussions: Vec<Discussion>,
+) -> Result<(), eyre::Error> {
+    let mut discussions = discussions
+        .into_iter()
+        .filter(|d| d.comment_count >= MIN_COMMENTS)
+        .filter(|d| d.source_key == HACKER_NEWS_KEY || d.source_key == LOBSTERS_KEY)
+        .collect::<Vec<_>>();
+    if discussions.is_empty() || progress.is_cancelled() {
+        return Ok(());
+    }
+
+    let urls = discussions
+        .iter()
+        .map(|d| d.url.as_str())
+        .collect::<Vec<_>>();
+    let mut conn = ctx.diesel.get().await?;
+    let articles = online_articles::table
+        .filter(online_articles::url.eq_any(&urls))
+        .filter(diesel::dsl::not(diesel::dsl::exists(
+            online_article_chunks::table
+                .filter(online_article_chunks::online_article_id.eq(online_articles::id))
+                .filter(online_article_chunks::kind.eq(COMMENTS_CHUNK)),
+        )))
+        .select((
+            online_articles::url,
+            online_articles::id,
+            online_articles::title,
+        ))
+        .load::<(String, i32, String)>(&mut conn)
+        .await?
+        .into_iter()
+        .map(|(url, id, title)| (url, (id, title)))
+        .collect::<HashMap<_, _>>();
+    // Release connection before HTTP fetches
+    drop(conn);
+
+    // The most discussed first, an article submitted to both sites once
+    discussions.sort_by_key(|d| std::cmp::Reverse(d.comment_count));
+    let mut seen = HashSet::new();
+    let due = discussions
+        .into_iter()
+        .filter_map(|d| {
+            let (id, title) = articles.get(&d.url)?;
+            seen.insert(*id).then(|| (*id, title.clone(), d))
+        })
+        .take(MAX_DISCUSSIONS_PER_RUN)
+        .collect::<Vec<_>>();
+
+    futures::stream::iter(due)
+        .take_while(|_| std::future::ready(!progress.is_cancelled()))
+        .map(|(id, title, discussion)| async move {
+            let _ = embed_discussion(ctx, id, &title, &discussion)
+                .await
+                .inspect_err(|err| {
+                    tracing::warn!(?err, id, url = %discussion.url, "Failed to embed comments")
+                });
+        })
+        .buffer_unordered(MAX_CONCURRENT_FETCHES)
+        .collect::<Vec<_>>()
+        .await;
+
+    Ok(())
+}
+
+async fn embed_discussion(
+    ctx: &App,
+    article_id: i32,
+    title: &str,
+    discussion: &Discussion,
+) -> Result<(), eyre::Error> {
+    let comments = match discussion.source_key.as_str() {
+        HACKER_NEWS_KEY => hacker_news_comments(ctx, &discussion.external_id).await?,
+        _ => lobsters_comments(ctx, &discussion.external_id).await?,
+    };
+    let Some(summary) = summarize(&comments) else {
+        return Ok(());
+    };
+    let embedding = generate_comment_embedding(&ctx.embedder, title, &summary).await?;
+
+    let mut conn = ctx.diesel.get().await?;
+    diesel::sql_query(format!(
+        "INSERT INTO online_article_chunks (online_article_id, embedding, kind) \
+         VALUES ($1, binary_quantize($2)::BIT({}), $3)",
+        crate::utils::RECOMMENDER_EMBEDDING_BITS
+    ))
+    .bind::<Integer, _>(article_id)
+    .bind::<crate::schema::PgVector, _>(&embedding)
+    .bind::<Text, _>(COMMENTS_CHUNK)
+    .execute(&mut conn)
+    .await?;
+    Ok(())
+}
+
+/// The top level comments of the story, in the order Hacker News ranks them
+async fn hacker_news_comments(ctx: &App, story_id: &str) -> Result<Vec<String>, eyre::Error> {
+    let story = ctx.clients.hacker_news.item(story_id.parse()?).await?;
+    let comments = futures::stream::iter(story.kids.into_iter().take(MAX_SUMMARIZED_COMMENTS))
+        .map(|id| ctx.clients.hacker_news.item(id))
+        .buffered(MAX_CONCURRENT_HACKER_NEWS_ITEMS)
+        .collect::<Vec<_>>()
+        .await;
+
+    Ok(comments
+        .into_iter()
+        .filter_map(|comment| {
+            comment
+                .inspect_err(|err| tracing::debug!(?err, "Failed to fetch a Hacker News comment"))
+                .ok()
+        })
+        .filter(|comment| !comment.dead)
+        .filter_map(|comment| crawler::submission_text(comment.text.as_deref()?))
+        .collect())
+}
+
+/// The comments of the story with the highest scores
+async fn lobsters_comments(ctx: &App, short_id: &str) -> Result<Vec<String>, eyre::Error> {
+    let mut comments = ctx.clients.lobsters.comments(short_id).await?;
+    comments.retain(|comment| !comment.is_deleted);
+    comments.sort_by_key(|comment| std::cmp::Reverse(comment.score));
+    Ok(comments
+        .into_iter()
+        .take(MAX_SUMMARIZED_COMMENTS)
+        .map(|comment| comment.comment_plain)
+        .collect())
+}
+
+/// The comments one after another, cut at the length the model reads
+fn summarize(comments: &[String]) -> Option<String> {
+    let summary = comments
+        .iter()
+        .map(|comment| comment.trim())
+        .filter(|comment| !comment.is_empty())
+        .collect::<Vec<_>>()
+        .join("\n\n")
+        .chars()
+        .take(MAX_SUMMARY_CHARS)
+        .collect::<String>();
+    (!summary.is_empty()).then_some(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn summaries_join_the_comments_up_to_the_limit() {
+        let comments = [
+            "  First! ".to_string(),
+            String::new(),
+            "x".repeat(MAX_SUMMARY_CHARS),
+        ];
+
+        let summary = summarize(&comments).expect("summary");
+        assert!(summary.starts_with("First!\n\nxx"));
+        assert_eq!(summary.chars().count(), MAX_SUMMARY_CHARS);
+        assert_eq!(summarize(&[" ".to_string()]), None);
+    }
+}
@@ -1,6 +1,17 @@
-use std::{collections::HashMap, time::Duration};
-
-use crate::App;
+use std::{
+    collections::HashMap,
+    sync::{
+        Mutex,
+        atomic::{AtomicBool, AtomicI32, Ordering},
+    },
+    time::{Duration, Instant},
+};
+
+use crate::{
+    App,
+    clients::{HackerNewsClient, LobstersClient},
+    schema::{crawl_runs, online_article_sources},
+};
 use diesel::prelude::*;
 use diesel::sql_types::Integer;
 use diesel_async::{AsyncPgConnection, RunQueryDsl};
@@ -8,9 +19,13 @@ use eyre::{OptionExt, WrapErr, eyre};
 use futures::stream::StreamExt;
 use pgvector::Vector;
 use robotxt::Robots;
-use serde::Deserialize;
 
-use super::get_or_create_source;
+use super::{
+    CrawlFailure, CrawlRun, CrawlStatus, CrawlTiming, CrawlTrigger,
+    comments::{self, Discussion},
+    get_or_create_source, rss,
+};
+use crate::models::recommendation::OnlineArticleSource;
 
 async fn upsert_metadata(
     conn: &mut AsyncPgConnection,
@@ -51,8 +66,21 @@ async fn upsert_metadata(
 }
 
 pub const MAX_CONCURRENT_FETCHES: usize = 4;
+pub const LOBSTERS_KEY: &str = "lobsters";
+pub const HACKER_NEWS_KEY: &str = "hacker-news";
+/// Entries taken per crawl from the sources that don't set it
+const DEFAULT_LOBSTERS_ITEMS: usize = 50;
+const DEFAULT_HACKER_NEWS_ITEMS: usize = 64;
+/// Hacker News items fetched at once, the API has no endpoint for a batch
+/// of them
+pub const MAX_CONCURRENT_HACKER_NEWS_ITEMS: usize = 16;
+/// Lobsters has 25 stories per page
+const MAX_LOBSTERS_PAGES: u32 = 10;
+/// The failures of a run kept with it, the rest are only counted
+const MAX_RECORDED_FAILURES: usize = 100;
+const MAX_FAILURE_REASON_CHARS: usize = 300;
 const ROBOTS_USER_AGENT: &str = "wrx-recommendation-bot";
-const DEFAULT_CRAWL_DELAY: Duration = Duration::from_secs(1);
+pub const DEFAULT_CRAWL_DELAY: Duration = Duration::from_secs(1);
 
 #[derive(Clone, Debug)]
 pub struct SourceEntry {
@@ -62,6 +90,10 @@ pub struct SourceEntry {
     pub external_score: Option<f64>,
     pub submitted_at: chrono::NaiveDateTime,
     pub external_id: String,
+    /// Markdown of the text the submitter wrote, if any
+    pub submission_text: Option<String>,
+    /// Comments of the submission, for the sites that have them
+    pub comment_count: Option<i64>,
 }
 
 #[derive(Debug)]
@@ -70,9 +102,38 @@ pub struct FetchedArticle {
     title: String,
     recommender_terms: Vec<String>,
     embeddings: Vec<Vector>,
+    lang: Option<String>,
+    paywalled: bool,
+}
+
+impl FetchedArticle {
+    pub fn new(
+        url: url::Url,
+        title: String,
+        recommender_terms: Vec<String>,
+        embeddings: Vec<Vector>,
+        lang: Option<String>,
+        paywalled: bool,
+    ) -> Self {
+        Self {
+            url,
+            title,
+            recommender_terms,
+            embeddings,
+            lang,
+            paywalled,
+        }
+    }
+}
+
+/// The article of a page, `paywalled` if only a teaser of it could be read
+pub struct ArticlePage {
+    pub title: Option<String>,
+    pub markdown: String,
+    pub paywalled: bool,
 }
 
-async fn insert_article_chunks(
+pub async fn insert_article_chunks(
     conn: &mut AsyncPgConnection,
     article_id: i32,
     embeddings: &[Vector],
@@ -124,7 +185,7 @@ pub async fn backfill_recommender_fields(
         build_recommender_terms_json(&article.title, Some(&markdown))
     };
 
-    let mut conn = ctx.diesel.get().await?;
+    let mut conn = ctx.diesel.get_unbounded().await?;
     diesel::update(articles_dsl::online_articles.filter(articles_dsl::id.eq(article.id)))
         .set((
             articles_dsl::content_text.eq::<Option<String>>(None),
@@ -136,40 +197,220 @@ pub async fn backfill_recommender_fields(
     Ok(true)
 }
 
-#[tracing::instrument(skip(ctx))]
-pub async fn run_crawl(ctx: &App) -> Result<(), eyre::Error> {
-    tracing::debug!("Starting crawl job");
+/// Progress of a crawl, shared with the admin endpoints and saved to its row
+/// in `crawl_runs` as it goes
+pub struct CrawlProgress {
+    pub run_id: i32,
+    trigger: CrawlTrigger,
+    started_at: chrono::NaiveDateTime,
+    fetched: AtomicI32,
+    embedded: AtomicI32,
+    failed: AtomicI32,
+    failures: Mutex<Vec<CrawlFailure>>,
+    timings: Mutex<Vec<CrawlTiming>>,
+    cancelled: AtomicBool,
+}
 
-    let mut entries = fetch_lobsters(ctx)
-        .await
-        .inspect_err(|err| {
-            tracing::error!(?err, "Failed to fetch entries from Lobsters");
+impl CrawlProgress {
+    /// Records the start of a run
+    pub async fn start(ctx: &App, trigger: CrawlTrigger) -> Result<Self, eyre::Error> {
+        let mut conn = ctx.diesel.get_unbounded().await?;
+        // Runs are never left running but by a restart in the middle of them
+        diesel::update(
+            crawl_runs::table.filter(crawl_runs::status.eq(CrawlStatus::Running.as_str())),
+        )
+        .set((
+            crawl_runs::status.eq(CrawlStatus::Failed.as_str()),
+            crawl_runs::finished_at.eq(diesel::dsl::now),
+        ))
+        .execute(&mut conn)
+        .await?;
+        let (run_id, started_at) = diesel::insert_into(crawl_runs::table)
+            .values(crawl_runs::trigger.eq(trigger.as_str()))
+            .returning((crawl_runs::id, crawl_runs::started_at))
+            .get_result::<(i32, chrono::NaiveDateTime)>(&mut conn)
+            .await?;
+
+        Ok(Self {
+            run_id,
+            trigger,
+            started_at,
+            fetched: AtomicI32::new(0),
+            embedded: AtomicI32::new(0),
+            failed: AtomicI32::new(0),
+            failures: Mutex::new(Vec::new()),
+            timings: Mutex::new(Vec::new()),
+            cancelled: AtomicBool::new(false),
         })
-        .unwrap_or_default();
-    entries.extend(
-        fetch_hackernews(ctx)
-            .await
-            .inspect_err(|err| {
-                tracing::error!(?err, "Failed to fetch entries from Hacker News");
-            })
-            .unwrap_or_default(),
-    );
+    }
+
+    /// Stops the crawl before the next article, the ones being fetched are
+    /// still stored
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        AtomicBool::load(&self.cancelled, Ordering::Relaxed)
+    }
+
+    fn fail(&self, url: Option<&url::Url>, reason: String) {
+        self.failed.fetch_add(1, Ordering::Relaxed);
+        let mut failures = self.failures.lock().expect("crawl failures lock poisoned");
+        if failures.len() < MAX_RECORDED_FAILURES {
+            failures.push(CrawlFailure {
+                url: url.map(url::Url::to_string),
+                reason: reason.chars().take(MAX_FAILURE_REASON_CHARS).collect(),
+            });
+        }
+    }
+
+    /// Records how long a step took since it started
+    fn time(&self, step: &str, started: Instant) {
+        let elapsed = started.elapsed();
+        tracing::info!(run_id = self.run_id, step, ?elapsed, "Crawl step finished");
+        self.timings
+            .lock()
+            .expect("crawl timings lock poisoned")
+            .push(CrawlTiming {
+                step: step.to_string(),
+                millis: elapsed.as_millis().try_into().unwrap_or(i64::MAX),
+            });
+    }
+
+    /// The run as it is now
+    pub fn snapshot(&self) -> CrawlRun {
+        CrawlRun {
+            id: self.run_id,
+            status: CrawlStatus::Running,
+            trigger: self.trigger,
+            fetched: AtomicI32::load(&self.fetched, Ordering::Relaxed),
+            embedded: AtomicI32::load(&self.embedded, Ordering::Relaxed),
+            failed: AtomicI32::load(&self.failed, Ordering::Relaxed),
+            failures: self
+                .failures
+                .lock()
+                .expect("crawl failures lock poisoned")
+                .clone(),
+            timings: self
+                .timings
+                .lock()
+                .expect("crawl timings lock poisoned")
+                .clone(),
+            started_at: self.started_at,
+            finished_at: None,
+        }
+    }
+
+    async fn save(&self, ctx: &App) -> Result<(), eyre::Error> {
+        let run = self.snapshot();
+        let mut conn = ctx.diesel.get_unbounded().await?;
+        diesel::update(crawl_runs::table.filter(crawl_runs::id.eq(self.run_id)))
+            .set((
+                crawl_runs::fetched.eq(run.fetched),
+                crawl_runs::embedded.eq(run.embedded),
+                crawl_runs::failed.eq(run.failed),
+                crawl_runs::failures.eq(serde_json::to_value(&run.failures)?),
+                crawl_runs::timings.eq(serde_json::to_value(&run.timings)?),
+            ))
+            .execute(&mut conn)
+            .await?;
+        Ok(())
+    }
+
+    /// Records the end of the run
+    pub async fn finish(&self, ctx: &App, status: CrawlStatus) -> Result<(), eyre::Error> {
+        self.save(ctx).await?;
+        let mut conn = ctx.diesel.get_unbounded().await?;
+        diesel::update(crawl_runs::table.filter(crawl_runs::id.eq(self.run_id)))
+            .set((
+                crawl_runs::status.eq(status.as_str()),
+                crawl_runs::finished_at.eq(diesel::dsl::now),
+            ))
+            .execute(&mut conn)
+            .await?;
+        Ok(())
+    }
+}
+
+#[tracing::instrument(skip_all, fields(run_id = progress.run_id))]
+pub async fn run_crawl(ctx: &App, progress: &CrawlProgress) -> Result<(), eyre::Error> {
+    tracing::debug!("Starting crawl job");
+
+    let mut entries = Vec::new();
+    let mut source_keys = HashMap::new();
+    for source in sources_to_crawl(ctx, progress.trigger).await? {
+        source_keys.insert(source.id, source.key.clone());
+        let started = Instant::now();
+        let fetched = match source.key.as_str() {
+            LOBSTERS_KEY => {
+                let max_items = max_items(&source, DEFAULT_LOBSTERS_ITEMS);
+                lobsters_entries(ctx.clients.lobsters.as_ref(), source.id, max_items).await
+            }
+            HACKER_NEWS_KEY => {
+                let max_items = max_items(&source, DEFAULT_HACKER_NEWS_ITEMS);
+                hackernews_entries(ctx.clients.hacker_news.as_ref(), source.id, max_items).await
+            }
+            _ => match &source.feed_url {
+                Some(feed_url) => {
+                    let max_items = max_items(&source, rss::DEFAULT_RSS_ITEMS);
+                    rss::entries(ctx, &source, feed_url, max_items).await
+                }
+                None => continue,
+            },
+        };
+        progress.time(&source.key, started);
+
+        match fetched {
+            Ok(fetched) => {
+                entries.extend(fetched);
+                let mut conn = ctx.diesel.get_unbounded().await?;
+                diesel::update(
+                    online_article_sources::table.filter(online_article_sources::id.eq(source.id)),
+                )
+                .set(online_article_sources::last_crawled_at.eq(diesel::dsl::now))
+                .execute(&mut conn)
+                .await?;
+            }
+            Err(err) => {
+                tracing::error!(?err, source = source.key, "Failed to fetch entries");
+                progress.fail(None, format!("{}: {err:#}", source.name));
+            }
+        }
+    }
 
     tracing::debug!("Fetched {} total entries from sources", entries.len());
+    progress.fetched.store(
+        entries.len().try_into().unwrap_or(i32::MAX),
+        Ordering::Relaxed,
+    );
+    progress.save(ctx).await?;
 
     // First pass: filter out already-existing URLs
-    let mut conn = ctx.diesel.get().await?;
+    let mut conn = ctx.diesel.get_unbounded().await?;
     let mut new_entries = Vec::new();
     let mut articles_to_backfill = HashMap::new();
+    let mut discussions = Vec::new();
     // FIXME: N+1 query
     for entry in entries {
         let url = match canonicalize_url(entry.url.clone()) {
             Ok(u) => u,
             Err(err) => {
                 tracing::warn!(url = %entry.url, ?err, "Failed to canonicalize URL");
+                progress.fail(Some(&entry.url), format!("{err:#}"));
                 continue;
             }
         };
+        if let (Some(comment_count), Some(source_key)) =
+            (entry.comment_count, source_keys.get(&entry.source_id))
+        {
+            discussions.push(Discussion {
+                url: url.to_string(),
+                source_key: source_key.clone(),
+                external_id: entry.external_id.clone(),
+                comment_count,
+            });
+        }
 
         use crate::schema::online_articles::dsl as online_articles_dsl;
         let existing = online_articles_dsl::online_articles
@@ -179,10 +420,12 @@ pub async fn run_crawl(ctx: &App) -> Result<(), eyre::Error> {
             .optional()?;
 
         if let Some(existing) = existing {
-            // Update metadata for existing item (score, editorialized title, external_id, submitted_at)
+            // Update metadata for existing item (score, editorialized title, external_id,
+            // comment count, submitted_at)
             let metadata_json = serde_json::json!({
                 "editorialized_title": entry.title,
                 "external_id": entry.external_id,
+                "comment_count": entry.comment_count,
             });
             upsert_metadata(
                 &mut conn,
@@ -205,13 +448,14 @@ pub async fn run_crawl(ctx: &App) -> Result<(), eyre::Error> {
     drop(conn);
 
     let articles_to_backfill = articles_to_backfill.into_values().collect::<Vec<_>>();
-    if !articles_to_backfill.is_empty() {
+    if !articles_to_backfill.is_empty() && !progress.is_cancelled() {
         tracing::debug!(
             "Backfilling recommender content for {} existing articles",
             articles_to_backfill.len()
         );
 
         futures::stream::iter(articles_to_backfill)
+            .take_while(|_| std::future::ready(!progress.is_cancelled()))
             .map(|article| {
                 let ctx = ctx.clone();
                 async move { backfill_recommender_fields(&ctx, article).await.map(|_| ()) }
@@ -232,45 +476,77 @@ pub async fn run_crawl(ctx: &App) -> Result<(), eyre::Error> {
 
     if new_entries.is_empty() {
         tracing::debug!("No new entries to process");
-        return Ok(());
+        return embed_discussions(ctx, progress, discussions).await;
     }
 
     tracing::debug!("Processing {} new entries", new_entries.len());
+    let started = Instant::now();
 
     futures::stream::iter(new_entries)
+        .take_while(|_| std::future::ready(!progress.is_cancelled()))
         .map(|entry| {
             let ctx = ctx.clone();
             async move {
-                let article =
-                    fetch_and_generate_embedding(&ctx, entry.url.clone(), entry.title.clone())
-                        .await?;
-                let mut conn = ctx.diesel.get().await?;
-                insert_article(&mut conn, article, Some(&entry)).await
+                let result = async {
+                    let article = fetch_and_generate_embedding(
+                        &ctx,
+                        entry.url.clone(),
+                        entry.title.clone(),
+                        entry.submission_text.as_deref(),
+                    )
+                    .await?;
+                    let mut conn = ctx.diesel.get_unbounded().await?;
+                    insert_article(&mut conn, article, Some(&entry)).await
+                }
+                .await;
+                (entry.url, result)
             }
         })
         .buffer_unordered(MAX_CONCURRENT_FETCHES)
-        .filter_map(|result| async {
+        .for_each(|(url, result)| async move {
             match result {
-                Ok(ok) => Some(ok),
+                Ok(_) => {
+                    progress.embedded.fetch_add(1, Ordering::Relaxed);
+                }
                 Err(err) => {
                     tracing::warn!(?err, "Failed to fetch and insert article");
-                    None
+                    progress.fail(Some(&url), format!("{err:#}"));
                 }
             }
+            let _ = progress
+                .save(ctx)
+                .await
+                .inspect_err(|err| tracing::warn!(?err, "Failed to save crawl progress"));
         })
-        .collect::<Vec<_>>()
         .await;
 
-    Ok(())
+    progress.time("articles", started);
+
+    // The new articles are stored by now
+    embed_discussions(ctx, progress, discussions).await
+}
+
+async fn embed_discussions(
+    ctx: &App,
+    progress: &CrawlProgress,
+    discussions: Vec<Discussion>,
+) -> Result<(), eyre::Error> {
+    let started = Instant::now();
+    let result = comments::embed_discussions(ctx, progress, discussions).await;
+    progress.time("comments", started);
+    result
 }
 
-#[tracing::instrument(skip(ctx, url))]
+/// Fetches and embeds the article. Paywalled articles are embedded from
+/// their title and `submission_text` instead of the teaser of the page.
+#[tracing::instrument(skip(ctx, url, submission_text))]
 pub async fn fetch_and_generate_embedding(
     ctx: &App,
     url: url::Url,
     title: Option<String>,
+    submission_text: Option<&str>,
 ) -> Result<FetchedArticle, eyre::Error> {
-    let (real_title, markdown) = fetch_markdown(ctx, &url)
+    let page = fetch_article_page(ctx, &url)
         .await
         .inspect_err(|err| {
             tracing::warn!(
@@ -279,20 +555,38 @@ pub async fn fetch_and_generate_embedding(
                 "Failed to fetch and parse article, falling back to using title only"
             );
         })
-        .unwrap_or_else(|_| (title.clone(), title.clone().unwrap_or_default()));
+        .ok();
+    let paywalled = page.as_ref().is_some_and(|page| page.paywalled);
+    let (real_title, markdown) = match page {
+        Some(page) if !page.paywalled => (page.title, page.markdown),
+        Some(page) => {
+            tracing::debug!(url = %url, "Article is paywalled, embedding its submission instead");
+            (page.title, submission_text.unwrap_or_default().to_string())
+        }
+        None => (
+            title.clone(),
+            submission_text
+                .map(str::to_string)
+                .or_else(|| title.clone())
+                .unwrap_or_default(),
+        ),
+    };
 
     let title = real_title
         .or(title)
         .ok_or_eyre("couldn't extract title from the article, maybe manually supply one")?;
 
     let recommender_terms = crate::utils::extract_recommender_terms(&title, Some(&markdown));
-    let embeddings = super::engine::generate_embeddings(&title, &markdown).await?;
+    let lang = crate::utils::detect_language(&title, &markdown);
+    let embeddings = super::engine::generate_embeddings(&ctx.embedder, &title, &markdown).await?;
 
     Ok(FetchedArticle {
         url,
         title,
         recommender_terms,
         embeddings,
+        lang,
+        paywalled,
     })
 }
 
@@ -311,6 +605,8 @@ pub async fn insert_article(
         title,
         recommender_terms,
         embeddings,
+        lang,
+        paywalled,
         ..
     } = article;
 
@@ -322,6 +618,8 @@ pub async fn insert_article(
                 content_text: None,
                 recommender_terms: (!recommender_terms.is_empty())
                     .then_some(serde_json::json!(recommender_terms)),
+                lang,
+                paywalled,
             };
 
             let article_id = diesel::insert_into(articles_dsl::online_articles)
@@ -336,6 +634,7 @@ pub async fn insert_article(
                 let metadata_json = serde_json::json!({
                     "editorialized_title": source_entry.title,
                     "external_id": source_entry.external_id,
+                    "comment_count": source_entry.comment_count,
                 });
                 let new_metadata = crate::models::recommendation::NewArticleMetadata {
                     online_article_id: article_id,
@@ -384,7 +683,7 @@ async fn get_robots_info(ctx: &App, url: &url::Url) -> Result<Robots, eyre::Erro
 
     let base = url::Url::parse(&format!("{}://{}/", url.scheme(), host))?;
     let robots_url = robotxt::create_url(&base).map_err(|err| eyre!(err))?;
-    let body = match ctx.http.get(robots_url).send().await {
+    let body = match ctx.http.public_only().get(robots_url).send().await {
         Ok(resp) => resp.text().await.unwrap_or_default(),
         Err(_) => String::new(),
     };
@@ -404,12 +703,22 @@ async fn get_robots_info(ctx: &App, url: &url::Url) -> Result<Robots, eyre::Erro
     Ok(robots)
 }
 
-async fn fetch_markdown(
+/// Title and main content of the page as markdown, respecting robots.txt and
+/// the crawl delay of the site
+pub async fn fetch_markdown(
     ctx: &App,
     url: &url::Url,
 ) -> Result<(Option<String>, String), eyre::Error> {
+    let page = fetch_article_page(ctx, url).await?;
+    Ok((page.title, page.markdown))
+}
+
+pub async fn fetch_article_page(ctx: &App, url: &url::Url) -> Result<ArticlePage, eyre::Error> {
     let domain = url.host_str().ok_or_else(|| eyre!("missing host"))?;
 
+    // Check before robots.txt is fetched from the same host
+    crate::ssrf::check_url(url).await?;
+
     let robots = get_robots_info(ctx, url).await?;
     if !robots.is_absolute_allowed(url) {
         return Err(eyre!("robots.txt disallows crawling this URL"));
@@ -420,16 +729,17 @@ async fn fetch_markdown(
         .wait(domain, robots.crawl_delay().unwrap_or(DEFAULT_CRAWL_DELAY))
         .await;
 
+    let page = crate::ssrf::fetch(&ctx.http, url).await?;
+    // The markup of the wall is gone once the article is extracted
+    let html = page.body.clone();
+
     let article = {
-        // `ArticleScraper::parse` can panic internally, so run it in a separate task
+        // The parser can panic internally, so run it in a separate task
         // possibly related: https://gitlab.com/news-flash/article_scraper/-/issues/9
-        let url = url.clone();
-        let ctx = ctx.clone();
         tokio::spawn(async move {
-            article_scraper::ArticleScraper::new(None)
-                .await
-                .parse(&url, &ctx.http)
+            article_scraper::FullTextParser::new(None)
                 .await
+                .parse_offline(vec![page.body], None, Some(page.url))
         })
         .await??
     };
@@ -444,28 +754,114 @@ async fn fetch_markdown(
     .content
     .ok_or_else(|| eyre!("html to markdown conversion produced no content"))?;
 
-    Ok((article.title, markdown))
+    let paywalled = super::paywall::is_paywalled(
+        url,
+        &html,
+        article.title.as_deref().unwrap_or_default(),
+        &markdown,
+    );
+    Ok(ArticlePage {
+        title: article.title,
+        markdown,
+        paywalled,
+    })
 }
 
-async fn fetch_lobsters(ctx: &App) -> Result<Vec<SourceEntry>, eyre::Error> {
-    #[derive(Deserialize)]
-    struct LobstersEntry {
-        short_id: String,
-        title: String,
-        url: String,
-        score: i64,
-        created_at: String,
+/// Markdown of the HTML text of a submission or comment, none if it's empty
+pub(super) fn submission_text(html: &str) -> Option<String> {
+    if html.trim().is_empty() {
+        return None;
     }
+    html_to_markdown_rs::convert(html, None)
+        .inspect_err(|err| tracing::warn!(?err, "Failed to convert a submission text"))
+        .ok()?
+        .content
+        .filter(|text| !text.trim().is_empty())
+}
 
+/// The enabled sources due for a crawl, all of them if the owner asked for it
+async fn sources_to_crawl(
+    ctx: &App,
+    trigger: CrawlTrigger,
+) -> Result<Vec<OnlineArticleSource>, eyre::Error> {
+    let mut sources = crawled_sources(ctx).await?;
+    let default_interval = ctx.tunables.get().crawl_interval;
+    let now = chrono::Utc::now().naive_utc();
+    sources.retain(|source| {
+        source.enabled
+            && (trigger == CrawlTrigger::Admin || next_crawl_at(source, default_interval) <= now)
+    });
+    Ok(sources)
+}
+
+/// The sources the crawler takes entries from, Lobsters and Hacker News are
+/// created on the first crawl and the feeds are imported by the owner
+pub async fn crawled_sources(ctx: &App) -> Result<Vec<OnlineArticleSource>, eyre::Error> {
     let conn = &mut ctx.diesel.get().await?;
-    let lobsters_source_id =
-        get_or_create_source(conn, "lobsters", "Lobsters", Some("https://lobste.rs/")).await?;
-    let url = "https://lobste.rs/hottest.json";
+    let ids = [
+        get_or_create_source(conn, LOBSTERS_KEY, "Lobsters", Some("https://lobste.rs/")).await?,
+        get_or_create_source(
+            conn,
+            HACKER_NEWS_KEY,
+            "Hacker News",
+            Some("https://news.ycombinator.com/"),
+        )
+        .await?,
+    ];
+
+    Ok(online_article_sources::table
+        .filter(
+            online_article_sources::id
+                .eq_any(ids)
+                .or(online_article_sources::feed_url.is_not_null()),
+        )
+        .select(OnlineArticleSource::as_select())
+        .order(online_article_sources::id)
+        .load(conn)
+        .await?)
+}
+
+/// When the source is due for its next crawl, right away if it never was
+pub fn next_crawl_at(
+    source: &OnlineArticleSource,
+    default_interval: Duration,
+) -> chrono::NaiveDateTime {
+    let interval = source
+        .crawl_interval_mins
+        .and_then(|mins| u64::try_from(mins).ok())
+        .map(Duration::from_mins)
+        .unwrap_or(default_interval);
+
+    match source.last_crawled_at {
+        Some(last) => chrono::Duration::from_std(interval)
+            .ok()
+            .and_then(|interval| last.checked_add_signed(interval))
+            .unwrap_or(chrono::NaiveDateTime::MAX),
+        None => chrono::NaiveDateTime::MIN,
+    }
+}
+
+fn max_items(source: &OnlineArticleSource, default: usize) -> usize {
+    source
+        .max_items
+        .and_then(|n| usize::try_from(n).ok())
+        .unwrap_or(default)
+}
 
+async fn lobsters_entries(
+    lobsters: &dyn LobstersClient,
+    lobsters_source_id: i32,
+    max_items: usize,
+) -> Result<Vec<SourceEntry>, eyre::Error> {
     let mut entries = Vec::new();
-    for page in 1..=2 {
-        let response = ctx.http.get(format!("{url}/?page={page}")).send().await?;
-        let resp: Vec<LobstersEntry> = response.json().await?;
+    for page in 1..=MAX_LOBSTERS_PAGES {
+        if entries.len() >= max_items {
+            break;
+        }
+        let resp = lobsters.hottest(page).await?;
+        if resp.is_empty() {
+            break;
+        }
 
         let new_entries = resp
             .into_iter()
@@ -488,52 +884,40 @@ async fn fetch_lobsters(ctx: &App) -> Result<Vec<SourceEntry>, eyre::Error> {
                     external_score: Some(entry.score as f64),
                     submitted_at,
                     external_id: entry.short_id,
+                    submission_text: submission_text(&entry.description),
+                    comment_count: Some(entry.comment_count),
                 })
             })
             .collect::<Vec<_>>();
 
         entries.extend(new_entries);
     }
+    entries.truncate(max_items);
 
     Ok(entries)
 }
 
-async fn fetch_hackernews(ctx: &App) -> Result<Vec<SourceEntry>, eyre::Error> {
-    #[derive(Deserialize)]
-    struct HNItem {
-        title: String,
-        url: Option<String>,
-        score: i64,
-        r#type: String,
-        time: i64,
-    }
+async fn hackernews_entries(
+    hacker_news: &dyn HackerNewsClient,
+    hn_source_id: i32,
+    max_items: usize,
+) -> Result<Vec<SourceEntry>, eyre::Error> {
+    let top_story_ids = hacker_news.top_stories().await?;
+    let items = futures::stream::iter(top_story_ids.into_iter().take(max_items))
+        .map(|story_id| hacker_news.item(story_id))
+        .buffered(MAX_CONCURRENT_HACKER_NEWS_ITEMS)
+        .collect::<Vec<_>>()
+        .await;
 
-    let conn = &mut ctx.diesel.get().await?;
-    let hn_source_id = get_or_create_source(
-        conn,
-        "hacker-news",
-        "Hacker News",
-        Some("https://news.ycombinator.com/"),
-    )
-    .await?;
-
-    let top_stories_resp = ctx
-        .http
-        .get("https://hacker-news.firebaseio.com/v0/topstories.json")
-        .send()
-        .await?;
-    let top_story_ids: Vec<i64> = top_stories_resp.json().await?;
     let mut entries = Vec::new();
-    for story_id in top_story_ids.into_iter().take(64) {
-        let item_resp = ctx
-            .http
-            .get(format!(
-                "https://hacker-news.firebaseio.com/v0/item/{}.json",
-                story_id
-            ))
-            .send()
-            .await?;
-        let item: HNItem = item_resp.json().await?;
+    for item in items {
+        let item = match item {
+            Ok(item) => item,
+            Err(err) => {
+                tracing::warn!(?err, "Failed to fetch a Hacker News item");
+                continue;
+            }
+        };
 
         if item.r#type != "story" {
             continue;
@@ -557,10 +941,81 @@ async fn fetch_hackernews(ctx: &App) -> Result<Vec<SourceEntry>, eyre::Error> {
                     url,
                     external_score: Some(item.score as f64),
                     submitted_at,
-                    external_id: story_id.to_string(),
+                    external_id: item.id.to_string(),
+                    submission_text: item.text.as_deref().and_then(submission_text),
+                    comment_count: Some(item.descendants.unwrap_or_default()),
                 });
             }
         }
     }
     Ok(entries)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clients::{HackerNewsItem, offline::OfflineHackerNews};
+
+    #[tokio::test]
+    async fn hackernews_entries_keep_stories_with_links() {
+        let item = |id: i64, r#type: &str, url: Option<&str>| HackerNewsItem {
+            id,
+            title: format!("Item {id}"),
+            url: url.map(str::to_string),
+            score: 100,
+            r#type: r#type.to_string(),
+            time: 1_760_000_000,
+            ..Default::default()
+        };
+        let hacker_news = OfflineHackerNews {
+            items: vec![
+                item(1, "story", Some("https://example.com/a")),
+                item(2, "job", Some("https://example.com/jobs")),
+                // Ask HN posts have no link
+                item(3, "story", None),
+                item(4, "story", Some("ftp://example.com/file")),
+            ],
+        };
+
+        let entries = hackernews_entries(&hacker_news, 7, DEFAULT_HACKER_NEWS_ITEMS)
+            .await
+            .expect("entries");
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].source_id, 7);
+        assert_eq!(entries[0].external_id, "1");
+        assert_eq!(entries[0].url.as_str(), "https://example.com/a");
+    }
+
+    #[test]
+    fn sources_are_due_after_their_interval() {
+        let crawled_at = chrono::DateTime::from_timestamp(1_760_000_000, 0)
+            .expect("timestamp")
+            .naive_utc();
+        let source = |crawl_interval_mins, last_crawled_at| OnlineArticleSource {
+            id: 1,
+            key: LOBSTERS_KEY.to_string(),
+            name: "Lobsters".to_string(),
+            base_url: None,
+            created_at: crawled_at,
+            enabled: true,
+            crawl_interval_mins,
+            max_items: None,
+            last_crawled_at,
+            feed_url: None,
+        };
+        let default_interval = Duration::from_hours(8);
+
+        assert_eq!(
+            next_crawl_at(&source(None, None), default_interval),
+            chrono::NaiveDateTime::MIN
+        );
+        assert_eq!(
+            next_crawl_at(&source(None, Some(crawled_at)), default_interval),
+            crawled_at + chrono::Duration::hours(8)
+        );
+        assert_eq!(
+            next_crawl_at(&source(Some(30), Some(crawled_at)), default_interval),
+            crawled_at + chrono::Duration::minutes(30)
+        );
+    }
+}
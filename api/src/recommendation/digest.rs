@@ -0,0 +1,374 @@
+//! The digest of the feed, for catching up on a period rather than browsing
+//! as the articles come. The articles of the period are ranked by their
+//! scores and the history without decaying the older ones, the ones the
+//! reader already read are left out, and the rest are grouped by topic.
+
+use std::collections::{HashMap, HashSet};
+
+use axum::{
+    Json, Router,
+    extract::{Query, State},
+    http::{StatusCode, header},
+    response::IntoResponse,
+    routing::get,
+};
+use diesel::prelude::*;
+use diesel::sql_types::{Array, Bool, Float8, Integer, Jsonb, Nullable, Text, Timestamp};
+use diesel_async::RunQueryDsl;
+use serde::Deserialize;
+use utoipa::IntoParams;
+
+use crate::{App, error::AppError, identity::MaybeAuthUser, utils::RECOMMENDER_EMBEDDING_BITS};
+
+use super::{
+    COMMENT_COUNT_WEIGHT, DigestItem, DigestPeriod, DigestTopic, FeedDigest, FeedItemSummary,
+    SourceInfo, history_owner_for, recommender_terms_for_article,
+};
+
+const DEFAULT_DIGEST_ITEMS: u32 = 30;
+const MAX_DIGEST_ITEMS: u32 = 100;
+/// The best scored articles of the period that are ranked by similarity too
+const DIGEST_CANDIDATE_POOL: i64 = 300;
+/// RRF k constants, the same for both signals since neither is decayed
+const SIMILARITY_K: f64 = 20.0;
+const EXTERNAL_K: f64 = 20.0;
+/// Terms an article shares with the first article of a topic to be in it
+const MIN_SHARED_TERMS: usize = 3;
+const MAX_LABEL_TERMS: usize = 2;
+
+pub fn route() -> Router<App> {
+    Router::<App>::new().route("/feed/digest", get(get_digest))
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct DigestQuery {
+    #[serde(default)]
+    period: DigestPeriod,
+    /// The articles in the digest, 30 by default and at most 100
+    limit: Option<u32>,
+    /// Comma separated ISO 639-3 codes of the languages, as in the feed
+    lang: Option<String>,
+}
+
+#[derive(QueryableByName, Debug)]
+struct DigestRow {
+    #[diesel(sql_type = Integer)]
+    id: i32,
+    #[diesel(sql_type = Text)]
+    title: String,
+    #[diesel(sql_type = Text)]
+    url: String,
+    #[diesel(sql_type = Nullable<Timestamp>)]
+    submitted_at: Option<chrono::NaiveDateTime>,
+    #[diesel(sql_type = Float8)]
+    score: f64,
+    #[diesel(sql_type = Nullable<Jsonb>)]
+    sources: Option<serde_json::Value>,
+    #[diesel(sql_type = Nullable<Jsonb>)]
+    recommender_terms: Option<serde_json::Value>,
+    #[diesel(sql_type = Bool)]
+    paywalled: bool,
+    #[diesel(sql_type = Nullable<Text>)]
+    summary: Option<String>,
+    #[diesel(sql_type = Nullable<Jsonb>)]
+    takeaways: Option<serde_json::Value>,
+    #[diesel(sql_type = Nullable<Timestamp>)]
+    summarized_at: Option<chrono::NaiveDateTime>,
+}
+
+/// The top articles submitted in the period, without the ones in the history
+/// of the reader and, for the owner, the ones clicked in the feed. Grouped by
+/// topic, with the summaries of the articles that have one.
+#[utoipa::path(
+    get,
+    path = "/feed/digest",
+    tag = "recommendation",
+    params(DigestQuery),
+    responses(
+        (status = 200, description = "The digest", body = FeedDigest),
+        (status = 400, description = "Unknown language code"),
+    ),
+)]
+pub async fn get_digest(
+    State(ctx): State<App>,
+    Query(query): Query<DigestQuery>,
+    auth_user: MaybeAuthUser,
+) -> Result<impl IntoResponse, AppError> {
+    let limit = query
+        .limit
+        .unwrap_or(DEFAULT_DIGEST_ITEMS)
+        .clamp(1, MAX_DIGEST_ITEMS);
+    let languages = match &query.lang {
+        Some(lang) => crate::utils::parse_languages(lang)
+            .ok_or(("Unknown language code", StatusCode::BAD_REQUEST))?,
+        None => ctx.config.recommender_feed_languages.clone(),
+    };
+    let since = chrono::Utc::now().naive_utc() - chrono::Days::new(query.period.days());
+
+    let reader = auth_user.0.ok().map(|identity| identity.id);
+    let history_owner = match reader {
+        Some(id) => history_owner_for(&ctx, id).await?,
+        None => None,
+    };
+    // The clicks aren't recorded per reader, they are the owner's for the
+    // most part
+    let exclude_clicked = reader == Some(ctx.config.owner_identity_id);
+
+    let mut conn = ctx.diesel.get().await?;
+    let rows = diesel::sql_query(format!(
+        r#"
+        WITH history_chunks AS (
+            SELECT
+                hc.embedding,
+                COALESCE(uh.weight, 0.1) AS weight
+            FROM user_history uh
+            JOIN online_article_chunks hc ON hc.online_article_id = uh.online_article_id
+            WHERE uh.identity_id IS NOT DISTINCT FROM $2
+        ),
+        period_items AS (
+            SELECT
+                i.id,
+                MIN(m.submitted_at) AS submitted_at,
+                SUM(
+                    LN(COALESCE(m.external_score, 0.0) + 1.0)
+                    + {COMMENT_COUNT_WEIGHT} * LN(COALESCE((m.metadata->>'comment_count')::FLOAT8, 0.0) + 1.0)
+                ) AS log_external_score
+            FROM online_articles i
+            JOIN online_article_metadata m ON m.online_article_id = i.id
+            WHERE m.submitted_at >= $1
+            AND NOT EXISTS (
+                SELECT 1 FROM user_history uh
+                WHERE uh.online_article_id = i.id AND uh.identity_id IS NOT DISTINCT FROM $2
+            )
+            AND NOT ($4 AND EXISTS (SELECT 1 FROM feed_clicks c WHERE c.online_article_id = i.id))
+            AND (i.link_status <> 'dead' OR i.archive_url IS NOT NULL)
+            AND (CARDINALITY($3::TEXT[]) = 0 OR i.lang IS NULL OR i.lang = ANY($3))
+            GROUP BY i.id
+        ),
+        candidates AS (
+            SELECT
+                id,
+                submitted_at,
+                ROW_NUMBER() OVER (ORDER BY log_external_score DESC, id DESC) AS external_rank
+            FROM period_items
+            ORDER BY external_rank
+            LIMIT {DIGEST_CANDIDATE_POOL}
+        ),
+        item_similarities AS (
+            SELECT
+                c.id AS online_article_id,
+                COALESCE(MAX((
+                    1.0
+                    - ((cc.embedding <~> hc.embedding) / {RECOMMENDER_EMBEDDING_BITS}.0)
+                ) * hc.weight) FILTER (WHERE hc.weight > 0), 0.0)
+                - COALESCE(MAX((
+                    1.0
+                    - ((cc.embedding <~> hc.embedding) / {RECOMMENDER_EMBEDDING_BITS}.0)
+                ) * -hc.weight) FILTER (WHERE hc.weight < 0), 0.0) AS similarity
+            FROM candidates c
+            JOIN online_article_chunks cc ON cc.online_article_id = c.id
+            CROSS JOIN history_chunks hc
+            GROUP BY c.id
+        ),
+        similarity_ranked AS (
+            SELECT
+                online_article_id,
+                ROW_NUMBER() OVER (ORDER BY similarity DESC NULLS LAST) AS rank
+            FROM item_similarities
+        ),
+        ranked AS (
+            SELECT
+                c.id,
+                c.submitted_at,
+                (
+                    COALESCE(1.0 / ({SIMILARITY_K} + sr.rank), 0.0)
+                    + 1.0 / ({EXTERNAL_K} + c.external_rank)
+                )::FLOAT8 AS score
+            FROM candidates c
+            LEFT JOIN similarity_ranked sr ON sr.online_article_id = c.id
+            ORDER BY score DESC, c.id DESC
+            LIMIT $5
+        )
+        SELECT
+            r.id,
+            COALESCE(
+                (SELECT im.metadata->>'editorialized_title'
+                 FROM online_article_metadata im
+                 WHERE im.online_article_id = r.id
+                   AND im.metadata->>'editorialized_title' IS NOT NULL
+                 ORDER BY im.submitted_at
+                 LIMIT 1),
+                oa.title
+            ) AS title,
+            oa.url,
+            r.submitted_at,
+            r.score,
+            (SELECT JSONB_AGG(JSONB_BUILD_OBJECT(
+                'key', s.key,
+                'score', im.external_score,
+                'external_id', im.metadata->>'external_id'
+            ))
+            FROM online_article_metadata im
+            JOIN online_article_sources s ON s.id = im.source_id
+            WHERE im.online_article_id = r.id) AS sources,
+            oa.recommender_terms,
+            oa.paywalled,
+            os.summary,
+            os.takeaways,
+            os.created_at AS summarized_at
+        FROM ranked r
+        JOIN online_articles oa ON oa.id = r.id
+        LEFT JOIN online_article_summaries os ON os.online_article_id = r.id
+        ORDER BY r.score DESC, r.id DESC
+    "#
+    ))
+    .bind::<Timestamp, _>(since)
+    .bind::<Nullable<Integer>, _>(history_owner)
+    .bind::<Array<Text>, _>(&languages)
+    .bind::<Bool, _>(exclude_clicked)
+    .bind::<Integer, _>(limit as i32)
+    .load::<DigestRow>(&mut conn)
+    .await?;
+
+    let items = rows
+        .into_iter()
+        .map(|row| {
+            let terms = recommender_terms_for_article(&row.title, row.recommender_terms.as_ref());
+            let summary = row
+                .summary
+                .zip(row.summarized_at)
+                .map(|(summary, generated_at)| FeedItemSummary {
+                    id: row.id,
+                    summary,
+                    takeaways: row
+                        .takeaways
+                        .and_then(|value| serde_json::from_value(value).ok())
+                        .unwrap_or_default(),
+                    generated_at,
+                });
+            let item = DigestItem {
+                id: row.id,
+                title: row.title,
+                url: row.url,
+                score: row.score,
+                submitted_at: row.submitted_at,
+                sources: row
+                    .sources
+                    .and_then(|value| serde_json::from_value::<Vec<SourceInfo>>(value).ok())
+                    .unwrap_or_default(),
+                paywalled: row.paywalled,
+                summary,
+            };
+            (item, terms)
+        })
+        .collect();
+
+    Ok((
+        [
+            // Personalized to the session
+            (header::CACHE_CONTROL, "private"),
+            (header::VARY, "Cookie"),
+        ],
+        Json(FeedDigest {
+            period: query.period,
+            since,
+            topics: group_by_topic(items),
+        }),
+    ))
+}
+
+/// Groups the items, best first, with the first topic whose first item shares
+/// enough terms with them. The items that share them with no other are
+/// grouped last without a label.
+fn group_by_topic(items: Vec<(DigestItem, HashSet<String>)>) -> Vec<DigestTopic> {
+    let mut topics: Vec<Vec<(DigestItem, HashSet<String>)>> = Vec::new();
+    for (item, terms) in items {
+        let topic = topics
+            .iter_mut()
+            .find(|topic| topic[0].1.intersection(&terms).count() >= MIN_SHARED_TERMS);
+        match topic {
+            Some(topic) => topic.push((item, terms)),
+            None => topics.push(vec![(item, terms)]),
+        }
+    }
+
+    let (topics, singles): (Vec<_>, Vec<_>) = topics.into_iter().partition(|t| t.len() > 1);
+    let mut grouped = topics
+        .into_iter()
+        .map(|topic| DigestTopic {
+            label: topic_label(topic.iter().map(|(_, terms)| terms)),
+            items: topic.into_iter().map(|(item, _)| item).collect(),
+        })
+        .collect::<Vec<_>>();
+    if !singles.is_empty() {
+        grouped.push(DigestTopic {
+            label: None,
+            items: singles
+                .into_iter()
+                .flatten()
+                .map(|(item, _)| item)
+                .collect(),
+        });
+    }
+    grouped
+}
+
+/// The terms in the most of the articles, alphabetically among as many
+fn topic_label<'a>(terms: impl Iterator<Item = &'a HashSet<String>>) -> Option<String> {
+    let mut counts = HashMap::<&str, usize>::new();
+    for term in terms.flatten() {
+        *counts.entry(term.as_str()).or_default() += 1;
+    }
+
+    let mut counts = counts
+        .into_iter()
+        .filter(|(_, count)| *count > 1)
+        .collect::<Vec<_>>();
+    counts.sort_by(|(left, left_count), (right, right_count)| {
+        right_count.cmp(left_count).then_with(|| left.cmp(right))
+    });
+    let label = counts
+        .into_iter()
+        .take(MAX_LABEL_TERMS)
+        .map(|(term, _)| term)
+        .collect::<Vec<_>>();
+    (!label.is_empty()).then(|| label.join(", "))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn item(id: i32, terms: &[&str]) -> (DigestItem, HashSet<String>) {
+        let item = DigestItem {
+            id,
+            title: String::new(),
+            url: String::new(),
+            score: 0.0,
+            submitted_at: None,
+            sources: Vec::new(),
+            paywalled: false,
+            summary: None,
+        };
+        (item, terms.iter().map(|term| term.to_string()).collect())
+    }
+
+    #[test]
+    fn items_sharing_terms_are_grouped_under_them() {
+        let topics = group_by_topic(vec![
+            item(1, &["rust", "compiler", "llvm", "speed"]),
+            item(2, &["postgres", "index", "vacuum"]),
+            item(3, &["rust", "compiler", "llvm", "borrow"]),
+            item(4, &["gardening"]),
+            item(5, &["rust", "compiler", "speed", "cranelift"]),
+        ]);
+
+        assert_eq!(topics.len(), 2);
+        assert_eq!(topics[0].label.as_deref(), Some("compiler, rust"));
+        let ids = |topic: &DigestTopic| topic.items.iter().map(|i| i.id).collect::<Vec<_>>();
+        assert_eq!(ids(&topics[0]), vec![1, 3, 5]);
+        assert_eq!(topics[1].label, None);
+        assert_eq!(ids(&topics[1]), vec![2, 4]);
+    }
+}
@@ -1,9 +1,13 @@
 use pgvector::Vector;
 use text_splitter::MarkdownSplitter;
 
-use crate::utils::embed_texts;
+use crate::embedding::Embedder;
 
-pub async fn generate_embeddings(title: &str, markdown: &str) -> Result<Vec<Vector>, eyre::Error> {
+pub async fn generate_embeddings(
+    embedder: &Embedder,
+    title: &str,
+    markdown: &str,
+) -> Result<Vec<Vector>, eyre::Error> {
     // AllMiniLML12V2 truncates input text longer than 256 tokens
     let splitter = MarkdownSplitter::new(512..768);
     let chunks: Vec<String> = if markdown.trim().is_empty() {
@@ -20,11 +24,20 @@ pub async fn generate_embeddings(title: &str, markdown: &str) -> Result<Vec<Vect
         return Ok(Vec::new());
     }
 
-    tokio::task::spawn_blocking(move || {
-        let embeddings = embed_texts(chunks).map_err(|err| eyre::eyre!(err))?;
-        let vectors = embeddings.into_iter().map(Vector::from).collect();
-        Ok::<_, eyre::Error>(vectors)
-    })
-    .await
-    .map_err(|err| eyre::eyre!(err))?
+    let embeddings = embedder.embed(chunks).await?;
+    Ok(embeddings.into_iter().map(Vector::from).collect())
+}
+
+/// One embedding of the top comments of the discussion of an article
+pub async fn generate_comment_embedding(
+    embedder: &Embedder,
+    title: &str,
+    summary: &str,
+) -> Result<Vector, eyre::Error> {
+    embedder
+        .embed(vec![format!("Title: {title}\nComments:\n{summary}")])
+        .await?
+        .pop()
+        .map(Vector::from)
+        .ok_or_else(|| eyre::eyre!("no embedding was generated for the comments"))
 }
@@ -0,0 +1,514 @@
+//! Imports of the export files of read-it-later services into the reader's
+//! history. The APIs of Pocket and Omnivore are gone since they shut down and
+//! Instapaper only grants API access on request, so their export files are
+//! the way in.
+
+use std::sync::LazyLock;
+
+use axum::{
+    Json, Router,
+    extract::{DefaultBodyLimit, Path, Query, State},
+    http::StatusCode,
+    routing::{get, post},
+};
+use diesel::prelude::*;
+use diesel_async::RunQueryDsl;
+use regex::Regex;
+use serde::Deserialize;
+use utoipa::{IntoParams, ToSchema};
+
+use crate::{
+    App,
+    config::BodyLimits,
+    error::AppError,
+    identity::AuthUser,
+    models::recommendation::HistoryImport as HistoryImportRow,
+    schema::{history_imports, user_history},
+};
+
+use super::{HistoryImport, HistorySource, UserHistorySource, insert_user_history};
+
+/// The oldest entries of bigger files are left out, each new article is
+/// fetched and embedded
+const MAX_IMPORT_ITEMS: usize = 2000;
+/// Saved articles aren't necessarily read, so they count less than the
+/// curated Raindrop collections by default
+const DEFAULT_WEIGHT: f64 = 0.4;
+
+/// A link of the HTML export of Pocket
+static POCKET_LINK: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#"(?is)<a\s[^>]*?href="([^"]+)"[^>]*>(.*?)</a>"#).expect("link regex is valid")
+});
+
+pub fn route(limits: &BodyLimits) -> Router<App> {
+    Router::<App>::new()
+        .route("/me/import", get(list_imports))
+        .route(
+            "/me/import/{source}",
+            post(import_history)
+                .layer(DefaultBodyLimit::max(limits.imports))
+                .put(update_import)
+                .delete(delete_import),
+        )
+}
+
+#[utoipa::path(
+    get,
+    path = "/me/import",
+    tag = "recommendation",
+    responses(
+        (status = 200, description = "The files the reader imported into their history", body = Vec<HistoryImport>),
+        (status = 401, description = "Not logged in"),
+    ),
+    security(("session" = [])),
+)]
+pub async fn list_imports(
+    State(ctx): State<App>,
+    AuthUser(i): AuthUser,
+) -> Result<Json<Vec<HistoryImport>>, AppError> {
+    let mut conn = ctx.diesel.get().await?;
+
+    let rows = history_imports::table
+        .filter(history_imports::identity_id.eq(i.id))
+        .select(HistoryImportRow::as_select())
+        .order(history_imports::imported_at.desc())
+        .load(&mut conn)
+        .await?;
+
+    Ok(Json(
+        rows.into_iter().filter_map(to_history_import).collect(),
+    ))
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ImportQuery {
+    /// How much the imported articles count, from 0 to 1
+    weight: Option<f64>,
+}
+
+/// The body is the export file as is: the HTML or CSV export of Pocket, the
+/// CSV export of Instapaper or the metadata JSON of an Omnivore export. The
+/// articles are added to the history in the background.
+#[utoipa::path(
+    post,
+    path = "/me/import/{source}",
+    tag = "recommendation",
+    params(("source" = HistorySource, Path), ImportQuery),
+    request_body(content = String, description = "The export file"),
+    responses(
+        (status = 202, description = "The file is being imported", body = HistoryImport),
+        (status = 400, description = "Not an export file of the source"),
+        (status = 401, description = "Not logged in"),
+    ),
+    security(("session" = [])),
+)]
+pub async fn import_history(
+    State(ctx): State<App>,
+    AuthUser(i): AuthUser,
+    Path(source): Path<String>,
+    Query(query): Query<ImportQuery>,
+    body: String,
+) -> Result<(StatusCode, Json<HistoryImport>), AppError> {
+    let source = importable_source(&source)?;
+    let weight = validate_weight(query.weight.unwrap_or(DEFAULT_WEIGHT))?;
+
+    let mut entries = match source {
+        HistorySource::Pocket => parse_pocket(&body),
+        HistorySource::Instapaper => parse_instapaper(&body),
+        HistorySource::Omnivore => parse_omnivore(&body),
+        HistorySource::Raindrop | HistorySource::Extension => {
+            Err("This source isn't imported from a file")
+        }
+    }
+    .map_err(|msg| (msg, StatusCode::BAD_REQUEST))?;
+    if entries.is_empty() {
+        return Err(("No articles found in the file", StatusCode::BAD_REQUEST).into());
+    }
+    entries.truncate(MAX_IMPORT_ITEMS);
+
+    let row = HistoryImportRow {
+        identity_id: i.id,
+        source: source.as_str().to_string(),
+        weight,
+        item_count: entries.len() as i32,
+        imported_at: chrono::Utc::now().naive_utc(),
+    };
+    let mut conn = ctx.diesel.get().await?;
+    diesel::insert_into(history_imports::table)
+        .values(&row)
+        .on_conflict((history_imports::identity_id, history_imports::source))
+        .do_update()
+        .set((
+            history_imports::weight.eq(row.weight),
+            history_imports::item_count.eq(row.item_count),
+            history_imports::imported_at.eq(row.imported_at),
+        ))
+        .execute(&mut conn)
+        .await?;
+
+    let sources = entries
+        .into_iter()
+        .map(|entry| UserHistorySource {
+            title: entry.title,
+            url: entry.url,
+            weight: Some(weight),
+        })
+        .collect();
+    let import_ctx = ctx.clone();
+    let identity_id = i.id;
+    tokio::spawn(async move {
+        let source_key = source.as_str();
+        match insert_user_history(&import_ctx, Some(identity_id), source, sources).await {
+            Ok(inserted) => {
+                tracing::info!(identity_id, source_key, inserted, "Imported history");
+            }
+            Err(err) => {
+                tracing::error!(?err, identity_id, source_key, "Failed to import history");
+            }
+        }
+    });
+
+    Ok((
+        StatusCode::ACCEPTED,
+        Json(HistoryImport {
+            source,
+            weight: row.weight,
+            item_count: row.item_count,
+            imported_at: row.imported_at,
+        }),
+    ))
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct ImportUpdate {
+    /// How much the imported articles count, from 0 to 1
+    weight: f64,
+}
+
+/// Changes the weight of the articles imported from the source
+#[utoipa::path(
+    put,
+    path = "/me/import/{source}",
+    tag = "recommendation",
+    params(("source" = HistorySource, Path)),
+    request_body = ImportUpdate,
+    responses(
+        (status = 200, description = "The weight is changed", body = HistoryImport),
+        (status = 404, description = "Nothing was imported from the source"),
+    ),
+    security(("session" = [])),
+)]
+pub async fn update_import(
+    State(ctx): State<App>,
+    AuthUser(i): AuthUser,
+    Path(source): Path<String>,
+    Json(update): Json<ImportUpdate>,
+) -> Result<Json<HistoryImport>, AppError> {
+    let source = importable_source(&source)?;
+    let weight = validate_weight(update.weight)?;
+
+    let mut conn = ctx.diesel.get().await?;
+    let row = diesel::update(
+        history_imports::table
+            .filter(history_imports::identity_id.eq(i.id))
+            .filter(history_imports::source.eq(source.as_str())),
+    )
+    .set(history_imports::weight.eq(weight))
+    .returning(HistoryImportRow::as_returning())
+    .get_result(&mut conn)
+    .await
+    .optional()?
+    .ok_or((
+        "Nothing was imported from this source",
+        StatusCode::NOT_FOUND,
+    ))?;
+
+    diesel::update(
+        user_history::table
+            .filter(user_history::identity_id.eq(i.id))
+            .filter(user_history::source.eq(source.as_str())),
+    )
+    .set(user_history::weight.eq(weight))
+    .execute(&mut conn)
+    .await?;
+
+    Ok(Json(
+        to_history_import(row).ok_or("imported history has an unknown source")?,
+    ))
+}
+
+/// Removes the articles imported from the source from the history
+#[utoipa::path(
+    delete,
+    path = "/me/import/{source}",
+    tag = "recommendation",
+    params(("source" = HistorySource, Path)),
+    responses((status = 204, description = "The imported articles are removed")),
+    security(("session" = [])),
+)]
+pub async fn delete_import(
+    State(ctx): State<App>,
+    AuthUser(i): AuthUser,
+    Path(source): Path<String>,
+) -> Result<StatusCode, AppError> {
+    let source = importable_source(&source)?;
+
+    let mut conn = ctx.diesel.get().await?;
+    diesel::delete(
+        user_history::table
+            .filter(user_history::identity_id.eq(i.id))
+            .filter(user_history::source.eq(source.as_str())),
+    )
+    .execute(&mut conn)
+    .await?;
+    diesel::delete(
+        history_imports::table
+            .filter(history_imports::identity_id.eq(i.id))
+            .filter(history_imports::source.eq(source.as_str())),
+    )
+    .execute(&mut conn)
+    .await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+fn importable_source(source: &str) -> Result<HistorySource, AppError> {
+    match HistorySource::parse(source) {
+        Some(
+            source @ (HistorySource::Pocket | HistorySource::Instapaper | HistorySource::Omnivore),
+        ) => Ok(source),
+        Some(HistorySource::Raindrop) => Err((
+            "Raindrop is linked from the settings rather than imported",
+            StatusCode::BAD_REQUEST,
+        )
+            .into()),
+        Some(HistorySource::Extension) => Err((
+            "Pages are saved one by one from the extension",
+            StatusCode::BAD_REQUEST,
+        )
+            .into()),
+        None => Err(("Unknown source", StatusCode::NOT_FOUND).into()),
+    }
+}
+
+fn validate_weight(weight: f64) -> Result<f64, AppError> {
+    if !(0.0..=1.0).contains(&weight) {
+        return Err(("The weight must be from 0 to 1", StatusCode::BAD_REQUEST).into());
+    }
+    Ok(weight)
+}
+
+fn to_history_import(row: HistoryImportRow) -> Option<HistoryImport> {
+    Some(HistoryImport {
+        source: HistorySource::parse(&row.source)?,
+        weight: row.weight,
+        item_count: row.item_count,
+        imported_at: row.imported_at,
+    })
+}
+
+#[derive(Debug, PartialEq)]
+struct ImportedEntry {
+    url: url::Url,
+    title: Option<String>,
+}
+
+impl ImportedEntry {
+    /// `None` for the entries that aren't web pages
+    fn new(url: &str, title: Option<&str>) -> Option<Self> {
+        let url = url::Url::parse(url.trim()).ok()?;
+        if !matches!(url.scheme(), "http" | "https") {
+            return None;
+        }
+        Some(ImportedEntry {
+            url,
+            title: title
+                .map(|t| t.trim().to_string())
+                .filter(|t| !t.is_empty()),
+        })
+    }
+}
+
+/// Pocket exported an HTML list of links until 2024, then a CSV file with
+/// `title,url,time_added,tags,status` columns
+fn parse_pocket(body: &str) -> Result<Vec<ImportedEntry>, &'static str> {
+    if !body.trim_start().starts_with('<') {
+        return parse_csv_export(body, "url", "title");
+    }
+
+    let entries = POCKET_LINK
+        .captures_iter(body)
+        .filter_map(|c| {
+            let title = unescape_html(&c[2]);
+            ImportedEntry::new(&unescape_html(&c[1]), Some(&title))
+        })
+        .collect::<Vec<_>>();
+    if entries.is_empty() {
+        return Err("Not a Pocket export");
+    }
+    Ok(entries)
+}
+
+/// Instapaper exports a CSV file with `URL,Title,Selection,Folder,Timestamp`
+/// columns
+fn parse_instapaper(body: &str) -> Result<Vec<ImportedEntry>, &'static str> {
+    parse_csv_export(body, "url", "title")
+}
+
+#[derive(Deserialize)]
+struct OmnivoreItem {
+    url: String,
+    title: Option<String>,
+    state: Option<String>,
+}
+
+/// The `metadata_*.json` files of an Omnivore export, arrays of the saved
+/// items
+fn parse_omnivore(body: &str) -> Result<Vec<ImportedEntry>, &'static str> {
+    let items =
+        serde_json::from_str::<Vec<OmnivoreItem>>(body).map_err(|_| "Not an Omnivore export")?;
+    Ok(items
+        .into_iter()
+        .filter(|item| {
+            !item
+                .state
+                .as_deref()
+                .is_some_and(|s| s.eq_ignore_ascii_case("deleted"))
+        })
+        .filter_map(|item| ImportedEntry::new(&item.url, item.title.as_deref()))
+        .collect())
+}
+
+fn parse_csv_export(
+    body: &str,
+    url_column: &str,
+    title_column: &str,
+) -> Result<Vec<ImportedEntry>, &'static str> {
+    let mut rows = parse_csv(body).into_iter();
+    let header = rows.next().ok_or("The file is empty")?;
+    let column = |name: &str| {
+        header
+            .iter()
+            .position(|h| h.trim().eq_ignore_ascii_case(name))
+    };
+    let url_index = column(url_column).ok_or("The file has no URL column")?;
+    let title_index = column(title_column);
+
+    Ok(rows
+        .filter_map(|row| {
+            ImportedEntry::new(
+                row.get(url_index)?,
+                title_index.and_then(|i| row.get(i)).map(String::as_str),
+            )
+        })
+        .collect())
+}
+
+/// RFC 4180 CSV: fields may be quoted, with `""` for a quote and line breaks
+/// inside the quotes
+fn parse_csv(body: &str) -> Vec<Vec<String>> {
+    let mut rows = Vec::new();
+    let mut row = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = body.trim_start_matches('\u{feff}').chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match (c, quoted) {
+            ('"', true) if chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            ('"', true) => quoted = false,
+            ('"', false) if field.is_empty() => quoted = true,
+            (',', false) => row.push(std::mem::take(&mut field)),
+            ('\r', false) => {}
+            ('\n', false) => {
+                row.push(std::mem::take(&mut field));
+                rows.push(std::mem::take(&mut row));
+            }
+            (c, _) => field.push(c),
+        }
+    }
+    if !field.is_empty() || !row.is_empty() {
+        row.push(field);
+        rows.push(row);
+    }
+
+    rows.retain(|row| row.iter().any(|field| !field.trim().is_empty()));
+    rows
+}
+
+fn unescape_html(s: &str) -> String {
+    s.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&#039;", "'")
+        .replace("&amp;", "&")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn urls(entries: &[ImportedEntry]) -> Vec<&str> {
+        entries.iter().map(|e| e.url.as_str()).collect()
+    }
+
+    #[test]
+    fn parses_pocket_html_exports() {
+        let entries = parse_pocket(
+            r#"<!DOCTYPE html><html><body>
+            <h1>Unread</h1>
+            <ul>
+            <li><a href="https://example.com/a?x=1&amp;y=2" time_added="1700000000" tags="">A &amp; B</a></li>
+            <li><a href="javascript:void(0)" time_added="1700000000" tags="">Not a page</a></li>
+            </ul>
+            <h1>Read Archive</h1>
+            <ul><li><a href="https://example.com/b" time_added="1600000000" tags="rust">https://example.com/b</a></li></ul>
+            </body></html>"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            urls(&entries),
+            ["https://example.com/a?x=1&y=2", "https://example.com/b"]
+        );
+        assert_eq!(entries[0].title.as_deref(), Some("A & B"));
+    }
+
+    #[test]
+    fn parses_pocket_and_instapaper_csv_exports() {
+        let pocket = parse_pocket(
+            "title,url,time_added,tags,status\n\"Hello, world\",https://example.com/a,1700000000,,unread\n",
+        )
+        .unwrap();
+        assert_eq!(urls(&pocket), ["https://example.com/a"]);
+        assert_eq!(pocket[0].title.as_deref(), Some("Hello, world"));
+
+        let instapaper = parse_instapaper(
+            "URL,Title,Selection,Folder,Timestamp\r\nhttps://example.com/b,\"Say \"\"hi\"\"\",\"multi\nline\",Archive,1700000000\r\n",
+        )
+        .unwrap();
+        assert_eq!(urls(&instapaper), ["https://example.com/b"]);
+        assert_eq!(instapaper[0].title.as_deref(), Some("Say \"hi\""));
+
+        assert!(parse_instapaper("Title,Folder\nA,Unread\n").is_err());
+    }
+
+    #[test]
+    fn parses_omnivore_exports() {
+        let entries = parse_omnivore(
+            r#"[
+                {"id": "1", "url": "https://example.com/a", "title": "A", "state": "Archived"},
+                {"id": "2", "url": "https://example.com/b", "title": "B", "state": "Deleted"}
+            ]"#,
+        )
+        .unwrap();
+
+        assert_eq!(urls(&entries), ["https://example.com/a"]);
+        assert!(parse_omnivore("not json").is_err());
+    }
+}
@@ -0,0 +1,477 @@
+//! Re-checking the links of the stored articles. Links that keep failing are
+//! marked dead and looked up on the Wayback Machine, and the pages that changed
+//! much since they were embedded are embedded again.
+
+use std::{collections::HashSet, time::Duration};
+
+use axum::{
+    Json, Router,
+    extract::{Path, State},
+    http::StatusCode,
+    routing::get,
+};
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+use diesel_async::{AsyncConnection as _, RunQueryDsl};
+use futures::StreamExt as _;
+use serde::Deserialize;
+
+use crate::{
+    App,
+    error::AppError,
+    jobs,
+    models::recommendation::OnlineArticle,
+    schema::{
+        online_article_chunks, online_article_metadata, online_article_sources, online_articles,
+    },
+};
+
+use super::{
+    FeedItemDetail, LinkStatus, SourceInfo, comments, crawler, engine::generate_embeddings,
+    parse_recommender_terms_json,
+};
+
+const CHECK_INTERVAL: Duration = Duration::from_hours(1);
+const CHECK_BATCH_SIZE: i64 = 50;
+/// Alive links are checked again after this many days
+const RECHECK_AFTER_DAYS: u64 = 30;
+/// The first retry of a failed link is after an hour, each next one waits 4
+/// times longer, up to a week
+const FAILURE_BACKOFF_BASE_HOURS: i64 = 1;
+const FAILURE_BACKOFF_FACTOR: i64 = 4;
+const MAX_FAILURE_BACKOFF_HOURS: i64 = 7 * 24;
+/// A link failing this many checks in a row, over about 3 days, is dead
+const DEAD_AFTER_FAILURES: i32 = 4;
+/// The page is embedded again if it keeps less of its terms than this
+const MIN_TERM_OVERLAP: f64 = 0.5;
+const WAYBACK_AVAILABILITY_URL: &str = "https://archive.org/wayback/available";
+
+pub fn route() -> Router<App> {
+    Router::<App>::new().route("/feed/items/{id}", get(get_feed_item))
+}
+
+/// Checks the links that are due every hour
+pub fn start_link_checker(ctx: App) {
+    jobs::spawn_exclusive(&ctx.config.clone(), "link-check", move || {
+        let ctx = ctx.clone();
+        async move {
+            let mut interval = tokio::time::interval(CHECK_INTERVAL);
+            interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+            loop {
+                interval.tick().await;
+
+                match check_due_links(&ctx).await {
+                    Ok(0) => {}
+                    Ok(checked) => tracing::debug!(checked, "Checked article links"),
+                    Err(err) => tracing::error!(?err, "Failed to check article links"),
+                }
+            }
+        }
+    });
+}
+
+/// Checks a batch of the links due, the never checked ones first. Returns
+/// the number of checked links.
+async fn check_due_links(ctx: &App) -> Result<usize, eyre::Error> {
+    let articles = {
+        let mut conn = ctx.diesel.get_unbounded().await?;
+        online_articles::table
+            .filter(
+                online_articles::link_next_check_at
+                    .is_null()
+                    .or(online_articles::link_next_check_at.le(diesel::dsl::now)),
+            )
+            .order((
+                online_articles::link_next_check_at.is_not_null(),
+                online_articles::link_next_check_at.asc(),
+            ))
+            .limit(CHECK_BATCH_SIZE)
+            .select(OnlineArticle::as_select())
+            .load(&mut conn)
+            .await?
+    };
+
+    let checked = articles.len();
+    futures::stream::iter(articles)
+        .map(|article| {
+            let ctx = ctx.clone();
+            async move {
+                let id = article.id;
+                let _ = check_article(&ctx, article)
+                    .await
+                    .inspect_err(|err| tracing::warn!(?err, id, "Failed to check an article link"));
+            }
+        })
+        .buffer_unordered(crawler::MAX_CONCURRENT_FETCHES)
+        .collect::<Vec<_>>()
+        .await;
+
+    Ok(checked)
+}
+
+/// What a check of a link found
+#[derive(Debug)]
+enum LinkCheck {
+    /// With the ETag or Last-Modified of the page, if any
+    Alive(Option<String>),
+    /// Blocked or rate limited, the page is likely there
+    Inconclusive,
+    Failed(String),
+}
+
+async fn check_article(ctx: &App, article: OnlineArticle) -> Result<(), eyre::Error> {
+    let url = url::Url::parse(&article.url)?;
+    let now = chrono::Utc::now().naive_utc();
+
+    match check_link(ctx, &url).await {
+        LinkCheck::Alive(validator) => {
+            let refreshed = if needs_comparison(&article, validator.as_deref(), now) {
+                refresh_content(ctx, &article, &url)
+                    .await
+                    .inspect_err(|err| {
+                        tracing::warn!(?err, id = article.id, "Failed to refresh an article")
+                    })
+                    .unwrap_or(false)
+            } else {
+                false
+            };
+
+            let mut conn = ctx.diesel.get_unbounded().await?;
+            diesel::update(online_articles::table.find(article.id))
+                .set((
+                    online_articles::link_status.eq(LinkStatus::Alive.as_str()),
+                    online_articles::link_failures.eq(0),
+                    online_articles::link_checked_at.eq(now),
+                    online_articles::link_next_check_at.eq(next_check_at(now, 0)),
+                    online_articles::link_validator.eq(validator),
+                    online_articles::content_refreshed_at
+                        .eq(refreshed.then_some(now).or(article.content_refreshed_at)),
+                ))
+                .execute(&mut conn)
+                .await?;
+        }
+        LinkCheck::Inconclusive => {
+            let mut conn = ctx.diesel.get_unbounded().await?;
+            diesel::update(online_articles::table.find(article.id))
+                .set(online_articles::link_next_check_at.eq(next_check_at(now, 0)))
+                .execute(&mut conn)
+                .await?;
+        }
+        LinkCheck::Failed(reason) => {
+            tracing::debug!(id = article.id, reason, "Article link failed");
+            let failures = article.link_failures.saturating_add(1);
+            let status = if failures >= DEAD_AFTER_FAILURES {
+                LinkStatus::Dead
+            } else {
+                LinkStatus::Failing
+            };
+            let archive_url = match (status, article.archive_url) {
+                (LinkStatus::Dead, None) => archived_copy(ctx, &url)
+                    .await
+                    .inspect_err(|err| tracing::warn!(?err, "Failed to look up an archived copy"))
+                    .unwrap_or_default(),
+                (_, archive_url) => archive_url,
+            };
+
+            let mut conn = ctx.diesel.get_unbounded().await?;
+            diesel::update(online_articles::table.find(article.id))
+                .set((
+                    online_articles::link_status.eq(status.as_str()),
+                    online_articles::link_failures.eq(failures),
+                    online_articles::link_checked_at.eq(now),
+                    online_articles::link_next_check_at.eq(next_check_at(now, failures)),
+                    online_articles::archive_url.eq(archive_url),
+                ))
+                .execute(&mut conn)
+                .await?;
+        }
+    }
+
+    Ok(())
+}
+
+/// HEAD the page, or GET it from the servers that don't allow HEAD
+async fn check_link(ctx: &App, url: &url::Url) -> LinkCheck {
+    let response = async {
+        crate::ssrf::check_url(url).await?;
+        if let Some(host) = url.host_str() {
+            ctx.recommendation
+                .site_limiter
+                .wait(host, crawler::DEFAULT_CRAWL_DELAY)
+                .await;
+        }
+
+        let response = ctx.http.public_only().head(url.clone()).send().await?;
+        if matches!(
+            response.status(),
+            reqwest::StatusCode::METHOD_NOT_ALLOWED | reqwest::StatusCode::NOT_IMPLEMENTED
+        ) {
+            return Ok(ctx.http.public_only().get(url.clone()).send().await?);
+        }
+        Ok::<_, eyre::Error>(response)
+    }
+    .await;
+
+    match response {
+        Ok(response) => match response.status().as_u16() {
+            200..=399 => {
+                let headers = response.headers();
+                let validator = headers
+                    .get(reqwest::header::ETAG)
+                    .or_else(|| headers.get(reqwest::header::LAST_MODIFIED))
+                    .and_then(|v| v.to_str().ok())
+                    .map(str::to_string);
+                LinkCheck::Alive(validator)
+            }
+            401 | 403 | 429 => LinkCheck::Inconclusive,
+            status => LinkCheck::Failed(format!("status {status}")),
+        },
+        Err(err) => LinkCheck::Failed(format!("{err:#}")),
+    }
+}
+
+/// When the link is checked next, later after each failure in a row
+fn next_check_at(now: NaiveDateTime, failures: i32) -> NaiveDateTime {
+    let delay = if failures <= 0 {
+        chrono::Duration::days(RECHECK_AFTER_DAYS as i64)
+    } else {
+        let hours = FAILURE_BACKOFF_FACTOR
+            .checked_pow(failures as u32 - 1)
+            .and_then(|factor| factor.checked_mul(FAILURE_BACKOFF_BASE_HOURS))
+            .map_or(MAX_FAILURE_BACKOFF_HOURS, |hours| {
+                hours.min(MAX_FAILURE_BACKOFF_HOURS)
+            });
+        chrono::Duration::hours(hours)
+    };
+    now.checked_add_signed(delay).unwrap_or(NaiveDateTime::MAX)
+}
+
+/// Whether the content of the page may have changed since it was embedded,
+/// as told by its validator, or by its age if the server has none
+fn needs_comparison(article: &OnlineArticle, validator: Option<&str>, now: NaiveDateTime) -> bool {
+    let embedded_at = article.content_refreshed_at.unwrap_or(article.created_at);
+    let recent = now
+        .checked_sub_days(chrono::Days::new(RECHECK_AFTER_DAYS))
+        .is_some_and(|cutoff| embedded_at > cutoff);
+    if recent {
+        return false;
+    }
+    validator.is_none() || validator != article.link_validator.as_deref()
+}
+
+/// Fetches the page again, and embeds it again if its terms changed much.
+/// Returns whether it was.
+async fn refresh_content(
+    ctx: &App,
+    article: &OnlineArticle,
+    url: &url::Url,
+) -> Result<bool, eyre::Error> {
+    let page = crawler::fetch_article_page(ctx, url).await?;
+    // The teaser of a walled page isn't worth embedding again
+    if page.paywalled {
+        return Ok(false);
+    }
+    let markdown = page.markdown;
+    let terms = crate::utils::extract_recommender_terms(&article.title, Some(&markdown));
+    let stored_terms = parse_recommender_terms_json(article.recommender_terms.as_ref());
+    if term_overlap(&stored_terms, &terms) >= MIN_TERM_OVERLAP {
+        return Ok(false);
+    }
+
+    tracing::info!(id = article.id, url = %url, "Article changed, embedding it again");
+    let embeddings = generate_embeddings(&ctx.embedder, &article.title, &markdown).await?;
+    let lang = crate::utils::detect_language(&article.title, &markdown);
+
+    let id = article.id;
+    let mut conn = ctx.diesel.get().await?;
+    conn.transaction(async move |conn| {
+        // The comments don't change with the page
+        diesel::delete(
+            online_article_chunks::table
+                .filter(online_article_chunks::online_article_id.eq(id))
+                .filter(online_article_chunks::kind.ne(comments::COMMENTS_CHUNK)),
+        )
+        .execute(conn)
+        .await?;
+        crawler::insert_article_chunks(conn, id, &embeddings).await?;
+        diesel::update(online_articles::table.find(id))
+            .set((
+                online_articles::recommender_terms
+                    .eq((!terms.is_empty()).then(|| serde_json::json!(terms))),
+                online_articles::lang.eq(lang),
+            ))
+            .execute(conn)
+            .await?;
+        Ok::<_, diesel::result::Error>(())
+    })
+    .await?;
+
+    Ok(true)
+}
+
+/// Share of the terms the two sets have in common
+fn term_overlap(before: &[String], after: &[String]) -> f64 {
+    let before = before.iter().collect::<HashSet<_>>();
+    let after = after.iter().collect::<HashSet<_>>();
+    let union = before.union(&after).count();
+    if union == 0 {
+        return 1.0;
+    }
+    before.intersection(&after).count() as f64 / union as f64
+}
+
+#[derive(Deserialize)]
+struct WaybackAvailability {
+    archived_snapshots: WaybackSnapshots,
+}
+
+#[derive(Deserialize)]
+struct WaybackSnapshots {
+    closest: Option<WaybackSnapshot>,
+}
+
+#[derive(Deserialize)]
+struct WaybackSnapshot {
+    available: bool,
+    url: String,
+    status: String,
+}
+
+/// The closest copy of the page on the Wayback Machine
+async fn archived_copy(ctx: &App, url: &url::Url) -> Result<Option<String>, eyre::Error> {
+    let api = url::Url::parse_with_params(WAYBACK_AVAILABILITY_URL, [("url", url.as_str())])?;
+    let availability = ctx
+        .http
+        .get(api)
+        .send()
+        .await?
+        .error_for_status()?
+        .json::<WaybackAvailability>()
+        .await?;
+
+    Ok(availability
+        .archived_snapshots
+        .closest
+        .filter(|snapshot| snapshot.available && snapshot.status == "200")
+        .map(|snapshot| match snapshot.url.strip_prefix("http://") {
+            Some(rest) => format!("https://{rest}"),
+            None => snapshot.url,
+        }))
+}
+
+/// The article with the state of its link
+#[utoipa::path(
+    get,
+    path = "/feed/items/{id}",
+    tag = "recommendation",
+    params(("id" = i32, Path)),
+    responses(
+        (status = 200, description = "The article", body = FeedItemDetail),
+        (status = 404, description = "No such article"),
+    ),
+)]
+pub async fn get_feed_item(
+    State(ctx): State<App>,
+    Path(id): Path<i32>,
+) -> Result<Json<FeedItemDetail>, AppError> {
+    let mut conn = ctx.diesel.get().await?;
+
+    let article = online_articles::table
+        .find(id)
+        .select(OnlineArticle::as_select())
+        .first(&mut conn)
+        .await
+        .optional()?
+        .ok_or(("No such article", StatusCode::NOT_FOUND))?;
+
+    let metadata = online_article_metadata::table
+        .inner_join(online_article_sources::table)
+        .filter(online_article_metadata::online_article_id.eq(id))
+        .order(online_article_metadata::submitted_at.asc())
+        .select((
+            online_article_sources::key,
+            online_article_metadata::external_score,
+            online_article_metadata::metadata,
+            online_article_metadata::submitted_at,
+        ))
+        .load::<(
+            String,
+            Option<f64>,
+            Option<serde_json::Value>,
+            NaiveDateTime,
+        )>(&mut conn)
+        .await?;
+
+    // The feed shows the title the article was first submitted with
+    let title = metadata
+        .iter()
+        .find_map(|(_, _, metadata, _)| {
+            metadata.as_ref()?["editorialized_title"]
+                .as_str()
+                .map(str::to_string)
+        })
+        .unwrap_or(article.title);
+
+    Ok(Json(FeedItemDetail {
+        id: article.id,
+        title,
+        url: article.url,
+        submitted_at: metadata
+            .as_slice()
+            .first()
+            .map(|(_, _, _, submitted_at)| *submitted_at),
+        sources: metadata
+            .into_iter()
+            .map(|(key, score, metadata, _)| SourceInfo {
+                key,
+                score,
+                external_id: metadata
+                    .as_ref()
+                    .and_then(|m| m["external_id"].as_str())
+                    .map(str::to_string),
+            })
+            .collect(),
+        // Only the known statuses are written
+        link_status: LinkStatus::parse(&article.link_status).unwrap_or(LinkStatus::Unchecked),
+        link_checked_at: article.link_checked_at,
+        archive_url: article.archive_url,
+        content_refreshed_at: article.content_refreshed_at,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn failed_links_are_checked_again_with_a_backoff() {
+        let now = chrono::DateTime::from_timestamp(1_760_000_000, 0)
+            .expect("timestamp")
+            .naive_utc();
+
+        assert_eq!(next_check_at(now, 0), now + chrono::Duration::days(30));
+        assert_eq!(next_check_at(now, 1), now + chrono::Duration::hours(1));
+        assert_eq!(next_check_at(now, 3), now + chrono::Duration::hours(16));
+        assert_eq!(next_check_at(now, 50), now + chrono::Duration::days(7));
+    }
+
+    #[test]
+    fn overlap_is_the_share_of_common_terms() {
+        let terms = |t: &[&str]| t.iter().map(|t| t.to_string()).collect::<Vec<_>>();
+
+        assert_eq!(term_overlap(&[], &[]), 1.0);
+        assert_eq!(
+            term_overlap(&terms(&["rust", "async"]), &terms(&["rust", "async"])),
+            1.0
+        );
+        assert_eq!(
+            term_overlap(
+                &terms(&["rust", "async", "tokio"]),
+                &terms(&["rust", "wasm"])
+            ),
+            0.25
+        );
+        assert_eq!(term_overlap(&[], &terms(&["rust"])), 0.0);
+    }
+}
@@ -1,44 +1,98 @@
 use axum::{
-    Json, Router,
+    Router,
     extract::{Query, State},
-    response::sse::{Event, KeepAlive, Sse},
+    http::{StatusCode, header},
+    response::{
+        IntoResponse,
+        sse::{Event, KeepAlive, Sse},
+    },
     routing::get,
 };
 use diesel::prelude::*;
-use diesel::sql_types::{Float8, Integer, Jsonb, Nullable, Text, Timestamp};
+use diesel::sql_types::{Array, Bool, Float8, Integer, Jsonb, Nullable, Text, Timestamp};
 use diesel_async::{AsyncPgConnection, RunQueryDsl};
-use eyre::eyre;
 use futures_util::stream::StreamExt;
 use robotxt::Robots;
 use serde::{Deserialize, Serialize};
 use std::{
     collections::{HashMap, HashSet},
+    sync::Arc,
     time::Duration,
 };
 use tokio::sync::Mutex;
 use tokio::time::Instant;
 use tokio_stream::wrappers::BroadcastStream;
+use utoipa::{IntoParams, OpenApi};
+
+use api_models::FeedSnapshot;
+pub use api_models::{
+    CrawlFailure, CrawlRun, CrawlSource, CrawlStatus, CrawlTiming, CrawlTrigger, DigestItem,
+    DigestPeriod, DigestTopic, FeedDigest, FeedEvent, FeedExplanation, FeedItem, FeedItemDetail,
+    FeedItemSummary, HistoryImport, HistoryJob, HistoryJobStatus, HistoryMatch, HistorySource,
+    HistoryToken, LinkStatus, RankingPreset, SharedFeed, SharedFeedItem, SourceFilter, SourceInfo,
+};
 
 use crate::{
-    App, error::AppError, recommendation::crawler::MAX_CONCURRENT_FETCHES,
+    App,
+    config::{RankingK, RecommenderRaindropCollection},
+    error::AppError,
+    identity::{MaybeAuthUser, raindrop},
+    jobs::{self, Lease},
+    recommendation::crawler::{CrawlProgress, MAX_CONCURRENT_FETCHES},
     utils::RECOMMENDER_EMBEDDING_BITS,
 };
 
+mod archive;
+mod clicks;
+mod comments;
 mod crawler;
+mod digest;
 mod engine;
+mod import;
+mod links;
+mod paywall;
+mod publisher;
+mod reindex;
+mod retention;
+mod rss;
+mod runs;
+mod save;
+mod share;
+mod summary;
+
+pub use crawler::{FetchedArticle, SourceEntry, fetch_markdown, insert_article};
+pub use engine::generate_embeddings;
+pub use import::route as import_route;
+pub use links::start_link_checker;
+pub use publisher::start_discord_publisher;
+pub use reindex::{IndexParams, reindex_embeddings};
+pub use retention::start_retention_worker;
+pub use save::route as save_route;
 
-const MIN_CRAWL_INTERVAL: Duration = Duration::from_mins(10);
 const MIN_RERANK_CANDIDATE_POOL: i64 = 100;
 const MAX_RERANK_CANDIDATE_POOL: i64 = 400;
 const RERANK_CANDIDATE_POOL_MULTIPLIER: i64 = 2;
 const MAX_PROFILE_TERMS: usize = 32;
+/// History articles listed in the explanation of each item
+const MAX_EXPLAINED_MATCHES: i32 = 3;
+/// Weights of the log of the comments and of the comments per hour in the
+/// external score, the log of the points weighs 1
+const COMMENT_COUNT_WEIGHT: f64 = 0.5;
+const COMMENT_VELOCITY_WEIGHT: f64 = 0.5;
+/// Lock of the running crawl, whichever instance started it
+const CRAWL_JOB: &str = "crawl";
 
 pub struct RecommendationSystem {
     pub site_limiter: SiteLimiter,
     pub robots_cache: Mutex<HashMap<String, Robots>>,
     pub events: tokio::sync::broadcast::Sender<FeedEvent>,
     last_crawl_time: Mutex<Option<Instant>>,
-    crawl_in_progress: Mutex<bool>,
+    /// The running crawl, there's at most one at a time
+    current_crawl: Mutex<Option<Arc<CrawlProgress>>>,
+    /// Summaries generated per reader
+    summary_limiter: crate::rate_limit::RateLimiter<i32>,
+    /// The last snapshot of the shared feed
+    shared_feed: Mutex<Option<SharedFeed>>,
 }
 
 impl RecommendationSystem {
@@ -49,7 +103,9 @@ impl RecommendationSystem {
             robots_cache: Mutex::new(HashMap::new()),
             events,
             last_crawl_time: Mutex::new(None),
-            crawl_in_progress: Mutex::new(false),
+            current_crawl: Mutex::new(None),
+            summary_limiter: summary::rate_limiter(),
+            shared_feed: Mutex::new(None),
         }
     }
 }
@@ -93,49 +149,8 @@ impl SiteLimiter {
     }
 }
 
-#[derive(Clone, Debug, Serialize, Deserialize, Default)]
-pub struct SourceInfo {
-    pub key: String,
-    pub score: Option<f64>,
-    pub external_id: Option<String>,
-}
-
-#[derive(Clone, Debug, Serialize, Deserialize)]
-pub struct FeedItem {
-    pub id: i32,
-    pub title: String,
-    pub url: String,
-    pub score: f64,
-    pub similarity_score: Option<f64>,
-    pub submitted_at: Option<chrono::NaiveDateTime>,
-    pub sources: Vec<SourceInfo>,
-}
-
-#[derive(Clone, Debug, Serialize, Deserialize)]
-pub struct FeedSnapshot {
-    pub items: Vec<FeedItem>,
-}
-
-#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, PartialEq, Eq)]
-#[serde(rename_all = "snake_case")]
-pub enum RankingPreset {
-    #[default]
-    Balanced,
-    NewerFirst,
-    TopFirst,
-    SimilarFirst,
-}
-
-#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, PartialEq, Eq)]
-#[serde(rename_all = "snake_case")]
-pub enum SourceFilter {
-    #[default]
-    All,
-    HackerNews,
-    Lobsters,
-}
-
-#[derive(Deserialize)]
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
 pub struct FeedQuery {
     offset: Option<i64>,
     limit: Option<u32>,
@@ -143,12 +158,13 @@ pub struct FeedQuery {
     source: SourceFilter,
     #[serde(default)]
     ranking: RankingPreset,
-}
-
-#[derive(Clone, Debug, Serialize, Deserialize)]
-#[serde(tag = "type", content = "data")]
-pub enum FeedEvent {
-    NewEntries { count: usize },
+    /// Comma separated ISO 639-3 codes of the languages to show, e.g.
+    /// `eng,vie`, the default of the config if absent and all of them if
+    /// empty. The articles whose language couldn't be detected are shown.
+    lang: Option<String>,
+    /// Include how each item was ranked and which history articles it matched
+    #[serde(default)]
+    explain: bool,
 }
 
 #[derive(QueryableByName, Debug)]
@@ -167,10 +183,22 @@ struct RankedRow {
     score: f64,
     #[diesel(sql_type = Nullable<Float8>)]
     similarity_score: Option<f64>,
+    #[diesel(sql_type = Nullable<Integer>)]
+    similarity_rank: Option<i32>,
+    #[diesel(sql_type = Nullable<Integer>)]
+    external_rank: Option<i32>,
+    #[diesel(sql_type = Integer)]
+    freshness_rank: i32,
+    #[diesel(sql_type = Nullable<Float8>)]
+    freshness_score: Option<f64>,
     #[diesel(sql_type = Nullable<Jsonb>)]
     sources: Option<serde_json::Value>,
     #[diesel(sql_type = Nullable<Jsonb>)]
     recommender_terms: Option<serde_json::Value>,
+    #[diesel(sql_type = Nullable<Text>)]
+    lang: Option<String>,
+    #[diesel(sql_type = Bool)]
+    paywalled: bool,
 }
 
 #[derive(QueryableByName, Debug)]
@@ -183,52 +211,203 @@ struct HistoryProfileRow {
     recommender_terms: Option<serde_json::Value>,
 }
 
+#[derive(QueryableByName, Debug)]
+struct HistoryMatchRow {
+    #[diesel(sql_type = Integer)]
+    online_article_id: i32,
+    #[diesel(sql_type = Integer)]
+    history_article_id: i32,
+    #[diesel(sql_type = Text)]
+    title: String,
+    #[diesel(sql_type = Text)]
+    url: String,
+    #[diesel(sql_type = Integer)]
+    chunk_id: i32,
+    #[diesel(sql_type = Integer)]
+    history_chunk_id: i32,
+    #[diesel(sql_type = Float8)]
+    similarity: f64,
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct UserHistorySource {
     pub title: Option<String>,
     pub url: url::Url,
+    /// Negative for disliked articles, whose similar articles are demoted
     pub weight: Option<f64>,
 }
 
+#[derive(OpenApi)]
+#[openapi(paths(
+    get_feed_snapshot,
+    get_feed_stream,
+    clicks::redirect,
+    digest::get_digest,
+    links::get_feed_item,
+    summary::summarize_item,
+    share::get_shared_feed,
+    rss::export_opml,
+    import::list_imports,
+    import::import_history,
+    import::update_import,
+    import::delete_import,
+    save::save_page,
+    save::get_job,
+    save::list_tokens,
+    save::create_token,
+    save::delete_token,
+    save::mark_negative,
+    save::unmark_negative,
+))]
+pub struct ApiDoc;
+
 pub fn route() -> Router<App> {
     Router::<App>::new()
         .route("/feed", get(get_feed_snapshot))
         .route("/feed/stream", get(get_feed_stream))
+        .merge(clicks::route())
+        .merge(digest::route())
+        .merge(links::route())
+        .merge(summary::route())
+        .merge(share::route())
+        .merge(rss::route())
 }
 
+/// The crawls and the maintenance of the recommendations, for the owner
+pub fn admin_route() -> Router<App> {
+    runs::route()
+        .merge(archive::admin_route())
+        .merge(retention::route())
+        .merge(clicks::admin_route())
+        .merge(rss::admin_route())
+        .merge(share::admin_route())
+}
+
+/// Crawls on schedule from one instance, the feed and the owner can start
+/// crawls from any of them but only one runs at a time
 pub fn start_background_crawl(ctx: App) {
-    tokio::spawn(async move {
-        let mut interval = tokio::time::interval(Duration::from_hours(8));
-        loop {
-            interval.tick().await;
-            if let Err(err) = run_crawl_and_notify(ctx.clone()).await {
-                tracing::warn!(?err, "recommendation crawl failed");
+    jobs::spawn_exclusive(&ctx.config.clone(), "crawl-schedule", move || {
+        let ctx = ctx.clone();
+        async move {
+            loop {
+                if let Err(err) = run_crawl_and_notify(ctx.clone(), CrawlTrigger::Schedule).await {
+                    tracing::warn!(?err, "recommendation crawl failed");
+                }
+                let wait = next_crawl_in(&ctx).await.unwrap_or_else(|err| {
+                    tracing::warn!(?err, "Failed to schedule the next crawl");
+                    ctx.tunables.get().crawl_interval
+                });
+                tokio::time::sleep(wait).await;
             }
         }
     });
 }
 
+/// Time until the first enabled source is due, the sources have their own
+/// interval which defaults to the crawl interval of the config
+async fn next_crawl_in(ctx: &App) -> Result<Duration, eyre::Error> {
+    // Read on every run since they can be reloaded
+    let tunables = ctx.tunables.get();
+    let now = chrono::Utc::now().naive_utc();
+
+    let next = crawler::crawled_sources(ctx)
+        .await?
+        .iter()
+        .filter(|source| source.enabled)
+        .map(|source| crawler::next_crawl_at(source, tunables.crawl_interval))
+        .min();
+
+    Ok(match next {
+        Some(next) => (next - now).to_std().unwrap_or_default().clamp(
+            tunables.min_crawl_interval,
+            tunables.crawl_interval.max(tunables.min_crawl_interval),
+        ),
+        None => tunables.crawl_interval,
+    })
+}
+
+/// `{"items": [FeedItem, ...]}`, streamed since it can hold a few hundred items.
+/// Ranked against the history of the logged in reader if they have one, the
+/// default history otherwise.
+#[utoipa::path(
+    get,
+    path = "/feed",
+    tag = "recommendation",
+    params(FeedQuery),
+    responses(
+        (status = 200, description = "Ranked recommendations", body = FeedSnapshot),
+        (status = 400, description = "Unknown language code"),
+    ),
+)]
 async fn get_feed_snapshot(
     State(ctx): State<App>,
     Query(query): Query<FeedQuery>,
-) -> Result<Json<FeedSnapshot>, AppError> {
+    auth_user: MaybeAuthUser,
+) -> Result<impl IntoResponse, AppError> {
     let limit = query.limit.unwrap_or(20).min(100) as i64;
     let offset = query.offset.unwrap_or(0);
+    let languages = match &query.lang {
+        Some(lang) => crate::utils::parse_languages(lang)
+            .ok_or(("Unknown language code", StatusCode::BAD_REQUEST))?,
+        None => ctx.config.recommender_feed_languages.clone(),
+    };
 
-    let crawl_ctx = ctx.clone();
-    tokio::spawn(async move {
-        if let Err(err) = run_crawl_and_notify(crawl_ctx).await {
-            tracing::warn!(?err, "recommendation crawl failed");
-        }
-    });
+    // The server-only instances leave crawling to the workers
+    if ctx.config.run_mode.runs_workers() {
+        let crawl_ctx = ctx.clone();
+        tokio::spawn(async move {
+            if let Err(err) = run_crawl_and_notify(crawl_ctx, CrawlTrigger::Feed).await {
+                tracing::warn!(?err, "recommendation crawl failed");
+            }
+        });
+    }
 
-    let items = fetch_feed_items(&ctx, limit, offset, query.source, query.ranking).await?;
+    let history_owner = match auth_user.0 {
+        Ok(identity) => history_owner_for(&ctx, identity.id).await?,
+        Err(_) => None,
+    };
+    let items = fetch_feed_items(
+        &ctx,
+        limit,
+        offset,
+        query.source,
+        query.ranking,
+        history_owner,
+        &languages,
+        query.explain,
+    )
+    .await?;
 
-    let snapshot = FeedSnapshot { items };
+    let impressions_ctx = ctx.clone();
+    let (ranking, shown) = (query.ranking, items.len());
+    tokio::spawn(async move {
+        let _ = clicks::record_impressions(&impressions_ctx, ranking, offset, shown)
+            .await
+            .inspect_err(|err| tracing::warn!(?err, "Failed to record feed impressions"));
+    });
 
-    Ok(Json(snapshot))
+    Ok((
+        [
+            (header::CONTENT_TYPE, "application/json"),
+            // Personalized to the session
+            (header::CACHE_CONTROL, "private"),
+            (header::VARY, "Cookie"),
+        ],
+        crate::json::array_body(r#"{"items":["#, items, "]}"),
+    ))
 }
 
+#[utoipa::path(
+    get,
+    path = "/feed/stream",
+    tag = "recommendation",
+    responses((
+        status = 200,
+        description = "Server-sent events, one JSON encoded event per message",
+        content_type = "text/event-stream",
+        body = FeedEvent,
+    )),
+)]
 async fn get_feed_stream(
     State(ctx): State<App>,
 ) -> Result<Sse<impl futures_util::Stream<Item = Result<Event, std::convert::Infallible>>>, AppError>
@@ -243,12 +422,284 @@ async fn get_feed_stream(
     Ok(Sse::new(stream).keep_alive(KeepAlive::new().interval(Duration::from_secs(15))))
 }
 
+/// Ranks the first page of the default feed, which reads the articles and
+/// their chunks into the database's memory ahead of the first request
+pub async fn warm_feed(ctx: &App) -> Result<(), eyre::Error> {
+    fetch_feed_items(
+        ctx,
+        20,
+        0,
+        SourceFilter::All,
+        RankingPreset::default(),
+        None,
+        &ctx.config.recommender_feed_languages,
+        false,
+    )
+    .await
+    .map(drop)
+}
+
+/// Reads the default feed for the tools of the Discord agent
+#[derive(Clone)]
+pub struct FeedReader(App);
+
+impl FeedReader {
+    pub fn new(ctx: App) -> Self {
+        Self(ctx)
+    }
+
+    /// The top items of the default feed in the configured languages
+    pub async fn top(
+        &self,
+        limit: i64,
+        source: SourceFilter,
+        ranking: RankingPreset,
+    ) -> Result<Vec<FeedItem>, eyre::Error> {
+        fetch_feed_items(
+            &self.0,
+            limit,
+            0,
+            source,
+            ranking,
+            None,
+            &self.0.config.recommender_feed_languages,
+            false,
+        )
+        .await
+    }
+}
+
+/// The discussion of an item on the source it was submitted to
+pub fn discussion_url(source: &SourceInfo) -> Option<String> {
+    let id = source.external_id.as_deref()?;
+    match source.key.as_str() {
+        crawler::HACKER_NEWS_KEY => Some(format!("https://news.ycombinator.com/item?id={id}")),
+        crawler::LOBSTERS_KEY => Some(format!("https://lobste.rs/s/{id}")),
+        _ => None,
+    }
+}
+
+/// The identity whose history the feed of `identity_id` is ranked against,
+/// `None` for the default history if they have none
+async fn history_owner_for(ctx: &App, identity_id: i32) -> Result<Option<i32>, eyre::Error> {
+    use crate::schema::user_history::dsl as history_dsl;
+
+    let mut conn = ctx.diesel.get().await?;
+    let has_history = diesel::select(diesel::dsl::exists(
+        history_dsl::user_history.filter(history_dsl::identity_id.eq(identity_id)),
+    ))
+    .get_result::<bool>(&mut conn)
+    .await?;
+    Ok(has_history.then_some(identity_id))
+}
+
+/// Two-phase ranking with Reciprocal Rank Fusion (RRF):
+///
+/// Phase 1: Filter candidates by freshness and external score. This reduces the
+/// corpus to a manageable size before computing similarity and the lexical rerank.
+///
+/// Phase 2: Compute chunk-level similarity for the candidate pool. This keeps the
+/// query logic simple and pushes the more experimental taste-matching logic into
+/// the lightweight lexical reranker below.
+///
+/// Weight still matters: high-weight history items contribute more to similarity,
+/// meaning articles similar to important history items rank higher. History items
+/// with a negative weight are disliked, the closest of them is subtracted.
+///
+/// RRF combines ranking signals by converting each to 1/(k + rank), normalizing
+/// different scales. Each signal has its own k constant for tuning.
+///
+/// $1 is the size of the candidate pool, $2 the owner of the history, $3 the
+/// languages, $4 the key of the source to keep or null for all of them, $5 and
+/// $6 the external and similarity k, $7 the freshness half-life in hours, $8
+/// and $9 the weights of the comments and of the comments per hour, $10 the
+/// bits of the embeddings.
+const FEED_RANKING_QUERY: &str = r#"
+WITH history_chunks AS (
+    SELECT
+        hc.embedding,
+        COALESCE(uh.weight, 0.1) AS weight
+    FROM user_history uh
+    JOIN online_article_chunks hc ON hc.online_article_id = uh.online_article_id
+    WHERE uh.identity_id IS NOT DISTINCT FROM $2
+),
+feed_items AS (
+    SELECT i.id, i.title AS original_title, i.url, i.created_at
+    FROM online_articles i
+    WHERE NOT EXISTS (
+        SELECT 1 FROM user_history uh
+        WHERE uh.online_article_id = i.id AND uh.identity_id IS NOT DISTINCT FROM $2
+    )
+    -- Dead links are only shown if they were archived
+    AND (i.link_status <> 'dead' OR i.archive_url IS NOT NULL)
+    AND (CARDINALITY($3::TEXT[]) = 0 OR i.lang IS NULL OR i.lang = ANY($3))
+    AND ($4::TEXT IS NULL OR EXISTS (
+        SELECT 1 FROM online_article_metadata m
+        JOIN online_article_sources s ON s.id = m.source_id
+        WHERE m.online_article_id = i.id AND s.key = $4
+    ))
+),
+-- Aggregate external scores using log dampening, the comments and the comments
+-- per hour since the submission count next to the points
+-- When a source filter is applied, only that source's score is used for ranking
+item_external_scores AS (
+    SELECT
+        fi.id AS online_article_id,
+        SUM(
+            LN(COALESCE(im.external_score, 0.0) + 1.0)
+            + $8::FLOAT8 * LN(COALESCE((im.metadata->>'comment_count')::FLOAT8, 0.0) + 1.0)
+            + $9::FLOAT8 * LN(
+                COALESCE((im.metadata->>'comment_count')::FLOAT8, 0.0)
+                / GREATEST(EXTRACT(EPOCH FROM (NOW() - im.submitted_at)) / 3600.0, 1.0)
+                + 1.0
+            )
+        ) AS log_external_score
+    FROM feed_items fi
+    LEFT JOIN online_article_metadata im ON im.online_article_id = fi.id
+        AND ($4::TEXT IS NULL OR im.source_id IN (
+            SELECT s.id FROM online_article_sources s WHERE s.key = $4
+        ))
+    GROUP BY fi.id
+),
+-- Freshness score: exponential decay with configurable half-life
+item_freshness AS (
+    SELECT
+        fi.id AS online_article_id,
+        EXP(-EXTRACT(EPOCH FROM (NOW() - MIN(im.submitted_at))) / 3600.0 * LN(2) / $7::FLOAT8 + 3) AS freshness_score
+    FROM feed_items fi
+    JOIN online_article_metadata im ON im.online_article_id = fi.id
+    GROUP BY fi.id
+),
+-- Rank by external score (higher is better)
+external_ranked AS (
+    SELECT
+        online_article_id,
+        log_external_score,
+        ROW_NUMBER() OVER (ORDER BY log_external_score DESC NULLS LAST) AS rank
+    FROM item_external_scores
+),
+-- Phase 1: Select a candidate pool large enough for semantic + lexical reranking,
+-- and paginate only after the rerank so later pages stay consistent.
+candidates AS (
+    SELECT
+        fi.id,
+        fi.original_title,
+        fi.url,
+        fi.created_at,
+        er.rank AS external_rank,
+        ifr.freshness_score
+    FROM feed_items fi
+    LEFT JOIN external_ranked er ON er.online_article_id = fi.id
+    LEFT JOIN item_freshness ifr ON ifr.online_article_id = fi.id
+    ORDER BY (
+        COALESCE(1.0 / ($5::FLOAT8 + er.rank), 0.0) * COALESCE(ifr.freshness_score, 0.0)
+    ) DESC
+    LIMIT $1
+),
+item_similarities AS (
+    SELECT
+        c.id AS online_article_id,
+        COALESCE(MAX((
+            1.0
+            - ((cc.embedding <~> hc.embedding) / $10::FLOAT8)
+        ) * hc.weight) FILTER (WHERE hc.weight > 0), 0.0)
+        - COALESCE(MAX((
+            1.0
+            - ((cc.embedding <~> hc.embedding) / $10::FLOAT8)
+        ) * -hc.weight) FILTER (WHERE hc.weight < 0), 0.0) AS similarity
+    FROM candidates c
+    JOIN online_article_chunks cc ON cc.online_article_id = c.id
+    CROSS JOIN history_chunks hc
+    GROUP BY c.id
+),
+-- Rank by similarity (higher is better)
+similarity_ranked AS (
+    SELECT
+        online_article_id,
+        ROW_NUMBER() OVER (ORDER BY similarity DESC NULLS LAST) AS rank
+    FROM item_similarities
+),
+-- Combine similarity and external RRF, then multiply by freshness decay
+ranked AS (
+    SELECT
+        c.id,
+        c.original_title,
+        c.url,
+        c.created_at,
+        (
+            (
+                COALESCE(1.0 / ($6::FLOAT8 + sr.rank), 0.0)
+                + COALESCE(1.0 / ($5::FLOAT8 + c.external_rank), 0.0)
+            ) * COALESCE(c.freshness_score, 0.0)
+        )::FLOAT8 AS score,
+        ism.similarity AS similarity_score,
+        sr.rank::INTEGER AS similarity_rank,
+        c.external_rank::INTEGER AS external_rank,
+        ROW_NUMBER() OVER (ORDER BY c.freshness_score DESC NULLS LAST)::INTEGER AS freshness_rank,
+        c.freshness_score::FLOAT8 AS freshness_score
+    FROM candidates c
+    LEFT JOIN similarity_ranked sr ON sr.online_article_id = c.id
+    LEFT JOIN item_similarities ism ON ism.online_article_id = c.id
+)
+SELECT
+    r.id,
+    COALESCE(
+        (SELECT im.metadata->>'editorialized_title'
+         FROM online_article_metadata im
+         WHERE im.online_article_id = r.id
+           AND im.metadata->>'editorialized_title' IS NOT NULL
+         ORDER BY im.submitted_at
+         LIMIT 1),
+        r.original_title
+    ) AS title,
+    r.url,
+    r.created_at,
+    (SELECT MIN(im.submitted_at) FROM online_article_metadata im WHERE im.online_article_id = r.id) AS submitted_at,
+    r.score,
+    r.similarity_score,
+    r.similarity_rank,
+    r.external_rank,
+    r.freshness_rank,
+    r.freshness_score,
+    (SELECT JSONB_AGG(JSONB_BUILD_OBJECT(
+        'key', s.key,
+        'score', im.external_score,
+        'external_id', im.metadata->>'external_id'
+    ))
+    FROM online_article_metadata im
+    JOIN online_article_sources s ON s.id = im.source_id
+    WHERE im.online_article_id = r.id) AS sources,
+    oa.recommender_terms,
+    oa.lang,
+    oa.paywalled
+FROM ranked r
+JOIN online_articles oa ON oa.id = r.id
+ORDER BY r.score DESC, r.created_at DESC, r.id DESC
+"#;
+
+/// Key of the only source ranked by the filter, none for all of them
+fn source_key(filter: SourceFilter) -> Option<&'static str> {
+    match filter {
+        SourceFilter::All => None,
+        SourceFilter::HackerNews => Some(crawler::HACKER_NEWS_KEY),
+        SourceFilter::Lobsters => Some(crawler::LOBSTERS_KEY),
+    }
+}
+
+/// Ranks the articles against the history of `history_owner`, or the default
+/// history if `None`. Only the articles in `languages` are ranked, or of an
+/// unknown language, all of them if it's empty. With `explain`, each item
+/// says how it was ranked.
+#[allow(clippy::too_many_arguments)]
 async fn fetch_feed_items(
     ctx: &App,
     limit: i64,
     offset: i64,
     source_filter: SourceFilter,
     ranking: RankingPreset,
+    history_owner: Option<i32>,
+    languages: &[String],
+    explain: bool,
 ) -> Result<Vec<FeedItem>, eyre::Error> {
     let mut conn = ctx.diesel.get().await?;
     let offset = offset.max(0);
@@ -257,14 +708,12 @@ async fn fetch_feed_items(
         .saturating_mul(RERANK_CANDIDATE_POOL_MULTIPLIER)
         .clamp(MIN_RERANK_CANDIDATE_POOL, MAX_RERANK_CANDIDATE_POOL);
 
-    // RRF k constants for each ranking preset
+    // RRF k constants for each ranking preset, they are tunable while running
     // Lower k = more weight given to top-ranked items for that signal
-    let (similarity_k, external_k) = match ranking {
-        RankingPreset::Balanced => (12.0, 6.0),
-        RankingPreset::NewerFirst => (20.0, 15.0),
-        RankingPreset::TopFirst => (25.0, 1.0),
-        RankingPreset::SimilarFirst => (1.0, 25.0),
-    };
+    let RankingK {
+        similarity: similarity_k,
+        external: external_k,
+    } = ctx.tunables.get().ranking_k(ranking);
 
     // Freshness decay half-life in hours for each preset
     let freshness_half_life = match ranking {
@@ -280,175 +729,19 @@ async fn fetch_feed_items(
         RankingPreset::SimilarFirst => 0.75,
     };
 
-    // Source filter condition for feed_items
-    let source_filter_sql = match source_filter {
-        SourceFilter::All => String::new(),
-        SourceFilter::HackerNews => {
-            "AND EXISTS (SELECT 1 FROM online_article_metadata m JOIN online_article_sources s ON s.id = m.source_id WHERE m.online_article_id = i.id AND s.key = 'hacker-news')".to_string()
-        }
-        SourceFilter::Lobsters => {
-            "AND EXISTS (SELECT 1 FROM online_article_metadata m JOIN online_article_sources s ON s.id = m.source_id WHERE m.online_article_id = i.id AND s.key = 'lobsters')".to_string()
-        }
-    };
-
-    // Source filter for external score aggregation - only count the filtered source's score
-    let external_score_source_filter = match source_filter {
-        SourceFilter::All => String::new(),
-        SourceFilter::HackerNews => {
-            "JOIN online_article_sources s ON s.id = im.source_id AND s.key = 'hacker-news'"
-                .to_string()
-        }
-        SourceFilter::Lobsters => {
-            "JOIN online_article_sources s ON s.id = im.source_id AND s.key = 'lobsters'"
-                .to_string()
-        }
-    };
-
-    // Two-phase ranking with Reciprocal Rank Fusion (RRF):
-    //
-    // Phase 1: Filter candidates by freshness and external score. This reduces the
-    // corpus to a manageable size before computing similarity and the lexical rerank.
-    //
-    // Phase 2: Compute chunk-level similarity for the candidate pool. This keeps the
-    // query logic simple and pushes the more experimental taste-matching logic into
-    // the lightweight lexical reranker below.
-    //
-    // Weight still matters: high-weight history items contribute more to similarity,
-    // meaning articles similar to important history items rank higher.
-    //
-    // RRF combines ranking signals by converting each to 1/(k + rank), normalizing
-    // different scales. Each signal has its own k constant for tuning.
-    let sql = format!(
-        r#"
-        WITH history_chunks AS (
-            SELECT
-                hc.embedding,
-                COALESCE(uh.weight, 0.1) AS weight
-            FROM user_history uh
-            JOIN online_article_chunks hc ON hc.online_article_id = uh.online_article_id
-        ),
-        feed_items AS (
-            SELECT i.id, i.title AS original_title, i.url, i.created_at
-            FROM online_articles i
-            WHERE NOT EXISTS (SELECT 1 FROM user_history uh WHERE uh.online_article_id = i.id)
-            {source_filter_sql}
-        ),
-        -- Aggregate external scores using log dampening
-        -- When a source filter is applied, only that source's score is used for ranking
-        item_external_scores AS (
-            SELECT
-                fi.id AS online_article_id,
-                SUM(LN(COALESCE(im.external_score, 0.0) + 1.0)) AS log_external_score
-            FROM feed_items fi
-            LEFT JOIN online_article_metadata im ON im.online_article_id = fi.id
-            {external_score_source_filter}
-            GROUP BY fi.id
-        ),
-        -- Freshness score: exponential decay with configurable half-life
-        item_freshness AS (
-            SELECT
-                fi.id AS online_article_id,
-                EXP(-EXTRACT(EPOCH FROM (NOW() - MIN(im.submitted_at))) / 3600.0 * LN(2) / {freshness_half_life} + 3) AS freshness_score
-            FROM feed_items fi
-            JOIN online_article_metadata im ON im.online_article_id = fi.id
-            GROUP BY fi.id
-        ),
-        -- Rank by external score (higher is better)
-        external_ranked AS (
-            SELECT
-                online_article_id,
-                log_external_score,
-                ROW_NUMBER() OVER (ORDER BY log_external_score DESC NULLS LAST) AS rank
-            FROM item_external_scores
-        ),
-        -- Phase 1: Select a candidate pool large enough for semantic + lexical reranking,
-        -- and paginate only after the rerank so later pages stay consistent.
-        candidates AS (
-            SELECT
-                fi.id,
-                fi.original_title,
-                fi.url,
-                fi.created_at,
-                er.rank AS external_rank,
-                ifr.freshness_score
-            FROM feed_items fi
-            LEFT JOIN external_ranked er ON er.online_article_id = fi.id
-            LEFT JOIN item_freshness ifr ON ifr.online_article_id = fi.id
-            ORDER BY (
-                COALESCE(1.0 / ({external_k} + er.rank), 0.0) * COALESCE(ifr.freshness_score, 0.0)
-            ) DESC
-            LIMIT $1
-        ),
-        item_similarities AS (
-            SELECT
-                c.id AS online_article_id,
-                MAX((
-                    1.0
-                    - ((cc.embedding <~> hc.embedding) / {RECOMMENDER_EMBEDDING_BITS}.0)
-                ) * hc.weight) AS similarity
-            FROM candidates c
-            JOIN online_article_chunks cc ON cc.online_article_id = c.id
-            CROSS JOIN history_chunks hc
-            GROUP BY c.id
-        ),
-        -- Rank by similarity (higher is better)
-        similarity_ranked AS (
-            SELECT
-                online_article_id,
-                ROW_NUMBER() OVER (ORDER BY similarity DESC NULLS LAST) AS rank
-            FROM item_similarities
-        ),
-        -- Combine similarity and external RRF, then multiply by freshness decay
-        ranked AS (
-            SELECT
-                c.id,
-                c.original_title,
-                c.url,
-                c.created_at,
-                (
-                    (
-                        COALESCE(1.0 / ({similarity_k} + sr.rank), 0.0)
-                        + COALESCE(1.0 / ({external_k} + c.external_rank), 0.0)
-                    ) * COALESCE(c.freshness_score, 0.0)
-                )::FLOAT8 AS score,
-                ism.similarity AS similarity_score
-            FROM candidates c
-            LEFT JOIN similarity_ranked sr ON sr.online_article_id = c.id
-            LEFT JOIN item_similarities ism ON ism.online_article_id = c.id
-        )
-        SELECT
-            r.id,
-            COALESCE(
-                (SELECT im.metadata->>'editorialized_title'
-                 FROM online_article_metadata im
-                 WHERE im.online_article_id = r.id
-                   AND im.metadata->>'editorialized_title' IS NOT NULL
-                 ORDER BY im.submitted_at
-                 LIMIT 1),
-                r.original_title
-            ) AS title,
-            r.url,
-            r.created_at,
-            (SELECT MIN(im.submitted_at) FROM online_article_metadata im WHERE im.online_article_id = r.id) AS submitted_at,
-            r.score,
-            r.similarity_score,
-            (SELECT JSONB_AGG(JSONB_BUILD_OBJECT(
-                'key', s.key,
-                'score', im.external_score,
-                'external_id', im.metadata->>'external_id'
-            ))
-            FROM online_article_metadata im
-            JOIN online_article_sources s ON s.id = im.source_id
-            WHERE im.online_article_id = r.id) AS sources,
-            oa.recommender_terms
-        FROM ranked r
-        JOIN online_articles oa ON oa.id = r.id
-        ORDER BY r.score DESC, r.created_at DESC, r.id DESC
-    "#
-    );
-
-    let rows: Vec<RankedRow> = diesel::sql_query(sql)
+    // The k constants and the half-life are bound like the rest so that the
+    // query stays the same for every preset and source
+    let rows: Vec<RankedRow> = diesel::sql_query(FEED_RANKING_QUERY)
         .bind::<Integer, _>(candidate_pool_size as i32)
+        .bind::<Nullable<Integer>, _>(history_owner)
+        .bind::<Array<Text>, _>(languages)
+        .bind::<Nullable<Text>, _>(source_key(source_filter))
+        .bind::<Float8, _>(external_k)
+        .bind::<Float8, _>(similarity_k)
+        .bind::<Float8, _>(freshness_half_life)
+        .bind::<Float8, _>(COMMENT_COUNT_WEIGHT)
+        .bind::<Float8, _>(COMMENT_VELOCITY_WEIGHT)
+        .bind::<Float8, _>(RECOMMENDER_EMBEDDING_BITS as f64)
         .load(&mut conn)
         .await?;
 
@@ -460,8 +753,10 @@ async fn fetch_feed_items(
             oa.recommender_terms
         FROM user_history uh
         JOIN online_articles oa ON oa.id = uh.online_article_id
+        WHERE uh.identity_id IS NOT DISTINCT FROM $1
     "#,
     )
+    .bind::<Nullable<Integer>, _>(history_owner)
     .load(&mut conn)
     .await?;
 
@@ -470,10 +765,12 @@ async fn fetch_feed_items(
         .map(|row| recommender_terms_for_article(&row.title, row.recommender_terms.as_ref()))
         .collect::<Vec<_>>();
     let profile_term_weights = build_profile_term_weights(&history_rows, &candidate_terms);
+    // Scores are negative for the terms of disliked articles, the boost then
+    // demotes as much as it could promote
     let max_lexical_score = rows
         .iter()
         .zip(candidate_terms.iter())
-        .map(|(_, terms)| lexical_profile_score(terms, &profile_term_weights))
+        .map(|(_, terms)| lexical_profile_score(terms, &profile_term_weights).abs())
         .fold(0.0, f64::max);
 
     let mut reranked = rows
@@ -504,8 +801,16 @@ async fn fetch_feed_items(
     }
 
     let end = start.saturating_add(limit as usize).min(reranked.len());
+    let page = &reranked[start..end];
+
+    let mut matches = if explain {
+        let ids = page.iter().map(|(row, _)| row.id).collect::<Vec<_>>();
+        history_matches(&mut conn, &ids, history_owner).await?
+    } else {
+        HashMap::new()
+    };
 
-    Ok(reranked[start..end]
+    Ok(page
         .iter()
         .map(|(row, lexical_boost)| {
             let sources: Vec<SourceInfo> = row
@@ -522,11 +827,95 @@ async fn fetch_feed_items(
                 similarity_score: row.similarity_score,
                 submitted_at: row.submitted_at,
                 sources,
+                lang: row.lang.clone(),
+                paywalled: row.paywalled,
+                explanation: explain.then(|| FeedExplanation {
+                    similarity_rank: row.similarity_rank,
+                    similarity_contribution: rrf_contribution(similarity_k, row.similarity_rank),
+                    external_rank: row.external_rank,
+                    external_contribution: rrf_contribution(external_k, row.external_rank),
+                    freshness_rank: row.freshness_rank,
+                    freshness_score: row.freshness_score,
+                    lexical_boost: *lexical_boost,
+                    matches: matches.remove(&row.id).unwrap_or_default(),
+                }),
             }
         })
         .collect())
 }
 
+/// The share of a signal in the score before the freshness decay, as summed
+/// in the ranking query
+fn rrf_contribution(k: f64, rank: Option<i32>) -> f64 {
+    rank.map_or(0.0, |rank| 1.0 / (k + f64::from(rank)))
+}
+
+/// The history articles closest to each of the items, through the pair of
+/// chunks that makes their similarity
+async fn history_matches(
+    conn: &mut AsyncPgConnection,
+    ids: &[i32],
+    history_owner: Option<i32>,
+) -> Result<HashMap<i32, Vec<HistoryMatch>>, eyre::Error> {
+    let rows = diesel::sql_query(format!(
+        r#"
+        SELECT
+            m.online_article_id,
+            m.history_article_id,
+            ha.title,
+            ha.url,
+            m.chunk_id,
+            m.history_chunk_id,
+            m.similarity
+        FROM unnest($1::INTEGER[]) AS item(id)
+        CROSS JOIN LATERAL (
+            SELECT * FROM (
+                SELECT DISTINCT ON (hc.online_article_id)
+                    cc.online_article_id,
+                    hc.online_article_id AS history_article_id,
+                    cc.id AS chunk_id,
+                    hc.id AS history_chunk_id,
+                    ((
+                        1.0
+                        - ((cc.embedding <~> hc.embedding) / {RECOMMENDER_EMBEDDING_BITS}.0)
+                    ) * COALESCE(uh.weight, 0.1))::FLOAT8 AS similarity
+                FROM online_article_chunks cc
+                CROSS JOIN user_history uh
+                JOIN online_article_chunks hc ON hc.online_article_id = uh.online_article_id
+                WHERE cc.online_article_id = item.id
+                    AND uh.identity_id IS NOT DISTINCT FROM $2
+                ORDER BY hc.online_article_id, similarity DESC
+            ) best
+            ORDER BY similarity DESC
+            LIMIT $3
+        ) m
+        JOIN online_articles ha ON ha.id = m.history_article_id
+        ORDER BY m.online_article_id, m.similarity DESC
+    "#
+    ))
+    .bind::<Array<Integer>, _>(ids)
+    .bind::<Nullable<Integer>, _>(history_owner)
+    .bind::<Integer, _>(MAX_EXPLAINED_MATCHES)
+    .load::<HistoryMatchRow>(conn)
+    .await?;
+
+    let mut matches = HashMap::<i32, Vec<HistoryMatch>>::new();
+    for row in rows {
+        matches
+            .entry(row.online_article_id)
+            .or_default()
+            .push(HistoryMatch {
+                history_article_id: row.history_article_id,
+                title: row.title,
+                url: row.url,
+                chunk_id: row.chunk_id,
+                history_chunk_id: row.history_chunk_id,
+                similarity: row.similarity,
+            });
+    }
+    Ok(matches)
+}
+
 fn parse_recommender_terms_json(value: Option<&serde_json::Value>) -> Vec<String> {
     let Some(serde_json::Value::Array(items)) = value else {
         return Vec::new();
@@ -592,10 +981,12 @@ fn build_profile_term_weights(
         }
     }
 
+    // The terms of disliked articles are kept as well as the liked ones
     let mut ranked_terms = weighted_terms.into_iter().collect::<Vec<_>>();
     ranked_terms.sort_by(|(left_term, left_score), (right_term, right_score)| {
         right_score
-            .total_cmp(left_score)
+            .abs()
+            .total_cmp(&left_score.abs())
             .then_with(|| left_term.cmp(right_term))
     });
     ranked_terms.truncate(MAX_PROFILE_TERMS);
@@ -651,38 +1042,67 @@ async fn count_new_items(ctx: &App, since_id: Option<i32>) -> Result<usize, eyre
     Ok(count as usize)
 }
 
-async fn run_crawl_and_notify(ctx: App) -> Result<(), eyre::Error> {
-    // FIXME: possible race condition when updating in_progress outside lock,
-    // consider using atomics
-    {
-        let mut in_progress = ctx.recommendation.crawl_in_progress.lock().await;
-        if *in_progress {
-            tracing::debug!("Crawl already in progress, skipping");
-            return Ok(());
-        }
+async fn run_crawl_and_notify(ctx: App, trigger: CrawlTrigger) -> Result<(), eyre::Error> {
+    match begin_crawl(&ctx, trigger).await? {
+        Some((progress, lease)) => crawl_and_notify(ctx, progress, lease).await,
+        None => Ok(()),
+    }
+}
 
+/// Records the start of a crawl, unless one is running here or on another
+/// instance, or one ran recently and the owner didn't ask for it
+async fn begin_crawl(
+    ctx: &App,
+    trigger: CrawlTrigger,
+) -> Result<Option<(Arc<CrawlProgress>, Lease)>, eyre::Error> {
+    let mut current = ctx.recommendation.current_crawl.lock().await;
+    if current.is_some() {
+        tracing::debug!("Crawl already in progress, skipping");
+        return Ok(None);
+    }
+
+    if trigger != CrawlTrigger::Admin {
         let last_crawl = ctx.recommendation.last_crawl_time.lock().await;
         if let Some(last) = *last_crawl
-            && last.elapsed() < MIN_CRAWL_INTERVAL
+            && last.elapsed() < ctx.tunables.get().min_crawl_interval
         {
             tracing::debug!("Crawl ran recently, skipping");
-            return Ok(());
+            return Ok(None);
         }
-
-        *in_progress = true;
     }
 
-    let result = async {
+    let Some(lease) = jobs::try_acquire(&ctx.config, CRAWL_JOB).await? else {
+        tracing::debug!("Crawl in progress on another instance, skipping");
+        return Ok(None);
+    };
+
+    let progress = Arc::new(CrawlProgress::start(ctx, trigger).await?);
+    *current = Some(progress.clone());
+    Ok(Some((progress, lease)))
+}
+
+async fn crawl_and_notify(
+    ctx: App,
+    progress: Arc<CrawlProgress>,
+    lease: Lease,
+) -> Result<(), eyre::Error> {
+    let mut crawl_failed = false;
+    let crawl = async {
         tracing::debug!("Starting recommendation crawl");
         let newest_id = newest_item_id(&ctx).await?;
 
-        let (history, crawl) = tokio::join!(ensure_user_history(&ctx), crawler::run_crawl(&ctx),);
+        let (history, crawl) = tokio::join!(
+            ensure_user_history(&ctx),
+            crawler::run_crawl(&ctx, &progress),
+        );
         let _ = history.inspect_err(|err| {
             tracing::error!(?err, "Failed to ensure user history");
         });
-        let _ = crawl.inspect_err(|err| {
-            tracing::error!(?err, "Crawl failed");
-        });
+        crawl_failed = crawl
+            .inspect_err(|err| {
+                tracing::error!(?err, "Crawl failed");
+            })
+            .is_err();
 
         let new_items = count_new_items(&ctx, newest_id).await?;
         if new_items > 0 {
@@ -692,12 +1112,27 @@ async fn run_crawl_and_notify(ctx: App) -> Result<(), eyre::Error> {
                 .send(FeedEvent::NewEntries { count: new_items });
         }
         Ok::<(), eyre::Error>(())
-    }
-    .await;
+    };
+    let result = lease
+        .hold(crawl)
+        .await
+        .unwrap_or_else(|| Err(eyre::eyre!("lost the lock of the crawl")));
+
+    let status = if progress.is_cancelled() {
+        CrawlStatus::Cancelled
+    } else if crawl_failed || result.is_err() {
+        CrawlStatus::Failed
+    } else {
+        CrawlStatus::Completed
+    };
+    let _ = progress
+        .finish(&ctx, status)
+        .await
+        .inspect_err(|err| tracing::error!(?err, "Failed to record the end of the crawl"));
 
     {
-        let mut in_progress = ctx.recommendation.crawl_in_progress.lock().await;
-        *in_progress = false;
+        let mut current = ctx.recommendation.current_crawl.lock().await;
+        *current = None;
         let mut last_crawl = ctx.recommendation.last_crawl_time.lock().await;
         *last_crawl = Some(Instant::now());
     }
@@ -705,27 +1140,78 @@ async fn run_crawl_and_notify(ctx: App) -> Result<(), eyre::Error> {
     result
 }
 
+/// Imports the default history from the configured Raindrop collections, and
+/// the history of each reader who linked their Raindrop account
 async fn ensure_user_history(ctx: &App) -> Result<usize, eyre::Error> {
-    let sources = fetch_user_history_sources(ctx).await?;
-    tracing::debug!("Fetched {} user history sources", sources.len());
+    let mut inserted = match &ctx.config.raindrop_api_token {
+        Some(token) => {
+            import_raindrop_history(
+                ctx,
+                None,
+                token,
+                &ctx.config.recommender_raindrop_collections,
+            )
+            .await?
+        }
+        None => {
+            tracing::warn!("Raindrop API token not configured, no default history");
+            0
+        }
+    };
+
+    let linked = {
+        let mut conn = ctx.diesel.get().await?;
+        raindrop::linked_accounts(&mut conn).await?
+    };
+    for (identity_id, credentials) in linked {
+        let collections = credentials
+            .collections
+            .iter()
+            .map(RecommenderRaindropCollection::from)
+            .collect::<Vec<_>>();
+        // A revoked token shouldn't stop the others from being imported
+        match import_raindrop_history(ctx, Some(identity_id), &credentials.token, &collections)
+            .await
+        {
+            Ok(count) => inserted += count,
+            Err(err) => tracing::warn!(?err, identity_id, "Failed to import Raindrop history"),
+        }
+    }
+
+    Ok(inserted)
+}
+
+async fn import_raindrop_history(
+    ctx: &App,
+    identity_id: Option<i32>,
+    token: &str,
+    collections: &[RecommenderRaindropCollection],
+) -> Result<usize, eyre::Error> {
+    let sources = fetch_user_history_sources(ctx, token, collections).await?;
+    tracing::debug!(
+        identity_id,
+        "Fetched {} user history sources",
+        sources.len()
+    );
     if sources.is_empty() {
-        tracing::warn!("No user history sources found");
+        tracing::warn!(identity_id, "No user history sources found");
         return Ok(0);
     }
 
-    insert_user_history(ctx, sources).await.inspect(|inserted| {
-        tracing::info!("Inserted {} user history entries", inserted);
-    })
+    insert_user_history(ctx, identity_id, HistorySource::Raindrop, sources)
+        .await
+        .inspect(|inserted| {
+            tracing::info!(identity_id, "Inserted {} user history entries", inserted);
+        })
 }
 
-async fn fetch_user_history_sources(ctx: &App) -> Result<Vec<UserHistorySource>, eyre::Error> {
-    let raindrop_token = match &ctx.config.raindrop_api_token {
-        Some(token) => token,
-        None => return Err(eyre!("Raindrop API token not configured")),
-    };
-
+async fn fetch_user_history_sources(
+    ctx: &App,
+    raindrop_token: &str,
+    collections: &[RecommenderRaindropCollection],
+) -> Result<Vec<UserHistorySource>, eyre::Error> {
     let mut all = Vec::new();
-    for collection in ctx.config.recommender_raindrop_collections.iter() {
+    for collection in collections {
         let mut page = 0;
         let per_page = 50;
 
@@ -789,6 +1275,8 @@ async fn fetch_user_history_sources(ctx: &App) -> Result<Vec<UserHistorySource>,
 
 async fn insert_user_history(
     ctx: &App,
+    identity_id: Option<i32>,
+    source_key: HistorySource,
     sources: Vec<UserHistorySource>,
 ) -> Result<usize, eyre::Error> {
     use crate::schema::online_articles::dsl as articles_dsl;
@@ -823,6 +1311,7 @@ async fn insert_user_history(
                 // if the article is already indexed, just add to history
                 let existing_history = history_dsl::user_history
                     .filter(history_dsl::online_article_id.eq(item.id))
+                    .filter(history_dsl::identity_id.is_not_distinct_from(identity_id))
                     .first::<crate::models::recommendation::UserHistory>(&mut conn)
                     .await
                     .optional()?;
@@ -831,6 +1320,8 @@ async fn insert_user_history(
                         .values(crate::models::recommendation::NewUserHistory {
                             online_article_id: item.id,
                             weight: source.weight,
+                            identity_id,
+                            source: source_key.as_str().to_string(),
                         })
                         .execute(&mut conn)
                         .await?;
@@ -876,9 +1367,13 @@ async fn insert_user_history(
         .map(|entry| {
             let ctx = ctx.clone();
             async move {
-                let article =
-                    crawler::fetch_and_generate_embedding(&ctx, entry.url.clone(), entry.title)
-                        .await?;
+                let article = crawler::fetch_and_generate_embedding(
+                    &ctx,
+                    entry.url.clone(),
+                    entry.title,
+                    None,
+                )
+                .await?;
                 let mut conn = ctx.diesel.get().await?;
                 let article_id = crawler::insert_article(&mut conn, article, None)
                     .await
@@ -891,6 +1386,8 @@ async fn insert_user_history(
                     .values(crate::models::recommendation::NewUserHistory {
                         online_article_id: article_id,
                         weight: entry.weight,
+                        identity_id,
+                        source: source_key.as_str().to_string(),
                     })
                     .execute(&mut conn)
                     .await?;
@@ -0,0 +1,141 @@
+//! Telling the paywalled and login-walled articles apart. Their pages only
+//! have a teaser and a prompt to subscribe, which make junk embeddings, so
+//! they are embedded from the title and the text of the submission instead.
+
+use std::sync::LazyLock;
+
+use regex::Regex;
+
+/// Sites that wall most of their articles, their subdomains included
+const PAYWALLED_DOMAINS: [&str; 16] = [
+    "barrons.com",
+    "bloomberg.com",
+    "businessinsider.com",
+    "economist.com",
+    "foreignaffairs.com",
+    "ft.com",
+    "hbr.org",
+    "newscientist.com",
+    "newyorker.com",
+    "nytimes.com",
+    "technologyreview.com",
+    "telegraph.co.uk",
+    "theatlantic.com",
+    "theinformation.com",
+    "thetimes.co.uk",
+    "wsj.com",
+];
+/// Prompts of the walls, matched on the lowercased text
+const WALL_PHRASES: [&str; 12] = [
+    "subscribe to continue",
+    "subscribe to read",
+    "subscribers only",
+    "already a subscriber",
+    "to continue reading",
+    "sign in to continue",
+    "sign in to read",
+    "log in to continue",
+    "log in to read",
+    "create a free account to",
+    "register to continue",
+    "become a member to read",
+];
+/// A page with fewer words than this and a prompt to subscribe is a teaser
+const MAX_TEASER_WORDS: usize = 250;
+/// Content with fewer words per word of the title is all the wall let
+/// through
+const MIN_WORDS_PER_TITLE_WORD: usize = 8;
+
+/// `isAccessibleForFree: false` of the schema.org metadata, and the locked or
+/// metered `article:content_tier` meta tag
+static PAYWALL_MARKUP: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r#"(?i)"isAccessibleForFree"\s*:\s*"?false|<meta[^>]+article:content_tier["'][^>]*content\s*=\s*["'](?:locked|metered)|<meta[^>]+content\s*=\s*["'](?:locked|metered)["'][^>]*article:content_tier"#,
+    )
+    .expect("valid paywall markup regex")
+});
+
+/// Whether only a teaser of the article could be read, from its site, the
+/// markup of the page and the length of the extracted content
+pub fn is_paywalled(url: &url::Url, html: &str, title: &str, markdown: &str) -> bool {
+    if url.host_str().is_some_and(is_paywalled_domain) || PAYWALL_MARKUP.is_match(html) {
+        return true;
+    }
+
+    let words = markdown.split_whitespace().count();
+    if words < MAX_TEASER_WORDS && has_wall_phrase(markdown) {
+        return true;
+    }
+    // The prompt may have been left out of the extracted content
+    let title_words = title.split_whitespace().count().max(1);
+    words < title_words * MIN_WORDS_PER_TITLE_WORD && has_wall_phrase(html)
+}
+
+fn is_paywalled_domain(host: &str) -> bool {
+    let host = host.trim_end_matches('.').to_ascii_lowercase();
+    PAYWALLED_DOMAINS.iter().any(|domain| {
+        host == *domain
+            || host
+                .strip_suffix(domain)
+                .is_some_and(|subdomain| subdomain.ends_with('.'))
+    })
+}
+
+fn has_wall_phrase(text: &str) -> bool {
+    let text = text.to_lowercase();
+    WALL_PHRASES.iter().any(|phrase| text.contains(phrase))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn url(s: &str) -> url::Url {
+        url::Url::parse(s).expect("url")
+    }
+
+    #[test]
+    fn walled_sites_include_their_subdomains() {
+        assert!(is_paywalled_domain("www.nytimes.com"));
+        assert!(is_paywalled_domain("ft.com"));
+        assert!(!is_paywalled_domain("notft.com"));
+        assert!(!is_paywalled_domain("example.com"));
+    }
+
+    #[test]
+    fn teasers_and_marked_up_walls_are_paywalled() {
+        let article = "word ".repeat(600);
+        let title = "How the compiler got twice as fast";
+
+        assert!(!is_paywalled(
+            &url("https://example.com/a"),
+            "<html></html>",
+            title,
+            &article
+        ));
+        assert!(is_paywalled(
+            &url("https://example.com/a"),
+            r#"<script type="application/ld+json">{"isAccessibleForFree": "False"}</script>"#,
+            title,
+            &article
+        ));
+        assert!(is_paywalled(
+            &url("https://example.com/a"),
+            r#"<meta property="article:content_tier" content="metered">"#,
+            title,
+            &article
+        ));
+        assert!(is_paywalled(
+            &url("https://example.com/a"),
+            "<html></html>",
+            title,
+            "The first paragraph. Subscribe to continue reading."
+        ));
+        assert!(is_paywalled(
+            &url("https://example.com/a"),
+            "<div>Log in to read the rest</div>",
+            title,
+            "The first paragraph."
+        ));
+    }
+}
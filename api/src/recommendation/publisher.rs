@@ -0,0 +1,193 @@
+use std::{collections::HashSet, sync::Arc};
+
+use diesel::prelude::*;
+use diesel_async::RunQueryDsl;
+use eyre::Context as _;
+use serenity::all::{ChannelId, CreateEmbed, CreateMessage, Http};
+
+use super::{FeedItem, RankingPreset, SourceFilter, fetch_feed_items};
+use crate::{App, config::FeedPublisherConfig, jobs, schema::discord_feed_posts};
+
+/// Top ranked items the new ones are picked from
+const CANDIDATE_POOL: i64 = 50;
+
+/// Longest title shown, to stay within the embed description limit
+const MAX_TITLE_CHARS: usize = 200;
+
+/// Periodically post the top recommendations that weren't posted yet to the
+/// configured Discord channel
+pub fn start_discord_publisher(ctx: App) {
+    let (Some(config), Some(token)) = (
+        ctx.config.discord_feed_publisher,
+        ctx.config.discord_token.clone(),
+    ) else {
+        return;
+    };
+
+    let http = Arc::new(Http::new(&token));
+    jobs::spawn_exclusive(&ctx.config.clone(), "feed-publisher", move || {
+        let (ctx, http) = (ctx.clone(), http.clone());
+        async move {
+            // Not right away so that restarts and takeovers don't post
+            let mut interval = tokio::time::interval_at(
+                tokio::time::Instant::now() + config.interval,
+                config.interval,
+            );
+            interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+            loop {
+                interval.tick().await;
+                match publish(&ctx, &http, &config).await {
+                    Ok(0) => tracing::debug!("No new recommendations to post to Discord"),
+                    Ok(posted) => tracing::info!(posted, "Posted recommendations to Discord"),
+                    Err(err) => tracing::warn!(?err, "Failed to post recommendations to Discord"),
+                }
+            }
+        }
+    });
+}
+
+/// Post the top new items, returns how many were posted
+async fn publish(
+    ctx: &App,
+    http: &Http,
+    config: &FeedPublisherConfig,
+) -> Result<usize, eyre::Error> {
+    let candidates = fetch_feed_items(
+        ctx,
+        CANDIDATE_POOL,
+        0,
+        SourceFilter::All,
+        RankingPreset::Balanced,
+        None,
+        &ctx.config.recommender_feed_languages,
+        false,
+    )
+    .await?;
+
+    let channel_id = i64::try_from(config.channel_id).wrap_err("invalid Discord channel ID")?;
+    let mut conn = ctx.diesel.get().await?;
+
+    let posted = discord_feed_posts::table
+        .filter(discord_feed_posts::channel_id.eq(channel_id))
+        .filter(discord_feed_posts::online_article_id.eq_any(candidates.iter().map(|i| i.id)))
+        .select(discord_feed_posts::online_article_id)
+        .load::<i32>(&mut conn)
+        .await
+        .wrap_err("failed to load posted recommendations")?
+        .into_iter()
+        .collect::<HashSet<_>>();
+
+    let items = candidates
+        .into_iter()
+        .filter(|item| !posted.contains(&item.id))
+        .take(config.top_n)
+        .collect::<Vec<_>>();
+    if items.is_empty() {
+        return Ok(0);
+    }
+
+    ChannelId::new(config.channel_id)
+        .send_message(
+            http,
+            CreateMessage::new().embed(
+                CreateEmbed::new()
+                    .title("📰 Top reads")
+                    .description(describe(&items)),
+            ),
+        )
+        .await
+        .wrap_err("failed to send recommendations")?;
+
+    diesel::insert_into(discord_feed_posts::table)
+        .values(
+            items
+                .iter()
+                .map(|item| {
+                    (
+                        discord_feed_posts::online_article_id.eq(item.id),
+                        discord_feed_posts::channel_id.eq(channel_id),
+                    )
+                })
+                .collect::<Vec<_>>(),
+        )
+        .on_conflict_do_nothing()
+        .execute(&mut conn)
+        .await
+        .wrap_err("failed to record posted recommendations")?;
+
+    Ok(items.len())
+}
+
+/// One entry per item with its link, sources and score
+fn describe(items: &[FeedItem]) -> String {
+    items
+        .iter()
+        .enumerate()
+        .map(|(i, item)| {
+            let mut title = item
+                .title
+                .replace(['[', ']'], "")
+                .chars()
+                .take(MAX_TITLE_CHARS)
+                .collect::<String>();
+            if item.title.chars().count() > MAX_TITLE_CHARS {
+                title.push('…');
+            }
+
+            let sources = item
+                .sources
+                .iter()
+                .map(|source| match source.score {
+                    Some(score) => format!("{} ({score:.0})", source.key),
+                    None => source.key.clone(),
+                })
+                .collect::<Vec<_>>()
+                .join(", ");
+
+            format!(
+                "**{}. [{title}]({})**\n{}score {:.3}",
+                i + 1,
+                item.url,
+                if sources.is_empty() {
+                    String::new()
+                } else {
+                    format!("{sources} · ")
+                },
+                item.score
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::recommendation::SourceInfo;
+
+    #[test]
+    fn describe_lists_sources_and_scores() {
+        let items = vec![FeedItem {
+            id: 1,
+            title: "Why [Rust] is fast".to_string(),
+            url: "https://example.com/rust".to_string(),
+            score: 0.1234,
+            similarity_score: None,
+            submitted_at: None,
+            sources: vec![SourceInfo {
+                key: "hackernews".to_string(),
+                score: Some(312.0),
+                external_id: None,
+            }],
+            lang: None,
+            paywalled: false,
+            explanation: None,
+        }];
+
+        assert_eq!(
+            describe(&items),
+            "**1. [Why Rust is fast](https://example.com/rust)**\nhackernews (312) · score 0.123"
+        );
+    }
+}
@@ -0,0 +1,271 @@
+//! Rebuilding the HNSW index of the article embeddings, after bulk inserts or
+//! to try other parameters, run with `cargo run -- reindex-embeddings`. The
+//! recall and latency of the index are measured on sampled chunks before and
+//! after, against an exact search.
+
+use std::{collections::HashSet, fmt, time::Instant};
+
+use diesel::prelude::*;
+use diesel::sql_types::{Integer, Text};
+use diesel_async::{
+    AsyncConnection as _, AsyncPgConnection, RunQueryDsl, SimpleAsyncConnection as _,
+};
+use eyre::Context as _;
+
+use crate::config::ServerConfig;
+
+const INDEX: &str = "online_article_chunks_embedding_hnsw_idx";
+/// Built next to the old index so that the feed never goes without one
+const NEW_INDEX: &str = "online_article_chunks_embedding_hnsw_new_idx";
+
+/// Parameters of the HNSW index, see the pgvector documentation
+#[derive(Clone, Copy, Debug)]
+pub struct IndexParams {
+    /// Connections per layer
+    pub m: u32,
+    /// Candidates kept while building the graph
+    pub ef_construction: u32,
+}
+
+impl IndexParams {
+    /// The bounds pgvector accepts
+    fn validate(self) -> Result<Self, eyre::Error> {
+        if !(2..=100).contains(&self.m) {
+            eyre::bail!("m must be between 2 and 100");
+        }
+        if !(4..=1000).contains(&self.ef_construction) || self.ef_construction < 2 * self.m {
+            eyre::bail!("ef_construction must be between 4 and 1000, and at least twice m");
+        }
+        Ok(self)
+    }
+}
+
+/// How the index does on the sampled queries. Hamming distances often tie,
+/// and the tied neighbours may differ from the exact search's, so the recall
+/// is a lower bound.
+#[derive(Debug)]
+pub struct Measurement {
+    /// Share of the exact nearest neighbours the index returns
+    pub recall: f64,
+    pub mean_ms: f64,
+    pub p95_ms: f64,
+}
+
+#[derive(Debug)]
+pub struct ReindexReport {
+    pub params: IndexParams,
+    pub samples: usize,
+    pub k: u32,
+    /// None if there was no index yet
+    pub before: Option<Measurement>,
+    pub after: Measurement,
+    pub exact_mean_ms: f64,
+}
+
+impl fmt::Display for ReindexReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let measurement = |f: &mut fmt::Formatter<'_>, name: &str, m: &Measurement| {
+            writeln!(
+                f,
+                "{name}: recall@{} {:.3}, mean {:.2} ms, p95 {:.2} ms",
+                self.k, m.recall, m.mean_ms, m.p95_ms
+            )
+        };
+
+        writeln!(
+            f,
+            "Rebuilt {INDEX} with m = {}, ef_construction = {} on {} sampled chunks",
+            self.params.m, self.params.ef_construction, self.samples
+        )?;
+        match &self.before {
+            Some(before) => measurement(f, "before", before)?,
+            None => writeln!(f, "before: no index")?,
+        }
+        measurement(f, "after", &self.after)?;
+        writeln!(f, "exact search: mean {:.2} ms", self.exact_mean_ms)
+    }
+}
+
+#[derive(QueryableByName)]
+struct ChunkId {
+    #[diesel(sql_type = Integer)]
+    id: i32,
+}
+
+pub async fn reindex_embeddings(
+    config: &ServerConfig,
+    params: IndexParams,
+    samples: u32,
+    k: u32,
+) -> Result<ReindexReport, eyre::Error> {
+    let params = params.validate()?;
+    let mut conn = AsyncPgConnection::establish(&config.database_url)
+        .await
+        .wrap_err("failed to connect to the database")?;
+
+    let queries =
+        diesel::sql_query("SELECT id FROM online_article_chunks ORDER BY random() LIMIT $1")
+            .bind::<Integer, _>(i32::try_from(samples).unwrap_or(i32::MAX))
+            .load::<ChunkId>(&mut conn)
+            .await
+            .wrap_err("failed to sample the chunks")?
+            .into_iter()
+            .map(|c| c.id)
+            .collect::<Vec<_>>();
+    if queries.is_empty() {
+        eyre::bail!("there are no embeddings to index");
+    }
+
+    conn.batch_execute("SET enable_indexscan = off")
+        .await
+        .wrap_err("failed to disable the index scans")?;
+    let (exact, exact_latencies) = search(&mut conn, &queries, k).await?;
+    conn.batch_execute("RESET enable_indexscan").await?;
+
+    let before = if index_exists(&mut conn, INDEX).await? {
+        let (results, latencies) = search(&mut conn, &queries, k).await?;
+        Some(measure(&exact, &results, latencies))
+    } else {
+        None
+    };
+
+    tracing::info!(?params, "Building the HNSW index");
+    // CONCURRENTLY can't run in a transaction, so each statement is run alone
+    for statement in [
+        format!("DROP INDEX CONCURRENTLY IF EXISTS {NEW_INDEX}"),
+        format!(
+            "CREATE INDEX CONCURRENTLY {NEW_INDEX} ON online_article_chunks \
+             USING hnsw (embedding bit_hamming_ops) WITH (m = {}, ef_construction = {})",
+            params.m, params.ef_construction
+        ),
+        format!("DROP INDEX CONCURRENTLY IF EXISTS {INDEX}"),
+        format!("ALTER INDEX {NEW_INDEX} RENAME TO {INDEX}"),
+        "ANALYZE online_article_chunks".to_string(),
+    ] {
+        conn.batch_execute(&statement)
+            .await
+            .wrap_err_with(|| format!("failed to run `{statement}`"))?;
+    }
+
+    let (results, latencies) = search(&mut conn, &queries, k).await?;
+
+    Ok(ReindexReport {
+        params,
+        samples: queries.len(),
+        k,
+        before,
+        after: measure(&exact, &results, latencies),
+        exact_mean_ms: mean(&exact_latencies),
+    })
+}
+
+async fn index_exists(conn: &mut AsyncPgConnection, name: &str) -> Result<bool, eyre::Error> {
+    #[derive(QueryableByName)]
+    struct Exists {
+        #[diesel(sql_type = diesel::sql_types::Bool)]
+        found: bool,
+    }
+
+    Ok(
+        diesel::sql_query("SELECT to_regclass($1) IS NOT NULL AS found")
+            .bind::<Text, _>(name)
+            .get_result::<Exists>(conn)
+            .await?
+            .found,
+    )
+}
+
+/// The k nearest chunks of each sampled chunk, with the latency of each
+/// search in milliseconds
+async fn search(
+    conn: &mut AsyncPgConnection,
+    queries: &[i32],
+    k: u32,
+) -> Result<(Vec<HashSet<i32>>, Vec<f64>), eyre::Error> {
+    let mut results = Vec::with_capacity(queries.len());
+    let mut latencies = Vec::with_capacity(queries.len());
+
+    for &query in queries {
+        let started = Instant::now();
+        let neighbours = diesel::sql_query(
+            r#"
+            SELECT id FROM online_article_chunks
+            ORDER BY embedding <~> (SELECT embedding FROM online_article_chunks WHERE id = $1)
+            LIMIT $2
+        "#,
+        )
+        .bind::<Integer, _>(query)
+        .bind::<Integer, _>(i32::try_from(k).unwrap_or(i32::MAX))
+        .load::<ChunkId>(conn)
+        .await
+        .wrap_err("failed to search the nearest chunks")?;
+        latencies.push(started.elapsed().as_secs_f64() * 1000.0);
+        results.push(neighbours.into_iter().map(|c| c.id).collect());
+    }
+
+    Ok((results, latencies))
+}
+
+fn measure(
+    exact: &[HashSet<i32>],
+    results: &[HashSet<i32>],
+    mut latencies: Vec<f64>,
+) -> Measurement {
+    latencies.sort_by(f64::total_cmp);
+    let p95 = latencies
+        .get((latencies.len() * 95).div_ceil(100).saturating_sub(1))
+        .copied()
+        .unwrap_or_default();
+
+    Measurement {
+        recall: recall(exact, results),
+        mean_ms: mean(&latencies),
+        p95_ms: p95,
+    }
+}
+
+/// Share of the exact neighbours found, over all the queries
+fn recall(exact: &[HashSet<i32>], results: &[HashSet<i32>]) -> f64 {
+    let expected = exact.iter().map(HashSet::len).sum::<usize>();
+    if expected == 0 {
+        return 1.0;
+    }
+    let found = exact
+        .iter()
+        .zip(results)
+        .map(|(exact, result)| exact.intersection(result).count())
+        .sum::<usize>();
+    found as f64 / expected as f64
+}
+
+fn mean(values: &[f64]) -> f64 {
+    if values.is_empty() {
+        return 0.0;
+    }
+    values.iter().sum::<f64>() / values.len() as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recall_is_the_share_of_exact_neighbours_found() {
+        let set = |ids: &[i32]| ids.iter().copied().collect::<HashSet<_>>();
+        let exact = [set(&[1, 2, 3, 4]), set(&[5, 6, 7, 8])];
+
+        assert_eq!(recall(&exact, &exact), 1.0);
+        assert_eq!(recall(&exact, &[set(&[1, 2, 3, 9]), set(&[5, 6])]), 0.625);
+        assert_eq!(recall(&[], &[]), 1.0);
+    }
+
+    #[test]
+    fn hnsw_parameters_are_bounded() {
+        let params = |m, ef_construction| IndexParams { m, ef_construction };
+
+        assert!(params(16, 64).validate().is_ok());
+        assert!(params(1, 64).validate().is_err());
+        assert!(params(16, 20).validate().is_err());
+        assert!(params(16, 2000).validate().is_err());
+    }
+}
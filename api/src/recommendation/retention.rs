@@ -0,0 +1,235 @@
+//! Pruning the crawled articles nobody saved or was shown so that the
+//! articles, their chunks and metadata don't grow forever, and the sizes of
+//! the tables for the owner to keep an eye on
+
+use std::time::Duration;
+
+use axum::{Json, Router, extract::State, http::StatusCode, routing::get};
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+use diesel::sql_types::{Array, BigInt, Integer, Text, Timestamp};
+use diesel_async::RunQueryDsl;
+use eyre::Context as _;
+use serde::Serialize;
+
+use crate::{App, error::AppError, identity::AuthUser, jobs};
+
+const RETENTION_INTERVAL: Duration = Duration::from_hours(24);
+/// Small batches keep the locks short and let autovacuum keep up
+const DELETE_BATCH_SIZE: i32 = 500;
+const DELETE_BATCH_PAUSE: Duration = Duration::from_secs(1);
+
+/// The tables of the recommendations, with their chunks and metadata
+const TABLES: [&str; 9] = [
+    "online_articles",
+    "online_article_chunks",
+    "online_article_metadata",
+    "online_article_summaries",
+    "online_article_sources",
+    "user_history",
+    "crawl_runs",
+    "feed_clicks",
+    "feed_impressions",
+];
+
+/// Articles older than the cutoff that aren't in any history, weren't posted
+/// to Discord or clicked in the feed, and weren't submitted to a source since
+const STALE_ARTICLES: &str = r#"
+    SELECT a.id FROM online_articles a
+    WHERE a.created_at < $1
+        AND NOT EXISTS (SELECT 1 FROM user_history h WHERE h.online_article_id = a.id)
+        AND NOT EXISTS (SELECT 1 FROM discord_feed_posts p WHERE p.online_article_id = a.id)
+        AND NOT EXISTS (SELECT 1 FROM feed_clicks c WHERE c.online_article_id = a.id)
+        AND NOT EXISTS (
+            SELECT 1 FROM online_article_metadata m
+            WHERE m.online_article_id = a.id AND m.submitted_at >= $1
+        )
+"#;
+
+pub fn route() -> Router<App> {
+    Router::<App>::new().route("/admin/recommendation/stats", get(get_stats))
+}
+
+/// Prunes the stale articles once a day
+pub fn start_retention_worker(ctx: App) {
+    let Some(days) = ctx.config.recommender_article_retention_days else {
+        return;
+    };
+
+    jobs::spawn_exclusive(&ctx.config.clone(), "article-retention", move || {
+        let ctx = ctx.clone();
+        async move {
+            let mut interval = tokio::time::interval(RETENTION_INTERVAL);
+            interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+            loop {
+                interval.tick().await;
+
+                match prune_stale_articles(&ctx, days).await {
+                    Ok(0) => {}
+                    Ok(deleted) => tracing::info!(deleted, "Pruned stale articles"),
+                    Err(err) => tracing::error!(?err, "Failed to prune stale articles"),
+                }
+            }
+        }
+    });
+}
+
+/// Deletes the stale articles in batches, the chunks and metadata go with
+/// them. Returns the number of deleted articles.
+async fn prune_stale_articles(ctx: &App, days: u32) -> Result<usize, eyre::Error> {
+    let cutoff = retention_cutoff(chrono::Utc::now().naive_utc(), days);
+
+    let mut deleted = 0;
+    loop {
+        let batch = {
+            let mut conn = ctx.diesel.get_unbounded().await?;
+            diesel::sql_query(format!(
+                "DELETE FROM online_articles WHERE id IN ({STALE_ARTICLES} LIMIT $2)"
+            ))
+            .bind::<Timestamp, _>(cutoff)
+            .bind::<Integer, _>(DELETE_BATCH_SIZE)
+            .execute(&mut conn)
+            .await
+            .wrap_err("failed to delete stale articles")?
+        };
+        deleted += batch;
+
+        if batch < DELETE_BATCH_SIZE as usize {
+            return Ok(deleted);
+        }
+        tokio::time::sleep(DELETE_BATCH_PAUSE).await;
+    }
+}
+
+fn retention_cutoff(now: NaiveDateTime, days: u32) -> NaiveDateTime {
+    now.checked_sub_days(chrono::Days::new(u64::from(days)))
+        .unwrap_or(NaiveDateTime::MIN)
+}
+
+#[derive(Serialize)]
+struct RecommendationStats {
+    tables: Vec<TableSize>,
+    indexes: Vec<IndexSize>,
+    /// Articles that the next pruning deletes
+    stale_articles: Option<i64>,
+    retention_days: Option<u32>,
+}
+
+#[derive(Serialize, QueryableByName)]
+struct TableSize {
+    #[diesel(sql_type = Text)]
+    table_name: String,
+    /// Estimated by the planner, exact after an ANALYZE
+    #[diesel(sql_type = BigInt)]
+    row_estimate: i64,
+    /// In bytes, without the indexes and TOAST
+    #[diesel(sql_type = BigInt)]
+    table_bytes: i64,
+    #[diesel(sql_type = BigInt)]
+    index_bytes: i64,
+    /// Everything, TOAST included
+    #[diesel(sql_type = BigInt)]
+    total_bytes: i64,
+}
+
+#[derive(Serialize, QueryableByName)]
+struct IndexSize {
+    #[diesel(sql_type = Text)]
+    table_name: String,
+    #[diesel(sql_type = Text)]
+    index_name: String,
+    #[diesel(sql_type = BigInt)]
+    bytes: i64,
+    /// Scans since the statistics were reset
+    #[diesel(sql_type = BigInt)]
+    scans: i64,
+}
+
+#[derive(QueryableByName)]
+struct Count {
+    #[diesel(sql_type = BigInt)]
+    count: i64,
+}
+
+async fn get_stats(
+    State(ctx): State<App>,
+    AuthUser(i): AuthUser,
+) -> Result<Json<RecommendationStats>, AppError> {
+    if i.id != ctx.config.owner_identity_id {
+        return Err(("Not permitted", StatusCode::FORBIDDEN).into());
+    }
+
+    let mut conn = ctx.diesel.get().await?;
+    let tables = TABLES.map(str::to_string).to_vec();
+
+    let table_sizes = diesel::sql_query(
+        r#"
+        SELECT
+            c.relname::TEXT AS table_name,
+            GREATEST(c.reltuples, 0)::BIGINT AS row_estimate,
+            pg_relation_size(c.oid) AS table_bytes,
+            pg_indexes_size(c.oid) AS index_bytes,
+            pg_total_relation_size(c.oid) AS total_bytes
+        FROM pg_class c
+        JOIN pg_namespace n ON n.oid = c.relnamespace
+        WHERE n.nspname = current_schema() AND c.relkind = 'r' AND c.relname = ANY($1)
+        ORDER BY total_bytes DESC
+    "#,
+    )
+    .bind::<Array<Text>, _>(&tables)
+    .load::<TableSize>(&mut conn)
+    .await?;
+
+    let index_sizes = diesel::sql_query(
+        r#"
+        SELECT
+            s.relname::TEXT AS table_name,
+            s.indexrelname::TEXT AS index_name,
+            pg_relation_size(s.indexrelid) AS bytes,
+            s.idx_scan AS scans
+        FROM pg_stat_user_indexes s
+        WHERE s.schemaname = current_schema() AND s.relname = ANY($1)
+        ORDER BY bytes DESC
+    "#,
+    )
+    .bind::<Array<Text>, _>(&tables)
+    .load::<IndexSize>(&mut conn)
+    .await?;
+
+    let retention_days = ctx.config.recommender_article_retention_days;
+    let stale_articles = match retention_days {
+        Some(days) => Some(
+            diesel::sql_query(format!(
+                "SELECT COUNT(*) AS count FROM ({STALE_ARTICLES}) stale"
+            ))
+            .bind::<Timestamp, _>(retention_cutoff(chrono::Utc::now().naive_utc(), days))
+            .get_result::<Count>(&mut conn)
+            .await?
+            .count,
+        ),
+        None => None,
+    };
+
+    Ok(Json(RecommendationStats {
+        tables: table_sizes,
+        indexes: index_sizes,
+        stale_articles,
+        retention_days,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_cutoff_is_days_before_now() {
+        let now = chrono::DateTime::from_timestamp(1_760_000_000, 0)
+            .expect("timestamp")
+            .naive_utc();
+
+        assert_eq!(retention_cutoff(now, 30), now - chrono::Duration::days(30));
+        assert_eq!(retention_cutoff(now, u32::MAX), NaiveDateTime::MIN);
+    }
+}
@@ -0,0 +1,425 @@
+//! Sources crawled from an RSS or Atom feed, registered in bulk from the OPML
+//! export of a feed reader, and the OPML export of all the sources
+
+use axum::{
+    Json, Router,
+    extract::State,
+    http::{StatusCode, header},
+    response::IntoResponse,
+    routing::{get, post},
+};
+use diesel::prelude::*;
+use diesel_async::{AsyncPgConnection, RunQueryDsl};
+use eyre::{Context as _, bail};
+use roxmltree::{Document, Node};
+use serde::Serialize;
+
+use crate::{
+    App, error::AppError, identity::AuthUser, models::recommendation::OnlineArticleSource,
+    schema::online_article_sources,
+};
+
+use super::crawler::{self, HACKER_NEWS_KEY, LOBSTERS_KEY, SourceEntry};
+
+/// Entries taken per crawl from the feeds that don't set it
+pub const DEFAULT_RSS_ITEMS: usize = 20;
+const KEY_PREFIX: &str = "rss:";
+const MAX_IMPORTED_FEEDS: usize = 500;
+
+pub fn route() -> Router<App> {
+    Router::<App>::new().route("/feed/sources.opml", get(export_opml))
+}
+
+pub fn admin_route() -> Router<App> {
+    Router::<App>::new().route("/admin/feed/sources/import", post(import_opml))
+}
+
+/// The entries of the feed of the source, as many as the feed lists up to
+/// `max_items`
+pub async fn entries(
+    ctx: &App,
+    source: &OnlineArticleSource,
+    feed_url: &str,
+    max_items: usize,
+) -> Result<Vec<SourceEntry>, eyre::Error> {
+    let feed_url = url::Url::parse(feed_url).wrap_err("invalid feed URL")?;
+    let xml = ctx
+        .http
+        .public_only()
+        .get(feed_url.clone())
+        .send()
+        .await?
+        .error_for_status()?
+        .text()
+        .await?;
+
+    parse_feed(
+        &xml,
+        &feed_url,
+        source.id,
+        max_items,
+        chrono::Utc::now().naive_utc(),
+    )
+}
+
+/// The items of an RSS 2.0 or RSS 1.0 feed, or the entries of an Atom feed.
+/// Those without a date are dated `now`, they're taken as just published.
+fn parse_feed(
+    xml: &str,
+    feed_url: &url::Url,
+    source_id: i32,
+    max_items: usize,
+    now: chrono::NaiveDateTime,
+) -> Result<Vec<SourceEntry>, eyre::Error> {
+    let doc = Document::parse(xml).wrap_err("the feed isn't valid XML")?;
+    let root = doc.root_element();
+    let atom = match root.tag_name().name() {
+        "rss" | "RDF" => false,
+        "feed" => true,
+        other => bail!("expected an RSS or Atom feed, got <{other}>"),
+    };
+    let entry_name = if atom { "entry" } else { "item" };
+
+    Ok(root
+        .descendants()
+        .filter(|n| is_element(n, entry_name))
+        .filter_map(|item| {
+            let link = if atom {
+                item.children()
+                    .filter(|n| is_element(n, "link"))
+                    .find(|n| n.attribute("rel").is_none_or(|rel| rel == "alternate"))
+                    .and_then(|n| n.attribute("href"))
+                    .map(str::to_string)
+            } else {
+                child_text(item, "link")
+            }?;
+            let url = feed_url
+                .join(&link)
+                .ok()
+                .filter(|url| matches!(url.scheme(), "http" | "https"))?;
+
+            let submitted_at = if atom {
+                child_text(item, "published")
+                    .or_else(|| child_text(item, "updated"))
+                    .and_then(|date| chrono::DateTime::parse_from_rfc3339(&date).ok())
+            } else {
+                child_text(item, "pubDate")
+                    .and_then(|date| chrono::DateTime::parse_from_rfc2822(&date).ok())
+                    .or_else(|| {
+                        child_text(item, "date")
+                            .and_then(|date| chrono::DateTime::parse_from_rfc3339(&date).ok())
+                    })
+            }
+            .map_or(now, |date| date.naive_utc());
+
+            let id = child_text(item, if atom { "id" } else { "guid" });
+            Some(SourceEntry {
+                source_id,
+                title: child_text(item, "title"),
+                external_id: id.unwrap_or_else(|| url.to_string()),
+                url,
+                external_score: None,
+                submitted_at,
+                submission_text: None,
+                comment_count: None,
+            })
+        })
+        .take(max_items)
+        .collect())
+}
+
+/// A feed listed in an OPML file
+#[derive(Debug, PartialEq)]
+struct OpmlFeed {
+    title: Option<String>,
+    feed_url: url::Url,
+    site_url: Option<String>,
+}
+
+/// The feeds of the outlines at any depth, feed readers nest them in folders
+fn parse_opml(xml: &str) -> Result<Vec<OpmlFeed>, eyre::Error> {
+    let doc = Document::parse(xml).wrap_err("the file isn't valid XML")?;
+    if !is_element(&doc.root_element(), "opml") {
+        bail!("expected an OPML document");
+    }
+
+    let mut feeds = Vec::<OpmlFeed>::new();
+    for outline in doc.descendants().filter(|n| is_element(n, "outline")) {
+        let Some(feed_url) = outline
+            .attribute("xmlUrl")
+            .and_then(|url| url::Url::parse(url.trim()).ok())
+            .filter(|url| matches!(url.scheme(), "http" | "https"))
+        else {
+            continue;
+        };
+        if feeds.iter().any(|f| f.feed_url == feed_url) {
+            continue;
+        }
+
+        feeds.push(OpmlFeed {
+            title: outline
+                .attribute("title")
+                .or_else(|| outline.attribute("text"))
+                .map(str::trim)
+                .filter(|t| !t.is_empty())
+                .map(str::to_string),
+            feed_url,
+            site_url: outline.attribute("htmlUrl").map(str::to_string),
+        });
+    }
+    Ok(feeds)
+}
+
+fn to_opml(feeds: &[OpmlFeed]) -> String {
+    let outlines = feeds
+        .iter()
+        .map(|feed| {
+            let title = escape(feed.title.as_deref().unwrap_or(feed.feed_url.as_str()));
+            let site_url = feed
+                .site_url
+                .as_deref()
+                .map(|url| format!(r#" htmlUrl="{}""#, escape(url)))
+                .unwrap_or_default();
+            format!(
+                r#"    <outline type="rss" text="{title}" title="{title}" xmlUrl="{}"{site_url}/>"#,
+                escape(feed.feed_url.as_str())
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<opml version="2.0">
+  <head>
+    <title>Recommendation sources</title>
+  </head>
+  <body>
+{outlines}
+  </body>
+</opml>
+"#
+    )
+}
+
+/// The enabled sources as OPML, Lobsters and Hacker News with their public
+/// feeds
+#[utoipa::path(
+    get,
+    path = "/feed/sources.opml",
+    tag = "recommendation",
+    responses((
+        status = 200,
+        description = "The sources of the recommendations",
+        content_type = "text/x-opml",
+        body = String,
+    )),
+)]
+pub async fn export_opml(State(ctx): State<App>) -> Result<impl IntoResponse, AppError> {
+    let sources = crawler::crawled_sources(&ctx).await?;
+
+    let feeds = sources
+        .into_iter()
+        .filter(|source| source.enabled)
+        .filter_map(|source| {
+            let feed_url = match source.key.as_str() {
+                LOBSTERS_KEY => Some("https://lobste.rs/rss"),
+                HACKER_NEWS_KEY => Some("https://news.ycombinator.com/rss"),
+                _ => source.feed_url.as_deref(),
+            }
+            .and_then(|url| url::Url::parse(url).ok())?;
+            Some(OpmlFeed {
+                title: Some(source.name),
+                feed_url,
+                site_url: source.base_url,
+            })
+        })
+        .collect::<Vec<_>>();
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, "text/x-opml; charset=utf-8"),
+            (
+                header::CONTENT_DISPOSITION,
+                r#"attachment; filename="sources.opml""#,
+            ),
+        ],
+        to_opml(&feeds),
+    ))
+}
+
+#[derive(Serialize)]
+struct OpmlImport {
+    added: usize,
+    /// Feeds that were already sources
+    existing: usize,
+}
+
+/// Registers the feeds of the OPML export of a feed reader as sources, they
+/// are crawled from the next crawl
+async fn import_opml(
+    State(ctx): State<App>,
+    AuthUser(i): AuthUser,
+    body: String,
+) -> Result<Json<OpmlImport>, AppError> {
+    if i.id != ctx.config.owner_identity_id {
+        return Err(("Not permitted", StatusCode::FORBIDDEN).into());
+    }
+
+    let feeds = parse_opml(&body).map_err(|_| ("Not an OPML file", StatusCode::BAD_REQUEST))?;
+    if feeds.len() > MAX_IMPORTED_FEEDS {
+        return Err(("Too many feeds, at most 500", StatusCode::BAD_REQUEST).into());
+    }
+
+    let mut conn = ctx.diesel.get().await?;
+    let mut import = OpmlImport {
+        added: 0,
+        existing: 0,
+    };
+    for feed in feeds {
+        let exists = diesel::select(diesel::dsl::exists(
+            online_article_sources::table
+                .filter(online_article_sources::feed_url.eq(feed.feed_url.as_str())),
+        ))
+        .get_result::<bool>(&mut conn)
+        .await?;
+        if exists {
+            import.existing += 1;
+            continue;
+        }
+
+        let key = free_key(&mut conn, &feed.feed_url).await?;
+        let name = feed
+            .title
+            .unwrap_or_else(|| key[KEY_PREFIX.len()..].to_string());
+        diesel::insert_into(online_article_sources::table)
+            .values((
+                online_article_sources::key.eq(key),
+                online_article_sources::name.eq(name),
+                online_article_sources::base_url.eq(feed.site_url),
+                online_article_sources::feed_url.eq(feed.feed_url.as_str()),
+            ))
+            .execute(&mut conn)
+            .await?;
+        import.added += 1;
+    }
+
+    Ok(Json(import))
+}
+
+/// `rss:` and the host of the feed, numbered if the host has other feeds
+async fn free_key(
+    conn: &mut AsyncPgConnection,
+    feed_url: &url::Url,
+) -> Result<String, eyre::Error> {
+    let base = format!("{KEY_PREFIX}{}", feed_url.host_str().unwrap_or_default());
+    let taken = online_article_sources::table
+        .filter(online_article_sources::key.like(format!("{base}%")))
+        .select(online_article_sources::key)
+        .load::<String>(conn)
+        .await?;
+
+    Ok(std::iter::once(base.clone())
+        .chain((2..).map(|n| format!("{base}-{n}")))
+        .find(|key| !taken.contains(key))
+        .unwrap_or(base))
+}
+
+fn is_element(node: &Node, name: &str) -> bool {
+    node.is_element() && node.tag_name().name() == name
+}
+
+/// The first child named `name` with some text, RSS items can have an empty
+/// `atom:link` next to their `link`
+fn child_text(node: Node, name: &str) -> Option<String> {
+    node.children()
+        .filter(|n| is_element(n, name))
+        .filter_map(|n| n.text())
+        .map(str::trim)
+        .find(|text| !text.is_empty())
+        .map(str::to_string)
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rss_and_atom_entries_are_parsed() {
+        let feed_url = url::Url::parse("https://example.com/feed.xml").expect("url");
+        let now = chrono::DateTime::from_timestamp(1_760_000_000, 0)
+            .expect("timestamp")
+            .naive_utc();
+
+        let rss = r#"<?xml version="1.0"?>
+            <rss version="2.0"><channel><title>Blog</title>
+                <item>
+                    <title><![CDATA[Hello & welcome]]></title>
+                    <link>https://example.com/hello</link>
+                    <guid>hello</guid>
+                    <pubDate>Wed, 08 Oct 2025 10:00:00 GMT</pubDate>
+                </item>
+                <item><title>No link</title></item>
+                <item><link>/relative</link></item>
+            </channel></rss>"#;
+        let entries = parse_feed(rss, &feed_url, 3, 10, now).expect("rss");
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].title.as_deref(), Some("Hello & welcome"));
+        assert_eq!(entries[0].external_id, "hello");
+        assert_eq!(
+            entries[0].submitted_at,
+            chrono::NaiveDate::from_ymd_opt(2025, 10, 8)
+                .and_then(|d| d.and_hms_opt(10, 0, 0))
+                .expect("date")
+        );
+        assert_eq!(entries[1].url.as_str(), "https://example.com/relative");
+        assert_eq!(entries[1].submitted_at, now);
+
+        let atom = r#"<feed xmlns="http://www.w3.org/2005/Atom">
+                <entry>
+                    <title>Post</title>
+                    <link rel="edit" href="https://example.com/edit/1"/>
+                    <link href="https://example.com/post"/>
+                    <id>urn:post:1</id>
+                    <updated>2025-10-08T10:00:00Z</updated>
+                </entry>
+            </feed>"#;
+        let entries = parse_feed(atom, &feed_url, 3, 10, now).expect("atom");
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].url.as_str(), "https://example.com/post");
+        assert_eq!(entries[0].external_id, "urn:post:1");
+
+        assert!(parse_feed("<html></html>", &feed_url, 3, 10, now).is_err());
+    }
+
+    #[test]
+    fn exported_opml_is_imported_back() {
+        let opml = r#"<opml version="1.0"><body>
+                <outline text="Tech">
+                    <outline text="A &amp; B" xmlUrl="https://a.example/rss" htmlUrl="https://a.example/"/>
+                    <outline text="Duplicate" xmlUrl="https://a.example/rss"/>
+                </outline>
+                <outline title="Folder only"/>
+                <outline text="Mail" xmlUrl="mailto:someone@example.com"/>
+            </body></opml>"#;
+
+        let feeds = parse_opml(opml).expect("opml");
+        assert_eq!(
+            feeds,
+            vec![OpmlFeed {
+                title: Some("A & B".to_string()),
+                feed_url: url::Url::parse("https://a.example/rss").expect("url"),
+                site_url: Some("https://a.example/".to_string()),
+            }]
+        );
+        assert_eq!(parse_opml(&to_opml(&feeds)).expect("export"), feeds);
+        assert!(parse_opml("<rss/>").is_err());
+    }
+}
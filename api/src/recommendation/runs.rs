@@ -0,0 +1,253 @@
+//! Starting, following and cancelling the crawls, and adjusting how each
+//! source is crawled, for the owner
+
+use axum::{
+    Json, Router,
+    extract::{Path, Query, State},
+    http::StatusCode,
+    routing::{get, post, put},
+};
+use diesel::prelude::*;
+use diesel_async::RunQueryDsl;
+use serde::Deserialize;
+
+use crate::{
+    App,
+    error::AppError,
+    identity::AuthUser,
+    models::recommendation::{CrawlRun as CrawlRunRow, OnlineArticleSource},
+    schema::{crawl_runs, online_article_sources},
+};
+
+use super::{
+    CrawlRun, CrawlSource, CrawlStatus, CrawlTrigger, begin_crawl, crawl_and_notify, crawler,
+};
+
+const DEFAULT_RUNS: i64 = 20;
+const MAX_RUNS: i64 = 100;
+const MAX_SOURCE_ITEMS: i32 = 500;
+/// A week
+const MAX_CRAWL_INTERVAL_MINS: i32 = 7 * 24 * 60;
+
+pub fn route() -> Router<App> {
+    Router::<App>::new()
+        .route("/admin/crawl", get(list_runs).post(start_crawl))
+        .route("/admin/crawl/current", get(get_current_run))
+        .route("/admin/crawl/sources", get(list_sources))
+        .route("/admin/crawl/sources/{key}", put(update_source))
+        .route("/admin/crawl/{id}", get(get_run))
+        .route("/admin/crawl/{id}/cancel", post(cancel_run))
+}
+
+/// Starts a crawl right away, even if one ran recently
+async fn start_crawl(
+    State(ctx): State<App>,
+    AuthUser(i): AuthUser,
+) -> Result<(StatusCode, Json<CrawlRun>), AppError> {
+    ensure_owner(&ctx, i.id)?;
+
+    let (progress, lease) = begin_crawl(&ctx, CrawlTrigger::Admin)
+        .await?
+        .ok_or(("A crawl is already running", StatusCode::CONFLICT))?;
+    let run = progress.snapshot();
+
+    tokio::spawn(async move {
+        if let Err(err) = crawl_and_notify(ctx, progress, lease).await {
+            tracing::warn!(?err, "recommendation crawl failed");
+        }
+    });
+
+    Ok((StatusCode::ACCEPTED, Json(run)))
+}
+
+#[derive(Deserialize)]
+struct RunsQuery {
+    /// Only the runs started before this one
+    before: Option<i32>,
+    limit: Option<i64>,
+}
+
+/// The latest runs, newest first
+async fn list_runs(
+    State(ctx): State<App>,
+    AuthUser(i): AuthUser,
+    Query(query): Query<RunsQuery>,
+) -> Result<Json<Vec<CrawlRun>>, AppError> {
+    ensure_owner(&ctx, i.id)?;
+
+    let mut conn = ctx.diesel.get().await?;
+
+    let mut runs = crawl_runs::table
+        .select(CrawlRunRow::as_select())
+        .order(crawl_runs::id.desc())
+        .limit(query.limit.unwrap_or(DEFAULT_RUNS).clamp(1, MAX_RUNS))
+        .into_boxed();
+    if let Some(before) = query.before {
+        runs = runs.filter(crawl_runs::id.lt(before));
+    }
+    let runs = runs.load(&mut conn).await?;
+
+    Ok(Json(runs.into_iter().map(to_run).collect()))
+}
+
+/// The running crawl with its live progress
+async fn get_current_run(
+    State(ctx): State<App>,
+    AuthUser(i): AuthUser,
+) -> Result<Json<CrawlRun>, AppError> {
+    ensure_owner(&ctx, i.id)?;
+
+    let current = ctx.recommendation.current_crawl.lock().await;
+    let progress = current
+        .as_ref()
+        .ok_or(("No crawl is running", StatusCode::NOT_FOUND))?;
+
+    Ok(Json(progress.snapshot()))
+}
+
+async fn get_run(
+    State(ctx): State<App>,
+    AuthUser(i): AuthUser,
+    Path(id): Path<i32>,
+) -> Result<Json<CrawlRun>, AppError> {
+    ensure_owner(&ctx, i.id)?;
+
+    {
+        let current = ctx.recommendation.current_crawl.lock().await;
+        if let Some(progress) = current.as_ref().filter(|p| p.run_id == id) {
+            return Ok(Json(progress.snapshot()));
+        }
+    }
+
+    let mut conn = ctx.diesel.get().await?;
+    let run = crawl_runs::table
+        .filter(crawl_runs::id.eq(id))
+        .select(CrawlRunRow::as_select())
+        .first(&mut conn)
+        .await
+        .optional()?
+        .ok_or(("Crawl not found", StatusCode::NOT_FOUND))?;
+
+    Ok(Json(to_run(run)))
+}
+
+/// Stops the crawl before its next article, the articles being fetched are
+/// still stored
+async fn cancel_run(
+    State(ctx): State<App>,
+    AuthUser(i): AuthUser,
+    Path(id): Path<i32>,
+) -> Result<StatusCode, AppError> {
+    ensure_owner(&ctx, i.id)?;
+
+    let current = ctx.recommendation.current_crawl.lock().await;
+    let progress = current
+        .as_ref()
+        .filter(|p| p.run_id == id)
+        .ok_or(("The crawl isn't running", StatusCode::CONFLICT))?;
+    progress.cancel();
+
+    Ok(StatusCode::ACCEPTED)
+}
+
+async fn list_sources(
+    State(ctx): State<App>,
+    AuthUser(i): AuthUser,
+) -> Result<Json<Vec<CrawlSource>>, AppError> {
+    ensure_owner(&ctx, i.id)?;
+
+    let sources = crawler::crawled_sources(&ctx).await?;
+
+    Ok(Json(sources.into_iter().map(to_source).collect()))
+}
+
+#[derive(Deserialize)]
+struct SourceSettings {
+    enabled: bool,
+    /// The crawl interval of the config if unset
+    crawl_interval_mins: Option<i32>,
+    /// The crawler's default if unset
+    max_items: Option<i32>,
+}
+
+/// Takes effect from the next crawl, the schedule is computed after each one
+async fn update_source(
+    State(ctx): State<App>,
+    AuthUser(i): AuthUser,
+    Path(key): Path<String>,
+    Json(settings): Json<SourceSettings>,
+) -> Result<Json<CrawlSource>, AppError> {
+    ensure_owner(&ctx, i.id)?;
+
+    if settings
+        .crawl_interval_mins
+        .is_some_and(|mins| !(1..=MAX_CRAWL_INTERVAL_MINS).contains(&mins))
+    {
+        return Err((
+            "The interval must be between 1 minute and a week",
+            StatusCode::BAD_REQUEST,
+        )
+            .into());
+    }
+    if settings
+        .max_items
+        .is_some_and(|n| !(1..=MAX_SOURCE_ITEMS).contains(&n))
+    {
+        return Err((
+            "The number of items must be between 1 and 500",
+            StatusCode::BAD_REQUEST,
+        )
+            .into());
+    }
+
+    let mut conn = ctx.diesel.get().await?;
+    let source =
+        diesel::update(online_article_sources::table.filter(online_article_sources::key.eq(&key)))
+            .set((
+                online_article_sources::enabled.eq(settings.enabled),
+                online_article_sources::crawl_interval_mins.eq(settings.crawl_interval_mins),
+                online_article_sources::max_items.eq(settings.max_items),
+            ))
+            .returning(OnlineArticleSource::as_returning())
+            .get_result(&mut conn)
+            .await
+            .optional()?
+            .ok_or(("Source not found", StatusCode::NOT_FOUND))?;
+
+    Ok(Json(to_source(source)))
+}
+
+fn to_source(row: OnlineArticleSource) -> CrawlSource {
+    CrawlSource {
+        key: row.key,
+        name: row.name,
+        enabled: row.enabled,
+        crawl_interval_mins: row.crawl_interval_mins,
+        max_items: row.max_items,
+        last_crawled_at: row.last_crawled_at,
+        feed_url: row.feed_url,
+    }
+}
+
+fn to_run(row: CrawlRunRow) -> CrawlRun {
+    CrawlRun {
+        id: row.id,
+        // Only the known statuses are written
+        status: CrawlStatus::parse(&row.status).unwrap_or(CrawlStatus::Failed),
+        trigger: CrawlTrigger::parse(&row.trigger).unwrap_or(CrawlTrigger::Schedule),
+        fetched: row.fetched,
+        embedded: row.embedded,
+        failed: row.failed,
+        failures: serde_json::from_value(row.failures).unwrap_or_default(),
+        timings: serde_json::from_value(row.timings).unwrap_or_default(),
+        started_at: row.started_at,
+        finished_at: row.finished_at,
+    }
+}
+
+fn ensure_owner(ctx: &App, identity_id: i32) -> Result<(), AppError> {
+    if identity_id != ctx.config.owner_identity_id {
+        return Err(("Not permitted", StatusCode::FORBIDDEN).into());
+    }
+    Ok(())
+}
@@ -0,0 +1,501 @@
+//! Pages saved to the reader's history one by one, from a browser extension or
+//! a bookmarklet authenticated with a token of the reader, and the entries the
+//! reader marked as disliked
+
+use axum::{
+    Json, Router,
+    extract::{Path, State},
+    http::{HeaderMap, StatusCode, header},
+    routing::{delete, get, post, put},
+};
+use base64::{Engine as _, engine::general_purpose::URL_SAFE_NO_PAD};
+use diesel::prelude::*;
+use diesel::sql_types::{Integer, Nullable};
+use diesel_async::RunQueryDsl;
+use rand::TryRng as _;
+use serde::Deserialize;
+use sha2::{Digest as _, Sha256};
+use utoipa::ToSchema;
+
+use crate::{
+    App,
+    crypto::random,
+    error::AppError,
+    identity::AuthUser,
+    models::recommendation::{
+        HistoryJob as HistoryJobRow, HistoryToken as HistoryTokenRow, NewHistoryJob,
+        NewHistoryToken, NewUserHistory,
+    },
+    schema::{history_jobs, history_tokens, online_articles, user_history},
+};
+
+use super::{
+    HistoryJob, HistoryJobStatus, HistorySource, HistoryToken, crawler, history_owner_for,
+};
+
+const TOKEN_PREFIX: &str = "wrxh_";
+const TOKEN_BYTES: usize = 32;
+const MAX_TOKENS: i64 = 10;
+const MAX_TOKEN_NAME_CHARS: usize = 50;
+const MAX_URL_CHARS: usize = 2048;
+const MAX_TITLE_CHARS: usize = 500;
+const MAX_ERROR_CHARS: usize = 500;
+/// A page the reader chose to save counts as much as the best Raindrop
+/// collection by default
+const DEFAULT_WEIGHT: f64 = 0.8;
+/// What the ranking counts the entries without a weight as
+const UNWEIGHTED: f64 = 0.1;
+
+pub fn route() -> Router<App> {
+    Router::<App>::new()
+        .route("/history", post(save_page))
+        .route("/history/{id}", get(get_job))
+        .route("/me/history-tokens", get(list_tokens).post(create_token))
+        .route("/me/history-tokens/{id}", delete(delete_token))
+        .route(
+            "/me/history/{article_id}/negative",
+            put(mark_negative).delete(unmark_negative),
+        )
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct SavedPage {
+    url: String,
+    /// The title of the page in the browser, the fetched one is used if absent
+    title: Option<String>,
+    /// How much the page counts, from -1 to 1. Pages with a negative weight
+    /// are disliked, and the articles similar to them are demoted.
+    weight: Option<f64>,
+}
+
+/// Fetching and embedding a page takes a while, so it's done in the
+/// background and the job can be polled with the returned ID
+#[utoipa::path(
+    post,
+    path = "/history",
+    tag = "recommendation",
+    request_body = SavedPage,
+    responses(
+        (status = 202, description = "The page is being saved", body = HistoryJob),
+        (status = 400, description = "Invalid URL or weight"),
+        (status = 401, description = "Missing or invalid token"),
+    ),
+    security(("history_token" = [])),
+)]
+pub async fn save_page(
+    State(ctx): State<App>,
+    headers: HeaderMap,
+    Json(page): Json<SavedPage>,
+) -> Result<(StatusCode, Json<HistoryJob>), AppError> {
+    let identity_id = authenticate(&ctx, &headers).await?;
+
+    if page.url.chars().count() > MAX_URL_CHARS {
+        return Err(("The URL is too long", StatusCode::BAD_REQUEST).into());
+    }
+    let url = url::Url::parse(page.url.trim())
+        .ok()
+        .filter(|url| matches!(url.scheme(), "http" | "https"))
+        .ok_or(("The URL must be an http(s) URL", StatusCode::BAD_REQUEST))?;
+    let url = crawler::canonicalize_url(url)?;
+    let weight = page.weight.unwrap_or(DEFAULT_WEIGHT);
+    if !(-1.0..=1.0).contains(&weight) {
+        return Err(("The weight must be from -1 to 1", StatusCode::BAD_REQUEST).into());
+    }
+    let title = page
+        .title
+        .map(|t| t.trim().chars().take(MAX_TITLE_CHARS).collect::<String>())
+        .filter(|t| !t.is_empty());
+
+    let mut conn = ctx.diesel.get().await?;
+    let job = diesel::insert_into(history_jobs::table)
+        .values(&NewHistoryJob {
+            identity_id,
+            url: url.to_string(),
+            weight: Some(weight),
+        })
+        .returning(HistoryJobRow::as_returning())
+        .get_result(&mut conn)
+        .await?;
+
+    let job_ctx = ctx.clone();
+    let job_id = job.id;
+    tokio::spawn(async move {
+        let result = record_page(&job_ctx, identity_id, url, title, weight).await;
+        if let Err(err) = &result {
+            tracing::warn!(?err, job_id, "Failed to save a page to the history");
+        }
+        let _ = finish_job(&job_ctx, job_id, result.err())
+            .await
+            .inspect_err(|err| tracing::error!(?err, job_id, "Failed to record a history job"));
+    });
+
+    Ok((StatusCode::ACCEPTED, Json(to_job(job))))
+}
+
+#[utoipa::path(
+    get,
+    path = "/history/{id}",
+    tag = "recommendation",
+    params(("id" = i32, Path)),
+    responses(
+        (status = 200, description = "Progress of the saved page", body = HistoryJob),
+        (status = 404, description = "No such job"),
+    ),
+    security(("history_token" = [])),
+)]
+pub async fn get_job(
+    State(ctx): State<App>,
+    headers: HeaderMap,
+    Path(id): Path<i32>,
+) -> Result<Json<HistoryJob>, AppError> {
+    let identity_id = authenticate(&ctx, &headers).await?;
+
+    let mut conn = ctx.diesel.get().await?;
+    let job = history_jobs::table
+        .filter(history_jobs::id.eq(id))
+        .filter(history_jobs::identity_id.eq(identity_id))
+        .select(HistoryJobRow::as_select())
+        .first(&mut conn)
+        .await
+        .optional()?
+        .ok_or(("No such job", StatusCode::NOT_FOUND))?;
+
+    Ok(Json(to_job(job)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/me/history-tokens",
+    tag = "recommendation",
+    responses(
+        (status = 200, description = "The tokens of the reader's extensions", body = Vec<HistoryToken>),
+        (status = 401, description = "Not logged in"),
+    ),
+    security(("session" = [])),
+)]
+pub async fn list_tokens(
+    State(ctx): State<App>,
+    AuthUser(i): AuthUser,
+) -> Result<Json<Vec<HistoryToken>>, AppError> {
+    let mut conn = ctx.diesel.get().await?;
+
+    let tokens = history_tokens::table
+        .filter(history_tokens::identity_id.eq(i.id))
+        .select(HistoryTokenRow::as_select())
+        .order(history_tokens::created_at.desc())
+        .load(&mut conn)
+        .await?;
+
+    Ok(Json(
+        tokens.into_iter().map(|t| to_token(t, None)).collect(),
+    ))
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct TokenSubmission {
+    /// Where the token is used, e.g. the browser
+    name: String,
+}
+
+#[utoipa::path(
+    post,
+    path = "/me/history-tokens",
+    tag = "recommendation",
+    request_body = TokenSubmission,
+    responses(
+        (status = 200, description = "The token, shown only this once", body = HistoryToken),
+        (status = 401, description = "Not logged in"),
+    ),
+    security(("session" = [])),
+)]
+pub async fn create_token(
+    State(ctx): State<App>,
+    AuthUser(i): AuthUser,
+    Json(submission): Json<TokenSubmission>,
+) -> Result<Json<HistoryToken>, AppError> {
+    let name = submission.name.trim().to_string();
+    if name.is_empty() || name.chars().count() > MAX_TOKEN_NAME_CHARS {
+        return Err((
+            "The name must be between 1 and 50 characters",
+            StatusCode::BAD_REQUEST,
+        )
+            .into());
+    }
+
+    let mut conn = ctx.diesel.get().await?;
+    let count = history_tokens::table
+        .filter(history_tokens::identity_id.eq(i.id))
+        .count()
+        .get_result::<i64>(&mut conn)
+        .await?;
+    if count >= MAX_TOKENS {
+        return Err(("Too many tokens, revoke one first", StatusCode::CONFLICT).into());
+    }
+
+    let mut bytes = [0u8; TOKEN_BYTES];
+    random::get_rng()
+        .try_fill_bytes(&mut bytes)
+        .map_err(|_| eyre::eyre!("could not generate history token"))?;
+    let token = format!("{TOKEN_PREFIX}{}", URL_SAFE_NO_PAD.encode(bytes));
+
+    let row = diesel::insert_into(history_tokens::table)
+        .values(&NewHistoryToken {
+            identity_id: i.id,
+            name,
+            token_hash: hash_token(&token),
+        })
+        .returning(HistoryTokenRow::as_returning())
+        .get_result(&mut conn)
+        .await?;
+
+    Ok(Json(to_token(row, Some(token))))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/me/history-tokens/{id}",
+    tag = "recommendation",
+    params(("id" = i32, Path)),
+    responses(
+        (status = 204, description = "The token is revoked"),
+        (status = 404, description = "No such token"),
+    ),
+    security(("session" = [])),
+)]
+pub async fn delete_token(
+    State(ctx): State<App>,
+    AuthUser(i): AuthUser,
+    Path(id): Path<i32>,
+) -> Result<StatusCode, AppError> {
+    let mut conn = ctx.diesel.get().await?;
+
+    let deleted = diesel::delete(
+        history_tokens::table
+            .filter(history_tokens::id.eq(id))
+            .filter(history_tokens::identity_id.eq(i.id)),
+    )
+    .execute(&mut conn)
+    .await?;
+    if deleted == 0 {
+        return Err(("No such token", StatusCode::NOT_FOUND).into());
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Marks the article of the history as disliked, the articles similar to it
+/// are demoted as much as they were promoted
+#[utoipa::path(
+    put,
+    path = "/me/history/{article_id}/negative",
+    tag = "recommendation",
+    params(("article_id" = i32, Path)),
+    responses(
+        (status = 204, description = "The article is a negative example"),
+        (status = 404, description = "The article isn't in the history"),
+    ),
+    security(("session" = [])),
+)]
+pub async fn mark_negative(
+    State(ctx): State<App>,
+    AuthUser(i): AuthUser,
+    Path(article_id): Path<i32>,
+) -> Result<StatusCode, AppError> {
+    set_weight_sign(&ctx, i.id, article_id, true).await
+}
+
+/// Makes the disliked article of the history a positive example again
+#[utoipa::path(
+    delete,
+    path = "/me/history/{article_id}/negative",
+    tag = "recommendation",
+    params(("article_id" = i32, Path)),
+    responses(
+        (status = 204, description = "The article is a positive example"),
+        (status = 404, description = "The article isn't in the history"),
+    ),
+    security(("session" = [])),
+)]
+pub async fn unmark_negative(
+    State(ctx): State<App>,
+    AuthUser(i): AuthUser,
+    Path(article_id): Path<i32>,
+) -> Result<StatusCode, AppError> {
+    set_weight_sign(&ctx, i.id, article_id, false).await
+}
+
+/// Flips the weight of the entry, keeping how much it counts. The owner
+/// without a history of their own edits the default history.
+async fn set_weight_sign(
+    ctx: &App,
+    identity_id: i32,
+    article_id: i32,
+    negative: bool,
+) -> Result<StatusCode, AppError> {
+    let history_owner = match history_owner_for(ctx, identity_id).await? {
+        Some(owner) => Some(owner),
+        None if identity_id == ctx.config.owner_identity_id => None,
+        None => return Err(("The article isn't in the history", StatusCode::NOT_FOUND).into()),
+    };
+
+    let sign = if negative { "-" } else { "" };
+    let mut conn = ctx.diesel.get().await?;
+    let updated = diesel::sql_query(format!(
+        r#"
+        UPDATE user_history SET weight = {sign}ABS(COALESCE(weight, {UNWEIGHTED}))
+        WHERE online_article_id = $1 AND identity_id IS NOT DISTINCT FROM $2
+    "#
+    ))
+    .bind::<Integer, _>(article_id)
+    .bind::<Nullable<Integer>, _>(history_owner)
+    .execute(&mut conn)
+    .await?;
+    if updated == 0 {
+        return Err(("The article isn't in the history", StatusCode::NOT_FOUND).into());
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// The identity of the bearer token
+async fn authenticate(ctx: &App, headers: &HeaderMap) -> Result<i32, AppError> {
+    let token = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .map(str::trim)
+        .filter(|t| t.starts_with(TOKEN_PREFIX))
+        .ok_or(("Missing history token", StatusCode::UNAUTHORIZED))?;
+
+    let mut conn = ctx.diesel.get().await?;
+    diesel::update(history_tokens::table.filter(history_tokens::token_hash.eq(hash_token(token))))
+        .set(history_tokens::last_used_at.eq(diesel::dsl::now))
+        .returning(history_tokens::identity_id)
+        .get_result::<i32>(&mut conn)
+        .await
+        .optional()?
+        .ok_or(("Invalid history token", StatusCode::UNAUTHORIZED).into())
+}
+
+/// Tokens are random rather than passwords, so a plain digest is enough to
+/// look them up without storing them
+fn hash_token(token: &str) -> String {
+    Sha256::digest(token.as_bytes())
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect()
+}
+
+/// Adds the page to the history, fetching and embedding it first if it isn't
+/// indexed yet. The weight is updated if the page is already in the history.
+async fn record_page(
+    ctx: &App,
+    identity_id: i32,
+    url: url::Url,
+    title: Option<String>,
+    weight: f64,
+) -> Result<(), eyre::Error> {
+    let existing = {
+        let mut conn = ctx.diesel.get().await?;
+        online_articles::table
+            .filter(online_articles::url.eq(url.as_str()))
+            .select(online_articles::id)
+            .first::<i32>(&mut conn)
+            .await
+            .optional()?
+    };
+    let article_id = match existing {
+        Some(id) => id,
+        None => {
+            // Not holding a connection while the page is fetched
+            let article = crawler::fetch_and_generate_embedding(ctx, url, title, None).await?;
+            let mut conn = ctx.diesel.get().await?;
+            crawler::insert_article(&mut conn, article, None).await?
+        }
+    };
+
+    let mut conn = ctx.diesel.get().await?;
+    let updated = diesel::update(
+        user_history::table
+            .filter(user_history::identity_id.eq(identity_id))
+            .filter(user_history::online_article_id.eq(article_id)),
+    )
+    .set(user_history::weight.eq(weight))
+    .execute(&mut conn)
+    .await?;
+    if updated == 0 {
+        diesel::insert_into(user_history::table)
+            .values(NewUserHistory {
+                online_article_id: article_id,
+                weight: Some(weight),
+                identity_id: Some(identity_id),
+                source: HistorySource::Extension.as_str().to_string(),
+            })
+            .execute(&mut conn)
+            .await?;
+    }
+
+    Ok(())
+}
+
+async fn finish_job(ctx: &App, job_id: i32, error: Option<eyre::Error>) -> Result<(), eyre::Error> {
+    let (status, error) = match error {
+        Some(error) => (
+            HistoryJobStatus::Failed,
+            Some(
+                error
+                    .to_string()
+                    .chars()
+                    .take(MAX_ERROR_CHARS)
+                    .collect::<String>(),
+            ),
+        ),
+        None => (HistoryJobStatus::Done, None),
+    };
+
+    let mut conn = ctx.diesel.get().await?;
+    diesel::update(history_jobs::table.filter(history_jobs::id.eq(job_id)))
+        .set((
+            history_jobs::status.eq(status.as_str()),
+            history_jobs::error.eq(error),
+            history_jobs::finished_at.eq(diesel::dsl::now),
+        ))
+        .execute(&mut conn)
+        .await?;
+    Ok(())
+}
+
+fn to_job(row: HistoryJobRow) -> HistoryJob {
+    HistoryJob {
+        id: row.id,
+        url: row.url,
+        // Only the known statuses are written
+        status: HistoryJobStatus::parse(&row.status).unwrap_or(HistoryJobStatus::Pending),
+        error: row.error,
+        created_at: row.created_at,
+        finished_at: row.finished_at,
+    }
+}
+
+fn to_token(row: HistoryTokenRow, token: Option<String>) -> HistoryToken {
+    HistoryToken {
+        id: row.id,
+        name: row.name,
+        token,
+        last_used_at: row.last_used_at,
+        created_at: row.created_at,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tokens_are_hashed_to_hex() {
+        assert_eq!(
+            hash_token("abc"),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+    }
+}
@@ -0,0 +1,145 @@
+//! Sharing the top recommendations of the owner with a public link, so that
+//! "what I'm reading" can be shown without logging in. The links are signed
+//! with `RECOMMENDER_SHARE_SECRET` and expire, only the titles, the links and
+//! where the articles were submitted are shared.
+
+use axum::{
+    Json, Router,
+    extract::{Path, State},
+    http::{StatusCode, header},
+    response::IntoResponse,
+    routing::{get, post},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{App, crypto::signed_token, error::AppError, identity::AuthUser};
+
+use super::{RankingPreset, SharedFeed, SharedFeedItem, SourceFilter, history_owner_for};
+
+const SHARED_ITEMS: i64 = 20;
+const DEFAULT_LINK_DAYS: u32 = 30;
+const MAX_LINK_DAYS: u32 = 365;
+/// The snapshot is ranked again at most this often, however many visit it
+const SNAPSHOT_TTL: chrono::TimeDelta = chrono::TimeDelta::minutes(10);
+
+pub fn route() -> Router<App> {
+    Router::<App>::new().route("/feed/shared/{token}", get(get_shared_feed))
+}
+
+pub fn admin_route() -> Router<App> {
+    Router::<App>::new().route("/admin/recommendation/share", post(create_link))
+}
+
+#[derive(Deserialize)]
+struct LinkRequest {
+    /// How long the link is valid, 30 days by default
+    days: Option<u32>,
+}
+
+#[derive(Serialize)]
+struct ShareLink {
+    url: String,
+    expires_at: chrono::NaiveDateTime,
+}
+
+/// A new link to the shared feed
+async fn create_link(
+    State(ctx): State<App>,
+    AuthUser(i): AuthUser,
+    Json(request): Json<LinkRequest>,
+) -> Result<Json<ShareLink>, AppError> {
+    if i.id != ctx.config.owner_identity_id {
+        return Err(("Not permitted", StatusCode::FORBIDDEN).into());
+    }
+    let secret = ctx.config.recommender_share_secret.as_deref().ok_or((
+        "Sharing the feed is not configured",
+        StatusCode::SERVICE_UNAVAILABLE,
+    ))?;
+
+    let days = request
+        .days
+        .unwrap_or(DEFAULT_LINK_DAYS)
+        .clamp(1, MAX_LINK_DAYS);
+    let expires_at = chrono::Utc::now() + chrono::Days::new(u64::from(days));
+    let token = signed_token::sign(secret, "", expires_at.timestamp())?;
+
+    Ok(Json(ShareLink {
+        url: format!(
+            "{}/api/feed/shared/{token}",
+            ctx.config.site_url.trim_end_matches('/')
+        ),
+        expires_at: expires_at.naive_utc(),
+    }))
+}
+
+/// The current top recommendations of the owner, read-only
+#[utoipa::path(
+    get,
+    path = "/feed/shared/{token}",
+    tag = "recommendation",
+    params(("token" = String, Path)),
+    responses(
+        (status = 200, description = "The shared feed", body = SharedFeed),
+        (status = 404, description = "The link is invalid or expired"),
+    ),
+)]
+pub async fn get_shared_feed(
+    State(ctx): State<App>,
+    Path(token): Path<String>,
+) -> Result<impl IntoResponse, AppError> {
+    // The tokens of the shared feed have no subject, the ones signed with the
+    // same secret for anything else aren't valid here
+    let subject = ctx
+        .config
+        .recommender_share_secret
+        .as_deref()
+        .and_then(|secret| signed_token::verify_at(secret, &token, chrono::Utc::now().timestamp()));
+    let valid = subject == Some("");
+    if !valid {
+        return Err(("No such shared feed", StatusCode::NOT_FOUND).into());
+    }
+
+    // Held while ranking, so that the visits meanwhile wait for the snapshot
+    let mut snapshot = ctx.recommendation.shared_feed.lock().await;
+    let now = chrono::Utc::now().naive_utc();
+    let feed = match snapshot.as_ref() {
+        Some(feed) if now - feed.generated_at < SNAPSHOT_TTL => feed.clone(),
+        _ => {
+            let feed = rank_shared_feed(&ctx).await?;
+            *snapshot = Some(feed.clone());
+            feed
+        }
+    };
+    drop(snapshot);
+
+    Ok(([(header::CACHE_CONTROL, "public, max-age=600")], Json(feed)))
+}
+
+/// The top of the owner's feed as they'd see it by default
+async fn rank_shared_feed(ctx: &App) -> Result<SharedFeed, eyre::Error> {
+    let history_owner = history_owner_for(ctx, ctx.config.owner_identity_id).await?;
+    let items = super::fetch_feed_items(
+        ctx,
+        SHARED_ITEMS,
+        0,
+        SourceFilter::All,
+        RankingPreset::default(),
+        history_owner,
+        &ctx.config.recommender_feed_languages,
+        false,
+    )
+    .await?;
+
+    Ok(SharedFeed {
+        generated_at: chrono::Utc::now().naive_utc(),
+        items: items
+            .into_iter()
+            .map(|item| SharedFeedItem {
+                title: item.title,
+                url: item.url,
+                submitted_at: item.submitted_at,
+                sources: item.sources.into_iter().map(|source| source.key).collect(),
+            })
+            .collect(),
+    })
+}
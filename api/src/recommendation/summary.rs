@@ -0,0 +1,216 @@
+//! Summaries of the feed articles generated by the LLM on demand, so that the
+//! reader gets a TL;DR without opening the link. Each article is summarized
+//! once and the summary is kept with it.
+
+use std::time::Duration;
+
+use axum::{
+    Json, Router,
+    extract::{Path, State},
+    http::StatusCode,
+    routing::post,
+};
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+use diesel_async::RunQueryDsl;
+use eyre::Context as _;
+use rig::{
+    agent::AgentBuilder, client::CompletionClient, completion::Prompt,
+    providers::openrouter::Client,
+};
+use serde::Deserialize;
+
+use crate::{
+    App,
+    discord::constants::DEFAULT_MODEL,
+    error::AppError,
+    identity::AuthUser,
+    rate_limit::RateLimiter,
+    schema::{online_article_summaries, online_articles},
+};
+
+use super::{FeedItemSummary, crawler};
+
+/// Articles a reader may have summarized per hour, the kept summaries don't
+/// count
+const SUMMARIES_PER_HOUR: usize = 20;
+/// About 6000 tokens, the start of an article tells most of what it's about
+const MAX_ARTICLE_CHARS: usize = 24_000;
+const MAX_TAKEAWAYS: usize = 5;
+const SUMMARY_PROMPT: &str = r#"You summarize articles for a reader deciding whether to read them.
+Reply with a JSON object and nothing else: {"summary": "...", "takeaways": ["...", "..."]}.
+The summary is two or three plain sentences on what the article says, not what it is. The
+takeaways are the three to five most useful points of the article, one short sentence each.
+Write in the language of the article and don't make up anything it doesn't say."#;
+
+pub fn route() -> Router<App> {
+    Router::<App>::new().route("/feed/items/{id}/summary", post(summarize_item))
+}
+
+pub fn rate_limiter() -> RateLimiter<i32> {
+    RateLimiter::new(SUMMARIES_PER_HOUR, Duration::from_secs(60 * 60))
+}
+
+#[derive(Deserialize)]
+struct GeneratedSummary {
+    summary: String,
+    #[serde(default)]
+    takeaways: Vec<String>,
+}
+
+/// The summary of the article, generated on the first request. Only the owner
+/// may summarize unless `RECOMMENDER_SUMMARIES_PUBLIC` is set.
+#[utoipa::path(
+    post,
+    path = "/feed/items/{id}/summary",
+    tag = "recommendation",
+    params(("id" = i32, Path)),
+    responses(
+        (status = 200, description = "The summary", body = FeedItemSummary),
+        (status = 403, description = "Not permitted"),
+        (status = 404, description = "No such article"),
+        (status = 422, description = "Only a teaser of the article could be read"),
+        (status = 429, description = "Too many summaries generated in the last hour"),
+        (status = 503, description = "No LLM is configured"),
+    ),
+)]
+pub async fn summarize_item(
+    State(ctx): State<App>,
+    AuthUser(i): AuthUser,
+    Path(id): Path<i32>,
+) -> Result<Json<FeedItemSummary>, AppError> {
+    if !ctx.config.recommender_summaries_public && i.id != ctx.config.owner_identity_id {
+        return Err(("Not permitted", StatusCode::FORBIDDEN).into());
+    }
+
+    let mut conn = ctx.diesel.get().await?;
+    let (url, title, content_text) = online_articles::table
+        .find(id)
+        .select((
+            online_articles::url,
+            online_articles::title,
+            online_articles::content_text,
+        ))
+        .first::<(String, String, Option<String>)>(&mut conn)
+        .await
+        .optional()?
+        .ok_or(("No such article", StatusCode::NOT_FOUND))?;
+
+    let cached = online_article_summaries::table
+        .find(id)
+        .select((
+            online_article_summaries::summary,
+            online_article_summaries::takeaways,
+            online_article_summaries::created_at,
+        ))
+        .first::<(String, serde_json::Value, NaiveDateTime)>(&mut conn)
+        .await
+        .optional()?;
+    if let Some((summary, takeaways, generated_at)) = cached {
+        return Ok(Json(FeedItemSummary {
+            id,
+            summary,
+            takeaways: serde_json::from_value(takeaways).unwrap_or_default(),
+            generated_at,
+        }));
+    }
+    // Release connection before the fetch and the completion
+    drop(conn);
+
+    let api_key = ctx.config.openai_api_key.as_deref().ok_or((
+        "Summaries are not configured",
+        StatusCode::SERVICE_UNAVAILABLE,
+    ))?;
+    if !ctx.recommendation.summary_limiter.check(i.id) {
+        return Err((
+            "Too many summaries, try again later",
+            StatusCode::TOO_MANY_REQUESTS,
+        )
+            .into());
+    }
+
+    let text = match content_text {
+        Some(text) => text,
+        None => {
+            let url = url::Url::parse(&url).wrap_err("invalid article URL")?;
+            let page = crawler::fetch_article_page(&ctx, &url).await?;
+            if page.paywalled {
+                return Err((
+                    "Only a teaser of the article could be read",
+                    StatusCode::UNPROCESSABLE_ENTITY,
+                )
+                    .into());
+            }
+            page.markdown
+        }
+    };
+
+    let client = Client::new(api_key).wrap_err("failed to create OpenRouter client")?;
+    let summarizer = AgentBuilder::new(client.completion_model(DEFAULT_MODEL))
+        .preamble(SUMMARY_PROMPT)
+        .build();
+    let article = text.chars().take(MAX_ARTICLE_CHARS).collect::<String>();
+    let response = summarizer
+        .prompt(format!("Title: {title}\n\n{article}"))
+        .await
+        .wrap_err("failed to summarize the article")?;
+    let generated = parse_summary(&response).ok_or_else(|| {
+        eyre::eyre!("the summary of the article isn't the expected JSON: {response}")
+    })?;
+
+    let mut conn = ctx.diesel.get().await?;
+    // Another request may have summarized it meanwhile, the first one is kept
+    diesel::insert_into(online_article_summaries::table)
+        .values((
+            online_article_summaries::online_article_id.eq(id),
+            online_article_summaries::summary.eq(&generated.summary),
+            online_article_summaries::takeaways.eq(serde_json::json!(generated.takeaways)),
+            online_article_summaries::model.eq(DEFAULT_MODEL),
+        ))
+        .on_conflict_do_nothing()
+        .execute(&mut conn)
+        .await?;
+
+    Ok(Json(FeedItemSummary {
+        id,
+        summary: generated.summary,
+        takeaways: generated.takeaways,
+        generated_at: chrono::Utc::now().naive_utc(),
+    }))
+}
+
+/// The JSON object of the reply, which models tend to wrap in a code block
+fn parse_summary(response: &str) -> Option<GeneratedSummary> {
+    let start = response.find('{')?;
+    let end = response.rfind('}')?;
+    let mut generated =
+        serde_json::from_str::<GeneratedSummary>(response.get(start..=end)?).ok()?;
+
+    generated.summary = generated.summary.trim().to_string();
+    generated.takeaways = generated
+        .takeaways
+        .into_iter()
+        .map(|takeaway| takeaway.trim().to_string())
+        .filter(|takeaway| !takeaway.is_empty())
+        .take(MAX_TAKEAWAYS)
+        .collect();
+    (!generated.summary.is_empty()).then_some(generated)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn summaries_are_read_from_the_json_of_the_reply() {
+        let generated = parse_summary(
+            "```json\n{\"summary\": \" The compiler got faster. \", \"takeaways\": [\"a\", \" \", \"b\"]}\n```",
+        )
+        .expect("summary");
+        assert_eq!(generated.summary, "The compiler got faster.");
+        assert_eq!(generated.takeaways, vec!["a", "b"]);
+
+        assert!(parse_summary("I can't summarize this article.").is_none());
+        assert!(parse_summary(r#"{"summary": "", "takeaways": []}"#).is_none());
+    }
+}
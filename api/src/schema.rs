@@ -25,6 +25,71 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    activitypub_followers (id) {
+        id -> Int4,
+        actor_id -> Text,
+        inbox -> Text,
+        shared_inbox -> Nullable<Text>,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    asset_variants (asset_id, name) {
+        asset_id -> Int4,
+        name -> Text,
+        key -> Text,
+        width -> Int4,
+        height -> Int4,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    assets (id) {
+        id -> Int4,
+        key -> Text,
+        identity_id -> Int4,
+        filename -> Text,
+        content_type -> Text,
+        size_bytes -> Int8,
+        width -> Nullable<Int4>,
+        height -> Nullable<Int4>,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    background_job_leases (job) {
+        job -> Text,
+        instance -> Text,
+        acquired_at -> Timestamp,
+        renewed_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    blog_comment_mentions (comment_id, identity_id) {
+        comment_id -> Int4,
+        identity_id -> Int4,
+        created_at -> Timestamp,
+        seen_at -> Nullable<Timestamp>,
+    }
+}
+
+diesel::table! {
+    blog_comment_reports (id) {
+        id -> Int4,
+        comment_id -> Int4,
+        reason -> Text,
+        reporter_ip -> Text,
+        identity_id -> Nullable<Int4>,
+        created_at -> Timestamp,
+        resolved_at -> Nullable<Timestamp>,
+    }
+}
+
 diesel::table! {
     blog_comment_votes (id) {
         id -> Int4,
@@ -47,6 +112,19 @@ diesel::table! {
         post_id -> Int4,
         parent_id -> Nullable<Int4>,
         created_at -> Timestamp,
+        author_country -> Nullable<Text>,
+        author_asn -> Nullable<Int8>,
+        source -> Text,
+        source_id -> Nullable<Text>,
+    }
+}
+
+diesel::table! {
+    blog_post_chunks (id) {
+        id -> Int4,
+        post_id -> Int4,
+        embedding -> crate::schema::PgBit,
+        created_at -> Timestamp,
     }
 }
 
@@ -56,6 +134,25 @@ diesel::table! {
         category -> Text,
         slug -> Text,
         title -> Nullable<Text>,
+        reading_time_mins -> Nullable<Int4>,
+        summary -> Nullable<Text>,
+        meta_updated_at -> Nullable<Timestamp>,
+        federated_at -> Nullable<Timestamp>,
+    }
+}
+
+diesel::table! {
+    crawl_runs (id) {
+        id -> Int4,
+        status -> Text,
+        trigger -> Text,
+        fetched -> Int4,
+        embedded -> Int4,
+        failed -> Int4,
+        failures -> Jsonb,
+        started_at -> Timestamp,
+        finished_at -> Nullable<Timestamp>,
+        timings -> Jsonb,
     }
 }
 
@@ -70,6 +167,219 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    discord_archived_messages (id) {
+        id -> Int4,
+        channel_id -> Int8,
+        thread_id -> Nullable<Int8>,
+        guild_id -> Nullable<Int8>,
+        message_id -> Nullable<Int8>,
+        user_id -> Nullable<Int8>,
+        kind -> Text,
+        tool_name -> Nullable<Text>,
+        content -> Text,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    discord_channel_settings (channel_id) {
+        channel_id -> Int8,
+        guild_id -> Nullable<Int8>,
+        enabled -> Bool,
+        mention_only -> Nullable<Bool>,
+        persona -> Nullable<Text>,
+        model -> Nullable<Text>,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+        streaming -> Nullable<Bool>,
+        allowed_tools -> Nullable<Array<Text>>,
+        memory_retention_days -> Nullable<Int4>,
+        archive -> Nullable<Bool>,
+        archive_retention_days -> Nullable<Int4>,
+        persona_profile -> Nullable<Text>,
+        user_rate_limit -> Nullable<Int4>,
+        store_attachments -> Nullable<Bool>,
+        summary_schedule -> Nullable<Text>,
+    }
+}
+
+diesel::table! {
+    discord_channel_summaries (id) {
+        id -> Int4,
+        channel_id -> Int8,
+        guild_id -> Nullable<Int8>,
+        schedule -> Text,
+        period_start -> Timestamp,
+        period_end -> Timestamp,
+        content -> Text,
+        message_count -> Int4,
+        message_id -> Nullable<Int8>,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    discord_dm_consents (user_id) {
+        user_id -> Int8,
+        consented_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    discord_feed_posts (online_article_id, channel_id) {
+        online_article_id -> Int4,
+        channel_id -> Int8,
+        posted_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    discord_guild_settings (guild_id) {
+        guild_id -> Int8,
+        mention_only -> Nullable<Bool>,
+        persona -> Nullable<Text>,
+        model -> Nullable<Text>,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+        streaming -> Nullable<Bool>,
+        allowed_tools -> Nullable<Array<Text>>,
+        memory_retention_days -> Nullable<Int4>,
+        moderation_enforcement -> Nullable<Bool>,
+        moderation_threshold -> Nullable<Float8>,
+        persona_profile -> Nullable<Text>,
+    }
+}
+
+diesel::table! {
+    discord_message_feedback (id) {
+        id -> Int4,
+        message_id -> Int8,
+        channel_id -> Int8,
+        user_id -> Int8,
+        positive -> Bool,
+        content -> Text,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    discord_moderation_actions (id) {
+        id -> Int4,
+        guild_id -> Int8,
+        channel_id -> Int8,
+        user_id -> Nullable<Int8>,
+        message_id -> Nullable<Int8>,
+        action -> Text,
+        reason -> Text,
+        categories -> Nullable<Jsonb>,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    discord_personas (name) {
+        name -> Text,
+        tone -> Text,
+        language -> Nullable<Text>,
+        slang -> Text,
+        safety -> Text,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    discord_reminders (id) {
+        id -> Int4,
+        channel_id -> Int8,
+        user_id -> Int8,
+        message -> Text,
+        remind_at -> Timestamp,
+        delivered_at -> Nullable<Timestamp>,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    discord_token_usage (id) {
+        id -> Int4,
+        channel_id -> Int8,
+        day -> Date,
+        model -> Text,
+        input_tokens -> Int8,
+        output_tokens -> Int8,
+        cached_input_tokens -> Int8,
+        requests -> Int4,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    discord_transcripts (id) {
+        id -> Text,
+        channel_id -> Int8,
+        guild_id -> Nullable<Int8>,
+        model -> Text,
+        transcript -> Jsonb,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    feed_clicks (id) {
+        id -> Int4,
+        online_article_id -> Int4,
+        ranking -> Text,
+        position -> Int4,
+        clicked_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    feed_impressions (day, ranking, position) {
+        day -> Date,
+        ranking -> Text,
+        position -> Int4,
+        count -> Int8,
+    }
+}
+
+diesel::table! {
+    history_imports (identity_id, source) {
+        identity_id -> Int4,
+        source -> Text,
+        weight -> Float8,
+        item_count -> Int4,
+        imported_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    history_jobs (id) {
+        id -> Int4,
+        identity_id -> Int4,
+        url -> Text,
+        weight -> Nullable<Float8>,
+        status -> Text,
+        error -> Nullable<Text>,
+        created_at -> Timestamp,
+        finished_at -> Nullable<Timestamp>,
+    }
+}
+
+diesel::table! {
+    history_tokens (id) {
+        id -> Int4,
+        identity_id -> Int4,
+        name -> Text,
+        token_hash -> Text,
+        last_used_at -> Nullable<Timestamp>,
+        created_at -> Timestamp,
+    }
+}
+
 diesel::table! {
     identities (id) {
         id -> Int4,
@@ -99,12 +409,34 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    identity_roles (identity_id, role) {
+        identity_id -> Int4,
+        role -> Text,
+        granted_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    notes (id) {
+        id -> Int4,
+        kind -> Text,
+        content -> Nullable<Text>,
+        name -> Nullable<Text>,
+        url -> Nullable<Text>,
+        categories -> Array<Text>,
+        published_at -> Timestamp,
+        created_at -> Timestamp,
+    }
+}
+
 diesel::table! {
     online_article_chunks (id) {
         id -> Int4,
         online_article_id -> Int4,
         embedding -> crate::schema::PgBit,
         created_at -> Timestamp,
+        kind -> Text,
     }
 }
 
@@ -129,6 +461,15 @@ diesel::table! {
         content_text -> Nullable<Text>,
         recommender_terms -> Nullable<Jsonb>,
         created_at -> Timestamp,
+        link_status -> Text,
+        link_failures -> Int4,
+        link_checked_at -> Nullable<Timestamp>,
+        link_next_check_at -> Nullable<Timestamp>,
+        link_validator -> Nullable<Text>,
+        archive_url -> Nullable<Text>,
+        content_refreshed_at -> Nullable<Timestamp>,
+        lang -> Nullable<Text>,
+        paywalled -> Bool,
     }
 }
 
@@ -146,6 +487,63 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    short_link_clicks (id) {
+        id -> Int4,
+        short_link_id -> Int4,
+        ip -> Text,
+        country -> Nullable<Text>,
+        asn -> Nullable<Int8>,
+        referrer -> Nullable<Text>,
+        user_agent -> Nullable<Text>,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    short_links (id) {
+        id -> Int4,
+        code -> Text,
+        url -> Text,
+        expires_at -> Nullable<Timestamp>,
+        max_clicks -> Nullable<Int4>,
+        click_count -> Int4,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    status_incidents (id) {
+        id -> Int4,
+        title -> Text,
+        description -> Nullable<Text>,
+        component -> Nullable<Text>,
+        started_at -> Timestamp,
+        resolved_at -> Nullable<Timestamp>,
+    }
+}
+
+diesel::table! {
+    status_probes (id) {
+        id -> Int4,
+        component -> Text,
+        healthy -> Bool,
+        latency_ms -> Int4,
+        error -> Nullable<Text>,
+        checked_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    online_article_summaries (online_article_id) {
+        online_article_id -> Int4,
+        summary -> Text,
+        takeaways -> Jsonb,
+        model -> Text,
+        created_at -> Timestamp,
+    }
+}
+
 diesel::table! {
     online_article_sources (id) {
         id -> Int4,
@@ -153,6 +551,11 @@ diesel::table! {
         name -> Text,
         base_url -> Nullable<Text>,
         created_at -> Timestamp,
+        enabled -> Bool,
+        crawl_interval_mins -> Nullable<Int4>,
+        max_items -> Nullable<Int4>,
+        last_crawled_at -> Nullable<Timestamp>,
+        feed_url -> Nullable<Text>,
     }
 }
 
@@ -162,33 +565,83 @@ diesel::table! {
         online_article_id -> Int4,
         weight -> Nullable<Float8>,
         added_at -> Timestamp,
+        identity_id -> Nullable<Int4>,
+        source -> Text,
     }
 }
 
+diesel::joinable!(asset_variants -> assets (asset_id));
+diesel::joinable!(assets -> identities (identity_id));
+diesel::joinable!(blog_comment_mentions -> blog_comments (comment_id));
+diesel::joinable!(blog_comment_mentions -> identities (identity_id));
+diesel::joinable!(blog_comment_reports -> blog_comments (comment_id));
+diesel::joinable!(blog_comment_reports -> identities (identity_id));
 diesel::joinable!(blog_comment_votes -> blog_comments (comment_id));
 diesel::joinable!(blog_comments -> blog_posts (post_id));
+diesel::joinable!(blog_post_chunks -> blog_posts (post_id));
 diesel::joinable!(blog_comments -> identities (identity_id));
+diesel::joinable!(discord_feed_posts -> online_articles (online_article_id));
+diesel::joinable!(feed_clicks -> online_articles (online_article_id));
+diesel::joinable!(history_imports -> identities (identity_id));
+diesel::joinable!(history_jobs -> identities (identity_id));
+diesel::joinable!(history_tokens -> identities (identity_id));
 diesel::joinable!(identity_credentials -> identities (identity_id));
 diesel::joinable!(identity_credentials -> identity_credential_types (credential_type_id));
+diesel::joinable!(identity_roles -> identities (identity_id));
 diesel::joinable!(online_article_chunks -> online_articles (online_article_id));
 diesel::joinable!(online_article_metadata -> online_articles (online_article_id));
 diesel::joinable!(online_article_metadata -> online_article_sources (source_id));
+diesel::joinable!(online_article_summaries -> online_articles (online_article_id));
 diesel::joinable!(sessions -> identities (identity_id));
+diesel::joinable!(short_link_clicks -> short_links (short_link_id));
+diesel::joinable!(user_history -> identities (identity_id));
 diesel::joinable!(user_history -> online_articles (online_article_id));
 
 diesel::allow_tables_to_appear_in_same_query!(
     _prisma_migrations,
+    activitypub_followers,
+    asset_variants,
+    assets,
+    background_job_leases,
+    blog_comment_mentions,
+    blog_comment_reports,
     blog_comment_votes,
     blog_comments,
+    blog_post_chunks,
     blog_posts,
     counters,
+    crawl_runs,
+    discord_archived_messages,
+    discord_channel_settings,
+    discord_channel_summaries,
+    discord_dm_consents,
+    discord_feed_posts,
+    discord_guild_settings,
+    discord_message_feedback,
+    discord_moderation_actions,
+    discord_personas,
+    discord_reminders,
+    discord_token_usage,
+    discord_transcripts,
+    feed_clicks,
+    feed_impressions,
+    history_imports,
+    history_jobs,
+    history_tokens,
     identities,
     identity_credential_types,
     identity_credentials,
+    identity_roles,
+    notes,
     online_article_chunks,
     online_article_metadata,
+    online_article_summaries,
     online_articles,
     sessions,
+    short_link_clicks,
+    short_links,
+    status_incidents,
+    status_probes,
     online_article_sources,
     user_history,
 );
@@ -0,0 +1,307 @@
+//! Fills a fresh database with fake data to develop the frontend against,
+//! run with `cargo run -- seed`. The highlights aren't stored in the database,
+//! run the server with `--offline` to get sample ones.
+
+use api_models::{CommentSource, HistorySource};
+use diesel::prelude::*;
+use diesel_async::{AsyncPgConnection, RunQueryDsl};
+use eyre::Context as _;
+use pgvector::Vector;
+use rand::{RngExt as _, SeedableRng as _, rngs::StdRng, seq::IndexedRandom as _};
+
+use crate::{
+    blog::models::{NewBlogComment, NewBlogPost},
+    config::{Env, ServerConfig},
+    identity::models::identity::{Identity, Traits},
+    models::recommendation::NewUserHistory,
+    recommendation::{self, FetchedArticle, SourceEntry},
+    schema::{blog_comments, blog_posts, counters, identities, user_history},
+    utils::{RECOMMENDER_EMBEDDING_BITS, extract_recommender_terms},
+};
+
+/// The seeded identities have an email under this domain, which also tells
+/// whether the database was already seeded
+const EMAIL_DOMAIN: &str = "seed.localhost";
+
+const NAMES: &[&str] = &[
+    "Ada Lovelace",
+    "Alan Turing",
+    "Grace Hopper",
+    "Edsger Dijkstra",
+    "Barbara Liskov",
+    "Ken Thompson",
+];
+
+const SENTENCES: &[&str] = &[
+    "This was a great read, thanks for writing it.",
+    "I ran into the same problem last year and ended up with a similar solution.",
+    "Have you considered measuring this under a more realistic load?",
+    "The second section could use an example, it took me a while to follow.",
+    "Bookmarked, I'll definitely come back to this one.",
+    "I disagree with the conclusion, but the reasoning is well laid out.",
+    "What tools did you use to make the diagrams?",
+    "Small typo in the third paragraph, otherwise spot on.",
+];
+
+const TOPICS: &[&str] = &[
+    "Rust",
+    "Postgres",
+    "WebAssembly",
+    "SQLite",
+    "Kubernetes",
+    "compilers",
+    "type systems",
+    "distributed systems",
+    "garbage collection",
+    "TCP congestion control",
+];
+
+const TITLE_TEMPLATES: &[&str] = &[
+    "How we made {} ten times faster",
+    "A gentle introduction to {}",
+    "What I learned from a year of {}",
+    "{} is not what you think",
+    "Debugging {} in production",
+    "The hidden costs of {}",
+];
+
+const POSTS: &[(&str, &str)] = &[("test", "Test page"), ("hello-world", "Hello, world")];
+
+/// Articles in the feed, a few of them are also in the reading history
+const ARTICLES: usize = 40;
+const HISTORY_EVERY: usize = 5;
+
+pub async fn seed(config: &ServerConfig) -> Result<(), eyre::Error> {
+    if matches!(config.env, Env::Production) {
+        eyre::bail!("refusing to seed a production database");
+    }
+
+    let mut conn =
+        <AsyncPgConnection as diesel_async::AsyncConnection>::establish(&config.database_url)
+            .await
+            .wrap_err("failed to connect to the database")?;
+
+    let seeded = identities::table
+        .select(identities::id)
+        .filter(
+            identities::traits
+                .retrieve_as_text("email")
+                .like(format!("%@{EMAIL_DOMAIN}")),
+        )
+        .first::<i32>(&mut conn)
+        .await
+        .optional()?
+        .is_some();
+    if seeded {
+        eyre::bail!("the database was already seeded");
+    }
+
+    // Same data on every run
+    let mut rng = StdRng::seed_from_u64(0);
+
+    let identity_ids = seed_identities(&mut conn).await?;
+    tracing::info!(count = identity_ids.len(), "Seeded identities");
+
+    let comments = seed_comments(&mut conn, &mut rng, &identity_ids).await?;
+    tracing::info!(posts = POSTS.len(), comments, "Seeded blog posts");
+
+    let articles = seed_feed(&mut conn, &mut rng).await?;
+    tracing::info!(articles, "Seeded feed articles");
+
+    diesel::insert_into(counters::table)
+        .values((
+            counters::key.eq("github-profile-views"),
+            counters::name.eq("wonrax"),
+            counters::count.eq(255),
+        ))
+        .on_conflict_do_nothing()
+        .execute(&mut conn)
+        .await?;
+
+    Ok(())
+}
+
+async fn seed_identities(conn: &mut AsyncPgConnection) -> Result<Vec<i32>, eyre::Error> {
+    let new_identities = NAMES
+        .iter()
+        .map(|name| {
+            let handle = name.to_lowercase().replace(' ', ".");
+            Identity::new_with_traits(Traits {
+                email: Some(format!("{handle}@{EMAIL_DOMAIN}")),
+                name: Some(name.to_string()),
+            })
+        })
+        .collect::<Vec<_>>();
+
+    Ok(diesel::insert_into(identities::table)
+        .values(&new_identities)
+        .returning(identities::id)
+        .get_results(conn)
+        .await?)
+}
+
+/// Comment trees three levels deep on each post, returns how many comments
+/// were created
+async fn seed_comments(
+    conn: &mut AsyncPgConnection,
+    rng: &mut StdRng,
+    identity_ids: &[i32],
+) -> Result<usize, eyre::Error> {
+    let mut count = 0;
+
+    for (slug, title) in POSTS {
+        let post_id = diesel::insert_into(blog_posts::table)
+            .values(&NewBlogPost {
+                category: "blog".to_string(),
+                slug: slug.to_string(),
+                title: Some(title.to_string()),
+            })
+            .on_conflict((blog_posts::category, blog_posts::slug))
+            .do_update()
+            .set(blog_posts::title.eq(title))
+            .returning(blog_posts::id)
+            .get_result::<i32>(conn)
+            .await?;
+
+        // Each level has one less reply per comment than its parent
+        let mut parents = vec![None];
+        for replies in (1..=3).rev() {
+            let mut children = Vec::new();
+            for parent_id in parents {
+                for _ in 0..replies {
+                    let comment = new_comment(rng, identity_ids, post_id, parent_id);
+                    let id = diesel::insert_into(blog_comments::table)
+                        .values(&comment)
+                        .returning(blog_comments::id)
+                        .get_result::<i32>(conn)
+                        .await?;
+                    children.push(Some(id));
+                }
+            }
+            count += children.len();
+            parents = children;
+        }
+    }
+
+    Ok(count)
+}
+
+fn new_comment(
+    rng: &mut StdRng,
+    identity_ids: &[i32],
+    post_id: i32,
+    parent_id: Option<i32>,
+) -> NewBlogComment {
+    let paragraphs = (0..rng.random_range(1..=3))
+        .map(|_| {
+            (0..rng.random_range(1..=3))
+                .filter_map(|_| SENTENCES.choose(rng).copied())
+                .collect::<Vec<_>>()
+                .join(" ")
+        })
+        .collect::<Vec<_>>()
+        .join("\n\n");
+
+    let author = rng.random_range(0..identity_ids.len() + 1);
+    let (identity_id, author_name) = match identity_ids.get(author) {
+        Some(id) => (Some(*id), NAMES.get(author).map(|name| name.to_string())),
+        // Some of the comments are anonymous
+        None => (None, Some("Anonymous Reader".to_string())),
+    };
+
+    NewBlogComment {
+        author_ip: "127.0.0.1".to_string(),
+        author_name,
+        author_email: None,
+        identity_id,
+        content: paragraphs,
+        post_id,
+        parent_id,
+        author_country: Some("VN".to_string()),
+        author_asn: None,
+        source: CommentSource::Site.as_str().to_string(),
+        source_id: None,
+    }
+}
+
+/// Articles with random embeddings from both sources, returns how many were
+/// created
+async fn seed_feed(conn: &mut AsyncPgConnection, rng: &mut StdRng) -> Result<usize, eyre::Error> {
+    let sources = [
+        recommendation::get_or_create_source(
+            conn,
+            "hacker-news",
+            "Hacker News",
+            Some("https://news.ycombinator.com/"),
+        )
+        .await?,
+        recommendation::get_or_create_source(
+            conn,
+            "lobsters",
+            "Lobsters",
+            Some("https://lobste.rs/"),
+        )
+        .await?,
+    ];
+    let now = chrono::Utc::now().naive_utc();
+
+    for i in 0..ARTICLES {
+        let topic = TOPICS.choose(rng).copied().unwrap_or("Rust");
+        let title = TITLE_TEMPLATES
+            .choose(rng)
+            .copied()
+            .unwrap_or("{}")
+            .replace("{}", topic);
+        let url = url::Url::parse(&format!("https://example.com/articles/{i}"))?;
+
+        let embeddings = (0..rng.random_range(1..=3))
+            .map(|_| {
+                Vector::from(
+                    (0..RECOMMENDER_EMBEDDING_BITS)
+                        .map(|_| rng.random_range(-1.0..1.0))
+                        .collect::<Vec<f32>>(),
+                )
+            })
+            .collect();
+
+        let source = SourceEntry {
+            source_id: sources[i % sources.len()],
+            title: Some(title.clone()),
+            url: url.clone(),
+            external_score: Some(rng.random_range(5.0..800.0_f64).round()),
+            submitted_at: now - chrono::Duration::minutes(rng.random_range(10..60 * 72)),
+            external_id: format!("seed{i}"),
+            submission_text: None,
+            comment_count: Some(rng.random_range(0..400)),
+        };
+
+        let terms = extract_recommender_terms(&title, None);
+        let article_id = recommendation::insert_article(
+            conn,
+            FetchedArticle::new(
+                url,
+                title,
+                terms,
+                embeddings,
+                Some("eng".to_string()),
+                false,
+            ),
+            Some(&source),
+        )
+        .await?;
+
+        if i % HISTORY_EVERY == 0 {
+            diesel::insert_into(user_history::table)
+                .values(&NewUserHistory {
+                    online_article_id: article_id,
+                    weight: Some(1.0),
+                    identity_id: None,
+                    source: HistorySource::Raindrop.as_str().to_string(),
+                })
+                .execute(conn)
+                .await?;
+        }
+    }
+
+    Ok(ARTICLES)
+}
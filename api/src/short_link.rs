@@ -0,0 +1,296 @@
+//! Short links to share, `/s/{code}` redirects to the long URL and records
+//! who clicked it
+
+use axum::{
+    Json, Router,
+    extract::{Path, State},
+    http::{HeaderMap, StatusCode, header},
+    response::{IntoResponse, Response},
+    routing::get,
+};
+use base64::{Engine as _, engine::general_purpose::URL_SAFE_NO_PAD};
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+use diesel_async::RunQueryDsl;
+use rand::TryRng as _;
+use serde::Deserialize;
+
+use crate::{
+    App,
+    crypto::random,
+    error::AppError,
+    identity::AuthUser,
+    models::short_link::{NewShortLink, NewShortLinkClick, ShortLink, ShortLinkClick},
+    real_ip::ClientIp,
+    schema::{short_link_clicks, short_links},
+};
+
+/// Random bytes of a generated code, 8 characters once encoded
+const GENERATED_CODE_BYTES: usize = 6;
+const MAX_CODE_CHARS: usize = 32;
+const MAX_URL_CHARS: usize = 2048;
+/// Header values recorded with a click are cut to this length
+const MAX_HEADER_CHARS: usize = 512;
+const MAX_LISTED_CLICKS: i64 = 100;
+
+/// The redirects, served outside of the versioned API so that the links stay
+/// short
+pub fn route() -> Router<App> {
+    Router::<App>::new().route("/s/{code}", get(redirect))
+}
+
+/// Creation and analytics of the links, for the owner
+pub fn admin_route() -> Router<App> {
+    Router::<App>::new()
+        .route(
+            "/admin/short-links",
+            get(list_short_links).post(create_short_link),
+        )
+        .route("/admin/short-links/{code}/clicks", get(get_clicks))
+}
+
+async fn redirect(
+    State(ctx): State<App>,
+    Path(code): Path<String>,
+    client_ip: ClientIp,
+    headers: HeaderMap,
+) -> Result<Response, AppError> {
+    let mut conn = ctx.diesel.get().await?;
+
+    // Counted in the same statement that checks the limits, so that
+    // concurrent clicks can't go over them
+    let link = diesel::update(
+        short_links::table
+            .filter(short_links::code.eq(&code))
+            .filter(
+                short_links::expires_at
+                    .is_null()
+                    .or(short_links::expires_at.gt(diesel::dsl::now)),
+            )
+            .filter(
+                short_links::max_clicks
+                    .is_null()
+                    .or(short_links::click_count.lt(short_links::max_clicks.assume_not_null())),
+            ),
+    )
+    .set(short_links::click_count.eq(short_links::click_count + 1))
+    .returning((short_links::id, short_links::url))
+    .get_result::<(i32, String)>(&mut conn)
+    .await
+    .optional()?;
+
+    let Some((id, url)) = link else {
+        let exists = short_links::table
+            .filter(short_links::code.eq(&code))
+            .select(short_links::id)
+            .first::<i32>(&mut conn)
+            .await
+            .optional()?
+            .is_some();
+        return Err(if exists {
+            ("This link has expired", StatusCode::GONE).into()
+        } else {
+            ("Link not found", StatusCode::NOT_FOUND).into()
+        });
+    };
+
+    let header = |name: header::HeaderName| {
+        headers
+            .get(name)
+            .and_then(|v| v.to_str().ok())
+            .map(|v| v.chars().take(MAX_HEADER_CHARS).collect::<String>())
+    };
+    let geo = client_ip.geo(&ctx);
+    let click = NewShortLinkClick {
+        short_link_id: id,
+        ip: client_ip.0.to_string(),
+        country: geo.as_ref().and_then(|g| g.country.clone()),
+        asn: geo.and_then(|g| g.asn).map(i64::from),
+        referrer: header(header::REFERER),
+        user_agent: header(header::USER_AGENT),
+    };
+
+    // The reader is redirected even if the click couldn't be recorded
+    let _ = diesel::insert_into(short_link_clicks::table)
+        .values(&click)
+        .execute(&mut conn)
+        .await
+        .inspect_err(|e| tracing::error!(?e, code, "Failed to record short link click"));
+
+    Ok((
+        StatusCode::MOVED_PERMANENTLY,
+        [
+            (header::LOCATION, url),
+            // Otherwise browsers remember the redirect and the next clicks
+            // are neither counted nor limited
+            (header::CACHE_CONTROL, "no-store".to_string()),
+        ],
+    )
+        .into_response())
+}
+
+#[derive(Deserialize)]
+struct ShortLinkSubmission {
+    url: String,
+    /// Generated if absent
+    code: Option<String>,
+    expires_at: Option<NaiveDateTime>,
+    max_clicks: Option<i32>,
+}
+
+async fn create_short_link(
+    State(ctx): State<App>,
+    AuthUser(i): AuthUser,
+    Json(submission): Json<ShortLinkSubmission>,
+) -> Result<Json<ShortLink>, AppError> {
+    ensure_owner(&ctx, i.id)?;
+
+    validate_url(&submission.url)?;
+    if let Some(code) = &submission.code {
+        validate_code(code)?;
+    }
+    if submission.max_clicks.is_some_and(|max| max < 1) {
+        return Err(("The click limit must be positive", StatusCode::BAD_REQUEST).into());
+    }
+    if submission
+        .expires_at
+        .is_some_and(|expires_at| expires_at <= chrono::Utc::now().naive_utc())
+    {
+        return Err(("The expiry must be in the future", StatusCode::BAD_REQUEST).into());
+    }
+
+    let mut conn = ctx.diesel.get().await?;
+
+    // A generated code is retried in the unlikely case it's taken
+    let attempts = if submission.code.is_some() { 1 } else { 3 };
+    for _ in 0..attempts {
+        let code = match &submission.code {
+            Some(code) => code.clone(),
+            None => generate_code()?,
+        };
+
+        let link = diesel::insert_into(short_links::table)
+            .values(&NewShortLink {
+                code,
+                url: submission.url.clone(),
+                expires_at: submission.expires_at,
+                max_clicks: submission.max_clicks,
+            })
+            .on_conflict(short_links::code)
+            .do_nothing()
+            .returning(ShortLink::as_returning())
+            .get_result(&mut conn)
+            .await
+            .optional()?;
+
+        if let Some(link) = link {
+            return Ok(Json(link));
+        }
+    }
+
+    Err(("The code is already taken", StatusCode::CONFLICT).into())
+}
+
+async fn list_short_links(
+    State(ctx): State<App>,
+    AuthUser(i): AuthUser,
+) -> Result<Json<Vec<ShortLink>>, AppError> {
+    ensure_owner(&ctx, i.id)?;
+
+    let mut conn = ctx.diesel.get().await?;
+
+    let links = short_links::table
+        .order(short_links::created_at.desc())
+        .select(ShortLink::as_select())
+        .load(&mut conn)
+        .await?;
+
+    Ok(Json(links))
+}
+
+/// The latest clicks of the link, newest first
+async fn get_clicks(
+    State(ctx): State<App>,
+    AuthUser(i): AuthUser,
+    Path(code): Path<String>,
+) -> Result<Json<Vec<ShortLinkClick>>, AppError> {
+    ensure_owner(&ctx, i.id)?;
+
+    let mut conn = ctx.diesel.get().await?;
+
+    let clicks = short_link_clicks::table
+        .inner_join(short_links::table)
+        .filter(short_links::code.eq(&code))
+        .order(short_link_clicks::created_at.desc())
+        .limit(MAX_LISTED_CLICKS)
+        .select(ShortLinkClick::as_select())
+        .load(&mut conn)
+        .await?;
+
+    Ok(Json(clicks))
+}
+
+fn ensure_owner(ctx: &App, identity_id: i32) -> Result<(), AppError> {
+    if identity_id != ctx.config.owner_identity_id {
+        return Err(("Not permitted", StatusCode::FORBIDDEN).into());
+    }
+    Ok(())
+}
+
+fn generate_code() -> Result<String, eyre::Error> {
+    let mut bytes = [0u8; GENERATED_CODE_BYTES];
+    random::get_rng()
+        .try_fill_bytes(&mut bytes)
+        .map_err(|_| eyre::eyre!("could not generate short link code"))?;
+    Ok(URL_SAFE_NO_PAD.encode(bytes))
+}
+
+fn validate_code(code: &str) -> Result<(), AppError> {
+    let valid = !code.is_empty()
+        && code.chars().count() <= MAX_CODE_CHARS
+        && code
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_'));
+    if !valid {
+        return Err((
+            "The code must be up to 32 letters, digits, dashes or underscores",
+            StatusCode::BAD_REQUEST,
+        )
+            .into());
+    }
+    Ok(())
+}
+
+fn validate_url(url: &str) -> Result<(), AppError> {
+    let valid = url.chars().count() <= MAX_URL_CHARS
+        && url::Url::parse(url).is_ok_and(|url| matches!(url.scheme(), "http" | "https"));
+    if !valid {
+        return Err((
+            "The URL must be an absolute HTTP URL",
+            StatusCode::BAD_REQUEST,
+        )
+            .into());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn generated_codes_are_valid_custom_codes() {
+        let code = generate_code().expect("the system RNG is available");
+        assert_eq!(code.len(), 8);
+        assert!(validate_code(&code).is_ok());
+
+        assert!(validate_code("my-post_2").is_ok());
+        assert!(validate_code("").is_err());
+        assert!(validate_code("../admin").is_err());
+        assert!(validate_code(&"a".repeat(33)).is_err());
+
+        assert!(validate_url("https://wrx.sh/blog/post").is_ok());
+        assert!(validate_url("javascript:alert(1)").is_err());
+        assert!(validate_url("/blog/post").is_err());
+    }
+}
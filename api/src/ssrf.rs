@@ -0,0 +1,227 @@
+//! Guards for requests to URLs that users control, e.g. links shared with the
+//! Discord bot or crawled articles, so that they can't be used to reach the
+//! private network the server runs in.
+
+use std::{
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    sync::Arc,
+};
+
+use eyre::Context as _;
+use reqwest::{
+    dns::{Addrs, Name, Resolve, Resolving},
+    redirect,
+};
+use url::Url;
+
+use crate::outbound::HttpClient;
+
+/// Responses larger than this are rejected
+pub const MAX_RESPONSE_BYTES: usize = 5 * 1024 * 1024;
+
+const MAX_REDIRECTS: usize = 5;
+
+/// Whether the address is routable on the public internet
+pub fn is_public_ip(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => is_public_ipv4(ip),
+        IpAddr::V6(ip) => is_public_ipv6(ip),
+    }
+}
+
+fn is_public_ipv4(ip: Ipv4Addr) -> bool {
+    let [a, b, c, _] = ip.octets();
+
+    !(ip.is_private()
+        || ip.is_loopback()
+        || ip.is_link_local()
+        || ip.is_unspecified()
+        || ip.is_broadcast()
+        || ip.is_multicast()
+        || ip.is_documentation()
+        // "This network"
+        || a == 0
+        // Carrier-grade NAT
+        || (a == 100 && (64..128).contains(&b))
+        // IETF protocol assignments
+        || (a == 192 && b == 0 && c == 0)
+        // Benchmarking
+        || (a == 198 && (b == 18 || b == 19))
+        // Reserved
+        || a >= 240)
+}
+
+fn is_public_ipv6(ip: Ipv6Addr) -> bool {
+    if let Some(ip) = ip.to_ipv4_mapped() {
+        return is_public_ipv4(ip);
+    }
+
+    let segments = ip.segments();
+
+    !(ip.is_loopback()
+        || ip.is_unspecified()
+        || ip.is_multicast()
+        // IPv4-compatible addresses
+        || segments[..6].iter().all(|s| *s == 0)
+        // Unique local
+        || (segments[0] & 0xfe00) == 0xfc00
+        // Link local
+        || (segments[0] & 0xffc0) == 0xfe80
+        // Site local, deprecated
+        || (segments[0] & 0xffc0) == 0xfec0
+        // Documentation
+        || (segments[0] == 0x2001 && segments[1] == 0x0db8)
+        // NAT64, which may map to a private IPv4 address
+        || (segments[0] == 0x0064 && segments[1] == 0xff9b))
+}
+
+/// Check the parts of a URL that don't need a DNS lookup: the scheme, and the
+/// address if the host is an IP
+fn check_url_target(url: &Url) -> Result<(), eyre::Error> {
+    if !matches!(url.scheme(), "http" | "https") {
+        eyre::bail!("only http and https URLs are allowed");
+    }
+
+    match url.host() {
+        None => eyre::bail!("URL has no host"),
+        Some(url::Host::Ipv4(ip)) if !is_public_ipv4(ip) => {
+            eyre::bail!("URL points to a non-public address")
+        }
+        Some(url::Host::Ipv6(ip)) if !is_public_ipv6(ip) => {
+            eyre::bail!("URL points to a non-public address")
+        }
+        Some(_) => Ok(()),
+    }
+}
+
+/// Reject URLs that aren't http(s) or point to a non-public address. Clients
+/// from [client_builder] check the resolved addresses again when connecting, so
+/// a changed DNS record after this check doesn't get through either.
+pub async fn check_url(url: &Url) -> Result<(), eyre::Error> {
+    check_url_target(url)?;
+
+    if let Some(url::Host::Domain(domain)) = url.host() {
+        let port = url.port_or_known_default().unwrap_or(443);
+        let addrs: Vec<SocketAddr> = tokio::net::lookup_host((domain, port))
+            .await
+            .wrap_err_with(|| format!("failed to resolve {domain}"))?
+            .collect();
+
+        if addrs.is_empty() || addrs.iter().any(|addr| !is_public_ip(addr.ip())) {
+            eyre::bail!("{domain} resolves to a non-public address");
+        }
+    }
+
+    Ok(())
+}
+
+/// Resolves host names with the system resolver but only to public addresses
+struct PublicResolver;
+
+impl Resolve for PublicResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        let host = name.as_str().to_string();
+        Box::pin(async move {
+            let addrs: Vec<SocketAddr> = tokio::net::lookup_host((host.as_str(), 0))
+                .await?
+                .filter(|addr| is_public_ip(addr.ip()))
+                .collect();
+
+            if addrs.is_empty() {
+                return Err(format!("{host} does not resolve to a public address").into());
+            }
+
+            let addrs: Addrs = Box::new(addrs.into_iter());
+            Ok(addrs)
+        })
+    }
+}
+
+/// A client builder that only connects to public addresses, including on
+/// redirects. Time limits are left to the caller.
+pub fn client_builder() -> reqwest::ClientBuilder {
+    reqwest::Client::builder()
+        .dns_resolver(Arc::new(PublicResolver))
+        .redirect(redirect::Policy::custom(|attempt| {
+            if attempt.previous().len() >= MAX_REDIRECTS {
+                return attempt.error("too many redirects");
+            }
+            match check_url_target(attempt.url()) {
+                Ok(()) => attempt.follow(),
+                Err(e) => attempt.error(e.to_string()),
+            }
+        }))
+}
+
+pub struct FetchedPage {
+    /// The URL after redirects
+    pub url: Url,
+    pub content_type: Option<String>,
+    pub body: String,
+}
+
+/// GET a user supplied URL, reading at most [MAX_RESPONSE_BYTES] of the
+/// response
+pub async fn fetch(client: &HttpClient, url: &Url) -> Result<FetchedPage, eyre::Error> {
+    check_url(url).await?;
+
+    let mut response = client
+        .public_only()
+        .get(url.clone())
+        .send()
+        .await
+        .wrap_err_with(|| format!("failed to fetch {url}"))?
+        .error_for_status()?;
+
+    if response
+        .content_length()
+        .is_some_and(|len| len > MAX_RESPONSE_BYTES as u64)
+    {
+        eyre::bail!("response is larger than {MAX_RESPONSE_BYTES} bytes");
+    }
+
+    let final_url = response.url().clone();
+    let content_type = response
+        .headers()
+        .get(reqwest::header::CONTENT_TYPE)
+        .and_then(|ct| ct.to_str().ok())
+        .map(|ct| ct.to_ascii_lowercase());
+
+    // The length header may be missing or lie
+    let mut body = Vec::new();
+    while let Some(chunk) = response.chunk().await? {
+        if body.len() + chunk.len() > MAX_RESPONSE_BYTES {
+            eyre::bail!("response is larger than {MAX_RESPONSE_BYTES} bytes");
+        }
+        body.extend_from_slice(&chunk);
+    }
+
+    Ok(FetchedPage {
+        url: final_url,
+        content_type,
+        body: String::from_utf8_lossy(&body).into_owned(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_url_target_rejects_private_targets() {
+        let check = |url: &str| Url::parse(url).map(|url| check_url_target(&url).is_ok());
+
+        assert_eq!(check("https://example.com/"), Ok(true));
+        assert_eq!(check("http://93.184.215.14/"), Ok(true));
+        assert_eq!(check("http://[2606:4700::1111]/"), Ok(true));
+        assert_eq!(check("file:///etc/passwd"), Ok(false));
+        assert_eq!(check("http://127.0.0.1:5432/"), Ok(false));
+        assert_eq!(check("http://10.0.0.1/"), Ok(false));
+        assert_eq!(check("http://169.254.169.254/latest/meta-data/"), Ok(false));
+        assert_eq!(check("http://100.64.0.1/"), Ok(false));
+        assert_eq!(check("http://[::1]/"), Ok(false));
+        assert_eq!(check("http://[::ffff:192.168.0.1]/"), Ok(false));
+        assert_eq!(check("http://[fd00::1]/"), Ok(false));
+        assert_eq!(check("http://[fe80::1]/"), Ok(false));
+    }
+}
@@ -0,0 +1,340 @@
+//! Public status of the API: the dependencies are probed in the background and
+//! their uptime is rolled up from the recorded probes, along with the
+//! incidents the owner announces
+
+use std::time::Duration;
+
+use axum::{
+    Json, Router,
+    extract::{Path, State},
+    http::StatusCode,
+    routing::{get, post},
+};
+use diesel::prelude::*;
+use diesel::sql_types::{Bool, Float8, Nullable, Text, Timestamp};
+use diesel_async::RunQueryDsl;
+use eyre::eyre;
+use serde::Deserialize;
+use utoipa::OpenApi;
+
+use crate::{
+    App,
+    config::Env,
+    error::AppError,
+    identity::AuthUser,
+    schema::{status_incidents, status_probes},
+};
+
+pub use api_models::{ComponentStatus, Incident, StatusReport};
+
+const PROBE_INTERVAL: Duration = Duration::from_secs(60);
+const PROBE_TIMEOUT: Duration = Duration::from_secs(10);
+/// Probes older than this are deleted, it's the longest uptime period
+const PROBE_RETENTION_DAYS: i64 = 30;
+/// Resolved incidents are shown for this long
+const RESOLVED_INCIDENT_DAYS: i64 = 30;
+const MAX_ERROR_CHARS: usize = 500;
+
+#[derive(OpenApi)]
+#[openapi(paths(get_status))]
+pub struct ApiDoc;
+
+pub fn route() -> Router<App> {
+    Router::<App>::new().route("/status", get(get_status))
+}
+
+/// Announcing and resolving incidents, for the owner
+pub fn admin_route() -> Router<App> {
+    Router::<App>::new()
+        .route("/admin/status/incidents", post(create_incident))
+        .route(
+            "/admin/status/incidents/{id}/resolve",
+            post(resolve_incident),
+        )
+}
+
+/// Probes the dependencies periodically and records the results
+pub fn start_probes(ctx: App) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(PROBE_INTERVAL);
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+        loop {
+            interval.tick().await;
+
+            if let Err(e) = probe_all(&ctx).await {
+                tracing::error!(?e, "Failed to record the status probes");
+            }
+        }
+    });
+}
+
+struct Probe {
+    component: &'static str,
+    healthy: bool,
+    latency_ms: i32,
+    error: Option<String>,
+}
+
+async fn probe_all(ctx: &App) -> Result<(), eyre::Error> {
+    let mut probes = vec![probe("database", probe_database(ctx)).await];
+    if let Some(memories) = &ctx.discord_memories {
+        probes.push(
+            probe("vector_db", async {
+                memories.list_scopes().await.map_err(|e| eyre!("{e}"))?;
+                Ok(())
+            })
+            .await,
+        );
+    }
+    // The website isn't running next to the API in development
+    if matches!(ctx.config.env, Env::Production) {
+        probes.push(probe("website", probe_website(ctx)).await);
+    }
+
+    // While the database is down nothing can be recorded, so the outage shows
+    // as a gap rather than failed probes
+    let mut conn = ctx.diesel.get().await?;
+
+    diesel::insert_into(status_probes::table)
+        .values(
+            probes
+                .iter()
+                .map(|probe| {
+                    (
+                        status_probes::component.eq(probe.component),
+                        status_probes::healthy.eq(probe.healthy),
+                        status_probes::latency_ms.eq(probe.latency_ms),
+                        status_probes::error.eq(probe.error.as_deref()),
+                    )
+                })
+                .collect::<Vec<_>>(),
+        )
+        .execute(&mut conn)
+        .await?;
+
+    let cutoff = chrono::Utc::now().naive_utc() - chrono::Duration::days(PROBE_RETENTION_DAYS);
+    diesel::delete(status_probes::table.filter(status_probes::checked_at.lt(cutoff)))
+        .execute(&mut conn)
+        .await?;
+
+    Ok(())
+}
+
+/// Runs the check within [PROBE_TIMEOUT], a component is healthy if it
+/// succeeds in time
+async fn probe(
+    component: &'static str,
+    check: impl Future<Output = Result<(), eyre::Error>>,
+) -> Probe {
+    let started = tokio::time::Instant::now();
+    let result = tokio::time::timeout(PROBE_TIMEOUT, check)
+        .await
+        .unwrap_or_else(|_| Err(eyre!("timed out after {PROBE_TIMEOUT:?}")));
+    let latency_ms = i32::try_from(started.elapsed().as_millis()).unwrap_or(i32::MAX);
+
+    let error = result.err().map(|e| {
+        format!("{e:#}")
+            .chars()
+            .take(MAX_ERROR_CHARS)
+            .collect::<String>()
+    });
+    if let Some(error) = &error {
+        tracing::warn!(component, error, "Status probe failed");
+    }
+
+    Probe {
+        component,
+        healthy: error.is_none(),
+        latency_ms,
+        error,
+    }
+}
+
+async fn probe_database(ctx: &App) -> Result<(), eyre::Error> {
+    let mut conn = ctx.diesel.get().await?;
+    diesel::sql_query("SELECT 1").execute(&mut conn).await?;
+    Ok(())
+}
+
+async fn probe_website(ctx: &App) -> Result<(), eyre::Error> {
+    ctx.http
+        .get(&ctx.config.site_url)
+        .send()
+        .await?
+        .error_for_status()?;
+    Ok(())
+}
+
+#[derive(QueryableByName)]
+struct ComponentRow {
+    #[diesel(sql_type = Text)]
+    component: String,
+    #[diesel(sql_type = Bool)]
+    healthy: bool,
+    #[diesel(sql_type = Timestamp)]
+    checked_at: chrono::NaiveDateTime,
+    #[diesel(sql_type = Nullable<Float8>)]
+    uptime_24h: Option<f64>,
+    #[diesel(sql_type = Nullable<Float8>)]
+    uptime_7d: Option<f64>,
+    #[diesel(sql_type = Nullable<Float8>)]
+    uptime_30d: Option<f64>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/status",
+    tag = "status",
+    responses(
+        (status = 200, description = "Uptime of the dependencies and the recent incidents", body = StatusReport),
+    ),
+)]
+pub async fn get_status(State(ctx): State<App>) -> Result<Json<StatusReport>, AppError> {
+    let mut conn = ctx.diesel.get().await?;
+
+    let components = diesel::sql_query(
+        r#"
+        WITH latest AS (
+            SELECT DISTINCT ON (component) component, healthy, checked_at
+            FROM status_probes
+            ORDER BY component, checked_at DESC
+        ),
+        uptime AS (
+            SELECT
+                component,
+                (100.0 * AVG(healthy::INT) FILTER (WHERE checked_at > NOW() - INTERVAL '24 hours'))::FLOAT8 AS uptime_24h,
+                (100.0 * AVG(healthy::INT) FILTER (WHERE checked_at > NOW() - INTERVAL '7 days'))::FLOAT8 AS uptime_7d,
+                (100.0 * AVG(healthy::INT))::FLOAT8 AS uptime_30d
+            FROM status_probes
+            WHERE checked_at > NOW() - INTERVAL '30 days'
+            GROUP BY component
+        )
+        SELECT l.component, l.healthy, l.checked_at, u.uptime_24h, u.uptime_7d, u.uptime_30d
+        FROM latest l
+        LEFT JOIN uptime u USING (component)
+        ORDER BY l.component
+        "#,
+    )
+    .load::<ComponentRow>(&mut conn)
+    .await?;
+
+    let resolved_cutoff =
+        chrono::Utc::now().naive_utc() - chrono::Duration::days(RESOLVED_INCIDENT_DAYS);
+    let incidents = status_incidents::table
+        .filter(
+            status_incidents::resolved_at
+                .is_null()
+                .or(status_incidents::resolved_at.gt(resolved_cutoff)),
+        )
+        .order(status_incidents::started_at.desc())
+        .select(IncidentRow::as_select())
+        .load(&mut conn)
+        .await?;
+
+    Ok(Json(StatusReport {
+        components: components
+            .into_iter()
+            .map(|row| ComponentStatus {
+                name: row.component,
+                operational: row.healthy,
+                last_checked_at: row.checked_at,
+                uptime_24h: row.uptime_24h,
+                uptime_7d: row.uptime_7d,
+                uptime_30d: row.uptime_30d,
+            })
+            .collect(),
+        incidents: incidents.into_iter().map(Incident::from).collect(),
+    }))
+}
+
+#[derive(Queryable, Selectable)]
+#[diesel(table_name = status_incidents)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+struct IncidentRow {
+    id: i32,
+    title: String,
+    description: Option<String>,
+    component: Option<String>,
+    started_at: chrono::NaiveDateTime,
+    resolved_at: Option<chrono::NaiveDateTime>,
+}
+
+impl From<IncidentRow> for Incident {
+    fn from(row: IncidentRow) -> Self {
+        Incident {
+            id: row.id,
+            title: row.title,
+            description: row.description,
+            component: row.component,
+            started_at: row.started_at,
+            resolved_at: row.resolved_at,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct IncidentSubmission {
+    title: String,
+    description: Option<String>,
+    component: Option<String>,
+}
+
+async fn create_incident(
+    State(ctx): State<App>,
+    AuthUser(i): AuthUser,
+    Json(submission): Json<IncidentSubmission>,
+) -> Result<Json<Incident>, AppError> {
+    ensure_owner(&ctx, i.id)?;
+
+    let title = submission.title.trim();
+    if title.is_empty() {
+        return Err(("The title must not be empty", StatusCode::BAD_REQUEST).into());
+    }
+
+    let mut conn = ctx.diesel.get().await?;
+
+    let incident = diesel::insert_into(status_incidents::table)
+        .values((
+            status_incidents::title.eq(title),
+            status_incidents::description.eq(submission.description),
+            status_incidents::component.eq(submission.component),
+        ))
+        .returning(IncidentRow::as_returning())
+        .get_result(&mut conn)
+        .await?;
+
+    Ok(Json(incident.into()))
+}
+
+async fn resolve_incident(
+    State(ctx): State<App>,
+    AuthUser(i): AuthUser,
+    Path(id): Path<i32>,
+) -> Result<Json<Incident>, AppError> {
+    ensure_owner(&ctx, i.id)?;
+
+    let mut conn = ctx.diesel.get().await?;
+
+    // Resolving again keeps the original resolution time
+    let incident = diesel::update(status_incidents::table.filter(status_incidents::id.eq(id)))
+        .set(
+            status_incidents::resolved_at.eq(diesel::dsl::sql::<Nullable<Timestamp>>(
+                "COALESCE(resolved_at, NOW())",
+            )),
+        )
+        .returning(IncidentRow::as_returning())
+        .get_result(&mut conn)
+        .await
+        .optional()?
+        .ok_or(("Incident not found", StatusCode::NOT_FOUND))?;
+
+    Ok(Json(incident.into()))
+}
+
+fn ensure_owner(ctx: &App, identity_id: i32) -> Result<(), AppError> {
+    if identity_id != ctx.config.owner_identity_id {
+        return Err(("Not permitted", StatusCode::FORBIDDEN).into());
+    }
+    Ok(())
+}
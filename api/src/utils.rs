@@ -1,14 +1,12 @@
 use std::{
     collections::{HashMap, HashSet},
-    sync::{LazyLock, Mutex},
+    sync::LazyLock,
 };
 
-use fastembed::{EmbeddingModel, InitOptions, TextEmbedding};
-
-use crate::config::FASTEMBED_CACHE_DIR;
-
 pub const RECOMMENDER_EMBEDDING_BITS: usize = 384;
 pub const MAX_RECOMMENDER_TERMS: usize = 48;
+/// Characters of the content the language is detected from
+const LANGUAGE_SAMPLE_CHARS: usize = 2000;
 
 static RECOMMENDER_STOPWORDS: LazyLock<HashSet<&'static str>> = LazyLock::new(|| {
     HashSet::from([
@@ -102,35 +100,40 @@ pub fn extract_recommender_terms(title: &str, content: Option<&str>) -> Vec<Stri
         .collect()
 }
 
-/// Shared FastEmbed embedding model instance.
-/// This is lazily initialized on first use and shared across the application
-/// to reduce memory usage by avoiding multiple model instances.
-static SHARED_EMBEDDING_MODEL: LazyLock<Result<Mutex<TextEmbedding>, EmbeddingError>> =
-    LazyLock::new(|| {
-        tracing::info!("Initializing shared FastEmbed embedding model");
-        let cache_dir = FASTEMBED_CACHE_DIR
-            .parse()
-            .map_err(|err| EmbeddingError(format!("invalid fastembed cache dir: {err}")))?;
-        let model = TextEmbedding::try_new(
-            InitOptions::new(EmbeddingModel::AllMiniLML12V2).with_cache_dir(cache_dir),
-        )
-        .map_err(|err| EmbeddingError(format!("failed to initialize embedding model: {err}")))?;
-
-        Ok(Mutex::new(model))
-    });
+/// ISO 639-3 code of the language of the article, none if it can't be told
+/// reliably
+pub fn detect_language(title: &str, content: &str) -> Option<String> {
+    let sample = content
+        .chars()
+        .take(LANGUAGE_SAMPLE_CHARS)
+        .collect::<String>();
+    whatlang::detect(&format!("{title}\n{sample}"))
+        .filter(whatlang::Info::is_reliable)
+        .map(|info| info.lang().code().to_string())
+}
 
-/// Error type for embedding operations
-#[derive(Clone, Debug, thiserror::Error)]
-#[error("Embedding error: {0}")]
-pub struct EmbeddingError(String);
-
-/// Generate embeddings for a list of texts using the shared model.
-pub fn embed_texts(texts: Vec<String>) -> Result<Vec<Vec<f32>>, EmbeddingError> {
-    let model = SHARED_EMBEDDING_MODEL.as_ref().map_err(Clone::clone)?;
-    let mut model = model
-        .lock()
-        .map_err(|_| EmbeddingError("embedding model lock poisoned".to_string()))?;
-    model
-        .embed(texts, None)
-        .map_err(|e| EmbeddingError(format!("failed to generate embeddings: {e}")))
+/// Comma separated ISO 639-3 codes, none if one of them is unknown
+pub fn parse_languages(list: &str) -> Option<Vec<String>> {
+    list.split(',')
+        .map(str::trim)
+        .filter(|code| !code.is_empty())
+        .map(|code| {
+            whatlang::Lang::from_code(code.to_ascii_lowercase()).map(|lang| lang.code().to_string())
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn languages_are_iso_639_3_codes() {
+        assert_eq!(
+            parse_languages(" eng, VIE ,"),
+            Some(vec!["eng".to_string(), "vie".to_string()])
+        );
+        assert_eq!(parse_languages(""), Some(Vec::new()));
+        assert_eq!(parse_languages("eng,xx"), None);
+    }
 }
@@ -0,0 +1,78 @@
+use axum::{
+    Router,
+    extract::{FromRequestParts, Request},
+    http::{HeaderValue, request::Parts},
+    middleware::{self, Next},
+    response::Response,
+};
+
+use crate::App;
+
+/// When the unversioned routes stop being served, as an HTTP date
+pub const LEGACY_SUNSET: &str = "Thu, 01 Jul 2027 00:00:00 GMT";
+
+/// Version of the API a request was made to. Handlers that need to serve
+/// different representations extract it and match on it, the others don't
+/// need to care.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ApiVersion {
+    #[default]
+    V1,
+}
+
+impl ApiVersion {
+    pub fn prefix(self) -> &'static str {
+        match self {
+            ApiVersion::V1 => "/v1",
+        }
+    }
+}
+
+impl<S: Send + Sync> FromRequestParts<S> for ApiVersion {
+    type Rejection = std::convert::Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _: &S) -> Result<Self, Self::Rejection> {
+        Ok(parts
+            .extensions
+            .get::<ApiVersion>()
+            .copied()
+            .unwrap_or_default())
+    }
+}
+
+/// Serve the routes under `/v1`, and under their legacy unversioned paths with
+/// the deprecation headers pointing to the versioned ones
+pub fn versioned(routes: Router<App>) -> Router<App> {
+    Router::new()
+        .nest(
+            ApiVersion::V1.prefix(),
+            routes.clone().layer(middleware::from_fn(
+                |mut req: Request, next: Next| async move {
+                    req.extensions_mut().insert(ApiVersion::V1);
+                    next.run(req).await
+                },
+            )),
+        )
+        .merge(routes.layer(middleware::from_fn(deprecate_legacy)))
+}
+
+async fn deprecate_legacy(mut req: Request, next: Next) -> Response {
+    // The legacy routes serve the first version's representations
+    req.extensions_mut().insert(ApiVersion::V1);
+
+    let successor = format!(
+        "<{}{}>; rel=\"successor-version\"",
+        ApiVersion::V1.prefix(),
+        req.uri().path()
+    );
+
+    let mut response = next.run(req).await;
+    let headers = response.headers_mut();
+    headers.insert("deprecation", HeaderValue::from_static("true"));
+    headers.insert("sunset", HeaderValue::from_static(LEGACY_SUNSET));
+    if let Ok(link) = HeaderValue::from_str(&successor) {
+        headers.append("link", link);
+    }
+
+    response
+}
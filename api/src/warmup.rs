@@ -0,0 +1,82 @@
+//! Warming the caches of the hot endpoints at startup, so that the first
+//! readers after a deploy don't wait for Raindrop, Spotify and a cold
+//! database. `/ready` answers 503 until it's done, for the load balancer to
+//! hold the traffic back meanwhile, while `/health` only tells that the
+//! server is up.
+
+use std::{
+    fmt::Display,
+    sync::atomic::{AtomicBool, Ordering},
+    time::{Duration, Instant},
+};
+
+use axum::{Json, extract::State, http::StatusCode};
+use serde_json::json;
+
+use crate::{
+    App, config::ServerConfig, error::AppError, great_reads_feed, identity, recommendation,
+};
+
+/// Whether the caches were warmed, ready from the start if the warming is
+/// disabled
+pub struct Readiness(AtomicBool);
+
+impl Readiness {
+    pub fn new(config: &ServerConfig) -> Self {
+        Self(AtomicBool::new(config.cache_warmup.is_none()))
+    }
+
+    fn is_ready(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+impl App {
+    /// Warms the caches concurrently, each for at most the configured time,
+    /// then reports ready. The caches that fail to warm are logged and left
+    /// to the first request.
+    pub async fn warm_caches(&self) {
+        let Some(timeout) = self.config.cache_warmup else {
+            return;
+        };
+
+        let started = Instant::now();
+        tokio::join!(
+            warm(
+                "great-reads-highlights",
+                timeout,
+                great_reads_feed::warm_cache(self)
+            ),
+            warm("feed", timeout, recommendation::warm_feed(self)),
+            warm("currently-playing", timeout, identity::now_playing(self)),
+        );
+
+        self.readiness.0.store(true, Ordering::Relaxed);
+        tracing::info!(elapsed = ?started.elapsed(), "Warmed the caches, ready to serve");
+    }
+}
+
+async fn warm<T, E: Display>(
+    cache: &'static str,
+    timeout: Duration,
+    warming: impl Future<Output = Result<T, E>>,
+) {
+    match tokio::time::timeout(timeout, warming).await {
+        Ok(Ok(_)) => tracing::debug!(cache, "Warmed the cache"),
+        Ok(Err(err)) => tracing::warn!(cache, %err, "Failed to warm the cache"),
+        Err(_) => tracing::warn!(cache, ?timeout, "Timed out warming the cache"),
+    }
+}
+
+/// 503 until the caches are warmed
+pub async fn ready(State(app): State<App>) -> Result<Json<serde_json::Value>, AppError> {
+    if !app.readiness.is_ready() {
+        return Err(("Warming up", StatusCode::SERVICE_UNAVAILABLE).into());
+    }
+
+    Ok(Json(json!({
+        "status": 200,
+        "msg": "OK",
+        "detail": None::<String>,
+    })))
+}
@@ -0,0 +1,61 @@
+use api::{AppBuilder, build_router, config::ServerConfig};
+use axum::{
+    body::Body,
+    http::{Request, StatusCode},
+};
+use std::time::Duration;
+use tower::ServiceExt as _;
+
+#[tokio::test]
+async fn the_spec_is_served_versioned_and_under_the_legacy_path() {
+    let app = AppBuilder::new(ServerConfig::new_from_env())
+        .offline(true)
+        .build()
+        .await;
+    let router = build_router(app);
+
+    let response = router
+        .clone()
+        .oneshot(
+            Request::get("/v1/openapi.json")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(response.headers().get("deprecation").is_none());
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    let spec: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(spec["servers"][0]["url"], "/v1");
+
+    let response = router
+        .oneshot(Request::get("/openapi.json").body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers()["deprecation"], "true");
+}
+
+#[tokio::test]
+async fn ready_once_the_caches_are_warmed() {
+    let mut config = ServerConfig::new_from_env();
+    config.cache_warmup = Some(Duration::from_secs(1));
+    let app = AppBuilder::new(config).offline(true).build().await;
+    let router = build_router(app.clone());
+    let ready = || async {
+        router
+            .clone()
+            .oneshot(Request::get("/ready").body(Body::empty()).unwrap())
+            .await
+            .unwrap()
+            .status()
+    };
+
+    assert_eq!(ready().await, StatusCode::SERVICE_UNAVAILABLE);
+    // The caches that can't be warmed without a database don't hold it back
+    app.warm_caches().await;
+    assert_eq!(ready().await, StatusCode::OK);
+}
@@ -81,6 +81,7 @@
               buildInputs = with pkgs; [
                 openssl
                 libxml2
+                libopus # songbird voice decoding
                 pkgsUnstable.onnxruntime
               ];
               nativeBuildInputs = with pkgs; [
@@ -0,0 +1,3 @@
+ALTER TABLE blog_comments
+ADD COLUMN author_country TEXT,
+ADD COLUMN author_asn BIGINT;
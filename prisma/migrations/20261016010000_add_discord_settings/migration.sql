@@ -0,0 +1,23 @@
+-- Per-guild and per-channel Discord bot settings. NULL columns fall back to the
+-- guild settings, then to the environment defaults.
+CREATE TABLE discord_guild_settings (
+    guild_id BIGINT PRIMARY KEY,
+    mention_only BOOLEAN,
+    persona TEXT,
+    model TEXT,
+    created_at TIMESTAMP NOT NULL DEFAULT now(),
+    updated_at TIMESTAMP NOT NULL DEFAULT now()
+);
+
+CREATE TABLE discord_channel_settings (
+    channel_id BIGINT PRIMARY KEY,
+    guild_id BIGINT,
+    enabled BOOLEAN NOT NULL DEFAULT true,
+    mention_only BOOLEAN,
+    persona TEXT,
+    model TEXT,
+    created_at TIMESTAMP NOT NULL DEFAULT now(),
+    updated_at TIMESTAMP NOT NULL DEFAULT now()
+);
+
+CREATE INDEX idx_discord_channel_settings_guild_id ON discord_channel_settings(guild_id);
@@ -0,0 +1,16 @@
+-- Daily token usage of the Discord bot per channel and model
+CREATE TABLE discord_token_usage (
+    id SERIAL PRIMARY KEY,
+    channel_id BIGINT NOT NULL,
+    day DATE NOT NULL,
+    model TEXT NOT NULL,
+    input_tokens BIGINT NOT NULL DEFAULT 0,
+    output_tokens BIGINT NOT NULL DEFAULT 0,
+    cached_input_tokens BIGINT NOT NULL DEFAULT 0,
+    requests INTEGER NOT NULL DEFAULT 0,
+    created_at TIMESTAMP NOT NULL DEFAULT now(),
+    updated_at TIMESTAMP NOT NULL DEFAULT now()
+);
+
+CREATE UNIQUE INDEX discord_token_usage_channel_id_day_model_key ON discord_token_usage(channel_id, day, model);
+CREATE INDEX idx_discord_token_usage_day ON discord_token_usage(day);
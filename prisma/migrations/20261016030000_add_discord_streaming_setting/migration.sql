@@ -0,0 +1,2 @@
+ALTER TABLE discord_guild_settings ADD COLUMN streaming BOOLEAN;
+ALTER TABLE discord_channel_settings ADD COLUMN streaming BOOLEAN;
@@ -0,0 +1,3 @@
+-- NULL allows all tools, or inherits them from the guild for channels
+ALTER TABLE discord_guild_settings ADD COLUMN allowed_tools TEXT[];
+ALTER TABLE discord_channel_settings ADD COLUMN allowed_tools TEXT[];
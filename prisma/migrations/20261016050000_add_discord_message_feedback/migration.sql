@@ -0,0 +1,13 @@
+-- 👍/👎 reactions of users on the Discord bot's messages
+CREATE TABLE discord_message_feedback (
+    id SERIAL PRIMARY KEY,
+    message_id BIGINT NOT NULL,
+    channel_id BIGINT NOT NULL,
+    user_id BIGINT NOT NULL,
+    positive BOOLEAN NOT NULL,
+    content TEXT NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT now()
+);
+
+CREATE UNIQUE INDEX discord_message_feedback_message_id_user_id_positive_key ON discord_message_feedback(message_id, user_id, positive);
+CREATE INDEX idx_discord_message_feedback_created_at ON discord_message_feedback(created_at);
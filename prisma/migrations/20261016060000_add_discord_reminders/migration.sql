@@ -0,0 +1,12 @@
+-- Reminders scheduled by the Discord bot's agent
+CREATE TABLE discord_reminders (
+    id SERIAL PRIMARY KEY,
+    channel_id BIGINT NOT NULL,
+    user_id BIGINT NOT NULL,
+    message TEXT NOT NULL,
+    remind_at TIMESTAMP NOT NULL,
+    delivered_at TIMESTAMP,
+    created_at TIMESTAMP NOT NULL DEFAULT now()
+);
+
+CREATE INDEX idx_discord_reminders_pending ON discord_reminders(remind_at) WHERE delivered_at IS NULL;
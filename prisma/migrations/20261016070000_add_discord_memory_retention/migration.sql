@@ -0,0 +1,4 @@
+-- Days after which memories decay, 0 keeps them forever, NULL inherits the
+-- guild or default value
+ALTER TABLE discord_guild_settings ADD COLUMN memory_retention_days INTEGER;
+ALTER TABLE discord_channel_settings ADD COLUMN memory_retention_days INTEGER;
@@ -0,0 +1,23 @@
+-- Opt-in archive of the conversations in a channel, for analytics. The
+-- retention in days, 0 keeps the messages forever and NULL uses the default.
+ALTER TABLE discord_channel_settings ADD COLUMN archive BOOLEAN;
+ALTER TABLE discord_channel_settings ADD COLUMN archive_retention_days INTEGER;
+
+-- Messages, bot responses and tool calls of the archived channels. Threads are
+-- archived under their parent channel.
+CREATE TABLE discord_archived_messages (
+    id SERIAL PRIMARY KEY,
+    channel_id BIGINT NOT NULL,
+    thread_id BIGINT,
+    guild_id BIGINT,
+    message_id BIGINT,
+    user_id BIGINT,
+    -- message, response or tool_call
+    kind TEXT NOT NULL,
+    tool_name TEXT,
+    content TEXT NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT now()
+);
+
+CREATE INDEX discord_archived_messages_channel_id_created_at_idx ON discord_archived_messages(channel_id, created_at);
+CREATE INDEX discord_archived_messages_created_at_idx ON discord_archived_messages(created_at);
@@ -0,0 +1,22 @@
+-- Lets the agent warn, time out users and delete messages in the guild, and
+-- the classifier score from which messages are flagged, NULL uses the default
+ALTER TABLE discord_guild_settings ADD COLUMN moderation_enforcement BOOLEAN;
+ALTER TABLE discord_guild_settings ADD COLUMN moderation_threshold DOUBLE PRECISION;
+
+-- Audit trail of the flagged messages and the moderation actions taken
+CREATE TABLE discord_moderation_actions (
+    id SERIAL PRIMARY KEY,
+    guild_id BIGINT NOT NULL,
+    channel_id BIGINT NOT NULL,
+    -- The moderated user and message, if any
+    user_id BIGINT,
+    message_id BIGINT,
+    -- flag, warn, timeout or delete
+    action TEXT NOT NULL,
+    reason TEXT NOT NULL,
+    -- Classifier categories and scores of flagged messages
+    categories JSONB,
+    created_at TIMESTAMP NOT NULL DEFAULT now()
+);
+
+CREATE INDEX discord_moderation_actions_guild_id_created_at_idx ON discord_moderation_actions(guild_id, created_at);
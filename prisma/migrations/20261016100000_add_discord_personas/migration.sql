@@ -0,0 +1,17 @@
+-- Name of the persona profile, NULL inherits the guild or default profile
+ALTER TABLE discord_guild_settings ADD COLUMN persona_profile TEXT;
+ALTER TABLE discord_channel_settings ADD COLUMN persona_profile TEXT;
+
+-- Persona profiles besides the built-in ones, which they can override
+CREATE TABLE discord_personas (
+    name TEXT PRIMARY KEY,
+    tone TEXT NOT NULL,
+    -- Language to always respond in, the user's language if NULL
+    language TEXT,
+    -- none, light or heavy
+    slang TEXT NOT NULL,
+    -- strict, standard or edgy
+    safety TEXT NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT now(),
+    updated_at TIMESTAMP NOT NULL DEFAULT now()
+);
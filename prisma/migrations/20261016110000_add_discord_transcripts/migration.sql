@@ -0,0 +1,13 @@
+-- Structured transcripts of the agent runs, with secrets redacted, to replay
+-- them against other models
+CREATE TABLE discord_transcripts (
+    -- UUID, also the file name when recording to files
+    id TEXT PRIMARY KEY,
+    channel_id BIGINT NOT NULL,
+    guild_id BIGINT,
+    model TEXT NOT NULL,
+    transcript JSONB NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT now()
+);
+
+CREATE INDEX discord_transcripts_channel_id_created_at_idx ON discord_transcripts(channel_id, created_at);
@@ -0,0 +1,5 @@
+-- Users who agreed to talk to the bot in DMs
+CREATE TABLE discord_dm_consents (
+    user_id BIGINT PRIMARY KEY,
+    consented_at TIMESTAMP NOT NULL DEFAULT now()
+);
@@ -0,0 +1,8 @@
+-- Articles of the recommendation feed posted to Discord, so that each one is
+-- only posted once per channel
+CREATE TABLE discord_feed_posts (
+    online_article_id INTEGER NOT NULL REFERENCES online_articles(id) ON DELETE CASCADE,
+    channel_id BIGINT NOT NULL,
+    posted_at TIMESTAMP NOT NULL DEFAULT now(),
+    PRIMARY KEY (online_article_id, channel_id)
+);
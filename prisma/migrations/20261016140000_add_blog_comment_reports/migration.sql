@@ -0,0 +1,19 @@
+-- Readers' reports of abusive blog comments, the unresolved ones make up the
+-- moderation queue
+CREATE TABLE blog_comment_reports (
+    id SERIAL PRIMARY KEY,
+    comment_id INTEGER NOT NULL REFERENCES blog_comments(id) ON DELETE CASCADE,
+    reason TEXT NOT NULL,
+    reporter_ip TEXT NOT NULL,
+    identity_id INTEGER REFERENCES identities(id) ON DELETE SET NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT now(),
+    resolved_at TIMESTAMP
+);
+
+-- A reader reports a comment once
+CREATE UNIQUE INDEX blog_comment_reports_comment_id_reporter_ip_key
+    ON blog_comment_reports (comment_id, reporter_ip);
+
+CREATE INDEX blog_comment_reports_unresolved_idx
+    ON blog_comment_reports (created_at)
+    WHERE resolved_at IS NULL;
@@ -0,0 +1,8 @@
+-- Roles shown as badges next to the blog comments: co-authors, moderators and
+-- verified regulars. The site owner is an author without a row here.
+CREATE TABLE identity_roles (
+    identity_id INTEGER NOT NULL REFERENCES identities(id) ON DELETE CASCADE,
+    role TEXT NOT NULL CHECK (role IN ('author', 'moderator', 'verified')),
+    granted_at TIMESTAMP NOT NULL DEFAULT now(),
+    PRIMARY KEY (identity_id, role)
+);
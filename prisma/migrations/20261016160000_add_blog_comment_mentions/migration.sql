@@ -0,0 +1,12 @@
+-- Identities mentioned with @handle in a comment, the unseen ones are the
+-- mentioned user's notifications
+CREATE TABLE blog_comment_mentions (
+    comment_id INTEGER NOT NULL REFERENCES blog_comments(id) ON DELETE CASCADE,
+    identity_id INTEGER NOT NULL REFERENCES identities(id) ON DELETE CASCADE,
+    created_at TIMESTAMP NOT NULL DEFAULT now(),
+    seen_at TIMESTAMP,
+    PRIMARY KEY (comment_id, identity_id)
+);
+
+CREATE INDEX blog_comment_mentions_identity_id_idx
+    ON blog_comment_mentions (identity_id, created_at DESC);
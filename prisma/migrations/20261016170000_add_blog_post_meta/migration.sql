@@ -0,0 +1,5 @@
+-- Generated from the published post when it's registered
+ALTER TABLE blog_posts
+    ADD COLUMN reading_time_mins INTEGER,
+    ADD COLUMN summary TEXT,
+    ADD COLUMN meta_updated_at TIMESTAMP;
@@ -0,0 +1,10 @@
+-- Embeddings of the chunks of the blog posts, quantized the same way as
+-- online_article_chunks so that the two can be compared for related reading
+CREATE TABLE blog_post_chunks (
+    id SERIAL PRIMARY KEY,
+    post_id INTEGER NOT NULL REFERENCES blog_posts(id) ON DELETE CASCADE,
+    embedding BIT(384) NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT now()
+);
+
+CREATE INDEX blog_post_chunks_post_id_idx ON blog_post_chunks(post_id) INCLUDE (embedding);
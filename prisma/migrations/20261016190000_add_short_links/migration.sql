@@ -0,0 +1,25 @@
+-- Short codes redirecting to long URLs, shared by the owner to track where
+-- the readers come from
+CREATE TABLE short_links (
+    id SERIAL PRIMARY KEY,
+    code TEXT NOT NULL UNIQUE,
+    url TEXT NOT NULL,
+    expires_at TIMESTAMP,
+    max_clicks INTEGER,
+    click_count INTEGER NOT NULL DEFAULT 0,
+    created_at TIMESTAMP NOT NULL DEFAULT now()
+);
+
+CREATE TABLE short_link_clicks (
+    id SERIAL PRIMARY KEY,
+    short_link_id INTEGER NOT NULL REFERENCES short_links(id) ON DELETE CASCADE,
+    ip TEXT NOT NULL,
+    country TEXT,
+    asn BIGINT,
+    referrer TEXT,
+    user_agent TEXT,
+    created_at TIMESTAMP NOT NULL DEFAULT now()
+);
+
+CREATE INDEX short_link_clicks_short_link_id_idx
+    ON short_link_clicks (short_link_id, created_at DESC);
@@ -0,0 +1,25 @@
+-- Results of the periodic probes of the dependencies, rolled up into the
+-- uptime of the status endpoint
+CREATE TABLE status_probes (
+    id SERIAL PRIMARY KEY,
+    component TEXT NOT NULL,
+    healthy BOOLEAN NOT NULL,
+    latency_ms INTEGER NOT NULL,
+    error TEXT,
+    checked_at TIMESTAMP NOT NULL DEFAULT now()
+);
+
+CREATE INDEX status_probes_component_checked_at_idx ON status_probes (component, checked_at DESC);
+CREATE INDEX status_probes_checked_at_idx ON status_probes (checked_at);
+
+-- Incidents announced by the owner, open until resolved
+CREATE TABLE status_incidents (
+    id SERIAL PRIMARY KEY,
+    title TEXT NOT NULL,
+    description TEXT,
+    component TEXT,
+    started_at TIMESTAMP NOT NULL DEFAULT now(),
+    resolved_at TIMESTAMP
+);
+
+CREATE INDEX status_incidents_started_at_idx ON status_incidents (started_at DESC);
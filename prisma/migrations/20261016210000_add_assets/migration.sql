@@ -0,0 +1,27 @@
+-- Files uploaded to the S3-compatible bucket, referenced by the blog posts and
+-- comments through their signed URLs
+CREATE TABLE assets (
+    id SERIAL PRIMARY KEY,
+    key TEXT NOT NULL UNIQUE,
+    identity_id INTEGER NOT NULL REFERENCES identities(id) ON DELETE CASCADE,
+    filename TEXT NOT NULL,
+    content_type TEXT NOT NULL,
+    size_bytes BIGINT NOT NULL,
+    -- Set for the images that could be decoded
+    width INTEGER,
+    height INTEGER,
+    created_at TIMESTAMP NOT NULL DEFAULT now()
+);
+
+CREATE INDEX assets_identity_id_idx ON assets (identity_id, created_at DESC);
+
+-- Resized copies of the images, generated after the upload
+CREATE TABLE asset_variants (
+    asset_id INTEGER NOT NULL REFERENCES assets(id) ON DELETE CASCADE,
+    name TEXT NOT NULL,
+    key TEXT NOT NULL,
+    width INTEGER NOT NULL,
+    height INTEGER NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT now(),
+    PRIMARY KEY (asset_id, name)
+);
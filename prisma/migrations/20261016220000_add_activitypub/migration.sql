@@ -0,0 +1,16 @@
+-- Replies from the fediverse are stored as comments, identified by the ID of
+-- their ActivityPub object so that they are only stored once
+ALTER TABLE blog_comments ADD COLUMN source TEXT NOT NULL DEFAULT 'site';
+ALTER TABLE blog_comments ADD COLUMN source_id TEXT UNIQUE;
+
+-- When the post was announced to the followers, it's in the outbox since
+ALTER TABLE blog_posts ADD COLUMN federated_at TIMESTAMP;
+
+-- Fediverse accounts following the blog's actor
+CREATE TABLE activitypub_followers (
+    id SERIAL PRIMARY KEY,
+    actor_id TEXT NOT NULL UNIQUE,
+    inbox TEXT NOT NULL,
+    shared_inbox TEXT,
+    created_at TIMESTAMP NOT NULL DEFAULT now()
+);
@@ -0,0 +1,13 @@
+-- Notes, bookmarks and likes posted with a Micropub client
+CREATE TABLE notes (
+    id SERIAL PRIMARY KEY,
+    kind TEXT NOT NULL,
+    content TEXT,
+    name TEXT,
+    url TEXT,
+    categories TEXT[] NOT NULL DEFAULT '{}',
+    published_at TIMESTAMP NOT NULL DEFAULT now(),
+    created_at TIMESTAMP NOT NULL DEFAULT now()
+);
+
+CREATE INDEX notes_published_at_idx ON notes (published_at DESC, id DESC);
@@ -0,0 +1,7 @@
+-- History is kept per identity, the rows without one are the default history
+-- built from the configured Raindrop collections, which anonymous visitors
+-- and the identities without a history are ranked against
+ALTER TABLE user_history
+    ADD COLUMN identity_id INTEGER REFERENCES identities(id) ON DELETE CASCADE;
+
+CREATE INDEX user_history_identity_id_idx ON user_history (identity_id, online_article_id);
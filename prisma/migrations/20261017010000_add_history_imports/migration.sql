@@ -0,0 +1,14 @@
+-- Where each history entry came from, so that the weight of a source can be
+-- changed after it was imported
+ALTER TABLE user_history ADD COLUMN source TEXT NOT NULL DEFAULT 'raindrop';
+
+-- The export files of read-it-later services the readers imported into their
+-- history, one per service
+CREATE TABLE history_imports (
+    identity_id INTEGER NOT NULL REFERENCES identities(id) ON DELETE CASCADE,
+    source TEXT NOT NULL,
+    weight DOUBLE PRECISION NOT NULL,
+    item_count INTEGER NOT NULL DEFAULT 0,
+    imported_at TIMESTAMP NOT NULL DEFAULT now(),
+    PRIMARY KEY (identity_id, source)
+);
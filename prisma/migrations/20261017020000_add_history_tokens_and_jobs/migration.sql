@@ -0,0 +1,26 @@
+-- Tokens of the browser extensions and bookmarklets saving pages to the
+-- history of a reader, only their SHA-256 digest is stored
+CREATE TABLE history_tokens (
+    id SERIAL PRIMARY KEY,
+    identity_id INTEGER NOT NULL REFERENCES identities(id) ON DELETE CASCADE,
+    name TEXT NOT NULL,
+    token_hash TEXT NOT NULL UNIQUE,
+    last_used_at TIMESTAMP,
+    created_at TIMESTAMP NOT NULL DEFAULT now()
+);
+
+CREATE INDEX history_tokens_identity_id_idx ON history_tokens (identity_id);
+
+-- Pages saved to the history, fetched and embedded in the background
+CREATE TABLE history_jobs (
+    id SERIAL PRIMARY KEY,
+    identity_id INTEGER NOT NULL REFERENCES identities(id) ON DELETE CASCADE,
+    url TEXT NOT NULL,
+    weight DOUBLE PRECISION,
+    status TEXT NOT NULL DEFAULT 'pending',
+    error TEXT,
+    created_at TIMESTAMP NOT NULL DEFAULT now(),
+    finished_at TIMESTAMP
+);
+
+CREATE INDEX history_jobs_identity_id_idx ON history_jobs (identity_id, created_at DESC);
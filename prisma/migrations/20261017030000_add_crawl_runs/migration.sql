@@ -0,0 +1,15 @@
+-- Runs of the recommendation crawler with their progress, updated while they
+-- run so that they can be followed and kept for history
+CREATE TABLE crawl_runs (
+    id SERIAL PRIMARY KEY,
+    status TEXT NOT NULL DEFAULT 'running',
+    trigger TEXT NOT NULL,
+    fetched INTEGER NOT NULL DEFAULT 0,
+    embedded INTEGER NOT NULL DEFAULT 0,
+    failed INTEGER NOT NULL DEFAULT 0,
+    failures JSONB NOT NULL DEFAULT '[]',
+    started_at TIMESTAMP NOT NULL DEFAULT now(),
+    finished_at TIMESTAMP
+);
+
+CREATE INDEX crawl_runs_started_at_idx ON crawl_runs (started_at DESC);
@@ -0,0 +1,7 @@
+-- How each source is crawled, the interval and the number of items fall back
+-- to the defaults of the crawler when unset
+ALTER TABLE online_article_sources
+    ADD COLUMN enabled BOOLEAN NOT NULL DEFAULT true,
+    ADD COLUMN crawl_interval_mins INTEGER,
+    ADD COLUMN max_items INTEGER,
+    ADD COLUMN last_crawled_at TIMESTAMP;
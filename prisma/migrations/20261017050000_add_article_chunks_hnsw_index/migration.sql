@@ -0,0 +1,4 @@
+-- Nearest neighbours of the binary quantized embeddings by Hamming distance,
+-- rebuilt with other parameters by the `reindex-embeddings` command
+CREATE INDEX online_article_chunks_embedding_hnsw_idx ON online_article_chunks
+    USING hnsw (embedding bit_hamming_ops) WITH (m = 16, ef_construction = 64);
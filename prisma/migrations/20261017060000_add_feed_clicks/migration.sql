@@ -0,0 +1,21 @@
+-- Clicks on the feed items and how often each position of each ranking preset
+-- was shown, to compare the presets by click-through rate
+CREATE TABLE feed_clicks (
+    id SERIAL PRIMARY KEY,
+    online_article_id INTEGER NOT NULL REFERENCES online_articles(id) ON DELETE CASCADE,
+    ranking TEXT NOT NULL,
+    position INTEGER NOT NULL,
+    clicked_at TIMESTAMP NOT NULL DEFAULT now()
+);
+
+CREATE INDEX feed_clicks_clicked_at_idx ON feed_clicks (clicked_at);
+
+CREATE INDEX feed_clicks_online_article_id_idx ON feed_clicks (online_article_id);
+
+CREATE TABLE feed_impressions (
+    day DATE NOT NULL,
+    ranking TEXT NOT NULL,
+    position INTEGER NOT NULL,
+    count BIGINT NOT NULL DEFAULT 0,
+    PRIMARY KEY (day, ranking, position)
+);
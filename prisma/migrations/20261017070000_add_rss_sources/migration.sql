@@ -0,0 +1,5 @@
+-- Sources crawled from an RSS or Atom feed, the other sources have a client
+-- of their own
+ALTER TABLE online_article_sources ADD COLUMN feed_url TEXT;
+
+CREATE UNIQUE INDEX online_article_sources_feed_url_key ON online_article_sources(feed_url);
@@ -0,0 +1,12 @@
+-- The links of the stored articles are re-checked periodically, with a
+-- backoff while they fail. Dead links keep an archived copy if there is one.
+ALTER TABLE online_articles
+    ADD COLUMN link_status TEXT NOT NULL DEFAULT 'unchecked',
+    ADD COLUMN link_failures INTEGER NOT NULL DEFAULT 0,
+    ADD COLUMN link_checked_at TIMESTAMP,
+    ADD COLUMN link_next_check_at TIMESTAMP,
+    ADD COLUMN link_validator TEXT,
+    ADD COLUMN archive_url TEXT,
+    ADD COLUMN content_refreshed_at TIMESTAMP;
+
+CREATE INDEX online_articles_link_next_check_at_idx ON online_articles(link_next_check_at);
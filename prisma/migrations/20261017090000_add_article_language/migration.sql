@@ -0,0 +1,3 @@
+-- ISO 639-3 code of the language detected when the article was fetched, NULL
+-- if it couldn't be told
+ALTER TABLE online_articles ADD COLUMN lang TEXT;
@@ -0,0 +1,3 @@
+-- Only a teaser of the article could be read, it was embedded from its title
+-- and the text of the submission
+ALTER TABLE online_articles ADD COLUMN paywalled BOOLEAN NOT NULL DEFAULT false;
@@ -0,0 +1,3 @@
+-- 'content' for the chunks of the page, 'comments' for the one embedded from
+-- the top comments of its discussion
+ALTER TABLE online_article_chunks ADD COLUMN kind TEXT NOT NULL DEFAULT 'content';
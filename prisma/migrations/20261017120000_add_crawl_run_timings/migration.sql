@@ -0,0 +1,2 @@
+-- How long each source and each step of the run took
+ALTER TABLE crawl_runs ADD COLUMN timings JSONB NOT NULL DEFAULT '[]';
@@ -0,0 +1,9 @@
+-- Summaries of the feed articles generated on demand, kept so that each
+-- article is summarized once
+CREATE TABLE online_article_summaries (
+    online_article_id INTEGER PRIMARY KEY REFERENCES online_articles(id) ON DELETE CASCADE,
+    summary TEXT NOT NULL,
+    takeaways JSONB NOT NULL DEFAULT '[]',
+    model TEXT NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT now()
+);
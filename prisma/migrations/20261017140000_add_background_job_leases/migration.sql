@@ -0,0 +1,8 @@
+-- The instance running each background job, the jobs are held with advisory
+-- locks and the leases renewed while they run so that a dead holder shows
+CREATE TABLE background_job_leases (
+    job TEXT PRIMARY KEY,
+    instance TEXT NOT NULL,
+    acquired_at TIMESTAMP NOT NULL DEFAULT now(),
+    renewed_at TIMESTAMP NOT NULL DEFAULT now()
+);
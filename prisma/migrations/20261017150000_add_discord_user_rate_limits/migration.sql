@@ -0,0 +1,3 @@
+-- Messages a user can get the bot to answer per 10 minutes in the channel,
+-- 0 for no limit and null for the configured default
+ALTER TABLE discord_channel_settings ADD COLUMN user_rate_limit INTEGER;
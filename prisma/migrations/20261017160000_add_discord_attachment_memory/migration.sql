@@ -0,0 +1,3 @@
+-- Whether the text and PDF files attached in the channel are stored in its
+-- memory, null for off
+ALTER TABLE discord_channel_settings ADD COLUMN store_attachments BOOLEAN;
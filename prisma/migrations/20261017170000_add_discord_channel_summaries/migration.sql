@@ -0,0 +1,21 @@
+-- How often the bot posts a summary of the channel activity, 'daily' or
+-- 'weekly', null for never
+ALTER TABLE discord_channel_settings ADD COLUMN summary_schedule TEXT;
+
+-- The summaries posted by the bot, also served by the API
+CREATE TABLE discord_channel_summaries (
+    id SERIAL PRIMARY KEY,
+    channel_id BIGINT NOT NULL,
+    guild_id BIGINT,
+    schedule TEXT NOT NULL,
+    period_start TIMESTAMP NOT NULL,
+    period_end TIMESTAMP NOT NULL,
+    content TEXT NOT NULL,
+    message_count INTEGER NOT NULL,
+    -- The message of the embed, null if it couldn't be posted
+    message_id BIGINT,
+    created_at TIMESTAMP NOT NULL DEFAULT now()
+);
+
+-- A period is summarized once per channel
+CREATE UNIQUE INDEX idx_discord_channel_summaries_period ON discord_channel_summaries(channel_id, period_start);
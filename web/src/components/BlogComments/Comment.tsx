@@ -43,9 +43,14 @@ export default function CommentComponent(props: {
       <header class="ui-meta comment-header">
         <span class="comment-num" aria-hidden="true" />
         <span class="comment-author-name">{props.comment.author_name}</span>
-        {(props.comment.is_blog_author ?? false) && (
-          <span class="comment-badge">author</span>
-        )}
+        <For
+          each={
+            props.comment.roles ??
+            (props.comment.is_blog_author === true ? ["author"] : [])
+          }
+        >
+          {(role) => <span class="comment-badge">{role}</span>}
+        </For>
         <span class="comment-spacer" />
         <span class="comment-date">
           {timeSince(new Date(Date.parse(props.comment.created_at + "Z")))}
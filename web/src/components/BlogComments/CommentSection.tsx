@@ -14,6 +14,7 @@ import config from "@/config";
 import { createFetch } from "@/rpc";
 import { checkAuthUser } from "@/state";
 import { z } from "zod/v4";
+import type { CommentRole } from "@/types/api/CommentRole";
 import("./CommentSection.scss");
 
 const CommentComponent = lazy(async () => await import("./Comment"));
@@ -32,6 +33,7 @@ export interface Comment {
   depth: number;
   is_blog_author?: boolean;
   is_comment_owner?: boolean;
+  roles?: CommentRole[];
 }
 
 const fetchComments = createFetch(z.custom<Comment[]>());
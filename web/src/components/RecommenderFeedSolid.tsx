@@ -8,36 +8,15 @@ import {
 } from "solid-js";
 import { toast } from "solid-sonner";
 import config from "@/config";
+import type { FeedEvent } from "@/types/api/FeedEvent";
+import type { FeedItem } from "@/types/api/FeedItem";
+import type { FeedSnapshot } from "@/types/api/FeedSnapshot";
+import type { RankingPreset } from "@/types/api/RankingPreset";
+import type { SourceFilter } from "@/types/api/SourceFilter";
+import type { SourceInfo } from "@/types/api/SourceInfo";
 import { formatRelativeShort } from "@/utils/time";
 import "./RecommenderFeedSolid.scss";
 
-interface SourceInfo {
-  key: string;
-  score: number | null;
-  external_id: string | null;
-}
-
-interface FeedItem {
-  id: number;
-  title: string;
-  url: string;
-  score: number;
-  similarity_score: number | null;
-  submitted_at: string | null;
-  sources: SourceInfo[];
-}
-
-interface FeedSnapshot {
-  items: FeedItem[];
-}
-
-interface FeedEvent {
-  type: "NewEntries";
-  data: { count: number };
-}
-
-type SourceFilter = "all" | "hacker_news" | "lobsters";
-type RankingPreset = "balanced" | "newer_first" | "top_first" | "similar_first";
 type FeedFilters = {
   sourceFilter: SourceFilter;
   ranking: RankingPreset;
@@ -133,6 +112,8 @@ function getWebsiteUrl(url: string): string {
 
 function formatSourceKey(key: string): string {
   if (key === "hacker-news") return "hn";
+  // Feeds are keyed by their host
+  if (key.startsWith("rss:")) return key.slice("rss:".length);
   return key;
 }
 
@@ -247,7 +228,9 @@ export default function RecommenderFeed(): JSXElement {
         source: activeFilters.sourceFilter,
         ranking: activeFilters.ranking,
       });
-      const resp = await fetch(`${config.API_URL}/feed?${params}`);
+      const resp = await fetch(`${config.API_URL}/feed?${params}`, {
+        credentials: "include",
+      });
       if (!resp.ok) {
         throw new Error(`API error: ${resp.status}`);
       }
@@ -448,6 +431,10 @@ export default function RecommenderFeed(): JSXElement {
                     item.submitted_at ? new Date(item.submitted_at + "Z") : null
                   );
                   const domain = getWebsiteUrl(item.url);
+                  const href = () =>
+                    `${config.API_URL}/feed/items/${item.id}/redirect?${new URLSearchParams(
+                      { ranking: ranking(), position: String(i() + 1) }
+                    )}`;
                   return (
                     <li class="recommender-feed__entry">
                       <span class="recommender-feed__index">
@@ -495,7 +482,7 @@ export default function RecommenderFeed(): JSXElement {
                         </For>
                       </span>
                       <a
-                        href={item.url}
+                        href={href()}
                         target="_blank"
                         rel="noopener noreferrer"
                         class="recommender-feed__title"
@@ -503,12 +490,20 @@ export default function RecommenderFeed(): JSXElement {
                         {item.title}
                       </a>
                       <a
-                        href={item.url}
+                        href={href()}
                         target="_blank"
                         rel="noopener noreferrer"
                         class="ui-link ui-link--title recommender-feed__domain"
                       >
                         {domain}{" "}
+                        <Show when={item.paywalled}>
+                          <span
+                            class="recommender-feed__paywall"
+                            title="Only a teaser could be read, ranked by its title"
+                          >
+                            paywall{" "}
+                          </span>
+                        </Show>
                         <span class="recommender-feed__domain-arrow">
                           {"->"}
                         </span>
@@ -1,6 +1,6 @@
 const config = {
   // env.SITE is configured in astro.config.mts
-  API_URL: "/api",
+  API_URL: "/api/v1",
 };
 
 export default config;
@@ -0,0 +1,132 @@
+import config from "@/config";
+import { createFetch } from "@/rpc";
+import { For, Suspense, createResource, type JSXElement } from "solid-js";
+import { z } from "zod/v4";
+import "./_recent-runs.scss";
+
+const RUN_TYPES = new Set(["Run", "TrailRun", "VirtualRun"]);
+const MAX_RUNS = 3;
+
+const fetchRecentActivities = createFetch(
+  z.array(
+    z.object({
+      id: z.number(),
+      name: z.string(),
+      sport_type: z.string(),
+      distance_meters: z.number(),
+      moving_seconds: z.number(),
+      pace_seconds_per_km: z.nullable(z.number()),
+      started_at: z.string(),
+      polyline: z.nullable(z.string()),
+      url: z.string(),
+    })
+  )
+);
+
+/** The points of a polyline in Google's encoded format, as [lat, lng] */
+function decodePolyline(encoded: string): [number, number][] {
+  const values: number[] = [];
+  let value = 0;
+  let shift = 0;
+  for (let i = 0; i < encoded.length; i++) {
+    const chunk = encoded.charCodeAt(i) - 63;
+    value |= (chunk & 0x1f) << shift;
+    shift += 5;
+    if (chunk < 0x20) {
+      values.push(value & 1 ? ~(value >> 1) : value >> 1);
+      value = 0;
+      shift = 0;
+    }
+  }
+
+  const points: [number, number][] = [];
+  let lat = 0;
+  let lng = 0;
+  for (let i = 0; i + 1 < values.length; i += 2) {
+    lat += values[i];
+    lng += values[i + 1];
+    points.push([lat / 1e5, lng / 1e5]);
+  }
+  return points;
+}
+
+/** The route fitted in a 100x100 box, north up */
+function routePath(polyline: string): string {
+  const points = decodePolyline(polyline);
+  if (points.length < 2) return "";
+  const lats = points.map(([lat]) => lat);
+  const lngs = points.map(([, lng]) => lng);
+  const [minLat, maxLat] = [Math.min(...lats), Math.max(...lats)];
+  const [minLng, maxLng] = [Math.min(...lngs), Math.max(...lngs)];
+  const scale = 100 / Math.max(maxLat - minLat, maxLng - minLng, 1e-9);
+
+  return points
+    .map(
+      ([lat, lng], i) =>
+        `${i === 0 ? "M" : "L"}${((lng - minLng) * scale).toFixed(1)} ${(
+          (maxLat - lat) *
+          scale
+        ).toFixed(1)}`
+    )
+    .join(" ");
+}
+
+function formatPace(seconds: number): string {
+  const minutes = Math.floor(seconds / 60);
+  return `${minutes}:${String(seconds % 60).padStart(2, "0")}/km`;
+}
+
+export default function RecentRuns(): JSXElement {
+  const [activities] = createResource(async () => {
+    const res = await fetchRecentActivities(
+      `${config.API_URL}/recent-activities`
+    );
+    if (!res.ok) {
+      const err = await res.error();
+      throw Error(err.msg);
+    }
+
+    return await res.JSON();
+  });
+  const runs = () =>
+    (activities() ?? [])
+      .filter((a) => RUN_TYPES.has(a.sport_type))
+      .slice(0, MAX_RUNS);
+
+  return (
+    <Suspense fallback={null}>
+      {runs().length > 0 && (
+        <ul class="recent-runs">
+          <For each={runs()}>
+            {(run) => (
+              <li>
+                {run.polyline != null && (
+                  <svg
+                    class="recent-runs__map"
+                    viewBox="-5 -5 110 110"
+                    aria-hidden="true"
+                  >
+                    <path d={routePath(run.polyline)} />
+                  </svg>
+                )}
+                <p>
+                  <a href={run.url} target="_blank" rel="noopener noreferrer">
+                    <strong>
+                      {(run.distance_meters / 1000).toFixed(1)} km
+                    </strong>
+                  </a>
+                  {run.pace_seconds_per_km != null && (
+                    <span>{formatPace(run.pace_seconds_per_km)}</span>
+                  )}
+                  <span>
+                    {new Date(run.started_at + "Z").toLocaleDateString()}
+                  </span>
+                </p>
+              </li>
+            )}
+          </For>
+        </ul>
+      )}
+    </Suspense>
+  );
+}
@@ -1,8 +1,17 @@
 import config from "@/config";
 import { createFetch } from "@/rpc";
 import { AppState, checkAuthUser } from "@/state";
-import { Show, createEffect, createResource, type JSXElement } from "solid-js";
+import {
+  Show,
+  createEffect,
+  createResource,
+  createSignal,
+  type JSXElement,
+} from "solid-js";
 import { z } from "zod/v4";
+import type { HistoryImport } from "@/types/api/HistoryImport";
+import type { HistorySource } from "@/types/api/HistorySource";
+import type { HistoryToken } from "@/types/api/HistoryToken";
 import "./_page.scss";
 
 const fetchConnectedApps = createFetch(
@@ -19,6 +28,23 @@ const fetchConnectedApps = createFetch(
         added_on: z.string(),
       })
     ),
+    raindrop: z.optional(
+      z.object({
+        collection_ids: z.array(z.string()),
+        added_on: z.string(),
+      })
+    ),
+    strava: z.optional(
+      z.object({
+        athlete_name: z.string(),
+        added_on: z.string(),
+      })
+    ),
+    steam: z.optional(
+      z.object({
+        steam_id: z.string(),
+      })
+    ),
   })
 );
 
@@ -33,7 +59,7 @@ export default function AccountInfo(): JSXElement {
       .catch(() => {});
   });
 
-  const [connectedApps] = createResource(async () => {
+  const [connectedApps, { refetch }] = createResource(async () => {
     const res = await fetchConnectedApps(`${config.API_URL}/link/apps`, {
       credentials: "include",
     });
@@ -45,6 +71,148 @@ export default function AccountInfo(): JSXElement {
     return await res.JSON();
   });
 
+  const [imports, { refetch: refetchImports }] = createResource(async () => {
+    const res = await fetch(`${config.API_URL}/me/import`, {
+      credentials: "include",
+    });
+    if (!res.ok) {
+      throw Error("Could not load the imports");
+    }
+    return (await res.json()) as HistoryImport[];
+  });
+  const [importError, setImportError] = createSignal<string | null>(null);
+
+  // The export file is uploaded as is and imported in the background
+  async function importHistory(source: HistorySource, file: File) {
+    setImportError(null);
+    const res = await fetch(`${config.API_URL}/me/import/${source}`, {
+      method: "POST",
+      credentials: "include",
+      body: await file.text(),
+    });
+    if (!res.ok) {
+      const err = await res.json().catch(() => null);
+      setImportError(err?.msg ?? "Could not import the file");
+      return;
+    }
+    await refetchImports();
+  }
+
+  async function removeImport(source: HistorySource) {
+    setImportError(null);
+    const res = await fetch(`${config.API_URL}/me/import/${source}`, {
+      method: "DELETE",
+      credentials: "include",
+    });
+    if (!res.ok) {
+      setImportError("Could not remove the import");
+      return;
+    }
+    await refetchImports();
+  }
+
+  const [tokens, { refetch: refetchTokens }] = createResource(async () => {
+    const res = await fetch(`${config.API_URL}/me/history-tokens`, {
+      credentials: "include",
+    });
+    if (!res.ok) {
+      throw Error("Could not load the tokens");
+    }
+    return (await res.json()) as HistoryToken[];
+  });
+  const [tokenName, setTokenName] = createSignal("");
+  // Only shown once, right after it's created
+  const [newToken, setNewToken] = createSignal<string | null>(null);
+  const [tokenError, setTokenError] = createSignal<string | null>(null);
+
+  async function createToken() {
+    setTokenError(null);
+    const res = await fetch(`${config.API_URL}/me/history-tokens`, {
+      method: "POST",
+      credentials: "include",
+      headers: { "Content-Type": "application/json" },
+      body: JSON.stringify({ name: tokenName() }),
+    });
+    if (!res.ok) {
+      const err = await res.json().catch(() => null);
+      setTokenError(err?.msg ?? "Could not create the token");
+      return;
+    }
+    const token = (await res.json()) as HistoryToken;
+    setNewToken(token.token ?? null);
+    setTokenName("");
+    await refetchTokens();
+  }
+
+  async function revokeToken(id: number) {
+    setTokenError(null);
+    const res = await fetch(`${config.API_URL}/me/history-tokens/${id}`, {
+      method: "DELETE",
+      credentials: "include",
+    });
+    if (!res.ok) {
+      setTokenError("Could not revoke the token");
+      return;
+    }
+    await refetchTokens();
+  }
+
+  const [spotifyError, setSpotifyError] = createSignal<string | null>(null);
+
+  async function disconnectSpotify() {
+    setSpotifyError(null);
+    const res = await fetch(`${config.API_URL}/link/apps/spotify`, {
+      method: "DELETE",
+      credentials: "include",
+    });
+    if (!res.ok) {
+      const err = await res.json().catch(() => null);
+      setSpotifyError(err?.msg ?? "Could not disconnect Spotify");
+      return;
+    }
+    await refetch();
+  }
+
+  const [stravaError, setStravaError] = createSignal<string | null>(null);
+
+  async function disconnectStrava() {
+    setStravaError(null);
+    const res = await fetch(`${config.API_URL}/link/apps/strava`, {
+      method: "DELETE",
+      credentials: "include",
+    });
+    if (!res.ok) {
+      const err = await res.json().catch(() => null);
+      setStravaError(err?.msg ?? "Could not disconnect Strava");
+      return;
+    }
+    await refetch();
+  }
+
+  const [raindropToken, setRaindropToken] = createSignal("");
+  const [raindropError, setRaindropError] = createSignal<string | null>(null);
+
+  // Saved articles become the history the recommendations are ranked against
+  async function linkRaindrop(method: "PUT" | "DELETE") {
+    setRaindropError(null);
+    const res = await fetch(`${config.API_URL}/link/raindrop`, {
+      method,
+      credentials: "include",
+      headers: { "Content-Type": "application/json" },
+      body:
+        method === "PUT"
+          ? JSON.stringify({ token: raindropToken().trim() })
+          : undefined,
+    });
+    if (!res.ok) {
+      const err = await res.json().catch(() => null);
+      setRaindropError(err?.msg ?? "Could not update Raindrop");
+      return;
+    }
+    setRaindropToken("");
+    await refetch();
+  }
+
   return (
     <div class="settings-account">
       <a href="/">Homepage</a>
@@ -94,8 +262,11 @@ export default function AccountInfo(): JSXElement {
                     </p>
                   </>
                 )}
+                <Show when={spotifyError() != null}>
+                  <p>{spotifyError()}</p>
+                </Show>
               </div>
-              {connectedApps()?.spotify == null && (
+              {connectedApps()?.spotify == null ? (
                 <button
                   class="ui-button"
                   onClick={() =>
@@ -104,6 +275,63 @@ export default function AccountInfo(): JSXElement {
                 >
                   Connect
                 </button>
+              ) : (
+                <button
+                  class="ui-button"
+                  onClick={() => void disconnectSpotify()}
+                >
+                  Disconnect
+                </button>
+              )}
+            </div>
+          )}
+          {AppState.authUser?.siteOwner != null && (
+            <div class="settings-account__connection">
+              <svg
+                height="32"
+                width="32"
+                viewBox="0 0 24 24"
+                aria-hidden="true"
+                fill="var(--brand-strava, #fc4c02)"
+              >
+                <path d="M15.387 17.944l-2.089-4.116h-3.065L15.387 24l5.15-10.172h-3.066m-7.008-5.599l2.836 5.598h4.172L10.463 0l-7 13.828h4.169" />
+              </svg>
+              <div>
+                <h4>Strava</h4>
+                {connectedApps()?.strava == null ? (
+                  <p>Show your recent runs</p>
+                ) : (
+                  <p>
+                    <span>{connectedApps()?.strava?.athlete_name}</span>
+                    <span>•</span>
+                    <span>
+                      Added on{" "}
+                      {new Date(
+                        connectedApps()?.strava?.added_on ?? ""
+                      ).toLocaleDateString()}
+                    </span>
+                  </p>
+                )}
+                <Show when={stravaError() != null}>
+                  <p>{stravaError()}</p>
+                </Show>
+              </div>
+              {connectedApps()?.strava == null ? (
+                <button
+                  class="ui-button"
+                  onClick={() =>
+                    (window.location.href = `${config.API_URL}/link/strava`)
+                  }
+                >
+                  Connect
+                </button>
+              ) : (
+                <button
+                  class="ui-button"
+                  onClick={() => void disconnectStrava()}
+                >
+                  Disconnect
+                </button>
               )}
             </div>
           )}
@@ -132,6 +360,207 @@ export default function AccountInfo(): JSXElement {
               </p>
             </div>
           </div>
+          <div class="settings-account__connection">
+            <svg
+              height="32"
+              width="32"
+              viewBox="0 0 24 24"
+              aria-hidden="true"
+              fill="var(--brand-raindrop, #1988e0)"
+            >
+              <path d="M12 2.5c-.3 0-.6.2-.8.4C9.4 5.3 5 11.2 5 14.7 5 18.7 8.1 22 12 22s7-3.3 7-7.3c0-3.5-4.4-9.4-6.2-11.8-.2-.2-.5-.4-.8-.4Z" />
+            </svg>
+            <div>
+              <h4>Raindrop</h4>
+              {connectedApps()?.raindrop == null ? (
+                <>
+                  <p>Personalize the recommendations to your bookmarks</p>
+                  <input
+                    type="password"
+                    placeholder="Test token of a Raindrop app"
+                    value={raindropToken()}
+                    onInput={(e) => setRaindropToken(e.currentTarget.value)}
+                  />
+                </>
+              ) : (
+                <p>
+                  <span>
+                    {connectedApps()?.raindrop?.collection_ids.length}{" "}
+                    collection(s)
+                  </span>
+                  <span>•</span>
+                  <span>
+                    Added on{" "}
+                    {new Date(
+                      connectedApps()?.raindrop?.added_on ?? ""
+                    ).toLocaleDateString()}
+                  </span>
+                </p>
+              )}
+              <Show when={raindropError() != null}>
+                <p>{raindropError()}</p>
+              </Show>
+            </div>
+            {connectedApps()?.raindrop == null ? (
+              <button
+                class="ui-button"
+                disabled={raindropToken().trim() === ""}
+                onClick={() => void linkRaindrop("PUT")}
+              >
+                Connect
+              </button>
+            ) : (
+              <button
+                class="ui-button"
+                onClick={() => void linkRaindrop("DELETE")}
+              >
+                Disconnect
+              </button>
+            )}
+          </div>
+          <Show when={connectedApps()?.steam != null}>
+            <div class="settings-account__connection">
+              <svg
+                height="32"
+                width="32"
+                viewBox="0 0 24 24"
+                aria-hidden="true"
+                fill="var(--brand-steam, #1b2838)"
+              >
+                <circle cx="12" cy="12" r="10" />
+              </svg>
+              <div>
+                <h4>Steam</h4>
+                <p>
+                  <span>{connectedApps()?.steam?.steam_id}</span>
+                  <span>•</span>
+                  <span>Set in the config, shown on the now page</span>
+                </p>
+              </div>
+            </div>
+          </Show>
+        </div>
+      </Show>
+
+      <Show when={imports.state === "ready"}>
+        <div class="settings-account__connections">
+          <h3>History imports</h3>
+          {(
+            [
+              ["pocket", "Pocket", "The HTML or CSV export"],
+              ["instapaper", "Instapaper", "The CSV export"],
+              ["omnivore", "Omnivore", "A metadata JSON file of the export"],
+            ] as [HistorySource, string, string][]
+          ).map(([source, name, hint]) => {
+            const imported = () =>
+              imports()?.find((i) => i.source === source);
+            return (
+              <div class="settings-account__connection">
+                <svg
+                  height="32"
+                  width="32"
+                  viewBox="0 0 24 24"
+                  aria-hidden="true"
+                  fill="currentcolor"
+                >
+                  <path d="M6 2h8l6 6v12a2 2 0 0 1-2 2H6a2 2 0 0 1-2-2V4a2 2 0 0 1 2-2Zm7 1.5V9h5.5L13 3.5Z" />
+                </svg>
+                <div>
+                  <h4>{name}</h4>
+                  {imported() == null ? (
+                    <p>{hint}</p>
+                  ) : (
+                    <p>
+                      <span>{imported()?.item_count} articles</span>
+                      <span>•</span>
+                      <span>
+                        Imported on{" "}
+                        {new Date(
+                          imported()?.imported_at ?? ""
+                        ).toLocaleDateString()}
+                      </span>
+                    </p>
+                  )}
+                  <input
+                    type="file"
+                    accept=".html,.csv,.json"
+                    onChange={(e) => {
+                      const file = e.currentTarget.files?.[0];
+                      if (file != null) void importHistory(source, file);
+                    }}
+                  />
+                </div>
+                {imported() != null && (
+                  <button
+                    class="ui-button"
+                    onClick={() => void removeImport(source)}
+                  >
+                    Remove
+                  </button>
+                )}
+              </div>
+            );
+          })}
+          <Show when={importError() != null}>
+            <p>{importError()}</p>
+          </Show>
+        </div>
+      </Show>
+
+      <Show when={tokens.state === "ready"}>
+        <div class="settings-account__connections">
+          <h3>Extension tokens</h3>
+          <p>
+            Browser extensions and bookmarklets save pages to your history with
+            a token, sent as <code>Authorization: Bearer</code> to{" "}
+            <code>POST {config.API_URL}/history</code>.
+          </p>
+          {tokens()?.map((token) => (
+            <div class="settings-account__connection">
+              <div>
+                <h4>{token.name}</h4>
+                <p>
+                  <span>
+                    Created on{" "}
+                    {new Date(token.created_at).toLocaleDateString()}
+                  </span>
+                  <Show when={token.last_used_at != null}>
+                    <span>•</span>
+                    <span>
+                      Last used on{" "}
+                      {new Date(token.last_used_at ?? "").toLocaleDateString()}
+                    </span>
+                  </Show>
+                </p>
+              </div>
+              <button
+                class="ui-button"
+                onClick={() => void revokeToken(token.id)}
+              >
+                Revoke
+              </button>
+            </div>
+          ))}
+          <div class="settings-account__connection">
+            <input
+              type="text"
+              placeholder="Name, e.g. Firefox"
+              value={tokenName()}
+              onInput={(e) => setTokenName(e.currentTarget.value)}
+            />
+            <button class="ui-button" onClick={() => void createToken()}>
+              Create
+            </button>
+          </div>
+          <Show when={newToken() != null}>
+            <p>
+              Copy the token now, it won't be shown again:{" "}
+              <code>{newToken()}</code>
+            </p>
+          </Show>
+          <Show when={tokenError() != null}>
+            <p>{tokenError()}</p>
+          </Show>
         </div>
       </Show>
     </div>
@@ -0,0 +1,16 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { AssetVariant } from "./AssetVariant";
+
+/**
+ * An uploaded file, the URLs are signed and expire after a while so they
+ * should be fetched again rather than stored
+ */
+export type Asset = { id: number, filename: string, content_type: string, size_bytes: bigint, 
+/**
+ * Set for images
+ */
+width: number | null, height: number | null, url: string, 
+/**
+ * Resized copies of an image, they appear shortly after the upload
+ */
+variants: Array<AssetVariant>, created_at: string, };
@@ -0,0 +1,7 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export type AssetVariant = { 
+/**
+ * e.g. `w480`, to request it with `?variant=w480`
+ */
+name: string, width: number, height: number, url: string, };
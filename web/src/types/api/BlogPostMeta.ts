@@ -0,0 +1,14 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Reading time and summary of a post, generated when it's registered
+ */
+export type BlogPostMeta = { slug: string, title: string | null, 
+/**
+ * Minutes at an average reading speed, not generated yet if absent
+ */
+reading_time_mins: number | null, 
+/**
+ * A couple of sentences written by the LLM, absent if no LLM is configured
+ */
+summary: string | null, };
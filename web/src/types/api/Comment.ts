@@ -0,0 +1,3 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export type Comment = { id: number, author_name: string, content: string, parent_id: number | null, created_at: string, votes: number, depth: number, };
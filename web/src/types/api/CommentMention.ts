@@ -0,0 +1,10 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * An `@handle` in a comment that resolved to a commenter of the post
+ */
+export type CommentMention = { 
+/**
+ * As written after the `@`, lowercased
+ */
+handle: string, identity_id: number, name: string, };
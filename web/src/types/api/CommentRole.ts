@@ -0,0 +1,6 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Role granted to an identity, shown as a badge next to its comments
+ */
+export type CommentRole = "author" | "moderator" | "verified";
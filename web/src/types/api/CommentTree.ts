@@ -0,0 +1,40 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { CommentMention } from "./CommentMention";
+import type { CommentRole } from "./CommentRole";
+import type { CommentSource } from "./CommentSource";
+import type { GeoInfo } from "./GeoInfo";
+
+export type CommentTree = { id: number, author_name: string, content: string, parent_id: number | null, created_at: string, children: Array<CommentTree> | null, upvote: number, depth: number, 
+/**
+ * Replies left out by `children_limit`, to be fetched with the thread
+ */
+remaining_children: number, is_comment_owner: boolean, 
+/**
+ * Whether the commenter is the site owner or a co-author
+ */
+is_blog_author: boolean, 
+/**
+ * Badges of the commenter
+ */
+roles: Array<CommentRole>, 
+/**
+ * Only present for the blog author (moderator)
+ */
+author_geo?: GeoInfo, 
+/**
+ * Low scored or old and unvoted, the client shows it folded
+ */
+collapsed_by_default: boolean, 
+/**
+ * Set on the replies flattened past `max_depth`, the ID of their ancestor
+ * whose nested thread can be fetched separately
+ */
+continue_thread_id?: number, 
+/**
+ * Commenters mentioned with `@handle` in the content
+ */
+mentions: Array<CommentMention>, 
+/**
+ * Where the comment was written
+ */
+source: CommentSource, };
@@ -0,0 +1,11 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * A dependency of the API and its uptime, in percent of the probes that
+ * succeeded over the period
+ */
+export type ComponentStatus = { name: string, 
+/**
+ * Whether the latest probe succeeded
+ */
+operational: boolean, last_checked_at: string, uptime_24h: number | null, uptime_7d: number | null, uptime_30d: number | null, };
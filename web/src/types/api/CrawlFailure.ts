@@ -0,0 +1,7 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export type CrawlFailure = { 
+/**
+ * The article, or none if a whole source failed
+ */
+url: string | null, reason: string, };
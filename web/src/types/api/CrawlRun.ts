@@ -0,0 +1,26 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { CrawlFailure } from "./CrawlFailure";
+import type { CrawlStatus } from "./CrawlStatus";
+import type { CrawlTiming } from "./CrawlTiming";
+import type { CrawlTrigger } from "./CrawlTrigger";
+
+/**
+ * A run of the crawler over the sources, in progress or finished
+ */
+export type CrawlRun = { id: number, status: CrawlStatus, trigger: CrawlTrigger, 
+/**
+ * Entries fetched from the sources
+ */
+fetched: number, 
+/**
+ * New articles fetched, embedded and stored
+ */
+embedded: number, failed: number, 
+/**
+ * Why the entries failed, only the first ones are kept
+ */
+failures: Array<CrawlFailure>, 
+/**
+ * How long each source and step took, in the order they ran
+ */
+timings: Array<CrawlTiming>, started_at: string, finished_at: string | null, };
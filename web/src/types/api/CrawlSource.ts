@@ -0,0 +1,18 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * How a source of the recommendations is crawled
+ */
+export type CrawlSource = { key: string, name: string, enabled: boolean, 
+/**
+ * Minutes between two crawls, the crawl interval of the config if unset
+ */
+crawl_interval_mins: number | null, 
+/**
+ * Entries taken from the source per crawl, the crawler's default if unset
+ */
+max_items: number | null, last_crawled_at: string | null, 
+/**
+ * The RSS or Atom feed the source is crawled from, if it's one
+ */
+feed_url: string | null, };
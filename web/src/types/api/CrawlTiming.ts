@@ -0,0 +1,8 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export type CrawlTiming = { 
+/**
+ * The key of a source for fetching its entries, `articles` for fetching
+ * and embedding the new articles and `comments` for their discussions
+ */
+step: string, millis: number, };
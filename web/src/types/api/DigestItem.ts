@@ -0,0 +1,9 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { FeedItemSummary } from "./FeedItemSummary";
+import type { SourceInfo } from "./SourceInfo";
+
+export type DigestItem = { id: number, title: string, url: string, score: number, submitted_at: string | null, sources: Array<SourceInfo>, paywalled: boolean, 
+/**
+ * The kept summary, none if the article hasn't been summarized yet
+ */
+summary: FeedItemSummary | null, };
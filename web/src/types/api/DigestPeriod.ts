@@ -0,0 +1,6 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * The period a digest of the feed covers, up to now
+ */
+export type DigestPeriod = "day" | "week" | "month";
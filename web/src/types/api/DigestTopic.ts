@@ -0,0 +1,12 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { DigestItem } from "./DigestItem";
+
+/**
+ * Articles of a digest that share their terms
+ */
+export type DigestTopic = { 
+/**
+ * The terms most of the articles share, none for the articles that
+ * share them with no other
+ */
+label: string | null, items: Array<DigestItem>, };
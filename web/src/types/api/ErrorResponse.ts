@@ -0,0 +1,7 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { JsonValue } from "./serde_json/JsonValue";
+
+/**
+ * The body of the error responses
+ */
+export type ErrorResponse = { error?: string, msg: string, reason?: JsonValue, context?: { [key in string]?: JsonValue }, debug_info?: { [key in string]?: JsonValue }, };
@@ -0,0 +1,16 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { DigestPeriod } from "./DigestPeriod";
+import type { DigestTopic } from "./DigestTopic";
+
+/**
+ * The top articles of a period that the reader hasn't read, grouped by topic
+ */
+export type FeedDigest = { period: DigestPeriod, 
+/**
+ * Start of the period, the articles were submitted since
+ */
+since: string, 
+/**
+ * The topic of the best article first, the unlabelled one last
+ */
+topics: Array<DigestTopic>, };
@@ -0,0 +1,32 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { HistoryMatch } from "./HistoryMatch";
+
+/**
+ * How each signal contributed to the score of a feed item. The ranks are
+ * among the candidates of the ranking, from 1.
+ */
+export type FeedExplanation = { 
+/**
+ * None if the item has no chunks or there is no history
+ */
+similarity_rank: number | null, 
+/**
+ * `1 / (k + rank)` of the similarity
+ */
+similarity_contribution: number, external_rank: number | null, 
+/**
+ * `1 / (k + rank)` of the external score
+ */
+external_contribution: number, freshness_rank: number, 
+/**
+ * The decay the summed contributions are multiplied with
+ */
+freshness_score: number | null, 
+/**
+ * The multiplier of the terms the item shares with the history
+ */
+lexical_boost: number, 
+/**
+ * The history articles closest to the item, closest first
+ */
+matches: Array<HistoryMatch>, };
@@ -0,0 +1,19 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { FeedExplanation } from "./FeedExplanation";
+import type { SourceInfo } from "./SourceInfo";
+
+export type FeedItem = { id: number, title: string, url: string, score: number, similarity_score: number | null, submitted_at: string | null, sources: Array<SourceInfo>, 
+/**
+ * ISO 639-3 code of the language of the article, none if it couldn't be
+ * detected
+ */
+lang: string | null, 
+/**
+ * Only a teaser of the article could be read, it was ranked by its title
+ * and the text of the submission
+ */
+paywalled: boolean, 
+/**
+ * Only present with `explain=true`
+ */
+explanation?: FeedExplanation, };
@@ -0,0 +1,17 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { LinkStatus } from "./LinkStatus";
+import type { SourceInfo } from "./SourceInfo";
+
+/**
+ * A feed item with the state of its link
+ */
+export type FeedItemDetail = { id: number, title: string, url: string, submitted_at: string | null, sources: Array<SourceInfo>, link_status: LinkStatus, link_checked_at: string | null, 
+/**
+ * A copy on the Wayback Machine, looked up once the link is dead
+ */
+archive_url: string | null, 
+/**
+ * When the content and embeddings were last refreshed because the page
+ * changed
+ */
+content_refreshed_at: string | null, };
@@ -0,0 +1,10 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * A short summary of a feed article, generated once and kept
+ */
+export type FeedItemSummary = { id: number, summary: string, 
+/**
+ * The key points of the article, a few short sentences
+ */
+takeaways: Array<string>, generated_at: string, };
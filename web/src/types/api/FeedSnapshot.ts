@@ -0,0 +1,7 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { FeedItem } from "./FeedItem";
+
+/**
+ * The feed snapshot, streamed by the server rather than serialized at once
+ */
+export type FeedSnapshot = { items: Array<FeedItem>, };
@@ -0,0 +1,11 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Country and ASN information of an IP address resolved from the MaxMind
+ * (GeoLite2) databases.
+ */
+export type GeoInfo = { 
+/**
+ * ISO 3166-1 alpha-2 country code, e.g. `VN`
+ */
+country?: string, asn?: number, asn_org?: string, };
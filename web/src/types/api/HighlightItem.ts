@@ -0,0 +1,3 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export type HighlightItem = { id: string, title: string, text: string, note: string | null, color: string, created_at: string, link: string, tags: Array<string>, };
@@ -0,0 +1,15 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { HistorySource } from "./HistorySource";
+
+/**
+ * An export file the reader imported into their history
+ */
+export type HistoryImport = { source: HistorySource, 
+/**
+ * How much the imported articles count, from 0 to 1
+ */
+weight: number, 
+/**
+ * Articles found in the last imported file
+ */
+item_count: number, imported_at: string, };
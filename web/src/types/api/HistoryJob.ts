@@ -0,0 +1,11 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { HistoryJobStatus } from "./HistoryJobStatus";
+
+/**
+ * A page saved to the history, it's fetched and embedded in the background
+ */
+export type HistoryJob = { id: number, url: string, status: HistoryJobStatus, 
+/**
+ * Why the page couldn't be saved
+ */
+error: string | null, created_at: string, finished_at: string | null, };
@@ -0,0 +1,18 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * A history article close to a feed item, through their closest chunks
+ */
+export type HistoryMatch = { history_article_id: number, title: string, url: string, 
+/**
+ * The chunk of the feed item
+ */
+chunk_id: number, 
+/**
+ * The chunk of the history article
+ */
+history_chunk_id: number, 
+/**
+ * Weighted by the history entry, as in the similarity rank
+ */
+similarity: number, };
@@ -0,0 +1,8 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Where the entries of a reader's history come from: Raindrop is linked and
+ * synced, the extension saves single pages and the others are imported from
+ * their export files
+ */
+export type HistorySource = "raindrop" | "pocket" | "instapaper" | "omnivore" | "extension";
@@ -0,0 +1,7 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * A token the extensions save pages to the reader's history with, the token
+ * itself is only returned once when it's created
+ */
+export type HistoryToken = { id: number, name: string, token?: string, last_used_at: string | null, created_at: string, };
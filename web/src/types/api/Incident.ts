@@ -0,0 +1,7 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export type Incident = { id: number, title: string, description: string | null, 
+/**
+ * The affected component, if it's a single one
+ */
+component: string | null, started_at: string, resolved_at: string | null, };
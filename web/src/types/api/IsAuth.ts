@@ -0,0 +1,4 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { Traits } from "./Traits";
+
+export type IsAuth = { is_auth: boolean, id?: number, traits?: Traits, site_owner?: boolean, };
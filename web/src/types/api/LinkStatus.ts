@@ -0,0 +1,6 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Whether the link of an article still works, it's checked periodically
+ */
+export type LinkStatus = "unchecked" | "alive" | "failing" | "dead";
@@ -0,0 +1,19 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { NoteKind } from "./NoteKind";
+
+/**
+ * A short post published with a Micropub client
+ */
+export type Note = { id: number, kind: NoteKind, 
+/**
+ * Optional for bookmarks and likes
+ */
+content: string | null, 
+/**
+ * A title, notes usually have none
+ */
+name: string | null, 
+/**
+ * The bookmarked or liked page
+ */
+url: string | null, categories: Array<string>, published_at: string, };
@@ -0,0 +1,21 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { HighlightItem } from "./HighlightItem";
+import type { Note } from "./Note";
+import type { NowCommit } from "./NowCommit";
+import type { NowGaming } from "./NowGaming";
+import type { NowPlaying } from "./NowPlaying";
+import type { StravaActivity } from "./StravaActivity";
+
+/**
+ * What the owner is up to lately, for the "now" page. A source that isn't
+ * set up or has nothing yet is none.
+ */
+export type Now = { playing: NowPlaying | null, commit: NowCommit | null, gaming: NowGaming | null, 
+/**
+ * The latest activity recorded on Strava
+ */
+activity: StravaActivity | null, highlight: HighlightItem | null, note: Note | null, 
+/**
+ * The sources that failed or took too long, their data is missing
+ */
+unavailable: Array<string>, };
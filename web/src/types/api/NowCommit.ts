@@ -0,0 +1,10 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * The latest commit pushed to a public repository on GitHub
+ */
+export type NowCommit = { 
+/**
+ * `owner/name`
+ */
+repo: string, sha: string, message: string | null, url: string, pushed_at: string, };
@@ -0,0 +1,15 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { SteamGame } from "./SteamGame";
+
+/**
+ * The game being played on Steam and the ones played lately
+ */
+export type NowGaming = { 
+/**
+ * None if not in a game
+ */
+playing: SteamGame | null, 
+/**
+ * The games played in the last two weeks, the most played first
+ */
+recent: Array<SteamGame>, };
@@ -0,0 +1,14 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * The track or the episode playing on Spotify
+ */
+export type NowPlaying = { 
+/**
+ * Paused if false, the last played item is shown
+ */
+is_playing: boolean, title: string, 
+/**
+ * The artists of the track, or the show of the episode
+ */
+artists: Array<string>, url: string | null, };
@@ -0,0 +1,10 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * An article crawled by the recommendation system
+ */
+export type RelatedArticle = { id: number, title: string, url: string, 
+/**
+ * Between 0 and 1, of the most similar chunks of the post and the article
+ */
+similarity: number, };
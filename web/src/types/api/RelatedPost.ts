@@ -0,0 +1,10 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Another post of the blog
+ */
+export type RelatedPost = { slug: string, title: string | null, 
+/**
+ * Between 0 and 1, of the most similar chunks of the two posts
+ */
+similarity: number, };
@@ -0,0 +1,8 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { RelatedArticle } from "./RelatedArticle";
+import type { RelatedPost } from "./RelatedPost";
+
+/**
+ * Further reading of a post, most similar first
+ */
+export type RelatedReading = { posts: Array<RelatedPost>, articles: Array<RelatedArticle>, };
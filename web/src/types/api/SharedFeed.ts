@@ -0,0 +1,7 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { SharedFeedItem } from "./SharedFeedItem";
+
+/**
+ * The top recommendations of the owner, shared with a public link
+ */
+export type SharedFeed = { generated_at: string, items: Array<SharedFeedItem>, };
@@ -0,0 +1,10 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * The fields of a feed item that are fine to share, without the scores
+ */
+export type SharedFeedItem = { title: string, url: string, submitted_at: string | null, 
+/**
+ * Keys of the sites the article was submitted to
+ */
+sources: Array<string>, };
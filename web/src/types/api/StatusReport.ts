@@ -0,0 +1,9 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { ComponentStatus } from "./ComponentStatus";
+import type { Incident } from "./Incident";
+
+export type StatusReport = { components: Array<ComponentStatus>, 
+/**
+ * The open incidents and the ones resolved recently, newest first
+ */
+incidents: Array<Incident>, };
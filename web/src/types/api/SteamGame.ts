@@ -0,0 +1,11 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export type SteamGame = { app_id: number, name: string, 
+/**
+ * Played in the last two weeks
+ */
+recent_minutes: number, total_minutes: number, 
+/**
+ * The page of the game on the Steam store
+ */
+url: string, };
@@ -0,0 +1,19 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * An activity recorded on Strava
+ */
+export type StravaActivity = { id: number, name: string, 
+/**
+ * As Strava calls it, `Run`, `TrailRun`, `Ride`...
+ */
+sport_type: string, distance_meters: number, moving_seconds: number, 
+/**
+ * None for the activities without a distance
+ */
+pace_seconds_per_km: number | null, started_at: string, 
+/**
+ * The route as a simplified encoded polyline, none for the activities
+ * without a map
+ */
+polyline: string | null, url: string, };